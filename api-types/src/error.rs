@@ -0,0 +1,254 @@
+use crate::SettingsError;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// A machine-readable reason for a request failing. Codes stay the same even when the
+/// messages shown to users are reworded, so clients can branch on them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // Accounts
+    UsernameTooShort,
+    UsernameTaken,
+    InvalidPassword,
+    PasswordMismatch,
+    PasswordTooShort,
+    PasswordNoAlpha,
+    PasswordNoNumeric,
+    InvalidTimezone,
+    AccountLocked,
+    InvalidToken,
+    CsrfMismatch,
+    NotAdmin,
+    Suspended,
+    // Users
+    UserNotFound,
+    SearchQueryMissing,
+    AlreadyFriends,
+    FriendSelf,
+    FriendNotFound,
+    FriendRequestAlreadySent,
+    FriendRequestNotFound,
+    TooManyUsernames,
+    DuplicateUsername,
+    BlockSelf,
+    AlreadyBlocked,
+    Blocked,
+    BlockNotFound,
+    ReportSelf,
+    ReasonMissing,
+    ReasonTooLong,
+    QuoteTooLong,
+    ReportNotFound,
+    BanNotFound,
+    InvalidBanDuration,
+    AvatarMissing,
+    AvatarTooLarge,
+    AvatarUnsupported,
+    AvatarNotFound,
+    NotificationNotFound,
+    // Games
+    GameNotFound,
+    InvalidGameId,
+    GameSelf,
+    DuplicateGuest,
+    InviteExpired,
+    InvalidJoinCode,
+    ChallengeNotFound,
+    ChallengeAlreadyOpen,
+    InvalidSettings,
+    ClaimTooEarly,
+    GameOver,
+    Banned,
+    CasualOnly,
+    FogReplay,
+    AnalysisNotReady,
+    // Moves
+    SquareOccupied,
+    NotYourTurn,
+    NotYourPiece,
+    NotAdjacent,
+    OutOfBounds,
+    NoFlips,
+    // Tournaments
+    TournamentNotFound,
+    InvalidTournamentName,
+    InvalidTournamentSize,
+    InvalidTournamentRounds,
+    TournamentStarted,
+    TournamentFull,
+    TournamentTooSmall,
+    AlreadyEntered,
+    NotTournamentHost,
+    // Seasons
+    SeasonNotFound,
+    // Puzzles
+    PuzzleNotFound,
+    PuzzleAlreadyAnswered,
+    InvalidPuzzleSolutions,
+    // Bots
+    BotNotFound,
+    TooManyBots,
+    BotOwner,
+    // Arenas
+    ArenaNotFound,
+    InvalidArenaGames,
+    ArenaSameContender,
+    NotABot,
+    // Webhooks
+    WebhookNotFound,
+    TooManyWebhooks,
+    InvalidWebhookUrl,
+    WebhookEventsMissing,
+    // Lists
+    InvalidPageSize,
+    InvalidCursor,
+    // Retries
+    InvalidIdempotencyKey,
+    IdempotencyInProgress,
+    IdempotencyKeyReused,
+    // Sign-in with an identity provider
+    OauthUnknownProvider,
+    OauthUnavailable,
+    OauthInvalidState,
+    OauthFailed,
+    OauthIdentityTaken,
+    // Gateway
+    UnsupportedProtocolVersion,
+    IdentifyTimeout,
+    // Anything without a more specific code, by status
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    RateLimited,
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    /// The generic code for an error sent with the specified status, for errors that don't
+    /// have a more specific one.
+    #[must_use]
+    pub fn for_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Self::UnsupportedMediaType,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited,
+            StatusCode::SERVICE_UNAVAILABLE => Self::Unavailable,
+            status if status.is_client_error() => Self::BadRequest,
+            _ => Self::Internal,
+        }
+    }
+}
+
+/// The body of every error response, over HTTP and the gateway alike.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    /// A description of the error, which may be shown to users.
+    pub message: String,
+    /// Anything else about the error a client might need (e.g. which square a move was
+    /// rejected on), or `null`.
+    #[serde(default)]
+    pub details: Value,
+    /// The HTTP status the error was sent with.
+    pub status: u16,
+}
+
+impl ApiError {
+    #[must_use]
+    pub fn new(code: ErrorCode, message: impl Into<String>, status: StatusCode) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: Value::Null,
+            status: status.as_u16(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+
+    #[must_use]
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl From<SettingsError> for ApiError {
+    fn from(e: SettingsError) -> Self {
+        let details = match e {
+            SettingsError::BoardSize(width) => {
+                json!({ "setting": "board_size", "expected": width })
+            }
+            SettingsError::Unsupported(feature) => json!({ "unsupported": feature }),
+            SettingsError::Handicap(max) => json!({ "setting": "handicap", "max": max }),
+            SettingsError::DaysPerMove(max) => {
+                json!({ "setting": "correspondence", "max": max })
+            }
+        };
+        Self::new(
+            ErrorCode::InvalidSettings,
+            e.to_string(),
+            StatusCode::BAD_REQUEST,
+        )
+        .with_details(details)
+    }
+}
+
+#[cfg(feature = "axum")]
+impl axum::response::IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        (self.status(), axum::Json(self)).into_response()
+    }
+}
+
+#[cfg(feature = "axum")]
+impl From<ApiError> for axum::response::Response {
+    fn from(e: ApiError) -> Self {
+        axum::response::IntoResponse::into_response(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiError, ErrorCode};
+    use crate::SettingsError;
+    use http::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            ErrorCode::for_status(StatusCode::UNPROCESSABLE_ENTITY),
+            ErrorCode::BadRequest
+        );
+        assert_eq!(
+            ErrorCode::for_status(StatusCode::BAD_GATEWAY),
+            ErrorCode::Internal
+        );
+        let e = ApiError::from(SettingsError::BoardSize(8));
+        assert_eq!(e.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::to_value(e).unwrap(),
+            json!({
+                "code": "invalid_settings",
+                "message": "boards must be 8 squares wide",
+                "details": { "setting": "board_size", "expected": 8 },
+                "status": 400,
+            })
+        );
+    }
+}
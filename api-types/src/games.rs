@@ -0,0 +1,194 @@
+use crate::{timestamp, GameSettings, Piece, UserSummary};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameRequest {
+    pub guest: Option<String>,
+    /// Challenge several users at once. The first guest to accept plays the game, and
+    /// every other invitation is cancelled.
+    #[serde(default)]
+    pub guests: Vec<String>,
+    /// How the game should be played. Anything left out takes its default.
+    #[serde(default)]
+    pub settings: GameSettings,
+    #[serde(default)]
+    pub visibility: Visibility,
+}
+
+/// Who can join a game being created.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Only the guests it was sent to, who are named up front.
+    #[default]
+    Invite,
+    /// Anyone with the short join code the game is given, before the code expires.
+    Code,
+}
+
+/// A challenge to open in the lobby, for anyone to take up.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChallengeRequest {
+    /// How the game should be played. Anything left out takes its default.
+    #[serde(default)]
+    pub settings: GameSettings,
+}
+
+/// A challenge waiting in the lobby for somebody to take it up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenChallenge {
+    pub id: Uuid,
+    pub host: UserSummary,
+    pub settings: GameSettings,
+    #[serde(serialize_with = "timestamp::serialize")]
+    pub created_at: DateTime<FixedOffset>,
+}
+
+/// A game being played that anyone can watch, as the live game browser lists it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveGame {
+    pub id: Uuid,
+    pub black: UserSummary,
+    pub white: UserSummary,
+    pub settings: GameSettings,
+    /// How many moves have been played so far.
+    pub moves: usize,
+    /// The tournament the game was paired in, if it was.
+    pub tournament: Option<Uuid>,
+    /// The arena the game is being played in, if it is.
+    pub arena: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BotGameRequest {
+    pub difficulty: Difficulty,
+}
+
+/// How well a hosted opponent plays.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    /// Takes whatever looks best right now.
+    Easy,
+    /// Looks a few moves ahead.
+    Medium,
+    /// Looks as far ahead as it can in a couple of seconds.
+    Hard,
+}
+
+impl Difficulty {
+    /// The name games against this opponent are stored with.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Easy => "easy",
+            Self::Medium => "medium",
+            Self::Hard => "hard",
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A game as it's listed among a user's games.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub id: Uuid,
+    /// The username of the player who created the game.
+    pub host: String,
+    /// The colour the host plays.
+    pub host_color: Piece,
+    /// The username of the user's opponent.
+    pub opponent: String,
+    pub ended: bool,
+    pub settings: GameSettings,
+}
+
+/// A game as its players see it outside of play.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameDetails {
+    pub id: Uuid,
+    /// Whether the guest has yet to accept the game.
+    pub pending: bool,
+    /// The ID of the player who created the game.
+    pub host: String,
+    /// The ID of the player the game was created for.
+    pub guest: String,
+    /// The colour the host plays, settled when the guest accepts.
+    pub host_color: Piece,
+    pub ended: bool,
+    /// How the game ended, or `None` if it hasn't.
+    pub result: Option<Summary>,
+    pub settings: GameSettings,
+}
+
+/// How a game came to an end.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Termination {
+    /// Neither player could move, so the game was decided on the board.
+    Normal,
+    /// One of the players resigned.
+    Resignation,
+    /// One of the players disconnected and didn't come back in time.
+    Abandonment,
+    /// One of the players stalled on their turn, and their opponent claimed the win or
+    /// declared a draw.
+    Stalling,
+    /// One of the players missed the deadline on a move of a correspondence game.
+    Timeout,
+}
+
+/// Which side, if any, won the game.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Black,
+    White,
+    Draw,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Score {
+    pub black: usize,
+    pub white: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Links {
+    pub game: String,
+    pub export: String,
+}
+
+/// A summary of a finished game, broadcast to its room when it ends and stored alongside the
+/// game so that the REST API can serve the same document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    pub result: Outcome,
+    /// The username of the winner, or `None` for a draw.
+    pub winner: Option<String>,
+    pub termination: Termination,
+    pub score: Score,
+    /// The number of pieces the winner finished with.
+    pub points: usize,
+    /// The number of pieces on the board at the end of the game.
+    pub total: usize,
+    /// The change in each player's rating as `(black, white)`, or `None` if the game was
+    /// unrated.
+    pub rating_deltas: Option<(i32, i32)>,
+    pub links: Links,
+}
@@ -0,0 +1,444 @@
+//! The messages clients and the server send each other over the gateway. Messages carrying a
+//! game's position are generic over how it's held, so that the server and clients that know
+//! the rules can use the core crate's `Game` in place of a bare [`Position`].
+
+use crate::{ApiError, Notification, OpenChallenge, Piece, Position, Status, Summary};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use uuid::Uuid;
+
+/// The newest version of the websocket protocol that the server speaks. Clients state the
+/// version they speak when identifying, and the server replies with the version both sides
+/// will use for the rest of the connection.
+pub const PROTOCOL_VERSION: u16 = 3;
+/// The oldest version of the websocket protocol that the server still accepts. Clients that
+/// don't state a version when identifying are assumed to speak this one.
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+/// The first version of the websocket protocol whose server messages are tagged with their
+/// `type`. Messages are sent untagged before it.
+pub const TAGGED_MESSAGES_VERSION: u16 = 2;
+/// The first version of the websocket protocol whose boards are written compactly, as
+/// [`board`](crate::board) describes. Boards are sent as arrays of squares before it.
+pub const COMPACT_BOARDS_VERSION: u16 = 3;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Packet {
+    pub op: Opcode,
+    pub d: ClientMessage,
+    /// The sender's session token, or `Bot {token}` for bots.
+    pub t: String,
+}
+
+/// A message sent from a client to the server, tagged with its `type`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    Identify {
+        #[serde(default)]
+        version: Option<u16>,
+        /// The resume token of a previous connection whose session should be picked up.
+        #[serde(default)]
+        resume: Option<String>,
+        /// How often the connection wants the whole board after a move.
+        #[serde(default)]
+        snapshots: Snapshots,
+    },
+    Place {
+        id: String,
+        x: usize,
+        y: usize,
+        piece: Piece,
+        /// Identifies the move, so that sending it again (e.g. after the connection dropped
+        /// before it was acknowledged) doesn't play it twice.
+        #[serde(default)]
+        key: Option<String>,
+    },
+    Create {
+        guest: String,
+    },
+    /// Join a game, first catching up on the events after the specified sequence number
+    /// (that of the last event seen before reconnecting), if any.
+    Join {
+        id: String,
+        #[serde(default)]
+        since: Option<u64>,
+    },
+    Leave {
+        id: String,
+    },
+    Resign {
+        id: String,
+    },
+    /// End a game whose opponent is stalling on their turn, claiming the win or, if `draw`
+    /// is set, declaring a draw.
+    Claim {
+        id: String,
+        #[serde(default)]
+        draw: bool,
+    },
+    /// Start receiving the events of the specified channel. Subscribing to a game's channel
+    /// joins it as `Join` does, and a connection can be subscribed to any number of games at
+    /// once.
+    Subscribe {
+        channel: Channel,
+        /// For game channels, the sequence number after which to catch up on events, as for
+        /// `Join`.
+        #[serde(default)]
+        since: Option<u64>,
+    },
+    /// Stop receiving the events of the specified channel. Unsubscribing from a game's channel
+    /// only stops its events: unlike `Leave`, it doesn't abort the game.
+    Unsubscribe {
+        channel: Channel,
+    },
+}
+
+impl ClientMessage {
+    /// The ID of the game the message is about, if it's about one.
+    #[must_use]
+    pub fn game(&self) -> Option<&str> {
+        match self {
+            Self::Place { id, .. }
+            | Self::Join { id, .. }
+            | Self::Leave { id }
+            | Self::Resign { id }
+            | Self::Claim { id, .. } => Some(id),
+            Self::Identify { .. }
+            | Self::Create { .. }
+            | Self::Subscribe { .. }
+            | Self::Unsubscribe { .. } => None,
+        }
+    }
+}
+
+impl Packet {
+    /// Whether the packet's message is one its opcode carries. Games are only ever ended by
+    /// the server, so no opcode carries a message claiming how one ended.
+    #[must_use]
+    pub fn is_well_formed(&self) -> bool {
+        matches!(
+            (&self.op, &self.d),
+            (Opcode::Place | Opcode::Preview, ClientMessage::Place { .. })
+                | (Opcode::Join, ClientMessage::Join { .. })
+                | (Opcode::Leave, ClientMessage::Leave { .. })
+                | (Opcode::Identify, ClientMessage::Identify { .. })
+                | (Opcode::Resign, ClientMessage::Resign { .. })
+                | (Opcode::Claim, ClientMessage::Claim { .. })
+                | (Opcode::Subscribe, ClientMessage::Subscribe { .. })
+                | (Opcode::Unsubscribe, ClientMessage::Unsubscribe { .. })
+                | (Opcode::Reserved, _)
+        )
+    }
+}
+
+/// Events a connection can subscribe to, other than those addressed to its user. Unit channels
+/// are written as their names (e.g. `"lobby"`), and game channels as `{"game": id}`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Challenges being opened in and leaving the lobby.
+    Lobby,
+    /// Everything happening in the specified game, as `Join` sends it.
+    Game(Uuid),
+}
+
+/// How often a connection wants the whole board sent after a move, rather than just the
+/// squares that changed. Bots tend to want every board, while low-power devices would rather
+/// apply small changes.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Snapshots {
+    /// Send the whole board after every move.
+    #[default]
+    Every,
+    /// Send the whole board after every this many moves, and only the changes in between.
+    Interval(u32),
+    /// Only ever send the changes, apart from the board sent on joining a game.
+    Deltas,
+}
+
+impl Snapshots {
+    /// Whether the whole board is due, given how many moves have been made since it was last
+    /// sent.
+    #[must_use]
+    pub fn due(self, moves: u32) -> bool {
+        match self {
+            Self::Every => true,
+            Self::Interval(interval) => moves >= interval,
+            Self::Deltas => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum Opcode {
+    // Create = 1 << 0
+    Place = 1 << 1,
+    Join,
+    Leave,
+    Reserved,
+    Identify,
+    Preview,
+    Resign,
+    Claim,
+    Subscribe,
+    Unsubscribe,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event<G = Position> {
+    pub op: EventKind,
+    pub d: ServerMessage<G>,
+    /// The ID of the game the event comes from, for events sent to everyone in a game, so
+    /// that connections in several games at once can tell them apart.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub g: Option<String>,
+    /// The event's sequence number among the events of its game, counting from 1, for events
+    /// sent to everyone in a game. A connection that drops can rejoin asking for the events
+    /// after the last one it saw.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s: Option<u64>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum EventKind {
+    Ack = 1 << 0,
+    Ready,
+    GameAbort,
+    GameUpdate = 1 << 2,
+    GameUpdatePreview,
+    Error,
+    GameEnd,
+    GameInviteCancel,
+    Presence,
+    FriendPresence,
+    FriendRequestCancel,
+    FriendRequestDecline,
+    GameDelta,
+    ServerRestarting,
+    FriendRequestReceive,
+    FriendRequestAccept,
+    FriendRemove,
+    Notification,
+    LobbyChallengeCreate,
+    LobbyChallengeRemove,
+}
+
+/// A message sent from the server to a client, tagged with its `type`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ServerMessage<G = Position> {
+    Ack,
+    Ready {
+        version: u16,
+        /// A token the client can present when identifying on a new connection to resume the
+        /// session of this one if it drops.
+        resume: String,
+        /// Whether the session of a previous connection was resumed.
+        resumed: bool,
+    },
+    GameCreate {
+        id: String,
+    },
+    GameUpdate {
+        game: G,
+    },
+    GameUpdatePreview {
+        changed: Vec<(usize, usize)>,
+    },
+    /// The squares a move changed, with what each holds now, sent in place of the whole board
+    /// to connections that asked for fewer boards.
+    GameDelta {
+        turn: Piece,
+        changed: Vec<(usize, usize, Option<Piece>)>,
+    },
+    GameAbort,
+    GameEnd(Box<Summary>),
+    GameInviteCancel {
+        game: String,
+        challenge: String,
+    },
+    Presence {
+        user: String,
+        online: bool,
+    },
+    FriendPresence {
+        user: String,
+        status: Status,
+    },
+    /// The user (identified by username) withdrew the friend request they sent.
+    FriendRequestCancel {
+        user: String,
+    },
+    /// The user (identified by username) declined the friend request they were sent.
+    FriendRequestDecline {
+        user: String,
+    },
+    /// The server is shutting down and is about to close the connection. Clients should
+    /// reconnect (resuming their session) once it's back.
+    ServerRestarting,
+    /// The user (identified by username) sent a friend request.
+    FriendRequestReceive {
+        user: String,
+    },
+    /// The user (identified by username) accepted the friend request they were sent.
+    FriendRequestAccept {
+        user: String,
+    },
+    /// The user (identified by username) removed the recipient from their friends.
+    FriendRemove {
+        user: String,
+    },
+    /// A new notification, along with how many the recipient now has unread.
+    Notification {
+        notification: Notification,
+        unread: u64,
+    },
+    /// A challenge was opened in the lobby.
+    LobbyChallengeCreate {
+        challenge: OpenChallenge,
+    },
+    /// A challenge left the lobby, having been taken up or withdrawn.
+    LobbyChallengeRemove {
+        id: String,
+    },
+    Error(ApiError),
+}
+
+impl<G> From<ApiError> for Event<G> {
+    fn from(e: ApiError) -> Self {
+        Self {
+            op: EventKind::Error,
+            d: ServerMessage::Error(e),
+            g: None,
+            s: None,
+        }
+    }
+}
+
+impl<G> Event<G> {
+    #[must_use]
+    pub fn new(op: EventKind, d: ServerMessage<G>) -> Self {
+        Self {
+            op,
+            d,
+            g: None,
+            s: None,
+        }
+    }
+
+    /// The same event, marked as coming from the specified game.
+    #[must_use]
+    pub fn in_game(self, game: &impl ToString) -> Self {
+        Self {
+            g: Some(game.to_string()),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn data(&self) -> &ServerMessage<G> {
+        &self.d
+    }
+
+    #[must_use]
+    pub fn into_data(self) -> ServerMessage<G> {
+        self.d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Channel, ClientMessage, Event, EventKind, Opcode, Packet, ServerMessage, Snapshots,
+    };
+    use crate::{GameSettings, Piece, Position};
+    use serde_json::json;
+
+    #[test]
+    fn packets() {
+        let packet: Packet = serde_json::from_value(json!({
+            "op": 2,
+            "d": { "type": "Place", "id": "game", "x": 5, "y": 4, "piece": "Black" },
+            "t": "token",
+        }))
+        .unwrap();
+        assert_eq!(packet.op, Opcode::Place);
+        assert_eq!(packet.d.game(), Some("game"));
+        assert!(packet.is_well_formed());
+        // Messages only go with their own opcodes.
+        let packet: Packet = serde_json::from_value(json!({
+            "op": 9,
+            "d": { "type": "Resign", "id": "game" },
+            "t": "token",
+        }))
+        .unwrap();
+        assert!(!packet.is_well_formed());
+        // Nor can clients say how a game ended.
+        assert!(serde_json::from_value::<Packet>(json!({
+            "op": 2,
+            "d": { "type": "End", "id": "game" },
+            "t": "token",
+        }))
+        .is_err());
+        let packet: Packet = serde_json::from_value(json!({
+            "op": 6,
+            "d": { "type": "Identify", "snapshots": { "interval": 4 } },
+            "t": "token",
+        }))
+        .unwrap();
+        let ClientMessage::Identify { snapshots, .. } = packet.d else {
+            panic!("expected an identify packet");
+        };
+        assert_eq!(snapshots, Snapshots::Interval(4));
+        assert!(!snapshots.due(3));
+        assert!(snapshots.due(4));
+        let id = "0192a5f0-0000-7000-8000-000000000000";
+        let packet: Packet = serde_json::from_value(json!({
+            "op": 10,
+            "d": { "type": "Subscribe", "channel": { "game": id }, "since": 12 },
+            "t": "token",
+        }))
+        .unwrap();
+        let ClientMessage::Subscribe { channel, since } = packet.d else {
+            panic!("expected a subscribe packet");
+        };
+        assert_eq!(channel, Channel::Game(id.parse().unwrap()));
+        assert_eq!(since, Some(12));
+    }
+
+    #[test]
+    fn events() {
+        let position = Position {
+            board: vec![None; 64],
+            turn: Piece::White,
+            history: vec![(5, 4)],
+            settings: GameSettings::default(),
+        };
+        let event = Event::new(
+            EventKind::GameUpdate,
+            ServerMessage::GameUpdate {
+                game: position.clone(),
+            },
+        );
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["op"], 4);
+        assert_eq!(value["d"]["type"], "GameUpdate");
+        assert!(value.get("g").is_none());
+        assert!(value.get("s").is_none());
+        let value = serde_json::to_value(Event {
+            s: Some(3),
+            ..event.clone().in_game(&"game")
+        })
+        .unwrap();
+        assert_eq!(value["g"], "game");
+        assert_eq!(value["s"], 3);
+        let event: Event = serde_json::from_value(value).unwrap();
+        assert!(matches!(
+            event.into_data(),
+            ServerMessage::GameUpdate { game } if game == position
+        ));
+    }
+}
@@ -0,0 +1,78 @@
+//! The shapes of the server's requests, responses and gateway messages. The server, the client
+//! library and any other frontend all build and read them with these types, so they can't
+//! drift apart. Nothing here knows the rules of the game; the pieces, settings and positions
+//! come from `othello-rules-types`, which the core crate builds on too.
+//!
+//! With the `axum` feature, responses and errors can be returned straight from axum handlers.
+
+mod error;
+mod games;
+pub mod gateway;
+pub mod timestamp;
+mod users;
+
+pub use error::{ApiError, ErrorCode};
+pub use games::{
+    BotGameRequest, ChallengeRequest, Difficulty, GameDetails, GameRequest, GameSummary, LiveGame,
+    Links, OpenChallenge, Outcome, Score, Summary, Termination, Visibility,
+};
+pub use othello_rules_types::{
+    board, ColorPolicy, Correspondence, GameSettings, Piece, Position, SettingsError,
+    TimeControl, Variant, MAX_DAYS_PER_MOVE, MAX_HANDICAP,
+};
+pub use users::{
+    Credentials, Notification, Registration, Status, UpdateMeRequest, UpdatePasswordRequest,
+    UserSummary,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// The body of every successful response.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response<S: Serialize> {
+    pub message: S,
+    pub code: u16,
+    /// Where the message sits in the whole list, if it's a page of one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<Page>,
+}
+
+#[cfg(feature = "axum")]
+impl<S: Serialize> Response<S> {
+    pub fn new(message: S, code: http::StatusCode) -> (http::StatusCode, axum::Json<Self>) {
+        (
+            code,
+            axum::Json(Self {
+                message,
+                code: u16::from(code),
+                page: None,
+            }),
+        )
+    }
+
+    pub fn paginated(
+        message: S,
+        page: Page,
+        code: http::StatusCode,
+    ) -> (http::StatusCode, axum::Json<Self>) {
+        (
+            code,
+            axum::Json(Self {
+                message,
+                code: u16::from(code),
+                page: Some(page),
+            }),
+        )
+    }
+}
+
+/// Where a page sits in the list it was taken from, sent alongside the page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page {
+    /// The number of items in the whole list.
+    pub total: u64,
+    pub offset: u64,
+    pub limit: u64,
+    /// The cursor of the next page, or `None` if this is the last one.
+    pub next: Option<String>,
+}
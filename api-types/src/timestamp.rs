@@ -0,0 +1,34 @@
+//! How timestamps are written in the API.
+
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use serde::Serializer;
+
+/// Format a timestamp the way the API sends every timestamp: RFC 3339 in UTC, to the
+/// millisecond (e.g. `2026-10-16T09:30:00.000Z`), whatever offset it was stored with.
+#[must_use]
+pub fn rfc3339(timestamp: &DateTime<FixedOffset>) -> String {
+    timestamp
+        .with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Serialize a timestamp as `rfc3339` does, for fields of response types.
+/// # Errors
+/// Returns an error if the serializer fails.
+pub fn serialize<S: Serializer>(
+    timestamp: &DateTime<FixedOffset>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&rfc3339(timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    #[test]
+    fn rfc3339() {
+        let timestamp = DateTime::parse_from_rfc3339("2026-10-16T11:30:00+02:00").unwrap();
+        assert_eq!(super::rfc3339(&timestamp), "2026-10-16T09:30:00.000Z");
+    }
+}
@@ -0,0 +1,106 @@
+use crate::timestamp;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Registration {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdatePasswordRequest {
+    pub current: String,
+    pub new: String,
+    pub confirmed: String,
+}
+
+/// A change to the current user's account. Only one thing can be changed at a time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateMeRequest {
+    pub username: Option<String>,
+    pub password: Option<UpdatePasswordRequest>,
+    /// The IANA name of the time zone the user lives in.
+    pub timezone: Option<String>,
+}
+
+/// How a user appears wherever they show up in someone else's responses (e.g. friend lists).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSummary {
+    pub id: Uuid,
+    pub username: String,
+    /// The address of the user's avatar, if they've uploaded one.
+    pub avatar: Option<String>,
+    /// The user's rating in the season being played, or `None` if they haven't been placed in
+    /// it yet.
+    pub rating: Option<i32>,
+    pub presence: Status,
+    /// Whether the account is run by a program rather than a person.
+    #[serde(default)]
+    pub bot: bool,
+    /// When the user joined.
+    #[serde(serialize_with = "timestamp::serialize")]
+    pub created_at: DateTime<FixedOffset>,
+}
+
+/// What a user is up to, as far as their friends can tell.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// The user has no open connections.
+    Offline,
+    /// The user is connected, but hasn't done anything in a while.
+    Idle,
+    /// The user is connected and active.
+    Online,
+    /// The user is connected and has joined a game.
+    InGame,
+}
+
+impl Status {
+    /// The name the status is sent and stored as.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Offline => "offline",
+            Self::Idle => "idle",
+            Self::Online => "online",
+            Self::InGame => "in-game",
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "offline" => Ok(Self::Offline),
+            "idle" => Ok(Self::Idle),
+            "online" => Ok(Self::Online),
+            "in-game" => Ok(Self::InGame),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A notification as it's sent to clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: Uuid,
+    /// The notification's kind (e.g. `game_invite`).
+    pub kind: String,
+    /// What the notification is about (e.g. the game an invite is for).
+    pub payload: Value,
+    pub read: bool,
+    pub created_at: String,
+}
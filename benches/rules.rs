@@ -0,0 +1,61 @@
+//! How fast the rules and the companion run, to catch slowdowns when the board changes.
+//! Run with `cargo bench --no-default-features`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use olly::{companion::Companion, Game};
+
+/// A fixed midgame position, reached by the same 20 moves every time.
+fn midgame() -> Game {
+    play(20, 0x5eed)
+}
+
+/// Play up to the specified number of pseudo-random moves from the starting position, the same
+/// ones for the same seed.
+fn play(moves: usize, mut seed: u64) -> Game {
+    let mut game = Game::new();
+    for _ in 0..moves {
+        let piece = game.turn();
+        let legal = game.moves(piece);
+        if legal.is_empty() {
+            break;
+        }
+        // Xorshift, which is plenty random enough to pick moves with.
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        let (x, y) = legal[usize::try_from(seed % legal.len() as u64).unwrap()];
+        game.place(x, y, piece).unwrap();
+    }
+    game
+}
+
+fn rules(c: &mut Criterion) {
+    let game = midgame();
+    let piece = game.turn();
+    let (x, y) = game.clone().moves(piece)[0];
+    c.bench_function("flips", |b| {
+        b.iter(|| game.clone().preview(black_box(x), black_box(y), piece));
+    });
+    // Legal moves are cached once worked out, so each run starts from a fresh copy.
+    c.bench_function("legal moves", |b| {
+        b.iter(|| black_box(game.clone()).moves(piece));
+    });
+    c.bench_function("random game", |b| {
+        b.iter(|| play(black_box(60), 0x5eed));
+    });
+}
+
+fn search(c: &mut Criterion) {
+    let game = midgame();
+    let mut group = c.benchmark_group("search");
+    group.sample_size(10);
+    for depth in 1..=5 {
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, &depth| {
+            b.iter(|| Companion::from(&game).choice(depth));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rules, search);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+//! Playing a game over the gateway, a move at a time from the terminal.
+
+use crate::{
+    terminal::{render, Input},
+    Error,
+};
+use othello_client::{api::gateway::ServerMessage, Client, GameHandle};
+use tokio::io::{BufReader, Lines, Stdin};
+
+/// Play the specified game until it ends or the player leaves it. Anyone but the game's
+/// players can only watch.
+/// # Errors
+/// Returns an error if the gateway can't be reached or turns the player away.
+pub async fn play(
+    client: &Client,
+    id: &str,
+    input: &mut Lines<BufReader<Stdin>>,
+) -> Result<(), Error> {
+    let mut handle = client.join(id).await?;
+    println!("Type a square (e.g. f5) to play it, `resign` to give up or `leave` to stop.");
+    show(&handle);
+    loop {
+        tokio::select! {
+            event = handle.next_event() => {
+                let Some(event) = event else {
+                    println!("The server closed the connection.");
+                    return Ok(());
+                };
+                match event? {
+                    ServerMessage::GameUpdate { .. } => show(&handle),
+                    ServerMessage::GameEnd(summary) => {
+                        println!(
+                            "Game over ({:?}): {} – {}",
+                            summary.result, summary.score.black, summary.score.white,
+                        );
+                        return Ok(());
+                    }
+                    ServerMessage::GameAbort => {
+                        println!("The game was aborted.");
+                        return Ok(());
+                    }
+                    // Mistakes such as illegal moves leave the game as it was.
+                    ServerMessage::Error(e) => println!("{}", e.message),
+                    _ => {}
+                }
+            }
+            line = input.next_line() => {
+                let Some(line) = line? else {
+                    return Ok(());
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if handle.piece().is_none() {
+                    println!("You're only watching this game.");
+                    continue;
+                }
+                match Input::parse(&line) {
+                    Ok(Input::Place(x, y)) => handle.place(x, y).await?,
+                    Ok(Input::Resign) => handle.resign().await?,
+                    Ok(Input::Leave) => return Ok(()),
+                    Err(e) => println!("{e}"),
+                }
+            }
+        }
+    }
+}
+
+/// Draw the game's latest position and say whose move it is.
+fn show(handle: &GameHandle) {
+    let game = handle.game();
+    print!("{}", render(game));
+    let turn = game.turn();
+    if handle.piece() == Some(turn) {
+        println!("Your move ({turn:?}).");
+    } else {
+        println!("Waiting for {turn:?}.");
+    }
+}
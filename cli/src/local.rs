@@ -0,0 +1,176 @@
+//! Games played on this machine alone, with nothing but the core crate: two people taking
+//! turns at one keyboard, or one person against the companion.
+
+use crate::{
+    prompt,
+    terminal::{render, Input},
+    Error,
+};
+use olly::{companion::Companion, Coord, Game, Moves, Piece};
+use std::{fs, str::FromStr, time::Duration};
+use tokio::io::{BufReader, Lines, Stdin};
+
+/// How long the hardest companion thinks about each move.
+const HARD_BUDGET: Duration = Duration::from_secs(2);
+
+/// How well the companion plays.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Difficulty {
+    /// Takes whatever looks best right now.
+    Easy,
+    /// Looks a few moves ahead.
+    Medium,
+    /// Looks as far ahead as it can in a couple of seconds.
+    Hard,
+}
+
+impl Difficulty {
+    /// The move the companion would play in the specified game, or `None` if there are no
+    /// legal moves.
+    fn choose(self, game: &Game) -> Option<(usize, usize)> {
+        if game.clone().over() {
+            return None;
+        }
+        let mut companion = Companion::from(game);
+        match self {
+            Self::Easy => Some(companion.choice(1)),
+            Self::Medium => Some(companion.choice(4)),
+            Self::Hard => companion.best_move_within(HARD_BUDGET),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            _ => Err(format!(
+                "unknown difficulty: {s} (try easy, medium or hard)"
+            )),
+        }
+    }
+}
+
+/// How a local game ended.
+#[derive(Debug, PartialEq, Eq)]
+enum Ending {
+    /// Neither side could move any more.
+    Finished,
+    /// The specified side gave up.
+    Resigned(Piece),
+}
+
+/// Play a game at the terminal until it ends or the players leave it. Against the companion,
+/// the person plays black. Games that end (rather than being left) end with their transcript,
+/// which can be saved.
+/// # Errors
+/// Returns an error if the terminal can't be read from or the transcript can't be saved.
+pub async fn play(
+    companion: Option<Difficulty>,
+    input: &mut Lines<BufReader<Stdin>>,
+) -> Result<(), Error> {
+    let mut game = Game::new();
+    println!("Type a square (e.g. f5) to play it, `resign` to give up or `leave` to stop.");
+    let ending = loop {
+        print!("{}", render(&game));
+        if game.over() {
+            break Ending::Finished;
+        }
+        let turn = game.turn();
+        if let (Some(difficulty), Piece::White) = (companion, turn) {
+            // Searches can take a while, so they're kept off the runtime's threads.
+            let position = game.clone();
+            let choice = tokio::task::spawn_blocking(move || difficulty.choose(&position))
+                .await
+                .ok()
+                .flatten();
+            let Some((x, y)) = choice else {
+                break Ending::Finished;
+            };
+            println!("The companion plays {}.", Coord::new(x, y));
+            game.place(x, y, turn)
+                .expect("the companion should only choose legal moves");
+            continue;
+        }
+        loop {
+            let Some(line) = prompt(input, &format!("{turn:?} to move: ")).await? else {
+                return Ok(());
+            };
+            match Input::parse(&line) {
+                Ok(Input::Place(x, y)) => match game.place(x, y, turn) {
+                    Ok(()) => break,
+                    Err(e) => println!("{e}"),
+                },
+                Ok(Input::Resign) => return finish(&game, &Ending::Resigned(turn), input).await,
+                Ok(Input::Leave) => return Ok(()),
+                Err(e) => println!("{e}"),
+            }
+        }
+    };
+    finish(&game, &ending, input).await
+}
+
+/// Announce how the game ended and offer to save its transcript.
+async fn finish(
+    game: &Game,
+    ending: &Ending,
+    input: &mut Lines<BufReader<Stdin>>,
+) -> Result<(), Error> {
+    let (black, white) = game.score();
+    match ending {
+        Ending::Resigned(piece) => println!("{piece:?} resigned; {:?} wins.", !*piece),
+        Ending::Finished if black == white => println!("Game over: a draw, {black} – {white}."),
+        Ending::Finished => {
+            let winner = if black > white {
+                Piece::Black
+            } else {
+                Piece::White
+            };
+            println!("Game over: {winner:?} wins, {black} – {white}.");
+        }
+    }
+    let transcript = transcript(game);
+    println!("Transcript: {transcript}");
+    let path = prompt(input, "Save the transcript to (leave blank to skip): ")
+        .await?
+        .unwrap_or_default();
+    let path = path.trim();
+    if !path.is_empty() {
+        fs::write(path, format!("{transcript}\n"))?;
+        println!("Saved to {path}.");
+    }
+    Ok(())
+}
+
+/// The moves of the game in the standard notation, one after another, as the server exports
+/// them.
+fn transcript(game: &Game) -> String {
+    Moves::from(&game.history()[..]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{transcript, Difficulty};
+    use olly::{Game, Piece};
+
+    #[test]
+    fn difficulties() {
+        assert_eq!("medium".parse(), Ok(Difficulty::Medium));
+        assert!("impossible".parse::<Difficulty>().is_err());
+        let mut game = Game::new();
+        let choice = Difficulty::Easy.choose(&game).unwrap();
+        assert!(game.moves(Piece::Black).contains(&choice));
+    }
+
+    #[test]
+    fn transcripts() {
+        let mut game = Game::new();
+        game.place(5, 4, Piece::Black).unwrap();
+        game.place(3, 5, Piece::White).unwrap();
+        assert_eq!(transcript(&game), "f5d6");
+    }
+}
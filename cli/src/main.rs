@@ -0,0 +1,196 @@
+//! A terminal client for playing on an olly server: log in, look through your games and play
+//! them a move at a time over the gateway. Games can also be played offline, at one keyboard or
+//! against the companion.
+
+mod live;
+mod local;
+mod terminal;
+
+use othello_client::{Client, Difficulty};
+use std::{env, io::Write, process::ExitCode, str::FromStr};
+use tokio::io::{self, AsyncBufReadExt, BufReader, Lines, Stdin};
+
+/// The server the client connects to unless told otherwise.
+const DEFAULT_SERVER: &str = "http://localhost:3000";
+
+const HELP: &str = "\
+Commands:
+  login <username>   log in, asking for the password
+  games              list your games
+  bot <difficulty>   start a game against the server (easy, medium or hard)
+  play <id>          play (or watch) one of your games
+  local [difficulty] play offline, against the companion if given a difficulty (easy,
+                     medium or hard) or otherwise two players at one keyboard
+  help               show this message
+  quit               leave";
+
+/// Something typed in at the prompt.
+#[derive(Debug, PartialEq, Eq)]
+enum Command<'a> {
+    Login(&'a str),
+    Games,
+    Bot(&'a str),
+    Play(&'a str),
+    Local(Option<&'a str>),
+    Help,
+    Quit,
+}
+
+impl<'a> Command<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let command = match (words.next()?, words.next()) {
+            ("login", Some(username)) => Self::Login(username),
+            ("games", None) => Self::Games,
+            ("bot", Some(difficulty)) => Self::Bot(difficulty),
+            ("play", Some(id)) => Self::Play(id),
+            ("local", difficulty) => Self::Local(difficulty),
+            ("help", None) => Self::Help,
+            ("quit" | "exit", None) => Self::Quit,
+            _ => return None,
+        };
+        // Every command takes at most one argument.
+        words.next().is_none().then_some(command)
+    }
+}
+
+/// Show a prompt and wait for the next line typed in, or `None` once input runs out.
+async fn prompt(
+    input: &mut Lines<BufReader<Stdin>>,
+    prompt: &str,
+) -> Result<Option<String>, Error> {
+    print!("{prompt}");
+    std::io::stdout().flush()?;
+    Ok(input.next_line().await?)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Client(#[from] othello_client::Error),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+async fn run(
+    client: &mut Client,
+    command: Command<'_>,
+    input: &mut Lines<BufReader<Stdin>>,
+) -> Result<(), Error> {
+    match command {
+        Command::Login(username) => {
+            // Passwords are read like any other line, so they're shown as they're typed.
+            let password = prompt(input, "Password: ").await?.unwrap_or_default();
+            let me = client.login(username, &password).await?;
+            println!("Logged in as {}.", me.username);
+        }
+        Command::Games => {
+            let games = client.games().await?;
+            if games.is_empty() {
+                println!("You have no games yet.");
+            }
+            for game in games {
+                let status = if game.ended { "over" } else { "underway" };
+                println!("{}  {} vs {} ({status})", game.id, game.host, game.opponent);
+            }
+        }
+        Command::Bot(difficulty) => {
+            let Ok(difficulty) = Difficulty::from_str(difficulty) else {
+                println!("Unknown difficulty. Try easy, medium or hard.");
+                return Ok(());
+            };
+            let game = client.create_bot_game(difficulty).await?;
+            println!("Started game {}.", game.id);
+            live::play(client, &game.id.to_string(), input).await?;
+        }
+        Command::Play(id) => {
+            let game = client.game(id).await?;
+            if game.pending {
+                println!("That game hasn't been accepted yet.");
+            } else if game.ended {
+                println!("That game is over.");
+            } else {
+                live::play(client, &game.id.to_string(), input).await?;
+            }
+        }
+        Command::Local(difficulty) => {
+            let difficulty = match difficulty.map(str::parse).transpose() {
+                Ok(difficulty) => difficulty,
+                Err(e) => {
+                    println!("{e}");
+                    return Ok(());
+                }
+            };
+            local::play(difficulty, input).await?;
+        }
+        Command::Help => println!("{HELP}"),
+        Command::Quit => {}
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let server = match (args.next().as_deref(), args.next()) {
+        (None, None) => env::var("OLLY_SERVER").unwrap_or_else(|_| DEFAULT_SERVER.to_string()),
+        (Some("--server"), Some(server)) => server,
+        _ => {
+            eprintln!("usage: othello-cli [--server <url>]");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut client = match Client::new(&server) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("Connected to {server}. Type `help` for a list of commands.");
+    let mut input = BufReader::new(io::stdin()).lines();
+    loop {
+        let line = match prompt(&mut input, "> ").await {
+            Ok(Some(line)) => line,
+            Ok(None) => return ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(command) = Command::parse(&line) else {
+            println!("Unknown command. Type `help` for a list of commands.");
+            continue;
+        };
+        if command == Command::Quit {
+            return ExitCode::SUCCESS;
+        }
+        if let Err(e) = run(&mut client, command, &mut input).await {
+            println!("{e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Command;
+
+    #[test]
+    fn commands() {
+        assert_eq!(Command::parse("login alice"), Some(Command::Login("alice")));
+        assert_eq!(Command::parse("  games "), Some(Command::Games));
+        assert_eq!(Command::parse("bot hard"), Some(Command::Bot("hard")));
+        assert_eq!(Command::parse("local"), Some(Command::Local(None)));
+        assert_eq!(
+            Command::parse("local easy"),
+            Some(Command::Local(Some("easy")))
+        );
+        assert_eq!(Command::parse("quit"), Some(Command::Quit));
+        assert_eq!(Command::parse("login"), None);
+        assert_eq!(Command::parse("games please"), None);
+        assert_eq!(Command::parse("play a b"), None);
+    }
+}
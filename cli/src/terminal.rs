@@ -0,0 +1,55 @@
+//! What's shown on and read from the terminal while playing, whether on a server or not.
+
+use olly::{Coord, Game};
+
+/// Something typed in while playing.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Input {
+    Place(usize, usize),
+    Resign,
+    Leave,
+}
+
+impl Input {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        match line.trim() {
+            "resign" => Ok(Self::Resign),
+            "leave" | "quit" => Ok(Self::Leave),
+            square => square
+                .parse::<Coord>()
+                .map(|coord| Self::Place(coord.x, coord.y))
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Draw the board with the core's own formatting, its rows and columns labelled with the
+/// notation moves are typed in, followed by the score.
+pub fn render(game: &Game) -> String {
+    let (black, white) = game.score();
+    format!("{game}○ Black {black} – {white} White ●\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Input};
+    use olly::Game;
+
+    #[test]
+    fn input() {
+        assert_eq!(Input::parse("f5"), Ok(Input::Place(5, 4)));
+        assert_eq!(Input::parse(" resign "), Ok(Input::Resign));
+        assert_eq!(Input::parse("leave"), Ok(Input::Leave));
+        assert!(Input::parse("z9").is_err());
+    }
+
+    #[test]
+    fn board() {
+        let board = render(&Game::new());
+        let lines: Vec<_> = board.lines().collect();
+        assert_eq!(lines[0], "  abcdefgh");
+        assert_eq!(lines[4], "4 ...●○...");
+        assert_eq!(lines[5], "5 ...○●...");
+        assert_eq!(lines[9], "○ Black 2 – 2 White ●");
+    }
+}
@@ -3,6 +3,7 @@
 import { Button, Input } from "@headlessui/react";
 import { useEffect, useState } from "react";
 import { BASE_API_URL } from "@/lib";
+import { csrfHeaders } from "@/lib/call";
 import cn from "classnames";
 
 export default function AccountEditPassword() {
@@ -36,6 +37,7 @@ export default function AccountEditPassword() {
         mode: "cors",
         headers: {
           "Content-Type": "application/json",
+          ...csrfHeaders(),
         },
       });
       if (res.status === 200) {
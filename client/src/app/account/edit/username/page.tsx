@@ -4,6 +4,7 @@ import { Button, Input } from "@headlessui/react";
 import { useEffect, useState } from "react";
 import cn from "classnames";
 import { BASE_API_URL } from "@/lib";
+import { csrfHeaders } from "@/lib/call";
 
 export default function AccountEditUsername() {
   const [username, setUsername] = useState("");
@@ -27,6 +28,7 @@ export default function AccountEditUsername() {
         mode: "cors",
         headers: {
           "Content-Type": "application/json",
+          ...csrfHeaders(),
         },
       });
       if (res.status === 200) {
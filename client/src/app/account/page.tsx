@@ -2,6 +2,7 @@
 
 import FriendsList from "@/components/friends/FriendsList";
 import MergedFriendRequests from "@/components/friends/MergedFriendRequests";
+import Standing from "@/components/Standing";
 import call from "@/lib/call";
 import { Button, Input } from "@headlessui/react";
 import { useEffect, useState } from "react";
@@ -79,6 +80,7 @@ export default function Account() {
         <FriendsList />
         <MergedFriendRequests />
       </section>
+      <Standing />
     </>
   );
 }
@@ -1,6 +1,7 @@
 "use client";
 
 import { BASE_API_URL } from "@/lib";
+import { csrfHeaders } from "@/lib/call";
 import useUser from "@/lib/hooks/useUser";
 import Link from "next/link";
 import { useEffect, useState } from "react";
@@ -14,6 +15,7 @@ export default function Logout() {
     fetch(`${BASE_API_URL}/logout`, {
       method: "POST",
       credentials: "include",
+      headers: csrfHeaders(),
     }).then((res) => {
       if (res.status === 200) {
         mutate();
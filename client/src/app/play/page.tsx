@@ -8,6 +8,15 @@ import {
   handlePreviewEvent,
   handleErrorEvent,
   handleGameEnd,
+  handlePresence,
+  handleFriendPresence,
+  handleFriendRequestCancel,
+  handleFriendRequestDecline,
+  handleFriendRequestReceive,
+  handleFriendRequestAccept,
+  handleFriendRemove,
+  handleNotification,
+  handleServerRestarting,
 } from "@/lib/handlers";
 import { Board, Piece, Event } from "@/types";
 import { useEffect, useState } from "react";
@@ -59,6 +68,15 @@ function LiveBoard({ gameId }: LiveBoardProps) {
         5: handlePreviewEvent,
         6: handleErrorEvent,
         7: handleGameEnd,
+        9: handlePresence,
+        10: handleFriendPresence,
+        11: handleFriendRequestCancel,
+        12: handleFriendRequestDecline,
+        14: handleServerRestarting,
+        15: handleFriendRequestReceive,
+        16: handleFriendRequestAccept,
+        17: handleFriendRemove,
+        18: handleNotification,
       } as const;
       handlers[data.op]({
         //@ts-expect-error
@@ -85,6 +103,8 @@ function LiveBoard({ gameId }: LiveBoardProps) {
         t: token,
         d: {
           type: "Identify",
+          version: 2,
+          resume: sessionStorage.getItem("resume") ?? undefined,
         },
       });
       sendJsonMessage({
@@ -132,6 +152,29 @@ function LiveBoard({ gameId }: LiveBoardProps) {
       >
         Leave Game
       </Button>
+      {turn !== color && (
+        <div className="flex flex-row mx-auto space-x-2 mt-2">
+          {[false, true].map((draw) => (
+            <Button
+              key={`${draw}`}
+              className="border-2 border-mantle p-2 rounded-lg hover:border-mauve transition-all"
+              onClick={() =>
+                sendJsonMessage({
+                  op: 9,
+                  t: token,
+                  d: {
+                    type: "Claim",
+                    id: gameId,
+                    draw,
+                  },
+                })
+              }
+            >
+              {draw ? "Declare Draw" : "Claim Victory"}
+            </Button>
+          ))}
+        </div>
+      )}
       <div className="flex flex-row">
         <div className="mx-auto">
           <div
@@ -0,0 +1,49 @@
+"use client";
+
+import useStanding from "@/lib/hooks/useStanding";
+import { Penalty, StrikeReason } from "@/types";
+
+const REASONS: Record<StrikeReason, string> = {
+  chat: "Chat violation",
+  aborting: "Aborting too many games",
+  stalling: "Abandoning a lost game",
+};
+
+const PENALTIES: Record<Penalty, string> = {
+  mute: "Muted in chat",
+  "casual-only": "Restricted to casual games",
+  ban: "Banned from playing",
+};
+
+export default function Standing() {
+  const { isLoading, standing } = useStanding();
+
+  if (isLoading || !standing) return <></>;
+
+  return (
+    <section className="py-10 text-center">
+      <h2 className="font-bold text-text">Account Standing</h2>
+      {standing.strikes.length === 0 ? (
+        <p className="text-green">Your account is in good standing.</p>
+      ) : (
+        <>
+          <ul className="py-2">
+            {standing.penalties.map(({ penalty, expires }) => (
+              <li className="text-red" key={penalty}>
+                {PENALTIES[penalty]} until {new Date(expires).toLocaleString()}
+              </li>
+            ))}
+          </ul>
+          <ul className="py-2">
+            {standing.strikes.map(({ reason, timestamp }) => (
+              <li className="text-subtext0" key={timestamp}>
+                {REASONS[reason]} ({new Date(timestamp).toLocaleString()})
+              </li>
+            ))}
+          </ul>
+          <p className="text-subtext0">Strikes expire after 30 days.</p>
+        </>
+      )}
+    </section>
+  );
+}
@@ -40,20 +40,21 @@ export default function FriendsList() {
           friends?.map((friend) => (
             <li
               className="flex flex-row space-x-1 justify-center"
-              key={friend.username}
+              key={friend.user.username}
             >
-              <p className="text-text">{friend.username}</p>
+              <p className="text-text">{friend.user.username}</p>
+              <p className="text-subtext0">({friend.user.presence})</p>
               <Button
                 onClick={(e) => {
                   e.preventDefault();
-                  createGame(friend.username);
+                  createGame(friend.user.username);
                 }}
                 className="text-mauve hover:text-pink transition-all"
               >
                 {"["}Play{"]"}
               </Button>
               <Button
-                onClick={removeFriend(friend.username)}
+                onClick={removeFriend(friend.user.username)}
                 className="text-mauve hover:text-pink transition-all"
               >
                 {"["}Remove{"]"}
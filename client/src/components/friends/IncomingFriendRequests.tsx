@@ -12,7 +12,9 @@ export default function IncomingFriendRequests() {
       const action = accept ? "accept" : "decline";
       const pastTense = accept ? "accept" : "declin";
       (async () => {
-        const res = await call(`/@me/friends/${sender}/${action}`, "POST");
+        const res = accept
+          ? await call(`/@me/friends/${sender}/accept`, "POST")
+          : await call(`/@me/requests/incoming/${sender}`, "DELETE");
         if (res.status === 200) {
           toast.success(
             `Friend request ${pastTense}ed!`,
@@ -34,17 +36,17 @@ export default function IncomingFriendRequests() {
     incomingFriendRequests?.map((request) => (
       <li
         className="flex flex-row space-x-3 justify-center"
-        key={`${request.sender}-incoming`}
+        key={`${request.sender.username}-incoming`}
       >
-        <p className="text-text">{request.sender}</p>
+        <p className="text-text">{request.sender.username}</p>
         <button
-          onClick={onClick(request.sender, true)}
+          onClick={onClick(request.sender.username, true)}
           className="text-mauve hover:text-pink transition-all"
         >
           {"["}Accept{"]"}
         </button>
         <button
-          onClick={onClick(request.sender, false)}
+          onClick={onClick(request.sender.username, false)}
           className="text-mauve hover:text-pink transition-all"
         >
           {"["}Decline{"]"}
@@ -11,7 +11,7 @@ export default function OutgoingFriendRequests() {
     return async (e: React.MouseEvent<HTMLButtonElement, MouseEvent>) => {
       e.preventDefault();
       (async () => {
-        const res = await call(`/@me/friends/outgoing/${recipient}`, "DELETE");
+        const res = await call(`/@me/requests/outgoing/${recipient}`, "DELETE");
         if (res.status === 200) {
           toast.success(
             `Cancelled friend request to ${recipient}.`,
@@ -30,12 +30,12 @@ export default function OutgoingFriendRequests() {
     outgoingFriendRequests?.map((request) => (
       <li
         className="flex flex-row space-x-1 justify-center"
-        key={`${request.recipient}-outgoing`}
+        key={`${request.recipient.username}-outgoing`}
       >
-        <p className="text-text">{request.recipient}</p>
+        <p className="text-text">{request.recipient.username}</p>
         <Button
           className="text-mauve hover:text-pink transition-all"
-          onClick={cancelFriendRequest(request.recipient)}
+          onClick={cancelFriendRequest(request.recipient.username)}
         >
           {"["}Cancel{"]"}
         </Button>
@@ -13,6 +13,16 @@ export function fetchOptions(method: Method): RequestInit {
     mode: "cors",
     headers: {
       "Content-Type": "application/json",
+      ...csrfHeaders(),
     },
   };
 }
+
+// Requests that change anything have to repeat the token from the CSRF cookie.
+export function csrfHeaders(): Record<string, string> {
+  const token = document.cookie
+    .split("; ")
+    .find((cookie) => cookie.startsWith("csrf="))
+    ?.slice("csrf=".length);
+  return token ? { "X-CSRF-Token": decodeURIComponent(token) } : {};
+}
@@ -1,5 +1,6 @@
 import toast from "react-hot-toast";
 import { BASE_API_URL, TOAST_ERROR_OPTIONS, TOAST_SUCCESS_OPTIONS } from ".";
+import { csrfHeaders } from "./call";
 
 export const createGame = async (opponent: string) => {
   const res = await fetch(`${BASE_API_URL}/game`, {
@@ -7,6 +8,7 @@ export const createGame = async (opponent: string) => {
     method: "POST",
     headers: {
       "Content-Type": "application/json",
+      ...csrfHeaders(),
     },
     body: JSON.stringify({
       guest: opponent,
@@ -8,12 +8,23 @@ import {
   Context,
   GameAbortEvent,
   GameEndEvent,
+  PresenceEvent,
+  FriendPresenceEvent,
+  FriendRequestCancelEvent,
+  FriendRequestDeclineEvent,
+  FriendRequestReceiveEvent,
+  FriendRequestAcceptEvent,
+  FriendRemoveEvent,
+  NotificationEvent,
+  ServerRestartingEvent,
 } from "@/types";
 import toast from "react-hot-toast";
 
 export function handleAckEvent(_: Context<AckEvent>) {}
 
 export function handleReady(context: Context<ReadyEvent>) {
+  // Keep the resume token so that a dropped connection can pick up where it left off.
+  sessionStorage.setItem("resume", context.ev.d.resume);
   context.setReady(true);
 }
 
@@ -33,13 +44,65 @@ export function handleGameEnd(context: Context<GameEndEvent>) {
   const { ev } = context;
   if (!context.aborted) {
     context.setAborted(true);
-    toast.success(
-      `${ev.d.winner} won the game with a score of ${ev.d.points} / ${ev.d.total}!`,
-      { duration: 10_000 },
-    );
+    const { winner, termination, points, total, score } = ev.d;
+    const message =
+      winner === null
+        ? `The game ended in a draw at ${score.black} - ${score.white}!`
+        : termination === "resignation"
+          ? `${winner} won the game by resignation!`
+          : termination === "abandonment"
+            ? `${winner} won the game after their opponent left!`
+            : termination === "stalling"
+              ? `${winner} won the game after their opponent stalled!`
+              : `${winner} won the game with a score of ${points} / ${total}!`;
+    toast.success(message, { duration: 10_000 });
   }
 }
 
+export function handlePresence(context: Context<PresenceEvent>) {
+  // Players only see their own connections come and go while they're connected, so
+  // going offline can only be the opponent.
+  if (!context.ev.d.online && !context.aborted) {
+    toast.error("Your opponent lost their connection.", { duration: 5000 });
+  }
+}
+
+export function handleFriendPresence(_: Context<FriendPresenceEvent>) {}
+
+export function handleFriendRequestCancel(
+  context: Context<FriendRequestCancelEvent>
+) {
+  toast(`${context.ev.d.user} withdrew their friend request.`);
+}
+
+export function handleFriendRequestDecline(
+  context: Context<FriendRequestDeclineEvent>
+) {
+  toast(`${context.ev.d.user} declined your friend request.`);
+}
+
+export function handleFriendRequestReceive(
+  context: Context<FriendRequestReceiveEvent>
+) {
+  toast(`${context.ev.d.user} sent you a friend request.`);
+}
+
+export function handleFriendRequestAccept(
+  context: Context<FriendRequestAcceptEvent>
+) {
+  toast(`${context.ev.d.user} accepted your friend request.`);
+}
+
+export function handleFriendRemove(_: Context<FriendRemoveEvent>) {}
+
+export function handleNotification(_: Context<NotificationEvent>) {}
+
+export function handleServerRestarting(
+  _: Context<ServerRestartingEvent>
+) {
+  toast("The server is restarting. Reconnecting shortly...");
+}
+
 export function handleGameUpdate(context: Context<GameUpdateEvent>) {
   const { ev, board, setTurn, setPreview, setBoard } = context;
   const { board: gameBoard, turn } = ev.d.game;
@@ -0,0 +1,28 @@
+import useSWR, { SWRResponse } from "swr";
+import { BASE_API_URL } from "@/lib";
+import useUser from "@/lib/hooks/useUser";
+import { Standing } from "@/types";
+
+interface MyStandingRoute {
+  message: Standing;
+  code: number;
+}
+
+export default function useStanding() {
+  const { authenticated } = useUser();
+  const { data, isLoading }: SWRResponse<MyStandingRoute> = useSWR(
+    `${BASE_API_URL}/@me/standing`,
+    async (url) => {
+      const res = await fetch(url, {
+        credentials: "include",
+        mode: "cors",
+      });
+      const data = await res.json();
+      return data;
+    }
+  );
+  return {
+    isLoading,
+    standing: isLoading || !authenticated ? null : data?.message,
+  };
+}
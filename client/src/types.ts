@@ -5,28 +5,81 @@ export enum Piece {
   White,
 }
 
+export interface TimeControl {
+  initial: number;
+  increment: number;
+}
+
+export interface Correspondence {
+  days_per_move: number;
+}
+
+export interface GameSettings {
+  variant: "standard" | "fog";
+  board_size: number;
+  time_control: TimeControl | null;
+  correspondence: Correspondence | null;
+  rated: boolean;
+  handicap: number;
+  color_policy: "host-black" | "guest-black" | "random";
+}
+
 export interface Game {
   id: string;
   host: string;
+  host_color: Piece;
   opponent: string;
   ended: boolean;
+  settings: GameSettings;
 }
 
 export interface Member {
   id: string;
   username: string;
+  /** The IANA name of the member's time zone, e.g. `Europe/London`. */
+  timezone: string;
 }
 
-export interface Friend {
+export type Presence = "offline" | "idle" | "online" | "in-game";
+
+export interface UserSummary {
+  id: string;
   username: string;
+  avatar: string | null;
+  rating: number | null;
+  presence: Presence;
+  created_at: string;
+}
+
+export interface Friend {
+  user: UserSummary;
+  created_at: string;
 }
 
 export interface IncomingFriendRequest {
-  sender: string;
+  sender: UserSummary;
+  created_at: string;
 }
 
 export interface OutgoingFriendRequest {
-  recipient: string;
+  recipient: UserSummary;
+  created_at: string;
+}
+
+export type StrikeReason = "chat" | "aborting" | "stalling";
+
+export type Penalty = "mute" | "casual-only" | "ban";
+
+export interface Standing {
+  strikes: Array<{
+    reason: StrikeReason;
+    game: string | null;
+    timestamp: string;
+  }>;
+  penalties: Array<{
+    penalty: Penalty;
+    expires: string;
+  }>;
 }
 
 export type Board = Array<Array<Piece | null>>;
@@ -38,7 +91,10 @@ export interface AckEvent {
 export interface ReadyEvent {
   op: 2;
   d: {
-    token: string;
+    type: "Ready";
+    version: number;
+    resume: string;
+    resumed: boolean;
   };
 }
 
@@ -52,10 +108,13 @@ export interface GameAbortEvent {
 
 export interface GameUpdateEvent {
   op: 4;
+  g?: string;
+  s?: number;
   d: {
     game: {
       board: Array<string | null>;
       turn: string;
+      settings: GameSettings;
     };
   };
 }
@@ -67,23 +126,174 @@ export interface PreviewEvent {
   };
 }
 
+/** The body of every error, over HTTP and the gateway alike. */
+export interface ApiError {
+  /** A stable machine-readable reason, e.g. `"square_occupied"`. */
+  code: string;
+  message: string;
+  details: unknown;
+  status: number;
+}
+
 export interface ErrorEvent {
   op: 6;
-  d: {
-    message: string;
-    code: number;
-  };
+  d: ApiError;
 }
 
 export interface GameEndEvent {
   op: 7;
+  g?: string;
+  s?: number;
   d: {
-    winner: string;
+    result: "black" | "white" | "draw";
+    winner: string | null;
+    termination: "normal" | "resignation" | "abandonment" | "stalling" | "timeout";
+    score: {
+      black: number;
+      white: number;
+    };
     points: number;
     total: number;
+    rating_deltas: [number, number] | null;
+    links: {
+      game: string;
+      export: string;
+    };
+  };
+}
+
+export interface PresenceEvent {
+  op: 9;
+  g?: string;
+  s?: number;
+  d: {
+    type: "Presence";
+    user: string;
+    online: boolean;
+  };
+}
+
+export interface FriendPresenceEvent {
+  op: 10;
+  d: {
+    type: "FriendPresence";
+    user: string;
+    status: Presence;
+  };
+}
+
+export interface FriendRequestCancelEvent {
+  op: 11;
+  d: {
+    type: "FriendRequestCancel";
+    user: string;
+  };
+}
+
+export interface FriendRequestDeclineEvent {
+  op: 12;
+  d: {
+    type: "FriendRequestDecline";
+    user: string;
+  };
+}
+
+export interface GameDeltaEvent {
+  op: 13;
+  g?: string;
+  s?: number;
+  d: {
+    type: "GameDelta";
+    turn: Piece;
+    changed: Array<[number, number, Piece | null]>;
+  };
+}
+
+export interface ServerRestartingEvent {
+  op: 14;
+  d: {
+    type: "ServerRestarting";
+  };
+}
+
+export interface FriendRequestReceiveEvent {
+  op: 15;
+  d: {
+    type: "FriendRequestReceive";
+    user: string;
+  };
+}
+
+export interface FriendRequestAcceptEvent {
+  op: 16;
+  d: {
+    type: "FriendRequestAccept";
+    user: string;
+  };
+}
+
+export interface FriendRemoveEvent {
+  op: 17;
+  d: {
+    type: "FriendRemove";
+    user: string;
+  };
+}
+
+export type NotificationKind =
+  | "game_invite"
+  | "game_invite_expire"
+  | "game_join"
+  | "friend_request"
+  | "friend_request_accept"
+  | "game_end"
+  | "tournament_game"
+  | "season_reward"
+  | "move_reminder";
+
+export interface Notification {
+  id: string;
+  kind: NotificationKind;
+  payload: Record<string, unknown>;
+  read: boolean;
+  created_at: string;
+}
+
+export interface NotificationEvent {
+  op: 18;
+  d: {
+    type: "Notification";
+    notification: Notification;
+    unread: number;
+  };
+}
+
+export interface OpenChallenge {
+  id: string;
+  host: UserSummary;
+  settings: GameSettings;
+  created_at: string;
+}
+
+export interface LobbyChallengeCreateEvent {
+  op: 19;
+  d: {
+    type: "LobbyChallengeCreate";
+    challenge: OpenChallenge;
+  };
+}
+
+export interface LobbyChallengeRemoveEvent {
+  op: 20;
+  d: {
+    type: "LobbyChallengeRemove";
+    id: string;
   };
 }
 
+/** How often a connection wants the whole board after a move, sent when identifying. */
+export type Snapshots = "every" | "deltas" | { interval: number };
+
 export type Event =
   | AckEvent
   | ReadyEvent
@@ -91,7 +301,19 @@ export type Event =
   | GameUpdateEvent
   | ErrorEvent
   | PreviewEvent
-  | GameEndEvent;
+  | GameEndEvent
+  | PresenceEvent
+  | FriendPresenceEvent
+  | FriendRequestCancelEvent
+  | FriendRequestDeclineEvent
+  | GameDeltaEvent
+  | ServerRestartingEvent
+  | FriendRequestReceiveEvent
+  | FriendRequestAcceptEvent
+  | FriendRemoveEvent
+  | NotificationEvent
+  | LobbyChallengeCreateEvent
+  | LobbyChallengeRemoveEvent;
 
 export interface Context<T> {
   ws: WebSocket;
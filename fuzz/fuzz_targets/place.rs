@@ -0,0 +1,37 @@
+//! Plays arbitrary squares for either side, legal or not, checking that illegal moves change
+//! nothing and legal ones follow the rules.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use olly::{Game, Piece};
+
+fuzz_target!(|moves: &[u8]| {
+    let mut game = Game::new();
+    // Each byte is a move: the square in the low six bits, and the side in the next.
+    for &byte in moves {
+        let (x, y) = (usize::from(byte & 7), usize::from((byte >> 3) & 7));
+        let piece = if byte & 64 == 0 {
+            Piece::Black
+        } else {
+            Piece::White
+        };
+        let before = game.clone();
+        let legal = game.moves(piece).contains(&(x, y));
+        let preview = game.preview(x, y, piece);
+        match game.place(x, y, piece) {
+            Ok(()) => {
+                assert!(legal);
+                let flips = preview.unwrap();
+                assert!(!flips.is_empty());
+                assert_eq!(game.changes_since(&before).len(), flips.len() + 1);
+                assert_eq!(game.turn(), !piece);
+            }
+            Err(e) => {
+                assert!(!legal);
+                assert_eq!(preview, Err(e));
+                assert_eq!(game, before);
+            }
+        }
+    }
+});
@@ -10,6 +10,38 @@ mod m20240527_185255_create_friends;
 mod m20240527_191255_create_friend_requests;
 mod m20240621_143622_invite_only_games;
 mod m20241019_164847_game_endings_and_stats;
+mod m20261015_090000_create_login_attempts;
+mod m20261015_100000_game_challenges;
+mod m20261015_110000_create_identities;
+mod m20261015_120000_game_results;
+mod m20261015_130000_create_blocks;
+mod m20261016_090000_create_strikes;
+mod m20261016_100000_created_at_timestamps;
+mod m20261016_110000_game_settings;
+mod m20261016_120000_member_avatars;
+mod m20261016_125000_member_admins;
+mod m20261016_130000_create_bans;
+mod m20261016_140000_create_reports;
+mod m20261016_150000_session_timestamps;
+mod m20261016_160000_member_timezones;
+mod m20261016_170000_game_state;
+mod m20261016_180000_create_audit_log;
+mod m20261016_190000_create_notifications;
+mod m20261016_200000_create_tournaments;
+mod m20261016_210000_swiss_tournaments;
+mod m20261016_220000_create_seasons;
+mod m20261017_090000_create_puzzles;
+mod m20261017_100000_create_analysis;
+mod m20261017_110000_bot_accounts;
+mod m20261017_120000_hosted_opponents;
+mod m20261017_130000_create_arenas;
+mod m20261017_140000_archived_games;
+mod m20261017_150000_username_search;
+mod m20261017_160000_create_open_challenges;
+mod m20261017_170000_correspondence_games;
+mod m20261017_180000_create_game_moves;
+mod m20261017_190000_create_webhooks;
+mod m20261017_200000_game_host_colors;
 
 pub struct Migrator;
 
@@ -26,6 +58,38 @@ impl MigratorTrait for Migrator {
             Box::new(m20240527_191255_create_friend_requests::Migration),
             Box::new(m20240621_143622_invite_only_games::Migration),
             Box::new(m20241019_164847_game_endings_and_stats::Migration),
+            Box::new(m20261015_090000_create_login_attempts::Migration),
+            Box::new(m20261015_100000_game_challenges::Migration),
+            Box::new(m20261015_110000_create_identities::Migration),
+            Box::new(m20261015_120000_game_results::Migration),
+            Box::new(m20261015_130000_create_blocks::Migration),
+            Box::new(m20261016_090000_create_strikes::Migration),
+            Box::new(m20261016_100000_created_at_timestamps::Migration),
+            Box::new(m20261016_110000_game_settings::Migration),
+            Box::new(m20261016_120000_member_avatars::Migration),
+            Box::new(m20261016_125000_member_admins::Migration),
+            Box::new(m20261016_130000_create_bans::Migration),
+            Box::new(m20261016_140000_create_reports::Migration),
+            Box::new(m20261016_150000_session_timestamps::Migration),
+            Box::new(m20261016_160000_member_timezones::Migration),
+            Box::new(m20261016_170000_game_state::Migration),
+            Box::new(m20261016_180000_create_audit_log::Migration),
+            Box::new(m20261016_190000_create_notifications::Migration),
+            Box::new(m20261016_200000_create_tournaments::Migration),
+            Box::new(m20261016_210000_swiss_tournaments::Migration),
+            Box::new(m20261016_220000_create_seasons::Migration),
+            Box::new(m20261017_090000_create_puzzles::Migration),
+            Box::new(m20261017_100000_create_analysis::Migration),
+            Box::new(m20261017_110000_bot_accounts::Migration),
+            Box::new(m20261017_120000_hosted_opponents::Migration),
+            Box::new(m20261017_130000_create_arenas::Migration),
+            Box::new(m20261017_140000_archived_games::Migration),
+            Box::new(m20261017_150000_username_search::Migration),
+            Box::new(m20261017_160000_create_open_challenges::Migration),
+            Box::new(m20261017_170000_correspondence_games::Migration),
+            Box::new(m20261017_180000_create_game_moves::Migration),
+            Box::new(m20261017_190000_create_webhooks::Migration),
+            Box::new(m20261017_200000_game_host_colors::Migration),
         ]
     }
 }
@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Identity::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Identity::Provider).string().not_null())
+                    .col(ColumnDef::new(Identity::Subject).string().not_null())
+                    .col(ColumnDef::new(Identity::Member).uuid().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Identity::Table, Identity::Member)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .table(Identity::Table)
+                            .col(Identity::Provider)
+                            .col(Identity::Subject),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Accounts created through an identity provider don't have a password.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Member::Table)
+                    .modify_column(ColumnDef::new(Member::Password).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Member::Table)
+                    .modify_column(ColumnDef::new(Member::Password).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Identity::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Identity {
+    Table,
+    Provider,
+    Subject,
+    Member,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+    Password,
+}
@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Block::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Block::Blocker).uuid().not_null())
+                    .col(ColumnDef::new(Block::Blocked).uuid().not_null())
+                    .col(
+                        ColumnDef::new(Block::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Block::Table, Block::Blocker)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Block::Table, Block::Blocked)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .table(Block::Table)
+                            .col(Block::Blocker)
+                            .col(Block::Blocked),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Block::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Block {
+    Table,
+    Blocker,
+    Blocked,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Strike::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Strike::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Strike::Member).uuid().not_null())
+                    .col(ColumnDef::new(Strike::Reason).string().not_null())
+                    .col(ColumnDef::new(Strike::Game).uuid())
+                    .col(
+                        ColumnDef::new(Strike::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Strike::Table, Strike::Member)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Strike::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Strike {
+    Table,
+    Id,
+    Member,
+    Reason,
+    Game,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
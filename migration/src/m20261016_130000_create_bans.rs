@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Ban::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Ban::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Ban::Member).uuid().not_null())
+                    .col(ColumnDef::new(Ban::Reason).string().not_null())
+                    // Permanent bans never expire.
+                    .col(ColumnDef::new(Ban::ExpiresAt).timestamp_with_time_zone())
+                    .col(
+                        ColumnDef::new(Ban::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Ban::Table, Ban::Member)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Ban::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Ban {
+    Table,
+    Id,
+    Member,
+    Reason,
+    ExpiresAt,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Report::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Report::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Report::Reporter).uuid().not_null())
+                    .col(ColumnDef::new(Report::Target).uuid().not_null())
+                    .col(ColumnDef::new(Report::Reason).string().not_null())
+                    .col(ColumnDef::new(Report::Game).uuid())
+                    .col(ColumnDef::new(Report::Message).text())
+                    .col(
+                        ColumnDef::new(Report::Resolved)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(Report::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Report::Table, Report::Reporter)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Report::Table, Report::Target)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Report::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Report {
+    Table,
+    Id,
+    Reporter,
+    Target,
+    Reason,
+    Game,
+    Message,
+    Resolved,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
@@ -0,0 +1,201 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Tournament::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Tournament::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Tournament::Name).string().not_null())
+                    .col(ColumnDef::new(Tournament::Host).uuid().not_null())
+                    .col(ColumnDef::new(Tournament::Size).integer().not_null())
+                    .col(ColumnDef::new(Tournament::Status).string().not_null())
+                    .col(
+                        ColumnDef::new(Tournament::Settings)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'{}'::jsonb")),
+                    )
+                    .col(ColumnDef::new(Tournament::Winner).uuid())
+                    .col(
+                        ColumnDef::new(Tournament::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Tournament::Table, Tournament::Host)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Tournament::Table, Tournament::Winner)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(TournamentEntrant::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TournamentEntrant::Tournament)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TournamentEntrant::Member).uuid().not_null())
+                    .col(
+                        ColumnDef::new(TournamentEntrant::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(TournamentEntrant::Tournament)
+                            .col(TournamentEntrant::Member),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(TournamentEntrant::Table, TournamentEntrant::Tournament)
+                            .to(Tournament::Table, Tournament::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(TournamentEntrant::Table, TournamentEntrant::Member)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(TournamentRound::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TournamentRound::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentRound::Tournament)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TournamentRound::Round).integer().not_null())
+                    .col(ColumnDef::new(TournamentRound::Slot).integer().not_null())
+                    .col(ColumnDef::new(TournamentRound::Black).uuid().not_null())
+                    // A pairing without a second player is a bye.
+                    .col(ColumnDef::new(TournamentRound::White).uuid())
+                    .col(ColumnDef::new(TournamentRound::Game).uuid())
+                    .col(ColumnDef::new(TournamentRound::Winner).uuid())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(TournamentRound::Table, TournamentRound::Tournament)
+                            .to(Tournament::Table, Tournament::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-tournament-round-slot")
+                    .table(TournamentRound::Table)
+                    .col(TournamentRound::Tournament)
+                    .col(TournamentRound::Round)
+                    .col(TournamentRound::Slot)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        // Games look up the pairing they were played for when they end.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-tournament-round-game")
+                    .table(TournamentRound::Table)
+                    .col(TournamentRound::Game)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TournamentRound::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(TournamentEntrant::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Tournament::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Tournament {
+    Table,
+    Id,
+    Name,
+    Host,
+    Size,
+    Status,
+    Settings,
+    Winner,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum TournamentEntrant {
+    Table,
+    Tournament,
+    Member,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum TournamentRound {
+    Table,
+    Id,
+    Tournament,
+    Round,
+    Slot,
+    Black,
+    White,
+    Game,
+    Winner,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
@@ -0,0 +1,99 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Existing tournaments are all single elimination, which decides its own rounds.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Tournament::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Tournament::Format)
+                            .string()
+                            .not_null()
+                            .default("single_elimination"),
+                    )
+                    .add_column_if_not_exists(ColumnDef::new(Tournament::Rounds).integer())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TournamentRound::Table)
+                    .add_column_if_not_exists(ColumnDef::new(TournamentRound::Result).string())
+                    .add_column_if_not_exists(ColumnDef::new(TournamentRound::BlackDiscs).integer())
+                    .add_column_if_not_exists(ColumnDef::new(TournamentRound::WhiteDiscs).integer())
+                    .to_owned(),
+            )
+            .await?;
+        // Pairings that already have a winner were decided before results were recorded.
+        for (result, condition) in [
+            ("bye", Expr::col(TournamentRound::White).is_null()),
+            (
+                "black",
+                Expr::col(TournamentRound::Winner).equals(TournamentRound::Black),
+            ),
+            (
+                "white",
+                Expr::col(TournamentRound::Winner).equals(TournamentRound::White),
+            ),
+        ] {
+            manager
+                .exec_stmt(
+                    Query::update()
+                        .table(TournamentRound::Table)
+                        .value(TournamentRound::Result, result)
+                        .and_where(condition)
+                        .and_where(Expr::col(TournamentRound::Result).is_null())
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TournamentRound::Table)
+                    .drop_column(TournamentRound::Result)
+                    .drop_column(TournamentRound::BlackDiscs)
+                    .drop_column(TournamentRound::WhiteDiscs)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Tournament::Table)
+                    .drop_column(Tournament::Format)
+                    .drop_column(Tournament::Rounds)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Tournament {
+    Table,
+    Format,
+    Rounds,
+}
+
+#[derive(DeriveIden)]
+enum TournamentRound {
+    Table,
+    Black,
+    White,
+    Winner,
+    Result,
+    BlackDiscs,
+    WhiteDiscs,
+}
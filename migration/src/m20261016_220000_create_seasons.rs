@@ -0,0 +1,155 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Season::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Season::Id).uuid().not_null().primary_key())
+                    .col(
+                        ColumnDef::new(Season::Number)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Season::StartsAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Season::EndsAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Season::Archived)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(SeasonRating::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(SeasonRating::Season).uuid().not_null())
+                    .col(ColumnDef::new(SeasonRating::Member).uuid().not_null())
+                    .col(ColumnDef::new(SeasonRating::Rating).integer().not_null())
+                    .col(ColumnDef::new(SeasonRating::Peak).integer().not_null())
+                    .col(
+                        ColumnDef::new(SeasonRating::Games)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(SeasonRating::Wins)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(SeasonRating::Losses)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(SeasonRating::Draws)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(SeasonRating::LastPlayedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    // Only set once the season is archived.
+                    .col(ColumnDef::new(SeasonRating::Tier).string())
+                    .primary_key(
+                        Index::create()
+                            .col(SeasonRating::Season)
+                            .col(SeasonRating::Member),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(SeasonRating::Table, SeasonRating::Season)
+                            .to(Season::Table, Season::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(SeasonRating::Table, SeasonRating::Member)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Standings are sorted by rating within a season.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-season-rating-rating")
+                    .table(SeasonRating::Table)
+                    .col(SeasonRating::Season)
+                    .col(SeasonRating::Rating)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SeasonRating::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Season::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Season {
+    Table,
+    Id,
+    Number,
+    StartsAt,
+    EndsAt,
+    Archived,
+}
+
+#[derive(DeriveIden)]
+enum SeasonRating {
+    Table,
+    Season,
+    Member,
+    Rating,
+    Peak,
+    Games,
+    Wins,
+    Losses,
+    Draws,
+    LastPlayedAt,
+    Tier,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
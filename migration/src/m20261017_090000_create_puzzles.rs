@@ -0,0 +1,184 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Puzzle::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Puzzle::Id).uuid().not_null().primary_key())
+                    // The moves leading to the puzzle's position from the starting one.
+                    .col(ColumnDef::new(Puzzle::Moves).json_binary().not_null())
+                    .col(ColumnDef::new(Puzzle::Solutions).json_binary().not_null())
+                    .col(ColumnDef::new(Puzzle::LastShown).date())
+                    .col(
+                        ColumnDef::new(Puzzle::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(DailyPuzzle::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(DailyPuzzle::Day)
+                            .date()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(DailyPuzzle::Puzzle).uuid().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(DailyPuzzle::Table, DailyPuzzle::Puzzle)
+                            .to(Puzzle::Table, Puzzle::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(PuzzleAttempt::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(PuzzleAttempt::Member).uuid().not_null())
+                    .col(ColumnDef::new(PuzzleAttempt::Day).date().not_null())
+                    .col(ColumnDef::new(PuzzleAttempt::Puzzle).uuid().not_null())
+                    .col(ColumnDef::new(PuzzleAttempt::X).integer().not_null())
+                    .col(ColumnDef::new(PuzzleAttempt::Y).integer().not_null())
+                    .col(ColumnDef::new(PuzzleAttempt::Solved).boolean().not_null())
+                    .col(
+                        ColumnDef::new(PuzzleAttempt::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    // Only the first answer to each day's puzzle counts.
+                    .primary_key(
+                        Index::create()
+                            .col(PuzzleAttempt::Member)
+                            .col(PuzzleAttempt::Day),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PuzzleAttempt::Table, PuzzleAttempt::Member)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PuzzleAttempt::Table, PuzzleAttempt::Puzzle)
+                            .to(Puzzle::Table, Puzzle::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(PuzzleStreak::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PuzzleStreak::Member)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PuzzleStreak::Current)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(PuzzleStreak::Best)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(PuzzleStreak::LastSolved).date())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PuzzleStreak::Table, PuzzleStreak::Member)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PuzzleStreak::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PuzzleAttempt::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(DailyPuzzle::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Puzzle::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Puzzle {
+    Table,
+    Id,
+    Moves,
+    Solutions,
+    LastShown,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum DailyPuzzle {
+    Table,
+    Day,
+    Puzzle,
+}
+
+#[derive(DeriveIden)]
+enum PuzzleAttempt {
+    Table,
+    Member,
+    Day,
+    Puzzle,
+    X,
+    Y,
+    Solved,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum PuzzleStreak {
+    Table,
+    Member,
+    Current,
+    Best,
+    LastSolved,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
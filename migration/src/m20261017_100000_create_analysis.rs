@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Analysis::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Analysis::Game)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Analysis::Moves).json_binary().not_null())
+                    .col(
+                        ColumnDef::new(Analysis::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Analysis::Table, Analysis::Game)
+                            .to(Game::Table, Game::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Analysis::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Analysis {
+    Table,
+    Game,
+    Moves,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Game {
+    Table,
+    Id,
+}
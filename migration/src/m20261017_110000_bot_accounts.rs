@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Member::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Member::Bot)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column_if_not_exists(ColumnDef::new(Member::Owner).uuid().null())
+                    .add_column_if_not_exists(ColumnDef::new(Member::ApiToken).string().null())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk-member-owner")
+                            .from_tbl(Member::Table)
+                            .from_col(Member::Owner)
+                            .to_tbl(Member::Table)
+                            .to_col(Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-member-api-token")
+                    .table(Member::Table)
+                    .col(Member::ApiToken)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-member-api-token")
+                    .table(Member::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Member::Table)
+                    .drop_foreign_key(Alias::new("fk-member-owner"))
+                    .drop_column(Member::ApiToken)
+                    .drop_column(Member::Owner)
+                    .drop_column(Member::Bot)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+    Bot,
+    Owner,
+    ApiToken,
+}
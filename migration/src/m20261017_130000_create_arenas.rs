@@ -0,0 +1,109 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Arena::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Arena::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Arena::First).uuid().not_null())
+                    .col(ColumnDef::new(Arena::Second).uuid().not_null())
+                    .col(ColumnDef::new(Arena::Games).integer().not_null())
+                    .col(ColumnDef::new(Arena::CreatedBy).uuid())
+                    .col(
+                        ColumnDef::new(Arena::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Arena::Table, Arena::First)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Arena::Table, Arena::Second)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Arena::Table, Arena::CreatedBy)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Game::Arena).uuid().null())
+                    .add_column_if_not_exists(ColumnDef::new(Game::HostOpponent).string().null())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk-game-arena")
+                            .from_tbl(Game::Table)
+                            .from_col(Game::Arena)
+                            .to_tbl(Arena::Table)
+                            .to_col(Arena::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_foreign_key(Alias::new("fk-game-arena"))
+                    .drop_column(Game::HostOpponent)
+                    .drop_column(Game::Arena)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Arena::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Arena {
+    Table,
+    Id,
+    First,
+    Second,
+    Games,
+    CreatedBy,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Game {
+    Table,
+    Arena,
+    HostOpponent,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
@@ -0,0 +1,179 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Every finished game, whether it's still in `game` or has been archived, for reading
+/// histories and records without caring where a game is kept.
+const CREATE_FINISHED_GAME_VIEW: &str = r"
+CREATE VIEW finished_game AS
+    SELECT id, host, guest, pending, ended, challenge, result, settings, state,
+        turn_started_at, opponent, arena, host_opponent, ended_at
+    FROM game
+    WHERE ended
+UNION ALL
+    SELECT id, host, guest, FALSE, TRUE, challenge, result, settings, state,
+        NULL, opponent, arena, host_opponent, ended_at
+    FROM archived_game
+";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Game::EndedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Games that ended before it was recorded are counted from now.
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Game::Table)
+                    .value(Game::EndedAt, Expr::current_timestamp())
+                    .and_where(Expr::col(Game::Ended).eq(true))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(ArchivedGame::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ArchivedGame::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ArchivedGame::Host).string().not_null())
+                    .col(ColumnDef::new(ArchivedGame::Guest).string().not_null())
+                    .col(ColumnDef::new(ArchivedGame::Challenge).uuid().null())
+                    .col(ColumnDef::new(ArchivedGame::Result).json_binary().null())
+                    .col(
+                        ColumnDef::new(ArchivedGame::Settings)
+                            .json_binary()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ArchivedGame::State).json_binary().null())
+                    .col(ColumnDef::new(ArchivedGame::Opponent).string().null())
+                    .col(ColumnDef::new(ArchivedGame::Arena).uuid().null())
+                    .col(ColumnDef::new(ArchivedGame::HostOpponent).string().null())
+                    .col(
+                        ColumnDef::new(ArchivedGame::EndedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(ArchivedGame::ArchivedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Histories are looked up by player.
+        for (name, column) in [
+            ("idx-archived-game-host", ArchivedGame::Host),
+            ("idx-archived-game-guest", ArchivedGame::Guest),
+        ] {
+            manager
+                .create_index(
+                    Index::create()
+                        .name(name)
+                        .table(ArchivedGame::Table)
+                        .col(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        // Analyses outlive their games' rows, since archiving moves the games elsewhere.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Analysis::Table)
+                    .drop_foreign_key(Alias::new("analysis_game_fkey"))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .get_connection()
+            .execute_unprepared(CREATE_FINISHED_GAME_VIEW)
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP VIEW IF EXISTS finished_game")
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ArchivedGame::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Analysis::Table)
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("analysis_game_fkey")
+                            .from_tbl(Analysis::Table)
+                            .from_col(Analysis::Game)
+                            .to_tbl(Game::Table)
+                            .to_col(Game::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(Game::EndedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Game {
+    Table,
+    Id,
+    Ended,
+    EndedAt,
+}
+
+#[derive(DeriveIden)]
+enum ArchivedGame {
+    Table,
+    Id,
+    Host,
+    Guest,
+    Challenge,
+    Result,
+    Settings,
+    State,
+    Opponent,
+    Arena,
+    HostOpponent,
+    EndedAt,
+    ArchivedAt,
+}
+
+#[derive(DeriveIden)]
+enum Analysis {
+    Table,
+    Game,
+}
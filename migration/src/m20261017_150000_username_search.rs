@@ -0,0 +1,31 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Trigram matching, for finding usernames that are close to what was typed.
+const CREATE_TRIGRAM_EXTENSION: &str = "CREATE EXTENSION IF NOT EXISTS pg_trgm";
+/// Usernames are searched without regard to case, both by prefix and by similarity, which
+/// this index serves both of.
+const CREATE_USERNAME_SEARCH_INDEX: &str = r#"
+CREATE INDEX IF NOT EXISTS "idx-member-username-search"
+    ON member USING gin (lower(username) gin_trgm_ops)
+"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared(CREATE_TRIGRAM_EXTENSION).await?;
+        db.execute_unprepared(CREATE_USERNAME_SEARCH_INDEX).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(r#"DROP INDEX IF EXISTS "idx-member-username-search""#)
+            .await?;
+        Ok(())
+    }
+}
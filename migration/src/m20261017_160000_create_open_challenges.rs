@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OpenChallenge::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OpenChallenge::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(OpenChallenge::Host).uuid().not_null())
+                    .col(
+                        ColumnDef::new(OpenChallenge::Settings)
+                            .json_binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OpenChallenge::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(OpenChallenge::Table, OpenChallenge::Host)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Each player can only have one challenge open at a time.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-open-challenge-host")
+                    .table(OpenChallenge::Table)
+                    .col(OpenChallenge::Host)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OpenChallenge::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OpenChallenge {
+    Table,
+    Id,
+    Host,
+    Settings,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
@@ -0,0 +1,122 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// `finished_game` as it was, before games had a mode.
+const CREATE_FINISHED_GAME_VIEW: &str = r"
+CREATE VIEW finished_game AS
+    SELECT id, host, guest, pending, ended, challenge, result, settings, state,
+        turn_started_at, opponent, arena, host_opponent, ended_at
+    FROM game
+    WHERE ended
+UNION ALL
+    SELECT id, host, guest, FALSE, TRUE, challenge, result, settings, state,
+        NULL, opponent, arena, host_opponent, ended_at
+    FROM archived_game
+";
+
+/// `finished_game` with the mode each game was played in.
+const CREATE_FINISHED_GAME_VIEW_WITH_MODE: &str = r"
+CREATE VIEW finished_game AS
+    SELECT id, host, guest, pending, ended, challenge, result, settings, state,
+        turn_started_at, opponent, arena, host_opponent, ended_at, mode
+    FROM game
+    WHERE ended
+UNION ALL
+    SELECT id, host, guest, FALSE, TRUE, challenge, result, settings, state,
+        NULL, opponent, arena, host_opponent, ended_at, mode
+    FROM archived_game
+";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Every game so far has been played in one sitting.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Game::Mode)
+                            .string()
+                            .not_null()
+                            .default("live"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ArchivedGame::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ArchivedGame::Mode)
+                            .string()
+                            .not_null()
+                            .default("live"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // The scheduler looks for correspondence games with deadlines coming up.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-game-mode")
+                    .table(Game::Table)
+                    .col(Game::Mode)
+                    .to_owned(),
+            )
+            .await?;
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP VIEW IF EXISTS finished_game")
+            .await?;
+        db.execute_unprepared(CREATE_FINISHED_GAME_VIEW_WITH_MODE)
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP VIEW IF EXISTS finished_game")
+            .await?;
+        db.execute_unprepared(CREATE_FINISHED_GAME_VIEW).await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-game-mode")
+                    .table(Game::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ArchivedGame::Table)
+                    .drop_column(ArchivedGame::Mode)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(Game::Mode)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Game {
+    Table,
+    Mode,
+}
+
+#[derive(DeriveIden)]
+enum ArchivedGame {
+    Table,
+    Mode,
+}
@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Moves outlive their games' rows, which are moved elsewhere when they're archived, so
+        // they aren't tied to them.
+        manager
+            .create_table(
+                Table::create()
+                    .table(GameMove::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(GameMove::Game).uuid().not_null())
+                    .col(ColumnDef::new(GameMove::Ply).integer().not_null())
+                    .col(ColumnDef::new(GameMove::Player).uuid().not_null())
+                    .col(
+                        ColumnDef::new(GameMove::PlayedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(GameMove::TimeTaken).big_integer().not_null())
+                    .primary_key(Index::create().col(GameMove::Game).col(GameMove::Ply))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(GameMove::Table, GameMove::Player)
+                            .to(Member::Table, Member::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Players' stats average the time they take over all of their moves.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-game-move-player")
+                    .table(GameMove::Table)
+                    .col(GameMove::Player)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GameMove::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum GameMove {
+    Table,
+    Game,
+    Ply,
+    Player,
+    PlayedAt,
+    TimeTaken,
+}
+
+#[derive(DeriveIden)]
+enum Member {
+    Table,
+    Id,
+}
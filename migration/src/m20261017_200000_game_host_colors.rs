@@ -0,0 +1,105 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// `finished_game` as it was, before games recorded the colour their host played.
+const CREATE_FINISHED_GAME_VIEW: &str = r"
+CREATE VIEW finished_game AS
+    SELECT id, host, guest, pending, ended, challenge, result, settings, state,
+        turn_started_at, opponent, arena, host_opponent, ended_at, mode
+    FROM game
+    WHERE ended
+UNION ALL
+    SELECT id, host, guest, FALSE, TRUE, challenge, result, settings, state,
+        NULL, opponent, arena, host_opponent, ended_at, mode
+    FROM archived_game
+";
+
+/// `finished_game` with the colour each game's host played.
+const CREATE_FINISHED_GAME_VIEW_WITH_HOST_COLOR: &str = r"
+CREATE VIEW finished_game AS
+    SELECT id, host, guest, pending, ended, challenge, result, settings, state,
+        turn_started_at, opponent, arena, host_opponent, ended_at, mode, host_color
+    FROM game
+    WHERE ended
+UNION ALL
+    SELECT id, host, guest, FALSE, TRUE, challenge, result, settings, state,
+        NULL, opponent, arena, host_opponent, ended_at, mode, host_color
+    FROM archived_game
+";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Until now, whoever played black was stored as the host, so every game so far had
+        // its host playing black.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Game::HostColor)
+                            .string()
+                            .not_null()
+                            .default("black"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ArchivedGame::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ArchivedGame::HostColor)
+                            .string()
+                            .not_null()
+                            .default("black"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP VIEW IF EXISTS finished_game")
+            .await?;
+        db.execute_unprepared(CREATE_FINISHED_GAME_VIEW_WITH_HOST_COLOR)
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP VIEW IF EXISTS finished_game")
+            .await?;
+        db.execute_unprepared(CREATE_FINISHED_GAME_VIEW).await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ArchivedGame::Table)
+                    .drop_column(ArchivedGame::HostColor)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(Game::HostColor)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Game {
+    Table,
+    HostColor,
+}
+
+#[derive(DeriveIden)]
+enum ArchivedGame {
+    Table,
+    HostColor,
+}
@@ -3,6 +3,7 @@
   lib,
   stdenv,
   sea-orm-cli,
+  wasm-pack,
   nodejs-18_x,
   nodePackages,
   postgresql,
@@ -10,6 +11,7 @@
 }: let
   rust = pkgs.rust-bin.stable.latest.default.override {
     extensions = ["rust-src"];
+    targets = ["wasm32-unknown-unknown"];
   };
 in
   pkgs.mkShell {
@@ -18,6 +20,7 @@ in
         # Rust
         rust
         sea-orm-cli
+        wasm-pack
         # Node
         nodejs-18_x
         nodePackages.npm
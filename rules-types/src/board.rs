@@ -0,0 +1,150 @@
+//! How boards are written in the API and stored: as two bitboards, one for each side, packed
+//! into 16 bytes and sent as base64 (e.g. `AAAACBAAAAAAAAAQCAAAAA==` for the starting
+//! position).
+//!
+//! Square `(x, y)` is bit `x + 8 * y` of its side's bitboard, counting from the least
+//! significant bit. Black's bitboard comes first, and each is written most significant byte
+//! first.
+//!
+//! Boards used to be written as arrays of 64 squares, each `"Black"`, `"White"` or `null`, and
+//! are still read in that form.
+
+use crate::Piece;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+/// The number of squares on a standard board, which is as many as a bitboard has bits.
+pub const SQUARES: usize = 64;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("boards have {SQUARES} squares, not {0}")]
+    Length(usize),
+    #[error("board square ({0}, {1}) holds both pieces")]
+    Overlap(usize, usize),
+    #[error("boards are 16 bytes of base64")]
+    Encoding,
+}
+
+/// Pack a board into its two bitboards.
+/// # Errors
+/// Returns an error if the board doesn't have 64 squares.
+pub fn to_bytes(squares: &[Option<Piece>]) -> Result<[u8; 16], Error> {
+    if squares.len() != SQUARES {
+        return Err(Error::Length(squares.len()));
+    }
+    let (mut black, mut white) = (0u64, 0u64);
+    for (i, piece) in squares.iter().enumerate() {
+        match piece {
+            Some(Piece::Black) => black |= 1 << i,
+            Some(Piece::White) => white |= 1 << i,
+            None => {}
+        }
+    }
+    Ok((u128::from(black) << 64 | u128::from(white)).to_be_bytes())
+}
+
+/// Unpack a board from its two bitboards.
+/// # Errors
+/// Returns an error if a square is set in both bitboards.
+pub fn from_bytes(bytes: [u8; 16]) -> Result<Vec<Option<Piece>>, Error> {
+    let bits = u128::from_be_bytes(bytes);
+    #[allow(clippy::cast_possible_truncation)] // Each half is 64 bits
+    let (black, white) = ((bits >> 64) as u64, bits as u64);
+    if black & white != 0 {
+        let i = (black & white).trailing_zeros() as usize;
+        return Err(Error::Overlap(i % 8, i / 8));
+    }
+    Ok((0..SQUARES)
+        .map(|i| match (black >> i & 1, white >> i & 1) {
+            (1, _) => Some(Piece::Black),
+            (_, 1) => Some(Piece::White),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Write a board's bitboards in base64, as boards are sent.
+#[must_use]
+pub fn encode(bytes: [u8; 16]) -> String {
+    STANDARD.encode(bytes)
+}
+
+/// Read a board's bitboards from base64.
+/// # Errors
+/// Returns an error if the board isn't 16 bytes of base64.
+pub fn decode(board: &str) -> Result<[u8; 16], Error> {
+    let bytes = STANDARD.decode(board).map_err(|_| Error::Encoding)?;
+    bytes.try_into().map_err(|_| Error::Encoding)
+}
+
+/// A board as it's written, in either form.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Written {
+    Compact(String),
+    Squares(Vec<Option<Piece>>),
+}
+
+/// Serialize a board in its compact form, for fields holding one.
+/// # Errors
+/// Returns an error if the board doesn't have 64 squares, or the serializer fails.
+pub fn serialize<S: Serializer>(
+    squares: &[Option<Piece>],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let bytes = to_bytes(squares).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&encode(bytes))
+}
+
+/// Deserialize a board written either in its compact form or as an array of squares.
+/// # Errors
+/// Returns an error if the board is in neither form, or its compact form is invalid.
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Option<Piece>>, D::Error> {
+    match Written::deserialize(deserializer)? {
+        Written::Compact(board) => decode(&board)
+            .and_then(from_bytes)
+            .map_err(de::Error::custom),
+        Written::Squares(squares) => Ok(squares),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, from_bytes, to_bytes, Error, SQUARES};
+    use crate::Piece;
+
+    fn start() -> Vec<Option<Piece>> {
+        let mut squares = vec![None; SQUARES];
+        squares[3 + 3 * 8] = Some(Piece::White);
+        squares[4 + 3 * 8] = Some(Piece::Black);
+        squares[3 + 4 * 8] = Some(Piece::Black);
+        squares[4 + 4 * 8] = Some(Piece::White);
+        squares
+    }
+
+    #[test]
+    fn round_trip() {
+        let board = encode(to_bytes(&start()).unwrap());
+        assert_eq!(board, "AAAACBAAAAAAAAAQCAAAAA==");
+        assert_eq!(decode(&board).and_then(from_bytes), Ok(start()));
+        let mut corners = start();
+        corners[0] = Some(Piece::Black);
+        corners[63] = Some(Piece::White);
+        assert_eq!(from_bytes(to_bytes(&corners).unwrap()), Ok(corners));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(to_bytes(&[None; 36]), Err(Error::Length(36)));
+        assert_eq!(decode("not base64!"), Err(Error::Encoding));
+        assert_eq!(decode("AAAA"), Err(Error::Encoding));
+        let mut bytes = to_bytes(&start()).unwrap();
+        // Both pieces on f4.
+        bytes[4] |= 0x20;
+        bytes[12] |= 0x20;
+        assert_eq!(from_bytes(bytes), Err(Error::Overlap(5, 3)));
+    }
+}
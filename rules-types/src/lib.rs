@@ -0,0 +1,47 @@
+//! The few types both the rules and the API need: the pieces, the settings a game is played
+//! with and a game's position as it's sent and stored. The core crate plays games with them and
+//! `othello-api-types` sends them, re-exporting everything here, so neither has to depend on
+//! the other.
+
+pub mod board;
+mod settings;
+
+pub use settings::{
+    ColorPolicy, Correspondence, GameSettings, SettingsError, TimeControl, Variant,
+    MAX_DAYS_PER_MOVE, MAX_HANDICAP,
+};
+
+use serde::{Deserialize, Serialize};
+use std::ops::Not;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Piece {
+    Black,
+    White,
+}
+
+impl Not for Piece {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::Black => Self::White,
+            Self::White => Self::Black,
+        }
+    }
+}
+
+/// A game's position as it's sent over the wire: the squares from the top left, row by row
+/// (written compactly, as [`board`](crate::board) describes), the side to move and the moves
+/// played so far. Clients that know the rules can read it
+/// straight into the core crate's `Game`, which has the same shape.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    #[serde(with = "crate::board")]
+    pub board: Vec<Option<Piece>>,
+    pub turn: Piece,
+    pub history: Vec<(usize, usize)>,
+    /// Games cached before settings existed were all played with the defaults.
+    #[serde(default)]
+    pub settings: GameSettings,
+}
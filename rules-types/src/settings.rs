@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+/// The number of squares along each side of the board. Only the standard board can be played
+/// on so far.
+const BOARD_SIZE: usize = 8;
+/// The most corners black can be given, which is all of them.
+pub const MAX_HANDICAP: u8 = 4;
+/// The most days a correspondence game can give each player for a move.
+pub const MAX_DAYS_PER_MOVE: u8 = 14;
+
+/// The rules a game is played under.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// Players can only see the squares holding or next to their own pieces.
+    Fog,
+}
+
+/// How much time each player has to make their moves.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeControl {
+    /// The time (in seconds) each player starts with.
+    pub initial: u32,
+    /// The time (in seconds) added to a player's clock after each of their moves.
+    pub increment: u32,
+}
+
+/// The deadline on each move of a correspondence game, which is played over days rather than
+/// in one sitting. Players who miss it forfeit the game.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Correspondence {
+    /// The days each player has to make each of their moves.
+    pub days_per_move: u8,
+}
+
+/// Which player plays black. The choice is made once the game starts, when the guest accepts.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorPolicy {
+    #[default]
+    HostBlack,
+    GuestBlack,
+    Random,
+}
+
+/// Everything that can be configured about a game before it starts. Missing fields take their
+/// default values, which describe the standard game the server has always played.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameSettings {
+    pub variant: Variant,
+    /// The number of squares along each side of the board.
+    pub board_size: usize,
+    /// The clock the game is played with, or `None` for an untimed game.
+    pub time_control: Option<TimeControl>,
+    /// The deadline on each move, or `None` for a game played in one sitting.
+    pub correspondence: Option<Correspondence>,
+    /// Whether the result counts towards the players' ratings.
+    pub rated: bool,
+    /// The number of corners given to black before the game starts: a1, then h8, h1 and a8.
+    pub handicap: u8,
+    pub color_policy: ColorPolicy,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum SettingsError {
+    #[error("boards must be {0} squares wide")]
+    BoardSize(usize),
+    #[error("{0} games are not supported yet")]
+    Unsupported(&'static str),
+    #[error("handicaps are at most {0} corners")]
+    Handicap(u8),
+    #[error("correspondence games give between 1 and {0} days per move")]
+    DaysPerMove(u8),
+}
+
+impl GameSettings {
+    /// Check that a game can be played with these settings.
+    /// # Errors
+    /// Returns the first of the settings that isn't supported.
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        match self.errors().into_iter().next() {
+            Some((_, e)) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Everything stopping a game from being played with these settings, along with the name
+    /// of the setting each error is about.
+    #[must_use]
+    pub fn errors(&self) -> Vec<(&'static str, SettingsError)> {
+        let mut errors = vec![];
+        if self.board_size != BOARD_SIZE {
+            errors.push(("board_size", SettingsError::BoardSize(BOARD_SIZE)));
+        }
+        if self.time_control.is_some() {
+            errors.push(("time_control", SettingsError::Unsupported("timed")));
+        }
+        if self.handicap > MAX_HANDICAP {
+            errors.push(("handicap", SettingsError::Handicap(MAX_HANDICAP)));
+        }
+        if let Some(Correspondence { days_per_move }) = self.correspondence {
+            if !(1..=MAX_DAYS_PER_MOVE).contains(&days_per_move) {
+                errors.push((
+                    "correspondence",
+                    SettingsError::DaysPerMove(MAX_DAYS_PER_MOVE),
+                ));
+            }
+        }
+        errors
+    }
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            variant: Variant::Standard,
+            board_size: BOARD_SIZE,
+            time_control: None,
+            correspondence: None,
+            rated: false,
+            handicap: 0,
+            color_policy: ColorPolicy::HostBlack,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorPolicy, Correspondence, GameSettings, SettingsError};
+
+    #[test]
+    fn validate() {
+        // Settings only need to mention what differs from the standard game.
+        let settings: GameSettings = serde_json::from_str(r#"{"rated":false}"#).unwrap();
+        assert_eq!(settings, GameSettings::default());
+        assert!(settings.validate().is_ok());
+        let settings: GameSettings = serde_json::from_str(r#"{"board_size":10}"#).unwrap();
+        assert_eq!(settings.validate(), Err(SettingsError::BoardSize(8)));
+        let settings: GameSettings =
+            serde_json::from_str(r#"{"color_policy":"random","handicap":2}"#).unwrap();
+        assert_eq!(settings.color_policy, ColorPolicy::Random);
+        assert!(settings.validate().is_ok());
+        // Every unsupported setting is reported, not just the first.
+        let settings: GameSettings =
+            serde_json::from_str(r#"{"board_size":10,"handicap":5}"#).unwrap();
+        let fields: Vec<_> = settings.errors().iter().map(|&(field, _)| field).collect();
+        assert_eq!(fields, ["board_size", "handicap"]);
+        let settings: GameSettings =
+            serde_json::from_str(r#"{"correspondence":{"days_per_move":3}}"#).unwrap();
+        assert_eq!(
+            settings.correspondence,
+            Some(Correspondence { days_per_move: 3 })
+        );
+        assert!(settings.validate().is_ok());
+        let settings: GameSettings =
+            serde_json::from_str(r#"{"correspondence":{"days_per_move":0}}"#).unwrap();
+        assert_eq!(settings.validate(), Err(SettingsError::DaysPerMove(14)));
+        let settings: GameSettings = serde_json::from_str(r#"{"rated":true}"#).unwrap();
+        assert!(settings.validate().is_ok());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{
+    api::gateway::{
+        ClientMessage, Event, Opcode, Packet, ServerMessage, Snapshots, PROTOCOL_VERSION,
+    },
+    Error, Game, Piece,
+};
+use futures::{SinkExt, StreamExt};
+use reqwest::Url;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+/// A game joined over the gateway, with its own connection. The handle keeps track of the
+/// game's latest position as events arrive.
+pub struct GameHandle {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    token: String,
+    id: String,
+    piece: Option<Piece>,
+    game: Game,
+}
+
+impl GameHandle {
+    /// Identify on the gateway and join the specified game, waiting for its position.
+    pub(crate) async fn join(
+        gateway: Url,
+        token: String,
+        id: String,
+        piece: Option<Piece>,
+    ) -> Result<Self, Error> {
+        let (socket, _) = connect_async(gateway.as_str()).await?;
+        let mut handle = Self {
+            socket,
+            token,
+            id,
+            piece,
+            game: Game::new(),
+        };
+        handle
+            .send(
+                Opcode::Identify,
+                ClientMessage::Identify {
+                    version: Some(PROTOCOL_VERSION),
+                    resume: None,
+                    // The handle keeps whole boards only.
+                    snapshots: Snapshots::Every,
+                },
+            )
+            .await?;
+        let id = handle.id.clone();
+        handle
+            .send(Opcode::Join, ClientMessage::Join { id, since: None })
+            .await?;
+        // Errors before the game's position arrives mean it couldn't be joined.
+        loop {
+            match handle.next_event().await.ok_or(Error::Closed)?? {
+                ServerMessage::GameUpdate { .. } => return Ok(handle),
+                ServerMessage::Error(e) => return Err(Error::Api(e)),
+                _ => {}
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The side the user plays, or `None` if they're only watching.
+    #[must_use]
+    pub fn piece(&self) -> Option<Piece> {
+        self.piece
+    }
+
+    /// The game as of the latest update.
+    #[must_use]
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Play the specified square (counted from the top left, from 0). The server answers with
+    /// an update, or an error if the move isn't legal.
+    /// # Errors
+    /// Returns an error if the user is only watching or the connection has dropped.
+    pub async fn place(&mut self, x: usize, y: usize) -> Result<(), Error> {
+        let piece = self.piece.ok_or(Error::Spectating)?;
+        let id = self.id.clone();
+        self.send(
+            Opcode::Place,
+            ClientMessage::Place {
+                id,
+                x,
+                y,
+                piece,
+                key: None,
+            },
+        )
+        .await
+    }
+
+    /// Give up the game.
+    /// # Errors
+    /// Returns an error if the user is only watching or the connection has dropped.
+    pub async fn resign(&mut self) -> Result<(), Error> {
+        self.piece.ok_or(Error::Spectating)?;
+        let id = self.id.clone();
+        self.send(Opcode::Resign, ClientMessage::Resign { id })
+            .await
+    }
+
+    /// Wait for the next event from the server, or `None` once the connection closes.
+    /// Updates to the game are applied to the handle's position before they're returned.
+    /// Cancelling the wait loses nothing.
+    pub async fn next_event(&mut self) -> Option<Result<ServerMessage<Game>, Error>> {
+        loop {
+            let text = match self.socket.next().await? {
+                Ok(Message::Text(text)) => text,
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e.into())),
+            };
+            let event = match serde_json::from_str::<Event<Game>>(&text) {
+                Ok(event) => event.into_data(),
+                Err(e) => return Some(Err(e.into())),
+            };
+            if let ServerMessage::GameUpdate { game } = &event {
+                self.game = game.clone();
+            }
+            return Some(Ok(event));
+        }
+    }
+
+    async fn send(&mut self, op: Opcode, d: ClientMessage) -> Result<(), Error> {
+        let packet = Packet {
+            op,
+            d,
+            t: self.token.clone(),
+        };
+        let text = serde_json::to_string(&packet)?;
+        self.socket.send(Message::Text(text)).await?;
+        Ok(())
+    }
+}
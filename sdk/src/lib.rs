@@ -0,0 +1,292 @@
+//! A client for olly servers: typed methods for the HTTP API, and a handle for playing games
+//! over the gateway. Requests, responses and gateway messages are the same types the server
+//! speaks with, from `othello-api-types`, so the two can't drift apart.
+//!
+//! ```no_run
+//! # async fn play() -> Result<(), othello_client::Error> {
+//! let mut client = othello_client::Client::new("http://localhost:3000")?;
+//! client.login("alice", "password").await?;
+//! let game = client.create_bot_game(othello_client::Difficulty::Easy).await?;
+//! let mut handle = client.join(&game.id.to_string()).await?;
+//! handle.place(5, 4).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod game;
+
+pub use api::{Difficulty, GameDetails, GameSummary};
+pub use game::GameHandle;
+pub use olly::{Game, GameSettings, Piece};
+pub use othello_api_types as api;
+
+use api::{ApiError, BotGameRequest, Credentials, GameRequest, Response, Visibility};
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    RequestBuilder, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The cookie the server keeps the session token in. The gateway takes the same token.
+const SESSION_COOKIE_NAME: &str = "sid";
+/// The cookie the server hands out a CSRF token in, and the header it expects the token back
+/// in on requests that change anything.
+const CSRF_COOKIE_NAME: &str = "csrf";
+const CSRF_HEADER_NAME: &str = "X-CSRF-Token";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{0}")]
+    Gateway(Box<tokio_tungstenite::tungstenite::Error>),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    /// The server turned the request down.
+    #[error("{} ({})", .0.message, .0.status)]
+    Api(ApiError),
+    #[error("not logged in")]
+    LoggedOut,
+    /// Only players can act in a game; everyone else can only watch.
+    #[error("only the game's players can do that")]
+    Spectating,
+    #[error("the server closed the connection")]
+    Closed,
+    #[error("invalid server address: {0}")]
+    InvalidServer(String),
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        // Gateway errors are far bigger than the rest, so they're kept out of line.
+        Self::Gateway(Box::new(e))
+    }
+}
+
+/// The logged in user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Me {
+    pub id: String,
+    pub username: String,
+}
+
+/// A connection to a server's HTTP API, holding on to the session once logged in.
+pub struct Client {
+    inner: reqwest::Client,
+    jar: Arc<Jar>,
+    server: Url,
+    me: Option<Me>,
+}
+
+impl Client {
+    /// # Errors
+    /// Returns an error if the server's address isn't a valid HTTP(S) URL.
+    pub fn new(server: &str) -> Result<Self, Error> {
+        let server = Url::parse(server)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| Error::InvalidServer(server.to_string()))?;
+        let jar = Arc::new(Jar::default());
+        let inner = reqwest::Client::builder()
+            .cookie_provider(Arc::clone(&jar))
+            .build()?;
+        Ok(Self {
+            inner,
+            jar,
+            server,
+            me: None,
+        })
+    }
+
+    /// The logged in user, if there is one.
+    #[must_use]
+    pub fn me(&self) -> Option<&Me> {
+        self.me.as_ref()
+    }
+
+    /// Log in with the specified credentials, replacing any session already held.
+    /// # Errors
+    /// Returns an error if the credentials are wrong or the server can't be reached.
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<&Me, Error> {
+        let credentials = Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        // Logging in redirects to the new user's profile.
+        let me: Me = self
+            .send(self.inner.post(self.url("/login")).json(&credentials))
+            .await?;
+        Ok(self.me.insert(me))
+    }
+
+    /// Fetch the user's games that are underway or over, newest first.
+    /// # Errors
+    /// Returns an error if nobody is logged in or the server can't be reached.
+    pub async fn games(&self) -> Result<Vec<GameSummary>, Error> {
+        self.ensure_logged_in()?;
+        self.send(self.inner.get(self.url("/@me/games"))).await
+    }
+
+    /// Fetch one of the user's games.
+    /// # Errors
+    /// Returns an error if the game isn't one of the user's, or the server can't be reached.
+    pub async fn game(&self, id: &str) -> Result<GameDetails, Error> {
+        self.ensure_logged_in()?;
+        self.send(self.inner.get(self.url(&format!("/game/{id}"))))
+            .await
+    }
+
+    /// Invite the specified user to a game, which starts once they accept it. The user
+    /// plays black.
+    /// # Errors
+    /// Returns an error if the guest can't be invited or the server can't be reached.
+    pub async fn create_game(
+        &self,
+        guest: &str,
+        settings: GameSettings,
+    ) -> Result<GameDetails, Error> {
+        self.ensure_logged_in()?;
+        let request = GameRequest {
+            guest: Some(guest.to_string()),
+            guests: Vec::new(),
+            settings,
+            visibility: Visibility::Invite,
+        };
+        self.send(self.csrf(self.inner.post(self.url("/game"))).json(&request))
+            .await
+    }
+
+    /// Start a game against one of the server's own opponents, which needs no accepting. The
+    /// user plays black.
+    /// # Errors
+    /// Returns an error if nobody is logged in or the server can't be reached.
+    pub async fn create_bot_game(&self, difficulty: Difficulty) -> Result<GameDetails, Error> {
+        self.ensure_logged_in()?;
+        let request = BotGameRequest { difficulty };
+        self.send(
+            self.csrf(self.inner.post(self.url("/games/bot")))
+                .json(&request),
+        )
+        .await
+    }
+
+    /// Join the specified game over the gateway, to play it (or, for anyone but its players,
+    /// watch it).
+    /// # Errors
+    /// Returns an error if the game can't be joined or the gateway can't be reached.
+    pub async fn join(&self, id: &str) -> Result<GameHandle, Error> {
+        let details = self.game(id).await?;
+        let me = self.me.as_ref().map(|me| me.id.as_str());
+        let piece = if me == Some(details.host.as_str()) {
+            Some(details.host_color)
+        } else if me == Some(details.guest.as_str()) {
+            Some(!details.host_color)
+        } else {
+            None
+        };
+        GameHandle::join(self.gateway(), self.token()?, details.id.to_string(), piece).await
+    }
+
+    /// The address of the server's gateway.
+    #[must_use]
+    pub fn gateway(&self) -> Url {
+        let mut url = self.url("/live");
+        // Both schemes are valid in either case, so this can't fail.
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        let _ = url.set_scheme(scheme);
+        url
+    }
+
+    /// The session token to identify on the gateway with.
+    /// # Errors
+    /// Returns an error if nobody is logged in.
+    pub fn token(&self) -> Result<String, Error> {
+        self.cookie(SESSION_COOKIE_NAME).ok_or(Error::LoggedOut)
+    }
+
+    fn ensure_logged_in(&self) -> Result<(), Error> {
+        self.me.as_ref().map(|_| ()).ok_or(Error::LoggedOut)
+    }
+
+    fn url(&self, endpoint: &str) -> Url {
+        // Endpoints are all absolute paths, which always join.
+        self.server
+            .join(endpoint)
+            .expect("endpoint should be a valid path")
+    }
+
+    /// Fetch the value of the named cookie the server set.
+    fn cookie(&self, name: &str) -> Option<String> {
+        let header = self.jar.cookies(&self.server)?;
+        header.to_str().ok()?.split("; ").find_map(|cookie| {
+            let (key, value) = cookie.split_once('=')?;
+            (key == name).then(|| percent_decode(value))
+        })
+    }
+
+    /// Repeat the CSRF token from the cookie store in a header, as browser clients do.
+    fn csrf(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.cookie(CSRF_COOKIE_NAME) {
+            Some(token) => request.header(CSRF_HEADER_NAME, token),
+            None => request,
+        }
+    }
+
+    async fn send<T: Serialize + DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<T, Error> {
+        let res = request.send().await?;
+        if res.status().is_success() {
+            Ok(res.json::<Response<T>>().await?.message)
+        } else {
+            Err(Error::Api(res.json::<ApiError>().await?))
+        }
+    }
+}
+
+/// The server percent-encodes cookie values, so undo that to recover the raw value.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{percent_decode, Client, Error};
+
+    #[test]
+    fn gateway() {
+        let client = Client::new("http://localhost:3000").unwrap();
+        assert_eq!(client.gateway().as_str(), "ws://localhost:3000/live");
+        let client = Client::new("https://othello.example/").unwrap();
+        assert_eq!(client.gateway().as_str(), "wss://othello.example/live");
+        assert!(matches!(
+            Client::new("ftp://othello.example"),
+            Err(Error::InvalidServer(_))
+        ));
+        assert!(matches!(client.token(), Err(Error::LoggedOut)));
+    }
+
+    #[test]
+    fn cookies() {
+        assert_eq!(percent_decode("a%2Bb%2Fc%3D%3D"), "a+b/c==");
+        assert_eq!(percent_decode("plain%"), "plain%");
+    }
+}
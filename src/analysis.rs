@@ -0,0 +1,335 @@
+use crate::{board::Board, Game, GameSettings, Piece};
+use serde::{Deserialize, Serialize};
+
+/// How much holding each square is worth. Corners can never be flipped, so they're the most
+/// valuable, while the squares next to them tend to hand the corner to the opponent.
+const WEIGHTS: [[i32; 8]; 8] = [
+    [100, -20, 10, 5, 5, 10, -20, 100],
+    [-20, -50, -2, -2, -2, -2, -50, -20],
+    [10, -2, -1, -1, -1, -1, -2, 10],
+    [5, -2, -1, -1, -1, -1, -2, 5],
+    [5, -2, -1, -1, -1, -1, -2, 5],
+    [10, -2, -1, -1, -1, -1, -2, 10],
+    [-20, -50, -2, -2, -2, -2, -50, -20],
+    [100, -20, 10, 5, 5, 10, -20, 100],
+];
+/// How much each extra legal move is worth.
+const MOBILITY_WEIGHT: i32 = 5;
+/// How much each disc next to an empty square costs, since those are the discs that give the
+/// opponent moves.
+const FRONTIER_WEIGHT: i32 = 3;
+/// How much having the last move is worth.
+const PARITY_WEIGHT: i32 = 10;
+/// How few squares have to be left empty before parity counts. Until then, passes could still
+/// change who gets the last move.
+const PARITY_EMPTIES: i32 = 20;
+/// How many evaluation points it takes to turn an even game into roughly a 73% favourite.
+const SCALE: f64 = 50.0;
+
+/// The tunable parts of the evaluation, so that better values can be swapped in without
+/// rebuilding. The defaults are the built-in weights above.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Weights {
+    /// How much holding each square is worth, by row and then column.
+    pub squares: [[i32; 8]; 8],
+    /// How much each extra legal move is worth.
+    pub mobility: i32,
+    /// How much each disc next to an empty square costs.
+    pub frontier: i32,
+    /// How much having the last move is worth, once the board is nearly full.
+    pub parity: i32,
+    /// How many evaluation points it takes to turn an even game into roughly a 73% favourite.
+    pub scale: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            squares: WEIGHTS,
+            mobility: MOBILITY_WEIGHT,
+            frontier: FRONTIER_WEIGHT,
+            parity: PARITY_WEIGHT,
+            scale: SCALE,
+        }
+    }
+}
+
+/// The chances of each side winning a game. Draws count as half a win for each side, so the
+/// two always add up to one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Prediction {
+    pub black: f64,
+    pub white: f64,
+}
+
+impl Prediction {
+    /// The chance of the specified side winning.
+    #[must_use]
+    pub fn of(self, piece: Piece) -> f64 {
+        match piece {
+            Piece::Black => self.black,
+            Piece::White => self.white,
+        }
+    }
+}
+
+/// Evaluate the position of the specified game without searching ahead, combining the squares
+/// each side holds, their mobility and frontier discs, and who's due the last move. Positive
+/// scores favour black and negative scores favour white.
+#[must_use]
+pub fn evaluate(game: &Game) -> i32 {
+    evaluate_with(game, &Weights::default())
+}
+
+/// Evaluate the position of the specified game like [`evaluate`], from the specified side's
+/// point of view: positive scores favour them, whichever side they are.
+#[must_use]
+pub fn evaluate_for(game: &Game, piece: Piece) -> i32 {
+    match piece {
+        Piece::Black => evaluate(game),
+        Piece::White => -evaluate(game),
+    }
+}
+
+/// Evaluate the position of the specified game like [`evaluate`], using the specified weights.
+#[must_use]
+pub fn evaluate_with(game: &Game, weights: &Weights) -> i32 {
+    let board = game.board();
+    let mut positional = 0;
+    let mut discs = 0;
+    let mut filled = 0;
+    for ((x, y), piece) in board.iter() {
+        let sign = match piece {
+            Some(Piece::Black) => 1,
+            Some(Piece::White) => -1,
+            None => continue,
+        };
+        positional += sign * weights.squares[y][x];
+        discs += sign;
+        filled += 1;
+    }
+    let mobility = mobility(board, Piece::Black) - mobility(board, Piece::White);
+    let frontier = frontier(board, Piece::Black) - frontier(board, Piece::White);
+    // With an odd number of squares left, the side to move gets the last one.
+    let empties = 64 - filled;
+    let parity = if empties <= PARITY_EMPTIES && empties % 2 == 1 {
+        match game.turn() {
+            Piece::Black => weights.parity,
+            Piece::White => -weights.parity,
+        }
+    } else {
+        0
+    };
+    // Only the final disc count decides the game, so discs matter more the fuller the board.
+    positional + mobility * weights.mobility - frontier * weights.frontier
+        + parity
+        + discs * filled / 16
+}
+
+/// Estimate how the specified game is likely to end from its current position. Finished
+/// games are certain.
+#[must_use]
+pub fn predict_result(game: &Game) -> Prediction {
+    predict_result_with(game, &Weights::default())
+}
+
+/// Estimate how the specified game is likely to end like [`predict_result`], using the
+/// specified weights.
+#[must_use]
+pub fn predict_result_with(game: &Game, weights: &Weights) -> Prediction {
+    let mut game = game.clone();
+    let black = if game.over() {
+        let (black, white) = game.score();
+        match black.cmp(&white) {
+            std::cmp::Ordering::Greater => 1.0,
+            std::cmp::Ordering::Less => 0.0,
+            std::cmp::Ordering::Equal => 0.5,
+        }
+    } else {
+        1.0 / (1.0 + (-f64::from(evaluate_with(&game, weights)) / weights.scale).exp())
+    };
+    Prediction {
+        black,
+        white: 1.0 - black,
+    }
+}
+
+/// How a move compares to the best move the evaluation could find, by how much it lowered the
+/// mover's chance of winning.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Classification {
+    Best,
+    /// Gave up less than 5% of the mover's chances.
+    Good,
+    /// Gave up less than 10%.
+    Inaccuracy,
+    /// Gave up less than 20%.
+    Mistake,
+    Blunder,
+}
+
+impl Classification {
+    fn from_loss(loss: f64) -> Self {
+        match loss {
+            loss if loss <= 0.0 => Self::Best,
+            loss if loss < 0.05 => Self::Good,
+            loss if loss < 0.1 => Self::Inaccuracy,
+            loss if loss < 0.2 => Self::Mistake,
+            _ => Self::Blunder,
+        }
+    }
+}
+
+/// What the evaluation makes of a single move.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// The evaluation of the position after the move, with positive scores favouring black.
+    pub eval: i32,
+    /// The move that would have left the mover with the best evaluation.
+    pub best: (usize, usize),
+    pub classification: Classification,
+}
+
+/// Annotate each move of a game played from the starting position of the specified settings,
+/// looking one move ahead. Annotation stops at the first illegal move, if there is one.
+#[must_use]
+pub fn annotate(
+    settings: GameSettings,
+    history: &[(usize, usize)],
+    weights: &Weights,
+) -> Vec<Annotation> {
+    let mut game = Game::with_settings(settings);
+    let mut annotations = Vec::with_capacity(history.len());
+    for &(x, y) in history {
+        let piece = game.turn();
+        // Score every legal move from the mover's side, so that higher is always better.
+        let sign = if piece == Piece::Black { 1 } else { -1 };
+        let candidates: Vec<_> = game
+            .moves(piece)
+            .into_iter()
+            .filter_map(|(mx, my)| {
+                let mut next = game.clone();
+                next.place(mx, my, piece).ok()?;
+                Some(((mx, my), sign * evaluate_with(&next, weights)))
+            })
+            .collect();
+        let Some(&(best, best_eval)) = candidates.iter().max_by_key(|(_, eval)| *eval) else {
+            break;
+        };
+        if game.place(x, y, piece).is_err() {
+            break;
+        }
+        let eval = evaluate_with(&game, weights);
+        let chance = |eval: i32| 1.0 / (1.0 + (-f64::from(eval) / weights.scale).exp());
+        annotations.push(Annotation {
+            eval,
+            best,
+            classification: Classification::from_loss(chance(best_eval) - chance(sign * eval)),
+        });
+    }
+    annotations
+}
+
+/// The number of squares the specified piece could be placed on, whether or not it's their turn.
+fn mobility(board: &Board, piece: Piece) -> i32 {
+    let mut board = board.clone();
+    let mut moves = 0;
+    for (x, y) in board.empty_squares().collect::<Vec<_>>() {
+        if !board.flip(x, y, piece, false).is_empty() {
+            moves += 1;
+        }
+    }
+    moves
+}
+
+/// The number of the specified piece's discs next to at least one empty square.
+fn frontier(board: &Board, piece: Piece) -> i32 {
+    let width = Board::width();
+    let mut discs = 0;
+    for (x, y) in board.pieces(piece) {
+        let open = (x.saturating_sub(1)..=(x + 1).min(width - 1)).any(|nx| {
+            (y.saturating_sub(1)..=(y + 1).min(width - 1)).any(|ny| board[(nx, ny)].is_none())
+        });
+        if open {
+            discs += 1;
+        }
+    }
+    discs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{annotate, evaluate, evaluate_for, predict_result, Classification, Weights};
+    use crate::{Game, GameSettings, Piece};
+
+    #[test]
+    fn annotations() {
+        // Play out a few moves, always taking the first legal one.
+        let mut game = Game::new();
+        let mut history = vec![];
+        for _ in 0..12 {
+            let piece = game.turn();
+            let (x, y) = game.moves(piece)[0];
+            game.place(x, y, piece).unwrap();
+            history.push((x, y));
+        }
+        let settings = GameSettings::default();
+        let annotations = annotate(settings, &history, &Weights::default());
+        assert_eq!(annotations.len(), history.len());
+        for (annotation, played) in annotations.iter().zip(&history) {
+            if annotation.best == *played {
+                assert_eq!(annotation.classification, Classification::Best);
+            }
+        }
+        assert_eq!(annotations[11].eval, super::evaluate(&game));
+        // Annotation stops at the first illegal move.
+        assert_eq!(
+            annotate(settings, &[history[0], (0, 0)], &Weights::default()).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn perspective() {
+        let mut game = Game::new();
+        assert_eq!(evaluate(&game), 0);
+        game.place(2, 3, Piece::Black).unwrap();
+        assert_eq!(evaluate_for(&game, Piece::Black), evaluate(&game));
+        assert_eq!(evaluate_for(&game, Piece::White), -evaluate(&game));
+    }
+
+    #[test]
+    fn predict() {
+        // The starting position is perfectly balanced.
+        let mut game = Game::new();
+        let prediction = predict_result(&game);
+        assert!((prediction.black - 0.5).abs() < f64::EPSILON);
+        // Play out a game, with black always taking the move that flips the most pieces.
+        let mut piece = Piece::Black;
+        while !game.over() {
+            let moves = game.moves(piece);
+            let &(x, y) = if piece == Piece::Black {
+                moves
+                    .iter()
+                    .max_by_key(|&&(x, y)| game.preview(x, y, piece).unwrap().len())
+                    .unwrap()
+            } else {
+                moves.first().unwrap()
+            };
+            game.place(x, y, piece).unwrap();
+            let prediction = predict_result(&game);
+            assert!((prediction.black + prediction.white - 1.0).abs() < f64::EPSILON);
+            piece = !piece;
+        }
+        // Once the game is over, the result is certain.
+        let (black, white) = game.score();
+        let prediction = predict_result(&game);
+        assert_eq!(prediction.of(Piece::Black) > 0.5, black > white);
+        let certain = [0.0, 0.5, 1.0];
+        assert!(certain
+            .iter()
+            .any(|chance| (prediction.black - chance).abs() < f64::EPSILON));
+    }
+}
@@ -0,0 +1,26 @@
+use std::io::{self, BufRead, Write};
+
+use olly::engine::Engine;
+
+/// Speak the engine protocol over stdin and stdout until told to quit or stdin closes.
+fn main() -> io::Result<()> {
+    let mut engine = Engine::new();
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim() == "quit" {
+            break;
+        }
+        match engine.handle(&line) {
+            Ok(replies) => {
+                for reply in replies {
+                    writeln!(stdout, "{reply}")?;
+                }
+            }
+            // Errors go to stdout too, since that's the only stream most GUIs show.
+            Err(e) => writeln!(stdout, "info string error: {e}")?,
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
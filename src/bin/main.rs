@@ -1,27 +1,159 @@
-use std::sync::Arc;
+use std::{error::Error, future::IntoFuture, net::SocketAddr, sync::Arc};
 
-use olly::server::{app, restore_active_games, AppState, DEFAULT_DATABASE_URI, DEFAULT_REDIS_URI};
+use migration::{Migrator, MigratorTrait};
+use olly::server::{
+    app, init_tracing, relay, restore_active_games, AppState, Config, DiskStorage, Job,
+};
 use sea_orm::Database;
 use tokio::net::TcpListener;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Error)
-        .init();
-    // Get the database URL from the environment, or use the insecure default.
-    let database_url = std::env::var("DATABASE_URL").unwrap_or(String::from(DEFAULT_DATABASE_URI));
-    let redis_url = std::env::var("REDIS_URL").unwrap_or(String::from(DEFAULT_REDIS_URI));
-    // Connect to the database and bind a TCP listener to :3000.
-    let database = Database::connect(database_url).await.unwrap();
-    let redis = redis::Client::open(redis_url).unwrap();
-    // Ensure the connection to the database is established.
-    let _ = redis.get_connection().unwrap();
-    let state = Arc::new(AppState::new(database, redis));
+async fn main() -> Result<(), Box<dyn Error>> {
+    // Read the configuration from olly.toml (or CONFIG_FILE) and the environment, falling back
+    // to the insecure defaults for anything unset.
+    let config = Config::load()?;
+    init_tracing(&config.log_level, config.log_format);
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        None => serve(config).await,
+        Some("migrate") => migrate(&config, args.next().as_deref()).await,
+        Some(command) => Err(format!("unknown command: {command}").into()),
+    }
+}
+
+/// Bring the database schema up to date (`up`, the default), roll back the latest migration
+/// (`down`) or list the migrations yet to be applied (`status`).
+async fn migrate(config: &Config, action: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let database = Database::connect(&config.database_url).await?;
+    match action.unwrap_or("up") {
+        "up" => Migrator::up(&database, None).await?,
+        "down" => Migrator::down(&database, Some(1)).await?,
+        "status" => {
+            let pending = Migrator::get_pending_migrations(&database).await?;
+            println!("{} pending migrations", pending.len());
+            for migration in pending {
+                println!("  {}", migration.name());
+            }
+        }
+        action => return Err(format!("unknown migrate action: {action}").into()),
+    }
+    Ok(())
+}
+
+async fn serve(config: Config) -> Result<(), Box<dyn Error>> {
+    // Connect to the database and bind a TCP listener to the configured address.
+    let database = Database::connect(&config.database_url)
+        .await
+        .map_err(|e| format!("failed to connect to the database: {e}"))?;
+    // Connections to Redis are opened as they're needed.
+    let redis = redis::Client::open(config.redis_url.as_str())
+        .map_err(|e| format!("invalid Redis address: {e}"))?;
+    let mut state = AppState::new(database, redis)
+        .with_heartbeat(config.heartbeat)
+        .with_idle_timeout(config.idle_timeout)
+        // Give disconnected players this long to come back before forfeiting their games.
+        .with_grace_period(config.grace_period)
+        .with_login_limits(config.login)
+        .with_bot_limits(config.bots)
+        .with_search_workers(config.search_workers)
+        .with_network_policy(config.network)
+        .with_word_filter(config.word_filter)
+        .with_storage(DiskStorage::new(config.upload_dir))
+        .with_redis_pool(config.redis_pool)
+        .with_cors(config.cors);
+    // Share events with the other instances, so that games can be played through any of them.
+    if config.fanout {
+        state = state.with_fanout();
+    }
+    // Let players end games whose opponent has spent this long on a single turn.
+    if let Some(stall) = config.stall_timeout {
+        state = state.with_stall_timeout(stall);
+    }
+    // Clear out expired sessions as well as refusing them.
+    if let Some(ttl) = config.session_ttl {
+        state = state
+            .with_session_ttl(ttl)
+            .with_job(Job::session_cleanup(ttl));
+    }
+    // Move long-finished games out of the way of the ones being played.
+    if let Some(age) = config.archive_after {
+        state = state.with_job(Job::archival(age));
+    }
+    // Keep spectators of tournament games this far behind the players.
+    if let Some(delay) = config.spectator_delay {
+        state = state.with_spectator_delay(delay);
+    }
+    // Expire invites that nobody answers.
+    if let Some(ttl) = config.invite_ttl {
+        state = state.with_invite_ttl(ttl).with_job(Job::invite_expiry(ttl));
+    }
+    // Roll seasons over when they end, even while nobody is looking at them.
+    state = state.with_job(Job::season_rollover());
+    // Hold correspondence games to the deadlines on their moves.
+    state = state.with_job(Job::correspondence());
+    // Keep trying webhook deliveries that fail, for a while.
+    state = state.with_job(Job::webhook_retries());
+    // Read the engine's evaluation weights from this file instead of using the built-in ones.
+    if let Some(path) = config.eval_weights {
+        state = state.with_eval_weights(path);
+    }
+    // Name openings from this book instead of the built-in one.
+    if let Some(path) = config.opening_book {
+        state = state.with_opening_book(path);
+    }
+    state.reload_assets().await?;
+    let state = Arc::new(state);
     // Restore any active games to the cache.
     restore_active_games(&state).await?;
-    let listener = TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    // Serve the app on the port specified above.
-    axum::serve(listener, app(state)).await.unwrap();
+    if config.fanout {
+        relay(Arc::clone(&state));
+    }
+    state.start_jobs();
+    let listener = TcpListener::bind(config.bind)
+        .await
+        .map_err(|e| format!("failed to listen on {}: {e}", config.bind))?;
+    // Serve the app on the address specified above until asked to stop, then let in-flight
+    // requests finish (for up to the shutdown timeout).
+    let server = axum::serve(
+        listener,
+        app(Arc::clone(&state)).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(Arc::clone(&state)));
+    let drained = async {
+        state.shutting_down().await;
+        tokio::time::sleep(config.shutdown_timeout).await;
+    };
+    tokio::select! {
+        result = server.into_future() => result?,
+        () = drained => tracing::error!("Gave up waiting for in-flight requests to finish"),
+    }
+    // Let any jobs that are running finish before the games are saved.
+    state.jobs_stopped().await;
+    // Save the games in memory so that they're restored when the server comes back.
+    let saved = state.persist_games().await?;
+    tracing::info!("Saved {saved} games before shutting down");
     Ok(())
 }
+
+/// Wait for a request to stop (`SIGINT`, or `SIGTERM` on Unix), then start shutting down.
+async fn shutdown_signal(state: Arc<AppState>) {
+    let interrupt = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        () = interrupt => {},
+        () = terminate => {},
+    }
+    state.shut_down();
+}
@@ -1,7 +1,10 @@
-use serde::{Deserialize, Serialize};
+use crate::Symmetry;
+use othello_rules_types::board;
+pub use othello_rules_types::Piece;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     fmt,
-    ops::{Index, IndexMut, Not},
+    ops::{Index, IndexMut},
 };
 
 const DIRECTIONS: &[(i8, i8)] = &[
@@ -15,24 +18,7 @@ const DIRECTIONS: &[(i8, i8)] = &[
     (1, 1),   // Bottom right
 ];
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub enum Piece {
-    Black,
-    White,
-}
-
-impl Not for Piece {
-    type Output = Self;
-
-    fn not(self) -> Self::Output {
-        match self {
-            Self::Black => Self::White,
-            Self::White => Self::Black,
-        }
-    }
-}
-
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub(super) struct Board(Vec<Option<Piece>>);
 
 impl Board {
@@ -123,10 +109,100 @@ impl Board {
         x >= 0 && y >= 0 && x < WIDTH && y < WIDTH
     }
 
+    /// Every square, row by row from the top left, with what it holds.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), Option<Piece>)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, &piece)| ((i % Self::width(), i / Self::width()), piece))
+    }
+
+    /// The squares holding the specified piece, row by row from the top left.
+    pub fn pieces(&self, piece: Piece) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.iter()
+            .filter(move |&(_, held)| held == Some(piece))
+            .map(|(square, _)| square)
+    }
+
+    /// The squares holding nothing, row by row from the top left.
+    pub fn empty_squares(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.iter()
+            .filter(|&(_, held)| held.is_none())
+            .map(|(square, _)| square)
+    }
+
+    /// The board as it looks after the specified rotation or reflection.
+    pub fn transformed(&self, symmetry: Symmetry) -> Self {
+        let mut board = Self(vec![None; Self::width() * Self::width()]);
+        for ((x, y), piece) in self.iter() {
+            board[symmetry.apply_within(x, y, Self::width())] = piece;
+        }
+        board
+    }
+
+    /// The smallest of the board's symmetries, comparing squares from the top left with empty
+    /// squares first and black before white, along with the symmetry that gives it. Boards
+    /// that are symmetries of each other have the same canonical board, so it can stand in
+    /// for all of them. Ties go to the earliest symmetry in [`Symmetry::ALL`].
+    pub fn canonical(&self) -> (Self, Symmetry) {
+        let rank = |piece: &Option<Piece>| match piece {
+            None => 0,
+            Some(Piece::Black) => 1,
+            Some(Piece::White) => 2,
+        };
+        Symmetry::ALL
+            .into_iter()
+            .map(|symmetry| (self.transformed(symmetry), symmetry))
+            .min_by(|(a, _), (b, _)| a.0.iter().map(rank).cmp(b.0.iter().map(rank)))
+            .expect("there should be symmetries to choose from")
+    }
+
     /// The width of the board. A standard Othello board is an 8x8 grid.
     pub const fn width() -> usize {
         8
     }
+
+    /// The board packed into two bitboards, 16 bytes in all, as it's sent and stored.
+    pub fn encode(&self) -> [u8; 16] {
+        board::to_bytes(&self.0).expect("standard boards should have 64 squares")
+    }
+
+    /// Unpack a board from two bitboards.
+    /// # Errors
+    /// Returns an error if a square is set in both bitboards.
+    pub fn decode(bytes: [u8; 16]) -> Result<Self, board::Error> {
+        board::from_bytes(bytes).map(Self)
+    }
+}
+
+/// Standard boards are written compactly, rather than as 64 squares.
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&board::encode(self.encode()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match board::Written::deserialize(deserializer)? {
+            board::Written::Compact(board) => board::decode(&board)
+                .and_then(Self::decode)
+                .map_err(de::Error::custom),
+            board::Written::Squares(squares) => Ok(Self(squares)),
+        }
+    }
+}
+
+impl From<Vec<Option<Piece>>> for Board {
+    fn from(squares: Vec<Option<Piece>>) -> Self {
+        Self(squares)
+    }
+}
+
+impl From<Board> for Vec<Option<Piece>> {
+    fn from(board: Board) -> Self {
+        board.0
+    }
 }
 
 impl Index<(usize, usize)> for Board {
@@ -145,6 +221,19 @@ impl IndexMut<(usize, usize)> for Board {
     }
 }
 
+/// The board with its columns lettered and rows numbered, as squares are written in the
+/// standard notation.
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns: String = (b'a'..).take(Self::width()).map(char::from).collect();
+        writeln!(f, "  {columns}")?;
+        for (row, line) in format!("{self:?}").lines().enumerate() {
+            writeln!(f, "{} {line}", row + 1)?;
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Debug for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for (i, piece) in self.0.iter().enumerate() {
@@ -164,7 +253,7 @@ impl fmt::Debug for Board {
 
 #[cfg(test)]
 mod tests {
-    use super::Board;
+    use super::{Board, Piece, Symmetry};
 
     #[test]
     fn adjacent() {
@@ -172,4 +261,69 @@ mod tests {
         assert!(!board.adjacent(0, 0));
         assert!(board.adjacent(2, 3));
     }
+
+    #[test]
+    fn squares() {
+        let board = Board::new();
+        assert_eq!(board.iter().count(), 64);
+        assert_eq!(board.iter().nth(9), Some(((1, 1), None)));
+        assert_eq!(
+            board.pieces(Piece::Black).collect::<Vec<_>>(),
+            [(4, 3), (3, 4)]
+        );
+        assert_eq!(
+            board.pieces(Piece::White).collect::<Vec<_>>(),
+            [(3, 3), (4, 4)]
+        );
+        assert_eq!(board.empty_squares().count(), 60);
+        assert!(board.empty_squares().all(|(x, y)| board[(x, y)].is_none()));
+    }
+
+    #[test]
+    fn symmetries() {
+        let mut board = Board::new();
+        board.flip(5, 4, Piece::Black, true);
+        board[(5, 4)] = Some(Piece::Black);
+        let (canonical, _) = board.canonical();
+        for symmetry in Symmetry::ALL {
+            let transformed = board.transformed(symmetry);
+            assert_eq!(transformed.transformed(symmetry.inverse()), board);
+            // Every symmetry of a board has the same canonical form.
+            let (other, from) = transformed.canonical();
+            assert_eq!(other, canonical);
+            assert_eq!(transformed.transformed(from), canonical);
+        }
+        // Turning the starting position a quarter swaps the colours in the center, which
+        // puts black first.
+        let (start, symmetry) = Board::new().canonical();
+        assert_eq!(start[(3, 3)], Some(Piece::Black));
+        assert_eq!(symmetry, Symmetry::Rotate90);
+    }
+
+    #[test]
+    fn display() {
+        let board = format!("{}", Board::new());
+        let lines: Vec<_> = board.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "  abcdefgh");
+        assert_eq!(lines[4], "4 ...●○...");
+        assert_eq!(lines[8], "8 ........");
+    }
+
+    #[test]
+    fn encoding() {
+        let mut board = Board::new();
+        board.flip(5, 4, Piece::Black, true);
+        board[(5, 4)] = Some(Piece::Black);
+        let bytes = board.encode();
+        assert_eq!(Board::decode(bytes), Ok(board.clone()));
+        // Black's bitboard comes first, with e4 (bit 28) set in the starting position.
+        assert_eq!(Board::new().encode()[4], 0x10);
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json.len(), 26);
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), board);
+        // Boards written out square by square are still read.
+        let squares = serde_json::to_string(&Vec::from(board.clone())).unwrap();
+        assert_eq!(serde_json::from_str::<Board>(&squares).unwrap(), board);
+    }
 }
@@ -1,8 +1,109 @@
-use crate::{Game, Piece};
+use crate::{board::Board, Game, Piece};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicIsize, AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How many positions the transposition table remembers before it's cleared.
+const TABLE_SIZE: usize = 1 << 20;
+/// How many separately locked parts the transposition table is split into, so that threads
+/// seldom wait on each other to use it.
+const SHARDS: u64 = 64;
+/// How many positions are searched between checks of the clock. Must be a power of two.
+const CLOCK_INTERVAL: u64 = 1024;
+/// The bound searches start from, kept clear of `isize::MIN` so that it can be negated.
+const INFINITY: isize = isize::MAX;
+/// How many moves the line of play a search expects runs to, at most.
+const LINE_LENGTH: usize = 12;
+
+/// Random keys for each piece on each square, and for white being the side to move, which a
+/// position's hash is the XOR of.
+const ZOBRIST: ([[u64; 2]; 64], u64) = {
+    // SplitMix64, which is plenty for spreading positions across the table.
+    const fn next(state: u64) -> (u64, u64) {
+        let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        (state, z ^ (z >> 31))
+    }
+    let mut keys = [[0; 2]; 64];
+    let mut state = 0;
+    let mut i = 0;
+    while i < 64 {
+        let (s, black) = next(state);
+        let (s, white) = next(s);
+        keys[i] = [black, white];
+        state = s;
+        i += 1;
+    }
+    (keys, next(state).1)
+};
+
+/// How a remembered value relates to the position's true value, which depends on whether the
+/// search that found it was cut short by its bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    /// The true value is at least this.
+    Lower,
+    /// The true value is at most this.
+    Upper,
+}
+
+/// What an earlier search found out about a position.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    depth: usize,
+    value: isize,
+    bound: Bound,
+    best: Option<(usize, usize)>,
+}
+
+/// Positions already searched by any thread, by their Zobrist hash.
+struct Table {
+    shards: Vec<Mutex<HashMap<u64, Entry>>>,
+}
+
+impl Table {
+    fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    fn get(&self, key: u64) -> Option<Entry> {
+        self.shard(key).get(&key).copied()
+    }
+
+    fn insert(&self, key: u64, entry: Entry) {
+        let mut shard = self.shard(key);
+        if shard.len() >= TABLE_SIZE / self.shards.len() {
+            shard.clear();
+        }
+        shard.insert(key, entry);
+    }
+
+    fn shard(&self, key: u64) -> MutexGuard<'_, HashMap<u64, Entry>> {
+        let index = usize::try_from(key % SHARDS).unwrap();
+        self.shards[index].lock().unwrap()
+    }
+}
 
 pub struct Companion<'a> {
     game: &'a Game,
     color: isize,
+    /// How many threads search at once.
+    threads: usize,
+    table: Table,
+    /// The moves of the line the search was told to expect, by the hash of the position each
+    /// is played from. They're tried first wherever the table has nothing to go on.
+    expected: HashMap<u64, (usize, usize)>,
 }
 
 impl<'a> From<&'a Game> for Companion<'a> {
@@ -10,40 +111,206 @@ impl<'a> From<&'a Game> for Companion<'a> {
         Self {
             game,
             color: if game.turn() == Piece::Black { 1 } else { -1 },
+            threads: 1,
+            table: Table::new(),
+            expected: HashMap::new(),
         }
     }
 }
 
 impl Companion<'_> {
+    /// Search with the specified number of threads (at least one), which share out the moves
+    /// at the root and what they learn about the positions below them. The move chosen is
+    /// the same however many threads there are; only how long it takes to choose changes.
+    #[must_use]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Search as though the specified line of play, starting from this position, was expected
+    /// (e.g. because an earlier search found it), trying its moves before any others. Only the
+    /// order moves are searched in changes, so the search finishes sooner, but a move is only
+    /// chosen over the rest on a tie. The line stops being followed at the first move that
+    /// can't be played.
+    #[must_use]
+    pub fn expecting(mut self, line: &[(usize, usize)]) -> Self {
+        let mut game = self.game.clone();
+        for &(x, y) in line {
+            let key = Self::hash(&game);
+            if game.place(x, y, game.turn()).is_err() {
+                break;
+            }
+            self.expected.insert(key, (x, y));
+        }
+        self
+    }
+
+    /// The line of play the last search expects from this position: the move it chose, then
+    /// the best reply it found to each move after that, for as far as it remembers. Empty
+    /// before anything is searched.
+    #[must_use]
+    pub fn line(&self) -> Vec<(usize, usize)> {
+        let mut game = self.game.clone();
+        let mut line = vec![];
+        while line.len() < LINE_LENGTH {
+            let Some((x, y)) = self
+                .table
+                .get(Self::hash(&game))
+                .and_then(|entry| entry.best)
+            else {
+                break;
+            };
+            if game.place(x, y, game.turn()).is_err() {
+                break;
+            }
+            line.push((x, y));
+        }
+        line
+    }
+
+    /// The best move found by searching the specified number of moves ahead.
+    ///
+    /// # Panics
+    /// Panics if there are no legal moves.
     pub fn choice(&mut self, depth: usize) -> (usize, usize) {
-        let mut history = vec![];
+        self.deepen(depth.max(1), None).unwrap()
+    }
+
+    /// The best move found by searching as far ahead as the time budget allows, deepening one
+    /// move at a time and keeping the result of the deepest search that finished. A one-move
+    /// search always finishes, however small the budget. Returns `None` if there are no legal
+    /// moves.
+    pub fn best_move_within(&mut self, budget: Duration) -> Option<(usize, usize)> {
+        let deadline = Instant::now() + budget;
+        // No search needs to look further ahead than there are empty squares.
+        let (black, white) = self.game.score();
+        let empties = Board::width() * Board::width() - black - white;
+        self.deepen(empties.max(1), Some(deadline))
+    }
+
+    /// Search one move ahead, then two, and so on up to `depth`, stopping early if the clock
+    /// runs out. Each search starts from the best move of the one before it, and the first
+    /// from whatever is known about the position already. The result of each search is kept
+    /// in the table, like those of the positions below it.
+    fn deepen(&mut self, depth: usize, deadline: Option<Instant>) -> Option<(usize, usize)> {
         let mut root = self.game.clone();
-        Self::negamax(&mut root, &mut history, depth, self.color);
-        let index = self.game.history().len();
-        history.get(index).copied().unwrap()
+        let key = Self::hash(&root);
+        let mut moves = root.moves(Self::player(self.color));
+        let known = self.table.get(key).and_then(|entry| entry.best);
+        let mut best = known
+            .or_else(|| self.expected.get(&key).copied())
+            .filter(|m| moves.contains(m))
+            .unwrap_or(*moves.first()?);
+        let mut workers: Vec<_> = (0..self.threads)
+            .map(|_| Worker::new(&self.table, &self.expected, deadline))
+            .collect();
+        for depth in 1..=depth {
+            if let Some(i) = moves.iter().position(|&m| m == best) {
+                moves[..=i].rotate_right(1);
+            }
+            let Some((choice, value)) = self.root(&mut workers, &root, &moves, depth) else {
+                break;
+            };
+            best = choice;
+            self.table.insert(
+                key,
+                Entry {
+                    depth,
+                    value,
+                    bound: Bound::Exact,
+                    best: Some(best),
+                },
+            );
+            // There's no point starting a deeper search once the clock has run out.
+            if workers[0].expired() {
+                break;
+            }
+        }
+        Some(best)
     }
 
-    fn negamax(
-        game: &mut Game,
-        history: &mut Vec<(usize, usize)>,
+    /// The best of the specified moves and its value, searching `depth` moves ahead, or `None`
+    /// if the clock ran out first. Ties go to the earliest move.
+    ///
+    /// The first move is searched alone, to give the rest a value to beat, and the rest are
+    /// shared out between the workers as they become free.
+    fn root(
+        &self,
+        workers: &mut [Worker],
+        game: &Game,
+        moves: &[(usize, usize)],
         depth: usize,
-        color: isize,
-    ) -> isize {
-        if depth == 0 || game.over() {
-            color * Self::heuristic(game)
+    ) -> Option<((usize, usize), isize)> {
+        let piece = Self::player(self.color);
+        let color = self.color;
+        let (&(x, y), rest) = moves.split_first()?;
+        let mut child = game.clone();
+        child.place(x, y, piece).unwrap();
+        let first = -workers[0].search(&mut child, depth - 1, -INFINITY, INFINITY, -color, 1)?;
+        let alpha = &AtomicIsize::new(first);
+        let next = &AtomicUsize::new(0);
+        let share = |worker: &mut Worker| {
+            let mut found = vec![];
+            while let Some(&(x, y)) = rest.get(next.fetch_add(1, Ordering::Relaxed)) {
+                let mut child = game.clone();
+                child.place(x, y, piece).unwrap();
+                // Searching for anything better than one less than the best value so far
+                // means a move that ties it still gets its exact value, so ties are broken
+                // the same way however the moves were shared out.
+                let beat = alpha.load(Ordering::Relaxed) - 1;
+                let value = -worker.search(&mut child, depth - 1, -INFINITY, -beat, -color, 1)?;
+                alpha.fetch_max(value, Ordering::Relaxed);
+                found.push(((x, y), value));
+            }
+            Some(found)
+        };
+        // A lone worker searches on the calling thread, so single-threaded searches work
+        // where threads can't be spawned (e.g. in the browser).
+        let found = if let [worker] = workers {
+            vec![share(worker)?]
         } else {
-            game.moves(Self::player(color))
-                .iter()
-                .fold(isize::MIN, |value, &(x, y)| {
-                    let mut child = game.clone();
-                    child.place(x, y, Self::player(color)).unwrap();
-                    let alt = -Self::negamax(&mut child, history, depth - 1, -color);
-                    if alt > value {
-                        *history = child.history();
+            thread::scope(|scope| {
+                let handles: Vec<_> = workers
+                    .iter_mut()
+                    .map(|worker| scope.spawn(move || share(worker)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .collect::<Option<Vec<_>>>()
+            })?
+        };
+        let index = |m| moves.iter().position(|&other| other == m);
+        let best =
+            found
+                .into_iter()
+                .flatten()
+                .fold(((x, y), first), |(best, alpha), (m, value)| {
+                    if value > alpha || (value == alpha && index(m) < index(best)) {
+                        (m, value)
+                    } else {
+                        (best, alpha)
                     }
-                    value.max(alt)
-                })
+                });
+        Some(best)
+    }
+
+    /// The Zobrist hash of the position: the pieces on the board and the side to move.
+    fn hash(game: &Game) -> u64 {
+        let (keys, white) = ZOBRIST;
+        let board = game.board();
+        let mut hash = if game.turn() == Piece::White {
+            white
+        } else {
+            0
+        };
+        for ((x, y), piece) in board.iter() {
+            if let Some(piece) = piece {
+                hash ^= keys[x + y * Board::width()][piece as usize];
+            }
         }
+        hash
     }
 
     fn player(color: isize) -> Piece {
@@ -62,13 +329,147 @@ impl Companion<'_> {
     }
 }
 
+/// One thread's share of a search.
+struct Worker<'t> {
+    table: &'t Table,
+    expected: &'t HashMap<u64, (usize, usize)>,
+    /// Up to two moves at each ply that caused a cutoff, which are tried early in sibling
+    /// positions since they're likely to cause one there too.
+    killers: Vec<[Option<(usize, usize)>; 2]>,
+    /// When the search has to give up, if it has a time budget.
+    deadline: Option<Instant>,
+    nodes: u64,
+}
+
+impl<'t> Worker<'t> {
+    fn new(
+        table: &'t Table,
+        expected: &'t HashMap<u64, (usize, usize)>,
+        deadline: Option<Instant>,
+    ) -> Self {
+        Self {
+            table,
+            expected,
+            killers: Vec::new(),
+            deadline,
+            nodes: 0,
+        }
+    }
+
+    /// Negamax with alpha-beta pruning, returning the value of the position for the side
+    /// `color` stands for, or `None` if the clock ran out. Only positions with moves left to
+    /// search check the clock, so one-move searches always finish.
+    fn search(
+        &mut self,
+        game: &mut Game,
+        depth: usize,
+        mut alpha: isize,
+        mut beta: isize,
+        color: isize,
+        ply: usize,
+    ) -> Option<isize> {
+        self.nodes += 1;
+        if depth > 0 && self.nodes & (CLOCK_INTERVAL - 1) == 0 && self.expired() {
+            return None;
+        }
+        let piece = Companion::player(color);
+        let mut moves = game.moves(piece);
+        if depth == 0 || moves.is_empty() {
+            return Some(color * Companion::heuristic(game));
+        }
+        let key = Companion::hash(game);
+        let original = alpha;
+        let mut hint = self.expected.get(&key).copied();
+        if let Some(entry) = self.table.get(key) {
+            hint = entry.best.or(hint);
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return Some(entry.value),
+                    Bound::Lower => alpha = alpha.max(entry.value),
+                    Bound::Upper => beta = beta.min(entry.value),
+                }
+                if alpha >= beta {
+                    return Some(entry.value);
+                }
+            }
+        }
+        self.order(&mut moves, hint, ply);
+        let mut value = -INFINITY;
+        let mut best = None;
+        for (x, y) in moves {
+            let mut child = game.clone();
+            child.place(x, y, piece).unwrap();
+            let score = -self.search(&mut child, depth - 1, -beta, -alpha, -color, ply + 1)?;
+            if score > value {
+                value = score;
+                best = Some((x, y));
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                self.remember_killer(ply, (x, y));
+                break;
+            }
+        }
+        let bound = if value <= original {
+            Bound::Upper
+        } else if value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(
+            key,
+            Entry {
+                depth,
+                value,
+                bound,
+                best,
+            },
+        );
+        Some(value)
+    }
+
+    /// Put the moves most likely to cause a cutoff first: the best move an earlier search of
+    /// the position found (or failing that, the one expected there), then the killer moves
+    /// for this ply.
+    fn order(&self, moves: &mut [(usize, usize)], hint: Option<(usize, usize)>, ply: usize) {
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        moves.sort_by_key(|&m| {
+            if Some(m) == hint {
+                0
+            } else if killers.contains(&Some(m)) {
+                1
+            } else {
+                2
+            }
+        });
+    }
+
+    fn remember_killer(&mut self, ply: usize, m: (usize, usize)) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+    }
+
+    fn expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Companion;
     use crate::{Game, Piece};
+    use std::time::Duration;
 
     #[test]
-    fn draw() {
+    fn self_play() {
         let mut game = Game::new();
         let mut piece = Piece::Black;
         while !game.over() {
@@ -77,6 +478,58 @@ mod tests {
             game.place(x, y, piece).unwrap();
             piece = !piece;
         }
-        assert_eq!(game.score(), (32, 32));
+        assert_eq!(game.score(), (24, 40));
+    }
+
+    #[test]
+    fn within() {
+        let game = Game::new();
+        let mut companion = Companion::from(&game);
+        let choice = companion
+            .best_move_within(Duration::from_millis(50))
+            .unwrap();
+        assert!(Game::new().moves(Piece::Black).contains(&choice));
+        // A move is found even without any time to search.
+        let mut companion = Companion::from(&game);
+        assert!(companion.best_move_within(Duration::ZERO).is_some());
+    }
+
+    #[test]
+    fn threads() {
+        let mut game = Game::new();
+        for (x, y) in [(2, 3), (2, 2), (2, 1)] {
+            let piece = game.turn();
+            game.place(x, y, piece).unwrap();
+        }
+        let alone = Companion::from(&game).choice(5);
+        assert_eq!(Companion::from(&game).threads(4).choice(5), alone);
+    }
+
+    #[test]
+    fn lines() {
+        let game = Game::new();
+        let mut companion = Companion::from(&game);
+        assert!(companion.line().is_empty());
+        let choice = companion.choice(5);
+        let line = companion.line();
+        assert_eq!(line.first(), Some(&choice));
+        assert!(line.len() > 2);
+        // Searching again picks up from what the first search found.
+        assert_eq!(companion.choice(5), choice);
+        assert_eq!(companion.line(), line);
+        // Picking the line up after the moves expected of both sides leads to the same choice.
+        let mut later = game.clone();
+        for &(x, y) in &line[..2] {
+            let piece = later.turn();
+            later.place(x, y, piece).unwrap();
+        }
+        let alone = Companion::from(&later).choice(5);
+        let mut resumed = Companion::from(&later).expecting(&line[2..]);
+        assert_eq!(resumed.choice(5), alone);
+        assert_eq!(resumed.line().first(), Some(&alone));
+        // Lines that can't be played are ignored.
+        let mut wrong = Companion::from(&game).expecting(&[(0, 0)]);
+        assert!(wrong.line().is_empty());
+        assert_eq!(wrong.choice(5), choice);
     }
 }
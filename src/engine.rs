@@ -0,0 +1,221 @@
+//! A text protocol for driving the companion from other programs, modelled on UCI. Commands are
+//! read a line at a time and answered with zero or more lines:
+//!
+//! - `uci` names the engine and its options, then answers `uciok`.
+//! - `isready` answers `readyok`.
+//! - `setoption name Threads value <n>` sets how many threads searches use.
+//! - `ucinewgame` goes back to the starting position.
+//! - `position startpos [moves <square>...]` sets up the position after the specified moves.
+//! - `go [depth <n> | movetime <ms>]` searches the position, answering `bestmove <square>`, or
+//!   `bestmove none` if there are no legal moves.
+//!
+//! Squares are written in the standard notation: columns lettered from the left, rows numbered
+//! from the top.
+
+use crate::{companion::Companion, Coord, Game, PlaceError};
+use std::time::Duration;
+
+/// How far ahead `go` searches when it isn't told.
+const DEFAULT_DEPTH: usize = 6;
+/// The most threads a search can be given.
+const MAX_THREADS: usize = 64;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum EngineError {
+    #[error("unknown command: {0}")]
+    UnknownCommand(String),
+    #[error("unknown option: {0}")]
+    UnknownOption(String),
+    #[error("invalid value for {0}: {1}")]
+    InvalidValue(&'static str, String),
+    #[error("invalid square: {0}")]
+    InvalidSquare(String),
+    #[error("illegal move {0}: {1}")]
+    IllegalMove(String, PlaceError),
+    #[error("malformed command: {0}")]
+    Malformed(String),
+}
+
+/// The position being analysed and the settings to search it with.
+pub struct Engine {
+    game: Game,
+    threads: usize,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self {
+            game: Game::new(),
+            threads: 1,
+        }
+    }
+}
+
+impl Engine {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Carry out a command, returning the lines to answer it with.
+    ///
+    /// # Errors
+    /// Returns an error if the command isn't understood, or would set up an impossible
+    /// position. The engine is left as it was.
+    pub fn handle(&mut self, line: &str) -> Result<Vec<String>, EngineError> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None => Ok(vec![]),
+            Some("uci") => Ok(vec![
+                format!("id name olly {}", env!("CARGO_PKG_VERSION")),
+                format!("option name Threads type spin default 1 min 1 max {MAX_THREADS}"),
+                String::from("uciok"),
+            ]),
+            Some("isready") => Ok(vec![String::from("readyok")]),
+            Some("ucinewgame") => {
+                self.game = Game::new();
+                Ok(vec![])
+            }
+            Some("setoption") => self.set_option(line, words).map(|()| vec![]),
+            Some("position") => self.position(line, words).map(|()| vec![]),
+            Some("go") => self.go(line, words).map(|choice| {
+                let choice = choice.map_or_else(|| String::from("none"), |(x, y)| square(x, y));
+                vec![format!("bestmove {choice}")]
+            }),
+            Some(command) => Err(EngineError::UnknownCommand(command.to_string())),
+        }
+    }
+
+    fn set_option<'a>(
+        &mut self,
+        line: &str,
+        mut words: impl Iterator<Item = &'a str>,
+    ) -> Result<(), EngineError> {
+        let (Some("name"), Some(name), Some("value"), Some(value), None) = (
+            words.next(),
+            words.next(),
+            words.next(),
+            words.next(),
+            words.next(),
+        ) else {
+            return Err(EngineError::Malformed(line.to_string()));
+        };
+        if !name.eq_ignore_ascii_case("threads") {
+            return Err(EngineError::UnknownOption(name.to_string()));
+        }
+        self.threads = value
+            .parse()
+            .ok()
+            .filter(|threads| (1..=MAX_THREADS).contains(threads))
+            .ok_or_else(|| EngineError::InvalidValue("Threads", value.to_string()))?;
+        Ok(())
+    }
+
+    fn position<'a>(
+        &mut self,
+        line: &str,
+        mut words: impl Iterator<Item = &'a str>,
+    ) -> Result<(), EngineError> {
+        if words.next() != Some("startpos") {
+            return Err(EngineError::Malformed(line.to_string()));
+        }
+        match words.next() {
+            None | Some("moves") => {}
+            Some(_) => return Err(EngineError::Malformed(line.to_string())),
+        }
+        let mut game = Game::new();
+        for word in words {
+            let (x, y) = parse_square(word)?;
+            game.place(x, y, game.turn())
+                .map_err(|e| EngineError::IllegalMove(word.to_string(), e))?;
+        }
+        self.game = game;
+        Ok(())
+    }
+
+    fn go<'a>(
+        &self,
+        line: &str,
+        mut words: impl Iterator<Item = &'a str>,
+    ) -> Result<Option<(usize, usize)>, EngineError> {
+        if self.game.clone().over() {
+            return Ok(None);
+        }
+        let mut companion = Companion::from(&self.game).threads(self.threads);
+        match (words.next(), words.next(), words.next()) {
+            (None, None, None) => Ok(Some(companion.choice(DEFAULT_DEPTH))),
+            (Some("depth"), Some(depth), None) => {
+                let depth = depth
+                    .parse()
+                    .ok()
+                    .filter(|&depth| depth > 0)
+                    .ok_or_else(|| EngineError::InvalidValue("depth", depth.to_string()))?;
+                Ok(Some(companion.choice(depth)))
+            }
+            (Some("movetime"), Some(time), None) => {
+                let time = time
+                    .parse()
+                    .map_err(|_| EngineError::InvalidValue("movetime", time.to_string()))?;
+                Ok(companion.best_move_within(Duration::from_millis(time)))
+            }
+            _ => Err(EngineError::Malformed(line.to_string())),
+        }
+    }
+}
+
+/// A square in the standard notation, e.g. `f5`.
+#[must_use]
+pub fn square(x: usize, y: usize) -> String {
+    Coord::new(x, y).to_string()
+}
+
+/// The square written in the standard notation.
+///
+/// # Errors
+/// Returns an error if the square isn't written properly or isn't on the board.
+pub fn parse_square(square: &str) -> Result<(usize, usize), EngineError> {
+    square
+        .parse::<Coord>()
+        .map(Into::into)
+        .map_err(|_| EngineError::InvalidSquare(square.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_square, square, Engine, EngineError};
+
+    #[test]
+    fn squares() {
+        assert_eq!(parse_square("f5"), Ok((5, 4)));
+        assert_eq!(square(5, 4), "f5");
+        for bad in ["", "f", "5f", "i1", "a0", "a9", "F5"] {
+            assert_eq!(
+                parse_square(bad),
+                Err(EngineError::InvalidSquare(bad.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn session() {
+        let mut engine = Engine::new();
+        assert_eq!(engine.handle("uci").unwrap().last().unwrap(), "uciok");
+        assert_eq!(engine.handle("isready").unwrap(), ["readyok"]);
+        assert!(engine.handle("setoption name Threads value 2").is_ok());
+        assert!(engine.handle("setoption name Threads value 0").is_err());
+        assert!(engine.handle("position startpos moves f5 d6").is_ok());
+        let reply = engine.handle("go depth 3").unwrap();
+        assert_eq!(reply.len(), 1);
+        assert!(reply[0].starts_with("bestmove "));
+        assert!(engine.handle("go movetime 10").is_ok());
+        // Illegal moves leave the position as it was.
+        assert!(matches!(
+            engine.handle("position startpos moves a1"),
+            Err(EngineError::IllegalMove(..))
+        ));
+        assert!(matches!(
+            engine.handle("castle"),
+            Err(EngineError::UnknownCommand(_))
+        ));
+    }
+}
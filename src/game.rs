@@ -1,46 +1,99 @@
 use crate::{
     board::{Board, Piece},
-    PlaceError,
+    opening::{self, Book},
+    settings::Variant,
+    GameSettings, PlaceError, PositionBuilder, Symmetry,
 };
+use othello_rules_types::Position;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
     board: Board,
     turn: Piece,
     history: Vec<(usize, usize)>,
+    /// Games cached before settings existed were all played with the defaults.
+    #[serde(default)]
+    settings: GameSettings,
+    /// The legal moves for the side to move, computed on demand and cleared whenever the
+    /// position changes.
+    #[serde(skip)]
+    legal: Option<Vec<(usize, usize)>>,
+    /// The pieces flipped by each of the latest moves, so that they can be taken back. Only
+    /// the moves played since the game was created or deserialized are covered.
+    #[serde(skip)]
+    flips: Vec<Vec<(usize, usize)>>,
 }
 
 impl Game {
     #[must_use]
     pub fn new() -> Self {
+        Self::with_settings(GameSettings::default())
+    }
+
+    /// A game played with the specified settings, from its starting position.
+    ///
+    /// # Panics
+    /// Never: every starting position is a valid one. Its squares are all on the board, the
+    /// center is filled, and every corner the handicap gives (at most all four, however large
+    /// it is) is exempt from being connected to it.
+    #[must_use]
+    pub fn with_settings(settings: GameSettings) -> Self {
+        // Handicap corners are set up like any other position.
+        if settings.handicap > 0 {
+            return PositionBuilder::start(settings)
+                .build()
+                .expect("starting positions are always valid");
+        }
         Self {
             board: Board::new(),
             turn: Piece::Black,
             history: Vec::new(),
+            settings,
+            legal: None,
+            flips: Vec::new(),
         }
     }
 
     #[must_use]
     pub fn score(&self) -> (usize, usize) {
-        let mut black = 0;
-        let mut white = 0;
-        for (x, y) in Self::points() {
-            match self.board[(x, y)] {
-                Some(Piece::Black) => black += 1,
-                Some(Piece::White) => white += 1,
-                None => (),
-            }
-        }
-        (black, white)
+        (
+            self.board.pieces(Piece::Black).count(),
+            self.board.pieces(Piece::White).count(),
+        )
+    }
+
+    /// Every square, row by row from the top left, with what it holds.
+    pub fn squares(&self) -> impl Iterator<Item = ((usize, usize), Option<Piece>)> + '_ {
+        self.board.iter()
     }
 
+    /// The squares holding the specified piece, row by row from the top left.
+    pub fn pieces(&self, piece: Piece) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.board.pieces(piece)
+    }
+
+    /// The squares holding nothing, row by row from the top left.
+    pub fn empty_squares(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.board.empty_squares()
+    }
+
+    /// The squares the specified piece can legally be placed on. Only the side to move has
+    /// any legal moves.
     pub fn moves(&mut self, piece: Piece) -> Vec<(usize, usize)> {
-        Self::points()
+        if piece != self.turn {
+            return Vec::new();
+        }
+        if let Some(legal) = &self.legal {
+            return legal.clone();
+        }
+        let legal: Vec<_> = Self::points()
             .into_iter()
-            .filter(|&(x, y)| self.validate(x, y, piece).is_ok())
-            .collect()
+            .filter(|&(x, y)| self.check(x, y, piece).is_ok())
+            .collect();
+        self.legal = Some(legal.clone());
+        legal
     }
 
     fn points() -> impl IntoIterator<Item = (usize, usize)> {
@@ -52,12 +105,29 @@ impl Game {
     pub fn place(&mut self, x: usize, y: usize, piece: Piece) -> Result<(), PlaceError> {
         self.validate(x, y, piece)?;
         self.board[(x, y)] = Some(piece);
-        self.board.flip(x, y, piece, true);
+        let flips = self.board.flip(x, y, piece, true);
         self.history.push((x, y));
+        self.flips.push(flips);
         self.turn = !self.turn;
+        self.legal = None;
         Ok(())
     }
 
+    /// Take back the last move, returning the square it was played on, or `None` if there's
+    /// no move this game can take back. Only moves played since the game was created or
+    /// deserialized can be, since the pieces they flipped aren't kept anywhere else.
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        let flips = self.flips.pop()?;
+        let (x, y) = self.history.pop()?;
+        let piece = self.board[(x, y)].take()?;
+        for square in flips {
+            self.board[square] = Some(!piece);
+        }
+        self.turn = piece;
+        self.legal = None;
+        Some((x, y))
+    }
+
     /// # Errors
     /// Returns an error if the move is invalid.
     pub fn preview(
@@ -71,6 +141,19 @@ impl Game {
     }
 
     fn validate(&mut self, x: usize, y: usize, piece: Piece) -> Result<(), PlaceError> {
+        // Skip the full check for moves already known to be legal this turn.
+        let known = self
+            .legal
+            .as_ref()
+            .is_some_and(|legal| legal.contains(&(x, y)));
+        if known && piece == self.turn {
+            Ok(())
+        } else {
+            self.check(x, y, piece)
+        }
+    }
+
+    fn check(&mut self, x: usize, y: usize, piece: Piece) -> Result<(), PlaceError> {
         if x >= Board::width() || y >= Board::width() {
             Err(PlaceError::OutOfBounds(x, y))
         } else {
@@ -99,18 +182,168 @@ impl Game {
         self.history.clone()
     }
 
+    /// The name of the most specific opening this game's moves follow, if they follow any.
+    /// Openings are only named from the standard starting position, not a handicap one.
+    #[must_use]
+    pub fn opening_name(&self) -> Option<&'static str> {
+        if self.settings.handicap > 0 {
+            return None;
+        }
+        opening::name(&self.history)
+    }
+
+    /// The name of the most specific opening in the specified book this game's moves follow,
+    /// like [`Game::opening_name`] does with the built-in book.
+    #[must_use]
+    pub fn opening_name_in<'b>(&self, book: &'b Book) -> Option<&'b str> {
+        if self.settings.handicap > 0 {
+            return None;
+        }
+        book.name(&self.history)
+    }
+
+    /// The piece on the specified square, if there is one. Squares off the board are empty.
+    #[must_use]
+    pub fn piece(&self, x: usize, y: usize) -> Option<Piece> {
+        if x < Board::width() && y < Board::width() {
+            self.board[(x, y)]
+        } else {
+            None
+        }
+    }
+
     #[must_use]
     pub fn turn(&self) -> Piece {
         self.turn
     }
+
+    /// Whether the specified piece's player can see the specified square. Everything is
+    /// visible except in fog games, where players only see the squares holding or next to
+    /// their own pieces.
+    #[must_use]
+    pub fn visible(&self, x: usize, y: usize, piece: Piece) -> bool {
+        if self.settings.variant != Variant::Fog {
+            return true;
+        }
+        let width = Board::width();
+        (x.saturating_sub(1)..=(x + 1).min(width - 1)).any(|nx| {
+            (y.saturating_sub(1)..=(y + 1).min(width - 1))
+                .any(|ny| self.board[(nx, ny)] == Some(piece))
+        })
+    }
+
+    /// The game as the specified piece's player sees it, with every square they can't see
+    /// emptied and the moves played on those squares left out.
+    #[must_use]
+    pub fn visible_to(&self, piece: Piece) -> Self {
+        let mut view = self.clone();
+        for ((x, y), _) in self.squares() {
+            if !self.visible(x, y, piece) {
+                view.board[(x, y)] = None;
+            }
+        }
+        view.history.retain(|&(x, y)| self.visible(x, y, piece));
+        view.legal = None;
+        view.flips.clear();
+        view
+    }
+
+    /// The squares whose contents differ from those of the specified earlier position, with
+    /// what each holds now.
+    #[must_use]
+    pub fn changes_since(&self, earlier: &Self) -> Vec<(usize, usize, Option<Piece>)> {
+        Self::points()
+            .into_iter()
+            .filter(|&(x, y)| self.board[(x, y)] != earlier.board[(x, y)])
+            .map(|(x, y)| (x, y, self.board[(x, y)]))
+            .collect()
+    }
+
+    /// The game as it would look rotated or reflected by the specified symmetry, with its
+    /// moves transformed to match.
+    #[must_use]
+    pub fn transformed(&self, symmetry: Symmetry) -> Self {
+        Self {
+            board: self.board.transformed(symmetry),
+            turn: self.turn,
+            history: self
+                .history
+                .iter()
+                .map(|&(x, y)| symmetry.apply(x, y))
+                .collect(),
+            settings: self.settings,
+            legal: None,
+            flips: self
+                .flips
+                .iter()
+                .map(|flips| flips.iter().map(|&(x, y)| symmetry.apply(x, y)).collect())
+                .collect(),
+        }
+    }
+
+    /// The game transformed onto its board's canonical form, along with the symmetry that
+    /// does it, so positions that only differ by a rotation or reflection can be treated as
+    /// one (e.g. in opening books or when deduplicating puzzles).
+    #[must_use]
+    pub fn canonical(&self) -> (Self, Symmetry) {
+        let (_, symmetry) = self.board.canonical();
+        (self.transformed(symmetry), symmetry)
+    }
+
+    #[must_use]
+    pub fn settings(&self) -> &GameSettings {
+        &self.settings
+    }
+
+    pub(crate) fn board(&self) -> &Board {
+        &self.board
+    }
 }
 
+// The legal move cache is derived from the rest of the state, and the record of flips only
+// says how the state came about, so neither has any bearing on equality.
+impl PartialEq for Game {
+    fn eq(&self, other: &Self) -> bool {
+        self.board == other.board
+            && self.turn == other.turn
+            && self.history == other.history
+            && self.settings == other.settings
+    }
+}
+
+impl Eq for Game {}
+
 impl Default for Game {
     fn default() -> Self {
         Self::new()
     }
 }
 
+impl From<Game> for Position {
+    fn from(game: Game) -> Self {
+        Self {
+            board: game.board.into(),
+            turn: game.turn,
+            history: game.history,
+            settings: game.settings,
+        }
+    }
+}
+
+/// Positions aren't checked, just as games aren't when they're deserialized.
+impl From<Position> for Game {
+    fn from(position: Position) -> Self {
+        Self {
+            board: position.board.into(),
+            turn: position.turn,
+            history: position.history,
+            settings: position.settings,
+            legal: None,
+            flips: Vec::new(),
+        }
+    }
+}
+
 impl fmt::Debug for Game {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Turn: {:?}", self.turn)?;
@@ -119,21 +352,27 @@ impl fmt::Debug for Game {
     }
 }
 
+/// The board, labelled with the notation its squares are written in.
 impl fmt::Display for Game {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{self:?}")
+        write!(f, "{}", self.board)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Game, Piece, PlaceError};
+    use super::{Game, GameSettings, Piece, PlaceError, Position, Variant};
+    use crate::PositionBuilder;
+    use proptest::{collection::vec, prelude::*, sample::Index};
+    use std::cmp::Ordering;
 
     #[test]
     fn new() {
         let state = Game::new();
         assert_eq!(state.turn, Piece::Black);
         assert_eq!(state.score(), (2, 2));
+        assert_eq!(state.piece(3, 3), Some(Piece::White));
+        assert_eq!(state.piece(8, 0), None);
     }
 
     #[test]
@@ -143,6 +382,51 @@ mod tests {
         assert_eq!(moves.len(), 4);
     }
 
+    #[test]
+    fn fog() {
+        let settings = GameSettings {
+            variant: Variant::Fog,
+            ..GameSettings::default()
+        };
+        let mut state = Game::with_settings(settings);
+        for _ in 0..10 {
+            let piece = state.turn;
+            let (x, y) = state.moves(piece)[0];
+            state.place(x, y, piece).unwrap();
+        }
+        let view = state.visible_to(Piece::White);
+        for (x, y) in Game::points() {
+            if state.visible(x, y, Piece::White) {
+                assert_eq!(view.board[(x, y)], state.board[(x, y)]);
+            } else {
+                assert_eq!(view.board[(x, y)], None);
+            }
+            // Players can always see their own pieces.
+            if state.board[(x, y)] == Some(Piece::White) {
+                assert!(state.visible(x, y, Piece::White));
+            }
+        }
+        assert!(view.history.len() <= state.history.len());
+        // Without fog, everyone sees everything.
+        let mut standard = Game::new();
+        assert!(standard.place(2, 3, Piece::Black).is_ok());
+        assert_eq!(standard.visible_to(Piece::White), standard);
+    }
+
+    #[test]
+    fn cached_moves() {
+        let mut state = Game::new();
+        let moves = state.moves(Piece::Black);
+        assert_eq!(state.moves(Piece::Black), moves);
+        assert!(state.place(2, 3, Piece::Black).is_ok());
+        // Placing a piece invalidates the cached moves.
+        assert!(state.moves(Piece::Black).is_empty());
+        assert_eq!(state.moves(Piece::White).len(), 3);
+        let mut fresh = Game::new();
+        assert!(fresh.place(2, 3, Piece::Black).is_ok());
+        assert_eq!(state, fresh);
+    }
+
     #[test]
     fn flips_preview() {
         let mut state = Game::new();
@@ -190,4 +474,192 @@ mod tests {
         let outcome = state.place(8, 8, Piece::Black);
         assert_eq!(outcome.unwrap_err(), PlaceError::OutOfBounds(8, 8));
     }
+
+    #[test]
+    fn symmetries() {
+        // The four first moves are symmetries of each other.
+        let games: Vec<_> = Game::new()
+            .moves(Piece::Black)
+            .into_iter()
+            .map(|(x, y)| {
+                let mut game = Game::new();
+                game.place(x, y, Piece::Black).unwrap();
+                game
+            })
+            .collect();
+        let (canonical, _) = games[0].canonical();
+        for game in &games {
+            let (other, symmetry) = game.canonical();
+            assert_eq!(other, canonical);
+            assert_eq!(other.transformed(symmetry.inverse()), *game);
+            // Moves are transformed along with the board.
+            assert_eq!(other.clone().moves(Piece::White).len(), 3);
+        }
+    }
+
+    #[test]
+    fn handicap() {
+        let settings = GameSettings {
+            handicap: 1,
+            ..GameSettings::default()
+        };
+        let mut game = Game::with_settings(settings);
+        assert_eq!(game.piece(0, 0), Some(Piece::Black));
+        assert_eq!(game.turn(), Piece::Black);
+        game.place(2, 3, Piece::Black).unwrap();
+        assert_eq!(game.history(), [(2, 3)]);
+        assert_eq!(game.opening_name(), None);
+        // Handicaps too large to be played still start from a position, with every corner.
+        let game = Game::with_settings(GameSettings {
+            handicap: 9,
+            ..GameSettings::default()
+        });
+        assert_eq!(game.score(), (6, 2));
+    }
+
+    #[test]
+    fn positions() {
+        // Positions are how games are sent, so they have to look the same as games do.
+        let mut game = Game::new();
+        game.place(5, 4, Piece::Black).unwrap();
+        let position = Position::from(game.clone());
+        assert_eq!(
+            serde_json::to_value(&position).unwrap(),
+            serde_json::to_value(&game).unwrap()
+        );
+        assert_eq!(Game::from(position), game);
+    }
+
+    proptest! {
+        /// Play random games, picking each move from the legal ones, and check every move
+        /// against the rules.
+        #[test]
+        fn random_games(picks in vec(any::<Index>(), 0..64)) {
+            let mut game = Game::new();
+            for pick in picks {
+                let piece = game.turn();
+                let moves = game.moves(piece);
+                if moves.is_empty() {
+                    break;
+                }
+                let (x, y) = *pick.get(&moves);
+                let before = game.clone();
+                let mut flips = game.preview(x, y, piece).unwrap();
+                game.place(x, y, piece).unwrap();
+                // Every move adds a disc and flips at least one.
+                let (black, white) = before.score();
+                prop_assert_eq!(game.score().0 + game.score().1, black + white + 1);
+                prop_assert!(!flips.is_empty());
+                // The squares that change are the one played and the ones previewed, and
+                // they're all the mover's afterwards.
+                let changes = game.changes_since(&before);
+                prop_assert!(changes.iter().all(|&(_, _, now)| now == Some(piece)));
+                let mut changed: Vec<_> = changes.into_iter().map(|(x, y, _)| (x, y)).collect();
+                flips.push((x, y));
+                flips.sort_unstable();
+                changed.sort_unstable();
+                prop_assert_eq!(changed, flips);
+                prop_assert_eq!(game.turn(), !piece);
+            }
+            // Replaying the moves, or setting up the position they led to, gets the same game.
+            let mut replayed = Game::new();
+            for (x, y) in game.history() {
+                let piece = replayed.turn();
+                replayed.place(x, y, piece).unwrap();
+            }
+            prop_assert_eq!(&replayed, &game);
+            let mut built = PositionBuilder::from(&game).build().unwrap();
+            prop_assert_eq!(built.score(), game.score());
+            prop_assert_eq!(built.moves(game.turn()), game.moves(game.turn()));
+            let json = serde_json::to_string(&game).unwrap();
+            prop_assert_eq!(serde_json::from_str::<Game>(&json).unwrap(), game);
+        }
+
+        /// Taking a move back leaves the game as it was before the move, and taking every
+        /// move back gets to the start.
+        #[test]
+        fn place_undo(picks in vec(any::<Index>(), 1..64)) {
+            let mut game = Game::new();
+            for pick in picks {
+                let piece = game.turn();
+                let moves = game.moves(piece);
+                if moves.is_empty() {
+                    break;
+                }
+                let (x, y) = *pick.get(&moves);
+                let before = game.clone();
+                game.place(x, y, piece).unwrap();
+                let mut undone = game.clone();
+                prop_assert_eq!(undone.undo(), Some((x, y)));
+                prop_assert_eq!(&undone, &before);
+                prop_assert_eq!(undone.moves(piece), before.clone().moves(piece));
+            }
+            // Deserialized games don't know what their moves flipped.
+            let json = serde_json::to_string(&game).unwrap();
+            prop_assert_eq!(serde_json::from_str::<Game>(&json).unwrap().undo(), None);
+            for _ in game.history() {
+                prop_assert!(game.undo().is_some());
+            }
+            prop_assert_eq!(game.undo(), None);
+            prop_assert_eq!(game, Game::new());
+        }
+
+        /// Every piece a move flips lies on a straight line from the square played to another
+        /// of the mover's pieces, with only the opponent's pieces, all flipped, in between.
+        #[test]
+        fn flips_are_bracketed(picks in vec(any::<Index>(), 1..64)) {
+            let step = |from: usize, to: usize| match to.cmp(&from) {
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+                Ordering::Greater => 1,
+            };
+            let mut game = Game::new();
+            for pick in picks {
+                let piece = game.turn();
+                let moves = game.moves(piece);
+                if moves.is_empty() {
+                    break;
+                }
+                let (x, y) = *pick.get(&moves);
+                let before = game.clone();
+                let flips = game.preview(x, y, piece).unwrap();
+                game.place(x, y, piece).unwrap();
+                for &(fx, fy) in &flips {
+                    let (dx, dy) = (step(x, fx), step(y, fy));
+                    prop_assert!(dx == 0 || dy == 0 || fx.abs_diff(x) == fy.abs_diff(y));
+                    let distance = fx.abs_diff(x).max(fy.abs_diff(y));
+                    // Walk out from the square played until reaching one of the mover's pieces.
+                    let (x, y) = (isize::try_from(x).unwrap(), isize::try_from(y).unwrap());
+                    let mut k = 1;
+                    let anchor = loop {
+                        let (Ok(sx), Ok(sy)) =
+                            (usize::try_from(x + k * dx), usize::try_from(y + k * dy))
+                        else {
+                            break None;
+                        };
+                        match before.piece(sx, sy) {
+                            Some(p) if p == piece => break Some(k),
+                            Some(_) => prop_assert!(flips.contains(&(sx, sy))),
+                            None => break None,
+                        }
+                        k += 1;
+                    };
+                    prop_assert!(anchor.is_some_and(|k| k.unsigned_abs() > distance));
+                }
+            }
+        }
+
+        /// Moves that aren't legal leave the game as it was.
+        #[test]
+        fn illegal_moves(x in 0..10usize, y in 0..10usize, black in any::<bool>()) {
+            let mut game = Game::new();
+            let piece = if black { Piece::Black } else { Piece::White };
+            let legal = game.moves(piece).contains(&(x, y));
+            let before = game.clone();
+            prop_assert_eq!(game.place(x, y, piece).is_ok(), legal);
+            if !legal {
+                prop_assert_eq!(game, before);
+            }
+        }
+    }
 }
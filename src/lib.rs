@@ -1,12 +1,30 @@
+//! The rules of Othello, with a companion to play them and analysis of the games played.
+//!
+//! The `server` feature (on by default) adds the server that hosts games over HTTP and the
+//! gateway. Without it, nothing but the rules is compiled, so the crate builds for any target.
+
 pub use board::Piece;
 pub use game::Game;
+pub use notation::{Coord, Moves, NotationError};
+pub use position::{PositionBuilder, PositionError};
 use serde::{Deserialize, Serialize};
+pub use settings::GameSettings;
 use std::fmt;
+pub use symmetry::Symmetry;
 
+pub mod analysis;
 mod board;
-mod companion;
+pub mod companion;
+pub mod engine;
 mod game;
+mod notation;
+pub mod opening;
+mod position;
+pub mod render;
+#[cfg(feature = "server")]
 pub mod server;
+pub mod settings;
+mod symmetry;
 
 #[derive(thiserror::Error, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PlaceError {
@@ -0,0 +1,133 @@
+use crate::board::Board;
+use std::{fmt, str::FromStr};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum NotationError {
+    #[error("invalid square: {0}")]
+    InvalidSquare(String),
+}
+
+/// A square, written in the standard notation: its column lettered from the left and its row
+/// numbered from the top (e.g. `f5` for `(5, 4)`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Coord {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Coord {
+    #[must_use]
+    pub fn new(x: usize, y: usize) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<(usize, usize)> for Coord {
+    fn from((x, y): (usize, usize)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Coord> for (usize, usize) {
+    fn from(coord: Coord) -> Self {
+        (coord.x, coord.y)
+    }
+}
+
+impl fmt::Display for Coord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only the first 26 columns have letters, far more than any board has.
+        let column = (b'a'..=b'z').nth(self.x).map_or('?', char::from);
+        write!(f, "{column}{}", self.y + 1)
+    }
+}
+
+impl FromStr for Coord {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NotationError::InvalidSquare(s.to_string());
+        let mut chars = s.chars();
+        let x = chars
+            .next()
+            .filter(char::is_ascii_lowercase)
+            .map(|column| column as usize - 'a' as usize)
+            .ok_or_else(invalid)?;
+        let row: usize = chars.as_str().parse().map_err(|_| invalid())?;
+        if x >= Board::width() || row == 0 || row > Board::width() {
+            return Err(invalid());
+        }
+        Ok(Self { x, y: row - 1 })
+    }
+}
+
+/// Moves written out one after another in the standard notation, as games are exported (e.g.
+/// `f5d6c3`). They're read with or without spaces between them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Moves(pub Vec<Coord>);
+
+impl From<&[(usize, usize)]> for Moves {
+    fn from(history: &[(usize, usize)]) -> Self {
+        Self(history.iter().copied().map(Coord::from).collect())
+    }
+}
+
+impl fmt::Display for Moves {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|coord| write!(f, "{coord}"))
+    }
+}
+
+impl FromStr for Moves {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut moves = Vec::new();
+        for word in s.split_whitespace() {
+            let mut rest = word;
+            while !rest.is_empty() {
+                // Each square is a letter followed by its row's digits.
+                let end = rest
+                    .char_indices()
+                    .skip(1)
+                    .find(|(_, c)| !c.is_ascii_digit())
+                    .map_or(rest.len(), |(i, _)| i);
+                moves.push(rest[..end].parse()?);
+                rest = &rest[end..];
+            }
+        }
+        Ok(Self(moves))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Coord, Moves, NotationError};
+
+    #[test]
+    fn squares() {
+        assert_eq!("f5".parse(), Ok(Coord::new(5, 4)));
+        assert_eq!(Coord::new(5, 4).to_string(), "f5");
+        assert_eq!(Coord::new(0, 7).to_string(), "a8");
+        for bad in ["", "f", "5f", "i1", "a0", "a9", "F5", "f5 "] {
+            assert_eq!(
+                bad.parse::<Coord>(),
+                Err(NotationError::InvalidSquare(bad.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn moves() {
+        let moves = Moves(vec![Coord::new(5, 4), Coord::new(3, 5), Coord::new(2, 2)]);
+        assert_eq!(moves.to_string(), "f5d6c3");
+        assert_eq!("f5d6c3".parse(), Ok(moves.clone()));
+        assert_eq!(" f5 d6c3 ".parse(), Ok(moves));
+        assert_eq!("".parse(), Ok(Moves::default()));
+        assert_eq!(
+            "f5d6z3".parse::<Moves>(),
+            Err(NotationError::InvalidSquare(String::from("z3")))
+        );
+        assert_eq!(Moves::from(&[(5, 4)][..]).to_string(), "f5");
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{notation::Coord, Game, Symmetry};
+use serde::Deserialize;
+use std::{collections::HashMap, sync::OnceLock};
+
+/// Named openings, as the moves that define them in the standard notation (columns lettered
+/// from the left, rows numbered from the top) with black opening on f5. Games opened on any
+/// of the other three first moves are mirrored or rotated onto f5 before they're looked up.
+const BOOK: &[(&str, &str)] = &[
+    ("Perpendicular", "f5d6"),
+    ("Diagonal", "f5f6"),
+    ("Parallel", "f5f4"),
+    ("Cow", "f5d6c5"),
+    ("Tiger", "f5d6c3d3c4"),
+];
+
+/// The symmetries of the starting position.
+const SYMMETRIES: [Symmetry; 4] = [
+    Symmetry::Identity,
+    Symmetry::Transpose,
+    Symmetry::Rotate180,
+    Symmetry::AntiTranspose,
+];
+
+/// The first move every line of a book starts with.
+const F5: (usize, usize) = (5, 4);
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum BookError {
+    #[error("{0} isn't written as squares: {1}")]
+    Notation(String, String),
+    #[error("{0} doesn't open on f5")]
+    FirstMove(String),
+    #[error("{0} can't be played from the starting position")]
+    Illegal(String),
+}
+
+/// Named openings, each the moves that define it, opening on f5. The default book is the
+/// built-in one; others are read from JSON objects of names and moves in the standard
+/// notation, e.g. `{"Tiger": "f5d6c3d3c4"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "HashMap<String, String>")]
+pub struct Book {
+    lines: Vec<(String, Vec<(usize, usize)>)>,
+}
+
+impl Default for Book {
+    fn default() -> Self {
+        Self::new(BOOK.iter().map(|&(name, line)| (name.into(), line.into())))
+            .expect("the built-in book is valid")
+    }
+}
+
+impl TryFrom<HashMap<String, String>> for Book {
+    type Error = BookError;
+
+    fn try_from(lines: HashMap<String, String>) -> Result<Self, Self::Error> {
+        Self::new(lines)
+    }
+}
+
+impl Book {
+    /// A book of the specified openings, as names and moves in the standard notation.
+    ///
+    /// # Errors
+    /// Returns an error if a line isn't written properly, doesn't open on f5, or can't be
+    /// played from the starting position.
+    pub fn new(lines: impl IntoIterator<Item = (String, String)>) -> Result<Self, BookError> {
+        let lines = lines
+            .into_iter()
+            .map(|(name, line)| {
+                let moves = squares(&line)
+                    .ok_or_else(|| BookError::Notation(name.clone(), line.clone()))?;
+                if moves.first() != Some(&F5) {
+                    return Err(BookError::FirstMove(name));
+                }
+                let mut game = Game::new();
+                for &(x, y) in &moves {
+                    if game.place(x, y, game.turn()).is_err() {
+                        return Err(BookError::Illegal(name));
+                    }
+                }
+                Ok((name, moves))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { lines })
+    }
+
+    /// The name of the most specific opening in the book the specified moves, played from
+    /// the starting position, follow, if they follow any.
+    #[must_use]
+    pub fn name(&self, history: &[(usize, usize)]) -> Option<&str> {
+        let &(x, y) = history.first()?;
+        // Only one of the symmetries takes the first move onto f5.
+        let symmetry = SYMMETRIES
+            .iter()
+            .find(|symmetry| symmetry.apply(x, y) == F5)?;
+        self.lines
+            .iter()
+            .filter(|(_, line)| {
+                line.len() <= history.len()
+                    && line
+                        .iter()
+                        .zip(history)
+                        .all(|(&square, &(x, y))| symmetry.apply(x, y) == square)
+            })
+            .max_by_key(|(_, line)| line.len())
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// The squares of a line of moves written one after another in the standard notation, or
+/// `None` if it isn't written properly.
+fn squares(line: &str) -> Option<Vec<(usize, usize)>> {
+    if !line.is_ascii() || !line.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..line.len())
+        .step_by(2)
+        .map(|i| line[i..i + 2].parse::<Coord>().ok().map(Into::into))
+        .collect()
+}
+
+/// The name of the most specific opening in the built-in book the specified moves, played
+/// from the starting position, follow, if they follow any.
+#[must_use]
+pub fn name(history: &[(usize, usize)]) -> Option<&'static str> {
+    static BUILT_IN: OnceLock<Book> = OnceLock::new();
+    BUILT_IN.get_or_init(Book::default).name(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{name, squares, Book, BookError, BOOK, SYMMETRIES};
+    use crate::Game;
+
+    #[test]
+    fn book() {
+        // Every line in the book can be played, and is named after itself.
+        for (expected, line) in BOOK {
+            let mut game = Game::new();
+            for (x, y) in squares(line).unwrap() {
+                game.place(x, y, game.turn()).unwrap();
+            }
+            assert_eq!(game.opening_name(), Some(*expected));
+        }
+    }
+
+    #[test]
+    fn books() {
+        let book: Book = serde_json::from_str(r#"{"Rose": "f5d6c5f4e3"}"#).unwrap();
+        let rose = squares("f5d6c5f4e3f6").unwrap();
+        assert_eq!(book.name(&rose), Some("Rose"));
+        assert_eq!(book.name(&rose[..3]), None);
+        assert_eq!(Book::default().name(&rose[..3]), Some("Cow"));
+        let invalid =
+            |line: &str| Book::new([(String::from("Bad"), String::from(line))]).unwrap_err();
+        assert!(matches!(invalid("f5d"), BookError::Notation(..)));
+        assert!(matches!(invalid("f5z9"), BookError::Notation(..)));
+        assert_eq!(invalid("d3c5"), BookError::FirstMove("Bad".into()));
+        assert_eq!(invalid("f5f5"), BookError::Illegal("Bad".into()));
+        assert!(serde_json::from_str::<Book>(r#"{"Bad": "a1"}"#).is_err());
+    }
+
+    #[test]
+    fn names() {
+        let tiger = squares("f5d6c3d3c4f4").unwrap();
+        assert_eq!(name(&tiger[..1]), None);
+        assert_eq!(name(&tiger[..2]), Some("Perpendicular"));
+        assert_eq!(name(&tiger[..4]), Some("Perpendicular"));
+        assert_eq!(name(&tiger), Some("Tiger"));
+        // The same opening played from any first move has the same name.
+        for symmetry in SYMMETRIES {
+            let moves: Vec<_> = tiger.iter().map(|&(x, y)| symmetry.apply(x, y)).collect();
+            assert_eq!(name(&moves), Some("Tiger"));
+        }
+    }
+}
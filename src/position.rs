@@ -0,0 +1,245 @@
+use crate::{board::Board, Game, GameSettings, Piece};
+use othello_rules_types::Position;
+
+/// The squares the four starting pieces are placed on. Pieces are flipped but never removed,
+/// so these are filled in every game.
+const CENTER: [(usize, usize); 4] = [(3, 3), (4, 3), (3, 4), (4, 4)];
+/// The corners given to black in a handicap game, in the order they're given: a1, h8, h1 and
+/// then a8.
+const HANDICAP_CORNERS: [(usize, usize); 4] = [(0, 0), (7, 7), (7, 0), (0, 7)];
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum PositionError {
+    #[error("board square ({0}, {1}) is out of bounds")]
+    OutOfBounds(usize, usize),
+    #[error("center square ({0}, {1}) is empty")]
+    EmptyCenter(usize, usize),
+    #[error("board square ({0}, {1}) is cut off from the center")]
+    Disconnected(usize, usize),
+}
+
+/// Sets up a game from a position rather than the moves leading to it, for puzzles, tests and
+/// analysis. The board starts out empty, with black to move.
+///
+/// Positions are checked for what every game in play has in common: the center squares are
+/// filled, and since every piece is placed next to another, all the pieces are connected to
+/// them, or to the corners black was given as a handicap under the settings. Anything else is
+/// taken as given, so the game built may already be over. Its history is empty.
+#[derive(Debug, Clone)]
+pub struct PositionBuilder {
+    /// The squares set so far, in order, so that later ones win.
+    squares: Vec<(usize, usize, Option<Piece>)>,
+    turn: Piece,
+    settings: GameSettings,
+}
+
+impl Default for PositionBuilder {
+    fn default() -> Self {
+        Self {
+            squares: Vec::new(),
+            turn: Piece::Black,
+            settings: GameSettings::default(),
+        }
+    }
+}
+
+/// Start from the specified game's position, to change it from there.
+impl From<&Game> for PositionBuilder {
+    fn from(game: &Game) -> Self {
+        Self {
+            squares: game
+                .squares()
+                .map(|((x, y), piece)| (x, y, piece))
+                .collect(),
+            turn: game.turn(),
+            settings: *game.settings(),
+        }
+    }
+}
+
+impl PositionBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The position a game with the specified settings starts from: the four pieces in the
+    /// center, and black on as many corners as its handicap gives them.
+    #[must_use]
+    pub fn start(settings: GameSettings) -> Self {
+        let mut builder = Self::new()
+            .settings(settings)
+            .piece(3, 3, Piece::White)
+            .piece(4, 3, Piece::Black)
+            .piece(3, 4, Piece::Black)
+            .piece(4, 4, Piece::White);
+        for (x, y) in handicap_corners(&settings) {
+            builder = builder.piece(x, y, Piece::Black);
+        }
+        builder
+    }
+
+    /// Put the specified piece on the specified square, replacing whatever was there.
+    #[must_use]
+    pub fn piece(mut self, x: usize, y: usize, piece: Piece) -> Self {
+        self.squares.push((x, y, Some(piece)));
+        self
+    }
+
+    /// Empty the specified square.
+    #[must_use]
+    pub fn empty(mut self, x: usize, y: usize) -> Self {
+        self.squares.push((x, y, None));
+        self
+    }
+
+    #[must_use]
+    pub fn turn(mut self, piece: Piece) -> Self {
+        self.turn = piece;
+        self
+    }
+
+    #[must_use]
+    pub fn settings(mut self, settings: GameSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// # Errors
+    /// Returns an error if a square is out of bounds, or the position couldn't come about in
+    /// play.
+    pub fn build(self) -> Result<Game, PositionError> {
+        let width = Board::width();
+        let mut board = vec![None; width * width];
+        for (x, y, piece) in self.squares {
+            if x >= width || y >= width {
+                return Err(PositionError::OutOfBounds(x, y));
+            }
+            board[x + y * width] = piece;
+        }
+        for (x, y) in CENTER {
+            if board[x + y * width].is_none() {
+                return Err(PositionError::EmptyCenter(x, y));
+            }
+        }
+        // Spread out from the center (and any handicap corners) through the pieces next to
+        // each other.
+        let mut reached = vec![false; width * width];
+        let mut pending = CENTER.to_vec();
+        pending.extend(
+            handicap_corners(&self.settings).filter(|&(x, y)| board[x + y * width].is_some()),
+        );
+        while let Some((x, y)) = pending.pop() {
+            if std::mem::replace(&mut reached[x + y * width], true) {
+                continue;
+            }
+            for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                for ny in y.saturating_sub(1)..=(y + 1).min(width - 1) {
+                    if board[nx + ny * width].is_some() && !reached[nx + ny * width] {
+                        pending.push((nx, ny));
+                    }
+                }
+            }
+        }
+        if let Some(i) = (0..width * width).find(|&i| board[i].is_some() && !reached[i]) {
+            return Err(PositionError::Disconnected(i % width, i / width));
+        }
+        Ok(Game::from(Position {
+            board,
+            turn: self.turn,
+            history: Vec::new(),
+            settings: self.settings,
+        }))
+    }
+}
+
+/// The corners the specified settings give black before the game starts.
+fn handicap_corners(settings: &GameSettings) -> impl Iterator<Item = (usize, usize)> {
+    HANDICAP_CORNERS
+        .into_iter()
+        .take(usize::from(settings.handicap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PositionBuilder, PositionError};
+    use crate::{Game, GameSettings, Piece};
+
+    #[test]
+    fn start() {
+        let game = PositionBuilder::new()
+            .piece(3, 3, Piece::White)
+            .piece(4, 3, Piece::Black)
+            .piece(3, 4, Piece::Black)
+            .piece(4, 4, Piece::White)
+            .build();
+        assert_eq!(game, Ok(Game::new()));
+    }
+
+    #[test]
+    fn edits() {
+        let mut game = Game::new();
+        game.place(5, 4, Piece::Black).unwrap();
+        // A corner for white to take.
+        let mut puzzle = PositionBuilder::from(&game)
+            .piece(5, 5, Piece::White)
+            .piece(6, 6, Piece::Black)
+            .piece(5, 4, Piece::White)
+            .turn(Piece::White)
+            .build()
+            .unwrap();
+        assert_eq!(puzzle.turn(), Piece::White);
+        assert!(puzzle.history().is_empty());
+        assert_eq!(puzzle.piece(5, 4), Some(Piece::White));
+        puzzle.place(7, 7, Piece::White).unwrap();
+        assert_eq!(puzzle.piece(6, 6), Some(Piece::White));
+        // Unchanged games come back as they were, less their history.
+        let mut copy = PositionBuilder::from(&game).build().unwrap();
+        assert_eq!(copy.score(), game.score());
+        assert_eq!(copy.moves(Piece::White), game.moves(Piece::White));
+    }
+
+    #[test]
+    fn invalid() {
+        let start = || PositionBuilder::from(&Game::new());
+        assert_eq!(
+            start().piece(8, 0, Piece::Black).build().unwrap_err(),
+            PositionError::OutOfBounds(8, 0)
+        );
+        assert_eq!(
+            start().empty(4, 4).build().unwrap_err(),
+            PositionError::EmptyCenter(4, 4)
+        );
+        assert_eq!(
+            start().piece(0, 0, Piece::Black).build().unwrap_err(),
+            PositionError::Disconnected(0, 0)
+        );
+        assert!(start().piece(2, 2, Piece::Black).build().is_ok());
+    }
+
+    #[test]
+    fn handicap() {
+        let settings = GameSettings {
+            handicap: 2,
+            ..GameSettings::default()
+        };
+        let mut game = PositionBuilder::start(settings).build().unwrap();
+        assert_eq!(game.score(), (4, 2));
+        assert_eq!(game.piece(0, 0), Some(Piece::Black));
+        assert_eq!(game.piece(7, 7), Some(Piece::Black));
+        assert_eq!(game.piece(7, 0), None);
+        assert_eq!(game.moves(Piece::Black), Game::new().moves(Piece::Black));
+        // Only the corners the handicap gives are exempt from being connected.
+        assert_eq!(
+            PositionBuilder::start(settings)
+                .piece(7, 0, Piece::Black)
+                .build()
+                .unwrap_err(),
+            PositionError::Disconnected(7, 0)
+        );
+        assert_eq!(
+            PositionBuilder::start(GameSettings::default()).build(),
+            Ok(Game::new())
+        );
+    }
+}
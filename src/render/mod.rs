@@ -0,0 +1,328 @@
+use crate::{Game, Piece};
+use std::fmt::{self, Write};
+
+#[cfg(feature = "png")]
+mod png;
+
+/// A colour, as its red, green and blue components.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// The colours a board is drawn in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// Around the board.
+    pub frame: Color,
+    /// The squares.
+    pub board: Color,
+    /// The lines between the squares.
+    pub grid: Color,
+    pub black: Color,
+    pub white: Color,
+    /// The outline of every piece.
+    pub outline: Color,
+    /// The mark on the square of the last move played.
+    pub marker: Color,
+}
+
+impl Theme {
+    /// Black and white pieces on a green board, as the game is usually played.
+    pub const CLASSIC: Self = Self {
+        frame: Color(0x2b, 0x4a, 0x30),
+        board: Color(0x3a, 0x7d, 0x44),
+        grid: Color(0x1c, 0x33, 0x20),
+        black: Color(0x11, 0x11, 0x11),
+        white: Color(0xf4, 0xf4, 0xf4),
+        outline: Color(0x11, 0x11, 0x11),
+        marker: Color(0xd9, 0x40, 0x40),
+    };
+    /// A dim board that sits well on dark backgrounds.
+    pub const DARK: Self = Self {
+        frame: Color(0x16, 0x18, 0x1d),
+        board: Color(0x2a, 0x2e, 0x37),
+        grid: Color(0x0e, 0x10, 0x14),
+        black: Color(0x05, 0x05, 0x05),
+        white: Color(0xdc, 0xdc, 0xdc),
+        outline: Color(0x05, 0x05, 0x05),
+        marker: Color(0xe0, 0x9b, 0x3d),
+    };
+    /// Black on white, for printing (e.g. analysis reports and puzzle sheets).
+    pub const PRINT: Self = Self {
+        frame: Color(0xff, 0xff, 0xff),
+        board: Color(0xff, 0xff, 0xff),
+        grid: Color(0x00, 0x00, 0x00),
+        black: Color(0x00, 0x00, 0x00),
+        white: Color(0xff, 0xff, 0xff),
+        outline: Color(0x00, 0x00, 0x00),
+        marker: Color(0x80, 0x80, 0x80),
+    };
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::CLASSIC
+    }
+}
+
+/// Something drawn on a board, in pixels from the top left of the picture.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Shape {
+    Rect {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        fill: Color,
+    },
+    Circle {
+        /// What the circle shows, for telling circles apart in SVGs.
+        class: &'static str,
+        cx: usize,
+        cy: usize,
+        r: usize,
+        fill: Color,
+        /// The colour of a line a pixel wide around the circle, centred on its edge.
+        stroke: Option<Color>,
+    },
+}
+
+/// Draws games' boards as pictures: as SVG images or, with the `png` feature, as PNG images,
+/// which come out the same whichever they're drawn as.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Renderer {
+    theme: Theme,
+    /// The width of each square, in pixels.
+    square: usize,
+    /// The width of the frame around the board, in pixels.
+    frame: usize,
+    /// Whether the last move played is marked.
+    last_move: bool,
+}
+
+impl Renderer {
+    /// A renderer that draws in the classic theme, with squares 60 pixels wide and the last
+    /// move marked.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            theme: Theme::default(),
+            square: 60,
+            frame: 20,
+            last_move: true,
+        }
+    }
+
+    /// Draw in the specified theme.
+    #[must_use]
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Draw each square the specified number of pixels wide, with the frame a third as wide.
+    #[must_use]
+    pub fn with_square_size(mut self, pixels: usize) -> Self {
+        self.square = pixels;
+        self.frame = pixels / 3;
+        self
+    }
+
+    /// Leave the last move played unmarked (e.g. for puzzles, which shouldn't give away how
+    /// the position came about).
+    #[must_use]
+    pub fn without_last_move(mut self) -> Self {
+        self.last_move = false;
+        self
+    }
+
+    /// The width (and height) in pixels of the picture of the specified game's board.
+    #[must_use]
+    pub fn size(&self, game: &Game) -> usize {
+        width(game) * self.square + 2 * self.frame
+    }
+
+    /// Draw the specified game's board as an SVG image.
+    #[must_use]
+    pub fn svg(&self, game: &Game) -> String {
+        let size = self.size(game);
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\" \
+             viewBox=\"0 0 {size} {size}\">"
+        );
+        for shape in self.shapes(game) {
+            let _ = match shape {
+                Shape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    fill,
+                } => write!(
+                    svg,
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" \
+                     fill=\"{fill}\"/>"
+                ),
+                Shape::Circle {
+                    class,
+                    cx,
+                    cy,
+                    r,
+                    fill,
+                    stroke,
+                } => write!(
+                    svg,
+                    "<circle class=\"{class}\" cx=\"{cx}\" cy=\"{cy}\" r=\"{r}\" \
+                     fill=\"{fill}\"{}/>",
+                    stroke.map_or_else(String::new, |stroke| format!(" stroke=\"{stroke}\""))
+                ),
+            };
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+
+    /// Draw the specified game's board as a PNG image.
+    #[cfg(feature = "png")]
+    #[must_use]
+    pub fn png(&self, game: &Game) -> Vec<u8> {
+        let size = self.size(game);
+        png::encode(size, size, &png::rasterize(size, size, &self.shapes(game)))
+    }
+
+    /// What makes up the picture of the specified game's board, from the bottom up.
+    fn shapes(&self, game: &Game) -> Vec<Shape> {
+        let theme = &self.theme;
+        let (square, frame) = (self.square, self.frame);
+        let width = width(game);
+        let board = width * square;
+        let size = board + 2 * frame;
+        let mut shapes = vec![
+            Shape::Rect {
+                x: 0,
+                y: 0,
+                width: size,
+                height: size,
+                fill: theme.frame,
+            },
+            Shape::Rect {
+                x: frame,
+                y: frame,
+                width: board,
+                height: board,
+                fill: theme.board,
+            },
+        ];
+        // The lines between the squares, and around the outside of them.
+        for line in 0..=width {
+            let offset = frame + line * square;
+            shapes.extend([
+                Shape::Rect {
+                    x: offset,
+                    y: frame,
+                    width: 1,
+                    height: board,
+                    fill: theme.grid,
+                },
+                Shape::Rect {
+                    x: frame,
+                    y: offset,
+                    width: board,
+                    height: 1,
+                    fill: theme.grid,
+                },
+            ]);
+        }
+        let center = |coordinate: usize| frame + coordinate * square + square / 2;
+        for ((x, y), piece) in game.squares() {
+            let (class, fill) = match piece {
+                Some(Piece::Black) => ("piece black", theme.black),
+                Some(Piece::White) => ("piece white", theme.white),
+                None => continue,
+            };
+            shapes.push(Shape::Circle {
+                class,
+                cx: center(x),
+                cy: center(y),
+                r: square * 2 / 5,
+                fill,
+                stroke: Some(theme.outline),
+            });
+        }
+        if let Some(&(x, y)) = game.history().last().filter(|_| self.last_move) {
+            shapes.push(Shape::Circle {
+                class: "last",
+                cx: center(x),
+                cy: center(y),
+                r: square / 12,
+                fill: theme.marker,
+                stroke: None,
+            });
+        }
+        shapes
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How many squares wide the specified game's board is.
+fn width(game: &Game) -> usize {
+    game.squares().count().isqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, Renderer, Theme};
+    use crate::{Game, Piece};
+
+    #[test]
+    fn colors() {
+        assert_eq!(Color(0x3a, 0x7d, 0x44).to_string(), "#3a7d44");
+        assert_eq!(Color(0, 0, 0).to_string(), "#000000");
+    }
+
+    #[test]
+    fn svg() {
+        let renderer = Renderer::new();
+        let mut game = Game::new();
+        let drawn = renderer.svg(&game);
+        assert_eq!(renderer.size(&game), 8 * 60 + 2 * 20);
+        assert!(drawn
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"520\" height=\"520\""));
+        assert!(drawn.ends_with("</svg>"));
+        assert_eq!(drawn.matches("class=\"piece black\"").count(), 2);
+        assert_eq!(drawn.matches("class=\"piece white\"").count(), 2);
+        // Nothing's been played yet, so there's no last move to mark.
+        assert!(!drawn.contains("class=\"last\""));
+        game.place(5, 4, Piece::Black).unwrap();
+        let drawn = renderer.svg(&game);
+        assert_eq!(drawn.matches("class=\"piece black\"").count(), 4);
+        assert_eq!(drawn.matches("class=\"piece white\"").count(), 1);
+        assert!(drawn.contains("<circle class=\"last\" cx=\"350\" cy=\"290\""));
+        assert!(!Renderer::new()
+            .without_last_move()
+            .svg(&game)
+            .contains("class=\"last\""));
+    }
+
+    #[test]
+    fn themes() {
+        let game = Game::new();
+        let renderer = Renderer::new()
+            .with_theme(Theme::PRINT)
+            .with_square_size(30);
+        assert_eq!(renderer.size(&game), 8 * 30 + 2 * 10);
+        let drawn = renderer.svg(&game);
+        assert!(drawn.contains(&format!("fill=\"{}\"", Theme::PRINT.board)));
+        assert!(!drawn.contains(&Theme::CLASSIC.board.to_string()));
+    }
+}
@@ -0,0 +1,414 @@
+//! Drawing shapes into pixels and writing them out as PNG (RFC 2083). Boards are mostly flat
+//! colour, so the pixels are compressed by referring back to the pixel before or the row above
+//! wherever they repeat, which is all the compression they need.
+
+use super::{Color, Shape};
+
+/// How many samples are taken across (and down) each pixel on the edge of a circle, to smooth
+/// it out.
+const SAMPLES: usize = 4;
+/// How many bytes each pixel takes: one for each of red, green and blue.
+const PIXEL: usize = 3;
+/// The farthest back a compressed stream can refer.
+const WINDOW: usize = 32_768;
+/// The longest run of bytes a compressed stream can repeat at once.
+const MAX_MATCH: usize = 258;
+/// The shortest run of bytes worth repeating rather than writing out.
+const MIN_MATCH: usize = 3;
+
+/// The shortest length each length code stands for, from code 257 on, along with how many
+/// extra bits follow it.
+const LENGTHS: [(usize, u32); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+/// The shortest distance each distance code stands for, along with how many extra bits follow
+/// it.
+const DISTANCES: [(usize, u32); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+
+/// Draw the specified shapes, in order, into a picture of the specified size, returning its
+/// pixels row by row from the top left.
+pub(super) fn rasterize(width: usize, height: usize, shapes: &[Shape]) -> Vec<Color> {
+    let mut pixels = vec![Color(0, 0, 0); width * height];
+    for shape in shapes {
+        match *shape {
+            Shape::Rect {
+                x,
+                y,
+                width: w,
+                height: h,
+                fill,
+            } => {
+                for row in y.min(height)..(y + h).min(height) {
+                    pixels[row * width + x.min(width)..row * width + (x + w).min(width)].fill(fill);
+                }
+            }
+            Shape::Circle {
+                cx,
+                cy,
+                r,
+                fill,
+                stroke,
+                ..
+            } => {
+                // Distances are measured in eighths of a pixel, so that every sample falls on a
+                // whole number of them. Strokes reach half a pixel either side of the edge.
+                let (edge, outer) = match stroke {
+                    Some(_) => ((8 * r).saturating_sub(4), 8 * r + 4),
+                    None => (8 * r, 8 * r),
+                };
+                let reach = outer.div_ceil(8);
+                for py in cy.saturating_sub(reach)..(cy + reach).min(height) {
+                    for px in cx.saturating_sub(reach)..(cx + reach).min(width) {
+                        let (mut inside, mut on_edge) = (0, 0);
+                        for sy in 0..SAMPLES {
+                            for sx in 0..SAMPLES {
+                                // The middle of each of the pixel's samples.
+                                let dx = (8 * px + 1 + 2 * sx).abs_diff(8 * cx);
+                                let dy = (8 * py + 1 + 2 * sy).abs_diff(8 * cy);
+                                let distance = dx * dx + dy * dy;
+                                if distance <= edge * edge {
+                                    inside += 1;
+                                } else if distance <= outer * outer {
+                                    on_edge += 1;
+                                }
+                            }
+                        }
+                        let pixel = &mut pixels[py * width + px];
+                        *pixel = blend(*pixel, fill, inside);
+                        if let Some(stroke) = stroke {
+                            *pixel = blend(*pixel, stroke, on_edge);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    pixels
+}
+
+/// Paint the specified colour over another, covering as many of the pixel's samples as
+/// specified.
+fn blend(under: Color, over: Color, covered: usize) -> Color {
+    let total = SAMPLES * SAMPLES;
+    let mix = |under: u8, over: u8| {
+        let mixed = (usize::from(under) * (total - covered) + usize::from(over) * covered) / total;
+        u8::try_from(mixed).unwrap_or(u8::MAX)
+    };
+    Color(
+        mix(under.0, over.0),
+        mix(under.1, over.1),
+        mix(under.2, over.2),
+    )
+}
+
+/// Write out the specified pixels, row by row from the top left of a picture of the specified
+/// size, as a PNG image.
+pub(super) fn encode(width: usize, height: usize, pixels: &[Color]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(height * (1 + width * PIXEL));
+    for row in pixels.chunks(width.max(1)) {
+        // Rows go through no filter.
+        raw.push(0);
+        for pixel in row {
+            raw.extend([pixel.0, pixel.1, pixel.2]);
+        }
+    }
+    let dimension = |length: usize| u32::try_from(length).unwrap_or(u32::MAX).to_be_bytes();
+    let mut header = Vec::with_capacity(13);
+    header.extend(dimension(width));
+    header.extend(dimension(height));
+    // Eight bits for each of red, green and blue, compressed, unfiltered and not interlaced.
+    header.extend([8, 2, 0, 0, 0]);
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, *b"IHDR", &header);
+    chunk(&mut png, *b"IDAT", &zlib(&raw, 1 + width * PIXEL));
+    chunk(&mut png, *b"IEND", &[]);
+    png
+}
+
+/// Append a chunk of the specified type holding the specified data to a PNG image.
+fn chunk(png: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    png.extend(u32::try_from(data.len()).unwrap_or(u32::MAX).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Compress the specified data as a zlib stream (RFC 1950), looking for repeats of the
+/// previous pixel and of the specified distance back (the length of a row).
+fn zlib(data: &[u8], row: usize) -> Vec<u8> {
+    let mut bits = Bits::default();
+    // One block, the last, compressed with the fixed codes.
+    bits.push(1, 1);
+    bits.push(1, 2);
+    let mut i = 0;
+    while i < data.len() {
+        let (distance, length) = [PIXEL, row]
+            .into_iter()
+            .filter(|&distance| distance <= i && distance <= WINDOW)
+            .map(|distance| {
+                let length = (0..MAX_MATCH.min(data.len() - i))
+                    .take_while(|&k| data[i + k] == data[i + k - distance])
+                    .count();
+                (distance, length)
+            })
+            .max_by_key(|&(_, length)| length)
+            .unwrap_or_default();
+        if length >= MIN_MATCH {
+            bits.repeat(length, distance);
+            i += length;
+        } else {
+            bits.literal(usize::from(data[i]));
+            i += 1;
+        }
+    }
+    // The end of the block.
+    bits.literal(256);
+    let mut zlib = vec![0x78, 0x01];
+    zlib.extend(bits.finish());
+    zlib.extend(adler32(data).to_be_bytes());
+    zlib
+}
+
+/// Bits written from the least significant of each byte up, as compressed streams are.
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    current: u8,
+    filled: u32,
+}
+
+impl Bits {
+    /// Write the lowest `count` bits of `value`, lowest first.
+    fn push(&mut self, value: u32, count: u32) {
+        for bit in 0..count {
+            self.current |= u8::from((value >> bit) & 1 == 1) << self.filled;
+            self.filled += 1;
+            if self.filled == 8 {
+                self.bytes.push(self.current);
+                (self.current, self.filled) = (0, 0);
+            }
+        }
+    }
+
+    /// Write a Huffman code of `count` bits, which are written highest first.
+    fn code(&mut self, code: u32, count: u32) {
+        self.push(code.reverse_bits() >> (32 - count), count);
+    }
+
+    /// Write a literal byte, a length or the end of a block, in the fixed codes.
+    fn literal(&mut self, value: usize) {
+        let value = u32::try_from(value).unwrap_or_default();
+        match value {
+            0..=143 => self.code(0x30 + value, 8),
+            144..=255 => self.code(0x190 + value - 144, 9),
+            256..=279 => self.code(value - 256, 7),
+            _ => self.code(0xc0 + value - 280, 8),
+        }
+    }
+
+    /// Write a repeat of the specified number of bytes from the specified distance back.
+    fn repeat(&mut self, length: usize, distance: usize) {
+        let (code, (base, extra)) = position(&LENGTHS, length);
+        self.literal(257 + code);
+        self.push(u32::try_from(length - base).unwrap_or_default(), extra);
+        let (code, (base, extra)) = position(&DISTANCES, distance);
+        self.code(u32::try_from(code).unwrap_or_default(), 5);
+        self.push(u32::try_from(distance - base).unwrap_or_default(), extra);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.filled > 0 {
+            self.bytes.push(self.current);
+        }
+        self.bytes
+    }
+}
+
+/// The code standing for the specified value in a table of codes' shortest values, along with
+/// that entry of the table.
+fn position(table: &[(usize, u32)], value: usize) -> (usize, (usize, u32)) {
+    let code = table.partition_point(|&(base, _)| base <= value) - 1;
+    (code, table[code])
+}
+
+/// The CRC-32 of the specified bytes, as each chunk of a PNG image is checked with.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(u32::MAX, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// The Adler-32 checksum of the specified bytes, as a zlib stream is checked with.
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1, 0), |(a, b), &byte| {
+        let a = (a + u32::from(byte)) % 65_521;
+        (a, (b + a) % 65_521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, crc32, encode, rasterize, Color, Shape};
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn shapes() {
+        let (red, blue) = (Color(255, 0, 0), Color(0, 0, 255));
+        let pixels = rasterize(
+            20,
+            20,
+            &[
+                Shape::Rect {
+                    x: 0,
+                    y: 0,
+                    width: 20,
+                    height: 20,
+                    fill: red,
+                },
+                Shape::Circle {
+                    class: "piece",
+                    cx: 10,
+                    cy: 10,
+                    r: 5,
+                    fill: blue,
+                    stroke: None,
+                },
+            ],
+        );
+        assert_eq!(pixels[0], red);
+        assert_eq!(pixels[10 * 20 + 10], blue);
+        // Pixels on the edge are somewhere in between.
+        let edge = pixels[13 * 20 + 13];
+        assert_ne!(edge, red);
+        assert_ne!(edge, blue);
+        assert_eq!(pixels[10 * 20 + 16], red);
+    }
+
+    #[test]
+    fn png() {
+        let pixels = vec![Color(58, 125, 68); 64 * 48];
+        let png = encode(64, 48, &pixels);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x40\0\0\0\x30"));
+        assert!(png.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+        // A picture of one colour is little more than a single pixel repeated.
+        assert!(png.len() < pixels.len() / 10);
+    }
+
+    #[test]
+    fn round_trip() {
+        let (width, height) = (37, 29);
+        // Squares of one colour to repeat, between others that change every pixel.
+        let pixels: Vec<_> = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                if (x / 8 + y / 8) % 2 == 0 {
+                    Color(58, 125, 68)
+                } else {
+                    let channel = |value: usize| u8::try_from(value % 256).unwrap();
+                    Color(channel(x * 7), channel(y * 5), channel(x ^ y))
+                }
+            })
+            .collect();
+        let png = encode(width, height, &pixels);
+        // Walk the chunks, checking each one's CRC and gathering up the image data.
+        let mut data = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length =
+                usize::try_from(u32::from_be_bytes(rest[..4].try_into().unwrap())).unwrap();
+            let (chunk, crc) = rest[4..].split_at(4 + length);
+            assert_eq!(
+                crc32(chunk),
+                u32::from_be_bytes(crc[..4].try_into().unwrap())
+            );
+            if &chunk[..4] == b"IDAT" {
+                data.extend(&chunk[4..]);
+            }
+            rest = &crc[4..];
+        }
+        let raw = miniz_oxide::inflate::decompress_to_vec_zlib(&data).unwrap();
+        let decoded: Vec<_> = raw
+            .chunks(1 + width * 3)
+            .flat_map(|row| {
+                assert_eq!(row[0], 0);
+                row[1..]
+                    .chunks(3)
+                    .map(|pixel| Color(pixel[0], pixel[1], pixel[2]))
+            })
+            .collect();
+        assert_eq!(decoded, pixels);
+    }
+}
@@ -0,0 +1,210 @@
+//! Moving long-finished games out of `game`, so that the table games are played from stays
+//! small. Archived games are kept in `archived_game`, and read back through the
+//! `finished_game` view alongside those that haven't been archived yet, or one at a time
+//! through [`find`]. Games are archived by the `archival` job.
+
+use crate::server::{
+    entities::{
+        archived_game, finished_game, game,
+        prelude::{ArchivedGame, Game},
+    },
+    state::AppState,
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QuerySelect,
+    TransactionTrait,
+};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How many games are moved at a time, each batch in a transaction of its own.
+const ARCHIVE_BATCH: u64 = 500;
+
+/// Move the games that finished longer ago than the specified age into the archive, returning
+/// how many were moved.
+pub(super) async fn archive_older_than(state: &AppState, age: Duration) -> Result<u64, DbErr> {
+    let Some(cutoff) = chrono::Duration::from_std(age)
+        .ok()
+        .and_then(|age| Utc::now().checked_sub_signed(age))
+    else {
+        return Ok(0);
+    };
+    let mut archived = 0;
+    loop {
+        let txn = state.database.begin().await?;
+        let games = Game::find()
+            .filter(game::Column::Ended.eq(true))
+            .filter(game::Column::EndedAt.lt(cutoff.fixed_offset()))
+            .limit(ARCHIVE_BATCH)
+            .all(&txn)
+            .await?;
+        if games.is_empty() {
+            return Ok(archived);
+        }
+        let moved = games.len() as u64;
+        let ids: Vec<_> = games.iter().map(|game| game.id).collect();
+        ArchivedGame::insert_many(games.into_iter().map(archived_game::ActiveModel::from))
+            .exec(&txn)
+            .await?;
+        Game::delete_many()
+            .filter(game::Column::Id.is_in(ids))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+        archived += moved;
+        if moved < ARCHIVE_BATCH {
+            return Ok(archived);
+        }
+    }
+}
+
+/// Find a game by its ID, whether or not it's been archived.
+pub(super) async fn find<C: ConnectionTrait>(
+    db: &C,
+    id: Uuid,
+) -> Result<Option<game::Model>, DbErr> {
+    if let Some(game) = Game::find_by_id(id).one(db).await? {
+        return Ok(Some(game));
+    }
+    Ok(ArchivedGame::find_by_id(id)
+        .one(db)
+        .await?
+        .map(game::Model::from))
+}
+
+impl From<game::Model> for archived_game::ActiveModel {
+    fn from(game: game::Model) -> Self {
+        Self {
+            id: ActiveValue::set(game.id),
+            host: ActiveValue::set(game.host),
+            guest: ActiveValue::set(game.guest),
+            challenge: ActiveValue::set(game.challenge),
+            result: ActiveValue::set(game.result),
+            settings: ActiveValue::set(game.settings),
+            state: ActiveValue::set(game.state),
+            opponent: ActiveValue::set(game.opponent),
+            arena: ActiveValue::set(game.arena),
+            host_opponent: ActiveValue::set(game.host_opponent),
+            ended_at: ActiveValue::set(game.ended_at),
+            mode: ActiveValue::set(game.mode),
+            host_color: ActiveValue::set(game.host_color),
+            archived_at: ActiveValue::not_set(),
+        }
+    }
+}
+
+/// Archived games read as they did before they were archived, with nobody's turn running.
+impl From<archived_game::Model> for game::Model {
+    fn from(game: archived_game::Model) -> Self {
+        Self {
+            id: game.id,
+            host: game.host,
+            guest: game.guest,
+            pending: false,
+            ended: true,
+            challenge: game.challenge,
+            result: game.result,
+            settings: game.settings,
+            state: game.state,
+            turn_started_at: None,
+            opponent: game.opponent,
+            arena: game.arena,
+            host_opponent: game.host_opponent,
+            ended_at: game.ended_at,
+            mode: game.mode,
+            host_color: game.host_color,
+        }
+    }
+}
+
+impl From<finished_game::Model> for game::Model {
+    fn from(game: finished_game::Model) -> Self {
+        Self {
+            id: game.id,
+            host: game.host,
+            guest: game.guest,
+            pending: game.pending,
+            ended: game.ended,
+            challenge: game.challenge,
+            result: game.result,
+            settings: game.settings,
+            state: game.state,
+            turn_started_at: game.turn_started_at,
+            opponent: game.opponent,
+            arena: game.arena,
+            host_opponent: game.host_opponent,
+            ended_at: game.ended_at,
+            mode: game.mode,
+            host_color: game.host_color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{
+        self, correspondence,
+        entities::{game, prelude::Game},
+        handlers::Response,
+        helpers,
+    };
+    use chrono::{Duration, Utc};
+    use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn archive() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        Client::authenticated(&[&host, &guest], &url, true).await;
+        let black = helpers::get_user(&state, &host, true).await.unwrap().id;
+        let white = helpers::get_user(&state, &guest, true).await.unwrap().id;
+        let finished = |ended_at| game::ActiveModel {
+            id: ActiveValue::set(Uuid::now_v7()),
+            host: ActiveValue::set(black.to_string()),
+            guest: ActiveValue::set(white.to_string()),
+            pending: ActiveValue::set(false),
+            ended: ActiveValue::set(true),
+            challenge: ActiveValue::set(None),
+            result: ActiveValue::set(None),
+            settings: ActiveValue::set(json!({})),
+            state: ActiveValue::set(None),
+            turn_started_at: ActiveValue::set(None),
+            opponent: ActiveValue::set(None),
+            arena: ActiveValue::set(None),
+            host_opponent: ActiveValue::set(None),
+            ended_at: ActiveValue::set(Some(ended_at)),
+            mode: ActiveValue::set(correspondence::LIVE.into()),
+            host_color: ActiveValue::set(String::from("black")),
+        };
+        let now = Utc::now().fixed_offset();
+        let old = finished(now - Duration::days(2))
+            .insert(state.database.as_ref())
+            .await
+            .unwrap();
+        let recent = finished(now).insert(state.database.as_ref()).await.unwrap();
+        let archived = super::archive_older_than(&state, std::time::Duration::from_hours(24))
+            .await
+            .unwrap();
+        assert!(archived >= 1);
+        let db = state.database.as_ref();
+        assert!(Game::find_by_id(old.id).one(db).await.unwrap().is_none());
+        assert!(Game::find_by_id(recent.id).one(db).await.unwrap().is_some());
+        // Archived games are still found, as they were.
+        assert_eq!(super::find(db, old.id).await.unwrap(), Some(old.clone()));
+        let client = Client::new();
+        let resp: Response<Vec<Map>> = client.get(&url, &format!("/users/{host}/games")).await;
+        let ids: Vec<_> = resp.message.iter().map(|game| game["id"].clone()).collect();
+        assert_eq!(ids, [json!(recent.id), json!(old.id)]);
+    }
+}
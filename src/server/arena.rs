@@ -0,0 +1,269 @@
+//! Exhibition matches between engines. Two bots (or two of the server's own opponents) play a
+//! series of games, swapping colours each game, out in the open so that anyone can watch.
+
+use crate::{
+    server::{
+        correspondence, create_in_memory_game,
+        entities::{arena, game},
+        handlers::StringError,
+        helpers,
+        notifications::{self, Kind},
+        opponent::{self, Difficulty},
+        state::AppState,
+        strings,
+        summary::{Outcome, Summary},
+        webhooks,
+    },
+    Piece,
+};
+use axum::http::StatusCode;
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseTransaction, DbErr, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+/// The most games a single arena can be played over.
+pub const MAX_GAMES: i32 = 20;
+
+/// Someone who can play in an arena: a registered bot, by its username, or one of the
+/// server's own opponents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Contender {
+    Bot(String),
+    Difficulty(Difficulty),
+}
+
+/// How one player has fared against another, from the first player's side.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeadToHead {
+    /// How many of their games have ended.
+    pub played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// The discs the first player finished those games with.
+    pub discs: u32,
+    /// The discs the second player finished those games with.
+    pub opponent_discs: u32,
+}
+
+/// Tally how the specified player did in the specified games against whoever they played.
+/// Games that haven't ended yet don't count.
+#[must_use]
+pub fn tally(games: &[game::Model], player: Uuid) -> HeadToHead {
+    let mut tally = HeadToHead::default();
+    for game in games {
+        let Some(summary) = game
+            .result
+            .clone()
+            .and_then(|result| serde_json::from_value::<Summary>(result).ok())
+        else {
+            continue;
+        };
+        let black = helpers::black_white(game).0 == Some(player);
+        let (discs, opponent_discs) = if black {
+            (summary.score.black, summary.score.white)
+        } else {
+            (summary.score.white, summary.score.black)
+        };
+        tally.played += 1;
+        tally.discs += u32::try_from(discs).unwrap_or_default();
+        tally.opponent_discs += u32::try_from(opponent_discs).unwrap_or_default();
+        match (summary.result, black) {
+            (Outcome::Draw, _) => tally.draws += 1,
+            (Outcome::Black, true) | (Outcome::White, false) => tally.wins += 1,
+            (Outcome::Black, false) | (Outcome::White, true) => tally.losses += 1,
+        }
+    }
+    tally
+}
+
+/// Find the account a contender plays from, along with the difficulty it plays at if it's one
+/// of the server's own opponents.
+async fn resolve(
+    state: &AppState,
+    contender: &Contender,
+) -> Result<(Uuid, Option<Difficulty>), StringError> {
+    match contender {
+        Contender::Difficulty(difficulty) => Ok((
+            opponent::account(state, *difficulty).await?,
+            Some(*difficulty),
+        )),
+        Contender::Bot(username) => {
+            let member = helpers::get_user(state, username, true).await?;
+            // The server's own opponents have no owner, and only play when they're asked
+            // for by difficulty.
+            if !member.bot || member.owner.is_none() {
+                return Err(StringError(
+                    strings::NOT_A_BOT.into(),
+                    StatusCode::BAD_REQUEST,
+                ));
+            }
+            Ok((member.id, None))
+        }
+    }
+}
+
+/// Start an arena between two contenders, scheduling every game at once. The first
+/// contender plays black in the first game, and the colours swap each game after.
+/// # Errors
+/// Returns an error if either contender isn't a bot, or the arena can't be saved.
+pub async fn start(
+    state: &AppState,
+    first: &Contender,
+    second: &Contender,
+    games: i32,
+    created_by: Uuid,
+) -> Result<(arena::Model, Vec<game::Model>), StringError> {
+    let first = resolve(state, first).await?;
+    let second = resolve(state, second).await?;
+    if first.0 == second.0 {
+        return Err(StringError(
+            strings::ARENA_SAME_CONTENDER.into(),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    let txn = state.database.begin().await?;
+    let arena = arena::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        first: ActiveValue::set(first.0),
+        second: ActiveValue::set(second.0),
+        games: ActiveValue::set(games),
+        created_by: ActiveValue::set(Some(created_by)),
+        created_at: ActiveValue::NotSet,
+    }
+    .insert(&txn)
+    .await?;
+    let mut scheduled = Vec::new();
+    for round in 0..games {
+        let color = if round % 2 == 0 {
+            Piece::Black
+        } else {
+            Piece::White
+        };
+        scheduled.push(schedule(&txn, arena.id, first, second, color).await?);
+    }
+    txn.commit().await?;
+    for game in &scheduled {
+        // The failure is logged, and the game can still be loaded by joining it.
+        let _ = create_in_memory_game(state, game).await;
+        webhooks::game_started(state, game).await;
+        // Registered bots need to hear about their games to play them; the server's own
+        // opponents start by themselves.
+        for (player, hosted) in [
+            (&game.host, &game.host_opponent),
+            (&game.guest, &game.opponent),
+        ] {
+            let Ok(player) = Uuid::parse_str(player) else {
+                continue;
+            };
+            if hosted.is_none() {
+                notifications::send(
+                    state,
+                    player,
+                    Kind::ArenaGame,
+                    json!({ "arena": arena.id, "game": game.id }),
+                )
+                .await;
+            }
+        }
+    }
+    Ok((arena, scheduled))
+}
+
+/// Create an arena game between the specified contenders, the first hosting it and playing
+/// the specified colour. Nobody has to accept arena games, so they start out ready to play.
+async fn schedule(
+    txn: &DatabaseTransaction,
+    arena: Uuid,
+    (host, host_difficulty): (Uuid, Option<Difficulty>),
+    (guest, guest_difficulty): (Uuid, Option<Difficulty>),
+    host_color: Piece,
+) -> Result<game::Model, DbErr> {
+    game::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        host: ActiveValue::set(host.to_string()),
+        guest: ActiveValue::set(guest.to_string()),
+        pending: ActiveValue::set(false),
+        ended: ActiveValue::set(false),
+        challenge: ActiveValue::set(None),
+        result: ActiveValue::set(None),
+        settings: ActiveValue::set(json!({})),
+        state: ActiveValue::set(None),
+        turn_started_at: ActiveValue::set(None),
+        opponent: ActiveValue::set(guest_difficulty.map(|d| d.name().into())),
+        arena: ActiveValue::set(Some(arena)),
+        host_opponent: ActiveValue::set(host_difficulty.map(|d| d.name().into())),
+        ended_at: ActiveValue::set(None),
+        mode: ActiveValue::set(correspondence::LIVE.into()),
+        host_color: ActiveValue::set(helpers::color_name(host_color).into()),
+    }
+    .insert(txn)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tally, HeadToHead};
+    use crate::server::{correspondence, entities::game};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn arena_game(host: Uuid, guest: Uuid, result: Option<(&str, u32, u32)>) -> game::Model {
+        game::Model {
+            id: Uuid::now_v7(),
+            host: host.to_string(),
+            guest: guest.to_string(),
+            pending: false,
+            ended: result.is_some(),
+            challenge: None,
+            result: result.map(|(result, black, white)| {
+                json!({
+                    "result": result,
+                    "winner": null,
+                    "termination": "normal",
+                    "score": { "black": black, "white": white },
+                    "points": 0,
+                    "total": black + white,
+                    "rating_deltas": null,
+                    "links": { "game": "", "export": "" },
+                })
+            }),
+            settings: json!({}),
+            state: None,
+            turn_started_at: None,
+            opponent: None,
+            arena: None,
+            host_opponent: None,
+            ended_at: None,
+            mode: correspondence::LIVE.into(),
+            host_color: String::from("black"),
+        }
+    }
+
+    #[test]
+    fn head_to_head() {
+        let (a, b) = (Uuid::now_v7(), Uuid::now_v7());
+        let games = [
+            arena_game(a, b, Some(("black", 40, 24))),
+            arena_game(b, a, Some(("black", 33, 31))),
+            arena_game(a, b, Some(("draw", 32, 32))),
+            arena_game(b, a, Some(("white", 10, 54))),
+            // Games still being played don't count yet.
+            arena_game(a, b, None),
+        ];
+        assert_eq!(
+            tally(&games, a),
+            HeadToHead {
+                played: 4,
+                wins: 2,
+                losses: 1,
+                draws: 1,
+                discs: 40 + 31 + 32 + 54,
+                opponent_discs: 24 + 33 + 32 + 10,
+            }
+        );
+        assert_eq!(tally(&games, b).wins, 1);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{analysis::Weights, opening::Book};
+use serde::de::DeserializeOwned;
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+/// Engine data that the server can reload while it runs, so engine improvements ship
+/// without restarting the server and disconnecting everyone.
+#[derive(Debug, Default)]
+pub struct Assets {
+    /// Where the evaluation weights are read from. The built-in weights are used while unset.
+    weights_path: Option<PathBuf>,
+    weights: RwLock<Weights>,
+    /// Where the opening book is read from. The built-in book is used while unset.
+    book_path: Option<PathBuf>,
+    book: RwLock<Book>,
+}
+
+impl Assets {
+    #[must_use]
+    pub fn new(weights_path: Option<PathBuf>, book_path: Option<PathBuf>) -> Self {
+        Self {
+            weights_path,
+            weights: RwLock::default(),
+            book_path,
+            book: RwLock::default(),
+        }
+    }
+
+    pub(super) fn weights_path(&self) -> Option<&PathBuf> {
+        self.weights_path.as_ref()
+    }
+
+    pub(super) fn book_path(&self) -> Option<&PathBuf> {
+        self.book_path.as_ref()
+    }
+
+    /// The evaluation weights currently in use.
+    /// # Panics
+    /// Panics if the lock is poisoned.
+    pub fn weights(&self) -> Weights {
+        self.weights.read().expect("lock was poisoned").clone()
+    }
+
+    /// The opening book currently in use.
+    /// # Panics
+    /// Panics if the lock is poisoned.
+    pub fn book(&self) -> Book {
+        self.book.read().expect("lock was poisoned").clone()
+    }
+
+    /// Read every configured asset from disk, returning the names of those that were
+    /// reloaded. Nothing is replaced unless every asset loads successfully.
+    /// # Errors
+    /// Returns an error if any of the assets can't be read or parsed.
+    /// # Panics
+    /// Panics if the lock is poisoned.
+    pub async fn reload(&self) -> Result<Vec<&'static str>, String> {
+        let weights = match &self.weights_path {
+            Some(path) => Some(load::<Weights>(path, "evaluation weights").await?),
+            None => None,
+        };
+        let book = match &self.book_path {
+            Some(path) => Some(load::<Book>(path, "opening book").await?),
+            None => None,
+        };
+        let mut reloaded = vec![];
+        if let Some(weights) = weights {
+            *self.weights.write().expect("lock was poisoned") = weights;
+            reloaded.push("eval_weights");
+        }
+        if let Some(book) = book {
+            *self.book.write().expect("lock was poisoned") = book;
+            reloaded.push("opening_book");
+        }
+        Ok(reloaded)
+    }
+}
+
+/// Read the specified JSON file, describing what it holds in the error if it can't be.
+async fn load<T: DeserializeOwned>(path: &Path, what: &str) -> Result<T, String> {
+    tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|raw| serde_json::from_str(&raw).map_err(|e| e.to_string()))
+        .map_err(|e| format!("failed to load {what} from {}: {e}", path.display()))
+}
@@ -0,0 +1,117 @@
+use crate::server::{
+    entities::{audit_log, prelude::AuditLog},
+    handlers::StringError,
+    network::ClientIp,
+    state::AppState,
+};
+use axum::http::StatusCode;
+use sea_orm::{ActiveValue, EntityTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Something done to an account, or by a moderator, that's kept on the record.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    UsernameChange,
+    PasswordChange,
+    FriendRemove,
+    Ban,
+    BanLift,
+    ReportResolve,
+    AssetsReload,
+    BotCreate,
+    BotTokenReset,
+    ArenaStart,
+}
+
+impl Action {
+    /// The name the action is stored and filtered by.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UsernameChange => "username_change",
+            Self::PasswordChange => "password_change",
+            Self::FriendRemove => "friend_remove",
+            Self::Ban => "ban",
+            Self::BanLift => "ban_lift",
+            Self::ReportResolve => "report_resolve",
+            Self::AssetsReload => "assets_reload",
+            Self::BotCreate => "bot_create",
+            Self::BotTokenReset => "bot_token_reset",
+            Self::ArenaStart => "arena_start",
+        }
+    }
+}
+
+/// An entry about to be written to the audit log.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    action: Action,
+    actor: Option<Uuid>,
+    target: Option<Uuid>,
+    details: Value,
+    ip: Option<ClientIp>,
+}
+
+impl Entry {
+    #[must_use]
+    pub fn new(action: Action) -> Self {
+        Self {
+            action,
+            actor: None,
+            target: None,
+            details: Value::Object(serde_json::Map::new()),
+            ip: None,
+        }
+    }
+
+    /// The user who took the action. Admin actions can be taken without a session, in which
+    /// case only the address they came from is known.
+    #[must_use]
+    pub fn actor(mut self, actor: Option<Uuid>) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    /// The user the action was taken against.
+    #[must_use]
+    pub fn target(mut self, target: Uuid) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Anything else worth knowing about the action (e.g. a ban's reason), as a JSON object.
+    #[must_use]
+    pub fn details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// The address the action was taken from.
+    #[must_use]
+    pub fn ip(mut self, ip: Option<ClientIp>) -> Self {
+        self.ip = ip;
+        self
+    }
+
+    /// Write the entry to the audit log.
+    /// # Errors
+    /// Returns an error if the entry can't be saved.
+    pub async fn record(self, state: &AppState) -> Result<(), StringError> {
+        AuditLog::insert(audit_log::ActiveModel {
+            id: ActiveValue::set(Uuid::now_v7()),
+            actor: ActiveValue::set(self.actor),
+            target: ActiveValue::set(self.target),
+            action: ActiveValue::set(self.action.name().into()),
+            details: ActiveValue::set(self.details),
+            ip: ActiveValue::set(self.ip.map(|ClientIp(ip)| ip.to_string())),
+            created_at: ActiveValue::NotSet,
+        })
+        .exec(state.database.as_ref())
+        .await
+        .map(|_| ())
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+    }
+}
@@ -0,0 +1,130 @@
+use crate::server::{
+    entities::{member, prelude::Member},
+    handlers::StringError,
+    state::AppState,
+    strings,
+};
+use axum::http::StatusCode;
+use base64::Engine;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// What bots put in front of their API token, both in the `Authorization` header and in place
+/// of a session token on the gateway.
+pub const TOKEN_SCHEME: &str = "Bot ";
+
+/// How many requests (HTTP requests and gateway messages alike) each bot can make in a window
+/// of time. People aren't limited this way; bots are, since they can send as fast as the
+/// server answers.
+#[derive(Debug, Clone, Copy)]
+pub struct BotLimits {
+    pub max_requests: u32,
+    pub window: Duration,
+}
+
+impl Default for BotLimits {
+    fn default() -> Self {
+        Self {
+            max_requests: 120,
+            window: Duration::from_mins(1),
+        }
+    }
+}
+
+/// How many requests a bot has made since its current window opened.
+#[derive(Debug, Clone, Copy)]
+pub struct Usage {
+    opened: Instant,
+    requests: u32,
+}
+
+impl Usage {
+    fn new(now: Instant) -> Self {
+        Self {
+            opened: now,
+            requests: 0,
+        }
+    }
+
+    /// Count a request made at `now`, returning whether it's within the limits. A new window
+    /// opens once the last one has gone by.
+    fn hit(&mut self, now: Instant, limits: BotLimits) -> bool {
+        if now.duration_since(self.opened) >= limits.window {
+            *self = Self::new(now);
+        }
+        self.requests = self.requests.saturating_add(1);
+        self.requests <= limits.max_requests
+    }
+}
+
+/// What's stored in place of an API token, so that a leaked database doesn't hand out
+/// working tokens. Tokens are random enough that a plain digest is all they need.
+#[must_use]
+pub fn digest(token: &str) -> String {
+    base64::prelude::BASE64_STANDARD.encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Fetch the ID of the bot an API token belongs to.
+/// # Errors
+/// Returns an error if the token doesn't belong to any bot, or the database can't be reached.
+pub async fn authenticate(state: &AppState, token: &str) -> Result<Uuid, StringError> {
+    Member::find()
+        .filter(member::Column::ApiToken.eq(digest(token)))
+        .filter(member::Column::Bot.eq(true))
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .map(|bot| bot.id)
+        .ok_or(StringError(
+            strings::INVALID_TOKEN.into(),
+            StatusCode::UNAUTHORIZED,
+        ))
+}
+
+/// Count a request made by the specified bot, turning it away if the bot has made too many
+/// lately.
+/// # Errors
+/// Returns an error if the bot is over its limit.
+/// # Panics
+/// Panics if the mutex is poisoned.
+pub fn ensure_within_limit(state: &AppState, bot: Uuid) -> Result<(), StringError> {
+    let now = Instant::now();
+    let mut usage = state.bot_usage.lock().expect("mutex was poisoned");
+    // Forget bots whose windows have gone by, so that the map doesn't keep every bot forever.
+    usage.retain(|_, usage| now.duration_since(usage.opened) < state.bot_limits.window);
+    if usage
+        .entry(bot)
+        .or_insert_with(|| Usage::new(now))
+        .hit(now, state.bot_limits)
+    {
+        Ok(())
+    } else {
+        Err(StringError(
+            strings::RATE_LIMITED.into(),
+            StatusCode::TOO_MANY_REQUESTS,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BotLimits, Usage};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn windows() {
+        let limits = BotLimits {
+            max_requests: 2,
+            window: Duration::from_secs(10),
+        };
+        let start = Instant::now();
+        let mut usage = Usage::new(start);
+        assert!(usage.hit(start, limits));
+        assert!(usage.hit(start + Duration::from_secs(5), limits));
+        assert!(!usage.hit(start + Duration::from_secs(9), limits));
+        // The limit resets once the window has gone by.
+        assert!(usage.hit(start + Duration::from_secs(10), limits));
+    }
+}
@@ -0,0 +1,218 @@
+//! Calendar feeds of tournament games, in the iCalendar format (RFC 5545), so that players can
+//! subscribe to them in their calendar apps. Rounds are paired as soon as the one before ends,
+//! so there's nothing to put in a calendar until a game is paired; each game appears from then
+//! on, running from when it started until it ended or, while it's being played, until it's
+//! expected to. Times are kept in UTC, but calendars are shown in the time zone of whoever
+//! they're for, and each game says when it was paired in that zone.
+
+use crate::server::{
+    correspondence,
+    entities::{game, tournament, tournament_round},
+    helpers,
+    links::GameLinks,
+    tournament::Decision,
+};
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use std::str::FromStr;
+
+/// How many moves each player is expected to make in a game, for working out how long its
+/// clocks could run for.
+const EXPECTED_MOVES: u32 = 30;
+/// How long a game played without a clock is expected to last.
+const UNTIMED_LENGTH: Duration = Duration::hours(1);
+/// The longest a line of a calendar can be, in bytes, before it's folded onto the next.
+const LINE_LENGTH: usize = 75;
+
+/// A game as it appears in a calendar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Entry {
+    pub uid: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub summary: String,
+    pub description: String,
+    pub url: String,
+}
+
+impl Entry {
+    /// The entry for the specified game of a tournament, paired as the specified pairing
+    /// between the players with the specified usernames.
+    pub(super) fn game(
+        tournament: &tournament::Model,
+        pairing: &tournament_round::Model,
+        game: &game::Model,
+        black: &str,
+        white: &str,
+    ) -> Self {
+        let start = helpers::created_at(game.id).unwrap_or_else(Utc::now);
+        let result = match pairing.result.as_deref().map(Decision::from_str) {
+            Some(Ok(Decision::Black)) => format!(" {black} won."),
+            Some(Ok(Decision::White)) => format!(" {white} won."),
+            Some(Ok(Decision::Draw)) => String::from(" The game was drawn."),
+            _ => String::new(),
+        };
+        Self {
+            uid: format!("{}@olly", game.id),
+            start,
+            end: end(game, start),
+            summary: format!("{}: {black} vs {white}", tournament.name),
+            description: format!(
+                "Round {} of {}, with {black} playing black and {white} playing white.{result}",
+                pairing.round, tournament.name
+            ),
+            url: GameLinks::new(game.id).web,
+        }
+    }
+}
+
+/// When the specified game, which started at the specified time, ended or is expected to.
+fn end(game: &game::Model, start: DateTime<Utc>) -> DateTime<Utc> {
+    if let Some(ended) = game.ended_at {
+        return ended.with_timezone(&Utc).max(start);
+    }
+    let settings = helpers::game_settings(game);
+    if let Some(correspondence) = settings.correspondence {
+        // The game goes on at least until the move being waited on is due.
+        return correspondence::deadline(game).map_or_else(
+            || start + Duration::days(correspondence.days_per_move.into()),
+            |deadline| deadline.with_timezone(&Utc),
+        );
+    }
+    match settings.time_control {
+        // Both clocks running down, with every move adding to them.
+        Some(clock) => {
+            let seconds = 2
+                * (i64::from(clock.initial)
+                    + i64::from(EXPECTED_MOVES) * i64::from(clock.increment));
+            start + Duration::seconds(seconds)
+        }
+        None => start + UNTIMED_LENGTH,
+    }
+}
+
+/// Render a calendar with the specified name holding the specified entries, as of the
+/// specified time, to be shown in the specified time zone.
+pub(super) fn render(name: &str, entries: &[Entry], now: DateTime<Utc>, zone: Tz) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//olly//Tournament schedules//EN"),
+        String::from("CALSCALE:GREGORIAN"),
+        String::from("METHOD:PUBLISH"),
+        format!("X-WR-CALNAME:{}", escape(name)),
+        format!("X-WR-TIMEZONE:{}", zone.name()),
+    ];
+    for entry in entries {
+        let paired = entry.start.with_timezone(&zone).format("%H:%M on %-d %B %Y");
+        let description = format!("{} Paired at {paired}, {zone} time.", entry.description);
+        lines.extend([
+            String::from("BEGIN:VEVENT"),
+            format!("UID:{}", entry.uid),
+            format!("DTSTAMP:{}", time(now)),
+            format!("DTSTART:{}", time(entry.start)),
+            format!("DTEND:{}", time(entry.end)),
+            format!("SUMMARY:{}", escape(&entry.summary)),
+            format!("DESCRIPTION:{}", escape(&description)),
+            format!("URL:{}", entry.url),
+            String::from("END:VEVENT"),
+        ]);
+    }
+    lines.push(String::from("END:VCALENDAR"));
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// Write a time as a calendar does, in UTC.
+fn time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape the characters that mean something in a calendar's text.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Fold a line that's too long onto as many more as it takes, each starting with a space,
+/// without splitting any character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, fold, render, Entry, LINE_LENGTH};
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            escape("Spring, 2024; a\\b\nc"),
+            "Spring\\, 2024\\; a\\\\b\\nc"
+        );
+        assert_eq!(fold("short"), "short");
+        // Long lines are folded without splitting any character, however wide.
+        let line = "é".repeat(LINE_LENGTH);
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|line| line.len() <= LINE_LENGTH));
+        assert!(folded
+            .split("\r\n")
+            .skip(1)
+            .all(|line| line.starts_with(' ')));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn calendar() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 18, 0, 0).unwrap();
+        let entry = Entry {
+            uid: String::from("game@olly"),
+            start,
+            end: start + chrono::Duration::minutes(20),
+            summary: String::from("Weekly: alice vs bob"),
+            description: String::from("Round 1"),
+            url: String::from("http://localhost:8000/play?gameId=game"),
+        };
+        let entries = [entry];
+        let calendar = render("Weekly, spring", &entries, start, Tz::UTC);
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert!(calendar.contains("X-WR-CALNAME:Weekly\\, spring\r\n"));
+        assert!(calendar.contains("DTSTART:20240301T180000Z\r\nDTEND:20240301T182000Z\r\n"));
+        assert!(calendar.contains("SUMMARY:Weekly: alice vs bob\r\n"));
+        assert!(calendar.contains("Round 1 Paired at 18:00 on 1 March 2024\\, UTC time.\r\n"));
+        // Calendars for other time zones say when games were paired there, but keep their times
+        // in UTC.
+        let calendar = render("Weekly", &entries, start, Tz::America__New_York);
+        assert!(calendar.contains("X-WR-TIMEZONE:America/New_York\r\n"));
+        assert!(calendar.contains("DTSTART:20240301T180000Z\r\n"));
+        let unfolded = calendar.replace("\r\n ", "");
+        assert!(unfolded.contains("Paired at 13:00 on 1 March 2024\\, America/New_York time."));
+        // A calendar without any games is still a calendar.
+        let calendar = render("Weekly", &[], start, Tz::UTC);
+        assert!(!calendar.contains("VEVENT"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+    }
+}
@@ -0,0 +1,320 @@
+use crate::{
+    analysis,
+    server::{
+        entities::{prelude::Strike, strike},
+        handlers::StringError,
+        state::AppState,
+        strings, timestamp,
+    },
+    Game, GameSettings, Piece,
+};
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use redis::AsyncCommands;
+use sea_orm::{
+    ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// How many days a strike counts against a player for.
+pub const STRIKE_WINDOW_DAYS: i64 = 30;
+/// How many games a player can abort in a day before each further abort earns a strike.
+pub const ABORT_ALLOWANCE: u64 = 3;
+/// The chance of winning below which a position counts as clearly lost.
+pub const LOST_THRESHOLD: f64 = 0.2;
+/// How long (in seconds) aborts are counted for.
+const ABORT_WINDOW: u64 = 24 * 60 * 60;
+/// The consequences of collecting strikes, as the number of strikes that triggers each
+/// penalty and how many hours the penalty lasts from the strike that triggered it.
+const LADDER: [(usize, Penalty, i64); 3] = [
+    (2, Penalty::Mute, 24),
+    (3, Penalty::CasualOnly, 7 * 24),
+    (5, Penalty::Ban, 3 * 24),
+];
+
+/// Why a player was given a strike.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reason {
+    /// The player broke the rules in chat.
+    Chat,
+    /// The player aborted more games than `ABORT_ALLOWANCE` allows.
+    Aborting,
+    /// The player let a lost position run out by disconnecting instead of resigning.
+    Stalling,
+}
+
+impl Reason {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Chat => "chat",
+            Self::Aborting => "aborting",
+            Self::Stalling => "stalling",
+        }
+    }
+}
+
+impl FromStr for Reason {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chat" => Ok(Self::Chat),
+            "aborting" => Ok(Self::Aborting),
+            "stalling" => Ok(Self::Stalling),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A consequence of collecting too many strikes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Penalty {
+    /// The player can't chat.
+    Mute,
+    /// The player can only play casual games.
+    CasualOnly,
+    /// The player can't create or accept games.
+    Ban,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StrikeRecord {
+    pub reason: Reason,
+    pub game: Option<Uuid>,
+    #[serde(serialize_with = "timestamp::serialize")]
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sanction {
+    pub penalty: Penalty,
+    #[serde(serialize_with = "timestamp::serialize")]
+    pub expires: DateTime<FixedOffset>,
+}
+
+/// A player's strikes that still count against them and the penalties currently in effect.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    pub strikes: Vec<StrikeRecord>,
+    pub penalties: Vec<Sanction>,
+}
+
+impl Standing {
+    /// Whether the specified penalty is currently in effect.
+    pub fn has(&self, penalty: Penalty) -> bool {
+        self.penalties.iter().any(|s| s.penalty == penalty)
+    }
+}
+
+/// Give the specified player a strike.
+pub async fn strike(
+    db: &impl ConnectionTrait,
+    member: Uuid,
+    reason: Reason,
+    game: Option<Uuid>,
+) -> Result<(), DbErr> {
+    Strike::insert(strike::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        member: ActiveValue::set(member),
+        reason: ActiveValue::set(reason.name().to_string()),
+        game: ActiveValue::set(game),
+        created_at: ActiveValue::NotSet,
+    })
+    .exec(db)
+    .await
+    .map(|_| ())
+}
+
+/// Count an aborted game against the specified player, giving them a strike if they have
+/// aborted more than `ABORT_ALLOWANCE` games recently.
+pub async fn record_abort(state: &AppState, member: Uuid, game: Uuid) -> Result<(), StringError> {
+    let aborts = {
+        let Ok(mut conn) = state.redis.get().await else {
+            return Ok(());
+        };
+        let key = format!("conduct:aborts:{member}");
+        let Ok(aborts) = conn.incr::<_, _, u64>(&key, 1).await else {
+            return Ok(());
+        };
+        if aborts == 1 {
+            #[allow(clippy::cast_possible_wrap)] // ABORT_WINDOW <= i64::MAX
+            let _ = conn.expire::<_, ()>(&key, ABORT_WINDOW as i64).await;
+        }
+        aborts
+    };
+    if aborts > ABORT_ALLOWANCE {
+        strike(
+            state.database.as_ref(),
+            member,
+            Reason::Aborting,
+            Some(game),
+        )
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    }
+    Ok(())
+}
+
+/// Count a game that the specified player stalled out (by disconnecting or idling on their
+/// turn) against them, giving them a strike if their position was clearly lost, since that's
+/// running the clock instead of resigning. The strike is written over `db`, so that it can be
+/// part of the transaction ending the game.
+pub async fn record_stall(
+    state: &AppState,
+    db: &impl ConnectionTrait,
+    member: Uuid,
+    id: Uuid,
+    game: &Game,
+    piece: Piece,
+) -> Result<(), DbErr> {
+    let prediction = analysis::predict_result_with(game, &state.assets.weights());
+    if prediction.of(piece) < LOST_THRESHOLD {
+        strike(db, member, Reason::Stalling, Some(id)).await?;
+    }
+    Ok(())
+}
+
+/// Fetch the specified player's standing.
+pub async fn standing(state: &AppState, member: Uuid) -> Result<Standing, StringError> {
+    let now = Utc::now().fixed_offset();
+    let strikes = Strike::find()
+        .filter(strike::Column::Member.eq(member))
+        .filter(strike::Column::CreatedAt.gt(now - Duration::days(STRIKE_WINDOW_DAYS)))
+        .order_by_asc(strike::Column::CreatedAt)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let strikes: Vec<_> = strikes
+        .into_iter()
+        .filter_map(|strike| {
+            Some(StrikeRecord {
+                reason: strike.reason.parse().ok()?,
+                game: strike.game,
+                timestamp: strike.created_at,
+            })
+        })
+        .collect();
+    let timestamps: Vec<_> = strikes.iter().map(|strike| strike.timestamp).collect();
+    Ok(Standing {
+        penalties: sanctions(&timestamps, now),
+        strikes,
+    })
+}
+
+/// Work out which penalties are in effect at `now`, given the times (in ascending order) of
+/// the strikes that still count.
+fn sanctions(strikes: &[DateTime<FixedOffset>], now: DateTime<FixedOffset>) -> Vec<Sanction> {
+    LADDER
+        .iter()
+        .filter_map(|&(threshold, penalty, hours)| {
+            let expires = *strikes.get(threshold - 1)? + Duration::hours(hours);
+            (expires > now).then_some(Sanction { penalty, expires })
+        })
+        .collect()
+}
+
+/// Ensures that the specified player is currently allowed to play a game with the specified
+/// settings, i.e. that they aren't banned, and aren't restricted to casual games if it's rated.
+pub async fn ensure_can_play(
+    state: &AppState,
+    member: Uuid,
+    settings: &GameSettings,
+) -> Result<(), StringError> {
+    let standing = standing(state, member).await?;
+    if standing.has(Penalty::Ban) {
+        return Err(StringError(strings::BANNED.into(), StatusCode::FORBIDDEN));
+    }
+    if settings.rated && standing.has(Penalty::CasualOnly) {
+        return Err(StringError(
+            strings::CASUAL_ONLY.into(),
+            StatusCode::FORBIDDEN,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Penalty, Reason};
+    use crate::server::{self, strings};
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client};
+
+    #[test]
+    fn sanctions() {
+        let now = Utc::now().fixed_offset();
+        let strikes: Vec<_> = [100, 50, 10, 2]
+            .iter()
+            .map(|&hours| now - Duration::hours(hours))
+            .collect();
+        // The mute ran out long ago, but the restriction to casual games hasn't.
+        let penalties: Vec<_> = super::sanctions(&strikes, now)
+            .iter()
+            .map(|s| s.penalty)
+            .collect();
+        assert_eq!(penalties, vec![Penalty::CasualOnly]);
+        assert!(super::sanctions(&strikes[..1], now).is_empty());
+    }
+
+    #[tokio::test]
+    async fn standing() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = format!("{}::1", function!());
+        let guest = format!("{}::2", function!());
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: serde_json::Value = client.get(&url, "/@me/standing").await;
+        assert_eq!(resp["code"], 200);
+        assert_eq!(resp["message"]["strikes"], json!([]));
+        let member = server::helpers::get_user(&state, &host, true)
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            super::strike(state.database.as_ref(), member.id, Reason::Stalling, None)
+                .await
+                .unwrap();
+        }
+        // Players restricted to casual games can't start rated ones, but can still play.
+        let settings = json!({ "rated": true });
+        let resp: serde_json::Value = client
+            .post(
+                &url,
+                "/game",
+                json!({ "guest": guest, "settings": settings }),
+            )
+            .await;
+        assert_eq!(resp["code"], "casual_only");
+        assert_eq!(resp["message"], strings::CASUAL_ONLY);
+        let resp: serde_json::Value = client.post(&url, "/game", json!({ "guest": guest })).await;
+        assert_eq!(resp["code"], 201);
+        for _ in 0..2 {
+            super::strike(state.database.as_ref(), member.id, Reason::Stalling, None)
+                .await
+                .unwrap();
+        }
+        let resp: serde_json::Value = client.get(&url, "/@me/standing").await;
+        assert_eq!(resp["message"]["strikes"].as_array().unwrap().len(), 5);
+        assert_eq!(resp["message"]["strikes"][0]["reason"], "stalling");
+        let penalties: Vec<_> = resp["message"]["penalties"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["penalty"].clone())
+            .collect();
+        assert_eq!(penalties, vec!["mute", "casual-only", "ban"]);
+        // Banned players can't start games.
+        let resp: serde_json::Value = client.post(&url, "/game", json!({ "guest": guest })).await;
+        assert_eq!(resp["code"], "banned");
+        assert_eq!(resp["message"], strings::BANNED);
+    }
+}
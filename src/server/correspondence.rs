@@ -0,0 +1,294 @@
+//! Correspondence games, played over days with a deadline on every move rather than in one
+//! sitting. Games are flagged as such by their `mode`, which the `correspondence` job looks
+//! for: it reminds players whose deadline is coming up, and forfeits the game on behalf of
+//! those who miss it. Reminders wait for the morning, in the player's own time zone, unless
+//! the deadline comes first. Players can come and go as they please in the meantime, so
+//! neither disconnecting nor stalling forfeits these games.
+
+use crate::{
+    server::{
+        entities::{game, prelude::Game as GameModel},
+        handlers::StringError,
+        helpers,
+        notifications::{self, Kind},
+        state::AppState,
+        summary::{self, Termination, Verdict},
+        timestamp,
+    },
+    Game, GameSettings, Piece,
+};
+use axum::http::StatusCode;
+use chrono::{DateTime, Days, Duration, FixedOffset, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+
+/// The mode of games played in one sitting.
+pub(super) const LIVE: &str = "live";
+/// The mode of games played with a deadline on every move.
+pub(super) const CORRESPONDENCE: &str = "correspondence";
+/// What's left of the time for a move, as a fraction of it, when the player is reminded.
+const REMINDER_FRACTION: i32 = 4;
+/// The hour of the night, in the player's time zone, from which reminders wait for the morning.
+const NIGHT_STARTS: u32 = 22;
+/// The hour of the morning, in the player's time zone, that reminders held overnight go out.
+const MORNING: u32 = 8;
+
+/// The mode a game played with the specified settings is stored with.
+pub(super) fn mode(settings: &GameSettings) -> &'static str {
+    if settings.correspondence.is_some() {
+        CORRESPONDENCE
+    } else {
+        LIVE
+    }
+}
+
+/// Whether the specified game is a correspondence game.
+pub(super) fn is_correspondence(game: &game::Model) -> bool {
+    game.mode == CORRESPONDENCE
+}
+
+/// When the move being waited on in the specified game is due, or `None` if it isn't a
+/// correspondence game that's underway.
+pub(super) fn deadline(game: &game::Model) -> Option<DateTime<FixedOffset>> {
+    if game.pending || game.ended {
+        return None;
+    }
+    let days = helpers::game_settings(game).correspondence?.days_per_move;
+    Some(turn_started(game) + Duration::days(days.into()))
+}
+
+/// When the current turn of the specified game started. Games that haven't had a move saved
+/// yet, and weren't started by accepting an invite, started when they were created.
+fn turn_started(game: &game::Model) -> DateTime<FixedOffset> {
+    game.turn_started_at.unwrap_or_else(|| {
+        helpers::created_at(game.id)
+            .unwrap_or_else(Utc::now)
+            .fixed_offset()
+    })
+}
+
+/// The position the specified game is in: as it's being played on this instance, if it is,
+/// otherwise as it was last saved.
+fn position(state: &AppState, game: &game::Model) -> Game {
+    if let Some(position) = state
+        .games
+        .lock()
+        .expect("mutex was poisoned")
+        .get(&game.id)
+    {
+        return position.clone();
+    }
+    game.state
+        .clone()
+        .and_then(|saved| serde_json::from_value(saved).ok())
+        .unwrap_or_else(|| Game::with_settings(helpers::game_settings(game)))
+}
+
+/// Whether a reminder of a deadline at the specified time should wait, at the specified time,
+/// for the morning in the specified time zone: it's night there, and the deadline is after
+/// the morning.
+fn overnight(now: DateTime<Utc>, deadline: DateTime<FixedOffset>, zone: Tz) -> bool {
+    let local = now.with_timezone(&zone);
+    if (MORNING..NIGHT_STARTS).contains(&local.hour()) {
+        return false;
+    }
+    let mut day = local.date_naive();
+    if local.hour() >= NIGHT_STARTS {
+        day = day + Days::new(1);
+    }
+    day.and_hms_opt(MORNING, 0, 0)
+        .and_then(|morning| zone.from_local_datetime(&morning).earliest())
+        .is_some_and(|morning| morning < deadline)
+}
+
+/// Remind the player on turn in every correspondence game whose deadline is coming up, and
+/// forfeit the games whose deadlines have passed on behalf of the players who missed them.
+/// Returns how many players were reminded and how many games were forfeited.
+pub(super) async fn enforce(state: &AppState) -> Result<(u64, u64), StringError> {
+    let games = GameModel::find()
+        .filter(game::Column::Mode.eq(CORRESPONDENCE))
+        .filter(game::Column::Pending.eq(false))
+        .filter(game::Column::Ended.eq(false))
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let now = Utc::now();
+    let (mut reminded, mut forfeited) = (0, 0);
+    for game in games {
+        let Some(deadline) = deadline(&game) else {
+            continue;
+        };
+        let position = position(state, &game);
+        let piece = position.turn();
+        let (black, white) = helpers::black_white(&game);
+        let Some(player) = (match piece {
+            Piece::Black => black,
+            Piece::White => white,
+        }) else {
+            continue;
+        };
+        if deadline <= now {
+            let forfeit = Some(Verdict::Forfeit(piece, Termination::Timeout));
+            match summary::conclude(state, &game, &position, forfeit, None).await {
+                Ok(_) => forfeited += 1,
+                Err(StringError(message, _)) => {
+                    tracing::error!(game = %game.id, "Failed to forfeit game: {message}");
+                }
+            }
+            continue;
+        }
+        let allowed = deadline.signed_duration_since(turn_started(&game));
+        if deadline.signed_duration_since(now) > allowed / REMINDER_FRACTION {
+            continue;
+        }
+        let zone = helpers::get_user(state, &player.to_string(), false)
+            .await
+            .map_or(Tz::UTC, |user| helpers::timezone(&user));
+        if !overnight(now, deadline, zone) && remind_once(state, &game, deadline).await
+        {
+            notifications::send(
+                state,
+                player,
+                Kind::MoveReminder,
+                json!({ "game": game.id, "deadline": timestamp::rfc3339(&deadline) }),
+            )
+            .await;
+            reminded += 1;
+        }
+    }
+    Ok((reminded, forfeited))
+}
+
+/// Whether the player on turn in the specified game still needs reminding of the deadline on
+/// their move, noting that they've been reminded if so. Each move is only reminded of once,
+/// however many instances run the job.
+async fn remind_once(
+    state: &AppState,
+    game: &game::Model,
+    deadline: DateTime<FixedOffset>,
+) -> bool {
+    let Ok(mut conn) = state.redis.get().await else {
+        return false;
+    };
+    let ttl = deadline
+        .signed_duration_since(Utc::now())
+        .num_seconds()
+        .max(1);
+    redis::cmd("SET")
+        .arg(format!("reminder:{}:{}", game.id, deadline.timestamp()))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(ttl)
+        .query_async::<_, Option<String>>(&mut conn)
+        .await
+        .is_ok_and(|set| set.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{
+        self,
+        entities::{game, prelude::Game},
+        handlers::Response,
+        helpers,
+    };
+    use chrono::{Duration, TimeZone, Timelike, Utc};
+    use chrono_tz::Tz;
+    use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn deadlines() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        // Reminders wait for the morning, so black is somewhere it's day.
+        let zone = ["UTC", "Asia/Tokyo", "America/Los_Angeles"]
+            .into_iter()
+            .find(|zone| {
+                let hour = Utc::now().with_timezone(&zone.parse::<Tz>().unwrap()).hour();
+                (super::MORNING..super::NIGHT_STARTS).contains(&hour)
+            })
+            .unwrap();
+        client
+            .patch::<_, Map>(&url, "/@me", json!({ "timezone": zone }))
+            .await;
+        let black = helpers::get_user(&state, &host, true).await.unwrap().id;
+        let white = helpers::get_user(&state, &guest, true).await.unwrap().id;
+        // Each game gives two days per move, and black's turn started a while ago.
+        let game = |hours| game::ActiveModel {
+            id: ActiveValue::set(Uuid::now_v7()),
+            host: ActiveValue::set(black.to_string()),
+            guest: ActiveValue::set(white.to_string()),
+            pending: ActiveValue::set(false),
+            ended: ActiveValue::set(false),
+            challenge: ActiveValue::set(None),
+            result: ActiveValue::set(None),
+            settings: ActiveValue::set(json!({ "correspondence": { "days_per_move": 2 } })),
+            state: ActiveValue::set(None),
+            turn_started_at: ActiveValue::set(Some(
+                (Utc::now() - Duration::hours(hours)).fixed_offset(),
+            )),
+            opponent: ActiveValue::set(None),
+            arena: ActiveValue::set(None),
+            host_opponent: ActiveValue::set(None),
+            ended_at: ActiveValue::set(None),
+            mode: ActiveValue::set(super::CORRESPONDENCE.into()),
+            host_color: ActiveValue::set(String::from("black")),
+        };
+        let db = state.database.as_ref();
+        let fresh = game(1).insert(db).await.unwrap();
+        let due = game(40).insert(db).await.unwrap();
+        let late = game(49).insert(db).await.unwrap();
+        assert_eq!(
+            super::deadline(&fresh),
+            Some(fresh.turn_started_at.unwrap() + Duration::days(2))
+        );
+        let (reminded, forfeited) = super::enforce(&state).await.unwrap();
+        assert!(reminded >= 1 && forfeited >= 1);
+        // Reminders aren't repeated for the same move.
+        super::enforce(&state).await.unwrap();
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/notifications").await;
+        let reminders: Vec<_> = resp
+            .message
+            .iter()
+            .filter(|n| n["kind"] == "move_reminder")
+            .map(|n| n["payload"]["game"].clone())
+            .collect();
+        assert_eq!(reminders, [json!(due.id)]);
+        let ended = |id| async move { Game::find_by_id(id).one(db).await.unwrap().unwrap().ended };
+        assert!(!ended(fresh.id).await);
+        assert!(!ended(due.id).await);
+        assert!(ended(late.id).await);
+        let resp: Response<Map> = client.get(&url, &format!("/games/{}", late.id)).await;
+        assert_eq!(resp.message["result"]["termination"], "timeout");
+        assert_eq!(resp.message["result"]["result"], "white");
+    }
+
+    #[test]
+    fn overnight() {
+        let at = |hour| Utc.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap();
+        let deadline = |hours| (at(0) + Duration::hours(hours)).fixed_offset();
+        let london = Tz::Europe__London;
+        // Reminders go out during the day.
+        assert!(!super::overnight(at(12), deadline(36), london));
+        // At night, they wait for the morning, unless the deadline comes first.
+        assert!(super::overnight(at(23), deadline(36), london));
+        assert!(super::overnight(at(3), deadline(10), london));
+        assert!(!super::overnight(at(23), deadline(31), london));
+        // It's the player's night that counts, not the server's.
+        assert!(super::overnight(at(14), deadline(36), Tz::Asia__Tokyo));
+        assert!(!super::overnight(at(14), deadline(36), Tz::UTC));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::server::strings;
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Which sites' scripts may call the API from a browser, and what they may send.
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+    /// The origins (e.g. `https://olly.example`) allowed to make requests. No other origin
+    /// is allowed, so nobody is while this is empty.
+    pub allowed_origins: Vec<HeaderValue>,
+    /// Whether browsers send cookies along with requests from other origins.
+    pub allow_credentials: bool,
+    /// The request headers scripts may set, beyond those browsers always allow.
+    pub allowed_headers: Vec<HeaderName>,
+    /// How long browsers may remember the answer to a preflight request.
+    pub max_age: Duration,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allow_credentials: true,
+            // Browser clients can't make any changes without sending their CSRF token.
+            allowed_headers: vec![
+                header::CONTENT_TYPE,
+                strings::CSRF_HEADER_NAME
+                    .parse()
+                    .expect("header name is valid"),
+                strings::IDEMPOTENCY_HEADER_NAME
+                    .parse()
+                    .expect("header name is valid"),
+            ],
+            max_age: Duration::from_hours(1),
+        }
+    }
+}
+
+impl CorsPolicy {
+    /// The middleware enforcing the policy on the app's routes.
+    pub fn layer(&self) -> CorsLayer {
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::list(self.allowed_origins.iter().cloned()))
+            .allow_credentials(self.allow_credentials)
+            .allow_headers(self.allowed_headers.clone())
+            .allow_methods([
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .expose_headers([
+                HeaderName::from_static("x-request-id"),
+                HeaderName::from_static("idempotent-replayed"),
+                // Scripts on other origins can't read the CSRF cookie, only this header.
+                HeaderName::from_static("x-csrf-token"),
+            ])
+            .max_age(self.max_age)
+    }
+}
+
+/// Parse a comma-separated list of origins. Every origin has to be listed: a wildcard would
+/// let any site make requests with its visitors' cookies, so `*` isn't accepted.
+pub fn parse_origins(s: &str) -> Option<Vec<HeaderValue>> {
+    list(s)
+        .map(|origin| {
+            origin
+                .contains("://")
+                .then(|| HeaderValue::from_str(origin.trim_end_matches('/')).ok())
+                .flatten()
+        })
+        .collect()
+}
+
+/// Parse a comma-separated list of header names.
+pub fn parse_headers(s: &str) -> Option<Vec<HeaderName>> {
+    list(s).map(|name| name.parse().ok()).collect()
+}
+
+fn list(s: &str) -> impl Iterator<Item = &str> {
+    s.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_headers, parse_origins, CorsPolicy};
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn parse() {
+        let origins = parse_origins("https://olly.example/, http://localhost:8000").unwrap();
+        assert_eq!(origins, ["https://olly.example", "http://localhost:8000"]);
+        assert!(parse_origins("*").is_none());
+        assert!(parse_origins("https://olly.example, *").is_none());
+        assert!(parse_origins("olly.example").is_none());
+        assert_eq!(parse_headers("Content-Type, X-Custom").unwrap().len(), 2);
+        assert!(parse_headers("not a header").is_none());
+    }
+
+    #[tokio::test]
+    async fn preflight() {
+        let policy = CorsPolicy {
+            allowed_origins: parse_origins("https://olly.example").unwrap(),
+            ..CorsPolicy::default()
+        };
+        let app = Router::new()
+            .route("/", get(|| async { "" }))
+            .layer(policy.layer());
+        let preflight = |origin: &str| {
+            Request::options("/")
+                .header("Origin", origin)
+                .header("Access-Control-Request-Method", "PATCH")
+                .header("Access-Control-Request-Headers", "x-csrf-token")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let resp = app
+            .clone()
+            .oneshot(preflight("https://olly.example"))
+            .await
+            .unwrap();
+        let headers = resp.headers();
+        assert_eq!(
+            headers["access-control-allow-origin"],
+            "https://olly.example"
+        );
+        assert_eq!(headers["access-control-allow-credentials"], "true");
+        assert!(headers["access-control-allow-headers"]
+            .to_str()
+            .unwrap()
+            .contains("x-csrf-token"));
+        // Other sites aren't told they're allowed.
+        let resp = app
+            .oneshot(preflight("https://evil.example"))
+            .await
+            .unwrap();
+        assert!(!resp.headers().contains_key("access-control-allow-origin"));
+        // Without any origins listed, nobody is.
+        let app = Router::new()
+            .route("/", get(|| async { "" }))
+            .layer(CorsPolicy::default().layer());
+        let resp = app
+            .oneshot(preflight("https://olly.example"))
+            .await
+            .unwrap();
+        assert!(!resp.headers().contains_key("access-control-allow-origin"));
+    }
+}
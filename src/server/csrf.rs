@@ -0,0 +1,106 @@
+use crate::server::{handlers::StringError, helpers, strings};
+use axum::{
+    extract::Request,
+    http::{HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+
+/// Routes that don't act on an existing session, so they're left alone even when a stale
+/// session cookie comes along with them.
+const EXEMPT: [&str; 2] = ["/login", "/register"];
+
+/// Add a fresh CSRF token to the jar, to go out alongside a new session cookie, and hand it
+/// back in a header too. Clients on other origins can't read the cookie, so they pick the
+/// token up from the header instead.
+#[must_use]
+pub fn issue(jar: CookieJar) -> (CookieJar, [(&'static str, String); 1]) {
+    let token = helpers::generate_key();
+    let mut cookie = Cookie::new(strings::CSRF_COOKIE_NAME, token.clone());
+    cookie.set_path("/");
+    (jar.add(cookie), [(strings::CSRF_HEADER_NAME, token)])
+}
+
+/// Middleware that refuses mutating requests made with a session cookie unless they repeat
+/// the CSRF cookie's value in a header. Other sites can make a browser send its cookies, but
+/// can't read them to fill in the header. The token is handed back in the same header on
+/// every response, so clients that lose track of it (e.g. after a reload) can pick it up
+/// again from any request.
+pub async fn enforce(jar: CookieJar, req: Request, next: Next) -> Result<Response, StringError> {
+    let cookie = jar.get(strings::CSRF_COOKIE_NAME);
+    let safe = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !safe
+        && !EXEMPT.contains(&req.uri().path())
+        && jar.get(strings::SESSION_COOKIE_NAME).is_some()
+    {
+        let header = req
+            .headers()
+            .get(strings::CSRF_HEADER_NAME)
+            .and_then(|value| value.to_str().ok());
+        match (cookie, header) {
+            (Some(cookie), Some(header))
+                if !header.is_empty() && cookie.value_trimmed() == header => {}
+            _ => {
+                return Err(StringError(
+                    strings::CSRF_MISMATCH.into(),
+                    StatusCode::FORBIDDEN,
+                ))
+            }
+        }
+    }
+    let token = cookie.and_then(|cookie| HeaderValue::from_str(cookie.value_trimmed()).ok());
+    let mut resp = next.run(req).await;
+    // A fresh token issued by the handler wins over the one the request came with.
+    if let Some(token) = token {
+        resp.headers_mut()
+            .entry(strings::CSRF_HEADER_NAME)
+            .or_insert(token);
+    }
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{self, handlers::ApiError, strings};
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map};
+
+    #[tokio::test]
+    async fn enforce() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let client = Client::authenticated(&[&function!()], &url, true).await;
+        let token = client.cookie(&url, strings::CSRF_COOKIE_NAME).unwrap();
+        let sid = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        // A request carrying the cookies but not the header is what a forged one looks like.
+        let forged = reqwest::Client::new()
+            .patch(format!("{url}/@me"))
+            .header("Cookie", format!("sid={sid}; csrf={token}"))
+            .json(&json!({ "timezone": "Europe/London" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(forged.status().as_u16(), 403);
+        let body: ApiError = forged.json().await.unwrap();
+        assert_eq!(body.message, strings::CSRF_MISMATCH);
+        // The client repeats the token, so its requests go through.
+        let resp: Map = client
+            .patch(&url, "/@me", json!({ "timezone": "Europe/London" }))
+            .await;
+        assert_eq!(resp["code"], 200);
+        // Clients that can't read the cookie get the token in a header instead.
+        let resp = reqwest::Client::new()
+            .get(format!("{url}/@me"))
+            .header("Cookie", format!("sid={sid}; csrf={token}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()[strings::CSRF_HEADER_NAME], token.as_str());
+    }
+}
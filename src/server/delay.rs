@@ -0,0 +1,192 @@
+//! Spectators of tournament games watch them some time behind the players, so that nobody can
+//! coach a player through a game as it happens. The events of each game being watched are
+//! queued on their way from its room to its spectators, and let out once the delay is up.
+
+use crate::{
+    server::{
+        handlers::StringError,
+        moves,
+        packet::{Channel, Event, EventKind, ServerMessage},
+        state::AppState,
+        strings,
+    },
+    Game,
+};
+use axum::http::StatusCode;
+use chrono::Utc;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::Instant,
+};
+use uuid::Uuid;
+
+/// A game's events as its spectators see them.
+pub(super) struct Delayed {
+    tx: broadcast::Sender<Event>,
+    /// The game as its spectators currently see it.
+    position: Arc<Mutex<Game>>,
+}
+
+impl Delayed {
+    fn watch(&self) -> (broadcast::Receiver<Event>, Game) {
+        let position = self.position.lock().expect("mutex was poisoned").clone();
+        (self.tx.subscribe(), position)
+    }
+}
+
+/// Listen to the specified game as its spectators see it, the specified time behind its
+/// players, returning the game as they currently see it. The game's events start being held
+/// back the first time anyone watches it.
+pub(super) async fn listen(
+    state: &AppState,
+    game: Uuid,
+    delay: Duration,
+) -> Result<(broadcast::Receiver<Event>, Game), StringError> {
+    if let Some(delayed) = state.delayed.lock().expect("mutex was poisoned").get(&game) {
+        return Ok(delayed.watch());
+    }
+    let not_found = || StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND);
+    // Listen to the room before looking at the game, so that no move is missed in between.
+    let rx = state.listen(Channel::Game(game)).ok_or_else(not_found)?;
+    let current = state
+        .games
+        .lock()
+        .expect("mutex was poisoned")
+        .get(&game)
+        .cloned()
+        .ok_or_else(not_found)?;
+    // Moves played within the delay are still to be shown, once it's up for each of them.
+    // Those played before times were recorded are long past.
+    let (now, utc) = (Instant::now(), Utc::now());
+    let due: HashMap<_, _> = moves::of_game(state, game)
+        .await?
+        .into_iter()
+        .filter_map(|played| {
+            let age = utc
+                .signed_duration_since(played.played_at)
+                .to_std()
+                .unwrap_or_default();
+            let ply = usize::try_from(played.ply).ok()?;
+            let left = delay.saturating_sub(age);
+            (!left.is_zero()).then(|| (ply, now + left))
+        })
+        .collect();
+    let history = current.history();
+    let mut position = Game::with_settings(*current.settings());
+    let mut shown = position.clone();
+    let mut queue = VecDeque::new();
+    for (ply, &(x, y)) in history.iter().enumerate() {
+        let piece = position.turn();
+        let _ = position.place(x, y, piece);
+        match due.get(&(ply + 1)) {
+            Some(&due) => queue.push_back((
+                due,
+                Event::new(
+                    EventKind::GameUpdate,
+                    ServerMessage::GameUpdate {
+                        game: position.clone(),
+                    },
+                )
+                .in_game(&game),
+            )),
+            None if queue.is_empty() => shown = position.clone(),
+            None => {}
+        }
+    }
+    let mut delayed = state.delayed.lock().expect("mutex was poisoned");
+    // Somebody else may have started watching in the meantime.
+    if let Some(delayed) = delayed.get(&game) {
+        return Ok(delayed.watch());
+    }
+    let (tx, _) = broadcast::channel(16);
+    let watched = Delayed {
+        tx: tx.clone(),
+        position: Arc::new(Mutex::new(shown)),
+    };
+    let watching = watched.watch();
+    let queue = Queue {
+        rx,
+        tx,
+        position: Arc::clone(&watched.position),
+        events: queue,
+        plies: history.len(),
+    };
+    delayed.insert(game, watched);
+    tokio::spawn(queue.run(game, delay, Arc::clone(&state.delayed)));
+    Ok(watching)
+}
+
+/// The events of a game on their way to its spectators.
+struct Queue {
+    rx: broadcast::Receiver<Event>,
+    tx: broadcast::Sender<Event>,
+    position: Arc<Mutex<Game>>,
+    /// The events waiting to be let out, along with when they're due.
+    events: VecDeque<(Instant, Event)>,
+    /// How many moves into the game the queued events go.
+    plies: usize,
+}
+
+impl Queue {
+    /// Let the game's events out to its spectators as they come due, until the game ends.
+    async fn run(
+        mut self,
+        game: Uuid,
+        delay: Duration,
+        delayed: Arc<Mutex<HashMap<Uuid, Delayed>>>,
+    ) {
+        let mut open = true;
+        loop {
+            let due = self.events.front().map(|&(due, _)| due);
+            let next = tokio::time::sleep_until(due.unwrap_or_else(Instant::now));
+            tokio::select! {
+                event = self.rx.recv(), if open => match event {
+                    Ok(event) => self.push(event, Instant::now() + delay),
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => open = false,
+                },
+                () = next, if due.is_some() => {
+                    if self.pop() {
+                        break;
+                    }
+                }
+                else => break,
+            }
+        }
+        delayed.lock().expect("mutex was poisoned").remove(&game);
+    }
+
+    /// Queue an event from the game's room to be let out when it's due, unless the move it
+    /// shows was already queued when the queue was set up.
+    fn push(&mut self, event: Event, due: Instant) {
+        if let ServerMessage::GameUpdate { game } = event.data() {
+            let plies = game.history().len();
+            if plies <= self.plies {
+                return;
+            }
+            self.plies = plies;
+        }
+        self.events.push_back((due, event));
+    }
+
+    /// Let out the next event, returning whether it ended the game.
+    fn pop(&mut self) -> bool {
+        let Some((_, event)) = self.events.pop_front() else {
+            return false;
+        };
+        if let ServerMessage::GameUpdate { game } = event.data() {
+            *self.position.lock().expect("mutex was poisoned") = game.clone();
+        }
+        let ended = matches!(
+            event.data(),
+            ServerMessage::GameEnd(_) | ServerMessage::GameAbort
+        );
+        let _ = self.tx.send(event);
+        ended
+    }
+}
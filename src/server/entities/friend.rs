@@ -9,6 +9,7 @@ pub struct Model {
     pub a: Uuid,
     #[sea_orm(primary_key, auto_increment = false)]
     pub b: Uuid,
+    pub created_at: DateTimeWithTimeZone,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -9,6 +9,7 @@ pub struct Model {
     pub sender: Uuid,
     #[sea_orm(primary_key, auto_increment = false)]
     pub recipient: Uuid,
+    pub created_at: DateTimeWithTimeZone,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
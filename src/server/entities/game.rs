@@ -11,6 +11,20 @@ pub struct Model {
     pub guest: String,
     pub pending: bool,
     pub ended: bool,
+    pub challenge: Option<Uuid>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub result: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub settings: Json,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub state: Option<Json>,
+    pub turn_started_at: Option<DateTimeWithTimeZone>,
+    pub opponent: Option<String>,
+    pub arena: Option<Uuid>,
+    pub host_opponent: Option<String>,
+    pub ended_at: Option<DateTimeWithTimeZone>,
+    pub mode: String,
+    pub host_color: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
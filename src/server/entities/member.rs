@@ -1,21 +1,56 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "member")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
     #[sea_orm(unique)]
     pub username: String,
-    pub password: String,
+    pub password: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+    pub avatar: Option<String>,
+    pub timezone: String,
+    pub admin: bool,
+    pub bot: bool,
+    pub owner: Option<Uuid>,
+    #[sea_orm(unique)]
+    pub api_token: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::ban::Entity")]
+    Ban,
+    #[sea_orm(has_many = "super::identity::Entity")]
+    Identity,
+    #[sea_orm(has_many = "super::login_attempt::Entity")]
+    LoginAttempt,
     #[sea_orm(has_many = "super::session::Entity")]
     Session,
+    #[sea_orm(has_many = "super::strike::Entity")]
+    Strike,
+}
+
+impl Related<super::ban::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Ban.def()
+    }
+}
+
+impl Related<super::identity::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Identity.def()
+    }
+}
+
+impl Related<super::login_attempt::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LoginAttempt.def()
+    }
 }
 
 impl Related<super::session::Entity> for Entity {
@@ -24,4 +59,10 @@ impl Related<super::session::Entity> for Entity {
     }
 }
 
+impl Related<super::strike::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Strike.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -2,8 +2,33 @@
 
 pub mod prelude;
 
+pub mod analysis;
+pub mod archived_game;
+pub mod arena;
+pub mod audit_log;
+pub mod ban;
+pub mod block;
+pub mod daily_puzzle;
+pub mod finished_game;
 pub mod friend;
 pub mod friend_request;
 pub mod game;
+pub mod game_move;
+pub mod identity;
+pub mod login_attempt;
 pub mod member;
+pub mod notification;
+pub mod open_challenge;
+pub mod puzzle;
+pub mod puzzle_attempt;
+pub mod puzzle_streak;
+pub mod report;
+pub mod season;
+pub mod season_rating;
 pub mod session;
+pub mod strike;
+pub mod tournament;
+pub mod tournament_entrant;
+pub mod tournament_round;
+pub mod webhook;
+pub mod webhook_delivery;
@@ -1,7 +1,32 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+pub use super::analysis::Entity as Analysis;
+pub use super::archived_game::Entity as ArchivedGame;
+pub use super::arena::Entity as Arena;
+pub use super::audit_log::Entity as AuditLog;
+pub use super::ban::Entity as Ban;
+pub use super::block::Entity as Block;
+pub use super::daily_puzzle::Entity as DailyPuzzle;
+pub use super::finished_game::Entity as FinishedGame;
 pub use super::friend::Entity as Friend;
 pub use super::friend_request::Entity as FriendRequest;
 pub use super::game::Entity as Game;
+pub use super::game_move::Entity as GameMove;
+pub use super::identity::Entity as Identity;
+pub use super::login_attempt::Entity as LoginAttempt;
 pub use super::member::Entity as Member;
+pub use super::notification::Entity as Notification;
+pub use super::open_challenge::Entity as OpenChallenge;
+pub use super::puzzle::Entity as Puzzle;
+pub use super::puzzle_attempt::Entity as PuzzleAttempt;
+pub use super::puzzle_streak::Entity as PuzzleStreak;
+pub use super::report::Entity as Report;
+pub use super::season::Entity as Season;
+pub use super::season_rating::Entity as SeasonRating;
 pub use super::session::Entity as Session;
+pub use super::strike::Entity as Strike;
+pub use super::tournament::Entity as Tournament;
+pub use super::tournament_entrant::Entity as TournamentEntrant;
+pub use super::tournament_round::Entity as TournamentRound;
+pub use super::webhook::Entity as Webhook;
+pub use super::webhook_delivery::Entity as WebhookDelivery;
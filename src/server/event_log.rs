@@ -0,0 +1,71 @@
+//! The latest events of each game's room, numbered in the order they were sent, so that
+//! connections that drop can catch up on what they missed when they rejoin. Only the tail of
+//! each game is kept, in Redis, for a day after its last event.
+
+use crate::server::{handlers::StringError, packet::Event, state::AppState};
+use redis::AsyncCommands;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How many of a game's latest events are kept.
+const TAIL: isize = 100;
+/// How long (in seconds) a game's events are kept after its last one.
+const TTL: i64 = 24 * 60 * 60;
+
+fn key(game: Uuid) -> String {
+    format!("game:{game}:events")
+}
+
+/// Keep a numbered event of the specified game for connections catching up later. Events
+/// that can't be kept are only missed by those connections.
+pub(super) fn append(state: &AppState, game: Uuid, event: &Event) {
+    let Some(sequence) = event.s else {
+        return;
+    };
+    let redis = Arc::clone(&state.redis);
+    let value = serde_json::to_string(event).unwrap();
+    tokio::spawn(async move {
+        let Ok(mut conn) = redis.get().await else {
+            return;
+        };
+        let key = key(game);
+        // Events are scored by their sequence numbers, so they're read back in order however
+        // they were written.
+        let result = redis::pipe()
+            .zadd(&key, value, sequence)
+            .ignore()
+            .zremrangebyrank(&key, 0, -(TAIL + 1))
+            .ignore()
+            .expire(&key, TTL)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await;
+        if let Err(e) = result {
+            tracing::warn!(game = %game, "Failed to keep event: {e}");
+        }
+    });
+}
+
+/// The kept events of the specified game that came after the specified sequence number, in
+/// order. Those that are too old to have been kept are missing.
+pub(super) async fn since(
+    state: &AppState,
+    game: Uuid,
+    sequence: u64,
+) -> Result<Vec<Event>, StringError> {
+    let mut conn = state.redis.get().await?;
+    let events: Vec<String> = conn
+        .zrangebyscore(key(game), format!("({sequence}"), "+inf")
+        .await?;
+    Ok(events
+        .iter()
+        .filter_map(|event| serde_json::from_str(event).ok())
+        .collect())
+}
+
+/// The sequence number of the latest kept event of the specified game, if any are kept.
+pub(super) async fn latest(state: &AppState, game: Uuid) -> Option<u64> {
+    let mut conn = state.redis.get().await.ok()?;
+    let latest: Vec<(String, u64)> = conn.zrevrange_withscores(key(game), 0, 0).await.ok()?;
+    latest.first().map(|&(_, sequence)| sequence)
+}
@@ -1,16 +1,24 @@
 use crate::server::{
-    handlers::{Response, StringError},
-    helpers,
+    bots,
+    entities::prelude::Member,
+    handlers::{ApiError, Response, StringError},
+    helpers, moderation,
+    network::ClientIp,
+    pagination::{Pagination, PaginationParams},
     state::AppState,
     strings,
+    validation::{Valid, Validate, Validator},
 };
 use axum::{
     async_trait,
-    extract::{FromRef, FromRequestParts, State},
-    http::{request::Parts, StatusCode},
+    extract::{FromRef, FromRequest, FromRequestParts, Query, Request, State},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
     response::IntoResponse,
+    Json,
 };
 use axum_extra::extract::CookieJar;
+use sea_orm::EntityTrait;
+use serde::de::DeserializeOwned;
 use serde_json::json;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -18,6 +26,10 @@ use uuid::Uuid;
 pub struct User {
     pub id: Uuid,
     pub username: String,
+    /// The IANA name of the time zone the user lives in, for showing them local times.
+    pub timezone: String,
+    /// Whether the account is run by a program rather than a person.
+    pub bot: bool,
 }
 
 #[async_trait]
@@ -26,39 +38,142 @@ where
     S: Send + Sync,
     Arc<AppState>: FromRef<S>,
 {
-    type Rejection = StringError;
+    type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         // Extract the session cookie from the app state. We use `from_request_parts` because we want to be able
         // to use other extractors after this one, and `from_request` consumes the request.
         let jar = CookieJar::from_request_parts(parts, state).await.unwrap();
         let state: State<Arc<AppState>> = State::from_request_parts(parts, state).await.unwrap();
-        let sid = jar
-            .get(strings::SESSION_COOKIE_NAME)
-            .ok_or(StringError(
-                strings::INVALID_TOKEN.into(),
-                StatusCode::UNAUTHORIZED,
-            ))?
-            .value_trimmed();
-        // Fetch the session associated with the cookie and then fetch the user associated with the session.
-        let session = helpers::get_session(&state, sid).await?;
+        // Bots authenticate with their API token in a header rather than a session cookie.
+        let bot = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| value.starts_with(bots::TOKEN_SCHEME));
+        let session = if let Some(token) = bot {
+            let id = helpers::get_session(&state, token).await?;
+            bots::ensure_within_limit(&state, Uuid::try_from(id.as_str()).unwrap())?;
+            id
+        } else {
+            let sid = jar
+                .get(strings::SESSION_COOKIE_NAME)
+                .ok_or(StringError(
+                    strings::INVALID_TOKEN.into(),
+                    StatusCode::UNAUTHORIZED,
+                ))?
+                .value_trimmed();
+            // Fetch the session associated with the cookie and then fetch the user associated with the session.
+            helpers::get_session(&state, sid).await?
+        };
         let user = helpers::get_user(&state, &session, false).await?;
+        // Suspended users keep their sessions, but can't do anything with them.
+        moderation::ensure_not_suspended(&state, user.id).await?;
         Ok(User {
             id: user.id,
             username: user.username,
+            timezone: user.timezone,
+            bot: user.bot,
         })
     }
 }
 
+/// A signed-in user who is also an admin. Anyone else is turned away, with a 401 if they
+/// aren't signed in and a 403 if they are.
+pub struct Admin(pub User);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Admin
+where
+    S: Send + Sync,
+    Arc<AppState>: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = User::from_request_parts(parts, state).await?;
+        let state: State<Arc<AppState>> = State::from_request_parts(parts, state).await.unwrap();
+        // Go to the database rather than the user cache, so that taking someone's admin
+        // rights away takes effect straight away.
+        let member = Member::find_by_id(user.id)
+            .one(state.database.as_ref())
+            .await
+            .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        if !member.is_some_and(|member| member.admin) {
+            return Err(StringError(strings::NOT_ADMIN.into(), StatusCode::FORBIDDEN).into());
+        }
+        Ok(Admin(user))
+    }
+}
+
 impl IntoResponse for User {
     fn into_response(self) -> axum::response::Response {
         Response::new(
             json!({
                 "id": self.id,
                 "username": self.username,
+                "timezone": self.timezone,
+                "bot": self.bot,
             }),
             StatusCode::OK,
         )
         .into_response()
     }
 }
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = StringError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        // The network middleware records the address; it's missing when the server wasn't
+        // started with connection info.
+        parts.extensions.get::<Self>().copied().ok_or(StringError(
+            strings::BAD_REQUEST.into(),
+            StatusCode::BAD_REQUEST,
+        ))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Pagination
+where
+    S: Send + Sync,
+{
+    type Rejection = StringError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(params) = Query::<PaginationParams>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| StringError(e.body_text(), StatusCode::BAD_REQUEST))?;
+        Pagination::new(params)
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Valid<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(body) = Json::<T>::from_request(req, state).await.map_err(|e| {
+            StringError(
+                e.body_text().replace(
+                    "Failed to deserialize the JSON body into the target type: ",
+                    "",
+                ),
+                StatusCode::BAD_REQUEST,
+            )
+        })?;
+        let mut v = Validator::default();
+        body.validate(&mut v);
+        v.finish()?;
+        Ok(Valid(body))
+    }
+}
@@ -0,0 +1,216 @@
+//! Heuristics for spotting assisted play, for admins looking into a player: how often their
+//! moves are the ones the engine would have picked, and whether the time they take over them
+//! looks like a person's. Neither proves anything on its own; they only point out players
+//! worth a closer look.
+
+use crate::{
+    server::{
+        entities::{finished_game::Column, game, prelude::FinishedGame},
+        handlers::StringError,
+        helpers, moves, review,
+        state::AppState,
+    },
+    Piece,
+};
+use axum::http::StatusCode;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// How many of a player's most recently finished games are looked at.
+const GAMES: u64 = 50;
+/// How many moves into a game the player's moves start counting. Openings are learnt by
+/// heart, so matching the engine there says little.
+const OPENING_PLIES: usize = 8;
+/// How many moves a rate has to be judged on before a player can be flagged for it.
+const MIN_MOVES: usize = 40;
+/// The share of moves matching the engine's choice above which a player is flagged.
+const MATCH_RATE_THRESHOLD: f64 = 0.9;
+/// How little a player's move times can vary, as a share of their average, before they're
+/// flagged. People take longer over hard moves than easy ones.
+const TIME_VARIATION_THRESHOLD: f64 = 0.2;
+
+/// Something about a player's play that stands out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum Flag {
+    /// Their moves match the engine's choice far more often than anyone's should.
+    HighMatchRate,
+    /// They take much the same time over every move, however hard.
+    SteadyTiming,
+}
+
+/// How one of the player's games went, move for move against the engine.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct GameReport {
+    pub game: Uuid,
+    pub moves: usize,
+    pub engine_matches: usize,
+    pub match_rate: Option<f64>,
+}
+
+/// What the heuristics made of a player's games.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct Report {
+    /// How many moves were compared with the engine's choice, across every game.
+    pub moves: usize,
+    pub engine_matches: usize,
+    pub match_rate: Option<f64>,
+    /// How many moves had their times recorded.
+    pub timed_moves: usize,
+    /// How long the player took over a move on average, in milliseconds.
+    pub average_time: Option<f64>,
+    /// The standard deviation of the player's move times, as a share of their average.
+    pub time_variation: Option<f64>,
+    pub flags: Vec<Flag>,
+    /// The games looked at, newest first.
+    pub games: Vec<GameReport>,
+}
+
+/// The share of the specified total that the specified count makes up, if there's any total.
+#[allow(clippy::cast_precision_loss)] // Nobody plays that many moves
+fn rate(count: usize, total: usize) -> Option<f64> {
+    (total > 0).then(|| count as f64 / total as f64)
+}
+
+/// The average of the specified move times, and their standard deviation as a share of it.
+#[allow(clippy::cast_precision_loss)] // Nobody takes that long over a move, or plays that many
+fn spread(times: &[i64]) -> (Option<f64>, Option<f64>) {
+    if times.is_empty() {
+        return (None, None);
+    }
+    let count = times.len() as f64;
+    let mean = times.iter().map(|&time| time as f64).sum::<f64>() / count;
+    let variance = times
+        .iter()
+        .map(|&time| (time as f64 - mean).powi(2))
+        .sum::<f64>()
+        / count;
+    (Some(mean), (mean > 0.0).then(|| variance.sqrt() / mean))
+}
+
+/// Weigh up a player's games, given whether each of their moves (past the opening) matched
+/// the engine's choice, game by game, and how long they took over each of their timed moves,
+/// in milliseconds.
+pub(super) fn assess(games: &[(Uuid, Vec<bool>)], times: &[i64]) -> Report {
+    let games: Vec<_> = games
+        .iter()
+        .map(|(game, matches)| {
+            let engine_matches = matches.iter().filter(|&&matched| matched).count();
+            GameReport {
+                game: *game,
+                moves: matches.len(),
+                engine_matches,
+                match_rate: rate(engine_matches, matches.len()),
+            }
+        })
+        .collect();
+    let moves = games.iter().map(|game| game.moves).sum();
+    let engine_matches = games.iter().map(|game| game.engine_matches).sum();
+    let match_rate = rate(engine_matches, moves);
+    let (average_time, time_variation) = spread(times);
+    let mut flags = Vec::new();
+    if moves >= MIN_MOVES && match_rate.is_some_and(|rate| rate >= MATCH_RATE_THRESHOLD) {
+        flags.push(Flag::HighMatchRate);
+    }
+    if times.len() >= MIN_MOVES
+        && time_variation.is_some_and(|variation| variation < TIME_VARIATION_THRESHOLD)
+    {
+        flags.push(Flag::SteadyTiming);
+    }
+    Report {
+        moves,
+        engine_matches,
+        match_rate,
+        timed_moves: times.len(),
+        average_time,
+        time_variation,
+        flags,
+        games,
+    }
+}
+
+/// Run the specified player's most recently finished games through the engine (those that
+/// haven't been analysed already) and weigh up how they played them.
+pub(super) async fn report(state: &AppState, player: Uuid) -> Result<Report, StringError> {
+    let id = player.to_string();
+    let finished = FinishedGame::find()
+        .filter(Column::Host.eq(&id).or(Column::Guest.eq(&id)))
+        // Game IDs are time-ordered, so this puts the newest first.
+        .order_by_desc(Column::Id)
+        .limit(GAMES)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let mut games = Vec::with_capacity(finished.len());
+    for game in finished.into_iter().map(game::Model::from) {
+        let Some(position) = game
+            .state
+            .clone()
+            .and_then(|saved| serde_json::from_value::<crate::Game>(saved).ok())
+        else {
+            continue;
+        };
+        let piece = if helpers::black_white(&game).0 == Some(player) {
+            Piece::Black
+        } else {
+            Piece::White
+        };
+        let reviewed = review::get_or_analyse(
+            state,
+            game.id,
+            helpers::game_settings(&game),
+            position.history(),
+        )
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        let matches: Vec<_> = reviewed
+            .iter()
+            .enumerate()
+            .skip(OPENING_PLIES)
+            .filter(|&(ply, _)| review::mover(ply) == piece)
+            .map(|(_, reviewed)| reviewed.played == reviewed.annotation.best)
+            .collect();
+        games.push((game.id, matches));
+    }
+    let times: Vec<_> = moves::of_player(state, player, games.iter().map(|(game, _)| *game))
+        .await?
+        .iter()
+        .map(|played| played.time_taken)
+        .collect();
+    Ok(assess(&games, &times))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assess, Flag, MIN_MOVES};
+    use uuid::Uuid;
+
+    #[test]
+    fn flags() {
+        // A player who matches the engine now and then, and takes their time over hard moves.
+        let games = [(Uuid::nil(), (0..MIN_MOVES).map(|i| i % 2 == 0).collect())];
+        let times: Vec<_> = (0..MIN_MOVES)
+            .map(|i| [800, 2_500, 12_000][i % 3])
+            .collect();
+        let report = assess(&games, &times);
+        assert_eq!(report.moves, MIN_MOVES);
+        assert_eq!(report.match_rate, Some(0.5));
+        assert!(report.time_variation.unwrap() > 0.5);
+        assert!(report.flags.is_empty());
+        // One who always plays the engine's move after exactly the same pause.
+        let games = [(Uuid::nil(), vec![true; MIN_MOVES])];
+        let report = assess(&games, &vec![1_500; MIN_MOVES]);
+        assert_eq!(report.match_rate, Some(1.0));
+        assert_eq!(report.time_variation, Some(0.0));
+        assert_eq!(report.flags, [Flag::HighMatchRate, Flag::SteadyTiming]);
+        // Too few moves to judge by aren't flagged, however they look.
+        let report = assess(&[(Uuid::nil(), vec![true; 5])], &[1_500; 5]);
+        assert!(report.flags.is_empty());
+        assert_eq!(report.games[0].engine_matches, 5);
+        // Players without any moves have no rates at all.
+        let report = assess(&[], &[]);
+        assert_eq!(report.match_rate, None);
+        assert_eq!(report.average_time, None);
+    }
+}
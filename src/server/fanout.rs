@@ -0,0 +1,258 @@
+use crate::server::{
+    packet::{Event, ServerMessage},
+    pool::RedisPool,
+    state::AppState,
+};
+use futures::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// The channels events for the connections in a game's room are published to, followed by
+/// the game's ID.
+const ROOM_CHANNEL: &str = "olly:room:";
+/// The channels events for a user's connections are published to, followed by their ID.
+const USER_CHANNEL: &str = "olly:user:";
+/// The channel events for the connections subscribed to the lobby are published to.
+pub(super) const LOBBY_CHANNEL: &str = "olly:lobby";
+/// How long the relay waits before subscribing again after losing its connection.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// An event as it's published, tagged with the instance it was sent on so that instance
+/// doesn't deliver it twice.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    origin: Uuid,
+    event: Event,
+}
+
+pub(super) fn room_channel(game: Uuid) -> String {
+    format!("{ROOM_CHANNEL}{game}")
+}
+
+pub(super) fn user_channel(user: Uuid) -> String {
+    format!("{USER_CHANNEL}{user}")
+}
+
+/// Publish an event sent on this instance, so that other instances can pass it on to the
+/// connections they hold. Events that can't be published are only seen on this instance.
+pub(super) fn publish(state: &AppState, channel: &str, event: &Event) {
+    let envelope = Envelope {
+        origin: state.instance,
+        event: event.clone(),
+    };
+    let outbox = state.outbox.get_or_init(|| {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(send(Arc::clone(&state.redis), rx));
+        tx
+    });
+    let _ = outbox.send((channel.into(), serde_json::to_string(&envelope).unwrap()));
+}
+
+/// Publish events one at a time, so that they arrive in the order they were sent.
+async fn send(redis: Arc<RedisPool>, mut outbox: mpsc::UnboundedReceiver<(String, String)>) {
+    while let Some((channel, payload)) = outbox.recv().await {
+        let result = match redis.get().await {
+            Ok(mut conn) => conn.publish::<_, _, ()>(&channel, payload).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!(channel, "Failed to publish event: {e}");
+        }
+    }
+}
+
+/// Pass events published by other instances on to the connections this one holds, until the
+/// server starts shutting down. Subscribes again whenever the connection drops.
+pub fn relay(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                result = subscribe(&state) => {
+                    if let Err(e) = result {
+                        tracing::error!("Lost the connection to other instances: {e}");
+                    }
+                }
+                () = state.shutting_down() => return,
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+async fn subscribe(state: &AppState) -> redis::RedisResult<()> {
+    let mut pubsub = state.redis.client().get_async_pubsub().await?;
+    pubsub.psubscribe(format!("{ROOM_CHANNEL}*")).await?;
+    pubsub.psubscribe(format!("{USER_CHANNEL}*")).await?;
+    pubsub.subscribe(LOBBY_CHANNEL).await?;
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let payload: String = message.get_payload()?;
+        match serde_json::from_str::<Envelope>(&payload) {
+            Ok(envelope) if envelope.origin != state.instance => {
+                deliver(state, message.get_channel_name(), envelope.event);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Ignoring malformed event: {e}"),
+        }
+    }
+    Err(redis::RedisError::from((
+        redis::ErrorKind::IoError,
+        "subscription ended",
+    )))
+}
+
+/// Send an event published by another instance to the connections on this one it's for.
+fn deliver(state: &AppState, channel: &str, event: Event) {
+    if let Some(game) = channel.strip_prefix(ROOM_CHANNEL) {
+        let Ok(game) = Uuid::parse_str(game) else {
+            return;
+        };
+        // Number this instance's events for the game after the other's.
+        if let Some(sequence) = event.s {
+            state.resume_sequence(game, sequence);
+        }
+        match event.data() {
+            // Keep this instance's copy of the game in step, so that moves can be made here
+            // too.
+            ServerMessage::GameUpdate { game: position } => {
+                let mut games = state.games.lock().expect("mutex was poisoned");
+                if let Some(local) = games.get_mut(&game) {
+                    *local = position.clone();
+                }
+                drop(games);
+                state.start_turn(game);
+            }
+            ServerMessage::GameAbort => {
+                let mut rooms = state.rooms.lock().expect("mutex was poisoned");
+                if let Some(tx) = rooms.remove(&game) {
+                    let _ = tx.send(event);
+                }
+                drop(rooms);
+                state
+                    .games
+                    .lock()
+                    .expect("mutex was poisoned")
+                    .remove(&game);
+                state
+                    .turns
+                    .lock()
+                    .expect("mutex was poisoned")
+                    .remove(&game);
+                state
+                    .sequences
+                    .lock()
+                    .expect("mutex was poisoned")
+                    .remove(&game);
+                return;
+            }
+            _ => {}
+        }
+        let rooms = state.rooms.lock().expect("mutex was poisoned");
+        if let Some(tx) = rooms.get(&game) {
+            let _ = tx.send(event);
+        }
+    } else if let Some(user) = channel.strip_prefix(USER_CHANNEL) {
+        let Ok(user) = Uuid::parse_str(user) else {
+            return;
+        };
+        let users = state.users.lock().expect("mutex was poisoned");
+        if let Some(tx) = users.get(&user) {
+            let _ = tx.send(event);
+        }
+    } else if channel == LOBBY_CHANNEL {
+        let _ = state.lobby.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{self, strings};
+    use serde_json::json;
+    use std::{sync::Arc, time::Duration};
+    use test_utils::{function, Client, Map, Socket};
+
+    async fn instance() -> (Arc<server::AppState>, String) {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis).with_fanout());
+        super::relay(Arc::clone(&state));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        (state, url)
+    }
+
+    /// Open a connection and join the specified game on it.
+    async fn join(url: &str, token: &str, id: &str) -> Socket {
+        let mut socket = Socket::connect(url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        socket.recv_op(4).await;
+        socket
+    }
+
+    /// Make the first legal move on one connection, and check that the opponent sees it on
+    /// theirs.
+    async fn play(
+        socket: &mut Socket,
+        opponent: &mut Socket,
+        token: &str,
+        id: &str,
+        game: &mut crate::Game,
+    ) {
+        let piece = game.turn();
+        let (x, y) = game.moves(piece)[0];
+        game.place(x, y, piece).unwrap();
+        socket
+            .send(json!({
+                "op": 2,
+                "d": { "type": "Place", "id": id, "x": x, "y": y, "piece": piece },
+                "t": token,
+            }))
+            .await;
+        socket.recv_op(1).await;
+        let seen =
+            async { while opponent.recv_op(4).await["d"]["game"]["turn"] != json!(game.turn()) {} };
+        tokio::time::timeout(Duration::from_secs(5), seen)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn instances() {
+        let (first, url) = instance().await;
+        let (second, elsewhere) = instance().await;
+        // Give the relays a moment to subscribe.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Map = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp["message"]["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        // The host plays through the instance the game was started on, and the guest through
+        // another one.
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let guest_token = other.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut black = join(&url, &token, &id).await;
+        let mut white = join(&elsewhere, &guest_token, &id).await;
+        // Each player sees the other's moves, even though they're connected to different
+        // instances.
+        let mut game = crate::Game::new();
+        play(&mut black, &mut white, &token, &id, &mut game).await;
+        play(&mut white, &mut black, &guest_token, &id, &mut game).await;
+        first.shut_down();
+        second.shut_down();
+    }
+}
@@ -0,0 +1,563 @@
+use super::StringError;
+use super::{report::validate_reason, user_summaries, user_summary};
+use crate::server::{
+    audit::{Action, Entry},
+    entities::{
+        audit_log, ban,
+        member::Column as MemberColumn,
+        prelude::{AuditLog, Ban, Member, Report},
+        report,
+    },
+    extractors::Admin,
+    fair_play, helpers, moderation,
+    network::ClientIp,
+    pagination::Pagination,
+    state::AppState,
+    strings, timestamp,
+    validation::{Valid, Validate, Validator},
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BanRequest {
+    reason: String,
+    /// How long the ban lasts. Bans without a duration are permanent.
+    hours: Option<i64>,
+}
+
+impl Validate for BanRequest {
+    fn validate(&self, v: &mut Validator) {
+        validate_reason(v, &self.reason);
+        v.ensure(
+            "hours",
+            self.hours.is_none_or(|hours| hours > 0),
+            strings::INVALID_BAN_DURATION,
+        );
+    }
+}
+
+fn ban_json(ban: &ban::Model) -> serde_json::Value {
+    json!({
+        "id": ban.id,
+        "member": ban.member,
+        "reason": ban.reason,
+        "expires_at": ban.expires_at.as_ref().map(timestamp::rfc3339),
+        "created_at": timestamp::rfc3339(&ban.created_at),
+    })
+}
+
+/// Ban the specified user, either for a number of hours or permanently. Banned users can't
+/// use their sessions until the ban expires or is lifted.
+pub async fn ban(
+    State(state): State<Arc<AppState>>,
+    Admin(actor): Admin,
+    ip: Option<ClientIp>,
+    Path(username): Path<String>,
+    Valid(body): Valid<BanRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let member = helpers::get_user(&state, &username, true).await?;
+    let ban = moderation::ban(&state, member.id, body.reason, body.hours).await?;
+    helpers::forget_user(&state, &member).await;
+    Entry::new(Action::Ban)
+        .actor(Some(actor.id))
+        .target(member.id)
+        .details(json!({ "ban": ban.id, "reason": ban.reason, "hours": body.hours }))
+        .ip(ip)
+        .record(&state)
+        .await?;
+    Ok(super::Response::new(ban_json(&ban), StatusCode::CREATED))
+}
+
+/// Fetch every ban the specified user has ever received, most recent first.
+pub async fn bans(
+    State(state): State<Arc<AppState>>,
+    _: Admin,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let member = helpers::get_user(&state, &username, true).await?;
+    let bans = Ban::find()
+        .filter(ban::Column::Member.eq(member.id))
+        .order_by_desc(ban::Column::CreatedAt)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let bans: Vec<_> = bans.iter().map(ban_json).collect();
+    Ok(super::Response::new(bans, StatusCode::OK))
+}
+
+/// Look into whether the specified user has been getting help with their moves: their most
+/// recently finished games are run through the engine, and their rate of matching its choices
+/// and the times they took over their moves are checked for anything that stands out.
+pub async fn fair_play(
+    State(state): State<Arc<AppState>>,
+    _: Admin,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let member = helpers::get_user(&state, &username, true).await?;
+    let report = fair_play::report(&state, member.id).await?;
+    let mut body = json!(report);
+    body["user"] = json!(user_summary(&state, &member).await);
+    Ok(super::Response::new(body, StatusCode::OK))
+}
+
+/// Lift the specified ban early.
+pub async fn lift_ban(
+    State(state): State<Arc<AppState>>,
+    Admin(actor): Admin,
+    ip: Option<ClientIp>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let not_found = || StringError(strings::BAN_NOT_FOUND.into(), StatusCode::NOT_FOUND);
+    let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+    let ban = Ban::find_by_id(id)
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(not_found)?;
+    let result = Ban::delete_by_id(id)
+        .exec(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    if result.rows_affected == 0 {
+        return Err(not_found().into_response());
+    }
+    let members = helpers::get_users_by_ids(&state, [ban.member]).await?;
+    if let Some(member) = members.get(&ban.member) {
+        helpers::forget_user(&state, member).await;
+    }
+    Entry::new(Action::BanLift)
+        .actor(Some(actor.id))
+        .target(ban.member)
+        .details(json!({ "ban": ban.id }))
+        .ip(ip)
+        .record(&state)
+        .await?;
+    Ok(super::Response::new(json!({}), StatusCode::OK))
+}
+
+/// Reload the engine's assets (e.g. evaluation weights) from disk without restarting. If any
+/// of them fail to load, the ones already in use are kept.
+pub async fn reload_assets(
+    State(state): State<Arc<AppState>>,
+    Admin(actor): Admin,
+    ip: Option<ClientIp>,
+) -> Result<impl IntoResponse, Response> {
+    let reloaded = state
+        .reload_assets()
+        .await
+        .map_err(|e| StringError(e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Entry::new(Action::AssetsReload)
+        .actor(Some(actor.id))
+        .details(json!({ "reloaded": reloaded }))
+        .ip(ip)
+        .record(&state)
+        .await?;
+    Ok(super::Response::new(
+        json!({ "reloaded": reloaded }),
+        StatusCode::OK,
+    ))
+}
+
+/// Fetch the reports waiting for review, oldest first. Reported messages are checked against
+/// the word filter, so the ones that trip it can be dealt with first.
+pub async fn reports(
+    State(state): State<Arc<AppState>>,
+    _: Admin,
+) -> Result<impl IntoResponse, Response> {
+    let reports = Report::find()
+        .filter(report::Column::Resolved.eq(false))
+        .order_by_asc(report::Column::CreatedAt)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let members = Member::find()
+        .filter(
+            MemberColumn::Id.is_in(
+                reports
+                    .iter()
+                    .flat_map(|report| [report.reporter, report.target]),
+            ),
+        )
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let summaries = user_summaries(&state, &members).await;
+    let summary = |id: Uuid| summaries.get(&id);
+    let reports: Vec<_> = reports
+        .iter()
+        .map(|report| {
+            json!({
+                "id": report.id,
+                "reporter": summary(report.reporter),
+                "target": summary(report.target),
+                "reason": report.reason,
+                "game": report.game,
+                "message": report.message,
+                "flagged": report
+                    .message
+                    .as_deref()
+                    .map(|message| state.word_filter.matches(message))
+                    .unwrap_or_default(),
+                "created_at": timestamp::rfc3339(&report.created_at),
+            })
+        })
+        .collect();
+    Ok(super::Response::new(reports, StatusCode::OK))
+}
+
+/// Mark the specified report as dealt with, taking it out of the queue.
+pub async fn resolve_report(
+    State(state): State<Arc<AppState>>,
+    Admin(actor): Admin,
+    ip: Option<ClientIp>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let not_found = || StringError(strings::REPORT_NOT_FOUND.into(), StatusCode::NOT_FOUND);
+    let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+    let report = Report::find_by_id(id)
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(not_found)?;
+    let target = report.target;
+    let mut active = report.into_active_model();
+    active.resolved = ActiveValue::set(true);
+    active
+        .update(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Entry::new(Action::ReportResolve)
+        .actor(Some(actor.id))
+        .target(target)
+        .details(json!({ "report": id }))
+        .ip(ip)
+        .record(&state)
+        .await?;
+    Ok(super::Response::new(json!({}), StatusCode::OK))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditParams {
+    /// Only include entries for actions taken by this user.
+    actor: Option<String>,
+    /// Only include entries for actions taken against this user.
+    target: Option<String>,
+    /// Only include entries for this kind of action (e.g. `ban`).
+    action: Option<Action>,
+}
+
+/// Fetch the audit log, most recent first, optionally narrowed down to an actor, a target or
+/// a kind of action.
+pub async fn audit(
+    State(state): State<Arc<AppState>>,
+    _: Admin,
+    Query(params): Query<AuditParams>,
+    pagination: Pagination,
+) -> Result<impl IntoResponse, Response> {
+    let mut query = AuditLog::find();
+    if let Some(actor) = params.actor {
+        let actor = helpers::get_user(&state, &actor, true).await?;
+        query = query.filter(audit_log::Column::Actor.eq(actor.id));
+    }
+    if let Some(target) = params.target {
+        let target = helpers::get_user(&state, &target, true).await?;
+        query = query.filter(audit_log::Column::Target.eq(target.id));
+    }
+    if let Some(action) = params.action {
+        query = query.filter(audit_log::Column::Action.eq(action.name()));
+    }
+    let query = query.order_by(audit_log::Column::CreatedAt, pagination.order.into());
+    let (entries, page) = pagination.fetch(state.database.as_ref(), query).await?;
+    let ids: Vec<_> = entries
+        .iter()
+        .flat_map(|entry| [entry.actor, entry.target])
+        .flatten()
+        .collect();
+    let members = helpers::get_users_by_ids(&state, ids).await?;
+    let summaries = user_summaries(&state, members.values()).await;
+    let summary = |id: Option<Uuid>| id.and_then(|id| summaries.get(&id));
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            json!({
+                "id": entry.id,
+                "action": entry.action,
+                "actor": summary(entry.actor),
+                "target": summary(entry.target),
+                "details": entry.details,
+                "ip": entry.ip,
+                "created_at": timestamp::rfc3339(&entry.created_at),
+            })
+        })
+        .collect();
+    Ok(super::Response::paginated(entries, page, StatusCode::OK))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        server::{
+            self, correspondence,
+            entities::{game, member, prelude::Member},
+            helpers, review, strings, NetworkPolicy, WordFilter,
+        },
+        Game,
+    };
+    use chrono::Utc;
+    use sea_orm::{
+        sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter,
+    };
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client};
+    use uuid::Uuid;
+
+    /// Give the specified user admin rights.
+    async fn promote(state: &server::AppState, username: &str) {
+        Member::update_many()
+            .col_expr(member::Column::Admin, Expr::value(true))
+            .filter(member::Column::Username.eq(username))
+            .exec(state.database.as_ref())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = server::AppState::new(database, redis)
+            .with_network_policy(NetworkPolicy {
+                admin_allowlist: vec!["127.0.0.1/32".parse().unwrap()],
+                ..NetworkPolicy::default()
+            })
+            .with_word_filter(WordFilter::new(&["heck"]));
+        let state = Arc::new(state);
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let user = format!("{}::1", function!());
+        let target = format!("{}::2", function!());
+        let client = Client::authenticated(&[&user, &target], &url, true).await;
+        // Being on the allowlist isn't enough without admin rights.
+        let resp: serde_json::Value = client.get(&url, "/admin/reports").await;
+        assert_eq!(resp["code"], "not_admin");
+        promote(&state, &user).await;
+        let resp: serde_json::Value = client
+            .post(&url, "/reports", json!({ "user": user, "reason": "rude" }))
+            .await;
+        assert_eq!(resp["code"], "report_self");
+        assert_eq!(resp["message"], strings::REPORT_SELF);
+        let resp: serde_json::Value = client
+            .post(
+                &url,
+                "/reports",
+                json!({ "user": target, "reason": "rude", "message": "what the HECK" }),
+            )
+            .await;
+        assert_eq!(resp["code"], 201);
+        let id = resp["message"]["id"].as_str().unwrap().to_string();
+        let resp: serde_json::Value = client.get(&url, "/admin/reports").await;
+        let report = resp["message"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|report| report["id"] == id.as_str())
+            .unwrap()
+            .clone();
+        assert_eq!(report["target"]["username"], target.as_str());
+        assert_eq!(report["flagged"], json!(["heck"]));
+        let resp: serde_json::Value = client
+            .post(&url, &format!("/admin/reports/{id}/resolve"), json!({}))
+            .await;
+        assert_eq!(resp["code"], 200);
+        let resp: serde_json::Value = client.get(&url, "/admin/reports").await;
+        assert!(resp["message"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|report| report["id"] != id.as_str()));
+    }
+
+    #[tokio::test]
+    async fn bans() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = server::AppState::new(database, redis).with_network_policy(NetworkPolicy {
+            admin_allowlist: vec!["127.0.0.1/32".parse().unwrap()],
+            ..NetworkPolicy::default()
+        });
+        let state = Arc::new(state);
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let user = function!();
+        let admin = format!("{user}::admin");
+        let moderator = Client::authenticated(&[&admin, &user], &url, true).await;
+        let client = Client::authenticated(&[&user], &url, false).await;
+        promote(&state, &admin).await;
+        let ban = json!({ "reason": "spam", "hours": 2 });
+        let path = format!("/admin/users/{user}/bans");
+        let resp: serde_json::Value = Client::new().post(&url, &path, &ban).await;
+        assert_eq!(resp["status"], 401);
+        let resp: serde_json::Value = client.post(&url, &path, &ban).await;
+        assert_eq!(resp["status"], 403);
+        assert_eq!(resp["message"], strings::NOT_ADMIN);
+        let resp: serde_json::Value = moderator.post(&url, &path, &ban).await;
+        assert_eq!(resp["code"], 201);
+        let id = resp["message"]["id"].as_str().unwrap().to_string();
+        // The user's existing session stops working.
+        let resp: serde_json::Value = client.get(&url, "/@me").await;
+        assert_eq!(resp["code"], "suspended");
+        assert!(resp["message"]
+            .as_str()
+            .unwrap()
+            .starts_with(strings::SUSPENDED));
+        assert!(resp["message"].as_str().unwrap().ends_with("spam"));
+        let resp: serde_json::Value = moderator.get(&url, &path).await;
+        assert_eq!(resp["message"][0]["reason"], "spam");
+        let resp: serde_json::Value = moderator.delete(&url, &format!("/admin/bans/{id}")).await;
+        assert_eq!(resp["code"], 200);
+        let resp: serde_json::Value = client.get(&url, "/@me").await;
+        assert_eq!(resp["code"], 200);
+        let resp: serde_json::Value = moderator
+            .get(&url, &format!("/admin/audit?target={user}"))
+            .await;
+        let actions: Vec<_> = resp["message"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(actions, ["ban_lift", "ban"]);
+        assert_eq!(resp["message"][1]["actor"]["username"], admin.as_str());
+        assert_eq!(resp["message"][1]["target"]["username"], user.as_str());
+        assert_eq!(resp["message"][1]["details"]["reason"], "spam");
+        assert_eq!(resp["message"][1]["ip"], "127.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn reload_assets() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let path = std::env::temp_dir().join(format!("weights-{}.json", uuid::Uuid::now_v7()));
+        let book_path =
+            std::env::temp_dir().join(format!("openings-{}.json", uuid::Uuid::now_v7()));
+        let state = server::AppState::new(database, redis)
+            .with_network_policy(NetworkPolicy {
+                admin_allowlist: vec!["127.0.0.1/32".parse().unwrap()],
+                ..NetworkPolicy::default()
+            })
+            .with_eval_weights(&path)
+            .with_opening_book(&book_path);
+        let state = Arc::new(state);
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let resp: serde_json::Value = Client::new()
+            .post(&url, "/admin/assets/reload", json!({}))
+            .await;
+        assert_eq!(resp["status"], 401);
+        let user = function!();
+        let client = Client::authenticated(&[&user], &url, true).await;
+        promote(&state, &user).await;
+        // A broken file leaves the weights in use alone.
+        std::fs::write(&path, "not json").unwrap();
+        let resp: serde_json::Value = client.post(&url, "/admin/assets/reload", json!({})).await;
+        assert_eq!(resp["status"], 500);
+        assert_eq!(state.assets.weights().mobility, 5);
+        // Nor are the weights replaced when the book can't be read.
+        std::fs::write(&path, r#"{"mobility": 8}"#).unwrap();
+        std::fs::write(&book_path, r#"{"Corner": "a1"}"#).unwrap();
+        let resp: serde_json::Value = client.post(&url, "/admin/assets/reload", json!({})).await;
+        assert_eq!(resp["status"], 500);
+        assert_eq!(state.assets.weights().mobility, 5);
+        std::fs::write(&book_path, r#"{"Rose": "f5d6c5f4e3"}"#).unwrap();
+        let resp: serde_json::Value = client.post(&url, "/admin/assets/reload", json!({})).await;
+        assert_eq!(resp["code"], 200);
+        assert_eq!(
+            resp["message"]["reloaded"],
+            json!(["eval_weights", "opening_book"])
+        );
+        assert_eq!(state.assets.weights().mobility, 8);
+        let rose = [(5, 4), (3, 5), (2, 4), (5, 3), (4, 2)];
+        assert_eq!(state.assets.book().name(&rose), Some("Rose"));
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(book_path);
+    }
+
+    #[tokio::test]
+    async fn fair_play() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = server::AppState::new(database, redis).with_network_policy(NetworkPolicy {
+            admin_allowlist: vec!["127.0.0.1/32".parse().unwrap()],
+            ..NetworkPolicy::default()
+        });
+        let state = Arc::new(state);
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let user = function!();
+        let admin = format!("{user}::admin");
+        let other = format!("{user}::other");
+        let client = Client::authenticated(&[&admin, &user, &other], &url, true).await;
+        let path = format!("/admin/users/{user}/fair-play");
+        let resp: serde_json::Value = client.get(&url, &path).await;
+        assert_eq!(resp["message"], strings::NOT_ADMIN);
+        promote(&state, &admin).await;
+        // A game that finished before it could be analysed.
+        let mut position = Game::new();
+        for _ in 0..12 {
+            let turn = position.turn();
+            let (x, y) = position.moves(turn)[0];
+            position.place(x, y, turn).unwrap();
+        }
+        let host = helpers::get_user(&state, &user, true).await.unwrap().id;
+        let guest = helpers::get_user(&state, &other, true).await.unwrap().id;
+        let finished = game::ActiveModel {
+            id: ActiveValue::set(Uuid::now_v7()),
+            host: ActiveValue::set(host.to_string()),
+            guest: ActiveValue::set(guest.to_string()),
+            pending: ActiveValue::set(false),
+            ended: ActiveValue::set(true),
+            challenge: ActiveValue::set(None),
+            result: ActiveValue::set(None),
+            settings: ActiveValue::set(json!({})),
+            state: ActiveValue::set(Some(serde_json::to_value(&position).unwrap())),
+            turn_started_at: ActiveValue::set(None),
+            opponent: ActiveValue::set(None),
+            arena: ActiveValue::set(None),
+            host_opponent: ActiveValue::set(None),
+            ended_at: ActiveValue::set(Some(Utc::now().fixed_offset())),
+            mode: ActiveValue::set(correspondence::LIVE.into()),
+            host_color: ActiveValue::set(String::from("black")),
+        }
+        .insert(state.database.as_ref())
+        .await
+        .unwrap();
+        let resp: serde_json::Value = client.get(&url, &path).await;
+        assert_eq!(resp["code"], 200);
+        let report = &resp["message"];
+        assert_eq!(report["user"]["username"], user.as_str());
+        assert_eq!(report["games"][0]["game"], json!(finished.id));
+        // Only black's moves past the opening count.
+        assert_eq!(report["moves"], 2);
+        assert_eq!(report["timed_moves"], 0);
+        assert_eq!(report["flags"], json!([]));
+        // The game's analysis is kept, like any other.
+        assert!(review::get(&state, finished.id).await.unwrap().is_some());
+    }
+}
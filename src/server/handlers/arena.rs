@@ -0,0 +1,240 @@
+use super::{user_summaries, StringError};
+use crate::server::{
+    arena::{self as exhibition, Contender, MAX_GAMES},
+    audit::{Action, Entry},
+    entities::{
+        archived_game, arena,
+        finished_game::Column as FinishedGameColumn,
+        game::{self, Column as GameColumn},
+        prelude::{ArchivedGame, Arena, FinishedGame, Game},
+    },
+    extractors::Admin,
+    helpers,
+    network::ClientIp,
+    state::AppState,
+    strings, timestamp,
+    validation::{Valid, Validate, Validator},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArenaRequest {
+    /// Plays black in the first game.
+    first: Contender,
+    second: Contender,
+    /// How many games to play. The colours swap each game.
+    #[serde(default = "default_games")]
+    games: i32,
+}
+
+fn default_games() -> i32 {
+    2
+}
+
+impl Validate for ArenaRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.ensure(
+            "games",
+            (1..=MAX_GAMES).contains(&self.games),
+            strings::INVALID_ARENA_GAMES,
+        )
+        .ensure(
+            "second",
+            self.first != self.second,
+            strings::ARENA_SAME_CONTENDER,
+        );
+    }
+}
+
+fn not_found() -> StringError {
+    StringError(strings::ARENA_NOT_FOUND.into(), StatusCode::NOT_FOUND)
+}
+
+/// Pit two bots (or two of the server's own opponents) against each other over a series of
+/// games, which start straight away.
+pub async fn start(
+    State(state): State<Arc<AppState>>,
+    Admin(actor): Admin,
+    ip: Option<ClientIp>,
+    Valid(body): Valid<ArenaRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let (arena, games) =
+        exhibition::start(&state, &body.first, &body.second, body.games, actor.id).await?;
+    Entry::new(Action::ArenaStart)
+        .actor(Some(actor.id))
+        .details(json!({
+            "arena": arena.id,
+            "first": body.first,
+            "second": body.second,
+            "games": body.games,
+        }))
+        .ip(ip)
+        .record(&state)
+        .await?;
+    Ok(super::Response::new(
+        arena_json(&state, &arena, &games).await?,
+        StatusCode::CREATED,
+    ))
+}
+
+/// Fetch an arena's games and how its contenders have fared against each other, both in this
+/// arena and in every arena they've met in.
+pub async fn arena(
+    State(state): State<Arc<AppState>>,
+    _: Admin,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+    let arena = Arena::find_by_id(id)
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(not_found)?;
+    let mut games = Game::find()
+        .filter(GameColumn::Arena.eq(arena.id))
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    // Long-finished arenas may have had their games archived.
+    let archived = ArchivedGame::find()
+        .filter(archived_game::Column::Arena.eq(arena.id))
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    games.extend(archived.into_iter().map(game::Model::from));
+    games.sort_by_key(|game| game.id);
+    Ok(super::Response::new(
+        arena_json(&state, &arena, &games).await?,
+        StatusCode::OK,
+    ))
+}
+
+async fn arena_json(
+    state: &AppState,
+    arena: &arena::Model,
+    games: &[game::Model],
+) -> Result<serde_json::Value, StringError> {
+    let (first, second) = (arena.first.to_string(), arena.second.to_string());
+    // Every arena game the two have played, whichever of them was first.
+    let meetings = FinishedGame::find()
+        .filter(FinishedGameColumn::Arena.is_not_null())
+        .filter(
+            Condition::any()
+                .add(
+                    Condition::all()
+                        .add(FinishedGameColumn::Host.eq(first.as_str()))
+                        .add(FinishedGameColumn::Guest.eq(second.as_str())),
+                )
+                .add(
+                    Condition::all()
+                        .add(FinishedGameColumn::Host.eq(second.as_str()))
+                        .add(FinishedGameColumn::Guest.eq(first.as_str())),
+                ),
+        )
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let meetings: Vec<_> = meetings.into_iter().map(game::Model::from).collect();
+    let members = helpers::get_users_by_ids(state, [arena.first, arena.second]).await?;
+    let summaries = user_summaries(state, members.values()).await;
+    let standing = exhibition::tally(games, arena.first);
+    let games: Vec<_> = games
+        .iter()
+        .map(|game| {
+            let (black, white) = helpers::black_white(game);
+            json!({
+                "id": game.id,
+                "black": black,
+                "white": white,
+                "ended": game.ended,
+                "result": game.result,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "id": arena.id,
+        "first": summaries.get(&arena.first),
+        "second": summaries.get(&arena.second),
+        "games": games,
+        "standing": standing,
+        "head_to_head": exhibition::tally(&meetings, arena.first),
+        "created_at": timestamp::rfc3339(&arena.created_at),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{
+        self,
+        entities::{member, prelude::Member},
+        handlers::{ApiError, Response},
+        strings, NetworkPolicy,
+    };
+    use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+    use serde_json::json;
+    use std::{sync::Arc, time::Duration};
+    use test_utils::{function, Client, Map};
+
+    #[tokio::test]
+    async fn arena() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = server::AppState::new(database, redis).with_network_policy(NetworkPolicy {
+            admin_allowlist: vec!["127.0.0.1/32".parse().unwrap()],
+            ..NetworkPolicy::default()
+        });
+        let state = Arc::new(state);
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let admin = function!();
+        let client = Client::authenticated(&[&admin], &url, true).await;
+        Member::update_many()
+            .col_expr(member::Column::Admin, Expr::value(true))
+            .filter(member::Column::Username.eq(&admin))
+            .exec(state.database.as_ref())
+            .await
+            .unwrap();
+        // People can't play in arenas, only bots.
+        let error: ApiError = client
+            .post(
+                &url,
+                "/admin/arena",
+                json!({ "first": { "bot": admin }, "second": { "difficulty": "easy" } }),
+            )
+            .await;
+        assert_eq!(error.message, strings::NOT_A_BOT);
+        let started: Response<Map> = client
+            .post(
+                &url,
+                "/admin/arena",
+                json!({
+                    "first": { "difficulty": "easy" },
+                    "second": { "difficulty": "medium" },
+                    "games": 1,
+                }),
+            )
+            .await;
+        let id = started.message["id"].as_str().unwrap().to_string();
+        // The server's own opponents play the whole game by themselves.
+        for _ in 0..100 {
+            let arena: Response<Map> = client.get(&url, &format!("/admin/arena/{id}")).await;
+            if arena.message["standing"]["played"] == 1 {
+                // Earlier arenas between the two count towards their head-to-head.
+                assert!(arena.message["head_to_head"]["played"].as_u64().unwrap() >= 1);
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("the arena game didn't finish");
+    }
+}
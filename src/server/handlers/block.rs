@@ -0,0 +1,218 @@
+use super::{user_summaries, StringError};
+use crate::server::{
+    entities::{
+        block::{ActiveModel, Column as BlockColumn},
+        friend::Column as FriendColumn,
+        friend_request::Column as FriendRequestColumn,
+        game::Column as GameColumn,
+        member::Column as MemberColumn,
+        prelude::{Block, Friend, FriendRequest, Game, Member},
+    },
+    extractors::User,
+    helpers,
+    state::AppState,
+    strings, timestamp,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, TransactionTrait};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Block the specified user. Any friendship, friend request or pending game invite between
+/// the two users is removed, and neither can send the other new ones until the block is lifted.
+pub async fn block(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let other = helpers::get_user(&state, &username, true).await?;
+    if user.id == other.id {
+        return Err(
+            StringError(strings::BLOCK_SELF.to_string(), StatusCode::BAD_REQUEST).into_response(),
+        );
+    }
+    let existing = Block::find_by_id((user.id, other.id))
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    if existing.is_some() {
+        return Err(
+            StringError(strings::ALREADY_BLOCKED.to_string(), StatusCode::CONFLICT).into_response(),
+        );
+    }
+    let txn = state
+        .database
+        .begin()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Block::insert(ActiveModel {
+        blocker: ActiveValue::Set(user.id),
+        blocked: ActiveValue::Set(other.id),
+        created_at: ActiveValue::NotSet,
+    })
+    .exec(&txn)
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Friend::delete_many()
+        .filter(
+            FriendColumn::A
+                .eq(user.id)
+                .and(FriendColumn::B.eq(other.id))
+                .or(FriendColumn::A
+                    .eq(other.id)
+                    .and(FriendColumn::B.eq(user.id))),
+        )
+        .exec(&txn)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    FriendRequest::delete_many()
+        .filter(
+            FriendRequestColumn::Sender
+                .eq(user.id)
+                .and(FriendRequestColumn::Recipient.eq(other.id))
+                .or(FriendRequestColumn::Sender
+                    .eq(other.id)
+                    .and(FriendRequestColumn::Recipient.eq(user.id))),
+        )
+        .exec(&txn)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let (a, b) = (user.id.to_string(), other.id.to_string());
+    Game::delete_many()
+        .filter(GameColumn::Pending.eq(true))
+        .filter(
+            GameColumn::Host
+                .eq(a.as_str())
+                .and(GameColumn::Guest.eq(b.as_str()))
+                .or(GameColumn::Host
+                    .eq(b.as_str())
+                    .and(GameColumn::Guest.eq(a.as_str()))),
+        )
+        .exec(&txn)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    txn.commit()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(super::Response::new(json!({}), StatusCode::CREATED))
+}
+
+/// Lift a block placed on the specified user.
+pub async fn unblock(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let other = helpers::get_user(&state, &username, true).await?;
+    let result = Block::delete_by_id((user.id, other.id))
+        .exec(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    if result.rows_affected == 0 {
+        return Err(
+            StringError(strings::BLOCK_NOT_FOUND.into(), StatusCode::NOT_FOUND).into_response(),
+        );
+    }
+    Ok(super::Response::new(json!({}), StatusCode::OK))
+}
+
+/// Fetch the users that the current user has blocked, most recent first.
+pub async fn blocks(
+    State(state): State<Arc<AppState>>,
+    user: User,
+) -> Result<impl IntoResponse, Response> {
+    let blocks = Block::find()
+        .filter(BlockColumn::Blocker.eq(user.id))
+        .order_by_desc(BlockColumn::CreatedAt)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let members = Member::find()
+        .filter(MemberColumn::Id.is_in(blocks.iter().map(|block| block.blocked)))
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let users = user_summaries(&state, &members).await;
+    let mut summaries = vec![];
+    for block in &blocks {
+        let Some(member) = members.iter().find(|member| member.id == block.blocked) else {
+            continue;
+        };
+        summaries.push(json!({
+            "user": users[&member.id],
+            "created_at": timestamp::rfc3339(&block.created_at),
+        }));
+    }
+    Ok(super::Response::new(summaries, StatusCode::OK))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::server::{
+        self,
+        handlers::{ApiError, Response},
+        strings,
+    };
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use test_utils::{function, Client, Map};
+
+    #[tokio::test]
+    async fn block() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let user = format!("{}::1", function!());
+        let target = format!("{}::2", function!());
+        let client = Client::authenticated(&[&user, &target], &url, true).await;
+        let resp: ApiError = client
+            .post(&url, &format!("/users/{user}/block"), json!({}))
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        // A pending friend request is dropped by the block.
+        let resp: Response<Map> = client
+            .post(&url, &format!("/users/{target}/friend"), json!({}))
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let resp: Response<Map> = client
+            .post(&url, &format!("/users/{target}/block"), json!({}))
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let resp: ApiError = client
+            .post(&url, &format!("/users/{target}/block"), json!({}))
+            .await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/friends/outgoing").await;
+        assert!(resp.message.is_empty());
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/blocks").await;
+        assert_eq!(resp.message.len(), 1);
+        assert_eq!(resp.message[0]["user"]["username"], target.as_str());
+        // The target user can no longer reach the user.
+        let other = Client::authenticated(&[&target], &url, false).await;
+        let resp: ApiError = other
+            .post(&url, &format!("/users/{user}/friend"), json!({}))
+            .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(resp.message, strings::BLOCKED);
+        let resp: ApiError = other.post(&url, "/game", json!({ "guest": user })).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        // Only the user can lift the block.
+        let resp: ApiError = other.delete(&url, &format!("/users/{user}/block")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp: Response<Map> = client.delete(&url, &format!("/users/{target}/block")).await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let resp: Response<Map> = other
+            .post(&url, &format!("/users/{user}/friend"), json!({}))
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+    }
+}
@@ -0,0 +1,214 @@
+use super::StringError;
+use crate::server::{
+    audit::{Action, Entry},
+    bots,
+    entities::{
+        member::{self, Column as MemberColumn},
+        prelude::Member,
+    },
+    extractors::User,
+    helpers,
+    network::ClientIp,
+    state::AppState,
+    strings, timestamp, validate_username,
+    validation::{Valid, Validate, Validator},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, IntoActiveModel, PaginatorTrait,
+    QueryFilter, QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How many bots each user can own.
+const MAX_BOTS: u64 = 5;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateBotRequest {
+    username: String,
+}
+
+impl Validate for CreateBotRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check("username", validate_username(&self.username));
+    }
+}
+
+/// Create a bot account owned by the current user. The bot's API token is only ever shown
+/// in this response; a lost token has to be replaced with a new one.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    ip: Option<ClientIp>,
+    Valid(CreateBotRequest { username }): Valid<CreateBotRequest>,
+) -> Result<impl IntoResponse, Response> {
+    if user.bot {
+        return Err(StringError(strings::BOT_OWNER.into(), StatusCode::FORBIDDEN).into_response());
+    }
+    let owned = Member::find()
+        .filter(MemberColumn::Owner.eq(user.id))
+        .count(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    if owned >= MAX_BOTS {
+        return Err(
+            StringError(strings::TOO_MANY_BOTS.into(), StatusCode::CONFLICT).into_response(),
+        );
+    }
+    let id = Uuid::now_v7();
+    let token = helpers::generate_key();
+    Member::insert(member::ActiveModel {
+        id: ActiveValue::set(id),
+        username: ActiveValue::set(username.clone()),
+        // Bots can't log in; they only ever authenticate with their token.
+        password: ActiveValue::set(None),
+        created_at: ActiveValue::NotSet,
+        avatar: ActiveValue::NotSet,
+        timezone: ActiveValue::NotSet,
+        admin: ActiveValue::NotSet,
+        bot: ActiveValue::set(true),
+        owner: ActiveValue::set(Some(user.id)),
+        api_token: ActiveValue::set(Some(bots::digest(&token))),
+    })
+    .exec(state.database.as_ref())
+    .await
+    .map_err(|e| {
+        if helpers::unique_violation(&e) {
+            StringError(strings::USERNAME_TAKEN.into(), StatusCode::CONFLICT)
+        } else {
+            StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    })?;
+    Entry::new(Action::BotCreate)
+        .actor(Some(user.id))
+        .target(id)
+        .details(json!({ "username": username }))
+        .ip(ip)
+        .record(&state)
+        .await?;
+    Ok(super::Response::new(
+        json!({ "id": id, "username": username, "token": token }),
+        StatusCode::CREATED,
+    ))
+}
+
+/// Fetch the bots the current user owns, oldest first.
+pub async fn bots(
+    State(state): State<Arc<AppState>>,
+    user: User,
+) -> Result<impl IntoResponse, Response> {
+    let bots = Member::find()
+        .filter(MemberColumn::Owner.eq(user.id))
+        .order_by_asc(MemberColumn::CreatedAt)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let bots: Vec<_> = bots
+        .iter()
+        .map(|bot| {
+            json!({
+                "id": bot.id,
+                "username": bot.username,
+                "created_at": timestamp::rfc3339(&bot.created_at),
+            })
+        })
+        .collect();
+    Ok(super::Response::new(bots, StatusCode::OK))
+}
+
+/// Replace the API token of one of the current user's bots, so that the old one stops
+/// working straight away.
+pub async fn reset_token(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    ip: Option<ClientIp>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, Response> {
+    let bot = Member::find_by_id(id)
+        .filter(MemberColumn::Owner.eq(user.id))
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(StringError(
+            strings::BOT_NOT_FOUND.into(),
+            StatusCode::NOT_FOUND,
+        ))?;
+    let token = helpers::generate_key();
+    let mut active = bot.into_active_model();
+    active.api_token = ActiveValue::set(Some(bots::digest(&token)));
+    active
+        .update(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Entry::new(Action::BotTokenReset)
+        .actor(Some(user.id))
+        .target(id)
+        .ip(ip)
+        .record(&state)
+        .await?;
+    Ok(super::Response::new(
+        json!({ "token": token }),
+        StatusCode::OK,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{
+        self,
+        handlers::{ApiError, Response},
+        strings,
+    };
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map};
+
+    #[tokio::test]
+    async fn bot() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let owner = Client::authenticated(&[&function!()], &url, true).await;
+        let name = format!("{}_bot", function!());
+        let created: Response<Map> = owner
+            .post(&url, "/@me/bots", serde_json::json!({ "username": name }))
+            .await;
+        let token = created.message["token"].as_str().unwrap();
+        let id = created.message["id"].as_str().unwrap();
+        // The bot can act with its token alone.
+        let bot = Client::new();
+        let authorization = format!("Bot {token}");
+        let me: Response<Map> = bot
+            .get_with_header(&url, "/@me", ("Authorization", &authorization))
+            .await;
+        assert_eq!(me.message["username"], name.as_str());
+        assert_eq!(me.message["bot"], true);
+        // Bots can't own bots of their own.
+        let error: ApiError = bot
+            .post_with_header(
+                &url,
+                "/@me/bots",
+                serde_json::json!({ "username": format!("{name}2") }),
+                ("Authorization", &authorization),
+            )
+            .await;
+        assert_eq!(error.message, strings::BOT_OWNER);
+        // A new token replaces the old one.
+        let _: Response<Map> = owner
+            .post(&url, &format!("/@me/bots/{id}/token"), Map::new())
+            .await;
+        let error: ApiError = bot
+            .get_with_header(&url, "/@me", ("Authorization", &authorization))
+            .await;
+        assert_eq!(error.message, strings::INVALID_TOKEN);
+    }
+}
@@ -1,59 +1,316 @@
 use super::StringError;
-use crate::server::{entities::game, extractors::User, helpers, state::AppState, strings};
+use crate::{
+    server::{
+        conduct, correspondence, create_in_memory_game,
+        entities::{game, member},
+        extractors::User,
+        helpers, join_codes,
+        links::GameLinks,
+        notifications::{self, Kind},
+        opponent,
+        state::AppState,
+        strings,
+        validation::{Valid, Validate, Validator},
+        webhooks::{self, Event},
+    },
+    GameSettings, Piece,
+};
 use axum::{
     body::Body,
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
-use sea_orm::{ActiveModelTrait, ActiveValue};
-use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use othello_api_types::{BotGameRequest, GameRequest, Visibility};
+use sea_orm::{ActiveModelTrait, ActiveValue, TransactionTrait};
 use serde_json::json;
 use std::sync::Arc;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GameRequest {
-    guest: String,
+impl Validate for GameRequest {
+    fn validate(&self, v: &mut Validator) {
+        for (field, e) in self.settings.errors() {
+            v.reject(&format!("settings.{field}"), e);
+        }
+    }
 }
 
-/// Create a new game with the specified host and guest.
+/// Create a new game with the specified host and guest, or an unlisted one that anyone with
+/// its join code can play.
 pub async fn create(
     State(state): State<Arc<AppState>>,
     host: User,
-    Json(body): Json<GameRequest>,
+    Valid(body): Valid<GameRequest>,
 ) -> Result<impl IntoResponse, Response<Body>> {
+    let settings = body.settings;
+    if body.visibility == Visibility::Code {
+        let unlisted = create_unlisted(&state, &host, &body).await?;
+        return Ok(super::Response::new(unlisted, StatusCode::CREATED));
+    }
+    let usernames = match body {
+        GameRequest {
+            guest: Some(guest),
+            guests,
+            ..
+        } if guests.is_empty() => vec![guest],
+        GameRequest {
+            guest: None,
+            guests,
+            ..
+        } if !guests.is_empty() => guests,
+        _ => {
+            return Err(
+                StringError(strings::BAD_REQUEST.into(), StatusCode::BAD_REQUEST).into_response(),
+            )
+        }
+    };
     // Fetch the user objects associated with the host and guest usernames to
     // ensure that they exist.
     let host = helpers::get_user(&state, &host.username, true).await?;
-    let guest = helpers::get_user(&state, &body.guest, true).await?;
-    // A user can't create a game with themself.
-    if host.id == guest.id {
+    conduct::ensure_can_play(&state, host.id, &settings).await?;
+    let guests = invitees(&state, &host, &usernames).await?;
+    // Invitations sent together share a challenge ID so that accepting one can cancel the rest.
+    let challenge = (guests.len() > 1).then(Uuid::now_v7);
+    // Create a new game record for each guest and insert them into the database.
+    let txn = state
+        .database
+        .begin()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let mut games = vec![];
+    for guest in &guests {
+        let id = Uuid::now_v7();
+        let model = game::ActiveModel {
+            id: ActiveValue::set(id),
+            host: ActiveValue::set(host.id.to_string()),
+            guest: ActiveValue::set(guest.id.to_string()),
+            pending: ActiveValue::set(true),
+            ended: ActiveValue::set(false),
+            challenge: ActiveValue::set(challenge),
+            result: ActiveValue::set(None),
+            settings: ActiveValue::set(json!(settings)),
+            state: ActiveValue::set(None),
+            turn_started_at: ActiveValue::set(None),
+            opponent: ActiveValue::set(None),
+            arena: ActiveValue::set(None),
+            host_opponent: ActiveValue::set(None),
+            ended_at: ActiveValue::set(None),
+            mode: ActiveValue::set(correspondence::mode(&settings).into()),
+            // Settled by the colour policy once the invitation is accepted.
+            host_color: ActiveValue::set(helpers::color_name(Piece::Black).into()),
+        };
+        model
+            .insert(&txn)
+            .await
+            .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        games.push(json!({
+            "id": id,
+            "host": host.id,
+            "guest": guest.id,
+            "pending": true,
+            "ended": false,
+            "settings": settings,
+            "links": GameLinks::new(id),
+        }));
+    }
+    txn.commit()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    for (guest, game) in guests.iter().zip(&games) {
+        let invite =
+            json!({ "game": game["id"].clone(), "host": host.username, "challenge": challenge });
+        notifications::send(&state, guest.id, Kind::GameInvite, invite.clone()).await;
+        webhooks::send(&state, guest.id, Event::Challenge, invite).await;
+    }
+    let resp = match challenge {
+        Some(challenge) => json!({ "challenge": challenge, "games": games }),
+        None => games.remove(0),
+    };
+    Ok(super::Response::new(resp, StatusCode::CREATED))
+}
+
+/// Store an unlisted game under a fresh join code, for whoever has the code to join, returning
+/// the code along with the game waiting behind it.
+async fn create_unlisted(
+    state: &AppState,
+    host: &User,
+    body: &GameRequest,
+) -> Result<serde_json::Value, Response<Body>> {
+    // Unlisted games are for whoever has the code, not anyone in particular.
+    if body.guest.is_some() || !body.guests.is_empty() {
         return Err(
-            StringError(strings::GAME_SELF.to_string(), StatusCode::BAD_REQUEST).into_response(),
+            StringError(strings::BAD_REQUEST.into(), StatusCode::BAD_REQUEST).into_response(),
         );
     }
-    // Create a new game record and insert it into the database.
-    let id = Uuid::now_v7();
-    let model = game::ActiveModel {
-        id: ActiveValue::set(id),
-        host: ActiveValue::set(host.id.to_string()),
-        guest: ActiveValue::set(guest.id.to_string()),
-        pending: ActiveValue::set(true),
-        ended: ActiveValue::set(false),
+    let host = helpers::get_user(state, &host.username, true).await?;
+    conduct::ensure_can_play(state, host.id, &body.settings).await?;
+    let opening = join_codes::Opening {
+        host: host.id,
+        settings: body.settings,
     };
-    model
+    let code = join_codes::issue(state, &opening).await?;
+    Ok(json!({
+        "code": code,
+        "host": host.id,
+        "settings": body.settings,
+        "expires_in": join_codes::TTL,
+    }))
+}
+
+/// Fetch the users with the specified usernames to invite to a game, making sure that the host
+/// can play each of them, and that none of them is invited twice.
+async fn invitees(
+    state: &AppState,
+    host: &member::Model,
+    usernames: &[String],
+) -> Result<Vec<member::Model>, Response<Body>> {
+    let mut guests: Vec<member::Model> = Vec::with_capacity(usernames.len());
+    for username in usernames {
+        let guest = helpers::get_user(state, username, true).await?;
+        // A user can't create a game with themself.
+        if host.id == guest.id {
+            return Err(
+                StringError(strings::GAME_SELF.to_string(), StatusCode::BAD_REQUEST)
+                    .into_response(),
+            );
+        }
+        // Inviting the same user twice would let them race themself.
+        if guests.iter().any(|g| g.id == guest.id) {
+            return Err(StringError(
+                strings::DUPLICATE_GUEST.to_string(),
+                StatusCode::BAD_REQUEST,
+            )
+            .into_response());
+        }
+        helpers::ensure_not_blocked(state, host.id, guest.id).await?;
+        guests.push(guest);
+    }
+    Ok(guests)
+}
+
+/// Join the unlisted game behind the specified join code. There's nobody left to accept it, so
+/// it starts straight away, with colours settled as they are when an invite is accepted.
+pub async fn join_by_code(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    user: User,
+) -> Result<impl IntoResponse, Response<Body>> {
+    let code = join_codes::normalize(&code);
+    let invalid = || StringError(strings::INVALID_JOIN_CODE.into(), StatusCode::NOT_FOUND);
+    let opening = join_codes::peek(&state, &code).await?.ok_or_else(invalid)?;
+    let guest = helpers::get_user(&state, &user.username, true).await?;
+    if guest.id == opening.host {
+        return Err(
+            StringError(strings::GAME_SELF.to_string(), StatusCode::BAD_REQUEST).into_response(),
+        );
+    }
+    helpers::ensure_not_blocked(&state, opening.host, guest.id).await?;
+    conduct::ensure_can_play(&state, guest.id, &opening.settings).await?;
+    let host = helpers::get_user(&state, &opening.host.to_string(), false).await?;
+    // Only the first player to get this far plays.
+    let opening = join_codes::redeem(&state, &code)
+        .await?
+        .ok_or_else(invalid)?;
+    let settings = opening.settings;
+    let model = started(host.id, guest.id, settings)
         .insert(state.database.as_ref())
         .await
         .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    create_in_memory_game(&state, &model).await?;
+    webhooks::game_started(&state, &model).await;
+    notifications::send(
+        &state,
+        host.id,
+        Kind::GameJoin,
+        json!({ "game": model.id, "guest": guest.username }),
+    )
+    .await;
     Ok(super::Response::new(
         json!({
-            "id": id,
+            "id": model.id,
+            "host": model.host,
+            "guest": model.guest,
+            "host_color": helpers::host_color(&model),
+            "pending": false,
+            "ended": false,
+            "settings": settings,
+            "links": GameLinks::new(model.id),
+        }),
+        StatusCode::CREATED,
+    ))
+}
+
+/// A game between the specified players that needs no accepting, with colours given out by
+/// the colour policy of its settings.
+pub(super) fn started(host: Uuid, guest: Uuid, settings: GameSettings) -> game::ActiveModel {
+    let host_color = helpers::draw_host_color(&settings);
+    game::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        host: ActiveValue::set(host.to_string()),
+        guest: ActiveValue::set(guest.to_string()),
+        pending: ActiveValue::set(false),
+        ended: ActiveValue::set(false),
+        challenge: ActiveValue::set(None),
+        result: ActiveValue::set(None),
+        settings: ActiveValue::set(json!(settings)),
+        state: ActiveValue::set(None),
+        // The first move is due from the moment the game starts.
+        turn_started_at: ActiveValue::set(Some(Utc::now().fixed_offset())),
+        opponent: ActiveValue::set(None),
+        arena: ActiveValue::set(None),
+        host_opponent: ActiveValue::set(None),
+        ended_at: ActiveValue::set(None),
+        mode: ActiveValue::set(correspondence::mode(&settings).into()),
+        host_color: ActiveValue::set(helpers::color_name(host_color).into()),
+    }
+}
+
+/// Start a game against one of the server's own opponents. The opponent plays white, and
+/// replies to each move over the gateway once it has found one. There's nobody to accept the
+/// game, so it's ready to play straight away.
+pub async fn create_bot_game(
+    State(state): State<Arc<AppState>>,
+    host: User,
+    Json(BotGameRequest { difficulty }): Json<BotGameRequest>,
+) -> Result<impl IntoResponse, Response<Body>> {
+    let settings = GameSettings::default();
+    conduct::ensure_can_play(&state, host.id, &settings).await?;
+    let opponent = opponent::account(&state, difficulty).await?;
+    let model = game::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        host: ActiveValue::set(host.id.to_string()),
+        guest: ActiveValue::set(opponent.to_string()),
+        pending: ActiveValue::set(false),
+        ended: ActiveValue::set(false),
+        challenge: ActiveValue::set(None),
+        result: ActiveValue::set(None),
+        settings: ActiveValue::set(json!(settings)),
+        state: ActiveValue::set(None),
+        turn_started_at: ActiveValue::set(None),
+        opponent: ActiveValue::set(Some(difficulty.name().into())),
+        arena: ActiveValue::set(None),
+        host_opponent: ActiveValue::set(None),
+        ended_at: ActiveValue::set(None),
+        mode: ActiveValue::set(correspondence::LIVE.into()),
+        host_color: ActiveValue::set(helpers::color_name(Piece::Black).into()),
+    }
+    .insert(state.database.as_ref())
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    create_in_memory_game(&state, &model).await?;
+    webhooks::game_started(&state, &model).await;
+    Ok(super::Response::new(
+        json!({
+            "id": model.id,
             "host": host.id,
-            "guest": guest.id,
-            "pending": true,
-            "ended": false
+            "guest": opponent,
+            "pending": false,
+            "ended": false,
+            "settings": settings,
+            "opponent": difficulty,
+            "links": GameLinks::new(model.id),
         }),
         StatusCode::CREATED,
     ))
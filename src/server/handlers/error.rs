@@ -0,0 +1,155 @@
+use super::StringError;
+use crate::{server::strings, PlaceError};
+use axum::http::StatusCode;
+pub use othello_api_types::{ApiError, ErrorCode};
+use serde_json::json;
+
+/// The code for an error with the specified message and status, falling back to a generic
+/// code for the status if the message isn't one of the known ones.
+fn code_of(message: &str, status: StatusCode) -> ErrorCode {
+    match message {
+        strings::USERNAME_TOO_SHORT => ErrorCode::UsernameTooShort,
+        strings::USERNAME_TAKEN => ErrorCode::UsernameTaken,
+        strings::INVALID_PASSWORD => ErrorCode::InvalidPassword,
+        strings::PASSWORD_MISMATCH => ErrorCode::PasswordMismatch,
+        strings::PASSWORD_TOO_SHORT => ErrorCode::PasswordTooShort,
+        strings::PASSWORD_NO_ALPHA => ErrorCode::PasswordNoAlpha,
+        strings::PASSWORD_NO_NUMERIC => ErrorCode::PasswordNoNumeric,
+        strings::INVALID_TIMEZONE => ErrorCode::InvalidTimezone,
+        strings::ACCOUNT_LOCKED => ErrorCode::AccountLocked,
+        strings::INVALID_TOKEN => ErrorCode::InvalidToken,
+        strings::CSRF_MISMATCH => ErrorCode::CsrfMismatch,
+        strings::NOT_ADMIN => ErrorCode::NotAdmin,
+        strings::INVALID_USERNAME => ErrorCode::UserNotFound,
+        strings::SEARCH_QUERY_MISSING => ErrorCode::SearchQueryMissing,
+        strings::ALREADY_FRIENDS => ErrorCode::AlreadyFriends,
+        strings::FRIEND_SELF => ErrorCode::FriendSelf,
+        strings::FRIEND_NOT_FOUND => ErrorCode::FriendNotFound,
+        strings::FRIEND_REQUEST_ALREADY_SENT => ErrorCode::FriendRequestAlreadySent,
+        strings::FRIEND_REQUEST_NOT_FOUND => ErrorCode::FriendRequestNotFound,
+        strings::TOO_MANY_USERNAMES => ErrorCode::TooManyUsernames,
+        strings::DUPLICATE_USERNAME => ErrorCode::DuplicateUsername,
+        strings::BLOCK_SELF => ErrorCode::BlockSelf,
+        strings::ALREADY_BLOCKED => ErrorCode::AlreadyBlocked,
+        strings::BLOCKED => ErrorCode::Blocked,
+        strings::BLOCK_NOT_FOUND => ErrorCode::BlockNotFound,
+        strings::REPORT_SELF => ErrorCode::ReportSelf,
+        strings::REASON_MISSING => ErrorCode::ReasonMissing,
+        strings::REASON_TOO_LONG => ErrorCode::ReasonTooLong,
+        strings::QUOTE_TOO_LONG => ErrorCode::QuoteTooLong,
+        strings::REPORT_NOT_FOUND => ErrorCode::ReportNotFound,
+        strings::BAN_NOT_FOUND => ErrorCode::BanNotFound,
+        strings::INVALID_BAN_DURATION => ErrorCode::InvalidBanDuration,
+        strings::AVATAR_MISSING => ErrorCode::AvatarMissing,
+        strings::AVATAR_TOO_LARGE => ErrorCode::AvatarTooLarge,
+        strings::AVATAR_UNSUPPORTED => ErrorCode::AvatarUnsupported,
+        strings::AVATAR_NOT_FOUND => ErrorCode::AvatarNotFound,
+        strings::NOTIFICATION_NOT_FOUND => ErrorCode::NotificationNotFound,
+        strings::INVALID_GAME_ID => ErrorCode::GameNotFound,
+        strings::INVALID_GAME_ID_FORMAT => ErrorCode::InvalidGameId,
+        strings::GAME_SELF => ErrorCode::GameSelf,
+        strings::DUPLICATE_GUEST => ErrorCode::DuplicateGuest,
+        strings::INVITE_EXPIRED => ErrorCode::InviteExpired,
+        strings::INVALID_JOIN_CODE => ErrorCode::InvalidJoinCode,
+        strings::CHALLENGE_NOT_FOUND => ErrorCode::ChallengeNotFound,
+        strings::CHALLENGE_ALREADY_OPEN => ErrorCode::ChallengeAlreadyOpen,
+        strings::CLAIM_TOO_EARLY => ErrorCode::ClaimTooEarly,
+        strings::GAME_OVER => ErrorCode::GameOver,
+        strings::NOT_YOUR_PIECE => ErrorCode::NotYourPiece,
+        strings::BANNED => ErrorCode::Banned,
+        strings::CASUAL_ONLY => ErrorCode::CasualOnly,
+        strings::FOG_REPLAY => ErrorCode::FogReplay,
+        strings::ANALYSIS_NOT_READY => ErrorCode::AnalysisNotReady,
+        strings::TOURNAMENT_NOT_FOUND => ErrorCode::TournamentNotFound,
+        strings::INVALID_TOURNAMENT_NAME => ErrorCode::InvalidTournamentName,
+        strings::INVALID_TOURNAMENT_SIZE => ErrorCode::InvalidTournamentSize,
+        strings::INVALID_TOURNAMENT_ROUNDS => ErrorCode::InvalidTournamentRounds,
+        strings::TOURNAMENT_STARTED => ErrorCode::TournamentStarted,
+        strings::TOURNAMENT_FULL => ErrorCode::TournamentFull,
+        strings::TOURNAMENT_TOO_SMALL => ErrorCode::TournamentTooSmall,
+        strings::ALREADY_ENTERED => ErrorCode::AlreadyEntered,
+        strings::NOT_TOURNAMENT_HOST => ErrorCode::NotTournamentHost,
+        strings::SEASON_NOT_FOUND => ErrorCode::SeasonNotFound,
+        strings::PUZZLE_NOT_FOUND => ErrorCode::PuzzleNotFound,
+        strings::PUZZLE_ALREADY_ANSWERED => ErrorCode::PuzzleAlreadyAnswered,
+        strings::INVALID_PUZZLE_SOLUTIONS => ErrorCode::InvalidPuzzleSolutions,
+        strings::BOT_NOT_FOUND => ErrorCode::BotNotFound,
+        strings::TOO_MANY_BOTS => ErrorCode::TooManyBots,
+        strings::BOT_OWNER => ErrorCode::BotOwner,
+        strings::ARENA_NOT_FOUND => ErrorCode::ArenaNotFound,
+        strings::INVALID_ARENA_GAMES => ErrorCode::InvalidArenaGames,
+        strings::ARENA_SAME_CONTENDER => ErrorCode::ArenaSameContender,
+        strings::NOT_A_BOT => ErrorCode::NotABot,
+        strings::WEBHOOK_NOT_FOUND => ErrorCode::WebhookNotFound,
+        strings::TOO_MANY_WEBHOOKS => ErrorCode::TooManyWebhooks,
+        strings::INVALID_WEBHOOK_URL => ErrorCode::InvalidWebhookUrl,
+        strings::WEBHOOK_EVENTS_MISSING => ErrorCode::WebhookEventsMissing,
+        strings::INVALID_PAGE_SIZE => ErrorCode::InvalidPageSize,
+        strings::INVALID_CURSOR => ErrorCode::InvalidCursor,
+        strings::INVALID_IDEMPOTENCY_KEY => ErrorCode::InvalidIdempotencyKey,
+        strings::IDEMPOTENCY_IN_PROGRESS => ErrorCode::IdempotencyInProgress,
+        strings::IDEMPOTENCY_KEY_REUSED => ErrorCode::IdempotencyKeyReused,
+        strings::OAUTH_UNKNOWN_PROVIDER => ErrorCode::OauthUnknownProvider,
+        strings::OAUTH_UNAVAILABLE => ErrorCode::OauthUnavailable,
+        strings::OAUTH_INVALID_STATE => ErrorCode::OauthInvalidState,
+        strings::OAUTH_FAILED => ErrorCode::OauthFailed,
+        strings::OAUTH_IDENTITY_TAKEN => ErrorCode::OauthIdentityTaken,
+        strings::UNSUPPORTED_PROTOCOL_VERSION => ErrorCode::UnsupportedProtocolVersion,
+        strings::IDENTIFY_TIMEOUT => ErrorCode::IdentifyTimeout,
+        _ => ErrorCode::for_status(status),
+    }
+}
+
+impl From<StringError> for ApiError {
+    fn from(StringError(message, status): StringError) -> Self {
+        Self::new(code_of(&message, status), message, status)
+    }
+}
+
+impl From<PlaceError> for ApiError {
+    fn from(e: PlaceError) -> Self {
+        let (code, details) = match e {
+            PlaceError::Occupied(x, y) => (ErrorCode::SquareOccupied, json!({ "x": x, "y": y })),
+            PlaceError::Turn(piece) => (ErrorCode::NotYourTurn, json!({ "piece": piece })),
+            PlaceError::NotAdjacent(x, y) => (ErrorCode::NotAdjacent, json!({ "x": x, "y": y })),
+            PlaceError::OutOfBounds(x, y) => (ErrorCode::OutOfBounds, json!({ "x": x, "y": y })),
+            PlaceError::NoFlips(x, y) => (ErrorCode::NoFlips, json!({ "x": x, "y": y })),
+        };
+        Self::new(code, e.to_string(), StatusCode::BAD_REQUEST).with_details(details)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiError, ErrorCode};
+    use crate::{
+        server::{handlers::StringError, strings},
+        PlaceError,
+    };
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn codes() {
+        let e = ApiError::from(StringError(
+            strings::INVALID_GAME_ID.into(),
+            StatusCode::NOT_FOUND,
+        ));
+        assert_eq!(e.code, ErrorCode::GameNotFound);
+        // Messages without a code of their own get one for their status.
+        let e = ApiError::from(StringError("oops".into(), StatusCode::NOT_FOUND));
+        assert_eq!(e.code, ErrorCode::NotFound);
+        let e = ApiError::from(StringError("oops".into(), StatusCode::BAD_GATEWAY));
+        assert_eq!(e.code, ErrorCode::Internal);
+        let e = ApiError::from(PlaceError::Occupied(3, 4));
+        assert_eq!(
+            serde_json::to_value(e).unwrap(),
+            json!({
+                "code": "square_occupied",
+                "message": "board square (3, 4) is occupied",
+                "details": { "x": 3, "y": 4 },
+                "status": 400,
+            })
+        );
+    }
+}
@@ -1,14 +1,18 @@
-use super::StringError;
+use super::{ApiError, ErrorCode, StringError};
 use crate::server::{
     entities::{
         friend::{ActiveModel, Column as FriendColumn},
         friend_request::{ActiveModel as FriendRequestAM, Column as FriendRequestColumn},
+        member,
         prelude::{Friend, FriendRequest},
     },
     extractors::User,
     helpers,
+    notifications::{self, Kind},
+    packet::{Event, EventKind, ServerMessage},
     state::AppState,
     strings,
+    validation::{Valid, Validate, Validator},
 };
 use axum::{
     extract::{Path, State},
@@ -16,8 +20,13 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter};
+use serde::Deserialize;
 use serde_json::json;
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
+use uuid::Uuid;
+
+/// The most usernames a single bulk friend request can name.
+pub const MAX_BULK_REQUESTS: usize = 50;
 
 /// Send a friend request to the specified user.
 pub async fn send(
@@ -27,12 +36,79 @@ pub async fn send(
 ) -> Result<impl IntoResponse, Response> {
     // Fetch the user object associated with the recipient username to ensure that it exists.
     let other = helpers::get_user(&state, &username, true).await?;
+    let id = request(&state, &user, &other).await?;
+    Ok(super::Response::new(
+        json!({ "id": id }),
+        StatusCode::CREATED,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkRequest {
+    usernames: Vec<String>,
+}
+
+impl Validate for BulkRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.ensure(
+            "usernames",
+            self.usernames.len() <= MAX_BULK_REQUESTS,
+            strings::TOO_MANY_USERNAMES,
+        );
+    }
+}
+
+/// Send friend requests to each of up to `MAX_BULK_REQUESTS` users at once, reporting what
+/// happened with each. Users who can't be sent a request (e.g. because one is already pending
+/// or either user has blocked the other) are skipped rather than failing the whole batch.
+pub async fn bulk(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Valid(body): Valid<BulkRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let mut seen = HashSet::new();
+    let mut results = Vec::with_capacity(body.usernames.len());
+    for username in body.usernames {
+        let outcome = match helpers::get_user(&state, &username, true).await {
+            Ok(other) if !seen.insert(other.id) => Err(StringError(
+                strings::DUPLICATE_USERNAME.to_string(),
+                StatusCode::BAD_REQUEST,
+            )),
+            Ok(other) => request(&state, &user, &other).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        let result = match outcome {
+            Ok(()) => json!({ "username": username, "outcome": "sent" }),
+            // Something is wrong with the server rather than the entry, so stop here.
+            Err(e) if e.1.is_server_error() => return Err(e.into_response()),
+            Err(e) => {
+                let error = ApiError::from(e);
+                let outcome = match error.code {
+                    ErrorCode::UserNotFound | ErrorCode::FriendSelf => "failed",
+                    _ => "skipped",
+                };
+                json!({ "username": username, "outcome": outcome, "error": error })
+            }
+        };
+        results.push(result);
+    }
+    Ok(super::Response::new(results, StatusCode::OK))
+}
+
+/// Send a friend request from the specified user to another, returning the request's ID.
+async fn request(
+    state: &AppState,
+    user: &User,
+    other: &member::Model,
+) -> Result<(Uuid, Uuid), StringError> {
     // A user can't become friends with themself.
     if user.id == other.id {
-        return Err(
-            StringError(strings::FRIEND_SELF.to_string(), StatusCode::BAD_REQUEST).into_response(),
-        );
+        return Err(StringError(
+            strings::FRIEND_SELF.to_string(),
+            StatusCode::BAD_REQUEST,
+        ));
     }
+    helpers::ensure_not_blocked(state, user.id, other.id).await?;
     // Check if the two users are already friends.
     let friend = Friend::find()
         .filter(
@@ -50,8 +126,7 @@ pub async fn send(
         return Err(StringError(
             strings::ALREADY_FRIENDS.to_string(),
             StatusCode::BAD_REQUEST,
-        )
-        .into_response());
+        ));
     }
     // Fetch a friend request record associated with the sender and recipient to see if one already exists.
     let request = FriendRequest::find()
@@ -73,26 +148,40 @@ pub async fn send(
         return Err(StringError(
             strings::FRIEND_REQUEST_ALREADY_SENT.to_string(),
             StatusCode::CONFLICT,
-        )
-        .into_response());
+        ));
     }
     let request = FriendRequestAM {
         sender: ActiveValue::Set(user.id),
         recipient: ActiveValue::Set(other.id),
+        created_at: ActiveValue::NotSet,
     };
     // Insert the friend request record into the database.
     let model = FriendRequest::insert(request)
         .exec(state.database.as_ref())
         .await;
     let model = model.map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
-    Ok(super::Response::new(
-        json!({ "id": model.last_insert_id}),
-        StatusCode::CREATED,
-    ))
+    // Let the recipient know about the request, so they don't have to keep checking for new ones.
+    state.notify(
+        other.id,
+        Event::new(
+            EventKind::FriendRequestReceive,
+            ServerMessage::FriendRequestReceive {
+                user: user.username.clone(),
+            },
+        ),
+    );
+    notifications::send(
+        state,
+        other.id,
+        Kind::FriendRequest,
+        json!({ "user": user.username }),
+    )
+    .await;
+    Ok(model.last_insert_id)
 }
 
 /// Reply to a friend request with the specified outcome.
-/// `outcome` must be either "accept" or "decline".
+/// Any `outcome` other than "accept" declines the request.
 pub async fn reply(
     State(state): State<Arc<AppState>>,
     user: User,
@@ -125,15 +214,53 @@ pub async fn reply(
         let friend = ActiveModel {
             a: ActiveValue::Set(user.id),
             b: ActiveValue::Set(other.id),
+            created_at: ActiveValue::NotSet,
         };
         Friend::insert(friend)
             .exec(state.database.as_ref())
             .await
             .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        // Let the sender know that they have a new friend.
+        state.notify(
+            other.id,
+            Event::new(
+                EventKind::FriendRequestAccept,
+                ServerMessage::FriendRequestAccept {
+                    user: user.username.clone(),
+                },
+            ),
+        );
+        notifications::send(
+            &state,
+            other.id,
+            Kind::FriendRequestAccept,
+            json!({ "user": user.username }),
+        )
+        .await;
+    } else {
+        // Let the sender know that their request was turned down.
+        state.notify(
+            other.id,
+            Event::new(
+                EventKind::FriendRequestDecline,
+                ServerMessage::FriendRequestDecline {
+                    user: user.username.clone(),
+                },
+            ),
+        );
     }
     Ok(super::Response::new(json!({}), StatusCode::OK))
 }
 
+/// Decline an incoming friend request sent by the specified user.
+pub async fn decline(
+    state: State<Arc<AppState>>,
+    user: User,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    reply(state, user, Path((username, String::from("decline")))).await
+}
+
 /// Cancel an outgoing friend request sent to the specified user.
 pub async fn cancel(
     State(state): State<Arc<AppState>>,
@@ -161,6 +288,16 @@ pub async fn cancel(
         .exec(state.database.as_ref())
         .await
         .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    // Let the recipient know that the request was withdrawn.
+    state.notify(
+        other.id,
+        Event::new(
+            EventKind::FriendRequestCancel,
+            ServerMessage::FriendRequestCancel {
+                user: user.username.clone(),
+            },
+        ),
+    );
     Ok(super::Response::new(json!({}), StatusCode::OK))
 }
 
@@ -168,9 +305,14 @@ pub async fn cancel(
 mod tests {
     use std::sync::Arc;
 
-    use crate::server::{self, handlers::Response};
+    use crate::server::{
+        self,
+        handlers::{ApiError, Response},
+        strings,
+    };
     use axum::http::StatusCode;
-    use test_utils::{function, Client};
+    use serde_json::json;
+    use test_utils::{function, Client, Map, Socket};
 
     struct SentRequest {
         sender: String,
@@ -194,24 +336,30 @@ mod tests {
 
     #[tokio::test]
     async fn send() {
-        let database = sea_orm::Database::connect(server::TEST_DATABASE_URI)
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
             .await
             .unwrap();
-        let redis = redis::Client::open(server::TEST_REDIS_URI).unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
         let state = Arc::new(server::AppState::new(database, redis));
         let url = test_utils::init(crate::server::app(state)).await;
-        send_friend_request(&function!(), &url).await;
+        let recipient = format!("{}::2", function!());
+        let client = Client::authenticated(&[&recipient], &url, true).await;
+        let mut socket = connect(&client, &url).await;
+        let SentRequest { sender, .. } = send_friend_request(&function!(), &url).await;
+        let event = socket.recv_op(15).await;
+        assert_eq!(event["d"]["user"], sender.as_str());
     }
 
     #[tokio::test]
     async fn accept() {
-        let database = sea_orm::Database::connect(server::TEST_DATABASE_URI)
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
             .await
             .unwrap();
-        let redis = redis::Client::open(server::TEST_REDIS_URI).unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
         let state = Arc::new(server::AppState::new(database, redis));
         let url = test_utils::init(crate::server::app(state)).await;
         let SentRequest { sender, recipient } = send_friend_request(&function!(), &url).await;
+        let mut socket = connect(&Client::authenticated(&[&sender], &url, false).await, &url).await;
         let client = Client::authenticated(&[&recipient], &url, false).await;
         let resp: Response<test_utils::Map> = client
             .post(
@@ -221,14 +369,24 @@ mod tests {
             )
             .await;
         assert_eq!(resp.code, StatusCode::OK);
+        let event = socket.recv_op(16).await;
+        assert_eq!(event["d"]["user"], recipient.as_str());
+        let resp: Response<Vec<Map>> = client.get(url.as_str(), "/@me/friends").await;
+        let friend = &resp.message[0];
+        assert_eq!(friend["user"]["username"], sender.as_str());
+        // The sender is still connected, waiting to hear that their request was accepted.
+        assert_eq!(friend["user"]["presence"], "online");
+        assert!(friend["user"]["id"].is_string());
+        assert!(friend["user"]["rating"].is_null());
+        assert!(friend["created_at"].as_str().unwrap().ends_with('Z'));
     }
 
     #[tokio::test]
     async fn reject() {
-        let database = sea_orm::Database::connect(server::TEST_DATABASE_URI)
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
             .await
             .unwrap();
-        let redis = redis::Client::open(server::TEST_REDIS_URI).unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
         let state = Arc::new(server::AppState::new(database, redis));
         let url = test_utils::init(crate::server::app(state)).await;
         let SentRequest { sender, recipient } = send_friend_request(&function!(), &url).await;
@@ -242,4 +400,131 @@ mod tests {
             .await;
         assert_eq!(resp.code, StatusCode::OK);
     }
+
+    /// Open a live connection on behalf of the client's user.
+    async fn connect(client: &Client, url: &str) -> Socket {
+        let token = client.cookie(url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+    }
+
+    #[tokio::test]
+    async fn decline() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let SentRequest { sender, recipient } = send_friend_request(&function!(), &url).await;
+        let mut socket = connect(&Client::authenticated(&[&sender], &url, false).await, &url).await;
+        let client = Client::authenticated(&[&recipient], &url, false).await;
+        let resp: Response<Map> = client
+            .delete(&url, &format!("/@me/requests/incoming/{sender}"))
+            .await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let event = socket.recv_op(12).await;
+        assert_eq!(event["d"]["user"], recipient.as_str());
+        let resp: ApiError = client
+            .delete(&url, &format!("/@me/requests/incoming/{sender}"))
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn cancel() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let SentRequest { sender, recipient } = send_friend_request(&function!(), &url).await;
+        let client = Client::authenticated(&[&recipient], &url, false).await;
+        let mut socket = connect(&client, &url).await;
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/friends/incoming").await;
+        assert_eq!(resp.message.len(), 1);
+        let other = Client::authenticated(&[&sender], &url, false).await;
+        let resp: Response<Map> = other
+            .delete(&url, &format!("/@me/requests/outgoing/{recipient}"))
+            .await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let event = socket.recv_op(11).await;
+        assert_eq!(event["d"]["user"], sender.as_str());
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/friends/incoming").await;
+        assert!(resp.message.is_empty());
+    }
+
+    #[tokio::test]
+    async fn bulk() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let sender = format!("{}::1", function!());
+        let first = format!("{}::2", function!());
+        let second = format!("{}::3", function!());
+        let blocker = format!("{}::4", function!());
+        let client = Client::authenticated(&[&sender, &first, &second, &blocker], &url, true).await;
+        let other = Client::authenticated(&[&blocker], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/users/{sender}/block"), json!({}))
+            .await;
+        let usernames = [&first, &second, &first, &blocker, "nobody", &sender];
+        let resp: Response<Vec<Map>> = client
+            .post(
+                &url,
+                "/@me/friends/requests/bulk",
+                json!({ "usernames": usernames }),
+            )
+            .await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let outcomes: Vec<_> = resp.message.iter().map(|r| r["outcome"].clone()).collect();
+        assert_eq!(
+            outcomes,
+            ["sent", "sent", "skipped", "skipped", "failed", "failed"]
+        );
+        assert_eq!(resp.message[2]["error"]["code"], "duplicate_username");
+        assert_eq!(resp.message[3]["error"]["code"], "blocked");
+        // The requests can be listed a page at a time.
+        let resp: Response<Vec<Map>> = client
+            .get(&url, "/@me/friends/outgoing?limit=1&order=asc")
+            .await;
+        assert_eq!(resp.message[0]["recipient"]["username"], first.as_str());
+        let page = resp.page.unwrap();
+        assert_eq!(page.total, 2);
+        let next = page.next.unwrap();
+        let resp: Response<Vec<Map>> = client
+            .get(
+                &url,
+                &format!("/@me/friends/outgoing?limit=1&order=asc&cursor={next}"),
+            )
+            .await;
+        assert_eq!(resp.message[0]["recipient"]["username"], second.as_str());
+        assert_eq!(resp.page.unwrap().next, None);
+        // Sending the same requests again skips them all.
+        let resp: Response<Vec<Map>> = client
+            .post(
+                &url,
+                "/@me/friends/requests/bulk",
+                json!({ "usernames": [&first, &second] }),
+            )
+            .await;
+        assert!(resp.message.iter().all(|r| r["outcome"] == "skipped"));
+        let usernames = vec![first; super::MAX_BULK_REQUESTS + 1];
+        let resp: ApiError = client
+            .post(
+                &url,
+                "/@me/friends/requests/bulk",
+                json!({ "usernames": usernames }),
+            )
+            .await;
+        assert_eq!(resp.message, strings::TOO_MANY_USERNAMES);
+    }
 }
@@ -1,17 +1,41 @@
-use super::StringError;
-use crate::server::{
-    create_in_memory_game, entities::game::Column, extractors::User, helpers, state::AppState,
-    strings,
+use super::{user_summaries, user_summary, StringError};
+use crate::{
+    analysis,
+    render::{Renderer, Theme},
+    server::{
+        conduct, correspondence, create_in_memory_game,
+        entities::{
+            game::{ActiveModel, Column, Model},
+            prelude::{Game as GameModel, TournamentRound},
+            tournament_round,
+        },
+        extractors::User,
+        helpers, invites, moves,
+        packet::{Event, EventKind, ServerMessage},
+        pagination::Pagination,
+        projection::{Permissions, Viewer},
+        review, season,
+        state::AppState,
+        strings, timestamp, tournament, webhooks,
+    },
+    Moves, Piece,
 };
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
 };
-use sea_orm::{ActiveModelTrait, IntoActiveModel, ModelTrait, Value};
+use chrono::Utc;
+use othello_api_types::{GameDetails, LiveGame};
+use redis::AsyncCommands;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, EntityTrait, IntoActiveModel,
+    ModelTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait, Value,
+};
+use serde::Deserialize;
 use serde_json::json;
-use std::{str::FromStr, sync::Arc};
+use std::{cmp::Reverse, collections::HashMap, str::FromStr, sync::Arc, time::Duration};
 use uuid::Uuid;
 
 /// Retrieve the details for the specified game.
@@ -25,27 +49,536 @@ pub async fn game(
     let game = helpers::get_game(&state, &id).await?;
     // Convert to strings for more ergonomic comparison.
     let authed = user.id.to_string();
-    let host = game.host.to_string();
-    let guest = game.guest.to_string();
+    let host = game.host.clone();
+    let guest = game.guest.clone();
     // Ensure that the authenticated user is either the host or the guest.
-    (authed == host || authed == guest)
+    if authed == host || authed == guest {
         // If so, provide the details for the specified game.
-        .then(|| {
-            Ok(super::Response::new(
-                json!({
-                    "id": game.id,
-                    "pending": game.pending,
-                    "host": game.host,
-                    "guest": game.guest,
-                    "ended": game.ended,
-                }),
-                StatusCode::OK,
-            ))
-        })
+        let settings = helpers::game_settings(&game);
+        Ok(super::Response::new(
+            GameDetails {
+                id: game.id,
+                pending: game.pending,
+                host,
+                guest,
+                host_color: helpers::host_color(&game),
+                ended: game.ended,
+                result: game
+                    .result
+                    .and_then(|result| serde_json::from_value(result).ok()),
+                settings,
+            },
+            StatusCode::OK,
+        ))
+    } else {
         // Otherwise, pretend the game does not exist.
-        .unwrap_or_else(|| {
-            Err(StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND).into_response())
+        Err(StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND).into_response())
+    }
+}
+
+/// Export the moves played in the specified game as a transcript.
+pub async fn export(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    user: User,
+) -> Result<impl IntoResponse, Response<Body>> {
+    let game = helpers::get_game(&state, &id).await?;
+    // Pretend games the user isn't participating in don't exist.
+    let authed = user.id.to_string();
+    if authed != game.host && authed != game.guest {
+        return Err(
+            StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND).into_response(),
+        );
+    }
+    let history = view(&state, &game, permissions(&state, &game, user.id))
+        .await
+        .ok_or(StringError(
+            strings::INVALID_GAME_ID.into(),
+            StatusCode::NOT_FOUND,
+        ))?
+        .history();
+    Ok(super::Response::new(
+        json!({
+            "id": game.id,
+            "transcript": transcript(&history),
+            "moves": history,
+        }),
+        StatusCode::OK,
+    ))
+}
+
+/// Retrieve everything a client needs to render the specified game in a single document:
+/// its players, status, current position, move list, and result.
+pub async fn detail(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    user: User,
+) -> Result<impl IntoResponse, Response<Body>> {
+    let game = helpers::get_game(&state, &id).await?;
+    // Pretend games the user isn't participating in don't exist.
+    let authed = user.id.to_string();
+    if authed != game.host && authed != game.guest {
+        return Err(
+            StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND).into_response(),
+        );
+    }
+    let (black, white) = match helpers::host_color(&game) {
+        Piece::Black => (&game.host, &game.guest),
+        Piece::White => (&game.guest, &game.host),
+    };
+    let black = helpers::get_user(&state, black, false).await?;
+    let white = helpers::get_user(&state, white, false).await?;
+    // Pending games haven't started yet, so they have no position.
+    let permissions = permissions(&state, &game, user.id);
+    let position = view(&state, &game, permissions).await;
+    let status = match (game.pending, game.ended) {
+        (true, _) => "pending",
+        (false, false) => "active",
+        (false, true) => "ended",
+    };
+    let history = position
+        .as_ref()
+        .map(crate::Game::history)
+        .unwrap_or_default();
+    let (score_black, score_white) = position.as_ref().map_or((2, 2), crate::Game::score);
+    let book = state.assets.book();
+    Ok(super::Response::new(
+        json!({
+            "id": game.id,
+            "status": status,
+            "challenge": game.challenge,
+            "settings": helpers::game_settings(&game),
+            "players": {
+                "black": user_summary(&state, &black).await,
+                "white": user_summary(&state, &white).await,
+            },
+            "turn": position.as_ref().map(crate::Game::turn),
+            "position": position,
+            "score": { "black": score_black, "white": score_white },
+            "prediction": position
+                .as_ref()
+                .filter(|_| permissions.evaluations)
+                .map(|game| analysis::predict_result_with(game, &state.assets.weights())),
+            "moves": history,
+            "transcript": transcript(&history),
+            // Fog games only show the moves a player could see, which may not be the opening.
+            "opening": position
+                .as_ref()
+                .filter(|_| permissions.fog.is_none())
+                .and_then(|position| position.opening_name_in(&book)),
+            "result": game.result,
+            // When the move being waited on is due, in correspondence games.
+            "deadline": correspondence::deadline(&game).as_ref().map(timestamp::rfc3339),
+        }),
+        StatusCode::OK,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplayParams {
+    /// Extra data to merge into each move, as a comma-separated list. Only `analysis` is
+    /// currently supported.
+    #[serde(default)]
+    with: String,
+}
+
+/// Retrieve the specified game move by move, with the score after each one and, for moves
+/// played since times were recorded, when it was played and how long it took. Asking for
+/// `?with=analysis` annotates each move with the engine's evaluation, the best move it could
+/// find, and how the played move compares.
+pub async fn replay(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<ReplayParams>,
+    user: User,
+) -> Result<impl IntoResponse, Response<Body>> {
+    let game = helpers::get_game(&state, &id).await?;
+    // Pretend games the user isn't participating in don't exist, apart from arena games,
+    // which are played in the open.
+    let authed = user.id.to_string();
+    if authed != game.host && authed != game.guest && game.arena.is_none() {
+        return Err(
+            StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND).into_response(),
+        );
+    }
+    let permissions = permissions(&state, &game, user.id);
+    if permissions.fog.is_some() {
+        return Err(StringError(strings::FOG_REPLAY.into(), StatusCode::FORBIDDEN).into_response());
+    }
+    let settings = helpers::game_settings(&game);
+    let history = view(&state, &game, permissions)
+        .await
+        .ok_or(StringError(
+            strings::INVALID_GAME_ID.into(),
+            StatusCode::NOT_FOUND,
+        ))?
+        .history();
+    let annotations = params
+        .with
+        .split(',')
+        .any(|extra| extra == "analysis")
+        .then(|| analysis::annotate(settings, &history, &state.assets.weights()));
+    let times = moves::of_game(&state, game.id).await?;
+    let mut position = crate::Game::with_settings(settings);
+    let mut plies = Vec::with_capacity(history.len());
+    for (ply, &(x, y)) in history.iter().enumerate() {
+        let piece = position.turn();
+        if position.place(x, y, piece).is_err() {
+            break;
+        }
+        let (black, white) = position.score();
+        let mut entry = json!({
+            "ply": ply + 1,
+            "piece": piece,
+            "move": [x, y],
+            "notation": transcript(&[(x, y)]),
+            "score": { "black": black, "white": white },
+        });
+        if let Some(annotation) = annotations.as_ref().and_then(|a| a.get(ply)) {
+            entry["analysis"] = json!(annotation);
+        }
+        // Moves played before times were recorded go without.
+        if let Some(time) = times
+            .iter()
+            .find(|time| usize::try_from(time.ply) == Ok(ply + 1))
+        {
+            entry["played_at"] = json!(timestamp::rfc3339(&time.played_at));
+            entry["time_taken"] = json!(time.time_taken);
+        }
+        plies.push(entry);
+    }
+    Ok(super::Response::new(
+        json!({
+            "id": game.id,
+            "opening": position.opening_name_in(&state.assets.book()),
+            "moves": plies,
+            "result": game.result,
+        }),
+        StatusCode::OK,
+    ))
+}
+
+/// Retrieve the engine's analysis of the specified game, which is run once the game ends:
+/// each move's evaluation, the best move it could find, and how the played move compares,
+/// along with how many inaccuracies, mistakes and blunders each side made.
+pub async fn analysis(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    user: User,
+) -> Result<impl IntoResponse, Response<Body>> {
+    let game = helpers::get_game(&state, &id).await?;
+    // Pretend games the user isn't participating in don't exist.
+    let authed = user.id.to_string();
+    if authed != game.host && authed != game.guest {
+        return Err(
+            StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND).into_response(),
+        );
+    }
+    // Games are only analysed once they're over, so there's no fog left to hide.
+    let moves = review::get(&state, game.id)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(StringError(
+            strings::ANALYSIS_NOT_READY.into(),
+            StatusCode::NOT_FOUND,
+        ))?;
+    let (black, white) = review::tally(&moves);
+    let plies: Vec<_> = moves
+        .iter()
+        .enumerate()
+        .map(|(ply, reviewed)| {
+            json!({
+                "ply": ply + 1,
+                "piece": review::mover(ply),
+                "move": reviewed.played,
+                "notation": transcript(&[reviewed.played]),
+                "eval": reviewed.annotation.eval,
+                "best": reviewed.annotation.best,
+                "classification": reviewed.annotation.classification,
+            })
+        })
+        .collect();
+    Ok(super::Response::new(
+        json!({
+            "id": game.id,
+            "moves": plies,
+            "black": black,
+            "white": white,
+        }),
+        StatusCode::OK,
+    ))
+}
+
+/// How the live game browser orders games.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LiveSort {
+    /// The newest games first.
+    #[default]
+    Recent,
+    /// The games between the highest rated players first.
+    Rating,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LiveParams {
+    #[serde(default)]
+    sort: LiveSort,
+}
+
+/// List the games being played that anyone can watch (those in arenas and tournaments), newest
+/// first (or oldest, with `?order=asc`) or, with `?sort=rating`, between the highest rated
+/// players first. Each comes with its players' ratings and presence, and how many moves have
+/// been played.
+pub async fn live(
+    State(state): State<Arc<AppState>>,
+    _: User,
+    Query(params): Query<LiveParams>,
+    pagination: Pagination,
+) -> Result<impl IntoResponse, Response<Body>> {
+    // Pairings are only decided once their games end.
+    let tournaments: HashMap<_, _> = TournamentRound::find()
+        .filter(tournament_round::Column::Game.is_not_null())
+        .filter(tournament_round::Column::Result.is_null())
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?
+        .into_iter()
+        .filter_map(|pairing| Some((pairing.game?, pairing.tournament)))
+        .collect();
+    let games = GameModel::find()
+        .filter(Column::Pending.eq(false))
+        .filter(Column::Ended.eq(false))
+        .filter(
+            Condition::any()
+                .add(Column::Arena.is_not_null())
+                .add(Column::Id.is_in(tournaments.keys().copied())),
+        )
+        // Game IDs are time-ordered, so this puts the newest first unless asked otherwise.
+        .order_by(Column::Id, pagination.order.into())
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    let moves = played(&state, &games).await;
+    let players: Vec<Uuid> = games
+        .iter()
+        .flat_map(|game| {
+            let (black, white) = helpers::black_white(game);
+            [black, white]
+        })
+        .flatten()
+        .collect();
+    let players = helpers::get_users_by_ids(&state, players).await?;
+    let summaries = user_summaries(&state, players.values()).await;
+    let summary = |player: Option<Uuid>| summaries.get(&player?).cloned();
+    let mut games: Vec<_> = games
+        .iter()
+        .zip(moves)
+        .filter_map(|(game, moves)| {
+            let (black, white) = helpers::black_white(game);
+            Some(LiveGame {
+                id: game.id,
+                black: summary(black)?,
+                white: summary(white)?,
+                settings: helpers::game_settings(game),
+                moves,
+                tournament: tournaments.get(&game.id).copied(),
+                arena: game.arena,
+            })
+        })
+        .collect();
+    if params.sort == LiveSort::Rating {
+        // Players who haven't been placed yet are taken to be as good as a newcomer.
+        let rating = |game: &LiveGame| {
+            [&game.black, &game.white]
+                .iter()
+                .map(|player| player.rating.unwrap_or(season::INITIAL_RATING))
+                .sum::<i32>()
+        };
+        games.sort_by_key(|game| Reverse(rating(game)));
+    }
+    let (games, page) = pagination.slice(games, pagination.limit());
+    Ok(super::Response::paginated(games, page, StatusCode::OK))
+}
+
+/// How many moves have been played in each of the specified games, as the cached positions
+/// (which every instance shares) have it. Games without a cached position haven't seen a
+/// move yet.
+async fn played(state: &AppState, games: &[Model]) -> Vec<usize> {
+    let keys: Vec<_> = games
+        .iter()
+        .map(|game| format!("game:{}", game.id))
+        .collect();
+    let cached: Vec<Option<String>> = match state.redis.get().await {
+        Ok(mut conn) if !keys.is_empty() => conn.mget(&keys).await.unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    games
+        .iter()
+        .enumerate()
+        .map(|(i, game)| {
+            cached
+                .get(i)
+                .and_then(Option::as_deref)
+                .and_then(|cached| serde_json::from_str::<crate::Game>(cached).ok())
+                .or_else(|| {
+                    let games = state.games.lock().expect("mutex was poisoned");
+                    games.get(&game.id).cloned()
+                })
+                .map_or(0, |position| position.history().len())
+        })
+        .collect()
+}
+
+/// How long (in seconds) a game's preview may be cached for by whoever shows it.
+const PREVIEW_TTL: u64 = 60;
+
+/// What a game's preview is drawn as.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewFormat {
+    /// What most chat apps show, unlike SVG images.
+    #[default]
+    Png,
+    Svg,
+}
+
+/// The theme a game's preview is drawn in.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewTheme {
+    #[default]
+    Classic,
+    Dark,
+    Print,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewParams {
+    #[serde(default)]
+    format: PreviewFormat,
+    #[serde(default)]
+    theme: PreviewTheme,
+}
+
+/// Draw the specified game's board as it stands, as an image for chat apps to show when a link
+/// to the game is shared (as the `og:image` of its page): a PNG image, or an SVG one with
+/// `?format=svg`, in the theme asked for with `?theme=`. Only games that anyone can watch
+/// (those in arenas and tournaments) can be previewed, and tournament games still being played
+/// are drawn as their spectators see them, behind the players.
+pub async fn preview(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<PreviewParams>,
+) -> Result<impl IntoResponse, Response<Body>> {
+    let game = helpers::get_game(&state, &id).await?;
+    let in_tournament = tournament::includes(state.database.as_ref(), game.id)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    // Pretend games played in private don't exist.
+    if game.arena.is_none() && !in_tournament {
+        return Err(
+            StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND).into_response(),
+        );
+    }
+    let mut position = position(&state, &game).await;
+    if let (true, false, Some(delay)) = (in_tournament, game.ended, state.spectator_delay) {
+        position = behind(&state, &game, &position, delay).await?;
+    }
+    let position = Viewer::Anonymous
+        .permissions(position.settings(), game.ended)
+        .position(&position);
+    let renderer = Renderer::new().with_theme(match params.theme {
+        PreviewTheme::Classic => Theme::CLASSIC,
+        PreviewTheme::Dark => Theme::DARK,
+        PreviewTheme::Print => Theme::PRINT,
+    });
+    let (content_type, image) = match params.format {
+        PreviewFormat::Svg => ("image/svg+xml", Body::from(renderer.svg(&position))),
+        PreviewFormat::Png => ("image/png", Body::from(renderer.png(&position))),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, String::from(content_type)),
+            (
+                header::CACHE_CONTROL,
+                format!("public, max-age={PREVIEW_TTL}"),
+            ),
+        ],
+        image,
+    ))
+}
+
+/// The specified game's position, wherever it's kept: in memory on this instance, in the cache
+/// that every instance shares or, failing those, as last saved in its row.
+async fn position(state: &AppState, game: &Model) -> crate::Game {
+    let loaded = {
+        let games = state.games.lock().expect("mutex was poisoned");
+        games.get(&game.id).cloned()
+    };
+    if let Some(position) = loaded {
+        return position;
+    }
+    let cached: Option<String> = match state.redis.get().await {
+        Ok(mut conn) => conn.get(format!("game:{}", game.id)).await.unwrap_or(None),
+        Err(_) => None,
+    };
+    cached
+        .and_then(|cached| serde_json::from_str(&cached).ok())
+        .or_else(|| serde_json::from_value(game.state.clone()?).ok())
+        .unwrap_or_else(|| crate::Game::with_settings(helpers::game_settings(game)))
+}
+
+/// The specified position of a game as its spectators see it, the specified time behind its
+/// players: without the moves played within the delay.
+async fn behind(
+    state: &AppState,
+    game: &Model,
+    position: &crate::Game,
+    delay: Duration,
+) -> Result<crate::Game, StringError> {
+    let now = Utc::now();
+    let first_hidden = moves::of_game(state, game.id)
+        .await?
+        .into_iter()
+        .filter(|played| {
+            now.signed_duration_since(played.played_at)
+                .to_std()
+                .unwrap_or_default()
+                < delay
         })
+        .filter_map(|played| usize::try_from(played.ply).ok())
+        .min();
+    let Some(first_hidden) = first_hidden else {
+        return Ok(position.clone());
+    };
+    let history = position.history();
+    let mut shown = crate::Game::with_settings(*position.settings());
+    for &(x, y) in &history[..first_hidden.saturating_sub(1).min(history.len())] {
+        let piece = shown.turn();
+        let _ = shown.place(x, y, piece);
+    }
+    Ok(shown)
+}
+
+/// What the specified user may see of the specified game.
+fn permissions(state: &AppState, game: &Model, user: Uuid) -> Permissions {
+    Viewer::of(state, game.id, Some(user)).permissions(&helpers::game_settings(game), game.ended)
+}
+
+/// The specified game as a viewer with the specified permissions may see it, wherever its
+/// position is kept, or `None` if it hasn't started.
+async fn view(state: &AppState, game: &Model, permissions: Permissions) -> Option<crate::Game> {
+    if game.pending {
+        return None;
+    }
+    Some(permissions.position(&position(state, game).await))
+}
+
+/// Write out a move list in the standard notation, where columns are lettered from the left
+/// and rows are numbered from the top (e.g. `f5d6c3`).
+fn transcript(history: &[(usize, usize)]) -> String {
+    Moves::from(history).to_string()
 }
 
 pub async fn cancel(
@@ -58,7 +591,7 @@ pub async fn cancel(
     let game = helpers::get_game(&state, &id).await?;
     // Convert to strings for more ergonomic comparison.
     let authed = user.id.to_string();
-    let host = game.host.to_string();
+    let host = game.host.clone();
     // Ensure that the authenticated user is the host.
     if authed == host {
         // If so, delete the game record from the database.
@@ -83,19 +616,55 @@ pub async fn accept(
     let game = helpers::get_game(&state, &id).await?;
     // Convert to strings for more ergonomic comparison.
     let authed = user.id.to_string();
-    let guest = game.guest.to_string();
+    let guest = game.guest.clone();
     // Ensure that the authenticated user is the guest.
     if authed == guest {
-        // If so, update the game record to indicate that the game is no longer pending.
-        let game = helpers::get_game(&state, &id).await?;
-        let mut active = game.into_active_model();
-        active.set(Column::Pending, Value::Bool(Some(false)));
-        active
-            .save(state.database.as_ref())
-            .await
-            .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
-        let gid = Uuid::from_str(&id).unwrap();
-        create_in_memory_game(&state, gid);
+        // Invites that went unanswered for too long can't be taken up, even before they're
+        // deleted.
+        if invites::expired(&state, &game) {
+            return Err(
+                StringError(strings::INVITE_EXPIRED.into(), StatusCode::GONE).into_response(),
+            );
+        }
+        conduct::ensure_can_play(&state, user.id, &helpers::game_settings(&game)).await?;
+        let game = if let Some(challenge) = game.challenge {
+            // The invitation was sent to several users, so claim it on behalf of this one
+            // and cancel every other invitation.
+            let (started, cancelled) = claim_challenge(&state, game.id, challenge).await?;
+            for other in cancelled {
+                let Ok(recipient) = Uuid::from_str(&other.guest) else {
+                    continue;
+                };
+                state.notify(
+                    recipient,
+                    Event::new(
+                        EventKind::GameInviteCancel,
+                        ServerMessage::GameInviteCancel {
+                            game: other.id.to_string(),
+                            challenge: challenge.to_string(),
+                        },
+                    ),
+                );
+            }
+            started
+        } else {
+            // If so, update the game record to indicate that the game is no longer pending.
+            // Only a pending game is updated, so that accepting it again (which could
+            // toss for colours again) finds nothing to accept.
+            GameModel::update_many()
+                .set(start(&game))
+                .filter(Column::Id.eq(game.id))
+                .filter(Column::Pending.eq(true))
+                .exec_with_returning(state.database.as_ref())
+                .await
+                .map_err(StringError::from)?
+                .pop()
+                .ok_or_else(|| {
+                    StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND)
+                })?
+        };
+        create_in_memory_game(&state, &game).await?;
+        webhooks::game_started(&state, &game).await;
         Ok(super::Response::new(json!({}), StatusCode::OK))
     } else {
         // Otherwise, pretend the game does not exist.
@@ -103,6 +672,51 @@ pub async fn accept(
     }
 }
 
+/// Mark the specified invitation as accepted, seating its players by the colour policy it was
+/// sent with, and start the clock on the first move.
+fn start(game: &Model) -> ActiveModel {
+    let mut active = game.clone().into_active_model();
+    active.set(Column::Pending, Value::Bool(Some(false)));
+    active.turn_started_at = ActiveValue::set(Some(Utc::now().fixed_offset()));
+    let host_color = helpers::draw_host_color(&helpers::game_settings(game));
+    active.host_color = ActiveValue::set(helpers::color_name(host_color).into());
+    active
+}
+
+/// Atomically accept the specified game on behalf of its guest and delete every other
+/// invitation belonging to the same challenge, returning the accepted game along with the
+/// deleted invitations.
+async fn claim_challenge(
+    state: &AppState,
+    gid: Uuid,
+    challenge: Uuid,
+) -> Result<(Model, Vec<Model>), StringError> {
+    let txn = state.database.begin().await?;
+    // Lock every invitation in the challenge (in a consistent order, to avoid deadlocks)
+    // so that two guests accepting at the same time can't both win.
+    let invitations = GameModel::find()
+        .filter(Column::Challenge.eq(challenge))
+        .order_by_asc(Column::Id)
+        .lock_exclusive()
+        .all(&txn)
+        .await?;
+    // If this invitation is gone or no longer pending, another guest got there first.
+    let Some(claimed) = invitations.iter().find(|g| g.id == gid && g.pending) else {
+        return Err(StringError(
+            strings::INVALID_GAME_ID.into(),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+    let started = start(claimed).update(&txn).await?;
+    let cancelled: Vec<_> = invitations.into_iter().filter(|g| g.id != gid).collect();
+    GameModel::delete_many()
+        .filter(Column::Id.is_in(cancelled.iter().map(|g| g.id)))
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    Ok((started, cancelled))
+}
+
 pub async fn decline(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -113,7 +727,7 @@ pub async fn decline(
     let game = helpers::get_game(&state, &id).await?;
     // Convert to strings for more ergonomic comparison.
     let authed = user.id.to_string();
-    let guest = game.guest.to_string();
+    let guest = game.guest.clone();
     // Ensure that the authenticated user is the guest.
     if authed == guest {
         // If so, delete the game record from the database.
@@ -127,3 +741,644 @@ pub async fn decline(
         Err(StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND).into_response())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::server::{
+        self,
+        handlers::{ApiError, ErrorCode, Response},
+        helpers, packet, season, strings, summary,
+        summary::{Termination, Verdict},
+    };
+    use axum::http::StatusCode;
+    use redis::AsyncCommands;
+    use serde_json::json;
+    use test_utils::{function, Client, Map, Socket};
+
+    #[tokio::test]
+    async fn challenge() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let first = format!("{host}::1");
+        let second = format!("{host}::2");
+        let client = Client::authenticated(&[&host, &first, &second], &url, true).await;
+        let resp: Response<Map> = client
+            .post(
+                &url,
+                "/game",
+                serde_json::json!({ "guests": [&first, &second] }),
+            )
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let games = resp.message["games"].as_array().unwrap();
+        assert_eq!(games.len(), 2);
+        // The first guest to accept gets to play.
+        let client = Client::authenticated(&[&first], &url, false).await;
+        let resp: Response<Map> = client
+            .post(
+                &url,
+                &format!("/@me/games/{}/accept", games[0]["id"].as_str().unwrap()),
+                serde_json::json!({}),
+            )
+            .await;
+        assert_eq!(resp.code, StatusCode::OK);
+        // The second guest's invitation was cancelled.
+        let client = Client::authenticated(&[&second], &url, false).await;
+        let resp: ApiError = client
+            .post(
+                &url,
+                &format!("/@me/games/{}/accept", games[1]["id"].as_str().unwrap()),
+                serde_json::json!({}),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        // The game can be shared with a link that opens it.
+        assert_eq!(
+            resp.message["links"]["app"],
+            format!("olly://play?gameId={id}")
+        );
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        // Only moves played through the server have their times recorded.
+        let metadata = helpers::get_game(&state, &id).await.unwrap();
+        packet::make_move(&state, &metadata, 2, 3, crate::Piece::Black)
+            .await
+            .unwrap();
+        {
+            let mut games = state.games.lock().unwrap();
+            let game = games.get_mut(&id.parse().unwrap()).unwrap();
+            game.place(2, 2, crate::Piece::White).unwrap();
+        }
+        let resp: Response<Map> = client.get(&url, &format!("/games/{id}/replay")).await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let moves = resp.message["moves"].as_array().unwrap();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0]["notation"], "c4");
+        assert_eq!(moves[0]["score"], json!({ "black": 4, "white": 1 }));
+        assert_eq!(resp.message["opening"], "Diagonal");
+        assert!(moves[0].get("analysis").is_none());
+        assert!(moves[0]["time_taken"].is_i64());
+        assert!(moves[0]["played_at"].is_string());
+        assert!(moves[1].get("time_taken").is_none());
+        let resp: Response<Map> = client.get(&url, &format!("/users/{host}")).await;
+        assert!(resp.message["stats"]["average_move_time"].is_i64());
+        let resp: Response<Map> = client.get(&url, &format!("/users/{guest}")).await;
+        assert!(resp.message["stats"]["average_move_time"].is_null());
+        let resp: Response<Map> = client
+            .get(&url, &format!("/games/{id}/replay?with=analysis"))
+            .await;
+        let moves = resp.message["moves"].as_array().unwrap();
+        // Every opening move is as good as any other.
+        assert_eq!(moves[0]["analysis"]["classification"], "best");
+        assert!(moves[1]["analysis"]["eval"].is_i64());
+    }
+
+    #[tokio::test]
+    async fn concluded() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let metadata = helpers::get_game(&state, &id).await.unwrap();
+        packet::make_move(&state, &metadata, 2, 3, crate::Piece::Black)
+            .await
+            .unwrap();
+        let game = state.games.lock().unwrap()[&metadata.id].clone();
+        let resign = Some(Verdict::Forfeit(
+            crate::Piece::White,
+            Termination::Resignation,
+        ));
+        summary::conclude(&state, &metadata, &game, resign, None)
+            .await
+            .unwrap();
+        // Once the server restarts, games that are over are only kept in their rows.
+        state.games.lock().unwrap().remove(&metadata.id);
+        let mut conn = state.redis.get().await.unwrap();
+        conn.del::<_, ()>(format!("game:{id}")).await.unwrap();
+        let resp: Response<Map> = client.get(&url, &format!("/games/{id}/replay")).await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let moves = resp.message["moves"].as_array().unwrap();
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0]["notation"], "c4");
+        let resp: Response<Map> = client.get(&url, &format!("/games/{id}")).await;
+        assert_eq!(resp.message["status"], "ended");
+        assert_eq!(resp.message["transcript"], "c4");
+        assert_eq!(resp.message["score"]["black"], 4);
+        let resp: Response<Map> = client.get(&url, &format!("/game/{id}/export")).await;
+        assert_eq!(resp.message["transcript"], "c4");
+    }
+
+    #[tokio::test]
+    async fn analysis() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let mut game = crate::Game::new();
+        game.place(2, 3, crate::Piece::Black).unwrap();
+        game.place(2, 2, crate::Piece::White).unwrap();
+        // Nothing is analysed until the game is over.
+        let resp: ApiError = client.get(&url, &format!("/games/{id}/analysis")).await;
+        assert_eq!(resp.message, strings::ANALYSIS_NOT_READY);
+        let metadata = helpers::get_game(&state, &id).await.unwrap();
+        let resign = Some(Verdict::Forfeit(
+            crate::Piece::White,
+            Termination::Resignation,
+        ));
+        summary::conclude(&state, &metadata, &game, resign, None)
+            .await
+            .unwrap();
+        // The analysis runs in the background.
+        let mut resp = Map::new();
+        for _ in 0..50 {
+            resp = client.get(&url, &format!("/games/{id}/analysis")).await;
+            if resp["code"] == 200 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(resp["code"], 200);
+        let moves = resp["message"]["moves"].as_array().unwrap();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0]["notation"], "c4");
+        assert_eq!(moves[0]["classification"], "best");
+        assert!(resp["message"]["white"]["blunders"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn fog() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client
+            .post(
+                &url,
+                "/game",
+                json!({ "guest": guest, "settings": { "variant": "fog" } }),
+            )
+            .await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let game = {
+            let mut games = state.games.lock().unwrap();
+            let game = games.get_mut(&id.parse().unwrap()).unwrap();
+            for _ in 0..8 {
+                let piece = game.turn();
+                let (x, y) = game.moves(piece)[0];
+                game.place(x, y, piece).unwrap();
+            }
+            game.clone()
+        };
+        // The guest plays white, and only sees what's around their own pieces.
+        let token = other.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify", "version": 3 }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        let event = socket.recv_op(4).await;
+        let view = game.visible_to(crate::Piece::White);
+        assert_eq!(event["d"]["game"], json!(view));
+        let resp: Response<Map> = other.get(&url, &format!("/games/{id}")).await;
+        assert_eq!(resp.message["position"], json!(view));
+        assert_eq!(resp.message["prediction"], serde_json::Value::Null);
+        let resp: ApiError = other.get(&url, &format!("/games/{id}/replay")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn resignation() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        // The host joins the game over the websocket and resigns.
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({
+                "op": 6,
+                "d": { "type": "Identify", "version": packet::PROTOCOL_VERSION },
+                "t": token,
+            }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        socket.recv_op(4).await;
+        socket
+            .send(json!({ "op": 8, "d": { "type": "Resign", "id": id }, "t": token }))
+            .await;
+        let mut event = socket.recv_op(7).await;
+        assert_eq!(event["d"]["type"], "GameEnd");
+        assert_eq!(event["d"]["termination"], "resignation");
+        assert_eq!(event["d"]["result"], "white");
+        assert_eq!(event["d"]["winner"], guest);
+        // The same summary, minus the message tag, is available from the REST API.
+        event["d"].as_object_mut().unwrap().remove("type");
+        let resp: Response<Map> = client.get(&url, &format!("/game/{id}")).await;
+        assert_eq!(resp.message["ended"], true);
+        assert_eq!(resp.message["result"], event["d"]);
+    }
+
+    #[tokio::test]
+    async fn verification() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        socket.recv_op(4).await;
+        let place = |x, y, piece| {
+            json!({
+                "op": 2,
+                "d": { "type": "Place", "id": id, "x": x, "y": y, "piece": piece },
+                "t": token,
+            })
+        };
+        socket.send(place(5, 4, "Black")).await;
+        socket.recv_op(4).await;
+        // Players can't move for their opponents, even when the move would be legal.
+        socket.send(place(3, 5, "White")).await;
+        let event = socket.recv_op(6).await;
+        assert_eq!(event["d"]["code"], "not_your_piece");
+        assert_eq!(event["d"]["message"], strings::NOT_YOUR_PIECE);
+        // Messages sent under the wrong opcode are turned away.
+        socket
+            .send(json!({ "op": 9, "d": { "type": "Resign", "id": id }, "t": token }))
+            .await;
+        let event = socket.recv_op(6).await;
+        assert_eq!(event["d"]["message"], strings::BAD_REQUEST);
+        let metadata = helpers::get_game(&state, &id).await.unwrap();
+        assert!(!metadata.ended);
+        socket
+            .send(json!({ "op": 8, "d": { "type": "Resign", "id": id }, "t": token }))
+            .await;
+        socket.recv_op(7).await;
+        // The result is kept along with the position it was decided from.
+        let metadata = helpers::get_game(&state, &id).await.unwrap();
+        let position: crate::Game = serde_json::from_value(metadata.state.unwrap()).unwrap();
+        assert_eq!(position.history(), [(5, 4)]);
+        assert_eq!(metadata.result.unwrap()["score"]["black"], 4);
+    }
+
+    #[tokio::test]
+    async fn stalling() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = server::AppState::new(database, redis)
+            .with_stall_timeout(std::time::Duration::from_millis(300));
+        let url = test_utils::init(crate::server::app(Arc::new(state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        // The guest joins the game over the websocket and waits for the host to move first.
+        let token = other.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        socket.recv_op(4).await;
+        let claim =
+            json!({ "op": 9, "d": { "type": "Claim", "id": id, "draw": true }, "t": token });
+        socket.send(&claim).await;
+        let event = socket.recv_op(6).await;
+        assert_eq!(event["d"]["message"], strings::CLAIM_TOO_EARLY);
+        // The host never moves, so the guest can declare a draw.
+        tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+        socket.send(&claim).await;
+        let event = socket.recv_op(7).await;
+        assert_eq!(event["d"]["termination"], "stalling");
+        assert_eq!(event["d"]["result"], "draw");
+        let resp: Response<Map> = client.get(&url, &format!("/game/{id}")).await;
+        assert_eq!(resp.message["ended"], true);
+    }
+
+    #[tokio::test]
+    async fn detail() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        // Settings the server can't honour are turned away.
+        let resp: ApiError = client
+            .post(
+                &url,
+                "/game",
+                json!({ "guest": guest, "settings": { "handicap": 5 } }),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.code, ErrorCode::InvalidSettings);
+        assert_eq!(resp.details["fields"][0]["field"], "settings.handicap");
+        assert_eq!(resp.details["fields"][0]["details"]["max"], 4);
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        assert_eq!(resp.message["settings"]["board_size"], 8);
+        let resp: Response<Map> = client.get(&url, &format!("/games/{id}")).await;
+        assert_eq!(resp.message["status"], "pending");
+        assert_eq!(resp.message["settings"]["variant"], "standard");
+        assert_eq!(resp.message["position"], serde_json::Value::Null);
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let resp: Response<Map> = client.get(&url, &format!("/games/{id}")).await;
+        assert_eq!(resp.code, StatusCode::OK);
+        assert_eq!(resp.message["status"], "active");
+        assert_eq!(resp.message["players"]["black"]["username"], host);
+        assert_eq!(resp.message["players"]["white"]["username"], guest);
+        assert_eq!(resp.message["turn"], "Black");
+        assert_eq!(resp.message["score"]["black"], 2);
+        assert_eq!(resp.message["prediction"]["black"], 0.5);
+        assert_eq!(resp.message["transcript"], "");
+        assert_eq!(
+            resp.message["position"]["settings"]["color_policy"],
+            "host-black"
+        );
+    }
+
+    #[tokio::test]
+    async fn colours() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let settings = json!({ "color_policy": "guest-black", "handicap": 2 });
+        let resp: Response<Map> = client
+            .post(
+                &url,
+                "/game",
+                json!({ "guest": guest, "settings": settings }),
+            )
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        // Accepting again can't toss for colours again.
+        let resp: ApiError = other
+            .post(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        // The host is still the host, and plays white.
+        let resp: Response<Map> = client.get(&url, &format!("/game/{id}")).await;
+        let host_id = helpers::get_user(&state, &host, true).await.unwrap().id;
+        assert_eq!(resp.message["host"], host_id.to_string());
+        assert_eq!(resp.message["host_color"], "White");
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/games").await;
+        let game = resp.message.iter().find(|game| game["id"] == id).unwrap();
+        assert_eq!(game["host"], host);
+        assert_eq!(game["host_color"], "White");
+        // The guest plays black, starting with the handicap corners.
+        let resp: Response<Map> = client.get(&url, &format!("/games/{id}")).await;
+        assert_eq!(resp.message["players"]["black"]["username"], guest);
+        assert_eq!(resp.message["players"]["white"]["username"], host);
+        assert_eq!(resp.message["score"]["black"], 4);
+        assert_eq!(resp.message["score"]["white"], 2);
+        let resp: Response<Map> = client.get(&url, &format!("/games/{id}/replay")).await;
+        assert_eq!(resp.message["opening"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn live() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client
+            .post(&url, "/tournaments", json!({ "name": "Weekly", "size": 2 }))
+            .await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/tournaments/{id}/join"), json!({}))
+            .await;
+        client
+            .post::<_, Map>(&url, &format!("/tournaments/{id}/start"), json!({}))
+            .await;
+        let resp: Response<Map> = client.get(&url, &format!("/tournaments/{id}")).await;
+        let game = resp.message["pairings"][0][0]["game"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let metadata = helpers::get_game(&state, &game).await.unwrap();
+        packet::make_move(&state, &metadata, 5, 4, crate::Piece::Black)
+            .await
+            .unwrap();
+        // The game shows up for anyone, along with its players and how far along it is.
+        let resp: Response<Vec<Map>> = client.get(&url, "/games/live?limit=100").await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let listed = resp
+            .message
+            .iter()
+            .find(|listed| listed["id"] == game.as_str())
+            .unwrap();
+        assert_eq!(listed["moves"], 1);
+        assert_eq!(listed["tournament"], id.as_str());
+        let players = [&listed["black"]["username"], &listed["white"]["username"]];
+        assert!(players.contains(&&json!(host)) && players.contains(&&json!(guest)));
+        // Sorted by rating, the strongest pairings come first.
+        let resp: Response<Vec<Map>> = client.get(&url, "/games/live?sort=rating").await;
+        let ratings: Vec<_> = resp
+            .message
+            .iter()
+            .map(|listed| {
+                [&listed["black"], &listed["white"]]
+                    .iter()
+                    .map(|player| {
+                        player["rating"]
+                            .as_i64()
+                            .unwrap_or(season::INITIAL_RATING.into())
+                    })
+                    .sum::<i64>()
+            })
+            .collect();
+        assert!(ratings.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[tokio::test]
+    async fn preview() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let delay = Duration::from_millis(500);
+        let state = Arc::new(server::AppState::new(database, redis).with_spectator_delay(delay));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        // Games played in private can't be previewed.
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let private = resp.message["id"].as_str().unwrap().to_string();
+        let resp: ApiError = Client::new()
+            .get(&url, &format!("/games/{private}/og"))
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.message, strings::INVALID_GAME_ID);
+        let resp: Response<Map> = client
+            .post(&url, "/tournaments", json!({ "name": "Weekly", "size": 2 }))
+            .await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/tournaments/{id}/join"), json!({}))
+            .await;
+        client
+            .post::<_, Map>(&url, &format!("/tournaments/{id}/start"), json!({}))
+            .await;
+        let resp: Response<Map> = client.get(&url, &format!("/tournaments/{id}")).await;
+        let game = resp.message["pairings"][0][0]["game"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let metadata = helpers::get_game(&state, &game).await.unwrap();
+        packet::make_move(&state, &metadata, 5, 4, crate::Piece::Black)
+            .await
+            .unwrap();
+        // Anyone can preview a tournament game, though not ahead of its spectators.
+        let resp = Client::new()
+            .get_raw(&url, &format!("/games/{game}/og?format=svg"))
+            .await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(resp.headers()["content-type"], "image/svg+xml");
+        assert_eq!(resp.headers()["cache-control"], "public, max-age=60");
+        let svg = resp.text().await.unwrap();
+        assert_eq!(svg.matches("class=\"piece black\"").count(), 2);
+        assert!(!svg.contains("class=\"last\""));
+        tokio::time::sleep(delay).await;
+        let svg = Client::new()
+            .get_raw(&url, &format!("/games/{game}/og?format=svg"))
+            .await
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(svg.matches("class=\"piece black\"").count(), 4);
+        assert!(svg.contains("class=\"last\""));
+        // Previews are PNG images unless asked for otherwise, since most chat apps don't show SVG.
+        let resp = Client::new()
+            .get_raw(&url, &format!("/games/{game}/og?theme=dark"))
+            .await;
+        assert_eq!(resp.headers()["content-type"], "image/png");
+        assert!(resp.bytes().await.unwrap().starts_with(b"\x89PNG\r\n"));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::server::{handlers::Response, state::AppState};
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// How long each component has to answer before it's considered down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Health {
+    Up,
+    Down,
+}
+
+/// How one of the services the server depends on is doing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Component {
+    pub status: Health,
+    /// How long the check took to answer, in milliseconds.
+    pub latency: u64,
+    /// What went wrong, if the component is down.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    /// Down if any component is, or the server is shutting down.
+    pub status: Health,
+    pub shutting_down: bool,
+    pub database: Component,
+    pub redis: Component,
+}
+
+impl Component {
+    fn from_check(started: Instant, result: Result<(), String>) -> Self {
+        #[allow(clippy::cast_possible_truncation)] // Checks give up long before u64::MAX ms
+        let latency = started.elapsed().as_millis() as u64;
+        match result {
+            Ok(()) => Self {
+                status: Health::Up,
+                latency,
+                error: None,
+            },
+            Err(e) => Self {
+                status: Health::Down,
+                latency,
+                error: Some(e),
+            },
+        }
+    }
+}
+
+async fn database(state: &AppState) -> Component {
+    let started = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, state.database.ping()).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(String::from("timed out")),
+    };
+    Component::from_check(started, result)
+}
+
+async fn redis(state: &AppState) -> Component {
+    let started = Instant::now();
+    let check = async {
+        let mut conn = state.redis.get().await?;
+        redis::cmd("PING").query_async::<_, String>(&mut conn).await
+    };
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result.map(|_| ()).map_err(|e| e.to_string()),
+        Err(_) => Err(String::from("timed out")),
+    };
+    Component::from_check(started, result)
+}
+
+/// Check every component at once.
+async fn report(state: &AppState) -> Report {
+    let (database, redis) = tokio::join!(database(state), redis(state));
+    let shutting_down = state.is_shutting_down();
+    let up = !shutting_down && database.status == Health::Up && redis.status == Health::Up;
+    Report {
+        status: if up { Health::Up } else { Health::Down },
+        shutting_down,
+        database,
+        redis,
+    }
+}
+
+/// Whether the server is alive. This succeeds as long as the server can answer at all, so
+/// that an outage of the database or Redis doesn't get it restarted for nothing, but still
+/// reports how each component is doing.
+pub async fn healthz(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Response::new(report(&state).await, StatusCode::OK)
+}
+
+/// Whether the server can take traffic: it's not shutting down, and the database and Redis
+/// both answered in time. Responds with `503 Service Unavailable` otherwise.
+pub async fn readyz(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let report = report(&state).await;
+    let code = match report.status {
+        Health::Up => StatusCode::OK,
+        Health::Down => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    Response::new(report, code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Health, Report};
+    use crate::server::{self, handlers::Response};
+    use reqwest::StatusCode;
+    use std::sync::Arc;
+    use test_utils::Client;
+
+    #[tokio::test]
+    async fn readiness() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let client = Client::new();
+        let resp: Response<Report> = client.get(&url, "/readyz").await;
+        assert_eq!(resp.message.status, Health::Up);
+        assert_eq!(resp.message.database.status, Health::Up);
+        assert_eq!(resp.message.redis.status, Health::Up);
+        // Every response carries the ID its request was traced with.
+        let resp = client.get_raw(&url, "/healthz").await;
+        assert!(resp.headers().contains_key("x-request-id"));
+        // Shutting down takes the server out of rotation without it being considered dead.
+        state.shut_down();
+        let resp = client.get_raw(&url, "/readyz").await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let resp: Response<Report> = resp.json().await.unwrap();
+        assert!(resp.message.shutting_down);
+        let resp = client.get_raw(&url, "/healthz").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        // Components that can't be reached are reported as down.
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open("redis://127.0.0.1:1").unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let resp = client.get_raw(&url, "/readyz").await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let resp: Response<Report> = resp.json().await.unwrap();
+        assert_eq!(resp.message.database.status, Health::Up);
+        assert_eq!(resp.message.redis.status, Health::Down);
+        assert!(resp.message.redis.error.is_some());
+    }
+}
@@ -1,15 +1,58 @@
-use crate::server::{
-    packet::{Event, EventData, EventKind, Packet},
-    state::AppState,
-    strings,
+use crate::{
+    server::{
+        bots, correspondence,
+        handlers::StringError,
+        helpers,
+        packet::{
+            self, relay, Channel, Event, EventKind, Request, ServerMessage, Snapshots, Subscriber,
+        },
+        presence::{self, Status},
+        projection::Viewer,
+        state::AppState,
+        strings,
+        summary::{self, Termination, Verdict},
+    },
+    Piece,
 };
 use axum::{
     extract::ws::{Message, WebSocket},
     http::StatusCode,
 };
 use futures::{SinkExt, StreamExt};
-use std::{sync::Arc, time::Duration};
-use tokio::sync::mpsc;
+use othello_api_types::{
+    board,
+    gateway::{COMPACT_BOARDS_VERSION, TAGGED_MESSAGES_VERSION},
+};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::Entry, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+use uuid::Uuid;
+
+/// How long (in seconds) the session of a dropped connection can be resumed for.
+const RESUME_TTL: u64 = 5 * 60;
+
+/// The outcome of a successful identify handshake.
+struct Identified {
+    user: Uuid,
+    version: u16,
+    /// The resume token issued to this connection.
+    token: String,
+    /// The resume token of a previous connection that the client asked to pick up.
+    resume: Option<String>,
+    snapshots: Snapshots,
+}
+
+/// What a dropped connection had joined, kept so that a new connection can pick it up.
+#[derive(Serialize, Deserialize)]
+struct Session {
+    user: Uuid,
+    games: Vec<Uuid>,
+}
 
 async fn send(socket: &mut (impl SinkExt<Message> + Unpin), resp: Event) {
     let text = serde_json::to_string(&resp).unwrap();
@@ -20,54 +63,469 @@ async fn authenticate(
     socket: &mut (impl SinkExt<Message> + Unpin),
     msg: &Message,
     state: &Arc<AppState>,
-) -> Option<()> {
-    match Packet::try_from(msg) {
+) -> Option<Identified> {
+    match Request::try_from(msg) {
         Ok(packet) => match packet.process(state, None).await.data() {
-            EventData::Ready => Some(()),
-            EventData::Error { message, code } => {
-                let resp = Event::error(message, StatusCode::from_u16(*code).unwrap());
-                send(socket, resp).await;
+            ServerMessage::Ready {
+                version, resume, ..
+            } => Some(Identified {
+                user: packet.user(state).await?,
+                version: *version,
+                token: resume.clone(),
+                resume: packet.resumes().map(String::from),
+                snapshots: packet.snapshots(),
+            }),
+            ServerMessage::Error(e) => {
+                send(socket, Event::from(e.clone())).await;
                 None
             }
             _ => panic!("packet processed by handler other than identify"),
         },
         Err(e) => {
-            let resp = Event::error(&e.to_string(), StatusCode::BAD_REQUEST);
+            let resp = packet::error(&e.to_string(), StatusCode::BAD_REQUEST);
             send(socket, resp).await;
             None
         }
     }
 }
 
+/// Keep the session of a dropped connection around so that a new connection can resume it,
+/// buffering the events of its games in the meantime.
+async fn suspend(state: &AppState, token: String, session: &Session) {
+    let Ok(mut conn) = state.redis.get().await else {
+        return;
+    };
+    let key = format!("live:session:{token}");
+    let value = serde_json::to_string(session).unwrap();
+    if conn
+        .set_ex::<_, _, ()>(&key, value, RESUME_TTL)
+        .await
+        .is_err()
+    {
+        return;
+    }
+    let (sender, mut receiver) = mpsc::channel::<Event>(16);
+    {
+        // Buffer whole boards, since the new connection may want them.
+        let subscriber = Subscriber::new(sender, Snapshots::Every);
+        for &game in &session.games {
+            if let Some(rx) = state.listen(Channel::Game(game)) {
+                let viewer = Viewer::of(state, game, Some(session.user));
+                subscriber.relay(Channel::Game(game), rx, viewer, None);
+            }
+        }
+    }
+    let owner = session.user.to_string();
+    let suspended = Arc::clone(&state.suspended);
+    // Which suspension of the session this is, so that its buffer only ever forgets itself.
+    let id = Uuid::now_v7();
+    let mut handles = state.suspended.lock().expect("mutex was poisoned");
+    let task = tokio::spawn({
+        let token = token.clone();
+        async move {
+            let buffer = format!("{key}:events");
+            let deadline = tokio::time::sleep(Duration::from_secs(RESUME_TTL));
+            tokio::pin!(deadline);
+            loop {
+                tokio::select! {
+                    () = &mut deadline => break,
+                    event = receiver.recv() => {
+                        let Some(event) = event else { break };
+                        // The client has no use for hearing about its own absence.
+                        let own = matches!(
+                            event.data(),
+                            ServerMessage::Presence { user, .. } if *user == owner
+                        );
+                        if own {
+                            continue;
+                        }
+                        let value = serde_json::to_string(&event).unwrap();
+                        let _ = conn.rpush::<_, _, ()>(&buffer, value).await;
+                        #[allow(clippy::cast_possible_wrap)] // RESUME_TTL <= i64::MAX
+                        let _ = conn.expire::<_, ()>(&buffer, RESUME_TTL as i64).await;
+                    }
+                }
+            }
+            let mut handles = suspended.lock().expect("mutex was poisoned");
+            if let Entry::Occupied(entry) = handles.entry(token) {
+                if entry.get().0 == id {
+                    entry.remove();
+                }
+            }
+        }
+    });
+    // Register the buffer under the same lock it's forgotten under, stopping any buffer the
+    // session already had.
+    match handles.entry(token) {
+        Entry::Occupied(mut entry) => entry.insert((id, task.abort_handle())).1.abort(),
+        Entry::Vacant(entry) => {
+            entry.insert((id, task.abort_handle()));
+        }
+    }
+}
+
+/// Pick up the session of a dropped connection, returning the games it had joined along with
+/// the events it missed since. Sessions can only be resumed by the user they belong to.
+async fn resume(state: &AppState, token: &str, user: Uuid) -> Option<(Vec<Uuid>, Vec<Event>)> {
+    let mut conn = state.redis.get().await.ok()?;
+    let key = format!("live:session:{token}");
+    let session: Option<String> = conn.get_del(&key).await.ok()?;
+    let session: Session = serde_json::from_str(&session?).ok()?;
+    if session.user != user {
+        return None;
+    }
+    // Stop buffering before collecting what was buffered.
+    let task = state
+        .suspended
+        .lock()
+        .expect("mutex was poisoned")
+        .remove(token);
+    if let Some((_, task)) = task {
+        task.abort();
+    }
+    let buffer = format!("{key}:events");
+    let missed: Vec<String> = conn.lrange(&buffer, 0, -1).await.unwrap_or_default();
+    let _ = conn.del::<_, ()>(&buffer).await;
+    let missed = missed
+        .iter()
+        .filter_map(|event| serde_json::from_str(event).ok())
+        .collect();
+    Some((session.games, missed))
+}
+
+/// Write an event as a connection speaking the specified version of the protocol reads it.
+/// Connections from before messages were tagged get them without their `type` (and messages
+/// with nothing else in them as `null`), and connections from before boards were compact get
+/// them as arrays of squares.
+fn encode(event: &Event, version: u16) -> String {
+    let mut value = serde_json::to_value(event).unwrap();
+    if version < TAGGED_MESSAGES_VERSION {
+        if let Some(data) = value.get_mut("d") {
+            if let Some(fields) = data.as_object_mut() {
+                fields.remove("type");
+                if fields.is_empty() {
+                    *data = serde_json::Value::Null;
+                }
+            }
+        }
+    }
+    if version < COMPACT_BOARDS_VERSION {
+        if let Some(board) = value.pointer_mut("/d/game/board") {
+            let squares = board
+                .as_str()
+                .and_then(|compact| board::decode(compact).and_then(board::from_bytes).ok());
+            if let Some(squares) = squares {
+                *board = serde_json::json!(squares);
+            }
+        }
+    }
+    value.to_string()
+}
+
+/// Forward messages from the mpsc channel to the websocket sink, pinging the client whenever
+/// the heartbeat interval elapses. The connection is closed after telling the client that the
+/// server is restarting.
+async fn write(
+    mut tx: impl SinkExt<Message> + Unpin,
+    mut receiver: mpsc::Receiver<Event>,
+    period: Duration,
+    version: u16,
+) {
+    let mut interval = tokio::time::interval(period);
+    loop {
+        let (msg, last) = tokio::select! {
+            resp = receiver.recv() => match resp {
+                Some(resp) => (
+                    Message::Text(encode(&resp, version)),
+                    matches!(resp.data(), ServerMessage::ServerRestarting),
+                ),
+                None => break,
+            },
+            _ = interval.tick() => (Message::Ping(Vec::new()), false),
+        };
+        if tx.send(msg).await.is_err() {
+            break;
+        }
+        if last {
+            let _ = tx.send(Message::Close(None)).await;
+            break;
+        }
+    }
+}
+
+/// Forfeit the specified games on behalf of a user who went away, unless they come back
+/// within the grace period.
+async fn forfeit_if_abandoned(state: Arc<AppState>, user: Uuid, games: Vec<Uuid>, since: Instant) {
+    tokio::time::sleep(state.grace).await;
+    if state.returned_since(user, since) {
+        return;
+    }
+    for id in games {
+        let Ok(metadata) = helpers::get_game(&state, &id.to_string()).await else {
+            continue;
+        };
+        // Correspondence games are played over days, so players are free to come and go.
+        if metadata.pending || metadata.ended || correspondence::is_correspondence(&metadata) {
+            continue;
+        }
+        let game = {
+            let games = state.games.lock().expect("mutex was poisoned");
+            let Some(game) = games.get(&id) else {
+                continue;
+            };
+            game.clone()
+        };
+        let piece = if helpers::black_white(&metadata).0 == Some(user) {
+            Piece::Black
+        } else {
+            Piece::White
+        };
+        let forfeit = Some(Verdict::Forfeit(piece, Termination::Abandonment));
+        if let Err(StringError(message, _)) =
+            summary::conclude(&state, &metadata, &game, forfeit, Some((user, piece))).await
+        {
+            tracing::error!(game = %id, "Failed to forfeit abandoned game: {message}");
+        }
+    }
+}
+
+/// The status a connection contributes to its user's presence.
+fn activity(joined: &HashSet<Uuid>, idle: bool) -> Status {
+    if idle {
+        Status::Idle
+    } else if joined.is_empty() {
+        Status::Online
+    } else {
+        Status::InGame
+    }
+}
+
+/// Catch a resuming client up on what it missed, then rejoin its games, returning the games
+/// that could be rejoined.
+async fn catch_up(
+    state: &AppState,
+    user: Uuid,
+    subscriber: &Subscriber,
+    games: Vec<Uuid>,
+    missed: Vec<Event>,
+) -> HashSet<Uuid> {
+    for event in missed {
+        let _ = subscriber.sender.send(event).await;
+    }
+    let mut joined = HashSet::new();
+    for game in games {
+        if let Ok(update) = packet::enter(state, game, user, subscriber) {
+            let _ = subscriber.sender.send(update).await;
+            joined.insert(game);
+            state.announce(game, user, true);
+        }
+    }
+    joined
+}
+
+/// Start serving an identified connection: forward the events addressed to its user, pick up
+/// the session of the connection it resumes (if any) and let the client know it's ready.
+/// Returns the games the connection has joined.
+async fn open(
+    state: &Arc<AppState>,
+    identified: &Identified,
+    subscriber: &Subscriber,
+) -> HashSet<Uuid> {
+    let user = identified.user;
+    // Forward events addressed to the authenticated user until the connection closes. They
+    // aren't seen from a seat at any game, so nothing in them is hidden.
+    let events = state.subscribe(user);
+    let forward = relay(events, subscriber.clone(), Viewer::Spectator, None);
+    let cleanup = Arc::clone(state);
+    tokio::spawn(async move {
+        forward.await;
+        // The relay's receiver is gone by now, so the user's channel goes too if it was the
+        // last one listening.
+        cleanup.unsubscribe(user);
+    });
+    state.connect(user);
+    let resumed = match &identified.resume {
+        Some(previous) => resume(state, previous, user).await,
+        None => None,
+    };
+    // Let the client know that they are ready to receive messages.
+    let _ = subscriber
+        .sender
+        .send(Event::new(
+            EventKind::Ready,
+            ServerMessage::Ready {
+                version: identified.version,
+                resume: identified.token.clone(),
+                resumed: resumed.is_some(),
+            },
+        ))
+        .await;
+    match resumed {
+        Some((games, missed)) => catch_up(state, user, subscriber, games, missed).await,
+        None => HashSet::new(),
+    }
+}
+
+/// Act on a message the client sent, returning the reply. Games the connection starts or stops
+/// playing through the message are added to or taken out of `joined`.
+async fn handle(
+    state: &Arc<AppState>,
+    user: Uuid,
+    bot: bool,
+    subscriber: &Subscriber,
+    joined: &mut HashSet<Uuid>,
+    msg: &Message,
+) -> Event {
+    let limited = bot
+        .then(|| bots::ensure_within_limit(state, user))
+        .and_then(Result::err);
+    match (limited, Request::try_from(msg)) {
+        (Some(StringError(message, code)), _) => packet::error(&message, code),
+        (None, Ok(packet)) => {
+            let resp = packet.process(state, Some(subscriber.clone())).await;
+            if let (Some(game), ServerMessage::GameUpdate { .. }) = (packet.joins(), resp.data()) {
+                // Spectators are only watching, so leaving doesn't forfeit anything.
+                if state.seat(game, user).is_some() {
+                    joined.insert(game);
+                    state.announce(game, user, true);
+                }
+            }
+            // Games the connection stops following aren't rejoined on resuming, and aren't
+            // forfeited when it goes away.
+            if let (Some(game), ServerMessage::Ack) = (packet.parts(), resp.data()) {
+                joined.remove(&game);
+            }
+            resp
+        }
+        (None, Err(e)) => packet::error(&e.to_string(), StatusCode::BAD_REQUEST),
+    }
+}
+
+/// Serve an identified connection until it closes or goes stale.
+async fn serve(socket: WebSocket, state: &Arc<AppState>, identified: Identified) {
+    let user = identified.user;
+    tracing::Span::current().record("user", tracing::field::display(user));
+    // Bots are limited in how many messages they can send.
+    let bot = helpers::get_user(state, &user.to_string(), false)
+        .await
+        .is_ok_and(|member| member.bot);
+    let heartbeat = state.heartbeat;
+    let (tx, mut rx) = socket.split();
+    let (sender, receiver) = mpsc::channel::<Event>(16);
+    let mut writer = tokio::spawn(write(tx, receiver, heartbeat.interval, identified.version));
+    let subscriber = Subscriber::new(sender.clone(), identified.snapshots);
+    let mut joined = open(state, &identified, &subscriber).await;
+    // Track what this connection contributes to the user's presence.
+    let connection = Uuid::now_v7();
+    let mut active = Instant::now();
+    let mut status = activity(&joined, false);
+    presence::update(state, user, connection, Some(status)).await;
+    // Listen for incoming messages from the client, giving up on the connection if
+    // nothing (not even a pong) arrives within the heartbeat timeout or the server starts
+    // shutting down.
+    let mut restarting = false;
+    loop {
+        let next = tokio::select! {
+            next = tokio::time::timeout(heartbeat.timeout, rx.next()) => next,
+            () = state.shutting_down() => {
+                restarting = true;
+                break;
+            }
+        };
+        let msg = match next {
+            Ok(Some(Ok(msg))) => msg,
+            Ok(_) => break,
+            Err(_) => {
+                tracing::info!("Reaping stale connection");
+                break;
+            }
+        };
+        presence::refresh(state, user).await;
+        match msg {
+            Message::Ping(_) | Message::Pong(_) => {}
+            Message::Close(_) => break,
+            msg => {
+                active = Instant::now();
+                let resp = handle(state, user, bot, &subscriber, &mut joined, &msg).await;
+                let _ = sender.send(resp).await;
+            }
+        }
+        let current = activity(&joined, active.elapsed() >= state.idle);
+        if current != status {
+            status = current;
+            presence::update(state, user, connection, Some(status)).await;
+        }
+    }
+    // Their session is suspended below as for any other dropped connection, so they can
+    // resume it once the server is back.
+    if restarting {
+        goodbye(&sender, &mut writer, &mut rx).await;
+    }
+    // Release everything held for this connection. Closing the channel stops the
+    // tasks forwarding room updates and notifications to it.
+    writer.abort();
+    subscriber.stop_all();
+    drop(sender);
+    release(state, user, identified.token, connection, joined).await;
+}
+
+/// Tell the client that the server is restarting and close the connection, waiting (briefly)
+/// for the writer to get that out and for the client to acknowledge the close. Anything the
+/// client sends until then has to be read, or the connection is reset before the client gets
+/// to read why it's closing.
+async fn goodbye(
+    sender: &mpsc::Sender<Event>,
+    writer: &mut JoinHandle<()>,
+    rx: &mut (impl StreamExt<Item = Result<Message, axum::Error>> + Unpin),
+) {
+    let restart = Event::new(EventKind::ServerRestarting, ServerMessage::ServerRestarting);
+    if sender.send(restart).await.is_err() {
+        return;
+    }
+    let _ = tokio::time::timeout(Duration::from_secs(1), async {
+        let _ = writer.await;
+        while let Some(Ok(msg)) = rx.next().await {
+            if let Message::Close(_) = msg {
+                break;
+            }
+        }
+    })
+    .await;
+}
+
+/// Let go of a closed connection: suspend its session, and if it was the user's last one,
+/// start the clock on forfeiting the games they were playing.
+async fn release(
+    state: &Arc<AppState>,
+    user: Uuid,
+    token: String,
+    connection: Uuid,
+    joined: HashSet<Uuid>,
+) {
+    if !joined.is_empty() {
+        let games = joined.iter().copied().collect();
+        suspend(state, token, &Session { user, games }).await;
+    }
+    presence::update(state, user, connection, None).await;
+    if state.disconnect(user) {
+        for &game in &joined {
+            state.announce(game, user, false);
+        }
+        if !joined.is_empty() {
+            let since = state.depart(user);
+            let games = joined.into_iter().collect();
+            tokio::spawn(forfeit_if_abandoned(Arc::clone(state), user, games, since));
+        }
+    }
+}
+
 pub async fn callback(mut socket: WebSocket, state: Arc<AppState>) {
     let duration = Duration::from_millis(500);
     let req = tokio::time::timeout(duration, socket.recv()).await;
     match req {
         Ok(Some(Ok(msg))) => {
-            if let Some(()) = authenticate(&mut socket, &msg, &state).await {
-                let (mut tx, mut rx) = socket.split();
-                let (sender, mut receiver) = mpsc::channel::<Event>(16);
-                // Forward messages from the mpsc channel to the websocket sink.
-                tokio::spawn(async move {
-                    while let Some(resp) = receiver.recv().await {
-                        let text = serde_json::to_string(&resp).unwrap();
-                        if tx.send(Message::Text(text)).await.is_err() {
-                            break;
-                        }
-                    }
-                });
-                // Let the client know that they are ready to receive messages.
-                let _ = sender
-                    .send(Event::new(EventKind::Ready, EventData::Ready))
-                    .await;
-                // Listen for incoming messages from the client.
-                while let Some(Ok(msg)) = rx.next().await {
-                    let resp = match Packet::try_from(&msg) {
-                        Ok(packet) => packet.process(&state, Some(sender.clone())).await,
-                        Err(e) => Event::error(&e.to_string(), StatusCode::BAD_REQUEST),
-                    };
-                    let _ = sender.send(resp).await;
-                }
+            if let Some(identified) = authenticate(&mut socket, &msg, &state).await {
+                serve(socket, &state, identified).await;
             } else {
                 let _ = socket.close().await;
             }
@@ -78,10 +536,706 @@ pub async fn callback(mut socket: WebSocket, state: Arc<AppState>) {
         Err(_) => {
             let () = send(
                 &mut socket,
-                Event::error(strings::IDENTIFY_TIMEOUT, StatusCode::REQUEST_TIMEOUT),
+                packet::error(strings::IDENTIFY_TIMEOUT, StatusCode::REQUEST_TIMEOUT),
             )
             .await;
             let _ = socket.close().await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::server::{
+        self,
+        handlers::Response,
+        packet::{Event, EventKind, ServerMessage, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+        strings, Heartbeat,
+    };
+    use serde_json::json;
+    use std::time::Duration;
+    use test_utils::{function, Client, Map, Socket};
+
+    #[test]
+    fn legacy_boards() {
+        let event = Event::new(
+            EventKind::GameUpdate,
+            ServerMessage::GameUpdate {
+                game: crate::Game::new(),
+            },
+        );
+        let compact: serde_json::Value =
+            serde_json::from_str(&super::encode(&event, PROTOCOL_VERSION)).unwrap();
+        assert_eq!(compact["d"]["game"]["board"], "AAAACBAAAAAAAAAQCAAAAA==");
+        // Older connections get every square, as they always have.
+        let legacy: serde_json::Value =
+            serde_json::from_str(&super::encode(&event, MIN_PROTOCOL_VERSION)).unwrap();
+        let squares = legacy["d"]["game"]["board"].as_array().unwrap();
+        assert_eq!(squares.len(), 64);
+        assert_eq!(squares[27], "White");
+        assert!(squares[0].is_null());
+    }
+
+    #[test]
+    fn untagged_messages() {
+        let event = Event::new(EventKind::Ack, ServerMessage::Ack);
+        let tagged: serde_json::Value =
+            serde_json::from_str(&super::encode(&event, PROTOCOL_VERSION)).unwrap();
+        assert_eq!(tagged["d"]["type"], "Ack");
+        // The first version of the protocol didn't tag messages.
+        let untagged: serde_json::Value =
+            serde_json::from_str(&super::encode(&event, MIN_PROTOCOL_VERSION)).unwrap();
+        assert!(untagged["d"].is_null());
+        let event = Event::new(
+            EventKind::Ready,
+            ServerMessage::Ready {
+                version: 1,
+                resume: String::new(),
+                resumed: false,
+            },
+        );
+        let untagged: serde_json::Value =
+            serde_json::from_str(&super::encode(&event, MIN_PROTOCOL_VERSION)).unwrap();
+        assert_eq!(untagged["d"]["version"], 1);
+        assert!(untagged["d"].get("type").is_none());
+    }
+
+    #[tokio::test]
+    async fn version_negotiation() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let client = Client::authenticated(&[&function!()], &url, true).await;
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        // Clients that don't state a version speak the oldest one.
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        let ready = socket.recv_op(2).await;
+        assert_eq!(ready["d"]["version"], MIN_PROTOCOL_VERSION);
+        // Clients newer than the server are negotiated down to the newest version it speaks.
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify", "version": 99 }, "t": token }))
+            .await;
+        let ready = socket.recv_op(2).await;
+        assert_eq!(ready["d"]["type"], "Ready");
+        assert_eq!(ready["d"]["version"], PROTOCOL_VERSION);
+        // Clients older than the server are turned away.
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify", "version": 0 }, "t": token }))
+            .await;
+        let error = socket.recv_op(6).await;
+        assert_eq!(error["d"]["message"], strings::UNSUPPORTED_PROTOCOL_VERSION);
+    }
+
+    #[tokio::test]
+    async fn stale_connection() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(
+            server::AppState::new(database, redis).with_heartbeat(Heartbeat {
+                interval: Duration::from_millis(100),
+                timeout: Duration::from_millis(300),
+            }),
+        );
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let resp: Response<Map> = client.get(&url, &format!("/game/{id}")).await;
+        let guest_id = resp.message["guest"].clone();
+        // Both players join the game over the websocket.
+        let mut sockets = Vec::new();
+        for client in [&client, &other] {
+            let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+            let mut socket = Socket::connect(&url).await;
+            socket
+                .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+                .await;
+            socket.recv_op(2).await;
+            socket
+                .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+                .await;
+            socket.recv_op(4).await;
+            sockets.push(socket);
+        }
+        let _guest_socket = sockets.pop().unwrap();
+        let mut host_socket = sockets.pop().unwrap();
+        // The host answers pings while waiting, so only the guest's presence changes.
+        let mut presence = Vec::new();
+        while presence.len() < 2 {
+            let event = tokio::time::timeout(Duration::from_secs(5), host_socket.recv_op(9))
+                .await
+                .unwrap();
+            if event["d"]["user"] == guest_id {
+                presence.push(event["d"]["online"].clone());
+            }
+        }
+        // The guest stops answering pings, so the server gives up on them.
+        assert_eq!(presence, [true, false]);
+    }
+
+    #[tokio::test]
+    async fn restart() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({
+                "op": 6,
+                "d": { "type": "Identify", "version": PROTOCOL_VERSION },
+                "t": token,
+            }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        socket.recv_op(4).await;
+        // Connections are told the server is restarting before being closed.
+        state.shut_down();
+        let restarting = tokio::time::timeout(Duration::from_secs(5), socket.recv_op(14))
+            .await
+            .unwrap();
+        assert_eq!(restarting["d"]["type"], "ServerRestarting");
+        tokio::time::timeout(Duration::from_secs(5), socket.closed())
+            .await
+            .unwrap();
+        // The game is saved so that it can be restored when the server comes back.
+        assert!(state.persist_games().await.unwrap() >= 1);
+        let mut conn = state.redis.get().await.unwrap();
+        assert!(
+            redis::AsyncCommands::exists::<_, bool>(&mut conn, format!("game:{id}"))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn recovery() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        socket.recv_op(4).await;
+        let mut game = crate::Game::new();
+        let piece = game.turn();
+        let (x, y) = game.moves(piece)[0];
+        game.place(x, y, piece).unwrap();
+        socket
+            .send(json!({
+                "op": 2,
+                "d": { "type": "Place", "id": id, "x": x, "y": y, "piece": piece },
+                "t": token,
+            }))
+            .await;
+        socket.recv_op(1).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        // The server restarts, and the cache was lost along the way.
+        let mut conn = state.redis.get().await.unwrap();
+        redis::AsyncCommands::del::<_, ()>(&mut conn, format!("game:{id}"))
+            .await
+            .unwrap();
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let restarted = Arc::new(
+            server::AppState::new(database, redis).with_stall_timeout(Duration::from_millis(200)),
+        );
+        server::restore_active_games(&restarted).await.unwrap();
+        let uuid = id.parse().unwrap();
+        let restored = restarted.games.lock().unwrap().get(&uuid).cloned().unwrap();
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&game).unwrap()
+        );
+        // The clock on the turn kept running while the server was down.
+        assert!(restarted.stalled(uuid));
+    }
+
+    #[tokio::test]
+    async fn snapshots() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        // The host wants the whole board every other move, and the guest never does.
+        let mut sockets = Vec::new();
+        for (client, snapshots) in [
+            (&client, json!({ "interval": 2 })),
+            (&other, json!("deltas")),
+        ] {
+            let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+            let mut socket = Socket::connect(&url).await;
+            socket
+                .send(json!({
+                    "op": 6,
+                    "d": { "type": "Identify", "snapshots": snapshots },
+                    "t": token,
+                }))
+                .await;
+            socket.recv_op(2).await;
+            socket
+                .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+                .await;
+            socket.recv_op(4).await;
+            sockets.push((socket, token));
+        }
+        let mut game = crate::Game::new();
+        for ply in 0..2 {
+            // The host plays black and moves first.
+            let piece = game.turn();
+            let (x, y) = game.moves(piece)[0];
+            game.place(x, y, piece).unwrap();
+            let (socket, token) = &mut sockets[ply];
+            socket
+                .send(json!({
+                    "op": 2,
+                    "d": { "type": "Place", "id": id, "x": x, "y": y, "piece": piece },
+                    "t": token,
+                }))
+                .await;
+            if ply == 0 {
+                let delta = sockets[0].0.recv_op(13).await;
+                assert!(delta["d"]["changed"]
+                    .as_array()
+                    .unwrap()
+                    .contains(&json!([x, y, "Black"])));
+            } else {
+                let update = sockets[0].0.recv_op(4).await;
+                assert_eq!(update["d"]["game"]["history"].as_array().unwrap().len(), 2);
+            }
+            let delta = sockets[1].0.recv_op(13).await;
+            assert_eq!(delta["d"]["turn"], json!(game.turn()));
+        }
+    }
+
+    #[tokio::test]
+    async fn hosted_opponent() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let client = Client::authenticated(&[&function!()], &url, true).await;
+        let resp: Response<Map> = client
+            .post(&url, "/games/bot", json!({ "difficulty": "easy" }))
+            .await;
+        assert_eq!(resp.message["opponent"], "easy");
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        socket.recv_op(4).await;
+        socket
+            .send(json!({
+                "op": 2,
+                "d": { "type": "Place", "id": id, "x": 5, "y": 4, "piece": "Black" },
+                "t": token,
+            }))
+            .await;
+        // The opponent replies on its own once it has found a move.
+        let update = socket.recv_op(4).await;
+        assert_eq!(update["d"]["game"]["history"].as_array().unwrap().len(), 1);
+        let update = socket.recv_op(4).await;
+        assert_eq!(update["d"]["game"]["history"].as_array().unwrap().len(), 2);
+        assert_eq!(update["d"]["game"]["turn"], "Black");
+    }
+
+    #[tokio::test]
+    async fn multiplexing() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        let mut games = Vec::new();
+        for _ in 0..2 {
+            let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+            let id = resp.message["id"].as_str().unwrap().to_string();
+            other
+                .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+                .await;
+            games.push(id);
+        }
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        // One game is joined and the other subscribed to, on the same connection.
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": games[0] }, "t": token }))
+            .await;
+        assert_eq!(socket.recv_op(4).await["g"], games[0].as_str());
+        let subscribe = json!({ "type": "Subscribe", "channel": { "game": games[1] } });
+        socket
+            .send(json!({ "op": 10, "d": subscribe, "t": token }))
+            .await;
+        assert_eq!(socket.recv_op(4).await["g"], games[1].as_str());
+        // Events say which game they come from.
+        socket
+            .send(json!({
+                "op": 2,
+                "d": { "type": "Place", "id": games[1], "x": 5, "y": 4, "piece": "Black" },
+                "t": token,
+            }))
+            .await;
+        let update = socket.recv_op(4).await;
+        assert_eq!(update["g"], games[1].as_str());
+        assert_eq!(update["d"]["game"]["history"].as_array().unwrap().len(), 1);
+        // Unsubscribing from a game stops its events without leaving it, and joining a game
+        // again doesn't relay it twice.
+        let unsubscribe = json!({ "type": "Unsubscribe", "channel": { "game": games[1] } });
+        socket
+            .send(json!({ "op": 11, "d": unsubscribe, "t": token }))
+            .await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": games[0] }, "t": token }))
+            .await;
+        assert_eq!(socket.recv_op(4).await["g"], games[0].as_str());
+        let guest_token = other.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut guest_socket = Socket::connect(&url).await;
+        guest_socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": guest_token }))
+            .await;
+        guest_socket.recv_op(2).await;
+        guest_socket
+            .send(json!({ "op": 8, "d": { "type": "Resign", "id": games[1] }, "t": guest_token }))
+            .await;
+        guest_socket.recv_op(1).await;
+        socket
+            .send(json!({
+                "op": 2,
+                "d": { "type": "Place", "id": games[0], "x": 5, "y": 4, "piece": "Black" },
+                "t": token,
+            }))
+            .await;
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), socket.recv())
+                .await
+                .unwrap();
+            assert_ne!(event["g"], games[1].as_str());
+            if event["op"] == 4 {
+                assert_eq!(event["g"], games[0].as_str());
+                assert_eq!(event["d"]["game"]["history"].as_array().unwrap().len(), 1);
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn sequence_numbers() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        // The game's state is sent to the connection alone, so it isn't numbered, while the
+        // events of the game's room are numbered one after another.
+        assert!(socket.recv_op(4).await.get("s").is_none());
+        assert_eq!(socket.recv_op(9).await["s"], 1);
+        socket
+            .send(json!({
+                "op": 2,
+                "d": { "type": "Place", "id": id, "x": 5, "y": 4, "piece": "Black" },
+                "t": token,
+            }))
+            .await;
+        assert_eq!(socket.recv_op(4).await["s"], 2);
+        // Events are kept in the background.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // A connection rejoining after the first event catches up on the rest before getting
+        // the game's state.
+        let mut rejoined = Socket::connect(&url).await;
+        rejoined
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        rejoined.recv_op(2).await;
+        rejoined
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id, "since": 1 }, "t": token }))
+            .await;
+        let missed = rejoined.recv_op(4).await;
+        assert_eq!(missed["s"], 2);
+        assert_eq!(missed["g"], id.as_str());
+        assert_eq!(missed["d"]["game"]["history"].as_array().unwrap().len(), 1);
+        assert!(rejoined.recv_op(4).await.get("s").is_none());
+        assert_eq!(rejoined.recv_op(9).await["s"], 3);
+    }
+
+    #[tokio::test]
+    async fn resume() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let resp: Response<Map> = client.get(&url, &format!("/game/{id}")).await;
+        let host_id = resp.message["host"].clone();
+        // Both players join the game over the websocket.
+        let mut sockets = Vec::new();
+        let mut resume = Vec::new();
+        for client in [&client, &other] {
+            let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+            let mut socket = Socket::connect(&url).await;
+            socket
+                .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+                .await;
+            let ready = socket.recv_op(2).await;
+            assert_eq!(ready["d"]["resumed"], false);
+            resume.push(ready["d"]["resume"].clone());
+            socket
+                .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+                .await;
+            socket.recv_op(4).await;
+            sockets.push(socket);
+        }
+        let mut guest_socket = sockets.pop().unwrap();
+        // The host's connection drops, and the guest resigns while the host is away.
+        drop(sockets);
+        loop {
+            let event = guest_socket.recv_op(9).await;
+            if event["d"]["user"] == host_id && event["d"]["online"] == false {
+                break;
+            }
+        }
+        let token = other.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        guest_socket
+            .send(json!({ "op": 8, "d": { "type": "Resign", "id": id }, "t": token }))
+            .await;
+        guest_socket.recv_op(7).await;
+        // The host reconnects and is caught up on what they missed.
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify", "resume": resume[0] }, "t": token }))
+            .await;
+        let ready = socket.recv_op(2).await;
+        assert_eq!(ready["d"]["resumed"], true);
+        assert_ne!(ready["d"]["resume"], resume[0]);
+        let missed = socket.recv().await;
+        assert_eq!(missed["op"], 7);
+        assert_eq!(missed["d"]["termination"], "resignation");
+        let update = socket.recv().await;
+        assert_eq!(update["op"], 4);
+        // Resume tokens can only be used once, and only by the user they were issued to.
+        for (client, resume) in [(&client, &resume[0]), (&other, &resume[0])] {
+            let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+            let mut socket = Socket::connect(&url).await;
+            socket
+                .send(json!({ "op": 6, "d": { "type": "Identify", "resume": resume }, "t": token }))
+                .await;
+            let ready = socket.recv_op(2).await;
+            assert_eq!(ready["d"]["resumed"], false);
+        }
+    }
+
+    #[tokio::test]
+    async fn friend_presence() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = server::AppState::new(database, redis)
+            .with_heartbeat(Heartbeat {
+                interval: Duration::from_millis(100),
+                timeout: Duration::from_secs(5),
+            })
+            .with_idle_timeout(Duration::from_millis(300));
+        let url = test_utils::init(crate::server::app(Arc::new(state))).await;
+        let first = format!("{}::1", function!());
+        let second = format!("{}::2", function!());
+        let client = Client::authenticated(&[&first, &second], &url, true).await;
+        client
+            .post::<_, Map>(&url, &format!("/users/{second}/friend"), json!({}))
+            .await;
+        let other = Client::authenticated(&[&second], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/friends/{first}/accept"), json!({}))
+            .await;
+        let resp: Response<Map> = other.get(&url, "/@me").await;
+        let id = resp.message["id"].clone();
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/friends").await;
+        assert_eq!(resp.message[0]["user"]["presence"], "offline");
+        // Both friends connect, and the first hears about the second coming online.
+        let mut sockets = Vec::new();
+        for client in [&client, &other] {
+            let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+            let mut socket = Socket::connect(&url).await;
+            socket
+                .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+                .await;
+            socket.recv_op(2).await;
+            sockets.push(socket);
+        }
+        let mut second_socket = sockets.pop().unwrap();
+        let mut first_socket = sockets.pop().unwrap();
+        let event = first_socket.recv_op(10).await;
+        assert_eq!(event["d"]["user"], id);
+        assert_eq!(event["d"]["status"], "online");
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/friends").await;
+        assert_eq!(resp.message[0]["user"]["presence"], "online");
+        // The second keeps answering pings but does nothing else, so they go idle.
+        let reader = tokio::spawn(async move {
+            loop {
+                second_socket.recv().await;
+            }
+        });
+        let event = first_socket.recv_op(10).await;
+        assert_eq!(event["d"]["status"], "idle");
+        // Once the second disconnects, they're offline.
+        reader.abort();
+        let event = first_socket.recv_op(10).await;
+        assert_eq!(event["d"]["status"], "offline");
+    }
+
+    #[tokio::test]
+    async fn abandonment() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state =
+            server::AppState::new(database, redis).with_grace_period(Duration::from_millis(300));
+        let url = test_utils::init(crate::server::app(Arc::new(state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let mut sockets = Vec::new();
+        for client in [&client, &other] {
+            let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+            let mut socket = Socket::connect(&url).await;
+            socket
+                .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+                .await;
+            socket.recv_op(2).await;
+            socket
+                .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+                .await;
+            socket.recv_op(4).await;
+            sockets.push(socket);
+        }
+        let mut guest_socket = sockets.pop().unwrap();
+        // The host leaves and doesn't come back, so they forfeit the game.
+        drop(sockets);
+        let event = tokio::time::timeout(Duration::from_secs(5), guest_socket.recv_op(7))
+            .await
+            .unwrap();
+        assert_eq!(event["d"]["termination"], "abandonment");
+        assert_eq!(event["d"]["result"], "white");
+        assert_eq!(event["d"]["winner"], guest);
+        let resp: Response<Map> = client.get(&url, &format!("/game/{id}")).await;
+        assert_eq!(resp.message["ended"], true);
+        assert_eq!(resp.message["result"]["termination"], "abandonment");
+    }
+}
@@ -0,0 +1,309 @@
+//! The lobby: challenges anyone can take up, for playing without matchmaking or friends.
+//! Connections subscribed to the lobby channel hear about challenges as they're opened and as
+//! they leave the lobby.
+
+use super::{create::started, user_summaries, user_summary, StringError};
+use crate::server::{
+    conduct, create_in_memory_game,
+    entities::{
+        block::Column as BlockColumn,
+        open_challenge::{self, Column},
+        prelude::{Block, OpenChallenge as OpenChallengeModel},
+    },
+    extractors::User,
+    helpers,
+    links::GameLinks,
+    notifications::{self, Kind},
+    packet::{Event, EventKind, ServerMessage},
+    pagination::Pagination,
+    state::AppState,
+    strings,
+    validation::{Valid, Validate, Validator},
+    webhooks,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use othello_api_types::{ChallengeRequest, OpenChallenge};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder,
+    TransactionTrait,
+};
+use serde_json::json;
+use std::{str::FromStr, sync::Arc};
+use uuid::Uuid;
+
+impl Validate for ChallengeRequest {
+    fn validate(&self, v: &mut Validator) {
+        for (field, e) in self.settings.errors() {
+            v.reject(&format!("settings.{field}"), e);
+        }
+    }
+}
+
+fn not_found() -> StringError {
+    StringError(strings::CHALLENGE_NOT_FOUND.into(), StatusCode::NOT_FOUND)
+}
+
+/// Tell the connections watching the lobby that the specified challenge has left it.
+fn removed(state: &AppState, id: Uuid) {
+    state.update_lobby(Event::new(
+        EventKind::LobbyChallengeRemove,
+        ServerMessage::LobbyChallengeRemove { id: id.to_string() },
+    ));
+}
+
+/// List the challenges open in the lobby, newest first. Challenges from users on either side
+/// of a block with the current user are left out.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    pagination: Pagination,
+) -> Result<impl IntoResponse, Response> {
+    let blocks = Block::find()
+        .filter(
+            BlockColumn::Blocker
+                .eq(user.id)
+                .or(BlockColumn::Blocked.eq(user.id)),
+        )
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    let hidden = blocks.iter().map(|block| {
+        if block.blocker == user.id {
+            block.blocked
+        } else {
+            block.blocker
+        }
+    });
+    let query = OpenChallengeModel::find()
+        .filter(Column::Host.is_not_in(hidden))
+        .order_by(Column::Id, pagination.order.into());
+    let (challenges, page) = pagination.fetch(state.database.as_ref(), query).await?;
+    let hosts = helpers::get_users_by_ids(&state, challenges.iter().map(|c| c.host)).await?;
+    let mut summaries = user_summaries(&state, hosts.values()).await;
+    let challenges: Vec<_> = challenges
+        .into_iter()
+        .filter_map(|challenge| {
+            let host = summaries.remove(&challenge.host)?;
+            Some(OpenChallenge {
+                id: challenge.id,
+                host,
+                settings: serde_json::from_value(challenge.settings).unwrap_or_default(),
+                created_at: challenge.created_at,
+            })
+        })
+        .collect();
+    Ok(super::Response::paginated(challenges, page, StatusCode::OK))
+}
+
+/// Open a challenge in the lobby, for anyone to take up. Each user can only have one challenge
+/// open at a time.
+pub async fn open(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Valid(body): Valid<ChallengeRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let host = helpers::get_user(&state, &user.username, true).await?;
+    conduct::ensure_can_play(&state, host.id, &body.settings).await?;
+    let existing = OpenChallengeModel::find()
+        .filter(Column::Host.eq(host.id))
+        .one(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    if existing.is_some() {
+        return Err(
+            StringError(strings::CHALLENGE_ALREADY_OPEN.into(), StatusCode::CONFLICT)
+                .into_response(),
+        );
+    }
+    let model = open_challenge::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        host: ActiveValue::set(host.id),
+        settings: ActiveValue::set(json!(body.settings)),
+        created_at: ActiveValue::set(Utc::now().fixed_offset()),
+    }
+    .insert(state.database.as_ref())
+    .await
+    .map_err(StringError::from)?;
+    let challenge = OpenChallenge {
+        id: model.id,
+        host: user_summary(&state, &host).await,
+        settings: body.settings,
+        created_at: model.created_at,
+    };
+    state.update_lobby(Event::new(
+        EventKind::LobbyChallengeCreate,
+        ServerMessage::LobbyChallengeCreate {
+            challenge: challenge.clone(),
+        },
+    ));
+    Ok(super::Response::new(challenge, StatusCode::CREATED))
+}
+
+/// Withdraw the current user's challenge from the lobby.
+pub async fn withdraw(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    user: User,
+) -> Result<impl IntoResponse, Response> {
+    let id = Uuid::from_str(&id).map_err(|_| not_found())?;
+    let deleted = OpenChallengeModel::delete_many()
+        .filter(Column::Id.eq(id))
+        .filter(Column::Host.eq(user.id))
+        .exec(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    if deleted.rows_affected == 0 {
+        return Err(not_found().into_response());
+    }
+    removed(&state, id);
+    Ok(super::Response::new(json!({}), StatusCode::OK))
+}
+
+/// Take up the specified challenge, which starts the game straight away. Colours are settled
+/// as they are when an invite is accepted.
+pub async fn accept(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    user: User,
+) -> Result<impl IntoResponse, Response> {
+    let id = Uuid::from_str(&id).map_err(|_| not_found())?;
+    let challenge = OpenChallengeModel::find_by_id(id)
+        .one(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?
+        .ok_or_else(not_found)?;
+    let guest = helpers::get_user(&state, &user.username, true).await?;
+    if guest.id == challenge.host {
+        return Err(
+            StringError(strings::GAME_SELF.to_string(), StatusCode::BAD_REQUEST).into_response(),
+        );
+    }
+    helpers::ensure_not_blocked(&state, challenge.host, guest.id).await?;
+    let settings = serde_json::from_value(challenge.settings).unwrap_or_default();
+    conduct::ensure_can_play(&state, guest.id, &settings).await?;
+    let host = helpers::get_user(&state, &challenge.host.to_string(), false).await?;
+    // Only the first player to get this far plays: whoever takes the challenge out of the
+    // lobby gets the game.
+    let txn = state.database.begin().await.map_err(StringError::from)?;
+    let deleted = OpenChallengeModel::delete_by_id(id)
+        .exec(&txn)
+        .await
+        .map_err(StringError::from)?;
+    if deleted.rows_affected == 0 {
+        return Err(not_found().into_response());
+    }
+    let model = started(host.id, guest.id, settings)
+        .insert(&txn)
+        .await
+        .map_err(StringError::from)?;
+    txn.commit().await.map_err(StringError::from)?;
+    removed(&state, id);
+    create_in_memory_game(&state, &model).await?;
+    webhooks::game_started(&state, &model).await;
+    notifications::send(
+        &state,
+        host.id,
+        Kind::GameJoin,
+        json!({ "game": model.id, "guest": guest.username, "challenge": id }),
+    )
+    .await;
+    Ok(super::Response::new(
+        json!({
+            "id": model.id,
+            "host": model.host,
+            "guest": model.guest,
+            "host_color": helpers::host_color(&model),
+            "pending": false,
+            "ended": false,
+            "settings": settings,
+            "links": GameLinks::new(model.id),
+        }),
+        StatusCode::CREATED,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{
+        self,
+        handlers::{ApiError, Response},
+        strings,
+    };
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use std::{sync::Arc, time::Duration};
+    use test_utils::{function, Client, Map, Socket};
+
+    #[tokio::test]
+    async fn lobby() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let watcher = format!("{host}::watcher");
+        let client = Client::authenticated(&[&host, &guest, &watcher], &url, true).await;
+        // Watch the lobby from another user's connection.
+        let other = Client::authenticated(&[&watcher], &url, false).await;
+        let token = other.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 10, "d": { "type": "Subscribe", "channel": "lobby" }, "t": token }))
+            .await;
+        socket.recv_op(1).await;
+        let resp: Response<Map> = client.post(&url, "/lobby", json!({})).await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        assert_eq!(resp.message["host"]["username"], host);
+        let event = tokio::time::timeout(Duration::from_secs(5), socket.recv_op(19))
+            .await
+            .unwrap();
+        assert_eq!(event["d"]["challenge"]["id"], id.as_str());
+        // Only one challenge can be open at a time.
+        let resp: ApiError = client.post(&url, "/lobby", json!({})).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let resp: Response<Vec<Map>> = other.get(&url, "/lobby").await;
+        assert!(resp.message.iter().any(|c| c["id"] == id.as_str()));
+        let resp: ApiError = client
+            .post(&url, &format!("/lobby/{id}/accept"), json!({}))
+            .await;
+        assert_eq!(resp.message, strings::GAME_SELF);
+        let taker = Client::authenticated(&[&guest], &url, false).await;
+        let resp: Response<Map> = taker
+            .post(&url, &format!("/lobby/{id}/accept"), json!({}))
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let game = resp.message["id"].as_str().unwrap().to_string();
+        let event = tokio::time::timeout(Duration::from_secs(5), socket.recv_op(20))
+            .await
+            .unwrap();
+        assert_eq!(event["d"]["id"], id.as_str());
+        let resp: Response<Map> = client.get(&url, &format!("/games/{game}")).await;
+        assert_eq!(resp.message["status"], "active");
+        // Challenges can only be taken up once.
+        let resp: ApiError = other
+            .post(&url, &format!("/lobby/{id}/accept"), json!({}))
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.message, strings::CHALLENGE_NOT_FOUND);
+        // Challenges can be withdrawn by their hosts.
+        let resp: Response<Map> = client.post(&url, "/lobby", json!({})).await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let resp: ApiError = other.delete(&url, &format!("/lobby/{id}")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp: Response<Map> = client.delete(&url, &format!("/lobby/{id}")).await;
+        assert_eq!(resp.code, StatusCode::OK);
+    }
+}
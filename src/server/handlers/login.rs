@@ -1,4 +1,4 @@
-use crate::server::{helpers, state::AppState, strings};
+use crate::server::{csrf, helpers, network::ClientIp, state::AppState, strings};
 use axum::{
     body::Body,
     extract::State,
@@ -6,59 +6,102 @@ use axum::{
     Json,
 };
 use axum_extra::extract::{cookie::Cookie, CookieJar};
-use base64::Engine;
-use rand::RngCore;
-use serde::{Deserialize, Serialize};
+use othello_api_types::Credentials;
 use std::sync::Arc;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Credentials {
-    username: String,
-    password: String,
-}
-
 /// Authenticate the user with the specified credentials.
 pub async fn login(
     State(state): State<Arc<AppState>>,
+    ip: Option<ClientIp>,
     jar: CookieJar,
     Json(credentials): Json<Credentials>,
 ) -> Result<impl IntoResponse, Response<Body>> {
     let Credentials { username, password } = credentials;
     let user = helpers::get_user(&state, &username, true).await?;
-    helpers::ensure_valid_password(&user.password, &password)?;
-    // Generate a random key to use as the session token.
-    let key = {
-        let mut dst = [0; 32];
-        // ThreadRng satisfies the CryptoRng trait, so
-        // it should be cryptographically secure. TODO:
-        // look into a more secure key generation method.
-        rand::thread_rng().fill_bytes(&mut dst);
-        base64::prelude::BASE64_STANDARD.encode(dst)
-    };
-    let token = helpers::create_session(&state, &user, key).await?;
-    Ok((
-        jar.add(Cookie::new(strings::SESSION_COOKIE_NAME, token.clone())),
-        Redirect::to("/@me"),
-    ))
+    // Refuse to authenticate accounts that have been locked after repeated failures.
+    helpers::ensure_not_locked(&state, &user).await?;
+    let ip = ip.map(|ClientIp(ip)| ip.to_string());
+    let hash = helpers::get_password(&state, user.id).await?;
+    if let Err(e) = helpers::ensure_valid_password(hash.as_deref(), &password) {
+        helpers::record_login_attempt(&state, &user, ip, false).await?;
+        helpers::register_login_failure(&state, &user).await;
+        return Err(e.into());
+    }
+    helpers::record_login_attempt(&state, &user, ip, true).await?;
+    helpers::clear_login_failures(&state, &user).await;
+    let token = helpers::create_session(&state, &user, helpers::generate_key()).await?;
+    let (jar, csrf) = csrf::issue(jar.add(Cookie::new(strings::SESSION_COOKIE_NAME, token)));
+    Ok((jar, csrf, Redirect::to("/@me")))
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{sync::Arc, time::Duration};
 
-    use crate::server;
+    use crate::server::{self, handlers::ApiError, state::LoginLimits, strings};
+    use axum::http::StatusCode;
+    use redis::AsyncCommands;
     use test_utils::{function, Client};
 
     #[tokio::test]
     async fn login() {
-        let database = sea_orm::Database::connect(server::TEST_DATABASE_URI)
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
             .await
             .unwrap();
-        let redis = redis::Client::open(server::TEST_REDIS_URI).unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
         let state = Arc::new(server::AppState::new(database, redis));
-        let url = test_utils::init(crate::server::app(state)).await;
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
         let client = Client::authenticated(&[&function!()], &url, true).await;
         let res: serde_json::Value = client.get(&url, "/@me").await;
         assert_eq!(&res["code"], &200);
+        // The user is cached by now, but their password hash isn't.
+        let id = res["message"]["id"].as_str().unwrap();
+        let mut conn = state.redis.get().await.unwrap();
+        let cached: String = conn.get(format!("user:{id}")).await.unwrap();
+        assert!(!cached.contains("password"));
+    }
+
+    #[tokio::test]
+    async fn lockout() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let client = Client::new();
+        let credentials = serde_json::json!({
+            "username": function!(),
+            "password": function!()
+        });
+        client
+            .post::<_, test_utils::Map>(&url, "/register", &credentials)
+            .await;
+        let wrong = serde_json::json!({
+            "username": function!(),
+            "password": "incorrect1"
+        });
+        for _ in 0..LoginLimits::default().max_failures {
+            let resp: ApiError = client.post(&url, "/login", &wrong).await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        }
+        // Even the correct password is rejected while the account is locked.
+        let resp: ApiError = client.post(&url, "/login", &credentials).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.message, strings::ACCOUNT_LOCKED);
+    }
+
+    #[tokio::test]
+    async fn expiry() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = server::AppState::new(database, redis).with_session_ttl(Duration::ZERO);
+        let url = test_utils::init(crate::server::app(Arc::new(state))).await;
+        let client = Client::authenticated(&[&function!()], &url, true).await;
+        // The session is over as soon as it begins.
+        let resp: ApiError = client.get(&url, "/@me").await;
+        assert_eq!(resp.message, strings::INVALID_TOKEN);
     }
 }
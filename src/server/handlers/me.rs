@@ -1,16 +1,22 @@
 use crate::server::{
+    audit::{Action, Entry},
+    conduct,
     entities::{
-        friend::Column as FriendColumn,
+        friend::{self, Column as FriendColumn},
         friend_request::Column as FriendRequestColumn,
         game::{Column as GameColumn, Model},
         member::Column,
         prelude::{Friend, FriendRequest, Game},
     },
     extractors::User,
-    handlers::StringError,
-    helpers,
+    handlers::{user_summaries, StringError},
+    helpers, invites,
+    network::ClientIp,
+    packet::{Event, EventKind, ServerMessage},
+    pagination::Pagination,
     state::AppState,
-    strings, validate_password, validate_username,
+    strings, timestamp, validate_password, validate_timezone, validate_username,
+    validation::{Valid, Validate, Validator},
 };
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
@@ -20,26 +26,33 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::{IntoResponse, Response},
-    Json,
 };
+use othello_api_types::{GameSummary, UpdateMeRequest, UpdatePasswordRequest};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, ModelTrait, QueryFilter, Value,
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, ModelTrait, QueryFilter,
+    QueryOrder, Value,
 };
-use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UpdatePasswordRequest {
-    current: String,
-    new: String,
-    confirmed: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UpdateMeRequest {
-    username: Option<String>,
-    password: Option<UpdatePasswordRequest>,
+impl Validate for UpdateMeRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(username) = &self.username {
+            v.check("username", validate_username(username));
+        }
+        if let Some(password) = &self.password {
+            v.ensure(
+                "password.confirmed",
+                password.new == password.confirmed,
+                strings::PASSWORD_MISMATCH,
+            )
+            .check("password.new", validate_password(&password.new));
+        }
+        if let Some(timezone) = &self.timezone {
+            v.check("timezone", validate_timezone(timezone));
+        }
+    }
 }
 
 /// Fetch the current user's information.
@@ -47,18 +60,28 @@ pub async fn me(user: User) -> Result<impl IntoResponse, Response> {
     Ok(user)
 }
 
+/// Fetch the current user's strikes and the penalties they have earned.
+pub async fn standing(
+    State(state): State<Arc<AppState>>,
+    user: User,
+) -> Result<impl IntoResponse, Response> {
+    let standing = conduct::standing(&state, user.id).await?;
+    Ok(super::Response::new(standing, StatusCode::OK))
+}
+
 pub async fn update(
     State(state): State<Arc<AppState>>,
     user: User,
-    Json(body): Json<UpdateMeRequest>,
+    ip: Option<ClientIp>,
+    Valid(body): Valid<UpdateMeRequest>,
 ) -> Result<impl IntoResponse, Response> {
     let stored = helpers::get_user(&state, &user.id.to_string(), false).await?;
     match body {
         UpdateMeRequest {
             username: Some(username),
             password: None,
+            timezone: None,
         } => {
-            validate_username(username.as_str())?;
             // Check if the username is already taken.
             if helpers::get_user(&state, &username, true).await.is_ok() {
                 return Err(
@@ -66,35 +89,36 @@ pub async fn update(
                         .into_response(),
                 );
             }
-            let mut active = stored.into_active_model();
+            let mut active = stored.clone().into_active_model();
             active.set(
                 Column::Username,
-                Value::String(Some(Box::new(username.to_string()))),
+                Value::String(Some(Box::new(username.clone()))),
             );
             active
                 .save(state.database.as_ref())
                 .await
                 .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+            helpers::forget_user(&state, &stored).await;
+            Entry::new(Action::UsernameChange)
+                .actor(Some(user.id))
+                .target(user.id)
+                .details(json!({ "from": stored.username, "to": username }))
+                .ip(ip)
+                .record(&state)
+                .await?;
             Ok(super::Response::new(json!({}), StatusCode::OK))
         }
         UpdateMeRequest {
             username: None,
-            password:
-                Some(UpdatePasswordRequest {
-                    current,
-                    new,
-                    confirmed,
-                }),
+            password: Some(UpdatePasswordRequest { current, new, .. }),
+            timezone: None,
         } => {
-            if new != confirmed {
-                return Err(StringError(
-                    strings::PASSWORD_MISMATCH.into(),
-                    StatusCode::BAD_REQUEST,
-                )
-                .into_response());
+            // Accounts created through an identity provider can set a password without
+            // knowing a current one.
+            let hash = helpers::get_password(&state, user.id).await?;
+            if hash.is_some() {
+                helpers::ensure_valid_password(hash.as_deref(), &current)?;
             }
-            helpers::ensure_valid_password(&stored.password, &current)?;
-            validate_password(confirmed.as_str())?;
             let salt = SaltString::generate(&mut OsRng);
             let argon2 = Argon2::default();
             let hashed = argon2
@@ -106,15 +130,36 @@ pub async fn update(
                     )
                 })
                 .map(|hashed| hashed.to_string())?;
-            let mut active = stored.into_active_model();
+            let mut active = stored.clone().into_active_model();
             active.set(
                 Column::Password,
-                Value::String(Some(Box::new(hashed.to_string()))),
+                Value::String(Some(Box::new(hashed.clone()))),
             );
             active
                 .save(state.database.as_ref())
                 .await
                 .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+            helpers::forget_user(&state, &stored).await;
+            Entry::new(Action::PasswordChange)
+                .actor(Some(user.id))
+                .target(user.id)
+                .ip(ip)
+                .record(&state)
+                .await?;
+            Ok(super::Response::new(json!({}), StatusCode::OK))
+        }
+        UpdateMeRequest {
+            username: None,
+            password: None,
+            timezone: Some(timezone),
+        } => {
+            let mut active = stored.clone().into_active_model();
+            active.set(Column::Timezone, Value::String(Some(Box::new(timezone))));
+            active
+                .save(state.database.as_ref())
+                .await
+                .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+            helpers::forget_user(&state, &stored).await;
             Ok(super::Response::new(json!({}), StatusCode::OK))
         }
         _ => Err(StringError(strings::BAD_REQUEST.into(), StatusCode::BAD_REQUEST).into_response()),
@@ -125,8 +170,9 @@ pub async fn update(
 pub async fn active_games(
     State(state): State<Arc<AppState>>,
     user: User,
+    pagination: Pagination,
 ) -> Result<impl IntoResponse, Response> {
-    let games = Game::find()
+    let query = Game::find()
         .filter(
             GameColumn::Host
                 .eq(user.id.to_string())
@@ -135,105 +181,123 @@ pub async fn active_games(
                 // Pending games are on a separate endpoint.
                 .and(GameColumn::Pending.eq(false)),
         )
-        .all(state.database.as_ref())
-        .await
-        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        // Game IDs are time-ordered, so this sorts by when the games were created.
+        .order_by(GameColumn::Id, pagination.order.into());
+    let (games, page) = pagination.fetch(state.database.as_ref(), query).await?;
     let resp = create_games_resp(state, &user, games).await?;
-    Ok(super::Response::new(resp, StatusCode::OK))
+    Ok(super::Response::paginated(resp, page, StatusCode::OK))
 }
 
 /// Fetch the games the current user is currently awaiting a response for.
 pub async fn pending_games(
     State(state): State<Arc<AppState>>,
     user: User,
+    pagination: Pagination,
 ) -> Result<impl IntoResponse, Response> {
-    let games = Game::find()
+    let query = Game::find()
         .filter(
             GameColumn::Host
                 .eq(user.id.to_string())
                 .or(GameColumn::Guest.eq(user.id.to_string()))
                 .and(GameColumn::Pending.eq(true)),
         )
-        .all(state.database.as_ref())
-        .await
-        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        // Game IDs are time-ordered, so this sorts by when the games were created.
+        .order_by(GameColumn::Id, pagination.order.into());
+    // Invites that have expired are as good as gone, even before they're deleted.
+    let query = match state.invite_ttl {
+        Some(ttl) => query.filter(GameColumn::Id.gte(invites::cutoff(ttl))),
+        None => query,
+    };
+    let (games, page) = pagination.fetch(state.database.as_ref(), query).await?;
     let resp = create_games_resp(state, &user, games).await?;
-    Ok(super::Response::new(resp, StatusCode::OK))
+    Ok(super::Response::paginated(resp, page, StatusCode::OK))
 }
 
 /// Fetch the friend requests the current user has received.
 pub async fn incoming(
     State(state): State<Arc<AppState>>,
     user: User,
+    pagination: Pagination,
 ) -> Result<impl IntoResponse, Response> {
-    let frs = FriendRequest::find()
+    let query = FriendRequest::find()
         .filter(FriendRequestColumn::Recipient.eq(user.id))
-        .all(state.database.as_ref())
-        .await
-        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        .order_by(FriendRequestColumn::CreatedAt, pagination.order.into());
+    let (frs, page) = pagination.fetch(state.database.as_ref(), query).await?;
+    let users = helpers::get_users_by_ids(&state, frs.iter().map(|fr| fr.sender)).await?;
+    let summaries = user_summaries(&state, users.values()).await;
     let mut incoming = vec![];
     for fr in &frs {
-        let sender = helpers::get_user(&state, &fr.sender.to_string(), false).await?;
+        let sender = helpers::found_user(&users, &fr.sender)?;
         incoming.push(json!({
-            "sender": sender.username,
+            "sender": summaries[&sender.id],
+            "created_at": timestamp::rfc3339(&fr.created_at),
         }));
     }
-    Ok(super::Response::new(incoming, StatusCode::OK))
+    Ok(super::Response::paginated(incoming, page, StatusCode::OK))
 }
 
 /// Fetch the friend requests the current user has sent.
 pub async fn outgoing(
     State(state): State<Arc<AppState>>,
     user: User,
+    pagination: Pagination,
 ) -> Result<impl IntoResponse, Response> {
-    let frs = FriendRequest::find()
+    let query = FriendRequest::find()
         .filter(FriendRequestColumn::Sender.eq(user.id))
-        .all(state.database.as_ref())
-        .await
-        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        .order_by(FriendRequestColumn::CreatedAt, pagination.order.into());
+    let (frs, page) = pagination.fetch(state.database.as_ref(), query).await?;
+    let users = helpers::get_users_by_ids(&state, frs.iter().map(|fr| fr.recipient)).await?;
+    let summaries = user_summaries(&state, users.values()).await;
     let mut outgoing = vec![];
     for fr in &frs {
-        let recipient = helpers::get_user(&state, &fr.recipient.to_string(), false).await?;
+        let recipient = helpers::found_user(&users, &fr.recipient)?;
         outgoing.push(json!({
-            "recipient": recipient.username,
+            "recipient": summaries[&recipient.id],
+            "created_at": timestamp::rfc3339(&fr.created_at),
         }));
     }
-    Ok(super::Response::new(outgoing, StatusCode::OK))
+    Ok(super::Response::paginated(outgoing, page, StatusCode::OK))
 }
 
 /// Fetch the friends of the current user.
 pub async fn friends(
     State(state): State<Arc<AppState>>,
     user: User,
+    pagination: Pagination,
 ) -> Result<impl IntoResponse, Response> {
-    let friends = Friend::find()
+    let query = Friend::find()
         .filter(FriendColumn::A.eq(user.id).or(FriendColumn::B.eq(user.id)))
-        .all(state.database.as_ref())
-        .await
-        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        .order_by(FriendColumn::CreatedAt, pagination.order.into());
+    let (friends, page) = pagination.fetch(state.database.as_ref(), query).await?;
+    let other = |friend: &friend::Model| {
+        if friend.a == user.id {
+            friend.b
+        } else {
+            friend.a
+        }
+    };
+    let users = helpers::get_users_by_ids(&state, friends.iter().map(other)).await?;
+    let summaries = user_summaries(&state, users.values()).await;
     let mut f = vec![];
     for friend in &friends {
-        let id = if friend.a == user.id {
-            &friend.b
-        } else {
-            &friend.a
-        };
-        let friend = helpers::get_user(&state, &id.to_string(), false).await?;
+        let member = helpers::found_user(&users, &other(friend))?;
         f.push(json!({
-            "username": friend.username,
+            "user": summaries[&member.id],
+            "created_at": timestamp::rfc3339(&friend.created_at),
         }));
     }
-    Ok(super::Response::new(f, StatusCode::OK))
+    Ok(super::Response::paginated(f, page, StatusCode::OK))
 }
 
 /// Remove a friend from the current user's friend list.
 pub async fn remove_friend(
     State(state): State<Arc<AppState>>,
     user: User,
+    ip: Option<ClientIp>,
     Path(friend): Path<String>,
 ) -> Result<impl IntoResponse, Response> {
     let friend = helpers::get_user(&state, &friend, true).await?;
-    let Some(friend) = Friend::find()
+    let Some(friendship) = Friend::find()
         .filter(
             FriendColumn::A
                 .eq(user.id)
@@ -250,10 +314,25 @@ pub async fn remove_friend(
             StringError(strings::FRIEND_NOT_FOUND.into(), StatusCode::NOT_FOUND).into_response(),
         );
     };
-    let result = friend
+    let result = friendship
         .delete(state.database.as_ref())
         .await
         .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    state.notify(
+        friend.id,
+        Event::new(
+            EventKind::FriendRemove,
+            ServerMessage::FriendRemove {
+                user: user.username.clone(),
+            },
+        ),
+    );
+    Entry::new(Action::FriendRemove)
+        .actor(Some(user.id))
+        .target(friend.id)
+        .ip(ip)
+        .record(&state)
+        .await?;
     Ok(super::Response::new(
         json!({ "affected": result.rows_affected }),
         StatusCode::OK,
@@ -264,22 +343,31 @@ async fn create_games_resp(
     state: Arc<AppState>,
     user: &User,
     games: Vec<Model>,
-) -> Result<Vec<serde_json::Value>, Response> {
+) -> Result<Vec<GameSummary>, Response> {
+    // Games store their players' IDs as text, so parse them before looking them up together.
+    let id = |s: &str| Uuid::try_from(s).unwrap();
+    let users = helpers::get_users_by_ids(
+        &state,
+        games.iter().flat_map(|g| [id(&g.host), id(&g.guest)]),
+    )
+    .await?;
     let mut resp = vec![];
     for g in &games {
-        let id = if user.id.to_string() == g.host {
+        let opponent = if user.id.to_string() == g.host {
             &g.guest
         } else {
             &g.host
         };
-        let host = helpers::get_user(&state, g.host.as_str(), false).await?;
-        let opponent = helpers::get_user(&state, id, false).await?;
-        resp.push(json!({
-            "id": g.id,
-            "host": host.username,
-            "opponent": opponent.username,
-            "ended": g.ended,
-        }));
+        let host = helpers::found_user(&users, &id(&g.host))?;
+        let opponent = helpers::found_user(&users, &id(opponent))?;
+        resp.push(GameSummary {
+            id: g.id,
+            host: host.username.clone(),
+            host_color: helpers::host_color(g),
+            opponent: opponent.username.clone(),
+            ended: g.ended,
+            settings: helpers::game_settings(g),
+        });
     }
     Ok(resp)
 }
@@ -288,19 +376,62 @@ async fn create_games_resp(
 mod tests {
     use std::sync::Arc;
 
-    use crate::server::{self, handlers::Response};
+    use crate::server::{
+        self,
+        handlers::{ApiError, Response},
+        strings,
+    };
+    use serde_json::json;
     use test_utils::{function, Client, Map};
 
     #[tokio::test]
     async fn me() {
-        let database = sea_orm::Database::connect(server::TEST_DATABASE_URI)
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
             .await
             .unwrap();
-        let redis = redis::Client::open(server::TEST_REDIS_URI).unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
         let state = Arc::new(server::AppState::new(database, redis));
         let url = test_utils::init(crate::server::app(state)).await;
         let client = Client::authenticated(&[&function!()], &url, true).await;
         let resp: Response<Map> = client.get(&url, "/@me").await;
         assert_eq!(resp.message["username"], function!());
+        assert_eq!(resp.message["timezone"], "UTC");
+        let resp: ApiError = client
+            .patch(&url, "/@me", json!({ "timezone": "Mars/Olympus Mons" }))
+            .await;
+        assert_eq!(resp.message, strings::INVALID_TIMEZONE);
+        // Names have to be of zones that exist, not just look like them.
+        let resp: ApiError = client
+            .patch(&url, "/@me", json!({ "timezone": "Europe/Atlantis" }))
+            .await;
+        assert_eq!(resp.message, strings::INVALID_TIMEZONE);
+        client
+            .patch::<_, Map>(&url, "/@me", json!({ "timezone": "America/New_York" }))
+            .await;
+        let resp: Response<Map> = client.get(&url, "/@me").await;
+        assert_eq!(resp.message["timezone"], "America/New_York");
+    }
+
+    #[tokio::test]
+    async fn rename() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let old = function!();
+        let new = format!("{old}::renamed");
+        let client = Client::authenticated(&[&old], &url, true).await;
+        // Look the user up so that they're in the cache before they're renamed.
+        let resp: Response<Map> = client.get(&url, &format!("/users/{old}")).await;
+        assert_eq!(resp.message["user"]["username"], old);
+        client
+            .patch::<_, Map>(&url, "/@me", json!({ "username": new }))
+            .await;
+        let resp: ApiError = client.get(&url, &format!("/users/{old}")).await;
+        assert_eq!(resp.message, strings::INVALID_USERNAME);
+        let resp: Response<Map> = client.get(&url, "/@me").await;
+        assert_eq!(resp.message["username"], new);
     }
 }
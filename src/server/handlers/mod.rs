@@ -1,44 +1,103 @@
-use axum::{http::StatusCode, response::IntoResponse, Json};
-use serde::{Deserialize, Serialize};
+use crate::server::{entities::member, presence, season as ladder, state::AppState};
+use axum::{http::StatusCode, response::IntoResponse};
+use redis::RedisError;
+use sea_orm::DbErr;
+use std::collections::HashMap;
+use uuid::Uuid;
 
+pub mod admin;
+pub mod arena;
+pub mod block;
+pub mod bot;
 mod companion;
 mod create;
+mod error;
 pub mod friend_request;
 mod game;
+pub mod health;
 mod live;
+pub mod lobby;
 mod login;
 mod logout;
 mod me;
+pub mod notification;
+pub mod oauth;
+pub mod profile;
+pub mod puzzle;
 mod register;
+pub mod report;
+pub mod search;
+pub mod season;
+pub mod security;
+pub mod tournament;
+pub mod webhook;
+pub mod widgets;
 
 pub use companion::companion;
-pub use create::create;
-pub use game::{accept as accept_game, cancel as cancel_invite, decline as decline_game, game};
+pub use create::{create, create_bot_game, join_by_code};
+pub use error::{ApiError, ErrorCode};
+pub use game::{
+    accept as accept_game, analysis as analyse_game, cancel as cancel_invite,
+    decline as decline_game, detail as game_detail, export as export_game, game,
+    live as live_games, preview as game_preview, replay as replay_game,
+};
 pub use live::callback;
 pub use login::login;
 pub use logout::logout;
 pub use me::{
-    active_games, friends, incoming, me, outgoing, pending_games, remove_friend,
+    active_games, friends, incoming, me, outgoing, pending_games, remove_friend, standing,
     update as update_me,
 };
+pub use othello_api_types::{Response, UserSummary};
 pub use register::register;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Response<S: Serialize> {
-    message: S,
-    code: u16,
-}
-
-impl<S: Serialize> Response<S> {
-    pub fn new(message: S, code: StatusCode) -> (StatusCode, Json<Self>) {
-        (
-            code,
-            Json(Self {
-                message,
-                code: u16::from(code),
-            }),
-        )
+/// Summarize the specified user, as they appear in someone else's responses.
+pub async fn user_summary(state: &AppState, member: &member::Model) -> UserSummary {
+    let ratings = ratings(state, vec![member.id]).await;
+    rated_summary(state, member, ratings.get(&member.id).copied()).await
+}
+
+/// Summarize several users at once, keyed by their ID. Their ratings are fetched together, so
+/// lists don't cost a query per user.
+pub async fn user_summaries<'a>(
+    state: &AppState,
+    members: impl IntoIterator<Item = &'a member::Model>,
+) -> HashMap<Uuid, UserSummary> {
+    let members: Vec<_> = members.into_iter().collect();
+    let ratings = ratings(state, members.iter().map(|member| member.id).collect()).await;
+    let mut summaries = HashMap::with_capacity(members.len());
+    for member in members {
+        let rating = ratings.get(&member.id).copied();
+        summaries.insert(member.id, rated_summary(state, member, rating).await);
     }
+    summaries
+}
+
+async fn rated_summary(
+    state: &AppState,
+    member: &member::Model,
+    rating: Option<i32>,
+) -> UserSummary {
+    UserSummary {
+        id: member.id,
+        username: member.username.clone(),
+        avatar: member.avatar.as_deref().map(profile::avatar_url),
+        rating,
+        presence: presence::status(state, member.id).await,
+        bot: member.bot,
+        created_at: member.created_at,
+    }
+}
+
+/// Fetch the ratings shown in user summaries. Ratings are only a detail of a summary, so
+/// failing to fetch them is logged and leaves them out rather than failing the response.
+async fn ratings(state: &AppState, members: Vec<Uuid>) -> HashMap<Uuid, i32> {
+    ladder::ratings(state.database.as_ref(), members)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to fetch ratings: {e}");
+            HashMap::new()
+        })
 }
 
 #[derive(Debug)]
@@ -46,7 +105,21 @@ pub struct StringError(pub String, pub StatusCode);
 
 impl IntoResponse for StringError {
     fn into_response(self) -> axum::response::Response {
-        Response::new(self.0, self.1).into_response()
+        ApiError::from(self).into_response()
+    }
+}
+
+/// Database errors are the server's fault, not the client's.
+impl From<DbErr> for StringError {
+    fn from(e: DbErr) -> Self {
+        Self(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// So are Redis errors.
+impl From<RedisError> for StringError {
+    fn from(e: RedisError) -> Self {
+        Self(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
     }
 }
 
@@ -57,5 +130,5 @@ impl From<StringError> for axum::response::Response {
 }
 
 pub async fn fallback() -> impl IntoResponse {
-    Response::new("not found", StatusCode::NOT_FOUND)
+    ApiError::new(ErrorCode::NotFound, "not found", StatusCode::NOT_FOUND)
 }
@@ -0,0 +1,160 @@
+use super::StringError;
+use crate::server::{
+    entities::{notification::Column, prelude::Notification as NotificationEntity},
+    extractors::User,
+    notifications::{self, Notification},
+    pagination::Pagination,
+    state::AppState,
+    strings,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, UpdateResult};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NotificationParams {
+    /// Only include notifications that haven't been read yet.
+    #[serde(default)]
+    unread: bool,
+}
+
+/// Fetch the current user's notifications, newest first.
+pub async fn notifications(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Query(params): Query<NotificationParams>,
+    pagination: Pagination,
+) -> Result<impl IntoResponse, Response> {
+    let mut query = NotificationEntity::find().filter(Column::Member.eq(user.id));
+    if params.unread {
+        query = query.filter(Column::Read.eq(false));
+    }
+    // Notification IDs are time-ordered, so this sorts by when they were sent.
+    let query = query.order_by(Column::Id, pagination.order.into());
+    let (models, page) = pagination.fetch(state.database.as_ref(), query).await?;
+    let notifications: Vec<_> = models.iter().map(Notification::from).collect();
+    Ok(super::Response::paginated(
+        notifications,
+        page,
+        StatusCode::OK,
+    ))
+}
+
+/// Fetch how many of the current user's notifications haven't been read yet.
+pub async fn unread(
+    State(state): State<Arc<AppState>>,
+    user: User,
+) -> Result<impl IntoResponse, Response> {
+    let unread = notifications::unread(&state, user.id).await?;
+    Ok(super::Response::new(
+        json!({ "unread": unread }),
+        StatusCode::OK,
+    ))
+}
+
+/// Mark the specified notification as read.
+pub async fn read(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let not_found = || {
+        StringError(
+            strings::NOTIFICATION_NOT_FOUND.into(),
+            StatusCode::NOT_FOUND,
+        )
+    };
+    let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+    let result = mark_read(&state, Column::Id.eq(id).and(Column::Member.eq(user.id))).await?;
+    if result.rows_affected == 0 {
+        return Err(not_found().into_response());
+    }
+    let unread = notifications::unread(&state, user.id).await?;
+    Ok(super::Response::new(
+        json!({ "unread": unread }),
+        StatusCode::OK,
+    ))
+}
+
+/// Mark every one of the current user's notifications as read.
+pub async fn read_all(
+    State(state): State<Arc<AppState>>,
+    user: User,
+) -> Result<impl IntoResponse, Response> {
+    let result = mark_read(&state, Column::Member.eq(user.id)).await?;
+    Ok(super::Response::new(
+        json!({ "affected": result.rows_affected }),
+        StatusCode::OK,
+    ))
+}
+
+async fn mark_read(
+    state: &AppState,
+    filter: sea_orm::sea_query::SimpleExpr,
+) -> Result<UpdateResult, StringError> {
+    NotificationEntity::update_many()
+        .col_expr(Column::Read, Expr::value(true))
+        .filter(filter)
+        .filter(Column::Read.eq(false))
+        .exec(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{self, handlers::Response, strings};
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map, Socket};
+
+    #[tokio::test]
+    async fn notifications() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let sender = format!("{}::1", function!());
+        let recipient = format!("{}::2", function!());
+        let client = Client::authenticated(&[&recipient, &sender], &url, true).await;
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        let other = Client::authenticated(&[&sender], &url, false).await;
+        let resp: Response<Map> = other
+            .post(&url, &format!("/users/{recipient}/friend"), json!({}))
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let event = socket.recv_op(18).await;
+        assert_eq!(event["d"]["notification"]["kind"], "friend_request");
+        assert_eq!(
+            event["d"]["notification"]["payload"]["user"],
+            sender.as_str()
+        );
+        assert_eq!(event["d"]["unread"], 1);
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/notifications?unread=true").await;
+        assert_eq!(resp.message.len(), 1);
+        let id = resp.message[0]["id"].as_str().unwrap().to_string();
+        let resp: Response<Map> = client
+            .post(&url, &format!("/@me/notifications/{id}/read"), json!({}))
+            .await;
+        assert_eq!(resp.message["unread"], 0);
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/notifications?unread=true").await;
+        assert!(resp.message.is_empty());
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/notifications").await;
+        assert_eq!(resp.message[0]["read"], true);
+    }
+}
@@ -0,0 +1,348 @@
+use super::StringError;
+use crate::server::{
+    csrf,
+    entities::{
+        identity::{self, Column as IdentityColumn},
+        member,
+        prelude::{Identity, Member},
+    },
+    extractors::User,
+    helpers,
+    network::ClientIp,
+    oauth::Provider,
+    state::AppState,
+    strings,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use base64::Engine;
+use rand::Rng;
+use redis::AsyncCommands;
+use sea_orm::{sea_query::OnConflict, ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{str::FromStr, sync::Arc};
+use uuid::Uuid;
+
+/// How long (in seconds) a user has to complete the authorization flow once started.
+const STATE_TTL: u64 = 10 * 60;
+/// The cookie tying a flow to the browser that started it.
+const BINDING_COOKIE_NAME: &str = "oauth_binding";
+/// How many usernames are tried for a new account before giving up.
+const USERNAME_ATTEMPTS: usize = 10;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuthorizeParams {
+    /// Link the identity to the signed-in user's account rather than signing in with it.
+    #[serde(default)]
+    link: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackParams {
+    code: String,
+    state: String,
+}
+
+/// What's remembered about a flow between starting it and the provider sending the user back.
+#[derive(Debug, Serialize, Deserialize)]
+struct Flow {
+    provider: String,
+    /// The hash of the binding cookie set on the browser that started the flow.
+    binding: String,
+    /// The account the identity should be linked to, if the flow was started to link one.
+    link: Option<Uuid>,
+}
+
+fn provider(name: &str) -> Result<Provider, StringError> {
+    Provider::from_str(name).map_err(|()| {
+        StringError(
+            strings::OAUTH_UNKNOWN_PROVIDER.into(),
+            StatusCode::NOT_FOUND,
+        )
+    })
+}
+
+/// Hash a binding cookie's value, so that Redis never holds anything that could be replayed
+/// as the cookie itself.
+fn digest(binding: &str) -> String {
+    base64::prelude::BASE64_STANDARD.encode(Sha256::digest(binding.as_bytes()))
+}
+
+fn invalid_state() -> StringError {
+    StringError(strings::OAUTH_INVALID_STATE.into(), StatusCode::FORBIDDEN)
+}
+
+/// Begin signing in with the specified identity provider, or with `?link=true`, linking an
+/// identity with it to the signed-in user's account.
+pub async fn authorize(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(params): Query<AuthorizeParams>,
+    jar: CookieJar,
+    current: Option<User>,
+) -> Result<impl IntoResponse, Response> {
+    let provider = provider(&name)?;
+    let credentials = provider.credentials().ok_or(StringError(
+        strings::OAUTH_UNAVAILABLE.into(),
+        StatusCode::SERVICE_UNAVAILABLE,
+    ))?;
+    let link = if params.link {
+        let user = current.ok_or(StringError(
+            strings::INVALID_TOKEN.into(),
+            StatusCode::UNAUTHORIZED,
+        ))?;
+        Some(user.id)
+    } else {
+        None
+    };
+    // Remember the state parameter so that the callback can verify that it was us who started
+    // the flow, and bind it to this browser so that nobody else can finish it.
+    let token = helpers::generate_key();
+    let binding = helpers::generate_key();
+    let flow = Flow {
+        provider: provider.name().into(),
+        binding: digest(&binding),
+        link,
+    };
+    let mut conn = state
+        .redis
+        .get()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    conn.set_ex::<_, _, ()>(
+        format!("oauth:state:{token}"),
+        serde_json::to_string(&flow).unwrap(),
+        STATE_TTL,
+    )
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    // The provider sends the user back with a top-level navigation, which `Lax` lets through.
+    // The cookie is only good for as long as the state it's stored with.
+    let cookie = Cookie::build((BINDING_COOKIE_NAME, binding))
+        .path("/auth/oauth")
+        .http_only(true)
+        .same_site(SameSite::Lax);
+    Ok((
+        jar.add(cookie),
+        Redirect::to(&provider.authorize(&credentials, &token)),
+    ))
+}
+
+/// Complete signing in with the specified identity provider. Flows started to link an
+/// identity link it to the account that started them, provided it isn't linked to another
+/// account already; any other flow signs in with the account the identity is linked to,
+/// creating one if there isn't any.
+pub async fn callback(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(params): Query<CallbackParams>,
+    ip: Option<ClientIp>,
+    jar: CookieJar,
+    current: Option<User>,
+) -> Result<impl IntoResponse, Response> {
+    let provider = provider(&name)?;
+    // Each state parameter can only be used once, only with the provider it was issued for,
+    // and only by the browser that started the flow.
+    let mut conn = state
+        .redis
+        .get()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let issued: Option<String> = conn
+        .get_del(format!("oauth:state:{}", params.state))
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let flow = issued
+        .and_then(|issued| serde_json::from_str::<Flow>(&issued).ok())
+        .filter(|flow| flow.provider == provider.name())
+        .ok_or_else(invalid_state)?;
+    let binding = jar
+        .get(BINDING_COOKIE_NAME)
+        .map(|cookie| digest(cookie.value()));
+    if binding.as_deref() != Some(flow.binding.as_str()) {
+        return Err(invalid_state().into_response());
+    }
+    let jar = jar.remove(Cookie::build(BINDING_COOKIE_NAME).path("/auth/oauth"));
+    // A link flow has to finish in the session that started it.
+    if flow.link.is_some() && flow.link != current.map(|user| user.id) {
+        return Err(invalid_state().into_response());
+    }
+    let credentials = provider.credentials().ok_or(StringError(
+        strings::OAUTH_UNAVAILABLE.into(),
+        StatusCode::SERVICE_UNAVAILABLE,
+    ))?;
+    let external = provider
+        .identify(&credentials, &params.code)
+        .await
+        .map_err(|e| {
+            tracing::error!(%provider, "Failed to identify user: {e}");
+            StringError(strings::OAUTH_FAILED.into(), StatusCode::BAD_GATEWAY)
+        })?;
+    // Find the account linked to this identity, linking or creating one if there isn't any.
+    let linked = Identity::find()
+        .filter(IdentityColumn::Provider.eq(provider.name()))
+        .filter(IdentityColumn::Subject.eq(&external.subject))
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let id = match (linked, flow.link) {
+        (Some(identity), Some(link)) if identity.member != link => {
+            return Err(
+                StringError(strings::OAUTH_IDENTITY_TAKEN.into(), StatusCode::CONFLICT)
+                    .into_response(),
+            )
+        }
+        (Some(identity), _) => identity.member,
+        (None, Some(link)) => link,
+        (None, None) => create_member(&state, &external.username).await?,
+    };
+    let user = helpers::get_user(&state, &id.to_string(), false).await?;
+    Identity::insert(identity::ActiveModel {
+        provider: ActiveValue::set(provider.name().to_string()),
+        subject: ActiveValue::set(external.subject),
+        member: ActiveValue::set(user.id),
+    })
+    .on_conflict(
+        OnConflict::columns([IdentityColumn::Provider, IdentityColumn::Subject])
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(state.database.as_ref())
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let ip = ip.map(|ClientIp(ip)| ip.to_string());
+    helpers::record_login_attempt(&state, &user, ip, true).await?;
+    let token = helpers::create_session(&state, &user, helpers::generate_key()).await?;
+    let (jar, csrf) = csrf::issue(jar.add(Cookie::new(strings::SESSION_COOKIE_NAME, token)));
+    Ok((jar, csrf, Redirect::to("/@me")))
+}
+
+/// Create a password-less account for a user signing in through an identity provider,
+/// deriving a free username from the one the provider suggested. The username's unique
+/// constraint decides who gets a name when two accounts are created at once, and the loser
+/// tries another.
+async fn create_member(state: &AppState, suggested: &str) -> Result<Uuid, StringError> {
+    let mut base: String = suggested
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+        .collect();
+    while base.len() < 3 {
+        base.push('_');
+    }
+    let mut username = base.clone();
+    for _ in 0..USERNAME_ATTEMPTS {
+        let id = Uuid::now_v7();
+        let result = Member::insert(member::ActiveModel {
+            id: ActiveValue::set(id),
+            username: ActiveValue::set(username),
+            password: ActiveValue::set(None),
+            created_at: ActiveValue::NotSet,
+            avatar: ActiveValue::NotSet,
+            timezone: ActiveValue::NotSet,
+            admin: ActiveValue::NotSet,
+            bot: ActiveValue::NotSet,
+            owner: ActiveValue::NotSet,
+            api_token: ActiveValue::NotSet,
+        })
+        .exec(state.database.as_ref())
+        .await;
+        match result {
+            Ok(_) => return Ok(id),
+            Err(e) if helpers::unique_violation(&e) => {
+                username = format!("{base}{}", rand::thread_rng().gen_range(1000..10000));
+            }
+            Err(e) => {
+                return Err(StringError(
+                    e.to_string(),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                ))
+            }
+        }
+    }
+    Err(StringError(
+        strings::USERNAME_TAKEN.into(),
+        StatusCode::CONFLICT,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::server::{self, handlers::ApiError, strings};
+    use axum::http::StatusCode;
+    use redis::AsyncCommands;
+    use test_utils::{function, Client};
+
+    #[tokio::test]
+    async fn unknown_provider() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let client = Client::new();
+        let resp: ApiError = client.get(&url, "/auth/oauth/myspace").await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.message, strings::OAUTH_UNKNOWN_PROVIDER);
+    }
+
+    #[tokio::test]
+    async fn invalid_state() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let client = Client::new();
+        let resp: ApiError = client
+            .get(&url, "/auth/oauth/github/callback?code=abc&state=forged")
+            .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(resp.message, strings::OAUTH_INVALID_STATE);
+    }
+
+    #[tokio::test]
+    async fn unbound_state() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        // A genuine state, issued to someone else's browser, can't be used from this one.
+        let token = function!().replace("::", "-");
+        let flow = super::Flow {
+            provider: "github".into(),
+            binding: super::digest("someone else's cookie"),
+            link: None,
+        };
+        let mut conn = state.redis.get().await.unwrap();
+        conn.set_ex::<_, _, ()>(
+            format!("oauth:state:{token}"),
+            serde_json::to_string(&flow).unwrap(),
+            60,
+        )
+        .await
+        .unwrap();
+        let client = Client::new();
+        let resp: ApiError = client
+            .get(
+                &url,
+                &format!("/auth/oauth/github/callback?code=abc&state={token}"),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(resp.message, strings::OAUTH_INVALID_STATE);
+    }
+}
@@ -0,0 +1,432 @@
+use super::{user_summaries, user_summary, widgets, StringError};
+use crate::server::{
+    arena,
+    entities::{
+        finished_game::Column as FinishedGameColumn,
+        game,
+        member::{self, Column as MemberColumn},
+        prelude::{FinishedGame, Member},
+    },
+    extractors::User,
+    helpers, moves,
+    pagination::Pagination,
+    state::AppState,
+    strings, timestamp,
+};
+use axum::{
+    extract::{Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, EntityTrait, IntoActiveModel,
+    QueryFilter, QueryOrder, QuerySelect, Select,
+};
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How many of a player's finished games their profile lists.
+const RECENT_GAMES: u64 = 10;
+/// The largest avatar (in bytes) that can be uploaded.
+const MAX_AVATAR_SIZE: usize = 1024 * 1024;
+/// The image formats avatars can be uploaded in, as the bytes their files start with, the
+/// extension they're stored with, and the content type they're served with.
+const AVATAR_FORMATS: [(&[u8], &str, &str); 5] = [
+    (b"\x89PNG\r\n\x1a\n", "png", "image/png"),
+    (b"\xff\xd8\xff", "jpg", "image/jpeg"),
+    (b"GIF87a", "gif", "image/gif"),
+    (b"GIF89a", "gif", "image/gif"),
+    (b"RIFF", "webp", "image/webp"),
+];
+
+/// The address an avatar stored under the specified key is served from.
+pub fn avatar_url(key: &str) -> String {
+    format!("/avatars/{key}")
+}
+
+/// Fetch the public profile of the specified user: who they are, their record, how long they
+/// take over their moves, and their most recently finished games.
+pub async fn profile(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let member = helpers::get_user(&state, &username, true).await?;
+    let record = widgets::records(&state, Some(member.id))
+        .await?
+        .remove(&member.id)
+        .unwrap_or_default();
+    let games = finished_games(&member)
+        // Game IDs are time-ordered, so this puts the newest first.
+        .order_by_desc(FinishedGameColumn::Id)
+        .limit(RECENT_GAMES)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let games: Vec<_> = games.into_iter().map(game::Model::from).collect();
+    let recent = summarize(&state, &member, &games).await?;
+    let average_move_time = moves::average_time(&state, member.id).await?;
+    Ok(super::Response::new(
+        json!({
+            "user": user_summary(&state, &member).await,
+            "stats": {
+                "played": record.played,
+                "wins": record.wins,
+                "losses": record.losses,
+                "draws": record.draws,
+                // In milliseconds.
+                "average_move_time": average_move_time,
+            },
+            "recent_games": recent,
+        }),
+        StatusCode::OK,
+    ))
+}
+
+/// Fetch the specified user's finished games, newest first unless sorted otherwise.
+pub async fn history(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    pagination: Pagination,
+) -> Result<impl IntoResponse, Response> {
+    let member = helpers::get_user(&state, &username, true).await?;
+    let query = finished_games(&member).order_by(FinishedGameColumn::Id, pagination.order.into());
+    let (games, page) = pagination.fetch(state.database.as_ref(), query).await?;
+    let games: Vec<_> = games.into_iter().map(game::Model::from).collect();
+    let games = summarize(&state, &member, &games).await?;
+    Ok(super::Response::paginated(games, page, StatusCode::OK))
+}
+
+/// Sum up how one player has fared against another across every game they've finished
+/// against each other, from the first player's side: their record, the discs each finished
+/// with, how many moves their games took on average, and when they last played.
+pub async fn head_to_head(
+    State(state): State<Arc<AppState>>,
+    Path((username, other)): Path<(String, String)>,
+) -> Result<impl IntoResponse, Response> {
+    let member = helpers::get_user(&state, &username, true).await?;
+    let opponent = helpers::get_user(&state, &other, true).await?;
+    let (a, b) = (member.id.to_string(), opponent.id.to_string());
+    let games = FinishedGame::find()
+        .filter(
+            Condition::any()
+                .add(
+                    Condition::all()
+                        .add(FinishedGameColumn::Host.eq(a.as_str()))
+                        .add(FinishedGameColumn::Guest.eq(b.as_str())),
+                )
+                .add(
+                    Condition::all()
+                        .add(FinishedGameColumn::Host.eq(b.as_str()))
+                        .add(FinishedGameColumn::Guest.eq(a.as_str())),
+                ),
+        )
+        // Game IDs are time-ordered, so this puts the newest first.
+        .order_by_desc(FinishedGameColumn::Id)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let games: Vec<_> = games.into_iter().map(game::Model::from).collect();
+    let record = arena::tally(&games, member.id);
+    let lengths: Vec<_> = games.iter().filter_map(length).collect();
+    #[allow(clippy::cast_precision_loss)] // Nobody plays that many games or moves
+    let average_length =
+        (!lengths.is_empty()).then(|| lengths.iter().sum::<usize>() as f64 / lengths.len() as f64);
+    let last = games.first();
+    let summaries = user_summaries(&state, [&member, &opponent]).await;
+    Ok(super::Response::new(
+        json!({
+            "user": summaries.get(&member.id),
+            "opponent": summaries.get(&opponent.id),
+            "played": record.played,
+            "wins": record.wins,
+            "losses": record.losses,
+            "draws": record.draws,
+            "discs": record.discs,
+            "opponent_discs": record.opponent_discs,
+            "disc_differential": i64::from(record.discs) - i64::from(record.opponent_discs),
+            "average_length": average_length,
+            "last_played": last
+                .and_then(|game| game.ended_at.as_ref())
+                .map(timestamp::rfc3339),
+            "last_game": last.map(|game| game.id),
+        }),
+        StatusCode::OK,
+    ))
+}
+
+/// How many moves the specified game took, if its final position was kept.
+fn length(game: &game::Model) -> Option<usize> {
+    let position: crate::Game = serde_json::from_value(game.state.clone()?).ok()?;
+    Some(position.history().len())
+}
+
+/// The specified player's finished games, archived or not.
+fn finished_games(member: &member::Model) -> Select<FinishedGame> {
+    let id = member.id.to_string();
+    FinishedGame::find().filter(
+        FinishedGameColumn::Host
+            .eq(&id)
+            .or(FinishedGameColumn::Guest.eq(&id)),
+    )
+}
+
+/// Describe each of the specified player's games from their side of the board.
+async fn summarize(
+    state: &AppState,
+    member: &member::Model,
+    games: &[game::Model],
+) -> Result<Vec<serde_json::Value>, StringError> {
+    let opponents: Vec<_> = games
+        .iter()
+        .filter_map(|game| {
+            let (black, white) = helpers::black_white(game);
+            if black == Some(member.id) {
+                white
+            } else {
+                black
+            }
+        })
+        .collect();
+    let opponents = Member::find()
+        .filter(MemberColumn::Id.is_in(opponents))
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(games
+        .iter()
+        .map(|game| {
+            let (black, white) = helpers::black_white(game);
+            let (color, opponent) = if black == Some(member.id) {
+                ("black", white)
+            } else {
+                ("white", black)
+            };
+            let opponent = opponents
+                .iter()
+                .find(|member| Some(member.id) == opponent)
+                .map(|member| member.username.clone());
+            json!({
+                "id": game.id,
+                "color": color,
+                "opponent": opponent,
+                "result": game.result,
+            })
+        })
+        .collect())
+}
+
+/// Replace the current user's avatar with the image uploaded in the `avatar` field of a
+/// multipart form.
+pub async fn upload_avatar(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, Response> {
+    let mut data = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| StringError(e.body_text(), e.status()))?
+    {
+        if field.name() == Some("avatar") {
+            let bytes = field
+                .bytes()
+                .await
+                .map_err(|e| StringError(e.body_text(), e.status()))?;
+            data = Some(bytes);
+            break;
+        }
+    }
+    let data = data.ok_or(StringError(
+        strings::AVATAR_MISSING.into(),
+        StatusCode::BAD_REQUEST,
+    ))?;
+    if data.len() > MAX_AVATAR_SIZE {
+        return Err(StringError(
+            strings::AVATAR_TOO_LARGE.into(),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )
+        .into_response());
+    }
+    // Go by what the file actually contains rather than what the client claims it is.
+    let (_, extension, _) = AVATAR_FORMATS
+        .iter()
+        .find(|(magic, extension, _)| {
+            data.starts_with(magic) && (*extension != "webp" || data.get(8..12) == Some(b"WEBP"))
+        })
+        .ok_or(StringError(
+            strings::AVATAR_UNSUPPORTED.into(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ))?;
+    // Every upload gets a fresh key, so avatars can be cached forever.
+    let key = format!("{}.{extension}", Uuid::now_v7());
+    state
+        .storage
+        .put(&key, data.to_vec())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let member = helpers::get_user(&state, &user.id.to_string(), false).await?;
+    let previous = member.avatar.clone();
+    let mut active: member::ActiveModel = member.clone().into_active_model();
+    active.avatar = ActiveValue::set(Some(key.clone()));
+    active
+        .update(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    helpers::forget_user(&state, &member).await;
+    if let Some(previous) = previous {
+        let _ = state.storage.delete(&previous).await;
+    }
+    Ok(super::Response::new(
+        json!({ "avatar": avatar_url(&key) }),
+        StatusCode::OK,
+    ))
+}
+
+/// Serve the avatar stored under the specified key.
+pub async fn avatar(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let not_found = || StringError(strings::AVATAR_NOT_FOUND.into(), StatusCode::NOT_FOUND);
+    // Only serve keys that uploads could have produced.
+    let (id, extension) = key.split_once('.').ok_or_else(not_found)?;
+    let (_, _, content_type) = AVATAR_FORMATS
+        .iter()
+        .find(|format| format.1 == extension)
+        .filter(|_| Uuid::parse_str(id).is_ok())
+        .ok_or_else(not_found)?;
+    let data = state
+        .storage
+        .get(&key)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(not_found)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, *content_type),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        data,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        server::{
+            self, correspondence,
+            entities::game,
+            handlers::{ApiError, Response},
+            helpers, strings,
+        },
+        Game, Piece,
+    };
+    use axum::http::StatusCode;
+    use chrono::Utc;
+    use sea_orm::{ActiveModelTrait, ActiveValue};
+    use serde_json::json;
+    use test_utils::{function, Client, Map};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn profile() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let user = function!();
+        let client = Client::authenticated(&[&user], &url, true).await;
+        let resp: Response<Map> = client.get(&url, &format!("/users/{user}")).await;
+        assert_eq!(resp.code, StatusCode::OK);
+        assert_eq!(resp.message["user"]["avatar"], serde_json::Value::Null);
+        assert_eq!(resp.message["stats"]["played"], 0);
+        let resp: Response<Vec<Map>> = client.get(&url, &format!("/users/{user}/games")).await;
+        assert!(resp.message.is_empty());
+        assert_eq!(resp.page.unwrap().total, 0);
+        // Only real images are accepted, whatever they're called.
+        let resp: ApiError = client
+            .put_file(&url, "/@me/avatar", "avatar", b"not an image".to_vec())
+            .await;
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(resp.message, strings::AVATAR_UNSUPPORTED);
+        let image = b"\x89PNG\r\n\x1a\nrest of the image".to_vec();
+        let resp: Response<Map> = client
+            .put_file(&url, "/@me/avatar", "avatar", image.clone())
+            .await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let avatar = resp.message["avatar"].as_str().unwrap().to_string();
+        let resp: Response<Map> = client.get(&url, &format!("/users/{user}")).await;
+        assert_eq!(resp.message["user"]["avatar"], avatar.as_str());
+        let resp = client.get_raw(&url, &avatar).await;
+        assert_eq!(resp.headers()["content-type"], "image/png");
+        assert_eq!(resp.bytes().await.unwrap().to_vec(), image);
+        let resp = client.get_raw(&url, "/avatars/..%2Fsecrets.png").await;
+        assert_eq!(resp.status().as_u16(), 404);
+    }
+
+    #[tokio::test]
+    async fn head_to_head() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let user = function!();
+        let other = format!("{user}::other");
+        let client = Client::authenticated(&[&user, &other], &url, true).await;
+        let a = helpers::get_user(&state, &user, true).await.unwrap().id;
+        let b = helpers::get_user(&state, &other, true).await.unwrap().id;
+        let mut position = Game::new();
+        position.place(5, 4, Piece::Black).unwrap();
+        position.place(3, 5, Piece::White).unwrap();
+        let finished =
+            |host: Uuid, guest: Uuid, result: &str, black: u32, white: u32| game::ActiveModel {
+                id: ActiveValue::set(Uuid::now_v7()),
+                host: ActiveValue::set(host.to_string()),
+                guest: ActiveValue::set(guest.to_string()),
+                pending: ActiveValue::set(false),
+                ended: ActiveValue::set(true),
+                challenge: ActiveValue::set(None),
+                result: ActiveValue::set(Some(json!({
+                    "result": result,
+                    "winner": null,
+                    "termination": "normal",
+                    "score": { "black": black, "white": white },
+                    "points": 0,
+                    "total": black + white,
+                    "rating_deltas": null,
+                    "links": { "game": "", "export": "" },
+                }))),
+                settings: ActiveValue::set(json!({})),
+                state: ActiveValue::set(Some(serde_json::to_value(&position).unwrap())),
+                turn_started_at: ActiveValue::set(None),
+                opponent: ActiveValue::set(None),
+                arena: ActiveValue::set(None),
+                host_opponent: ActiveValue::set(None),
+                ended_at: ActiveValue::set(Some(Utc::now().fixed_offset())),
+                mode: ActiveValue::set(correspondence::LIVE.into()),
+                host_color: ActiveValue::set(String::from("black")),
+            };
+        let db = state.database.as_ref();
+        finished(a, b, "black", 40, 24).insert(db).await.unwrap();
+        let last = finished(b, a, "black", 33, 31).insert(db).await.unwrap();
+        let resp: Response<Map> = client.get(&url, &format!("/users/{user}/vs/{other}")).await;
+        assert_eq!(resp.message["played"], 2);
+        assert_eq!(resp.message["wins"], 1);
+        assert_eq!(resp.message["losses"], 1);
+        assert_eq!(resp.message["disc_differential"], 40 + 31 - 24 - 33);
+        assert_eq!(resp.message["average_length"], 2.0);
+        assert_eq!(resp.message["last_game"], json!(last.id));
+        // The record reads the other way round from the other side.
+        let resp: Response<Map> = client.get(&url, &format!("/users/{other}/vs/{user}")).await;
+        assert_eq!(resp.message["disc_differential"], 24 + 33 - 40 - 31);
+        assert_eq!(resp.message["opponent"]["username"], user.as_str());
+    }
+}
@@ -0,0 +1,214 @@
+use super::StringError;
+use crate::{
+    server::{
+        entities::puzzle,
+        extractors::{Admin, User},
+        puzzle::{self as puzzles, Streak},
+        state::AppState,
+        strings,
+        validation::{Valid, Validate, Validator},
+    },
+    Game,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{NaiveDate, Utc};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PuzzleRequest {
+    /// The moves leading to the puzzle's position from the starting one.
+    moves: Vec<(usize, usize)>,
+    /// Every move that solves the puzzle.
+    solutions: Vec<(usize, usize)>,
+}
+
+impl Validate for PuzzleRequest {
+    fn validate(&self, v: &mut Validator) {
+        match puzzles::position(&self.moves) {
+            Ok(mut game) => {
+                let legal = game.moves(game.turn());
+                v.ensure(
+                    "solutions",
+                    !self.solutions.is_empty()
+                        && self.solutions.iter().all(|square| legal.contains(square)),
+                    strings::INVALID_PUZZLE_SOLUTIONS,
+                );
+            }
+            Err(e) => {
+                v.reject("moves", e);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnswerRequest {
+    x: usize,
+    y: usize,
+}
+
+fn streak_json(streak: Streak, today: NaiveDate) -> serde_json::Value {
+    json!({
+        "current": streak.as_of(today),
+        "best": streak.best,
+        "last_solved": streak.last_solved,
+    })
+}
+
+/// Today's puzzle and the position it's played from. Days start at midnight UTC.
+async fn today(state: &AppState) -> Result<(NaiveDate, puzzle::Model, Game), StringError> {
+    let today = Utc::now().date_naive();
+    let puzzle = puzzles::daily(state, today)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(|| StringError(strings::PUZZLE_NOT_FOUND.into(), StatusCode::NOT_FOUND))?;
+    let game = puzzles::position(&puzzles::squares(&puzzle.moves))
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((today, puzzle, game))
+}
+
+/// Fetch today's puzzle: the position to solve and whose turn it is. Solutions are only
+/// revealed once answered.
+pub async fn daily(
+    State(state): State<Arc<AppState>>,
+    user: Option<User>,
+) -> Result<impl IntoResponse, Response> {
+    let (today, puzzle, game) = today(&state).await?;
+    let streak = match user {
+        Some(user) => Some(
+            puzzles::streak(&state, user.id)
+                .await
+                .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?,
+        ),
+        None => None,
+    };
+    Ok(super::Response::new(
+        json!({
+            "id": puzzle.id,
+            "day": today,
+            "game": game,
+            "turn": game.turn(),
+            "streak": streak.map(|streak| streak_json(streak, today)),
+        }),
+        StatusCode::OK,
+    ))
+}
+
+/// Answer today's puzzle. Only the first answer each day counts towards the authenticated
+/// user's streak.
+pub async fn answer(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Json(body): Json<AnswerRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let (today, puzzle, mut game) = today(&state).await?;
+    // The answer has to be a legal move before it can be right or wrong.
+    game.preview(body.x, body.y, game.turn())
+        .map_err(super::ApiError::from)?;
+    let solutions = puzzles::squares(&puzzle.solutions);
+    let correct = solutions.contains(&(body.x, body.y));
+    let streak = puzzles::answer(&state, user.id, today, puzzle.id, (body.x, body.y), correct)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(|| {
+            StringError(
+                strings::PUZZLE_ALREADY_ANSWERED.into(),
+                StatusCode::CONFLICT,
+            )
+        })?;
+    Ok(super::Response::new(
+        json!({
+            "correct": correct,
+            "solutions": solutions,
+            "streak": streak_json(streak, today),
+        }),
+        StatusCode::OK,
+    ))
+}
+
+/// Add a puzzle to the rotation. Its moves have to be legal and its solutions have to be legal
+/// moves in the position they lead to.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    _: Admin,
+    Valid(body): Valid<PuzzleRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let puzzle = puzzle::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        moves: ActiveValue::set(json!(body.moves)),
+        solutions: ActiveValue::set(json!(body.solutions)),
+        ..Default::default()
+    }
+    .insert(state.database.as_ref())
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(super::Response::new(
+        json!({ "id": puzzle.id }),
+        StatusCode::CREATED,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{
+        self,
+        entities::{prelude::Puzzle, puzzle},
+        handlers::{ApiError, Response},
+        strings,
+    };
+    use sea_orm::{ActiveValue, EntityTrait};
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn daily() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        // Make sure there's at least one puzzle to pick from.
+        Puzzle::insert(puzzle::ActiveModel {
+            id: ActiveValue::set(Uuid::now_v7()),
+            moves: ActiveValue::set(json!([[2, 3]])),
+            solutions: ActiveValue::set(json!([[2, 2]])),
+            ..Default::default()
+        })
+        .exec(state.database.as_ref())
+        .await
+        .unwrap();
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let client = Client::authenticated(&[&function!()], &url, true).await;
+        let resp: Response<Map> = client.get(&url, "/puzzles/daily").await;
+        assert_eq!(resp.code, 200);
+        assert_eq!(resp.message["streak"]["current"], 0);
+        let id: Uuid = resp.message["id"].as_str().unwrap().parse().unwrap();
+        let puzzle = Puzzle::find_by_id(id)
+            .one(state.database.as_ref())
+            .await
+            .unwrap()
+            .unwrap();
+        let (x, y) = server::puzzle::squares(&puzzle.solutions)[0];
+        let resp: Response<Map> = client
+            .post(&url, "/puzzles/daily/answer", json!({ "x": x, "y": y }))
+            .await;
+        assert_eq!(resp.code, 200);
+        assert_eq!(resp.message["correct"], true);
+        assert_eq!(resp.message["streak"]["current"], 1);
+        let resp: ApiError = client
+            .post(&url, "/puzzles/daily/answer", json!({ "x": x, "y": y }))
+            .await;
+        assert_eq!(resp.message, strings::PUZZLE_ALREADY_ANSWERED);
+    }
+}
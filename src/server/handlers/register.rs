@@ -4,45 +4,31 @@ use crate::server::{
     handlers::Response,
     state::AppState,
     strings, validate_password, validate_username,
+    validation::{Valid, Validate, Validator},
 };
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
     Argon2,
 };
-use axum::{
-    extract::{rejection::JsonRejection, State},
-    http::StatusCode,
-    response::IntoResponse,
-    Json,
-};
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use othello_api_types::Registration;
 use sea_orm::{ActiveValue, DbErr, EntityTrait, RuntimeErr};
-use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Registration {
-    username: String,
-    password: String,
+impl Validate for Registration {
+    fn validate(&self, v: &mut Validator) {
+        v.check("username", validate_username(&self.username))
+            .check("password", validate_password(&self.password));
+    }
 }
 
 /// Register a new user with the specified username and password.
 pub async fn register(
     State(state): State<Arc<AppState>>,
-    body: Result<Json<Registration>, JsonRejection>,
+    Valid(Registration { username, password }): Valid<Registration>,
 ) -> Result<impl IntoResponse, axum::response::Response> {
-    let Json(Registration { username, password }) = body.map_err(|e| {
-        StringError(
-            e.body_text().replace(
-                "Failed to deserialize the JSON body into the target type: ",
-                "",
-            ),
-            StatusCode::BAD_REQUEST,
-        )
-    })?;
-    validate_username(&username)?;
-    validate_password(&password)?;
     let id = Uuid::now_v7();
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
@@ -58,7 +44,14 @@ pub async fn register(
     let registration = member::ActiveModel {
         id: ActiveValue::set(id),
         username: ActiveValue::set(username),
-        password: ActiveValue::set(hashed),
+        password: ActiveValue::set(Some(hashed)),
+        created_at: ActiveValue::NotSet,
+        avatar: ActiveValue::NotSet,
+        timezone: ActiveValue::NotSet,
+        admin: ActiveValue::NotSet,
+        bot: ActiveValue::NotSet,
+        owner: ActiveValue::NotSet,
+        api_token: ActiveValue::NotSet,
     };
     let model = Member::insert(registration)
         .exec(state.database.as_ref())
@@ -82,16 +75,20 @@ pub async fn register(
 mod tests {
     use std::sync::Arc;
 
-    use crate::server::{self, handlers::Response, strings};
+    use crate::server::{
+        self,
+        handlers::{ApiError, Response},
+        strings,
+    };
     use axum::http::StatusCode;
     use test_utils::function;
 
     #[tokio::test]
     async fn taken() {
-        let database = sea_orm::Database::connect(server::TEST_DATABASE_URI)
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
             .await
             .unwrap();
-        let redis = redis::Client::open(server::TEST_REDIS_URI).unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
         let state = Arc::new(server::AppState::new(database, redis));
         let url = test_utils::init(crate::server::app(state)).await;
         let client = test_utils::Client::authenticated(&[&function!()], &url, true).await;
@@ -99,17 +96,17 @@ mod tests {
             "username": function!(),
             "password": function!()
         });
-        let resp: Response<String> = client.post(&url, "/register", credentials).await;
-        assert_eq!(resp.code, StatusCode::CONFLICT);
+        let resp: ApiError = client.post(&url, "/register", credentials).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
         assert_eq!(resp.message, strings::USERNAME_TAKEN);
     }
 
     #[tokio::test]
     async fn success() {
-        let database = sea_orm::Database::connect(server::TEST_DATABASE_URI)
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
             .await
             .unwrap();
-        let redis = redis::Client::open(server::TEST_REDIS_URI).unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
         let state = Arc::new(server::AppState::new(database, redis));
         let url = test_utils::init(crate::server::app(state)).await;
         let client = test_utils::Client::new();
@@ -120,4 +117,23 @@ mod tests {
         let resp: Response<test_utils::Map> = client.post(&url, "/register", credentials).await;
         assert_eq!(resp.code, StatusCode::CREATED);
     }
+
+    #[tokio::test]
+    async fn invalid() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let client = test_utils::Client::new();
+        let credentials = serde_json::json!({ "username": "ab", "password": "password" });
+        let resp: ApiError = client.post(&url, "/register", credentials).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.message, strings::USERNAME_TOO_SHORT);
+        // Both fields are reported at once.
+        assert_eq!(resp.details["fields"][0]["field"], "username");
+        assert_eq!(resp.details["fields"][1]["field"], "password");
+        assert_eq!(resp.details["fields"][1]["code"], "password_no_numeric");
+    }
 }
@@ -0,0 +1,89 @@
+use super::StringError;
+use crate::server::{
+    entities::report,
+    extractors::User,
+    helpers,
+    state::AppState,
+    strings,
+    validation::{Valid, Validate, Validator},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The longest reason (in characters) a report can give.
+pub const MAX_REASON_LENGTH: usize = 1000;
+/// The longest message (in characters) a report can quote.
+pub const MAX_QUOTE_LENGTH: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportRequest {
+    /// The username of the player being reported.
+    user: String,
+    reason: String,
+    /// The game the report concerns, if any.
+    game: Option<Uuid>,
+    /// The offending message, quoted by the reporter.
+    message: Option<String>,
+}
+
+impl Validate for ReportRequest {
+    fn validate(&self, v: &mut Validator) {
+        validate_reason(v, &self.reason);
+        v.ensure(
+            "message",
+            self.message
+                .as_ref()
+                .is_none_or(|message| message.chars().count() <= MAX_QUOTE_LENGTH),
+            strings::QUOTE_TOO_LONG,
+        );
+    }
+}
+
+/// Check the reason given for a report or ban.
+pub(super) fn validate_reason(v: &mut Validator, reason: &str) {
+    v.ensure("reason", !reason.trim().is_empty(), strings::REASON_MISSING)
+        .ensure(
+            "reason",
+            reason.chars().count() <= MAX_REASON_LENGTH,
+            strings::REASON_TOO_LONG,
+        );
+}
+
+/// Report a player (or something they said) to the moderators.
+pub async fn report(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Valid(body): Valid<ReportRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let target = helpers::get_user(&state, &body.user, true).await?;
+    if target.id == user.id {
+        return Err(
+            StringError(strings::REPORT_SELF.into(), StatusCode::BAD_REQUEST).into_response(),
+        );
+    }
+    let report = report::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        reporter: ActiveValue::set(user.id),
+        target: ActiveValue::set(target.id),
+        reason: ActiveValue::set(body.reason),
+        game: ActiveValue::set(body.game),
+        message: ActiveValue::set(body.message),
+        resolved: ActiveValue::set(false),
+        created_at: ActiveValue::NotSet,
+    }
+    .insert(state.database.as_ref())
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(super::Response::new(
+        json!({ "id": report.id }),
+        StatusCode::CREATED,
+    ))
+}
@@ -0,0 +1,155 @@
+use super::{user_summaries, StringError};
+use crate::server::{
+    entities::{
+        block::Column as BlockColumn,
+        member::Column as MemberColumn,
+        prelude::{Block, Member},
+    },
+    extractors::User,
+    pagination::Pagination,
+    state::AppState,
+    strings,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, Order, QueryFilter, QueryOrder};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchParams {
+    /// What the user typed, which usernames are matched against.
+    #[serde(default)]
+    q: String,
+}
+
+/// Find users by username, ignoring case: those whose usernames start with the query come
+/// first, followed by those whose usernames are merely similar to it (e.g. misspelled), the
+/// closest first. Users on either side of a block with the current user are left out.
+pub async fn search(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Query(params): Query<SearchParams>,
+    pagination: Pagination,
+) -> Result<impl IntoResponse, Response> {
+    let q = params.q.trim().to_lowercase();
+    if q.is_empty() {
+        return Err(StringError(
+            strings::SEARCH_QUERY_MISSING.into(),
+            StatusCode::BAD_REQUEST,
+        )
+        .into_response());
+    }
+    let blocks = Block::find()
+        .filter(
+            BlockColumn::Blocker
+                .eq(user.id)
+                .or(BlockColumn::Blocked.eq(user.id)),
+        )
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    let hidden = blocks.iter().map(|block| {
+        if block.blocker == user.id {
+            block.blocked
+        } else {
+            block.blocker
+        }
+    });
+    // Both kinds of match are served by the trigram index on `lower(username)`. Postgres
+    // numbers its placeholders.
+    let prefix = format!("{}%", escape_like(&q));
+    let query = Member::find()
+        .filter(Expr::cust_with_values(
+            "(lower(username) LIKE $1 OR lower(username) % $2)",
+            [prefix.clone(), q.clone()],
+        ))
+        .filter(MemberColumn::Id.is_not_in(hidden))
+        .order_by(
+            Expr::cust_with_values("lower(username) LIKE $1", [prefix]),
+            Order::Desc,
+        )
+        .order_by(
+            Expr::cust_with_values("similarity(lower(username), $1)", [q]),
+            Order::Desc,
+        )
+        .order_by_asc(MemberColumn::Username);
+    let (members, page) = pagination.fetch(state.database.as_ref(), query).await?;
+    let mut summaries = user_summaries(&state, &members).await;
+    let users: Vec<_> = members
+        .iter()
+        .filter_map(|member| summaries.remove(&member.id))
+        .collect();
+    Ok(super::Response::paginated(users, page, StatusCode::OK))
+}
+
+/// Escape the characters `LIKE` treats specially, so that they only match themselves.
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::server::{
+        self,
+        handlers::{ApiError, Response},
+        strings,
+    };
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use test_utils::{function, Client, Map};
+
+    #[tokio::test]
+    async fn search() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let user = function!();
+        let friend = format!("{user}::Friend");
+        let blocked = format!("{user}::blocked");
+        let client = Client::authenticated(&[&user, &friend, &blocked], &url, true).await;
+        client
+            .post::<_, Map>(&url, &format!("/users/{blocked}/block"), json!({}))
+            .await;
+        let usernames = |resp: Response<Vec<Map>>| -> Vec<String> {
+            resp.message
+                .iter()
+                .map(|user| user["username"].as_str().unwrap().to_string())
+                .collect()
+        };
+        // Prefixes match regardless of case, and closer matches come first.
+        let resp: Response<Vec<Map>> = client
+            .get(&url, &format!("/users/search?q={}", user.to_uppercase()))
+            .await;
+        let found = usernames(resp);
+        assert_eq!(found[..2], [user.clone(), friend.clone()]);
+        assert!(!found.contains(&blocked));
+        // Misspelled names still turn up.
+        let typo = user.replace("search", "saerch");
+        let resp: Response<Vec<Map>> = client.get(&url, &format!("/users/search?q={typo}")).await;
+        assert!(usernames(resp).contains(&friend));
+        let resp: ApiError = client.get(&url, "/users/search?q=%20").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.message, strings::SEARCH_QUERY_MISSING);
+    }
+
+    #[test]
+    fn escape_like() {
+        assert_eq!(super::escape_like(r"50%_off\"), r"50\%\_off\\");
+    }
+}
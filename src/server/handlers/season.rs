@@ -0,0 +1,158 @@
+use super::{user_summaries, StringError};
+use crate::server::{
+    entities::{prelude::SeasonRating, season, season_rating},
+    helpers,
+    pagination::Pagination,
+    season::{self as ladder, PLACEMENT_GAMES},
+    state::AppState,
+    strings, timestamp,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Look up a season by its number, or `current` for the one being played.
+async fn find(state: &AppState, season: &str) -> Result<season::Model, StringError> {
+    let not_found = || StringError(strings::SEASON_NOT_FOUND.into(), StatusCode::NOT_FOUND);
+    let season = if season == "current" {
+        Some(ladder::current(state).await)
+    } else {
+        let number = season.parse().map_err(|_| not_found())?;
+        ladder::by_number(state, number).await.transpose()
+    };
+    season
+        .ok_or_else(not_found)?
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Fetch the specified season.
+pub async fn season(
+    State(state): State<Arc<AppState>>,
+    Path(season): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let season = find(&state, &season).await?;
+    Ok(super::Response::new(
+        json!({
+            "number": season.number,
+            "starts_at": timestamp::rfc3339(&season.starts_at),
+            "ends_at": timestamp::rfc3339(&season.ends_at),
+            "archived": season.archived,
+            "placement_games": PLACEMENT_GAMES,
+        }),
+        StatusCode::OK,
+    ))
+}
+
+/// Fetch the standings of the specified season, highest rating first. Players only appear
+/// once they've finished their placement games.
+pub async fn standings(
+    State(state): State<Arc<AppState>>,
+    Path(season): Path<String>,
+    pagination: Pagination,
+) -> Result<impl IntoResponse, Response> {
+    let season = find(&state, &season).await?;
+    let ratings = SeasonRating::find()
+        .filter(season_rating::Column::Season.eq(season.id))
+        .filter(season_rating::Column::Games.gte(PLACEMENT_GAMES))
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let standings = ladder::standings(&season, ratings, Utc::now().fixed_offset());
+    let offset = pagination.offset;
+    let (standings, page) = pagination.slice(standings, pagination.limit());
+    let ids: Vec<_> = standings.iter().map(|standing| standing.member).collect();
+    let users = helpers::get_users_by_ids(&state, ids).await?;
+    let summaries = user_summaries(&state, users.values()).await;
+    let mut entries = Vec::with_capacity(standings.len());
+    for (rank, standing) in (offset + 1..).zip(&standings) {
+        let Some(user) = summaries.get(&standing.member) else {
+            continue;
+        };
+        entries.push(json!({
+            "rank": rank,
+            "user": user,
+            "rating": standing.rating,
+            "peak": standing.model.peak,
+            "games": standing.model.games,
+            "wins": standing.model.wins,
+            "losses": standing.model.losses,
+            "draws": standing.model.draws,
+            "tier": standing.tier,
+        }));
+    }
+    Ok(super::Response::paginated(entries, page, StatusCode::OK))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        server::{
+            self, handlers::Response, helpers, season::PLACEMENT_GAMES, summary,
+            summary::Termination, summary::Verdict,
+        },
+        Game, Piece,
+    };
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map};
+
+    #[tokio::test]
+    async fn standings() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        for played in 0..PLACEMENT_GAMES {
+            let resp: Response<Map> = client
+                .post(
+                    &url,
+                    "/game",
+                    json!({ "guest": guest, "settings": { "rated": true } }),
+                )
+                .await;
+            let id = resp.message["id"].as_str().unwrap().to_string();
+            other
+                .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+                .await;
+            let metadata = helpers::get_game(&state, &id).await.unwrap();
+            // The guest resigns every game.
+            let resign = Some(Verdict::Forfeit(Piece::White, Termination::Resignation));
+            let summary = summary::conclude(&state, &metadata, &Game::new(), resign, None)
+                .await
+                .unwrap();
+            let (black, white) = summary.rating_deltas.unwrap();
+            assert!(black > 0 && white < 0);
+            // Neither player shows up until their placement games are over.
+            let resp: Response<Map> = client.get(&url, &format!("/users/{host}")).await;
+            assert_eq!(
+                resp.message["user"]["rating"].is_null(),
+                played + 1 < PLACEMENT_GAMES
+            );
+        }
+        let resp: Response<Map> = client.get(&url, "/seasons/current").await;
+        assert_eq!(resp.message["archived"], false);
+        let resp: Response<Vec<Map>> = client
+            .get(&url, "/seasons/current/standings?limit=100")
+            .await;
+        let rank = |username: &str| {
+            resp.message
+                .iter()
+                .find(|standing| standing["user"]["username"] == username)
+                .map(|standing| standing["rank"].as_u64().unwrap())
+                .unwrap()
+        };
+        assert!(rank(&host) < rank(&guest));
+    }
+}
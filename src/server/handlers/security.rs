@@ -0,0 +1,66 @@
+use super::StringError;
+use crate::server::{
+    entities::{login_attempt::Column, prelude::LoginAttempt},
+    extractors::User,
+    state::AppState,
+    timestamp,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde_json::json;
+use std::sync::Arc;
+
+/// The maximum number of login attempts returned by the audit trail endpoint.
+const LOGIN_HISTORY_LIMIT: u64 = 50;
+
+/// Fetch the most recent login attempts made against the current user's account.
+pub async fn logins(
+    State(state): State<Arc<AppState>>,
+    user: User,
+) -> Result<impl IntoResponse, Response> {
+    let attempts = LoginAttempt::find()
+        .filter(Column::Member.eq(user.id))
+        .order_by_desc(Column::CreatedAt)
+        .limit(LOGIN_HISTORY_LIMIT)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let attempts: Vec<_> = attempts
+        .iter()
+        .map(|attempt| {
+            json!({
+                "timestamp": timestamp::rfc3339(&attempt.created_at),
+                "ip": attempt.ip,
+                "success": attempt.success,
+            })
+        })
+        .collect();
+    Ok(super::Response::new(attempts, StatusCode::OK))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::server::{self, handlers::Response};
+    use test_utils::{function, Client};
+
+    #[tokio::test]
+    async fn logins() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let client = Client::authenticated(&[&function!()], &url, true).await;
+        let resp: Response<Vec<test_utils::Map>> = client.get(&url, "/@me/security/logins").await;
+        assert_eq!(resp.message.len(), 1);
+        assert_eq!(resp.message[0]["success"], true);
+        assert_eq!(resp.message[0]["ip"], "127.0.0.1");
+    }
+}
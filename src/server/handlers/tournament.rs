@@ -0,0 +1,720 @@
+use super::{user_summaries, StringError};
+use crate::{
+    server::{
+        calendar::{self, Entry},
+        conduct,
+        entities::{
+            game,
+            prelude::{Game as GameModel, Tournament, TournamentEntrant, TournamentRound},
+            tournament, tournament_entrant, tournament_round,
+        },
+        extractors::User,
+        helpers,
+        state::AppState,
+        strings, timestamp,
+        tournament::{self as bracket, Format, Status, MAX_ROUNDS, MAX_SIZE, MIN_SIZE},
+        validation::{Valid, Validate, Validator},
+    },
+    settings::{ColorPolicy, SettingsError},
+    GameSettings,
+};
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveValue, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+use uuid::Uuid;
+
+/// The longest a tournament's name can be.
+pub const MAX_NAME_LENGTH: usize = 100;
+/// How many of a user's most recent tournament games their calendar feed holds.
+pub const SCHEDULE_GAMES: u64 = 200;
+/// The content type of calendar feeds.
+const CALENDAR: &str = "text/calendar; charset=utf-8";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TournamentRequest {
+    name: String,
+    /// The most players that can enter. The tournament starts by itself once it's full.
+    size: i32,
+    #[serde(default)]
+    format: Format,
+    /// How many rounds a Swiss tournament is played over. Left out, it's enough rounds for a
+    /// single player to win every game.
+    rounds: Option<i32>,
+    /// How every game in the tournament is played.
+    #[serde(default)]
+    settings: GameSettings,
+}
+
+impl Validate for TournamentRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.ensure(
+            "name",
+            !self.name.trim().is_empty() && self.name.chars().count() <= MAX_NAME_LENGTH,
+            strings::INVALID_TOURNAMENT_NAME,
+        )
+        .ensure(
+            "size",
+            (MIN_SIZE..=MAX_SIZE).contains(&self.size),
+            strings::INVALID_TOURNAMENT_SIZE,
+        )
+        .ensure(
+            "rounds",
+            self.rounds.is_none_or(|rounds| {
+                self.format == Format::Swiss && (1..=MAX_ROUNDS).contains(&rounds)
+            }),
+            strings::INVALID_TOURNAMENT_ROUNDS,
+        );
+        for (field, e) in self.settings.errors() {
+            v.reject(&format!("settings.{field}"), e);
+        }
+        // Pairings decide who plays black in each game.
+        if self.settings.color_policy != ColorPolicy::HostBlack {
+            v.reject(
+                "settings.color_policy",
+                SettingsError::Unsupported("tournament colour choice"),
+            );
+        }
+    }
+}
+
+fn not_found() -> StringError {
+    StringError(strings::TOURNAMENT_NOT_FOUND.into(), StatusCode::NOT_FOUND)
+}
+
+/// Create a tournament, with the current user as its host and first entrant.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Valid(body): Valid<TournamentRequest>,
+) -> Result<impl IntoResponse, Response> {
+    conduct::ensure_can_play(&state, user.id, &body.settings).await?;
+    let id = Uuid::now_v7();
+    let txn = state
+        .database
+        .begin()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Tournament::insert(tournament::ActiveModel {
+        id: ActiveValue::set(id),
+        name: ActiveValue::set(body.name.trim().to_string()),
+        host: ActiveValue::set(user.id),
+        size: ActiveValue::set(body.size),
+        status: ActiveValue::set(Status::Open.name().into()),
+        settings: ActiveValue::set(json!(body.settings)),
+        winner: ActiveValue::set(None),
+        created_at: ActiveValue::NotSet,
+        format: ActiveValue::set(body.format.name().into()),
+        rounds: ActiveValue::set(body.rounds),
+    })
+    .exec(&txn)
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    TournamentEntrant::insert(tournament_entrant::ActiveModel {
+        tournament: ActiveValue::set(id),
+        member: ActiveValue::set(user.id),
+        created_at: ActiveValue::NotSet,
+    })
+    .exec(&txn)
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    txn.commit()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(super::Response::new(
+        json!({ "id": id }),
+        StatusCode::CREATED,
+    ))
+}
+
+/// Enter the specified tournament. The bracket is drawn as soon as the last place is taken.
+pub async fn join(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+    let txn = state
+        .database
+        .begin()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let tournament = Tournament::find_by_id(id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(not_found)?;
+    ensure_open(&tournament)?;
+    let settings: GameSettings =
+        serde_json::from_value(tournament.settings.clone()).unwrap_or_default();
+    conduct::ensure_can_play(&state, user.id, &settings).await?;
+    let entered = TournamentEntrant::find_by_id((id, user.id))
+        .one(&txn)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    if entered.is_some() {
+        return Err(
+            StringError(strings::ALREADY_ENTERED.to_string(), StatusCode::CONFLICT).into_response(),
+        );
+    }
+    let entrants = entrants(&txn, id).await?;
+    if entrants >= u64::try_from(tournament.size).unwrap_or_default() {
+        return Err(
+            StringError(strings::TOURNAMENT_FULL.to_string(), StatusCode::CONFLICT).into_response(),
+        );
+    }
+    TournamentEntrant::insert(tournament_entrant::ActiveModel {
+        tournament: ActiveValue::set(id),
+        member: ActiveValue::set(user.id),
+        created_at: ActiveValue::NotSet,
+    })
+    .exec(&txn)
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let games = if entrants + 1 == u64::try_from(tournament.size).unwrap_or_default() {
+        bracket::start(&txn, tournament)
+            .await
+            .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+    } else {
+        vec![]
+    };
+    txn.commit()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    bracket::announce(&state, id, &games).await;
+    Ok(super::Response::new(json!({}), StatusCode::OK))
+}
+
+/// Draw the bracket for the specified tournament before it fills up. Only its host can.
+pub async fn start(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+    let txn = state
+        .database
+        .begin()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let tournament = Tournament::find_by_id(id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(not_found)?;
+    if tournament.host != user.id {
+        return Err(StringError(
+            strings::NOT_TOURNAMENT_HOST.to_string(),
+            StatusCode::FORBIDDEN,
+        )
+        .into_response());
+    }
+    ensure_open(&tournament)?;
+    if entrants(&txn, id).await? < u64::try_from(MIN_SIZE).unwrap_or_default() {
+        return Err(StringError(
+            strings::TOURNAMENT_TOO_SMALL.to_string(),
+            StatusCode::BAD_REQUEST,
+        )
+        .into_response());
+    }
+    let games = bracket::start(&txn, tournament)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    txn.commit()
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    bracket::announce(&state, id, &games).await;
+    Ok(super::Response::new(json!({}), StatusCode::OK))
+}
+
+fn ensure_open(tournament: &tournament::Model) -> Result<(), StringError> {
+    if Status::from_str(&tournament.status) == Ok(Status::Open) {
+        Ok(())
+    } else {
+        Err(StringError(
+            strings::TOURNAMENT_STARTED.to_string(),
+            StatusCode::CONFLICT,
+        ))
+    }
+}
+
+async fn entrants(txn: &sea_orm::DatabaseTransaction, id: Uuid) -> Result<u64, StringError> {
+    TournamentEntrant::find()
+        .filter(tournament_entrant::Column::Tournament.eq(id))
+        .count(txn)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Fetch the specified tournament, along with its entrants and its pairings so far, one list
+/// per round. Swiss tournaments also come with their standings.
+pub async fn tournament(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+    let tournament = Tournament::find_by_id(id)
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(not_found)?;
+    let entrants = bracket::entrants(state.database.as_ref(), id)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let pairings = bracket::pairings(state.database.as_ref(), id)
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let members = helpers::get_users_by_ids(&state, entrants.iter().copied()).await?;
+    let summaries = user_summaries(&state, members.values()).await;
+    let summary = |id: Option<Uuid>| id.and_then(|id| summaries.get(&id));
+    let mut rounds: Vec<Vec<serde_json::Value>> = vec![];
+    for pairing in &pairings {
+        let round = usize::try_from(pairing.round - 1).unwrap_or_default();
+        if rounds.len() <= round {
+            rounds.resize_with(round + 1, Vec::new);
+        }
+        rounds[round].push(json!({
+            "slot": pairing.slot,
+            "black": summary(Some(pairing.black)),
+            "white": summary(pairing.white),
+            "game": pairing.game,
+            "result": pairing.result,
+            "winner": summary(pairing.winner),
+            "discs": pairing.black_discs.zip(pairing.white_discs),
+        }));
+    }
+    let format = Format::from_str(&tournament.format).unwrap_or_default();
+    let standings = (format == Format::Swiss).then(|| {
+        bracket::standings(&entrants, &pairings)
+            .iter()
+            .map(|standing| {
+                json!({
+                    "user": summary(Some(standing.player)),
+                    "points": f64::from(standing.half_points) / 2.0,
+                    "buchholz": f64::from(standing.buchholz) / 2.0,
+                    "discs": standing.discs,
+                })
+            })
+            .collect::<Vec<_>>()
+    });
+    Ok(super::Response::new(
+        json!({
+            "id": tournament.id,
+            "name": tournament.name,
+            "format": tournament.format,
+            "host": summary(Some(tournament.host)),
+            "size": tournament.size,
+            "rounds": tournament.rounds,
+            "status": tournament.status,
+            "settings": tournament.settings,
+            "winner": summary(tournament.winner),
+            "entrants": entrants
+                .iter()
+                .filter_map(|&entrant| summary(Some(entrant)))
+                .collect::<Vec<_>>(),
+            "pairings": rounds,
+            "standings": standings,
+            "created_at": timestamp::rfc3339(&tournament.created_at),
+        }),
+        StatusCode::OK,
+    ))
+}
+
+/// Serve the games of the specified tournament as a calendar feed, with an event for each game
+/// paired so far, shown in the time zone of the tournament's host.
+pub async fn schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+    let tournament = Tournament::find_by_id(id)
+        .one(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?
+        .ok_or_else(not_found)?;
+    let pairings = bracket::pairings(state.database.as_ref(), id)
+        .await
+        .map_err(StringError::from)?;
+    let name = tournament.name.clone();
+    let host = helpers::get_user(&state, &tournament.host.to_string(), false).await?;
+    let entries = entries(&state, HashMap::from([(id, tournament)]), &pairings).await?;
+    Ok((
+        [(header::CONTENT_TYPE, CALENDAR)],
+        calendar::render(&name, &entries, Utc::now(), helpers::timezone(&host)),
+    ))
+}
+
+/// Serve the specified user's most recent tournament games, across every tournament they've
+/// played in, as a calendar feed shown in their time zone.
+pub async fn user_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let member = helpers::get_user(&state, &username, true).await?;
+    let pairings = TournamentRound::find()
+        .filter(tournament_round::Column::Game.is_not_null())
+        .filter(
+            tournament_round::Column::Black
+                .eq(member.id)
+                .or(tournament_round::Column::White.eq(member.id)),
+        )
+        // Game IDs are time-ordered, so this keeps the newest.
+        .order_by_desc(tournament_round::Column::Game)
+        .limit(SCHEDULE_GAMES)
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    let tournaments = Tournament::find()
+        .filter(tournament::Column::Id.is_in(pairings.iter().map(|pairing| pairing.tournament)))
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?
+        .into_iter()
+        .map(|tournament| (tournament.id, tournament))
+        .collect();
+    let entries = entries(&state, tournaments, &pairings).await?;
+    Ok((
+        [(header::CONTENT_TYPE, CALENDAR)],
+        calendar::render(
+            &format!("{}'s tournament games", member.username),
+            &entries,
+            Utc::now(),
+            helpers::timezone(&member),
+        ),
+    ))
+}
+
+/// The calendar entries for the games of the specified pairings, which belong to the specified
+/// tournaments. Pairings without a game (byes, and those still to be played) are left out.
+async fn entries(
+    state: &AppState,
+    tournaments: HashMap<Uuid, tournament::Model>,
+    pairings: &[tournament_round::Model],
+) -> Result<Vec<Entry>, StringError> {
+    let games: HashMap<_, _> = GameModel::find()
+        .filter(game::Column::Id.is_in(pairings.iter().filter_map(|pairing| pairing.game)))
+        .all(state.database.as_ref())
+        .await?
+        .into_iter()
+        .map(|game| (game.id, game))
+        .collect();
+    let players: Vec<Uuid> = pairings
+        .iter()
+        .flat_map(|pairing| [Some(pairing.black), pairing.white])
+        .flatten()
+        .collect();
+    let players = helpers::get_users_by_ids(state, players).await?;
+    let username = |id: Option<Uuid>| Some(players.get(&id?)?.username.as_str());
+    Ok(pairings
+        .iter()
+        .filter_map(|pairing| {
+            Some(Entry::game(
+                tournaments.get(&pairing.tournament)?,
+                pairing,
+                games.get(&pairing.game?)?,
+                username(Some(pairing.black))?,
+                username(pairing.white)?,
+            ))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        server::{
+            self,
+            handlers::{ApiError, Response},
+            helpers, packet, strings,
+            summary::{self, Termination, Verdict},
+        },
+        Game, Piece,
+    };
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+    use test_utils::{function, Client, Map, Socket};
+
+    #[tokio::test]
+    async fn single_elimination() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let players: Vec<_> = (1..=3).map(|i| format!("{}::{i}", function!())).collect();
+        let usernames: Vec<_> = players.iter().map(String::as_str).collect();
+        let host = Client::authenticated(&usernames, &url, true).await;
+        let resp: Response<Map> = host
+            .post(&url, "/tournaments", json!({ "name": "Weekly", "size": 4 }))
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let second = Client::authenticated(&[&players[1]], &url, false).await;
+        let resp: Response<Map> = second
+            .post(&url, &format!("/tournaments/{id}/join"), json!({}))
+            .await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let resp: ApiError = second
+            .post(&url, &format!("/tournaments/{id}/join"), json!({}))
+            .await;
+        assert_eq!(resp.message, strings::ALREADY_ENTERED);
+        let resp: ApiError = second
+            .post(&url, &format!("/tournaments/{id}/start"), json!({}))
+            .await;
+        assert_eq!(resp.message, strings::NOT_TOURNAMENT_HOST);
+        let third = Client::authenticated(&[&players[2]], &url, false).await;
+        third
+            .post::<_, Map>(&url, &format!("/tournaments/{id}/join"), json!({}))
+            .await;
+        // Three players can start a bracket of four, in which one of them gets a bye.
+        let resp: Response<Map> = host
+            .post(&url, &format!("/tournaments/{id}/start"), json!({}))
+            .await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let resp: Response<Map> = host.get(&url, &format!("/tournaments/{id}")).await;
+        assert_eq!(resp.message["status"], "running");
+        let first = resp.message["pairings"][0].as_array().unwrap().clone();
+        assert_eq!(first.len(), 2);
+        assert!(first[0]["game"].is_null());
+        assert_eq!(first[0]["winner"], first[0]["black"]);
+        // Play out the one game in the first round, then the final.
+        for round in 0..2 {
+            let resp: Response<Map> = host.get(&url, &format!("/tournaments/{id}")).await;
+            let pairing = resp.message["pairings"][round]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|pairing| pairing["winner"].is_null())
+                .unwrap()
+                .clone();
+            let game = pairing["game"].as_str().unwrap();
+            let metadata = helpers::get_game(&state, game).await.unwrap();
+            let resign = Some(Verdict::Forfeit(Piece::White, Termination::Resignation));
+            summary::conclude(&state, &metadata, &Game::new(), resign, None)
+                .await
+                .unwrap();
+        }
+        let resp: Response<Map> = host.get(&url, &format!("/tournaments/{id}")).await;
+        assert_eq!(resp.message["status"], "finished");
+        assert_eq!(
+            resp.message["pairings"][1][0]["winner"],
+            resp.message["winner"]
+        );
+        assert_eq!(
+            resp.message["winner"],
+            resp.message["pairings"][1][0]["black"]
+        );
+    }
+
+    #[tokio::test]
+    async fn swiss() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let players: Vec<_> = (1..=3).map(|i| format!("{}::{i}", function!())).collect();
+        let usernames: Vec<_> = players.iter().map(String::as_str).collect();
+        let host = Client::authenticated(&usernames, &url, true).await;
+        let resp: ApiError = host
+            .post(
+                &url,
+                "/tournaments",
+                json!({ "name": "Club night", "size": 3, "rounds": 2 }),
+            )
+            .await;
+        assert_eq!(resp.message, strings::INVALID_TOURNAMENT_ROUNDS);
+        let resp: Response<Map> = host
+            .post(
+                &url,
+                "/tournaments",
+                json!({ "name": "Club night", "size": 3, "format": "swiss", "rounds": 2 }),
+            )
+            .await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        for player in &players[1..] {
+            let client = Client::authenticated(&[player], &url, false).await;
+            client
+                .post::<_, Map>(&url, &format!("/tournaments/{id}/join"), json!({}))
+                .await;
+        }
+        // Draws stand in Swiss, so each round is a single game (and a bye).
+        for round in 0..2 {
+            let resp: Response<Map> = host.get(&url, &format!("/tournaments/{id}")).await;
+            let pairings = resp.message["pairings"][round].as_array().unwrap().clone();
+            assert_eq!(pairings.len(), 2);
+            assert_eq!(pairings[1]["result"], "bye");
+            let game = pairings[0]["game"].as_str().unwrap();
+            let metadata = helpers::get_game(&state, game).await.unwrap();
+            let draw = Some(Verdict::Draw(Termination::Stalling));
+            summary::conclude(&state, &metadata, &Game::new(), draw, None)
+                .await
+                .unwrap();
+        }
+        let resp: Response<Map> = host.get(&url, &format!("/tournaments/{id}")).await;
+        assert_eq!(resp.message["status"], "finished");
+        let standings = resp.message["standings"].as_array().unwrap();
+        assert_eq!(standings.len(), 3);
+        assert_eq!(resp.message["winner"], standings[0]["user"]);
+        assert!(standings[0]["points"].as_f64().unwrap() >= 1.5);
+    }
+
+    #[tokio::test]
+    async fn spectators() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let delay = Duration::from_millis(500);
+        let state = Arc::new(server::AppState::new(database, redis).with_spectator_delay(delay));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let players: Vec<_> = (1..=3).map(|i| format!("{}::{i}", function!())).collect();
+        let usernames: Vec<_> = players.iter().map(String::as_str).collect();
+        let host = Client::authenticated(&usernames, &url, true).await;
+        let resp: Response<Map> = host
+            .post(&url, "/tournaments", json!({ "name": "Weekly", "size": 2 }))
+            .await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let second = Client::authenticated(&[&players[1]], &url, false).await;
+        second
+            .post::<_, Map>(&url, &format!("/tournaments/{id}/join"), json!({}))
+            .await;
+        host.post::<_, Map>(&url, &format!("/tournaments/{id}/start"), json!({}))
+            .await;
+        let resp: Response<Map> = host.get(&url, &format!("/tournaments/{id}")).await;
+        let game = resp.message["pairings"][0][0]["game"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        // Somebody not playing in the tournament watches its game.
+        let spectator = Client::authenticated(&[&players[2]], &url, false).await;
+        let token = spectator
+            .cookie(&url, strings::SESSION_COOKIE_NAME)
+            .unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": game }, "t": token }))
+            .await;
+        let update = socket.recv_op(4).await;
+        assert!(update["d"]["game"]["history"]
+            .as_array()
+            .unwrap()
+            .is_empty());
+        // Moves reach them once the delay is up, and not before.
+        let metadata = helpers::get_game(&state, &game).await.unwrap();
+        let played = Instant::now();
+        packet::make_move(&state, &metadata, 5, 4, Piece::Black)
+            .await
+            .unwrap();
+        let update = socket.recv_op(4).await;
+        assert!(played.elapsed() >= delay);
+        assert_eq!(update["g"], game.as_str());
+        assert_eq!(update["d"]["game"]["history"].as_array().unwrap().len(), 1);
+        // Those who start watching later see the game as it was before the delay.
+        packet::make_move(&state, &metadata, 3, 5, Piece::White)
+            .await
+            .unwrap();
+        let mut late = Socket::connect(&url).await;
+        late.send(json!({ "op": 6, "d": { "type": "Identify" }, "t": token }))
+            .await;
+        late.recv_op(2).await;
+        late.send(json!({ "op": 3, "d": { "type": "Join", "id": game }, "t": token }))
+            .await;
+        let update = late.recv_op(4).await;
+        assert_eq!(update["d"]["game"]["history"].as_array().unwrap().len(), 1);
+        let update = late.recv_op(4).await;
+        assert_eq!(update["d"]["game"]["history"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn schedule() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let players: Vec<_> = (1..=2).map(|i| format!("{}::{i}", function!())).collect();
+        let usernames: Vec<_> = players.iter().map(String::as_str).collect();
+        let host = Client::authenticated(&usernames, &url, true).await;
+        let resp: Response<Map> = host
+            .post(
+                &url,
+                "/tournaments",
+                json!({ "name": "Weekly, spring", "size": 2 }),
+            )
+            .await;
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        // Nothing is scheduled before the bracket is drawn.
+        let resp = host
+            .get_raw(&url, &format!("/tournaments/{id}/schedule.ics"))
+            .await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert!(resp.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/calendar"));
+        let calendar = resp.text().await.unwrap();
+        assert!(calendar.contains("X-WR-CALNAME:Weekly\\, spring\r\n"));
+        assert!(!calendar.contains("BEGIN:VEVENT"));
+        let second = Client::authenticated(&[&players[1]], &url, false).await;
+        second
+            .post::<_, Map>(&url, &format!("/tournaments/{id}/join"), json!({}))
+            .await;
+        host.post::<_, Map>(&url, &format!("/tournaments/{id}/start"), json!({}))
+            .await;
+        let resp: Response<Map> = host.get(&url, &format!("/tournaments/{id}")).await;
+        let pairing = &resp.message["pairings"][0][0];
+        let game = pairing["game"].as_str().unwrap();
+        let (black, white) = (
+            pairing["black"]["username"].as_str().unwrap(),
+            pairing["white"]["username"].as_str().unwrap(),
+        );
+        // Each game shows up once it's paired, in the tournament's feed and its players'.
+        let summary = format!("SUMMARY:Weekly\\, spring: {black} vs {white}\r\n");
+        for feed in [
+            format!("/tournaments/{id}/schedule.ics"),
+            format!("/users/{}/schedule.ics", players[1]),
+        ] {
+            let calendar = host.get_raw(&url, &feed).await.text().await.unwrap();
+            // Long lines are folded, so unfold them before looking for anything.
+            let calendar = calendar.replace("\r\n ", "");
+            assert!(calendar.contains(&format!("UID:{game}@olly\r\n")));
+            assert!(calendar.contains(&summary));
+        }
+        // Feeds are shown in the time zone of whoever they're for: the host, for the
+        // tournament's.
+        second
+            .patch::<_, Map>(&url, "/@me", json!({ "timezone": "Asia/Tokyo" }))
+            .await;
+        let feed = format!("/users/{}/schedule.ics", players[1]);
+        let calendar = host.get_raw(&url, &feed).await.text().await.unwrap();
+        assert!(calendar.contains("X-WR-TIMEZONE:Asia/Tokyo\r\n"));
+        let feed = format!("/tournaments/{id}/schedule.ics");
+        let calendar = host.get_raw(&url, &feed).await.text().await.unwrap();
+        assert!(calendar.contains("X-WR-TIMEZONE:UTC\r\n"));
+        let resp: ApiError = host.get(&url, "/tournaments/nonsense/schedule.ics").await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,273 @@
+use super::StringError;
+use crate::server::{
+    entities::{
+        prelude::Webhook,
+        webhook::{self, Column},
+    },
+    extractors::User,
+    helpers,
+    state::AppState,
+    strings, timestamp,
+    validation::{Valid, Validate, Validator},
+    webhooks::{self, Event},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use reqwest::Url;
+use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How many webhooks each user can have.
+const MAX_WEBHOOKS: u64 = 5;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateWebhookRequest {
+    /// Where the webhook's deliveries are posted.
+    url: String,
+    /// The events the webhook is called for.
+    events: Vec<Event>,
+}
+
+impl Validate for CreateWebhookRequest {
+    fn validate(&self, v: &mut Validator) {
+        let url = Url::parse(&self.url).ok();
+        v.ensure(
+            "url",
+            url.is_some_and(|url| {
+                matches!(url.scheme(), "http" | "https") && url.host_str().is_some()
+            }),
+            strings::INVALID_WEBHOOK_URL,
+        )
+        .ensure(
+            "events",
+            !self.events.is_empty(),
+            strings::WEBHOOK_EVENTS_MISSING,
+        );
+    }
+}
+
+fn invalid_url() -> StringError {
+    StringError(strings::INVALID_WEBHOOK_URL.into(), StatusCode::BAD_REQUEST)
+}
+
+fn not_found() -> StringError {
+    StringError(strings::WEBHOOK_NOT_FOUND.into(), StatusCode::NOT_FOUND)
+}
+
+/// Register a webhook for the current user. The secret its deliveries are signed with is only
+/// ever shown in this response.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Valid(CreateWebhookRequest { url, mut events }): Valid<CreateWebhookRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let registered = Webhook::find()
+        .filter(Column::Member.eq(user.id))
+        .count(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    if registered >= MAX_WEBHOOKS {
+        return Err(
+            StringError(strings::TOO_MANY_WEBHOOKS.into(), StatusCode::CONFLICT).into_response(),
+        );
+    }
+    // Only the form of the URL could be validated up front.
+    let parsed = Url::parse(&url).map_err(|_| invalid_url())?;
+    if let Err(e) = state.webhooks.ensure_reachable(&parsed).await {
+        tracing::info!(member = %user.id, "Refused webhook: {e}");
+        return Err(invalid_url().into_response());
+    }
+    events.sort_by_key(|event| event.name());
+    events.dedup();
+    let secret = helpers::generate_key();
+    let model = Webhook::insert(webhook::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        member: ActiveValue::set(user.id),
+        url: ActiveValue::set(url),
+        secret: ActiveValue::set(secret.clone()),
+        events: ActiveValue::set(json!(events)),
+        created_at: ActiveValue::NotSet,
+    })
+    .exec_with_returning(state.database.as_ref())
+    .await
+    .map_err(StringError::from)?;
+    let mut resp = describe(&model);
+    resp["secret"] = json!(secret);
+    Ok(super::Response::new(resp, StatusCode::CREATED))
+}
+
+/// Fetch the current user's webhooks, oldest first.
+pub async fn webhooks(
+    State(state): State<Arc<AppState>>,
+    user: User,
+) -> Result<impl IntoResponse, Response> {
+    let webhooks: Vec<_> = Webhook::find()
+        .filter(Column::Member.eq(user.id))
+        .order_by_asc(Column::CreatedAt)
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?
+        .iter()
+        .map(describe)
+        .collect();
+    Ok(super::Response::new(webhooks, StatusCode::OK))
+}
+
+/// Delete one of the current user's webhooks, along with any deliveries still to be made.
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    user: User,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+    let deleted = Webhook::delete_many()
+        .filter(Column::Id.eq(id))
+        .filter(Column::Member.eq(user.id))
+        .exec(state.database.as_ref())
+        .await
+        .map_err(StringError::from)?;
+    if deleted.rows_affected == 0 {
+        return Err(not_found().into_response());
+    }
+    Ok(super::Response::new(json!({}), StatusCode::OK))
+}
+
+/// A webhook as its owner sees it, without its secret.
+fn describe(webhook: &webhook::Model) -> serde_json::Value {
+    json!({
+        "id": webhook.id,
+        "url": webhook.url,
+        "events": webhooks::events(webhook),
+        "created_at": timestamp::rfc3339(&webhook.created_at),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{
+        self,
+        handlers::{ApiError, ErrorCode, Response},
+        webhooks::{self, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER},
+    };
+    use axum::{
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use serde_json::json;
+    use std::{sync::Arc, time::Duration};
+    use test_utils::{function, Client, Map};
+    use tokio::sync::mpsc;
+
+    /// Somewhere for webhooks to be delivered, which hands over what it receives.
+    async fn receiver() -> (String, mpsc::UnboundedReceiver<(HeaderMap, String)>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State(tx): State<mpsc::UnboundedSender<(HeaderMap, String)>>,
+                     headers: HeaderMap,
+                     body: String| async move {
+                        let _ = tx.send((headers, body));
+                        StatusCode::NO_CONTENT
+                    },
+                ),
+            )
+            .with_state(tx);
+        (test_utils::init(app).await, rx)
+    }
+
+    #[tokio::test]
+    async fn webhooks() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        // The receiver listens on this host, which webhooks are usually kept from.
+        let state = server::AppState::new(database, redis).with_private_webhooks(true);
+        let url = test_utils::init(crate::server::app(Arc::new(state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        let (hook, mut deliveries) = receiver().await;
+        // Webhooks need somewhere to go and something to go for.
+        let resp: ApiError = other
+            .post(
+                &url,
+                "/@me/webhooks",
+                json!({ "url": "ftp://example.com", "events": [] }),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.details["fields"].as_array().unwrap().len(), 2);
+        assert_eq!(resp.code, ErrorCode::InvalidWebhookUrl);
+        let resp: Response<Map> = other
+            .post(
+                &url,
+                "/@me/webhooks",
+                json!({ "url": format!("{hook}/hook"), "events": ["challenge", "game_start"] }),
+            )
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let secret = resp.message["secret"].as_str().unwrap().to_string();
+        let resp: Response<Vec<Map>> = other.get(&url, "/@me/webhooks").await;
+        assert_eq!(resp.message.len(), 1);
+        assert!(resp.message[0].get("secret").is_none());
+        assert_eq!(
+            resp.message[0]["events"],
+            json!(["challenge", "game_start"])
+        );
+        // Being challenged calls the webhook, signed with its secret.
+        let resp: Response<Map> = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let game = resp.message["id"].as_str().unwrap().to_string();
+        let (headers, body) = tokio::time::timeout(Duration::from_secs(5), deliveries.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(headers[EVENT_HEADER], "challenge");
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            webhooks::sign(&secret, body.as_bytes())
+        );
+        let delivery: Map = serde_json::from_str(&body).unwrap();
+        assert_eq!(headers[DELIVERY_HEADER].to_str().unwrap(), delivery["id"]);
+        assert_eq!(delivery["event"], "challenge");
+        // Deliveries are timed like every other timestamp the API sends.
+        let created = delivery["created_at"].as_str().unwrap();
+        assert_eq!(created.len(), "2026-10-16T09:30:00.000Z".len());
+        assert!(created.ends_with('Z'));
+        assert_eq!(delivery["data"]["game"], game.as_str());
+        assert_eq!(delivery["data"]["host"], host.as_str());
+        // So does the game starting.
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{game}/accept"), json!({}))
+            .await;
+        let (headers, body) = tokio::time::timeout(Duration::from_secs(5), deliveries.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(headers[EVENT_HEADER], "game_start");
+        let delivery: Map = serde_json::from_str(&body).unwrap();
+        assert_eq!(delivery["data"]["game"], game.as_str());
+        // Deleted webhooks stop being called.
+        let resp: Response<Map> = other.delete(&url, &format!("/@me/webhooks/{id}")).await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let resp: ApiError = other.delete(&url, &format!("/@me/webhooks/{id}")).await;
+        assert_eq!(resp.code, ErrorCode::WebhookNotFound);
+        client
+            .post::<_, Map>(&url, "/game", json!({ "guest": guest }))
+            .await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(deliveries.try_recv().is_err());
+    }
+}
@@ -0,0 +1,674 @@
+use super::StringError;
+use crate::{
+    board::Board,
+    server::{
+        entities::{
+            finished_game::Column as FinishedGameColumn,
+            game,
+            member::Column as MemberColumn,
+            prelude::{FinishedGame, Member},
+        },
+        helpers, links,
+        pagination::{Order, Page, Pagination},
+        state::AppState,
+        strings,
+        summary::{Outcome, Summary},
+    },
+    Piece,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use rand::Rng;
+use redis::AsyncCommands;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, ConnectionTrait, EntityTrait, FromQueryResult, QueryFilter,
+    QueryOrder, Select, Statement,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use uuid::{NoContext, Timestamp, Uuid};
+
+/// How long (in seconds) widgets are cached for, both by us and by whoever embeds them.
+const WIDGET_TTL: u64 = 60;
+/// How many players the leaderboard widget lists.
+const LEADERBOARD_SIZE: u64 = 10;
+/// How far back the showcase looks for games to pick from.
+const SHOWCASE_WINDOW: Duration = Duration::from_hours(7 * 24);
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    Html,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WidgetParams {
+    #[serde(default)]
+    format: Format,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ShowcaseParams {
+    /// Only pick games that ended with at least this many pieces on the board.
+    #[serde(default)]
+    min_pieces: u32,
+}
+
+/// A finished game picked for showcasing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Showcase {
+    pub id: Uuid,
+    /// The usernames of the players, as `(black, white)`.
+    pub players: (String, String),
+    pub summary: Summary,
+    /// The final board, one string per row with `b` for black, `w` for white and `.` for
+    /// empty squares, or `None` if the server no longer has the game in memory.
+    pub board: Option<Vec<String>>,
+}
+
+/// A player's record across all of their finished games.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub username: String,
+    pub played: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+/// Embeddable leaderboard of the players with the most wins, `LEADERBOARD_SIZE` at a time
+/// unless a different page size is asked for. Sorting in ascending order lists the players
+/// with the fewest wins first. Each page is tallied by the database and cached on its own.
+pub async fn leaderboard(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<WidgetParams>,
+    pagination: Pagination,
+) -> Result<impl IntoResponse, Response> {
+    let limit = pagination.limit_or(LEADERBOARD_SIZE);
+    let key = format!(
+        "widget:leaderboard:{:?}:{}:{limit}",
+        pagination.order, pagination.offset
+    );
+    let (leaders, page) = cached(&state, &key, || async {
+        let (leaders, total) = leaders(&state, pagination.order, pagination.offset, limit).await?;
+        Ok((leaders, pagination.page(total, limit)))
+    })
+    .await?;
+    Ok(render(
+        params.format,
+        &leaders,
+        Some(page.clone()),
+        |html| {
+            html.push_str(
+                "<table><tr><th>#</th><th>Player</th><th>W</th><th>L</th><th>D</th></tr>",
+            );
+            for (position, leader) in (page.offset..).zip(&leaders) {
+                let rank = match pagination.order {
+                    Order::Asc => page.total - position,
+                    Order::Desc => position + 1,
+                };
+                let _ = write!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    rank,
+                    escape(&leader.username),
+                    leader.wins,
+                    leader.losses,
+                    leader.draws
+                );
+            }
+            html.push_str("</table>");
+        },
+    ))
+}
+
+/// Embeddable record of a single player.
+pub async fn user(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(params): Query<WidgetParams>,
+) -> Result<impl IntoResponse, Response> {
+    let member = helpers::get_user(&state, &name, true).await?;
+    let record = cached(&state, &format!("widget:user:{}", member.id), || async {
+        let record = records(&state, Some(member.id)).await?.remove(&member.id);
+        Ok(record.unwrap_or_else(|| Stats {
+            username: member.username.clone(),
+            ..Stats::default()
+        }))
+    })
+    .await?;
+    Ok(render(params.format, &record, None, |html| {
+        let _ = write!(
+            html,
+            "<p><strong>{}</strong></p><p>{} played: {} won, {} lost, {} drawn</p>",
+            escape(&record.username),
+            record.played,
+            record.wins,
+            record.losses,
+            record.draws
+        );
+    }))
+}
+
+/// Embeddable showcase of a recently finished game that was decided on the board, picked at
+/// random (e.g. for the homepage).
+pub async fn random_game(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<WidgetParams>,
+    Query(showcase): Query<ShowcaseParams>,
+) -> Result<impl IntoResponse, Response> {
+    let game = sample(&state, showcase.min_pieces)
+        .await?
+        .ok_or(StringError(
+            strings::INVALID_GAME_ID.into(),
+            StatusCode::NOT_FOUND,
+        ))?;
+    let summary = game
+        .result
+        .clone()
+        .and_then(|result| serde_json::from_value(result).ok())
+        .ok_or(StringError(
+            strings::INVALID_GAME_ID.into(),
+            StatusCode::NOT_FOUND,
+        ))?;
+    let (black, white) = match helpers::host_color(&game) {
+        Piece::Black => (&game.host, &game.guest),
+        Piece::White => (&game.guest, &game.host),
+    };
+    let black = helpers::get_user(&state, black, false).await?;
+    let white = helpers::get_user(&state, white, false).await?;
+    let board = {
+        let games = state.games.lock().expect("mutex was poisoned");
+        games.get(&game.id).map(|position| {
+            (0..Board::width())
+                .map(|y| {
+                    (0..Board::width())
+                        .map(|x| match position.board()[(x, y)] {
+                            Some(Piece::Black) => 'b',
+                            Some(Piece::White) => 'w',
+                            None => '.',
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+    };
+    let showcase = Showcase {
+        id: game.id,
+        players: (black.username, white.username),
+        summary,
+        board,
+    };
+    Ok(render(params.format, &showcase, None, |html| {
+        let _ = write!(
+            html,
+            "<p><strong>{}</strong> ({}) vs <strong>{}</strong> ({})</p>",
+            escape(&showcase.players.0),
+            showcase.summary.score.black,
+            escape(&showcase.players.1),
+            showcase.summary.score.white
+        );
+        if let Some(board) = &showcase.board {
+            html.push_str("<table style=\"border-collapse: collapse; background: green\">");
+            for row in board {
+                html.push_str("<tr>");
+                for square in row.chars() {
+                    let piece = match square {
+                        'b' => "&#9679;",
+                        'w' => "&#9675;",
+                        _ => "",
+                    };
+                    let _ = write!(
+                        html,
+                        "<td style=\"width: 1.5em; height: 1.5em; text-align: center; \
+                         border: 1px solid black\">{piece}</td>"
+                    );
+                }
+                html.push_str("</tr>");
+            }
+            html.push_str("</table>");
+        }
+    }))
+}
+
+/// Pick a random game that ended on the board with at least `min_pieces` pieces on it,
+/// preferring those created within `SHOWCASE_WINDOW`. Game IDs are version 7 UUIDs, which sort by
+/// creation time, so rather than shuffling every candidate this picks a random moment in the
+/// window and seeks to the first candidate created after it (or the last one before it, if
+/// there are none after).
+async fn sample(state: &AppState, min_pieces: u32) -> Result<Option<game::Model>, StringError> {
+    let candidates = || -> Select<FinishedGame> {
+        FinishedGame::find()
+            .filter(Expr::expr(Expr::cust("result->>'termination'")).eq("normal"))
+            .filter(Expr::expr(Expr::cust("(result->>'total')::int")).gte(min_pieces))
+    };
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let moment = now.saturating_sub(SHOWCASE_WINDOW.mul_f64(rand::thread_rng().gen()));
+    let pivot = Uuid::new_v7(Timestamp::from_unix(
+        NoContext,
+        moment.as_secs(),
+        moment.subsec_nanos(),
+    ));
+    let after = candidates()
+        .filter(FinishedGameColumn::Id.gte(pivot))
+        .order_by_asc(FinishedGameColumn::Id)
+        .one(state.database.as_ref())
+        .await?;
+    if after.is_some() {
+        return Ok(after.map(game::Model::from));
+    }
+    candidates()
+        .filter(FinishedGameColumn::Id.lt(pivot))
+        .order_by_desc(FinishedGameColumn::Id)
+        .one(state.database.as_ref())
+        .await
+        .map(|before| before.map(game::Model::from))
+        .map_err(StringError::from)
+}
+
+/// Every seat taken in a game that ended with a result, as who took it and whether they won or
+/// drew. Results are stored by colour, and the host's colour along with them.
+const SEATS: &str = "
+    SELECT host AS player, result->>'result' = host_color AS won,
+        result->>'result' = 'draw' AS drawn
+    FROM finished_game WHERE result->>'result' IN ('black', 'white', 'draw')
+    UNION ALL
+    SELECT guest, result->>'result' NOT IN (host_color, 'draw'), result->>'result' = 'draw'
+    FROM finished_game WHERE result->>'result' IN ('black', 'white', 'draw')";
+
+/// A row of the leaderboard, as tallied by the database.
+#[derive(Debug, FromQueryResult)]
+struct Leader {
+    username: String,
+    played: i64,
+    wins: i64,
+    losses: i64,
+    draws: i64,
+}
+
+impl From<Leader> for Stats {
+    fn from(leader: Leader) -> Self {
+        let count = |n: i64| usize::try_from(n).unwrap_or_default();
+        Self {
+            username: leader.username,
+            played: count(leader.played),
+            wins: count(leader.wins),
+            losses: count(leader.losses),
+            draws: count(leader.draws),
+        }
+    }
+}
+
+/// Tally a page of the leaderboard in the database, along with how many players are on it:
+/// those with the most wins first (and, between them, the fewest losses), or the other way
+/// round in ascending order.
+async fn leaders(
+    state: &AppState,
+    order: Order,
+    offset: u64,
+    limit: u64,
+) -> Result<(Vec<Stats>, u64), StringError> {
+    let db = state.database.as_ref();
+    let backend = db.get_database_backend();
+    let records = format!(
+        "WITH seats AS ({SEATS}), records AS (
+            SELECT member.username, COUNT(*) AS played,
+                COUNT(*) FILTER (WHERE won) AS wins,
+                COUNT(*) FILTER (WHERE NOT won AND NOT drawn) AS losses,
+                COUNT(*) FILTER (WHERE drawn) AS draws
+            FROM seats JOIN member ON member.id::text = seats.player
+            GROUP BY member.id, member.username
+        )"
+    );
+    let total = db
+        .query_one(Statement::from_string(
+            backend,
+            format!("{records} SELECT COUNT(*) AS total FROM records"),
+        ))
+        .await?
+        .map(|row| row.try_get::<i64>("", "total"))
+        .transpose()?
+        .unwrap_or_default();
+    let sort = match order {
+        Order::Asc => "wins ASC, losses DESC, username DESC",
+        Order::Desc => "wins DESC, losses ASC, username ASC",
+    };
+    let leaders = Leader::find_by_statement(Statement::from_sql_and_values(
+        backend,
+        format!("{records} SELECT * FROM records ORDER BY {sort} LIMIT $1 OFFSET $2"),
+        [
+            i64::try_from(limit).unwrap_or(i64::MAX).into(),
+            i64::try_from(offset).unwrap_or(i64::MAX).into(),
+        ],
+    ))
+    .all(db)
+    .await?;
+    Ok((
+        leaders.into_iter().map(Stats::from).collect(),
+        u64::try_from(total).unwrap_or_default(),
+    ))
+}
+
+/// Tally the records of every player with a finished game, or only of the specified one.
+pub(super) async fn records(
+    state: &AppState,
+    member: Option<Uuid>,
+) -> Result<HashMap<Uuid, Stats>, StringError> {
+    let mut query = FinishedGame::find().filter(FinishedGameColumn::Result.is_not_null());
+    if let Some(member) = member {
+        query = query.filter(
+            FinishedGameColumn::Host
+                .eq(member.to_string())
+                .or(FinishedGameColumn::Guest.eq(member.to_string())),
+        );
+    }
+    let games = query
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let mut records: HashMap<Uuid, Stats> = HashMap::new();
+    for game in games {
+        let Some(outcome) = game
+            .result
+            .as_ref()
+            .and_then(|result| serde_json::from_value::<Outcome>(result["result"].clone()).ok())
+        else {
+            continue;
+        };
+        let (black, white) = helpers::black_white(&game.into());
+        for (player, piece) in [(black, Outcome::Black), (white, Outcome::White)] {
+            let Some(player) = player else {
+                continue;
+            };
+            let record = records.entry(player).or_default();
+            record.played += 1;
+            match outcome {
+                Outcome::Draw => record.draws += 1,
+                _ if outcome == piece => record.wins += 1,
+                _ => record.losses += 1,
+            }
+        }
+    }
+    if let Some(member) = member {
+        records.retain(|id, _| *id == member);
+    }
+    let members = Member::find()
+        .filter(MemberColumn::Id.is_in(records.keys().copied()))
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    for member in members {
+        if let Some(record) = records.get_mut(&member.id) {
+            record.username = member.username;
+        }
+    }
+    Ok(records)
+}
+
+/// Serve a widget from the cache, building and caching it if it isn't there.
+async fn cached<T, F, Fut>(state: &AppState, key: &str, build: F) -> Result<T, StringError>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<T, StringError>>,
+{
+    let mut conn = state.redis.get().await.ok();
+    if let Some(conn) = conn.as_mut() {
+        let hit: Option<String> = conn.get(key).await.unwrap_or(None);
+        if let Some(value) = hit.and_then(|hit| serde_json::from_str(&hit).ok()) {
+            return Ok(value);
+        }
+    }
+    let value = build().await?;
+    if let Some(conn) = conn.as_mut() {
+        let serialized = serde_json::to_string(&value).unwrap();
+        let _ = conn.set_ex::<_, _, ()>(key, serialized, WIDGET_TTL).await;
+    }
+    Ok(value)
+}
+
+/// Render a widget in the requested format, with headers that let it be cached by whoever
+/// embeds it.
+fn render<T: Serialize>(
+    format: Format,
+    value: &T,
+    page: Option<Page>,
+    body: impl FnOnce(&mut String),
+) -> Response {
+    let cache = [(
+        header::CACHE_CONTROL,
+        format!("public, max-age={WIDGET_TTL}"),
+    )];
+    match format {
+        Format::Json => match page {
+            Some(page) => (
+                cache,
+                super::Response::paginated(value, page, StatusCode::OK),
+            ),
+            None => (cache, super::Response::new(value, StatusCode::OK)),
+        }
+        .into_response(),
+        Format::Html => {
+            let site = links::site_url();
+            let mut html = String::from(
+                "<!DOCTYPE html><html><head><meta charset=\"utf-8\"></head>\
+                 <body style=\"font-family: sans-serif\">",
+            );
+            body(&mut html);
+            let _ = write!(
+                html,
+                "<p><a href=\"{}\" target=\"_blank\">Play Othello online</a></p></body></html>",
+                escape(&site)
+            );
+            (cache, Html(html)).into_response()
+        }
+    }
+}
+
+/// Escape text for safe inclusion in HTML.
+fn escape(s: &str) -> String {
+    s.chars()
+        .fold(String::with_capacity(s.len()), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::server::{
+        self, correspondence,
+        entities::{game, prelude::FinishedGame},
+        handlers::{ApiError, Response},
+        helpers, strings,
+    };
+    use axum::http::StatusCode;
+    use chrono::Utc;
+    use sea_orm::{sea_query::Expr, ActiveModelTrait, ActiveValue, EntityTrait, QueryFilter};
+    use serde_json::json;
+    use test_utils::{function, Client, Map};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn user() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let name = format!("{}<b>", function!());
+        // Widgets are public, so registering is the only thing the client needs to do.
+        Client::authenticated(&[&name], &url, true).await;
+        let client = Client::new();
+        let resp: ApiError = client.get(&url, "/widgets/user/nobody").await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.message, strings::INVALID_USERNAME);
+        let resp: Response<Map> = client.get(&url, &format!("/widgets/user/{name}")).await;
+        assert_eq!(resp.code, StatusCode::OK);
+        assert_eq!(resp.message["username"], name.as_str());
+        assert_eq!(resp.message["played"], 0);
+        let resp = client
+            .get_raw(&url, &format!("/widgets/user/{name}?format=html"))
+            .await;
+        assert_eq!(resp.headers()["cache-control"], "public, max-age=60");
+        assert!(resp.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+        let html = resp.text().await.unwrap();
+        assert!(html.contains("&lt;b&gt;"));
+        assert!(!html.contains("<b>"));
+    }
+
+    #[tokio::test]
+    async fn leaderboard() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let client = Client::new();
+        let resp: Response<Vec<Map>> = client.get(&url, "/widgets/leaderboard").await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let page = resp.page.unwrap();
+        assert_eq!(page.limit, super::LEADERBOARD_SIZE);
+        assert!(u64::try_from(resp.message.len()).unwrap() <= page.limit);
+        let wins: Vec<_> = resp.message.iter().map(|l| l["wins"].as_u64()).collect();
+        assert!(wins.windows(2).all(|w| w[0] >= w[1]));
+        let resp: Response<Vec<Map>> = client
+            .get(&url, "/widgets/leaderboard?order=asc&limit=5&offset=1")
+            .await;
+        let page = resp.page.unwrap();
+        assert_eq!((page.offset, page.limit), (1, 5));
+        assert!(resp.message.len() <= 5);
+        let wins: Vec<_> = resp.message.iter().map(|l| l["wins"].as_u64()).collect();
+        assert!(wins.windows(2).all(|w| w[0] <= w[1]));
+        let resp: ApiError = client.get(&url, "/widgets/leaderboard?limit=1000").await;
+        assert_eq!(resp.message, strings::INVALID_PAGE_SIZE);
+        let resp = client
+            .get_raw(&url, "/widgets/leaderboard?format=html")
+            .await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert!(resp.text().await.unwrap().contains("<table>"));
+    }
+
+    #[tokio::test]
+    async fn random_game() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        Client::authenticated(&[&host, &guest], &url, true).await;
+        let client = Client::new();
+        // No game can finish with more pieces than there are squares.
+        let resp: ApiError = client.get(&url, "/games/random?min_pieces=65").await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        // Only a full board qualifies.
+        let id = Uuid::now_v7();
+        game::ActiveModel {
+            id: ActiveValue::set(id),
+            host: ActiveValue::set(
+                helpers::get_user(&state, &host, true)
+                    .await
+                    .unwrap()
+                    .id
+                    .to_string(),
+            ),
+            guest: ActiveValue::set(
+                helpers::get_user(&state, &guest, true)
+                    .await
+                    .unwrap()
+                    .id
+                    .to_string(),
+            ),
+            pending: ActiveValue::set(false),
+            ended: ActiveValue::set(true),
+            challenge: ActiveValue::set(None),
+            result: ActiveValue::set(Some(json!({
+                "result": "black",
+                "winner": host,
+                "termination": "normal",
+                "score": { "black": 40, "white": 24 },
+                "points": 40,
+                "total": 64,
+                "rating_deltas": null,
+                "links": { "game": format!("/game/{id}"), "export": format!("/game/{id}/export") },
+            }))),
+            settings: ActiveValue::set(json!({})),
+            state: ActiveValue::set(None),
+            turn_started_at: ActiveValue::set(None),
+            opponent: ActiveValue::set(None),
+            arena: ActiveValue::set(None),
+            host_opponent: ActiveValue::set(None),
+            ended_at: ActiveValue::set(Some(Utc::now().fixed_offset())),
+            mode: ActiveValue::set(correspondence::LIVE.into()),
+            host_color: ActiveValue::set(String::from("black")),
+        }
+        .insert(state.database.as_ref())
+        .await
+        .unwrap();
+        // Other tests finish full boards too, and any of them can be picked, so each gets a
+        // position to show.
+        let candidates = FinishedGame::find()
+            .filter(Expr::expr(Expr::cust("(result->>'total')::int")).gte(64))
+            .all(state.database.as_ref())
+            .await
+            .unwrap();
+        for candidate in &candidates {
+            state
+                .games
+                .lock()
+                .unwrap()
+                .insert(candidate.id, crate::Game::new());
+        }
+        let resp: Response<Map> = client.get(&url, "/games/random?min_pieces=64").await;
+        assert_eq!(resp.code, StatusCode::OK);
+        let picked = candidates
+            .into_iter()
+            .find(|candidate| resp.message["id"] == candidate.id.to_string())
+            .unwrap();
+        let (black, white) = helpers::black_white(&picked.into());
+        let black = helpers::get_user(&state, &black.unwrap().to_string(), false)
+            .await
+            .unwrap();
+        let white = helpers::get_user(&state, &white.unwrap().to_string(), false)
+            .await
+            .unwrap();
+        assert_eq!(resp.message["players"][0], black.username.as_str());
+        assert_eq!(resp.message["players"][1], white.username.as_str());
+        assert_eq!(resp.message["summary"]["total"], 64);
+        assert_eq!(resp.message["summary"]["termination"], "normal");
+        assert_eq!(resp.message["board"][3], "...wb...");
+        let resp = client
+            .get_raw(&url, "/games/random?min_pieces=64&format=html")
+            .await;
+        assert!(resp.text().await.unwrap().contains("<table"));
+    }
+}
@@ -1,14 +1,29 @@
-use crate::server::{
-    entities::{game, member, prelude::*, session},
-    handlers::StringError,
-    strings, AppState, PasswordHash, StatusCode,
+use crate::{
+    server::{
+        archive, bots,
+        entities::{block, game, login_attempt, member, prelude::*, session},
+        handlers::StringError,
+        strings, AppState, PasswordHash, StatusCode,
+    },
+    settings::ColorPolicy,
+    GameSettings, Piece,
 };
 use argon2::{Argon2, PasswordVerifier};
-use sea_orm::{sea_query::OnConflict, ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+use base64::Engine;
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rand::RngCore;
+use redis::AsyncCommands;
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, sea_query::OnConflict, ActiveModelTrait, ActiveValue,
+    ColumnTrait, DbErr, EntityTrait, QueryFilter, QuerySelect, RuntimeErr,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, future::Future, time::Duration};
 use uuid::Uuid;
 
 /// Hashes a password string.
-fn hash(s: &str) -> Result<PasswordHash, StringError> {
+fn hash(s: &str) -> Result<PasswordHash<'_>, StringError> {
     PasswordHash::new(s).map_err(|_| {
         StringError(
             strings::INVALID_PASSWORD_FORMAT.to_string(),
@@ -17,19 +32,115 @@ fn hash(s: &str) -> Result<PasswordHash, StringError> {
     })
 }
 
-/// Fetch a user by their username or ID.
+/// How long users stay in the cache after being fetched from the database.
+const USER_CACHE_TTL: u64 = 300;
+
+/// What's kept of a user in the cache. Their password hash and API token are left out, so
+/// that they never end up anywhere but the database.
+#[derive(Serialize, Deserialize)]
+struct CachedUser {
+    id: Uuid,
+    username: String,
+    created_at: DateTimeWithTimeZone,
+    avatar: Option<String>,
+    timezone: String,
+    admin: bool,
+    #[serde(default)]
+    bot: bool,
+    #[serde(default)]
+    owner: Option<Uuid>,
+}
+
+impl From<member::Model> for CachedUser {
+    fn from(user: member::Model) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            created_at: user.created_at,
+            avatar: user.avatar,
+            timezone: user.timezone,
+            admin: user.admin,
+            bot: user.bot,
+            owner: user.owner,
+        }
+    }
+}
+
+impl From<CachedUser> for member::Model {
+    fn from(user: CachedUser) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            password: None,
+            created_at: user.created_at,
+            avatar: user.avatar,
+            timezone: user.timezone,
+            admin: user.admin,
+            bot: user.bot,
+            owner: user.owner,
+            api_token: None,
+        }
+    }
+}
+
+/// Fetch a user by their username or ID, from the cache if they're in it. Users never come
+/// back with their password hash, wherever they're found; [`get_password`] fetches it.
 pub async fn get_user(
     state: &AppState,
     s: &str,
     username: bool,
 ) -> Result<member::Model, StringError> {
+    // If the cache is unavailable, go to the database every time instead.
+    let mut conn = state.redis.get().await.ok();
+    if let Some(conn) = conn.as_mut() {
+        let id = if username {
+            conn.get::<_, Option<String>>(format!("user:name:{s}"))
+                .await
+                .ok()
+                .flatten()
+        } else {
+            Some(s.to_string())
+        };
+        if let Some(id) = id {
+            let cached = conn
+                .get::<_, Option<String>>(format!("user:{id}"))
+                .await
+                .ok()
+                .flatten()
+                .and_then(|cached| serde_json::from_str::<CachedUser>(&cached).ok())
+                .map(member::Model::from);
+            // Names are dropped from the cache when they change, but check anyway so that a
+            // stale entry can't hand out someone else's account.
+            if let Some(user) = cached.filter(|user| !username || user.username == s) {
+                return Ok(user);
+            }
+        }
+    }
     let query = if username {
         Member::find().filter(member::Column::Username.eq(s))
     } else {
         Member::find_by_id(Uuid::try_from(s).unwrap())
     };
     match query.one(state.database.as_ref()).await {
-        Ok(Some(user)) => Ok(user),
+        Ok(Some(user)) => {
+            let user = CachedUser::from(user);
+            if let Some(conn) = conn.as_mut() {
+                let _ = redis::pipe()
+                    .set_ex(
+                        format!("user:{}", user.id),
+                        serde_json::to_string(&user).unwrap(),
+                        USER_CACHE_TTL,
+                    )
+                    .set_ex(
+                        format!("user:name:{}", user.username),
+                        user.id.to_string(),
+                        USER_CACHE_TTL,
+                    )
+                    .query_async::<_, ()>(conn)
+                    .await;
+            }
+            Ok(user.into())
+        }
         Ok(None) => Err(StringError(
             strings::INVALID_USERNAME.to_string(),
             StatusCode::NOT_FOUND,
@@ -41,7 +152,68 @@ pub async fn get_user(
     }
 }
 
-/// Fetch a game by its ID.
+/// Fetch the password hash of the specified user, straight from the database. Users who
+/// signed up through an identity provider and never set a password don't have one.
+pub async fn get_password(state: &AppState, id: Uuid) -> Result<Option<String>, StringError> {
+    Member::find_by_id(id)
+        .select_only()
+        .column(member::Column::Password)
+        .into_tuple::<Option<String>>()
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(StringError(
+            strings::INVALID_USERNAME.to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+}
+
+/// Drop the specified user from the cache, so that changes to their row are seen by the next
+/// lookup. Must be given the user as they were before the change, so that their old username
+/// goes too.
+pub async fn forget_user(state: &AppState, user: &member::Model) {
+    if let Ok(mut conn) = state.redis.get().await {
+        let _ = conn
+            .del::<_, ()>(&[
+                format!("user:{}", user.id),
+                format!("user:name:{}", user.username),
+            ])
+            .await;
+    }
+}
+
+/// Fetch every user with one of the specified IDs in a single query, keyed by their ID. IDs
+/// that don't belong to anyone are left out.
+pub async fn get_users_by_ids(
+    state: &AppState,
+    ids: impl IntoIterator<Item = Uuid>,
+) -> Result<HashMap<Uuid, member::Model>, StringError> {
+    let mut ids: Vec<_> = ids.into_iter().collect();
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    Member::find()
+        .filter(member::Column::Id.is_in(ids))
+        .all(state.database.as_ref())
+        .await
+        .map(|users| users.into_iter().map(|user| (user.id, user)).collect())
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Take a user out of the results of `get_users_by_ids`, failing the same way `get_user`
+/// does if they weren't found.
+pub fn found_user<'a>(
+    users: &'a HashMap<Uuid, member::Model>,
+    id: &Uuid,
+) -> Result<&'a member::Model, StringError> {
+    users
+        .get(id)
+        .ok_or_else(|| StringError(strings::INVALID_USERNAME.to_string(), StatusCode::NOT_FOUND))
+}
+
+/// Fetch a game by its ID, whether or not it's been archived.
 pub async fn get_game(state: &AppState, id: &str) -> Result<game::Model, StringError> {
     let id = Uuid::parse_str(id).map_err(|_| {
         StringError(
@@ -49,7 +221,7 @@ pub async fn get_game(state: &AppState, id: &str) -> Result<game::Model, StringE
             StatusCode::BAD_REQUEST,
         )
     })?;
-    match Game::find_by_id(id).one(state.database.as_ref()).await {
+    match archive::find(state.database.as_ref(), id).await {
         Ok(Some(game)) => Ok(game),
         Ok(None) => Err(StringError(
             strings::INVALID_GAME_ID.to_string(),
@@ -62,13 +234,109 @@ pub async fn get_game(state: &AppState, id: &str) -> Result<game::Model, StringE
     }
 }
 
-/// Fetch an authentication session by its token.
+/// Read the settings that the specified game is played with.
+pub fn game_settings(game: &game::Model) -> GameSettings {
+    // Games created before settings existed store an empty object, which means the defaults.
+    serde_json::from_value(game.settings.clone()).unwrap_or_default()
+}
+
+/// When the game (or anything else) with the specified time-ordered ID was created, if the ID
+/// says.
+pub fn created_at(id: Uuid) -> Option<DateTime<Utc>> {
+    let (secs, nanos) = id.get_timestamp()?.to_unix();
+    Utc.timestamp_opt(secs.try_into().ok()?, nanos).single()
+}
+
+/// The time zone the specified user set as their preference. Preferences are checked as
+/// they're set, so only a zone since dropped from the tz database falls back to UTC.
+pub fn timezone(user: &member::Model) -> Tz {
+    user.timezone.parse().unwrap_or(Tz::UTC)
+}
+
+/// The colour the host of a game with the specified settings plays, tossing a coin for it if
+/// colours are assigned at random.
+pub fn draw_host_color(settings: &GameSettings) -> Piece {
+    match settings.color_policy {
+        ColorPolicy::HostBlack => Piece::Black,
+        ColorPolicy::Random if rand::random() => Piece::Black,
+        ColorPolicy::GuestBlack | ColorPolicy::Random => Piece::White,
+    }
+}
+
+/// The name a colour is stored by, as the colour a game's host plays.
+pub fn color_name(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Black => "black",
+        Piece::White => "white",
+    }
+}
+
+/// The colour the host of the specified game plays. Invitations are settled as they're
+/// accepted, so until then the host is taken to play black.
+pub fn host_color(game: &game::Model) -> Piece {
+    if game.host_color == color_name(Piece::White) {
+        Piece::White
+    } else {
+        Piece::Black
+    }
+}
+
+/// The players of the specified game, black and then white.
+pub fn black_white(game: &game::Model) -> (Option<Uuid>, Option<Uuid>) {
+    let host = Uuid::parse_str(&game.host).ok();
+    let guest = Uuid::parse_str(&game.guest).ok();
+    match host_color(game) {
+        Piece::Black => (host, guest),
+        Piece::White => (guest, host),
+    }
+}
+
+/// Save the position of a game in play to its row, along with when the current turn started,
+/// so that the game can be picked up where it was even if the cache is lost.
+pub async fn save_position(
+    state: &AppState,
+    id: Uuid,
+    position: &crate::Game,
+) -> Result<(), StringError> {
+    game::ActiveModel {
+        id: ActiveValue::unchanged(id),
+        state: ActiveValue::set(Some(serde_json::to_value(position).unwrap())),
+        turn_started_at: ActiveValue::set(Some(Utc::now().fixed_offset())),
+        ..Default::default()
+    }
+    .update(state.database.as_ref())
+    .await
+    .map(|_| ())
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Fetch the ID of the user an authentication session belongs to, by its token. Bots pass
+/// their API token, after [`bots::TOKEN_SCHEME`], instead.
 pub async fn get_session(state: &AppState, token: &str) -> Result<String, StringError> {
+    // Bots present their API token instead of a session.
+    if let Some(token) = token.strip_prefix(bots::TOKEN_SCHEME) {
+        return bots::authenticate(state, token)
+            .await
+            .map(|id| id.to_string());
+    }
     match Session::find()
         .filter(session::Column::Key.eq(token))
         .one(state.database.as_ref())
         .await
     {
+        // Sessions older than the configured lifetime are as good as logged out. Lifetimes too
+        // long for chrono to represent never run out.
+        Ok(Some(session))
+            if state
+                .session_ttl
+                .and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+                .is_some_and(|ttl| Utc::now().signed_duration_since(session.created_at) > ttl) =>
+        {
+            Err(StringError(
+                strings::INVALID_TOKEN.into(),
+                StatusCode::FORBIDDEN,
+            ))
+        }
         Ok(Some(session)) => Ok(session.id.to_string()),
         Ok(None) => Err(StringError(
             strings::INVALID_TOKEN.into(),
@@ -81,6 +349,16 @@ pub async fn get_session(state: &AppState, token: &str) -> Result<String, String
     }
 }
 
+/// Generate a random key suitable for use as a session token or other secret.
+pub fn generate_key() -> String {
+    let mut dst = [0; 32];
+    // ThreadRng satisfies the CryptoRng trait, so
+    // it should be cryptographically secure. TODO:
+    // look into a more secure key generation method.
+    rand::thread_rng().fill_bytes(&mut dst);
+    base64::prelude::BASE64_STANDARD.encode(dst)
+}
+
 /// Create a new authentication session for the specified user.
 pub async fn create_session(
     state: &AppState,
@@ -90,10 +368,11 @@ pub async fn create_session(
     Session::insert(session::ActiveModel {
         id: ActiveValue::set(user.id),
         key: ActiveValue::set(key.clone()),
+        created_at: ActiveValue::set(Utc::now().fixed_offset()),
     })
     .on_conflict(
         OnConflict::column(session::Column::Id)
-            .update_column(session::Column::Key)
+            .update_columns([session::Column::Key, session::Column::CreatedAt])
             .to_owned(),
     )
     .exec(state.database.as_ref())
@@ -124,10 +403,175 @@ pub async fn delete_session(state: &AppState, token: String) -> Result<(), Strin
     }
 }
 
-/// Verifies that the provided password matches the actual password.
-pub fn ensure_valid_password(actual: &str, provided: &str) -> Result<(), StringError> {
+/// Delete every session older than the specified lifetime, returning how many there were.
+/// They're refused anyway, but would otherwise stay until their users next log in.
+pub async fn delete_expired_sessions(state: &AppState, ttl: Duration) -> Result<u64, DbErr> {
+    let Some(cutoff) = chrono::Duration::from_std(ttl)
+        .ok()
+        .and_then(|ttl| Utc::now().checked_sub_signed(ttl))
+    else {
+        return Ok(0);
+    };
+    Session::delete_many()
+        .filter(session::Column::CreatedAt.lt(cutoff.fixed_offset()))
+        .exec(state.database.as_ref())
+        .await
+        .map(|result| result.rows_affected)
+}
+
+/// Verifies that the provided password matches the actual password. Accounts without a
+/// password (i.e. those created through an identity provider) never match.
+pub fn ensure_valid_password(actual: Option<&str>, provided: &str) -> Result<(), StringError> {
+    let Some(actual) = actual else {
+        return Err(StringError(
+            strings::INVALID_PASSWORD.to_string(),
+            StatusCode::FORBIDDEN,
+        ));
+    };
     let hashed = hash(actual)?;
     Argon2::default()
         .verify_password(provided.as_bytes(), &hashed)
         .map_err(|_| StringError(strings::INVALID_PASSWORD.to_string(), StatusCode::FORBIDDEN))
 }
+
+/// Ensures that the specified user is not currently locked out of their account.
+pub async fn ensure_not_locked(state: &AppState, user: &member::Model) -> Result<(), StringError> {
+    // If the cache is unavailable, lockouts can't be enforced. Fail open rather than
+    // preventing every user from logging in.
+    let Ok(mut conn) = state.redis.get().await else {
+        return Ok(());
+    };
+    if conn
+        .exists(format!("login:lockout:{}", user.id))
+        .await
+        .unwrap_or(false)
+    {
+        return Err(StringError(
+            strings::ACCOUNT_LOCKED.into(),
+            StatusCode::TOO_MANY_REQUESTS,
+        ));
+    }
+    Ok(())
+}
+
+/// Count a failed login attempt against the specified user, locking their account once the
+/// configured number of consecutive failures have been recorded.
+pub async fn register_login_failure(state: &AppState, user: &member::Model) {
+    let Ok(mut conn) = state.redis.get().await else {
+        return;
+    };
+    let key = format!("login:failures:{}", user.id);
+    let Ok(failures) = conn.incr::<_, _, u64>(&key, 1).await else {
+        return;
+    };
+    let lockout = state.login.lockout.as_secs();
+    if failures >= state.login.max_failures {
+        let _ = conn
+            .set_ex::<_, _, ()>(format!("login:lockout:{}", user.id), 1, lockout)
+            .await;
+        let _ = conn.del::<_, ()>(&key).await;
+    } else {
+        let _ = conn
+            .expire::<_, ()>(&key, i64::try_from(lockout).unwrap_or(i64::MAX))
+            .await;
+    }
+}
+
+/// Forget any failed login attempts recorded against the specified user.
+pub async fn clear_login_failures(state: &AppState, user: &member::Model) {
+    if let Ok(mut conn) = state.redis.get().await {
+        let _ = conn
+            .del::<_, ()>(format!("login:failures:{}", user.id))
+            .await;
+    }
+}
+
+/// Ensures that neither of the specified users has blocked the other. Blocks work both ways,
+/// so that blocking someone doesn't leave them able to pester the blocker regardless.
+pub async fn ensure_not_blocked(state: &AppState, a: Uuid, b: Uuid) -> Result<(), StringError> {
+    let block = Block::find()
+        .filter(
+            block::Column::Blocker
+                .eq(a)
+                .and(block::Column::Blocked.eq(b))
+                .or(block::Column::Blocker
+                    .eq(b)
+                    .and(block::Column::Blocked.eq(a))),
+        )
+        .one(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    if block.is_some() {
+        return Err(StringError(
+            strings::BLOCKED.to_string(),
+            StatusCode::FORBIDDEN,
+        ));
+    }
+    Ok(())
+}
+
+/// Record a login attempt for the specified user in the audit trail.
+pub async fn record_login_attempt(
+    state: &AppState,
+    user: &member::Model,
+    ip: Option<String>,
+    success: bool,
+) -> Result<(), StringError> {
+    LoginAttempt::insert(login_attempt::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        member: ActiveValue::set(user.id),
+        ip: ActiveValue::set(ip),
+        success: ActiveValue::set(success),
+        created_at: ActiveValue::NotSet,
+    })
+    .exec(state.database.as_ref())
+    .await
+    .map(|_| ())
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Whether the specified error means a write broke a unique constraint (e.g. because another
+/// request took the same username first).
+pub fn unique_violation(e: &DbErr) -> bool {
+    match e {
+        DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(e)))
+        | DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(e))) => {
+            e.code().as_deref() == Some("23505")
+        }
+        _ => false,
+    }
+}
+
+/// How many times a transaction is attempted if it keeps losing races with others.
+const TRANSACTION_ATTEMPTS: usize = 3;
+
+/// Whether the specified error means the transaction lost a race with another one (by failing
+/// to serialize or deadlocking), so that running it again may well succeed.
+pub fn conflicted(e: &DbErr) -> bool {
+    match e {
+        DbErr::Conn(RuntimeErr::SqlxError(sqlx::Error::Database(e)))
+        | DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(e)))
+        | DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(e))) => {
+            matches!(e.code().as_deref(), Some("40001" | "40P01"))
+        }
+        _ => false,
+    }
+}
+
+/// Run a transaction, running it again from the start (up to `TRANSACTION_ATTEMPTS` times in
+/// all) whenever it loses a race with another one.
+/// # Errors
+/// Returns the error the last attempt failed with.
+pub async fn retry_conflicts<T, F, Fut>(mut attempt: F) -> Result<T, DbErr>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, DbErr>>,
+{
+    let mut attempts = 1;
+    loop {
+        match attempt().await {
+            Err(e) if conflicted(&e) && attempts < TRANSACTION_ATTEMPTS => attempts += 1,
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use crate::server::{handlers::StringError, helpers, state::AppState, strings};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use base64::Engine;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How long (in seconds) a key is remembered after the request it was sent with.
+const TTL: u64 = 86_400;
+/// The longest key accepted, so that clients can't fill the cache with huge ones.
+const MAX_KEY_LENGTH: usize = 255;
+/// The largest request body read to fingerprint a request, the same as axum allows its
+/// extractors to read.
+const MAX_BODY: usize = 2 * 1024 * 1024;
+
+/// What's stored under a key: what the request it was sent with looked like, and its outcome
+/// once it's been handled.
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    fingerprint: String,
+    outcome: Option<String>,
+}
+
+/// The response to a request made with an idempotency key, kept to answer retries with.
+#[derive(Debug, Serialize, Deserialize)]
+struct Stored {
+    status: u16,
+    headers: Vec<(String, Vec<u8>)>,
+    body: String,
+}
+
+/// A key claimed for a request, to be completed or released once it's been handled.
+pub struct Ticket {
+    name: String,
+    fingerprint: String,
+}
+
+/// What's known about a key when a request arrives with it.
+pub enum Claim {
+    /// Nobody has used the key before, so the request should be handled as usual.
+    Fresh(Ticket),
+    /// A request with the key is still being handled.
+    Pending,
+    /// A request with the key was already handled, with the stored outcome.
+    Done(String),
+    /// The key was already used for a different request.
+    Mismatch,
+}
+
+/// A digest of everything that makes up a request, to tell retries apart from different
+/// requests that happen to reuse a key.
+#[must_use]
+pub fn fingerprint(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        // Prefix each part with its length, so that moving bytes between parts changes the
+        // digest.
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    base64::prelude::BASE64_STANDARD.encode(hasher.finalize())
+}
+
+/// Claim the specified key for a user's request, unless it was claimed before. Returns `None`
+/// if the cache is unavailable, in which case requests go ahead without the protection.
+pub async fn claim(
+    state: &AppState,
+    user: Uuid,
+    scope: &str,
+    key: &str,
+    fingerprint: String,
+) -> Option<Claim> {
+    let mut conn = state.redis.get().await.ok()?;
+    let name = format!("idempotency:{user}:{scope}:{key}");
+    let pending = Record {
+        fingerprint,
+        outcome: None,
+    };
+    let fresh: bool = redis::cmd("SET")
+        .arg(&name)
+        .arg(serde_json::to_string(&pending).unwrap())
+        .arg("NX")
+        .arg("EX")
+        .arg(TTL)
+        .query_async::<_, Option<String>>(&mut conn)
+        .await
+        .ok()?
+        .is_some();
+    let ticket = Ticket {
+        name,
+        fingerprint: pending.fingerprint,
+    };
+    if fresh {
+        return Some(Claim::Fresh(ticket));
+    }
+    let record = conn
+        .get::<_, Option<String>>(&ticket.name)
+        .await
+        .ok()?
+        .and_then(|record| serde_json::from_str::<Record>(&record).ok());
+    match record {
+        Some(record) if record.fingerprint != ticket.fingerprint => Some(Claim::Mismatch),
+        Some(Record { outcome: None, .. }) => Some(Claim::Pending),
+        Some(Record {
+            outcome: Some(outcome),
+            ..
+        }) => Some(Claim::Done(outcome)),
+        // The key expired in between, so there's nothing to replay.
+        None => Some(Claim::Fresh(ticket)),
+    }
+}
+
+/// Record the outcome of the request a key was claimed for, so that retries get it too.
+pub async fn complete(state: &AppState, ticket: Ticket, outcome: String) {
+    let record = Record {
+        fingerprint: ticket.fingerprint,
+        outcome: Some(outcome),
+    };
+    if let Ok(mut conn) = state.redis.get().await {
+        let record = serde_json::to_string(&record).unwrap();
+        let _ = conn.set_ex::<_, _, ()>(&ticket.name, record, TTL).await;
+    }
+}
+
+/// Give up a key, so that the request can be retried with it (e.g. after failing on the
+/// server's side).
+pub async fn release(state: &AppState, name: &str) {
+    if let Ok(mut conn) = state.redis.get().await {
+        let _ = conn.del::<_, ()>(name).await;
+    }
+}
+
+impl Ticket {
+    /// Give up the key, as with [`release`].
+    pub async fn release(self, state: &AppState) {
+        release(state, &self.name).await;
+    }
+}
+
+/// Ensures that an idempotency key isn't unreasonably long.
+pub fn ensure_valid_key(key: &str) -> Result<(), StringError> {
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Err(StringError(
+            strings::INVALID_IDEMPOTENCY_KEY.into(),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    Ok(())
+}
+
+/// The error for a key that was already used for a different request.
+#[must_use]
+pub fn reused() -> StringError {
+    StringError(
+        strings::IDEMPOTENCY_KEY_REUSED.into(),
+        StatusCode::UNPROCESSABLE_ENTITY,
+    )
+}
+
+/// The error for a key whose request is still being handled.
+#[must_use]
+pub fn in_progress() -> StringError {
+    StringError(
+        strings::IDEMPOTENCY_IN_PROGRESS.into(),
+        StatusCode::CONFLICT,
+    )
+}
+
+/// Middleware that answers retries of a request sent with an `Idempotency-Key` header with
+/// the response to the original, rather than handling them again. Keys belong to the user
+/// sending them, and requests without one (or without a session) are handled as usual. A
+/// key sent again with a different method, path or body is refused.
+pub async fn enforce(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    req: Request,
+    next: Next,
+) -> Result<Response, StringError> {
+    let Some(key) = req
+        .headers()
+        .get(strings::IDEMPOTENCY_HEADER_NAME)
+        .map(|value| value.to_str().unwrap_or_default().to_string())
+    else {
+        return Ok(next.run(req).await);
+    };
+    ensure_valid_key(&key)?;
+    let user = match jar.get(strings::SESSION_COOKIE_NAME) {
+        Some(sid) => helpers::get_session(&state, sid.value_trimmed())
+            .await
+            .ok()
+            .and_then(|user| Uuid::try_parse(&user).ok()),
+        None => None,
+    };
+    let Some(user) = user else {
+        return Ok(next.run(req).await);
+    };
+    let scope = format!("{} {}", req.method(), req.uri().path());
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, MAX_BODY).await.map_err(|_| {
+        StringError(
+            strings::PAYLOAD_TOO_LARGE.into(),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )
+    })?;
+    let fingerprint = fingerprint(&[
+        parts.method.as_str().as_bytes(),
+        parts.uri.path().as_bytes(),
+        &body,
+    ]);
+    let req = Request::from_parts(parts, Body::from(body));
+    let ticket = match claim(&state, user, &scope, &key, fingerprint).await {
+        None => return Ok(next.run(req).await),
+        Some(Claim::Pending) => return Err(in_progress()),
+        Some(Claim::Mismatch) => return Err(reused()),
+        Some(Claim::Done(stored)) => return Ok(replay(&stored)),
+        Some(Claim::Fresh(ticket)) => ticket,
+    };
+    let resp = next.run(req).await;
+    // Failures on the server's side might not happen again, so let those be retried.
+    if resp.status().is_server_error() {
+        ticket.release(&state).await;
+        return Ok(resp);
+    }
+    let (parts, body) = resp.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            ticket.release(&state).await;
+            return Err(StringError(
+                e.to_string(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+    let stored = Stored {
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+            .collect(),
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+    };
+    complete(&state, ticket, serde_json::to_string(&stored).unwrap()).await;
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+/// Send a stored response again, with all of its headers, marked as a replay.
+fn replay(stored: &str) -> Response {
+    let Ok(stored) = serde_json::from_str::<Stored>(stored) else {
+        return in_progress().into_response();
+    };
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut resp = Response::new(Body::from(stored.body));
+    *resp.status_mut() = status;
+    let headers = resp.headers_mut();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_bytes(&value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert("Idempotent-Replayed", HeaderValue::from_static("true"));
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{self, packet, strings};
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map, Socket};
+
+    #[tokio::test]
+    async fn create() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let key = uuid::Uuid::now_v7().to_string();
+        let create = |key: String| {
+            let client = &client;
+            let url = &url;
+            let guest = &guest;
+            async move {
+                client
+                    .post_with_header::<_, Map>(
+                        url,
+                        "/game",
+                        json!({ "guest": guest }),
+                        (strings::IDEMPOTENCY_HEADER_NAME, &key),
+                    )
+                    .await
+            }
+        };
+        // A retry gets the game the first request created, rather than a second one.
+        let first = create(key.clone()).await;
+        let retry = create(key.clone()).await;
+        assert_eq!(first["code"], 201);
+        assert_eq!(first["message"]["id"], retry["message"]["id"]);
+        let other = create(uuid::Uuid::now_v7().to_string()).await;
+        assert_ne!(first["message"]["id"], other["message"]["id"]);
+        let resp = create(String::new()).await;
+        assert_eq!(resp["message"], strings::INVALID_IDEMPOTENCY_KEY);
+        // Reusing a key for a different request is a mistake, not a retry.
+        let resp: Map = client
+            .post_with_header(
+                &url,
+                "/game",
+                json!({ "guest": guest, "settings": { "handicap": 2 } }),
+                (strings::IDEMPOTENCY_HEADER_NAME, &key),
+            )
+            .await;
+        assert_eq!(resp["status"], 422);
+        assert_eq!(resp["code"], "idempotency_key_reused");
+    }
+
+    #[tokio::test]
+    async fn place() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Map = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp["message"]["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let token = client.cookie(&url, strings::SESSION_COOKIE_NAME).unwrap();
+        let mut socket = Socket::connect(&url).await;
+        socket
+            .send(json!({
+                "op": 6,
+                "d": { "type": "Identify", "version": packet::PROTOCOL_VERSION },
+                "t": token,
+            }))
+            .await;
+        socket.recv_op(2).await;
+        socket
+            .send(json!({ "op": 3, "d": { "type": "Join", "id": id }, "t": token }))
+            .await;
+        socket.recv_op(4).await;
+        let mut game = crate::Game::new();
+        let piece = game.turn();
+        let (x, y) = game.moves(piece)[0];
+        let place = json!({
+            "op": 2,
+            "d": { "type": "Place", "id": id, "x": x, "y": y, "piece": piece, "key": "move-1" },
+            "t": token,
+        });
+        socket.send(&place).await;
+        socket.recv_op(1).await;
+        // Sending the move again is acknowledged, rather than refused as out of turn.
+        socket.send(&place).await;
+        let reply = loop {
+            let event = socket.recv().await;
+            if event["op"] == 1 || event["op"] == 6 {
+                break event;
+            }
+        };
+        assert_eq!(reply["d"]["type"], "Ack");
+        // A different move can't reuse the key.
+        let (x, y) = game.moves(piece)[1];
+        socket
+            .send(json!({
+                "op": 2,
+                "d": { "type": "Place", "id": id, "x": x, "y": y, "piece": piece, "key": "move-1" },
+                "t": token,
+            }))
+            .await;
+        let reply = socket.recv_op(6).await;
+        assert_eq!(reply["d"]["code"], "idempotency_key_reused");
+    }
+}
@@ -0,0 +1,156 @@
+//! Expiring game invitations that go unanswered, so that they don't pile up forever. Invites
+//! older than the configured lifetime are left out of pending games and can't be accepted, and
+//! the `invite_expiry` job deletes them and lets their hosts know.
+
+use crate::server::{
+    entities::{game, prelude::Game},
+    helpers,
+    notifications::{self, Kind},
+    state::AppState,
+};
+use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter, TransactionTrait};
+use serde_json::json;
+use std::{
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+use uuid::{NoContext, Timestamp, Uuid};
+
+/// The smallest ID a game created within the specified lifetime can have. Game IDs are
+/// version 7 UUIDs, which sort by creation time, so invites with smaller IDs have expired.
+pub(super) fn cutoff(ttl: Duration) -> Uuid {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let created = now.saturating_sub(ttl);
+    Uuid::new_v7(Timestamp::from_unix(
+        NoContext,
+        created.as_secs(),
+        created.subsec_nanos(),
+    ))
+}
+
+/// Whether the specified game is an invite that's gone unanswered for too long.
+pub(super) fn expired(state: &AppState, game: &game::Model) -> bool {
+    game.pending && state.invite_ttl.is_some_and(|ttl| game.id < cutoff(ttl))
+}
+
+/// Delete the invites that have gone unanswered for longer than the specified lifetime,
+/// notifying their hosts, and return how many there were.
+pub(super) async fn expire(state: &AppState, ttl: Duration) -> Result<u64, DbErr> {
+    let txn = state.database.begin().await?;
+    let invites = Game::find()
+        .filter(game::Column::Pending.eq(true))
+        .filter(game::Column::Id.lt(cutoff(ttl)))
+        .all(&txn)
+        .await?;
+    if invites.is_empty() {
+        return Ok(0);
+    }
+    Game::delete_many()
+        .filter(game::Column::Id.is_in(invites.iter().map(|invite| invite.id)))
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    let guests = invites
+        .iter()
+        .filter_map(|invite| Uuid::from_str(&invite.guest).ok());
+    let guests = helpers::get_users_by_ids(state, guests)
+        .await
+        .unwrap_or_default();
+    for invite in &invites {
+        let Ok(host) = Uuid::from_str(&invite.host) else {
+            continue;
+        };
+        let guest = Uuid::from_str(&invite.guest)
+            .ok()
+            .and_then(|guest| guests.get(&guest))
+            .map(|guest| guest.username.clone());
+        notifications::send(
+            state,
+            host,
+            Kind::GameInviteExpire,
+            json!({ "game": invite.id, "guest": guest, "challenge": invite.challenge }),
+        )
+        .await;
+    }
+    Ok(invites.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{
+        self, correspondence,
+        entities::{game, prelude::Game},
+        handlers::Response,
+        helpers,
+    };
+    use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+    use serde_json::json;
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+    use test_utils::{function, Client, Map};
+    use uuid::{NoContext, Timestamp, Uuid};
+
+    #[tokio::test]
+    async fn expiry() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let ttl = Duration::from_hours(24);
+        let state = Arc::new(server::AppState::new(database, redis).with_invite_ttl(ttl));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let black = helpers::get_user(&state, &host, true).await.unwrap().id;
+        let white = helpers::get_user(&state, &guest, true).await.unwrap().id;
+        let invite = |id| game::ActiveModel {
+            id: ActiveValue::set(id),
+            host: ActiveValue::set(black.to_string()),
+            guest: ActiveValue::set(white.to_string()),
+            pending: ActiveValue::set(true),
+            ended: ActiveValue::set(false),
+            challenge: ActiveValue::set(None),
+            result: ActiveValue::set(None),
+            settings: ActiveValue::set(json!({})),
+            state: ActiveValue::set(None),
+            turn_started_at: ActiveValue::set(None),
+            opponent: ActiveValue::set(None),
+            arena: ActiveValue::set(None),
+            host_opponent: ActiveValue::set(None),
+            ended_at: ActiveValue::set(None),
+            mode: ActiveValue::set(correspondence::LIVE.into()),
+            host_color: ActiveValue::set(String::from("black")),
+        };
+        let created = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .checked_sub(Duration::from_hours(48))
+            .unwrap();
+        let old = Uuid::new_v7(Timestamp::from_unix(
+            NoContext,
+            created.as_secs(),
+            created.subsec_nanos(),
+        ));
+        let db = state.database.as_ref();
+        let old = invite(old).insert(db).await.unwrap();
+        let recent = invite(Uuid::now_v7()).insert(db).await.unwrap();
+        assert!(super::expired(&state, &old));
+        assert!(!super::expired(&state, &recent));
+        // Expired invites are left out before they're deleted.
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/games/pending").await;
+        let ids: Vec<_> = resp.message.iter().map(|game| game["id"].clone()).collect();
+        assert_eq!(ids, [json!(recent.id)]);
+        assert!(super::expire(&state, ttl).await.unwrap() >= 1);
+        assert!(Game::find_by_id(old.id).one(db).await.unwrap().is_none());
+        assert!(Game::find_by_id(recent.id).one(db).await.unwrap().is_some());
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/notifications").await;
+        assert_eq!(resp.message[0]["kind"], "game_invite_expire");
+        assert_eq!(resp.message[0]["payload"]["game"], json!(old.id));
+        assert_eq!(resp.message[0]["payload"]["guest"], guest.as_str());
+    }
+}
@@ -0,0 +1,151 @@
+//! Short codes for joining unlisted games, which anyone can use without being invited. A game
+//! created with `"visibility": "code"` isn't stored until somebody joins it: until then, its
+//! host and settings are kept in Redis under its code, which expires if nobody uses it.
+
+use crate::{
+    server::{handlers::StringError, state::AppState},
+    GameSettings,
+};
+use axum::http::StatusCode;
+use rand::Rng;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// How long a join code can be used for, in seconds.
+pub(super) const TTL: u64 = 60 * 60;
+/// The characters codes are made of, leaving out those easily mistaken for others (0 and O,
+/// 1 and I).
+const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const LENGTH: usize = 6;
+/// How many codes are tried before giving up, should they all be taken.
+const ATTEMPTS: usize = 5;
+
+/// A game waiting for somebody to join it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Opening {
+    pub host: Uuid,
+    pub settings: GameSettings,
+}
+
+fn key(code: &str) -> String {
+    format!("join:{code}")
+}
+
+/// Codes are read regardless of case and surrounding whitespace, since people type them in.
+pub(super) fn normalize(code: &str) -> String {
+    code.trim().to_ascii_uppercase()
+}
+
+/// Store the specified game under a code nobody else is using, returning the code.
+pub(super) async fn issue(state: &AppState, opening: &Opening) -> Result<String, StringError> {
+    let mut conn = state.redis.get().await?;
+    let value = serde_json::to_string(opening)
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    for _ in 0..ATTEMPTS {
+        let code = generate();
+        let fresh = redis::cmd("SET")
+            .arg(key(&code))
+            .arg(&value)
+            .arg("NX")
+            .arg("EX")
+            .arg(TTL)
+            .query_async::<_, Option<String>>(&mut conn)
+            .await?
+            .is_some();
+        if fresh {
+            return Ok(code);
+        }
+    }
+    Err(StringError(
+        "no join code was free".into(),
+        StatusCode::SERVICE_UNAVAILABLE,
+    ))
+}
+
+/// Look up the game waiting behind the specified code, without using the code up.
+pub(super) async fn peek(state: &AppState, code: &str) -> Result<Option<Opening>, StringError> {
+    let mut conn = state.redis.get().await?;
+    let opening: Option<String> = conn.get(key(code)).await?;
+    Ok(opening.and_then(|opening| serde_json::from_str(&opening).ok()))
+}
+
+/// Use up the specified code, returning the game waiting behind it, unless somebody else got
+/// there first.
+pub(super) async fn redeem(state: &AppState, code: &str) -> Result<Option<Opening>, StringError> {
+    let mut conn = state.redis.get().await?;
+    let opening: Option<String> = conn.get_del(key(code)).await?;
+    Ok(opening.and_then(|opening| serde_json::from_str(&opening).ok()))
+}
+
+fn generate() -> String {
+    let mut rng = rand::thread_rng();
+    (0..LENGTH)
+        .map(|_| char::from(ALPHABET[rng.gen_range(0..ALPHABET.len())]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, normalize, ALPHABET, LENGTH};
+    use crate::server::{
+        self,
+        handlers::{ApiError, Response},
+        strings,
+    };
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map};
+
+    #[test]
+    fn codes() {
+        let code = generate();
+        assert_eq!(code.len(), LENGTH);
+        assert!(code.bytes().all(|c| ALPHABET.contains(&c)));
+        assert_eq!(normalize(&format!(" {} ", code.to_lowercase())), code);
+    }
+
+    #[tokio::test]
+    async fn join() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(state)).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let late = format!("{host}::late");
+        let client = Client::authenticated(&[&host, &guest, &late], &url, true).await;
+        let resp: Response<Map> = client
+            .post(&url, "/game", json!({ "visibility": "code" }))
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let code = resp.message["code"].as_str().unwrap().to_lowercase();
+        // Nobody can join their own game.
+        let resp: ApiError = client
+            .post(&url, &format!("/games/join/{code}"), json!({}))
+            .await;
+        assert_eq!(resp.message, strings::GAME_SELF);
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        let resp: Response<Map> = other
+            .post(&url, &format!("/games/join/{code}"), json!({}))
+            .await;
+        assert_eq!(resp.code, StatusCode::CREATED);
+        let id = resp.message["id"].as_str().unwrap().to_string();
+        let resp: Response<Map> = client.get(&url, &format!("/games/{id}")).await;
+        assert_eq!(resp.message["status"], "active");
+        assert_eq!(resp.message["players"]["white"]["username"], guest);
+        let resp: Response<Vec<Map>> = client.get(&url, "/@me/notifications").await;
+        assert_eq!(resp.message[0]["kind"], "game_join");
+        assert_eq!(resp.message[0]["payload"]["game"], id.as_str());
+        // Codes can only be used once.
+        let other = Client::authenticated(&[&late], &url, false).await;
+        let resp: ApiError = other
+            .post(&url, &format!("/games/join/{code}"), json!({}))
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.message, strings::INVALID_JOIN_CODE);
+    }
+}
@@ -0,0 +1,56 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+/// The address of the site, used when `SITE_URL` is not set.
+pub const DEFAULT_SITE_URL: &str = "http://localhost:8000";
+/// The URL scheme that the mobile apps register to open links with.
+pub const APP_SCHEME: &str = "olly";
+
+/// The address of the site that links point to.
+pub fn site_url() -> String {
+    std::env::var("SITE_URL").unwrap_or_else(|_| String::from(DEFAULT_SITE_URL))
+}
+
+/// Links that open a game, so that a challenge can be shared as a URL (or a QR code
+/// made from one) and opened straight into the game on another device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GameLinks {
+    /// Opens the game in the web client.
+    pub web: String,
+    /// Opens the game in the mobile apps.
+    pub app: String,
+}
+
+impl GameLinks {
+    /// The links to the specified game.
+    #[must_use]
+    pub fn new(game: Uuid) -> Self {
+        Self::at(&site_url(), game)
+    }
+
+    /// The links to the specified game on the site at `base`.
+    #[must_use]
+    pub fn at(base: &str, game: Uuid) -> Self {
+        Self {
+            web: format!("{}/play?gameId={game}", base.trim_end_matches('/')),
+            app: format!("{APP_SCHEME}://play?gameId={game}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameLinks;
+    use uuid::Uuid;
+
+    #[test]
+    fn links() {
+        let game = Uuid::nil();
+        let links = GameLinks::at("https://olly.example/", game);
+        assert_eq!(
+            links.web,
+            format!("https://olly.example/play?gameId={game}")
+        );
+        assert_eq!(links.app, format!("olly://play?gameId={game}"));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::server::{
+    entities::{ban, prelude::Ban},
+    handlers::{ApiError, ErrorCode, StringError},
+    state::AppState,
+    strings, timestamp,
+};
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, EntityTrait, QueryFilter, QueryOrder,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+/// A list of words that aren't welcome on the server, matched case-insensitively against whole
+/// words.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WordFilter {
+    words: Vec<String>,
+}
+
+impl WordFilter {
+    #[must_use]
+    pub fn new<S: AsRef<str>>(words: &[S]) -> Self {
+        Self {
+            words: words
+                .iter()
+                .map(|word| word.as_ref().trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Parse a comma-separated list of filtered words.
+    #[must_use]
+    pub fn parse(s: &str) -> Self {
+        Self::new(&s.split(',').collect::<Vec<_>>())
+    }
+
+    /// The filtered words that appear in the specified text, in the order they appear.
+    #[must_use]
+    pub fn matches(&self, text: &str) -> Vec<String> {
+        let mut found = vec![];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .map(str::to_lowercase)
+        {
+            if self.words.contains(&word) && !found.contains(&word) {
+                found.push(word);
+            }
+        }
+        found
+    }
+
+    /// Replace each filtered word in the specified text with asterisks.
+    #[must_use]
+    pub fn censor(&self, text: &str) -> String {
+        let mut censored = String::with_capacity(text.len());
+        let mut word = String::new();
+        let flush = |word: &mut String, censored: &mut String| {
+            if self.words.contains(&word.to_lowercase()) {
+                censored.extend(word.chars().map(|_| '*'));
+            } else {
+                censored.push_str(word);
+            }
+            word.clear();
+        };
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else {
+                flush(&mut word, &mut censored);
+                censored.push(c);
+            }
+        }
+        flush(&mut word, &mut censored);
+        censored
+    }
+}
+
+/// Ban the specified user, for the specified number of hours or, without one, permanently.
+pub async fn ban(
+    state: &AppState,
+    member: Uuid,
+    reason: String,
+    hours: Option<i64>,
+) -> Result<ban::Model, StringError> {
+    ban::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        member: ActiveValue::set(member),
+        reason: ActiveValue::set(reason),
+        expires_at: ActiveValue::set(
+            hours.map(|hours| (Utc::now() + Duration::hours(hours)).fixed_offset()),
+        ),
+        created_at: ActiveValue::NotSet,
+    }
+    .insert(state.database.as_ref())
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Fetch the ban currently in effect for the specified user, if there is one. When several
+/// overlap, the one that lasts longest wins.
+pub async fn active_ban(state: &AppState, member: Uuid) -> Result<Option<ban::Model>, StringError> {
+    let bans = Ban::find()
+        .filter(ban::Column::Member.eq(member))
+        .filter(
+            Condition::any()
+                .add(ban::Column::ExpiresAt.is_null())
+                .add(ban::Column::ExpiresAt.gt(Utc::now().fixed_offset())),
+        )
+        .order_by_desc(ban::Column::CreatedAt)
+        .all(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    // Permanent bans (with no expiry) sort after every temporary one.
+    Ok(bans.into_iter().max_by_key(|ban| {
+        ban.expires_at
+            .map_or((1, None), |expires| (0, Some(expires)))
+    }))
+}
+
+/// Ensures that the specified user's account isn't suspended, telling them why and until
+/// when if it is.
+pub async fn ensure_not_suspended(state: &AppState, member: Uuid) -> Result<(), ApiError> {
+    let Some(ban) = active_ban(state, member).await? else {
+        return Ok(());
+    };
+    let until = ban.expires_at.map_or_else(
+        || String::from("permanently"),
+        |expires| format!("until {}", timestamp::rfc3339(&expires)),
+    );
+    Err(ApiError::new(
+        ErrorCode::Suspended,
+        format!("{} ({until}): {}", strings::SUSPENDED, ban.reason),
+        StatusCode::FORBIDDEN,
+    )
+    .with_details(json!({ "reason": ban.reason, "expires_at": ban.expires_at.as_ref().map(timestamp::rfc3339) })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WordFilter;
+
+    #[test]
+    fn word_filter() {
+        let filter = WordFilter::new(&["heck", " Darn", ""]);
+        assert_eq!(
+            filter.matches("Oh HECK, darn it. heck!"),
+            vec!["heck", "darn"]
+        );
+        // Only whole words count.
+        assert!(filter.matches("checkmate").is_empty());
+        assert_eq!(filter.censor("Oh HECK, checkmate!"), "Oh ****, checkmate!");
+    }
+}
@@ -0,0 +1,101 @@
+//! When each move of a game was played and how long its player took over it, kept for replays
+//! and players' stats, and for looking into play that's suspiciously quick or steady. A move's
+//! time runs from the start of its turn, which for the first move is when the game started.
+
+use crate::{
+    server::{
+        entities::{
+            game,
+            game_move::{self, Column},
+            prelude::GameMove,
+        },
+        handlers::StringError,
+        helpers,
+        state::AppState,
+    },
+    Piece,
+};
+use axum::http::StatusCode;
+use chrono::Utc;
+use sea_orm::{
+    sea_query::Expr, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Note that the specified ply (counting from one) of a game was just played by whoever plays
+/// the specified piece, taking the specified time.
+pub(super) async fn record(
+    state: &AppState,
+    game: &game::Model,
+    ply: usize,
+    piece: Piece,
+    taken: Duration,
+) -> Result<(), StringError> {
+    let (black, white) = helpers::black_white(game);
+    let player = match piece {
+        Piece::Black => black,
+        Piece::White => white,
+    }
+    .ok_or_else(|| {
+        StringError(
+            format!("game {} has no {piece:?} player", game.id),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
+    GameMove::insert(game_move::ActiveModel {
+        game: ActiveValue::set(game.id),
+        ply: ActiveValue::set(ply.try_into().unwrap_or(i32::MAX)),
+        player: ActiveValue::set(player),
+        played_at: ActiveValue::set(Utc::now().fixed_offset()),
+        time_taken: ActiveValue::set(taken.as_millis().try_into().unwrap_or(i64::MAX)),
+    })
+    .exec_without_returning(state.database.as_ref())
+    .await
+    .map(|_| ())
+    .map_err(StringError::from)
+}
+
+/// The recorded moves of the specified game, in the order they were played. Moves played
+/// before times were recorded are missing.
+pub(super) async fn of_game(
+    state: &AppState,
+    game: Uuid,
+) -> Result<Vec<game_move::Model>, StringError> {
+    GameMove::find()
+        .filter(Column::Game.eq(game))
+        .order_by_asc(Column::Ply)
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)
+}
+
+/// The recorded moves the specified player played in any of the specified games.
+pub(super) async fn of_player(
+    state: &AppState,
+    player: Uuid,
+    games: impl IntoIterator<Item = Uuid>,
+) -> Result<Vec<game_move::Model>, StringError> {
+    GameMove::find()
+        .filter(Column::Player.eq(player))
+        .filter(Column::Game.is_in(games))
+        .all(state.database.as_ref())
+        .await
+        .map_err(StringError::from)
+}
+
+/// How long the specified player takes over a move on average, in milliseconds, or `None`
+/// if they haven't played any moves since times were recorded.
+pub(super) async fn average_time(
+    state: &AppState,
+    player: Uuid,
+) -> Result<Option<i64>, StringError> {
+    let average = GameMove::find()
+        .filter(Column::Player.eq(player))
+        .select_only()
+        .column_as(Expr::cust("ROUND(AVG(time_taken))::bigint"), "average")
+        .into_tuple::<Option<i64>>()
+        .one(state.database.as_ref())
+        .await?;
+    Ok(average.flatten())
+}
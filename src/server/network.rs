@@ -0,0 +1,249 @@
+use crate::server::{handlers::StringError, state::AppState, strings};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+/// Requests to paths under this prefix are only served to addresses on the admin allowlist.
+pub const ADMIN_PREFIX: &str = "/admin";
+
+/// The ranges of addresses that aren't reachable on the public internet: this host, private
+/// networks, and those set aside for special purposes (including the ones that embed IPv4
+/// addresses in IPv6 ones).
+const NON_GLOBAL: &[&str] = &[
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.0.0.0/24",
+    "192.0.2.0/24",
+    "192.88.99.0/24",
+    "192.168.0.0/16",
+    "198.18.0.0/15",
+    "198.51.100.0/24",
+    "203.0.113.0/24",
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+    "::/127",
+    "64:ff9b::/96",
+    "64:ff9b:1::/48",
+    "100::/64",
+    "2001::/23",
+    "2001:db8::/32",
+    "2002::/16",
+    "fc00::/7",
+    "fe80::/10",
+    "ff00::/8",
+];
+
+/// Which addresses the server believes, refuses, and trusts with admin routes.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkPolicy {
+    /// Proxies (e.g. load balancers) whose `X-Forwarded-For` headers are believed.
+    pub trusted_proxies: Vec<IpNet>,
+    /// Addresses that are refused outright.
+    pub denylist: Vec<IpNet>,
+    /// Addresses allowed to reach admin routes. Nobody can while this is empty.
+    pub admin_allowlist: Vec<IpNet>,
+}
+
+/// The address of the client that made a request, looking past any trusted proxies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+impl NetworkPolicy {
+    /// Work out who actually made a request that arrived from `peer`. Forwarded addresses
+    /// are only believed as far back as the chain of trusted proxies goes, since anything
+    /// before that could have been made up by the client.
+    #[must_use]
+    pub fn client_ip(&self, peer: IpAddr, forwarded: Option<&str>) -> IpAddr {
+        let mut client = peer;
+        if !contains(&self.trusted_proxies, client) {
+            return client;
+        }
+        for hop in forwarded.unwrap_or_default().rsplit(',').map(str::trim) {
+            let Ok(hop) = hop.parse() else {
+                break;
+            };
+            client = hop;
+            if !contains(&self.trusted_proxies, client) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Parse a comma-separated list of CIDR ranges or bare addresses.
+pub fn parse_ranges(s: &str) -> Option<Vec<IpNet>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|range| !range.is_empty())
+        .map(parse)
+        .collect()
+}
+
+/// Whether the specified address is reachable on the public internet. IPv4 addresses mapped
+/// into IPv6 are judged as the IPv4 addresses they are.
+#[must_use]
+pub fn is_global(ip: IpAddr) -> bool {
+    let ip = ip.to_canonical();
+    !NON_GLOBAL
+        .iter()
+        .filter_map(|range| range.parse::<IpNet>().ok())
+        .any(|range| range.contains(&ip))
+}
+
+fn parse(range: &str) -> Option<IpNet> {
+    range
+        .parse()
+        .ok()
+        .or_else(|| range.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn contains(ranges: &[IpNet], ip: IpAddr) -> bool {
+    ranges.iter().any(|range| range.contains(&ip))
+}
+
+/// Middleware that works out the client's address, refuses denylisted addresses, and keeps
+/// admin routes to the allowlist. The address is made available to handlers as [`ClientIp`].
+/// Admin routes are refused outright when the address can't be worked out.
+pub async fn enforce(
+    State(state): State<Arc<AppState>>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, StringError> {
+    let forbidden = || StringError(strings::ADDRESS_FORBIDDEN.into(), StatusCode::FORBIDDEN);
+    let admin = req.uri().path().starts_with(ADMIN_PREFIX);
+    let Some(ConnectInfo(addr)) = addr else {
+        // Without the peer's address there's no telling whether it's on the allowlist.
+        if admin {
+            return Err(forbidden());
+        }
+        return Ok(next.run(req).await);
+    };
+    let policy = &state.network;
+    let forwarded = req
+        .headers()
+        .get("X-Forwarded-For")
+        .and_then(|value| value.to_str().ok());
+    let ip = policy.client_ip(addr.ip(), forwarded);
+    if contains(&policy.denylist, ip) || (admin && !contains(&policy.admin_allowlist, ip)) {
+        return Err(forbidden());
+    }
+    req.extensions_mut().insert(ClientIp(ip));
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_global, NetworkPolicy};
+    use crate::server::{self, strings};
+    use axum::{body::Body, extract::Request};
+    use std::{net::IpAddr, sync::Arc};
+    use tower::ServiceExt;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn client_ip() {
+        let policy = NetworkPolicy {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+            ..NetworkPolicy::default()
+        };
+        // Untrusted peers can't vouch for anyone else.
+        let forwarded = Some("203.0.113.5");
+        assert_eq!(
+            policy.client_ip(ip("198.51.100.1"), forwarded),
+            ip("198.51.100.1")
+        );
+        // Trusted proxies are looked past, but only as far as the chain stays trusted.
+        assert_eq!(
+            policy.client_ip(ip("10.0.0.1"), forwarded),
+            ip("203.0.113.5")
+        );
+        let forwarded = Some("1.2.3.4, 203.0.113.5, 10.0.0.2");
+        assert_eq!(
+            policy.client_ip(ip("10.0.0.1"), forwarded),
+            ip("203.0.113.5")
+        );
+        // Garbage stops the walk at the last address that made sense.
+        let forwarded = Some("nonsense, 10.0.0.2");
+        assert_eq!(policy.client_ip(ip("10.0.0.1"), forwarded), ip("10.0.0.2"));
+        assert_eq!(policy.client_ip(ip("10.0.0.1"), None), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn global() {
+        for public in ["1.1.1.1", "93.184.216.34", "2606:4700:4700::1111"] {
+            assert!(is_global(ip(public)), "{public}");
+        }
+        for private in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.31.255.255",
+            "192.168.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_global(ip(private)), "{private}");
+        }
+    }
+
+    #[tokio::test]
+    async fn enforce() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = server::AppState::new(database, redis).with_network_policy(NetworkPolicy {
+            trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
+            denylist: vec!["203.0.113.0/24".parse().unwrap()],
+            admin_allowlist: vec!["198.51.100.7/32".parse().unwrap()],
+        });
+        let state = Arc::new(state);
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let client = reqwest::Client::new();
+        let get = |path: &str, forwarded: &str| {
+            client
+                .get(format!("{url}{path}"))
+                .header("X-Forwarded-For", forwarded)
+                .send()
+        };
+        // Clients forwarded from denylisted ranges are turned away.
+        let resp = get("/@me", "203.0.113.9").await.unwrap();
+        assert_eq!(resp.status().as_u16(), 403);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["message"], strings::ADDRESS_FORBIDDEN);
+        let resp = get("/@me", "198.51.100.1").await.unwrap();
+        assert_eq!(resp.status().as_u16(), 401);
+        // Admin routes are only reachable from the allowlist.
+        let resp = get("/admin/anything", "198.51.100.1").await.unwrap();
+        assert_eq!(resp.status().as_u16(), 403);
+        let resp = get("/admin/anything", "198.51.100.7").await.unwrap();
+        assert_eq!(resp.status().as_u16(), 404);
+        // Without the peer's address, admin routes are refused rather than waved through.
+        let request = Request::get("/admin/anything").body(Body::empty()).unwrap();
+        let resp = crate::server::app(state).oneshot(request).await.unwrap();
+        assert_eq!(resp.status().as_u16(), 403);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::server::{
+    entities::{
+        notification::{self, Column},
+        prelude::Notification as NotificationEntity,
+    },
+    handlers::StringError,
+    packet::{Event, EventKind, ServerMessage},
+    state::AppState,
+    timestamp,
+};
+use axum::http::StatusCode;
+pub use othello_api_types::Notification;
+use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// What a notification is about.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// Someone invited the user to a game.
+    GameInvite,
+    /// A game invite the user sent went unanswered for too long.
+    GameInviteExpire,
+    /// Someone took up a game the user opened to anyone, with its join code or from the
+    /// lobby.
+    GameJoin,
+    /// Someone sent the user a friend request.
+    FriendRequest,
+    /// Someone accepted the user's friend request.
+    FriendRequestAccept,
+    /// A game the user played in ended.
+    GameEnd,
+    /// A tournament the user entered paired them for a game.
+    TournamentGame,
+    /// A season ended and the user earned a tier for where they finished it.
+    SeasonReward,
+    /// An arena the user's bot plays in scheduled a game for it.
+    ArenaGame,
+    /// The deadline on the user's move in a correspondence game is coming up.
+    MoveReminder,
+}
+
+impl Kind {
+    /// The name the kind is stored as.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::GameInvite => "game_invite",
+            Self::GameInviteExpire => "game_invite_expire",
+            Self::GameJoin => "game_join",
+            Self::FriendRequest => "friend_request",
+            Self::FriendRequestAccept => "friend_request_accept",
+            Self::GameEnd => "game_end",
+            Self::TournamentGame => "tournament_game",
+            Self::SeasonReward => "season_reward",
+            Self::ArenaGame => "arena_game",
+            Self::MoveReminder => "move_reminder",
+        }
+    }
+}
+
+impl From<&notification::Model> for Notification {
+    fn from(model: &notification::Model) -> Self {
+        Self {
+            id: model.id,
+            kind: model.kind.clone(),
+            payload: model.payload.clone(),
+            read: model.read,
+            created_at: timestamp::rfc3339(&model.created_at),
+        }
+    }
+}
+
+/// Count the notifications the specified user hasn't read yet.
+/// # Errors
+/// Returns an error if the notifications can't be counted.
+pub async fn unread(state: &AppState, member: Uuid) -> Result<u64, StringError> {
+    NotificationEntity::find()
+        .filter(Column::Member.eq(member))
+        .filter(Column::Read.eq(false))
+        .count(state.database.as_ref())
+        .await
+        .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Keep a notification for the specified user, and deliver it to any connections they have
+/// open along with their new unread count. Notifications are a courtesy, so failing to keep
+/// one is logged rather than failing whatever prompted it.
+pub async fn send(state: &AppState, member: Uuid, kind: Kind, payload: Value) {
+    if let Err(StringError(message, _)) = store(state, member, kind, payload).await {
+        tracing::error!(%member, kind = kind.name(), "Failed to send notification: {message}");
+    }
+}
+
+async fn store(
+    state: &AppState,
+    member: Uuid,
+    kind: Kind,
+    payload: Value,
+) -> Result<(), StringError> {
+    let model = NotificationEntity::insert(notification::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        member: ActiveValue::set(member),
+        kind: ActiveValue::set(kind.name().into()),
+        payload: ActiveValue::set(payload),
+        read: ActiveValue::set(false),
+        created_at: ActiveValue::NotSet,
+    })
+    .exec_with_returning(state.database.as_ref())
+    .await
+    .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let unread = unread(state, member).await?;
+    state.notify(
+        member,
+        Event::new(
+            EventKind::Notification,
+            ServerMessage::Notification {
+                notification: Notification::from(&model),
+                unread,
+            },
+        ),
+    );
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use reqwest::Url;
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+
+/// The base URL that identity providers redirect back to, used when `OAUTH_REDIRECT_BASE`
+/// is not set.
+pub const DEFAULT_REDIRECT_BASE: &str = "http://localhost:3000";
+
+/// An external identity provider that users can sign in with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    GitHub,
+    Google,
+}
+
+/// The client credentials issued to this application by an identity provider.
+pub struct Credentials {
+    client_id: String,
+    client_secret: String,
+}
+
+/// A user as identified by an external identity provider.
+pub struct Identity {
+    /// The provider's stable, unique identifier for the user.
+    pub subject: String,
+    /// A suggested username, used when creating a new account for the user.
+    pub username: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum OAuthError {
+    #[error("{0}")]
+    Request(#[from] reqwest::Error),
+    #[error("identity provider did not issue an access token")]
+    MissingToken,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GitHubUser {
+    id: u64,
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct GoogleUser {
+    sub: String,
+    email: Option<String>,
+}
+
+impl Provider {
+    /// The name used to refer to the provider in URLs and the database.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::GitHub => "github",
+            Self::Google => "google",
+        }
+    }
+
+    fn authorize_url(self) -> &'static str {
+        match self {
+            Self::GitHub => "https://github.com/login/oauth/authorize",
+            Self::Google => "https://accounts.google.com/o/oauth2/v2/auth",
+        }
+    }
+
+    fn token_url(self) -> &'static str {
+        match self {
+            Self::GitHub => "https://github.com/login/oauth/access_token",
+            Self::Google => "https://oauth2.googleapis.com/token",
+        }
+    }
+
+    fn user_url(self) -> &'static str {
+        match self {
+            Self::GitHub => "https://api.github.com/user",
+            Self::Google => "https://openidconnect.googleapis.com/v1/userinfo",
+        }
+    }
+
+    fn scope(self) -> &'static str {
+        match self {
+            Self::GitHub => "read:user",
+            Self::Google => "openid email",
+        }
+    }
+
+    /// Fetch the client credentials for the provider from the environment
+    /// (`OAUTH_<PROVIDER>_CLIENT_ID` and `OAUTH_<PROVIDER>_CLIENT_SECRET`), if configured.
+    #[must_use]
+    pub fn credentials(self) -> Option<Credentials> {
+        let prefix = format!("OAUTH_{}", self.name().to_uppercase());
+        Some(Credentials {
+            client_id: std::env::var(format!("{prefix}_CLIENT_ID")).ok()?,
+            client_secret: std::env::var(format!("{prefix}_CLIENT_SECRET")).ok()?,
+        })
+    }
+
+    /// The URL the provider should redirect the user back to after they authorize us.
+    fn redirect_uri(self) -> String {
+        let base = std::env::var("OAUTH_REDIRECT_BASE")
+            .unwrap_or_else(|_| String::from(DEFAULT_REDIRECT_BASE));
+        format!(
+            "{}/auth/oauth/{}/callback",
+            base.trim_end_matches('/'),
+            self.name()
+        )
+    }
+
+    /// Build the URL to send the user to in order to begin the authorization flow.
+    /// # Panics
+    /// Panics if the provider's authorization URL is malformed.
+    #[must_use]
+    pub fn authorize(self, credentials: &Credentials, state: &str) -> String {
+        Url::parse_with_params(
+            self.authorize_url(),
+            &[
+                ("client_id", credentials.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri().as_str()),
+                ("response_type", "code"),
+                ("scope", self.scope()),
+                ("state", state),
+            ],
+        )
+        .expect("authorization url is valid")
+        .to_string()
+    }
+
+    /// Exchange an authorization code for an access token, then use it to look up the user.
+    /// # Errors
+    /// Returns an error if either request to the provider fails.
+    pub async fn identify(
+        self,
+        credentials: &Credentials,
+        code: &str,
+    ) -> Result<Identity, OAuthError> {
+        let client = reqwest::Client::new();
+        let token: TokenResponse = client
+            .post(self.token_url())
+            .header("Accept", "application/json")
+            .form(&[
+                ("client_id", credentials.client_id.as_str()),
+                ("client_secret", credentials.client_secret.as_str()),
+                ("code", code),
+                ("redirect_uri", self.redirect_uri().as_str()),
+                ("grant_type", "authorization_code"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let token = token.access_token.ok_or(OAuthError::MissingToken)?;
+        let request = client
+            .get(self.user_url())
+            .bearer_auth(token)
+            // GitHub rejects API requests without a user agent.
+            .header("User-Agent", "olly");
+        let response = request.send().await?.error_for_status()?;
+        Ok(match self {
+            Self::GitHub => {
+                let user: GitHubUser = response.json().await?;
+                Identity {
+                    subject: user.id.to_string(),
+                    username: user.login,
+                }
+            }
+            Self::Google => {
+                let user: GoogleUser = response.json().await?;
+                let username = user
+                    .email
+                    .as_deref()
+                    .and_then(|email| email.split('@').next())
+                    .unwrap_or("player")
+                    .to_string();
+                Identity {
+                    subject: user.sub,
+                    username,
+                }
+            }
+        })
+    }
+}
+
+impl FromStr for Provider {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Self::GitHub),
+            "google" => Ok(Self::Google),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,283 @@
+//! Opponents hosted by the server itself, for people to play whenever nobody else is around.
+//! Each difficulty plays from a bot account of its own (as white, against people), and
+//! answers moves over the same gateway flow as any other player.
+//!
+//! After each search, the line of play an opponent expects is saved to Redis, and its next
+//! search in the game starts from it, so that it plays consistently even when the game is
+//! picked up again after a restart.
+
+use crate::{
+    companion::Companion,
+    server::{
+        entities::{
+            game,
+            member::{self, Column as MemberColumn},
+            prelude::Member,
+        },
+        handlers::StringError,
+        helpers, packet,
+        state::AppState,
+        telemetry,
+    },
+    Game,
+};
+pub use othello_api_types::Difficulty;
+use redis::AsyncCommands;
+use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+use std::{
+    sync::{mpsc, Arc, Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
+use tokio::sync::oneshot;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// How many threads hosted opponents search on, unless configured otherwise.
+pub const DEFAULT_SEARCH_WORKERS: usize = 2;
+/// How long the hardest opponent thinks about each move.
+const HARD_BUDGET: Duration = Duration::from_secs(2);
+/// How long (in seconds) the line an opponent expects in a game is kept after its last move.
+const LINE_TTL: u64 = 7 * 24 * 60 * 60;
+
+/// The username of the account the specified opponent plays from.
+fn username(difficulty: Difficulty) -> &'static str {
+    match difficulty {
+        Difficulty::Easy => "olly_easy",
+        Difficulty::Medium => "olly_medium",
+        Difficulty::Hard => "olly_hard",
+    }
+}
+
+/// The line of play the specified opponent expects in the specified game, starting with the
+/// move it would play, or nothing if there are no legal moves. The search tries the moves of
+/// the line it expected before first.
+fn choose(difficulty: Difficulty, game: &Game, expected: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    if game.clone().over() {
+        return vec![];
+    }
+    let mut companion = Companion::from(game).expecting(expected);
+    let choice = match difficulty {
+        Difficulty::Easy => Some(companion.choice(1)),
+        Difficulty::Medium => Some(companion.choice(4)),
+        Difficulty::Hard => companion.best_move_within(HARD_BUDGET),
+    };
+    choice.map_or_else(Vec::new, |_| companion.line())
+}
+
+fn line_key(game: Uuid) -> String {
+    format!("game:{game}:line")
+}
+
+/// The rest of the line of play the hosted opponent in the specified game expected after its
+/// last search, if the game has followed it so far. Lines are kept as the whole game they
+/// expect, from its first move.
+async fn expected(state: &AppState, id: Uuid, game: &Game) -> Vec<(usize, usize)> {
+    let Ok(mut conn) = state.redis.get().await else {
+        return vec![];
+    };
+    let Some(expected) = conn
+        .get::<_, Option<String>>(line_key(id))
+        .await
+        .ok()
+        .flatten()
+        .and_then(|saved| serde_json::from_str::<Vec<(usize, usize)>>(&saved).ok())
+    else {
+        return vec![];
+    };
+    expected
+        .strip_prefix(game.history().as_slice())
+        .map(<[_]>::to_vec)
+        .unwrap_or_default()
+}
+
+/// Keep the line of play the hosted opponent in the specified game expects from its current
+/// position, for its next search. Lines that can't be kept only leave that search to start
+/// from scratch.
+async fn autosave(state: &AppState, id: Uuid, game: &Game, line: &[(usize, usize)]) {
+    let mut expected = game.history();
+    expected.extend_from_slice(line);
+    let Ok(mut conn) = state.redis.get().await else {
+        return;
+    };
+    let value = serde_json::to_string(&expected).unwrap();
+    if let Err(e) = conn.set_ex::<_, _, ()>(line_key(id), value, LINE_TTL).await {
+        tracing::warn!(game = %id, "Failed to keep hosted opponent's line: {e}");
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Threads set aside for hosted opponents to search on. Searches take long enough to hold up
+/// everything else on the runtime, and any number of games could want one at once, so they
+/// queue up for a fixed number of threads instead. The threads are started by the first
+/// search.
+pub struct SearchPool {
+    workers: usize,
+    jobs: OnceLock<mpsc::Sender<Job>>,
+}
+
+impl SearchPool {
+    #[must_use]
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers: workers.max(1),
+            jobs: OnceLock::new(),
+        }
+    }
+
+    fn jobs(&self) -> &mpsc::Sender<Job> {
+        self.jobs.get_or_init(|| {
+            let (tx, rx) = mpsc::channel::<Job>();
+            let rx = Arc::new(Mutex::new(rx));
+            for worker in 0..self.workers {
+                let rx = Arc::clone(&rx);
+                thread::Builder::new()
+                    .name(format!("search-{worker}"))
+                    .spawn(move || loop {
+                        // The lock is only held while waiting for a job, not while doing it.
+                        // The threads stop once the pool is dropped.
+                        let job = rx.lock().expect("mutex was poisoned").recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+                    .expect("failed to start a search thread");
+            }
+            tx
+        })
+    }
+
+    /// The line of play an opponent of the specified difficulty expects in the specified
+    /// game, starting with the move it would play, once a thread is free to search for it.
+    /// The search tries the moves of the line it expected before first. Returns nothing if
+    /// there are no legal moves.
+    pub async fn choose(
+        &self,
+        game: Game,
+        difficulty: Difficulty,
+        expected: Vec<(usize, usize)>,
+    ) -> Vec<(usize, usize)> {
+        let (tx, rx) = oneshot::channel();
+        let job = Box::new(move || {
+            let _ = tx.send(choose(difficulty, &game, &expected));
+        });
+        if self.jobs().send(job).is_err() {
+            return vec![];
+        }
+        rx.await.unwrap_or_default()
+    }
+}
+
+/// Fetch the ID of the account the specified opponent plays from, creating it the first time
+/// it's needed.
+/// # Errors
+/// Returns an error if the database can't be reached, or someone else has the username.
+pub async fn account(state: &AppState, difficulty: Difficulty) -> Result<Uuid, StringError> {
+    let find = || {
+        Member::find()
+            .filter(MemberColumn::Username.eq(username(difficulty)))
+            .filter(MemberColumn::Bot.eq(true))
+            .filter(MemberColumn::Owner.is_null())
+            .one(state.database.as_ref())
+    };
+    if let Some(account) = find().await? {
+        return Ok(account.id);
+    }
+    let id = Uuid::now_v7();
+    let inserted = Member::insert(member::ActiveModel {
+        id: ActiveValue::set(id),
+        username: ActiveValue::set(username(difficulty).into()),
+        password: ActiveValue::set(None),
+        created_at: ActiveValue::NotSet,
+        avatar: ActiveValue::NotSet,
+        timezone: ActiveValue::NotSet,
+        admin: ActiveValue::NotSet,
+        bot: ActiveValue::set(true),
+        owner: ActiveValue::set(None),
+        api_token: ActiveValue::set(None),
+    })
+    .exec(state.database.as_ref())
+    .await;
+    match inserted {
+        Ok(_) => Ok(id),
+        // Another request created the account first.
+        Err(e) if helpers::unique_violation(&e) => find()
+            .await?
+            .map(|account| account.id)
+            .ok_or_else(|| e.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Have the player on turn in the specified game reply with a move, if they're a hosted
+/// opponent. The move is searched for on the search pool, picking up the line the opponent
+/// expected last time, and then played like any other.
+pub fn respond(state: &AppState, metadata: &game::Model, game: &Game) {
+    let piece = game.turn();
+    let hosted = if piece == helpers::host_color(metadata) {
+        &metadata.host_opponent
+    } else {
+        &metadata.opponent
+    };
+    let Some(difficulty) = hosted
+        .as_deref()
+        .and_then(|name| name.parse::<Difficulty>().ok())
+    else {
+        return;
+    };
+    if game.clone().over() {
+        return;
+    }
+    let span = telemetry::game_span(&metadata.id.to_string());
+    let (state, metadata, game) = (state.clone(), metadata.clone(), game.clone());
+    tokio::spawn(
+        async move {
+            let expected = expected(&state, metadata.id, &game).await;
+            let line = state
+                .searches
+                .choose(game.clone(), difficulty, expected)
+                .await;
+            let Some(&(x, y)) = line.first() else {
+                return;
+            };
+            // The line is kept before the move is played, so that it's there to pick up even if
+            // the server stops in between.
+            autosave(&state, metadata.id, &game, &line).await;
+            // The game may have ended while the opponent was thinking, e.g. by resignation.
+            if let Err(e) = packet::make_move(&state, &metadata, x, y, piece).await {
+                tracing::warn!("Hosted opponent couldn't play its move: {e:?}");
+            }
+        }
+        .instrument(span),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Difficulty, SearchPool};
+    use crate::Game;
+
+    #[tokio::test]
+    async fn pool() {
+        let pool = SearchPool::new(2);
+        let game = Game::new();
+        let (easy, medium) = tokio::join!(
+            pool.choose(game.clone(), Difficulty::Easy, vec![]),
+            pool.choose(game.clone(), Difficulty::Medium, vec![]),
+        );
+        let moves = game.clone().moves(game.turn());
+        assert!(moves.contains(&easy[0]));
+        assert!(moves.contains(&medium[0]));
+        // The opponent expects replies to its own moves, when it searched far enough to find
+        // them, and picks its line up where it left off.
+        assert!(medium.len() > 1);
+        let resumed = pool
+            .choose(game.clone(), Difficulty::Medium, medium.clone())
+            .await;
+        assert_eq!(resumed[0], medium[0]);
+        assert_eq!("hard".parse(), Ok(Difficulty::Hard));
+        assert!("impossible".parse::<Difficulty>().is_err());
+    }
+}
@@ -1,64 +1,106 @@
 use crate::{
     server::{
+        conduct, correspondence, create_in_memory_game, delay,
         entities::{game, prelude::Game as GameModel},
-        handlers::StringError,
+        event_log,
+        handlers::{ApiError, StringError},
         helpers,
+        idempotency::{self, Claim},
+        moderation, moves, opponent,
+        projection::Viewer,
         state::AppState,
         strings,
+        summary::{self, Termination, Verdict},
+        telemetry, tournament,
     },
     Game, Piece,
 };
 use axum::{extract::ws::Message, http::StatusCode};
 use futures::Future;
-use redis::Commands;
-use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait, IntoActiveModel};
-use serde::{Deserialize, Serialize};
-use serde_repr::{Deserialize_repr, Serialize_repr};
-use std::str::FromStr;
-use tokio::sync::mpsc;
+use othello_api_types::gateway;
+pub use othello_api_types::gateway::{
+    Channel, ClientMessage, EventKind, Opcode, Packet, Snapshots, MIN_PROTOCOL_VERSION,
+    PROTOCOL_VERSION,
+};
+use redis::AsyncCommands;
+use sea_orm::EntityTrait;
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::AbortHandle,
+};
+use tracing::{Instrument, Span};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Packet {
-    op: Opcode,
-    d: Data,
-    t: String,
-}
+/// An event as the server holds it, with the game's position as a whole `Game`. Clients see
+/// the same shape either way.
+pub type Event = gateway::Event<Game>;
+pub type ServerMessage = gateway::ServerMessage<Game>;
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-enum Data {
-    Identify,
-    Place {
-        id: String,
-        x: usize,
-        y: usize,
-        piece: Piece,
-    },
-    Create {
-        guest: String,
-    },
-    Join {
-        id: String,
-    },
-    Leave {
-        id: String,
-    },
-    End {
-        id: String,
-    },
+/// Somewhere to send the events of a connection, how it wants them sent, and the channels
+/// being relayed to it.
+#[derive(Debug, Clone)]
+pub struct Subscriber {
+    pub sender: mpsc::Sender<Event>,
+    pub snapshots: Snapshots,
+    relays: Arc<Mutex<HashMap<Channel, AbortHandle>>>,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
-#[repr(u8)]
-enum Opcode {
-    // Create = 1 << 0
-    Place = 1 << 1,
-    Join,
-    Leave,
-    Reserved,
-    Identify,
-    Preview,
+impl Subscriber {
+    pub fn new(sender: mpsc::Sender<Event>, snapshots: Snapshots) -> Self {
+        Self {
+            sender,
+            snapshots,
+            relays: Arc::default(),
+        }
+    }
+
+    /// Relay the events of the specified channel, showing them as the specified viewer may see
+    /// them. Any relay of the channel that was already running is stopped, so that the
+    /// connection never hears about anything twice.
+    pub fn relay(
+        &self,
+        channel: Channel,
+        rx: broadcast::Receiver<Event>,
+        viewer: Viewer,
+        last: Option<Game>,
+    ) {
+        let task = tokio::spawn(relay(rx, self.clone(), viewer, last));
+        let previous = self
+            .relays
+            .lock()
+            .expect("mutex was poisoned")
+            .insert(channel, task.abort_handle());
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+    }
+
+    /// Stop relaying the events of the specified channel.
+    pub fn stop(&self, channel: Channel) {
+        let relay = self
+            .relays
+            .lock()
+            .expect("mutex was poisoned")
+            .remove(&channel);
+        if let Some(relay) = relay {
+            relay.abort();
+        }
+    }
+
+    /// Stop relaying the events of every channel.
+    pub fn stop_all(&self) {
+        let relays = std::mem::take(&mut *self.relays.lock().expect("mutex was poisoned"));
+        for relay in relays.into_values() {
+            relay.abort();
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -69,203 +111,420 @@ pub enum ParseError {
     Json(serde_json::Error),
 }
 
-impl TryFrom<&Message> for Packet {
+/// A packet received from a client, to be acted on.
+#[derive(Debug)]
+pub struct Request(Packet);
+
+impl Deref for Request {
+    type Target = Packet;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl TryFrom<&Message> for Request {
     type Error = ParseError;
 
     fn try_from(msg: &Message) -> Result<Self, Self::Error> {
         let s = msg.to_text().map_err(|_| ParseError::InvalidUtf8)?;
-        let packet: Self = serde_json::from_str(s).map_err(ParseError::Json)?;
-        Ok(packet)
+        let packet: Packet = serde_json::from_str(s).map_err(ParseError::Json)?;
+        Ok(Self(packet))
     }
 }
 
-impl Packet {
-    pub async fn process(&self, state: &AppState, sender: Option<mpsc::Sender<Event>>) -> Event {
+impl Request {
+    pub async fn process(&self, state: &AppState, subscriber: Option<Subscriber>) -> Event {
+        let span = self.d.game().map_or_else(Span::none, telemetry::game_span);
+        self.dispatch(state, subscriber).instrument(span).await
+    }
+
+    async fn dispatch(&self, state: &AppState, subscriber: Option<Subscriber>) -> Event {
+        if !self.is_well_formed() {
+            return error(strings::BAD_REQUEST, StatusCode::BAD_REQUEST);
+        }
         match self.op {
             Opcode::Identify => self.identify(state).await,
             Opcode::Place => self.authenticated(state, |p| p.place(state)).await,
             Opcode::Preview => self.authenticated(state, |p| p.preview(state)).await,
             Opcode::Join => {
-                self.authenticated(state, |p| p.join(state, sender.expect("missing sender")))
-                    .await
+                self.authenticated(state, |p| {
+                    p.join(state, subscriber.expect("missing subscriber"))
+                })
+                .await
+            }
+            Opcode::Subscribe => {
+                self.authenticated(state, |p| {
+                    p.subscribe(state, subscriber.expect("missing subscriber"))
+                })
+                .await
+            }
+            Opcode::Unsubscribe => {
+                self.authenticated(state, |p| async move {
+                    Ok(p.unsubscribe(&subscriber.expect("missing subscriber")))
+                })
+                .await
             }
             Opcode::Leave => self.authenticated(state, |p| p.leave(state)).await,
-            Opcode::Reserved => Ok(Event::error(
-                strings::RESERVED_OPCODE,
-                StatusCode::BAD_REQUEST,
-            )),
+            Opcode::Resign => self.authenticated(state, |p| p.resign(state)).await,
+            Opcode::Claim => self.authenticated(state, |p| p.claim(state)).await,
+            Opcode::Reserved => Ok(error(strings::RESERVED_OPCODE, StatusCode::BAD_REQUEST)),
         }
         .unwrap_or_else(std::convert::identity)
     }
 
     async fn identify(&self, state: &AppState) -> Result<Event, Event> {
+        let ClientMessage::Identify { version, .. } = &self.d else {
+            return Err(error(strings::BAD_REQUEST, StatusCode::BAD_REQUEST));
+        };
         // Verify that the token is valid.
         self.current_user(state).await?;
-        Ok(Event::new(EventKind::Ready, EventData::Ready))
+        // Speak the newest version of the protocol that both sides understand.
+        let version = version
+            .unwrap_or(MIN_PROTOCOL_VERSION)
+            .min(PROTOCOL_VERSION);
+        if version < MIN_PROTOCOL_VERSION {
+            return Err(error(
+                strings::UNSUPPORTED_PROTOCOL_VERSION,
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+        Ok(Event::new(
+            EventKind::Ready,
+            ServerMessage::Ready {
+                version,
+                resume: helpers::generate_key(),
+                resumed: false,
+            },
+        ))
     }
 
-    async fn join(&self, state: &AppState, sender: mpsc::Sender<Event>) -> Result<Event, Event> {
-        let Data::Join { id } = &self.d else {
+    async fn join(&self, state: &AppState, subscriber: Subscriber) -> Result<Event, Event> {
+        let ClientMessage::Join { id, since } = &self.d else {
             panic!("expected serde to reject invalid packet data")
         };
-        // Verify that the authenticated user is either the host or guest of the game.
-        self.ensure_participant(state, id).await?;
+        self.enter_game(state, id, *since, subscriber).await
+    }
+
+    /// Join the specified game on behalf of the packet's sender, relaying its events to the
+    /// connection from now on, and return its current state. Connections catching up are
+    /// first sent the kept events after the specified sequence number, unless they're only
+    /// watching a tournament game from behind its players.
+    async fn enter_game(
+        &self,
+        state: &AppState,
+        id: &str,
+        since: Option<u64>,
+        subscriber: Subscriber,
+    ) -> Result<Event, Event> {
+        // Verify that the authenticated user is either the host or guest of the game, unless
+        // it's an arena or tournament game, which anyone can watch.
+        let metadata = self.game(state, id).await?;
+        let spectating = self.ensure_participant(state, id).await.is_err();
+        let tournament = spectating
+            && tournament::includes(state.database.as_ref(), metadata.id)
+                .await
+                .map_err(|e| error(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        if spectating && metadata.arena.is_none() && !tournament {
+            return Err(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND));
+        }
         let uuid = Uuid::from_str(id)
-            .map_err(|_| Event::error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
-        // Subscribe to the broadcast channel for the specified room.
-        let mut rooms = state.rooms.lock().expect("mutex was poisoned");
-        let mut rx = rooms
-            .get_mut(&uuid)
-            .ok_or(Event::error(
-                strings::INVALID_GAME_ID,
-                StatusCode::NOT_FOUND,
-            ))?
-            .subscribe();
-        // Send the current state of the room.
-        let games = state.games.lock().expect("mutex was poisoned");
-        let game = games.get(&uuid).ok_or(Event::error(
-            strings::INVALID_GAME_ID,
-            StatusCode::NOT_FOUND,
-        ))?;
-        // Spawn a task to listen for room updates to broadcast.
-        tokio::spawn(async move {
-            while let Ok(update) = rx.recv().await {
-                let _ = sender.send(update).await;
+            .map_err(|_| error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
+        let user = self.current_user(state).await?;
+        let user = Uuid::from_str(&user)
+            .map_err(|_| error(strings::INVALID_TOKEN, StatusCode::UNAUTHORIZED))?;
+        // The game may have been started on another instance, in which case it has to be
+        // loaded here before it can be played.
+        if state.fanout
+            && !metadata.pending
+            && !metadata.ended
+            && !state
+                .rooms
+                .lock()
+                .expect("mutex was poisoned")
+                .contains_key(&uuid)
+        {
+            create_in_memory_game(state, &metadata)
+                .await
+                .map_err(|StringError(message, code)| error(&message, code))?;
+        }
+        // Spectators of tournament games being played watch from behind, so that nobody can
+        // coach the players.
+        if let (true, false, Some(delay)) = (tournament, metadata.ended, state.spectator_delay) {
+            return watch(state, uuid, delay, subscriber).await;
+        }
+        let update = enter(state, uuid, user, &subscriber)?;
+        if let Some(since) = since {
+            // Events are relayed from before the missed ones are read, so that none slip
+            // through the gap. Any sent twice have the same sequence number both times.
+            let missed = event_log::since(state, uuid, since)
+                .await
+                .map_err(|StringError(message, code)| error(&message, code))?;
+            let viewer = Viewer::of(state, uuid, Some(user));
+            for event in missed {
+                let _ = subscriber.sender.send(viewer.project(event)).await;
             }
-        });
-        Ok(Event::new(
-            EventKind::GameUpdate,
-            EventData::GameUpdate { game: game.clone() },
-        ))
+        }
+        Ok(update)
+    }
+
+    async fn subscribe(&self, state: &AppState, subscriber: Subscriber) -> Result<Event, Event> {
+        let ClientMessage::Subscribe { channel, since } = &self.d else {
+            return Err(error(strings::BAD_REQUEST, StatusCode::BAD_REQUEST));
+        };
+        match *channel {
+            Channel::Game(game) => {
+                self.enter_game(state, &game.to_string(), *since, subscriber)
+                    .await
+            }
+            channel @ Channel::Lobby => {
+                let rx = state
+                    .listen(channel)
+                    .ok_or(error(strings::BAD_REQUEST, StatusCode::BAD_REQUEST))?;
+                subscriber.relay(channel, rx, Viewer::Spectator, None);
+                Ok(Event::new(EventKind::Ack, ServerMessage::Ack))
+            }
+        }
+    }
+
+    fn unsubscribe(&self, subscriber: &Subscriber) -> Event {
+        let ClientMessage::Unsubscribe { channel } = &self.d else {
+            panic!("expected serde to reject invalid packet data")
+        };
+        subscriber.stop(*channel);
+        Event::new(EventKind::Ack, ServerMessage::Ack)
     }
 
     async fn leave(&self, state: &AppState) -> Result<Event, Event> {
-        let Data::Leave { id } = &self.d else {
+        let ClientMessage::Leave { id } = &self.d else {
             panic!("expected serde to reject invalid packet data")
         };
         // Verify that the authenticated user is either the host or guest of the game.
         self.ensure_participant(state, id).await?;
         let uuid = Uuid::from_str(id)
-            .map_err(|_| Event::error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
+            .map_err(|_| error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
         let metadata = self.game(state, id).await?;
         // If the game is over, prevent action.
         if metadata.ended {
-            return Err(Event::error(strings::BAD_REQUEST, StatusCode::BAD_REQUEST));
+            return Err(error(strings::BAD_REQUEST, StatusCode::BAD_REQUEST));
         }
+        let user = self.current_user(state).await?;
         // Delete the game from the database.
         GameModel::delete_by_id(uuid)
             .exec(state.database.as_ref())
             .await
-            .map_err(|e| Event::error(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
-        let mut rooms = state.rooms.lock().expect("mutex was poisoned");
-        let tx = rooms.get_mut(&uuid).ok_or(Event::error(
-            strings::INVALID_GAME_ID,
-            StatusCode::NOT_FOUND,
-        ))?;
-        let _ = tx.send(Event::new(EventKind::GameAbort, EventData::GameAbort));
+            .map_err(|e| error(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+        // Walking away from a game that has already started counts as aborting it. The game is
+        // gone either way, so failing to count it mustn't stop its room being cleaned up.
+        if let (false, Ok(user)) = (metadata.pending, Uuid::from_str(&user)) {
+            if let Err(StringError(message, _)) = conduct::record_abort(state, user, uuid).await {
+                tracing::error!("Failed to record abort: {message}");
+            }
+        }
+        if !state
+            .rooms
+            .lock()
+            .expect("mutex was poisoned")
+            .contains_key(&uuid)
+        {
+            return Err(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND));
+        }
+        state.broadcast(
+            uuid,
+            Event::new(EventKind::GameAbort, ServerMessage::GameAbort),
+        );
         // Delete game and room from global state.
+        let mut rooms = state.rooms.lock().expect("mutex was poisoned");
         let mut games = state.games.lock().expect("mutex was poisoned");
-        games.remove(&uuid).ok_or(Event::error(
-            strings::INVALID_GAME_ID,
-            StatusCode::NOT_FOUND,
-        ))?;
+        games
+            .remove(&uuid)
+            .ok_or(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND))?;
         rooms.remove(&uuid).unwrap();
-        Ok(Event::new(EventKind::Ack, EventData::Ack))
+        state
+            .turns
+            .lock()
+            .expect("mutex was poisoned")
+            .remove(&uuid);
+        state
+            .sequences
+            .lock()
+            .expect("mutex was poisoned")
+            .remove(&uuid);
+        Ok(Event::new(EventKind::Ack, ServerMessage::Ack))
     }
 
     async fn place(&self, state: &AppState) -> Result<Event, Event> {
-        let Data::Place { id, x, y, piece } = &self.d else {
+        let ClientMessage::Place { id, key, .. } = &self.d else {
+            panic!("expected serde to reject invalid packet data")
+        };
+        let Some(key) = key else {
+            return self.play(state).await;
+        };
+        idempotency::ensure_valid_key(key).map_err(|e| Event::from(ApiError::from(e)))?;
+        let user = self
+            .user(state)
+            .await
+            .ok_or(error(strings::INVALID_TOKEN, StatusCode::UNAUTHORIZED))?;
+        // Moves sent again with the same key get the first attempt's reply rather than being
+        // played again, unless the key was used for a different move.
+        let fingerprint = idempotency::fingerprint(&[&serde_json::to_vec(&self.d).unwrap()]);
+        let scope = format!("place {id}");
+        let ticket = match idempotency::claim(state, user, &scope, key, fingerprint).await {
+            Some(Claim::Fresh(ticket)) => ticket,
+            Some(Claim::Pending) => return Err(ApiError::from(idempotency::in_progress()).into()),
+            Some(Claim::Mismatch) => return Err(ApiError::from(idempotency::reused()).into()),
+            Some(Claim::Done(stored)) => {
+                return match serde_json::from_str(&stored) {
+                    Ok(result) => result,
+                    Err(_) => Err(ApiError::from(idempotency::in_progress()).into()),
+                }
+            }
+            None => return self.play(state).await,
+        };
+        let result = self.play(state).await;
+        match &result {
+            // Failures on the server's side might not happen again, so let those be retried.
+            Err(Event {
+                d: ServerMessage::Error(e),
+                ..
+            }) if e.status >= 500 => ticket.release(state).await,
+            _ => {
+                let stored = serde_json::to_string(&result).unwrap();
+                idempotency::complete(state, ticket, stored).await;
+            }
+        }
+        result
+    }
+
+    async fn play(&self, state: &AppState) -> Result<Event, Event> {
+        let ClientMessage::Place {
+            id, x, y, piece, ..
+        } = &self.d
+        else {
+            panic!("expected serde to reject invalid packet data")
+        };
+        // Verify that the authenticated user is either the host or guest of the game, and
+        // only moves for themselves.
+        self.ensure_participant(state, id).await?;
+        let metadata = self.game(state, id).await?;
+        if self.seat(state, &metadata).await? != *piece {
+            return Err(error(strings::NOT_YOUR_PIECE, StatusCode::FORBIDDEN));
+        }
+        make_move(state, &metadata, *x, *y, *piece).await
+    }
+
+    async fn resign(&self, state: &AppState) -> Result<Event, Event> {
+        let ClientMessage::Resign { id } = &self.d else {
             panic!("expected serde to reject invalid packet data")
         };
         // Verify that the authenticated user is either the host or guest of the game.
         self.ensure_participant(state, id).await?;
         let metadata = self.game(state, id).await?;
+        // Only games that are underway can be resigned.
+        if metadata.pending || metadata.ended {
+            return Err(error(strings::BAD_REQUEST, StatusCode::BAD_REQUEST));
+        }
         let uuid = Uuid::from_str(id)
-            .map_err(|_| Event::error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
-        let tx = {
-            let mut rooms = state.rooms.lock().expect("mutex was poisoned");
-            rooms
-                .get_mut(&uuid)
-                .ok_or(Event::error(
-                    strings::INVALID_GAME_ID,
-                    StatusCode::NOT_FOUND,
-                ))?
+            .map_err(|_| error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
+        let game = {
+            let games = state.games.lock().expect("mutex was poisoned");
+            games
+                .get(&uuid)
+                .ok_or(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND))?
                 .clone()
         };
-        let (res, mut game) = {
-            let mut games = state.games.lock().expect("mutex was poisoned");
-            let game = games.get_mut(&uuid).ok_or(Event::error(
-                strings::INVALID_GAME_ID,
-                StatusCode::NOT_FOUND,
-            ))?;
-            let res = game.place(*x, *y, *piece).map_or_else(
-                |e| Err(Event::error(&e.to_string(), StatusCode::BAD_REQUEST)),
-                |()| Ok(Event::new(EventKind::Ack, EventData::Ack)),
-            )?;
-            let _ = tx.send(Event::new(
-                EventKind::GameUpdate,
-                EventData::GameUpdate { game: game.clone() },
-            ));
-            if let Ok(mut conn) = state.redis.get_connection() {
-                let _ = conn.set::<String, String, String>(
-                    format!("game:{}", id.clone()),
-                    serde_json::to_string(game).unwrap(),
-                );
-            }
-            (res, game.clone())
+        let piece = self.seat(state, &metadata).await?;
+        summary::conclude(
+            state,
+            &metadata,
+            &game,
+            Some(Verdict::Forfeit(piece, Termination::Resignation)),
+            None,
+        )
+        .await
+        .map_err(|StringError(message, code)| error(&message, code))?;
+        Ok(Event::new(EventKind::Ack, ServerMessage::Ack))
+    }
+
+    async fn claim(&self, state: &AppState) -> Result<Event, Event> {
+        let ClientMessage::Claim { id, draw } = &self.d else {
+            panic!("expected serde to reject invalid packet data")
         };
-        if game.over() {
-            let (black, white) = game.score();
-            let winner = if black > white {
-                metadata.host.clone()
-            } else {
-                metadata.guest.clone()
-            };
-            let _ = tx.send(Event::new(
-                EventKind::GameEnd,
-                EventData::GameEnd {
-                    winner: helpers::get_user(state, &winner, false)
-                        .await
-                        .unwrap()
-                        .username,
-                    points: black.max(white),
-                    total: black + white,
-                },
-            ));
-            let mut model = metadata.clone().into_active_model();
-            model.ended = ActiveValue::set(true);
-            let _ = model.save(state.database.as_ref()).await;
+        // Verify that the authenticated user is either the host or guest of the game.
+        self.ensure_participant(state, id).await?;
+        let metadata = self.game(state, id).await?;
+        // Only games that are underway can be claimed.
+        if metadata.pending || metadata.ended {
+            return Err(error(strings::BAD_REQUEST, StatusCode::BAD_REQUEST));
         }
-        Ok(res)
+        let uuid = Uuid::from_str(id)
+            .map_err(|_| error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
+        let game = {
+            let games = state.games.lock().expect("mutex was poisoned");
+            games
+                .get(&uuid)
+                .ok_or(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND))?
+                .clone()
+        };
+        // Only the player waiting on their opponent can claim.
+        let user = self.current_user(state).await?;
+        let (black, white) = helpers::black_white(&metadata);
+        let (piece, opponent) = if black.is_some_and(|black| black.to_string() == user) {
+            (Piece::Black, white)
+        } else {
+            (Piece::White, black)
+        };
+        // Correspondence games have deadlines of their own instead.
+        if game.turn() == piece
+            || !state.stalled(uuid)
+            || correspondence::is_correspondence(&metadata)
+        {
+            return Err(error(strings::CLAIM_TOO_EARLY, StatusCode::BAD_REQUEST));
+        }
+        let verdict = if *draw {
+            Verdict::Draw(Termination::Stalling)
+        } else {
+            Verdict::Forfeit(!piece, Termination::Stalling)
+        };
+        let stalled = opponent.map(|opponent| (opponent, !piece));
+        summary::conclude(state, &metadata, &game, Some(verdict), stalled)
+            .await
+            .map_err(|StringError(message, code)| error(&message, code))?;
+        Ok(Event::new(EventKind::Ack, ServerMessage::Ack))
     }
 
     async fn preview(&self, state: &AppState) -> Result<Event, Event> {
-        let Data::Place { id, x, y, piece } = &self.d else {
+        let ClientMessage::Place {
+            id, x, y, piece, ..
+        } = &self.d
+        else {
             panic!("expected serde to reject invalid packet data")
         };
         // Verify that the authenticated user is either the host or guest of the game.
         self.ensure_participant(state, id).await?;
-        let mut games = state.games.lock().expect("mutex was poisoned");
+        let user = self.current_user(state).await?;
         let uuid = Uuid::from_str(id)
-            .map_err(|_| Event::error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
-        let game = games.get_mut(&uuid).ok_or(Event::error(
-            strings::INVALID_GAME_ID,
-            StatusCode::NOT_FOUND,
-        ))?;
-        game.preview(*x, *y, *piece).map_or_else(
-            |e| Err(Event::error(&e.to_string(), StatusCode::BAD_REQUEST)),
-            |changed| {
-                Ok(Event::new(
-                    EventKind::GameUpdatePreview,
-                    EventData::GameUpdatePreview { changed },
-                ))
-            },
-        )
+            .map_err(|_| error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
+        let viewer = Viewer::of(state, uuid, Uuid::from_str(&user).ok());
+        let mut games = state.games.lock().expect("mutex was poisoned");
+        let game = games
+            .get_mut(&uuid)
+            .ok_or(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND))?;
+        let mut changed = game
+            .preview(*x, *y, *piece)
+            .map_err(|e| error(&e.to_string(), StatusCode::BAD_REQUEST))?;
+        // Flips on squares the player can't see would give away what's hidden there.
+        let permissions = viewer.permissions(game.settings(), false);
+        changed.retain(|&(x, y)| permissions.square(game, x, y));
+        Ok(Event::new(
+            EventKind::GameUpdatePreview,
+            ServerMessage::GameUpdatePreview { changed },
+        ))
     }
 }
 
 // Middleware to require authentication for chosen Packet types.
-impl Packet {
+impl Request {
     async fn authenticated<'a, F>(
         &'a self,
         state: &AppState,
@@ -282,105 +541,274 @@ impl Packet {
 }
 
 // A collection of helper functions for performing database operations.
-impl Packet {
+impl Request {
+    /// The resume token this packet presents, if it is an identify packet presenting one.
+    pub fn resumes(&self) -> Option<&str> {
+        match &self.d {
+            ClientMessage::Identify { resume, .. } => resume.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// How often the connection this packet identifies wants the whole board.
+    pub fn snapshots(&self) -> Snapshots {
+        match &self.d {
+            ClientMessage::Identify { snapshots, .. } => *snapshots,
+            _ => Snapshots::default(),
+        }
+    }
+
+    /// The game this packet asks to join, if it is a request to join one (or to subscribe to
+    /// its channel).
+    pub fn joins(&self) -> Option<Uuid> {
+        match (&self.op, &self.d) {
+            (Opcode::Join, ClientMessage::Join { id, .. }) => Uuid::from_str(id).ok(),
+            (
+                Opcode::Subscribe,
+                ClientMessage::Subscribe {
+                    channel: Channel::Game(game),
+                    ..
+                },
+            ) => Some(*game),
+            _ => None,
+        }
+    }
+
+    /// The game this packet asks to stop receiving the events of, if it is a request to
+    /// unsubscribe from a game's channel.
+    pub fn parts(&self) -> Option<Uuid> {
+        match (&self.op, &self.d) {
+            (
+                Opcode::Unsubscribe,
+                ClientMessage::Unsubscribe {
+                    channel: Channel::Game(game),
+                },
+            ) => Some(*game),
+            _ => None,
+        }
+    }
+
+    /// Fetch the ID of the user this packet was sent on behalf of.
+    pub async fn user(&self, state: &AppState) -> Option<Uuid> {
+        let id = self.current_user(state).await.ok()?;
+        Uuid::from_str(&id).ok()
+    }
+
     async fn current_user(&self, state: &AppState) -> Result<String, Event> {
-        helpers::get_session(state, &self.t)
+        let user = helpers::get_session(state, &self.t)
             .await
-            .map_err(|StringError(message, code)| Event::error(&message, code))
+            .map_err(|StringError(message, code)| error(&message, code))?;
+        if let Ok(id) = Uuid::from_str(&user) {
+            moderation::ensure_not_suspended(state, id)
+                .await
+                .map_err(Event::from)?;
+        }
+        Ok(user)
     }
 
     async fn game(&self, state: &AppState, id: &str) -> Result<game::Model, Event> {
         let id = Uuid::from_str(id)
-            .map_err(|_| Event::error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
+            .map_err(|_| error(strings::INVALID_GAME_ID_FORMAT, StatusCode::BAD_REQUEST))?;
         match GameModel::find_by_id(id).one(state.database.as_ref()).await {
             Ok(Some(game)) => Ok(game),
-            Ok(None) => Err(Event::error(
-                strings::INVALID_GAME_ID,
-                StatusCode::NOT_FOUND,
-            )),
-            Err(e) => Err(Event::error(
-                &e.to_string(),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            )),
+            Ok(None) => Err(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND)),
+            Err(e) => Err(error(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR)),
         }
     }
 }
 
 // A collection of helper functions for validating data.
-impl Packet {
+impl Request {
+    /// The piece the packet's sender plays in the specified game, which they're a participant
+    /// in.
+    async fn seat(&self, state: &AppState, game: &game::Model) -> Result<Piece, Event> {
+        let user = self.current_user(state).await?;
+        let (black, _) = helpers::black_white(game);
+        Ok(if black.is_some_and(|black| black.to_string() == user) {
+            Piece::Black
+        } else {
+            Piece::White
+        })
+    }
+
     async fn ensure_participant(&self, state: &AppState, id: &str) -> Result<(), Event> {
         let user = self.current_user(state).await?;
         let game = self.game(state, id).await?;
         if game.host != user && game.guest != user {
-            return Err(Event::error(
-                strings::INVALID_GAME_ID,
-                StatusCode::NOT_FOUND,
-            ));
+            return Err(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND));
         }
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Event {
-    op: EventKind,
-    d: EventData,
+/// Play a move in the specified game on behalf of whoever is playing the specified piece,
+/// letting everyone in the game know and ending the game if that was its last move. Hosted
+/// opponents are asked for their reply once it's their turn.
+pub async fn make_move(
+    state: &AppState,
+    metadata: &game::Model,
+    x: usize,
+    y: usize,
+    piece: Piece,
+) -> Result<Event, Event> {
+    let uuid = metadata.id;
+    if !state
+        .rooms
+        .lock()
+        .expect("mutex was poisoned")
+        .contains_key(&uuid)
+    {
+        return Err(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND));
+    }
+    let (res, mut game) = {
+        let mut games = state.games.lock().expect("mutex was poisoned");
+        let game = games
+            .get_mut(&uuid)
+            .ok_or(error(strings::INVALID_GAME_ID, StatusCode::NOT_FOUND))?;
+        game.place(x, y, piece)
+            .map_err(|e| Event::from(ApiError::from(e)))?;
+        (Event::new(EventKind::Ack, ServerMessage::Ack), game.clone())
+    };
+    if let Ok(mut conn) = state.redis.get().await {
+        let _ = conn
+            .set::<_, _, ()>(
+                format!("game:{uuid}"),
+                serde_json::to_string(&game).unwrap(),
+            )
+            .await;
+    }
+    // Send the update once the game is unlocked, since broadcasting locks the rooms.
+    state.broadcast(
+        uuid,
+        Event::new(
+            EventKind::GameUpdate,
+            ServerMessage::GameUpdate { game: game.clone() },
+        ),
+    );
+    let taken = state.turn_time(uuid).unwrap_or_default();
+    state.start_turn(uuid);
+    if let Err(StringError(message, _)) = helpers::save_position(state, uuid, &game).await {
+        tracing::error!("Failed to save position: {message}");
+    }
+    let ply = game.history().len();
+    if let Err(StringError(message, _)) = moves::record(state, metadata, ply, piece, taken).await {
+        tracing::error!("Failed to record move: {message}");
+    }
+    if game.over() {
+        summary::conclude(state, metadata, &game, None, None)
+            .await
+            .map_err(|StringError(message, code)| error(&message, code))?;
+    } else {
+        opponent::respond(state, metadata, &game);
+    }
+    Ok(res)
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
-#[repr(u8)]
-pub enum EventKind {
-    Ack = 1 << 0,
-    Ready,
-    GameAbort,
-    GameUpdate = 1 << 2,
-    GameUpdatePreview,
-    Error,
-    GameEnd,
+/// Subscribe the specified user's connection to a game's updates, returning the current state
+/// of the game as they see it.
+pub fn enter(
+    state: &AppState,
+    uuid: Uuid,
+    user: Uuid,
+    subscriber: &Subscriber,
+) -> Result<Event, ApiError> {
+    let not_found = || StringError(strings::INVALID_GAME_ID.into(), StatusCode::NOT_FOUND);
+    // Subscribe to the broadcast channel for the specified room.
+    let rooms = state.rooms.lock().expect("mutex was poisoned");
+    let rx = rooms.get(&uuid).ok_or_else(not_found)?.subscribe();
+    // Send the current state of the room.
+    let games = state.games.lock().expect("mutex was poisoned");
+    let game = games.get(&uuid).ok_or_else(not_found)?;
+    let viewer = Viewer::of(state, uuid, Some(user));
+    let update = viewer.project(
+        Event::new(
+            EventKind::GameUpdate,
+            ServerMessage::GameUpdate { game: game.clone() },
+        )
+        .in_game(&uuid),
+    );
+    let ServerMessage::GameUpdate { game } = &update.d else {
+        unreachable!("projection changed the kind of event")
+    };
+    // Relay room updates from now on, in place of any relay of the room the connection
+    // already had.
+    subscriber.relay(Channel::Game(uuid), rx, viewer, Some(game.clone()));
+    Ok(update)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum EventData {
-    Ack,
-    Ready,
-    GameCreate {
-        id: String,
-    },
-    GameUpdate {
-        game: Game,
-    },
-    GameUpdatePreview {
-        changed: Vec<(usize, usize)>,
-    },
-    GameAbort,
-    GameEnd {
-        winner: String,
-        points: usize,
-        total: usize,
-    },
-    Error {
-        message: String,
-        code: u16,
-    },
+/// Subscribe a spectator's connection to a game's updates the specified time behind its
+/// players, returning the game as they see it.
+async fn watch(
+    state: &AppState,
+    uuid: Uuid,
+    delay: Duration,
+    subscriber: Subscriber,
+) -> Result<Event, Event> {
+    let (rx, game) = delay::listen(state, uuid, delay)
+        .await
+        .map_err(|StringError(message, code)| error(&message, code))?;
+    let update = Viewer::Spectator.project(
+        Event::new(EventKind::GameUpdate, ServerMessage::GameUpdate { game }).in_game(&uuid),
+    );
+    let ServerMessage::GameUpdate { game } = &update.d else {
+        unreachable!("projection changed the kind of event")
+    };
+    subscriber.relay(
+        Channel::Game(uuid),
+        rx,
+        Viewer::Spectator,
+        Some(game.clone()),
+    );
+    Ok(update)
 }
 
-impl Event {
-    pub fn new(op: EventKind, d: EventData) -> Self {
-        Self { op, d }
-    }
-
-    pub fn error(message: &str, code: StatusCode) -> Self {
-        Self {
-            op: EventKind::Error,
-            d: EventData::Error {
-                message: message.to_string(),
-                code: code.into(),
-            },
+/// Forward events from a broadcast channel to a connection until either side closes, showing
+/// them as the specified viewer may see them. Boards are sent as often as the connection asked
+/// for, with only the changes since the last board the connection was sent (if any) otherwise.
+pub async fn relay(
+    mut rx: broadcast::Receiver<Event>,
+    subscriber: Subscriber,
+    viewer: Viewer,
+    mut last: Option<Game>,
+) {
+    let Subscriber {
+        sender, snapshots, ..
+    } = subscriber;
+    // The number of moves since the connection was last sent the whole board.
+    let mut moves = 0;
+    loop {
+        tokio::select! {
+            () = sender.closed() => break,
+            event = rx.recv() => {
+                let Ok(event) = event else { break };
+                let event = match viewer.project(event) {
+                    Event { d: ServerMessage::GameUpdate { game }, g, s, .. } => {
+                        moves += 1;
+                        let changed = last
+                            .as_ref()
+                            .filter(|_| !snapshots.due(moves))
+                            .map(|last| game.changes_since(last));
+                        last = Some(game.clone());
+                        let event = if let Some(changed) = changed {
+                            let turn = game.turn();
+                            Event::new(EventKind::GameDelta, ServerMessage::GameDelta { turn, changed })
+                        } else {
+                            moves = 0;
+                            Event::new(EventKind::GameUpdate, ServerMessage::GameUpdate { game })
+                        };
+                        Event { g, s, ..event }
+                    }
+                    event => event,
+                };
+                if sender.send(event).await.is_err() {
+                    break;
+                }
+            }
         }
     }
+}
 
-    pub fn data(&self) -> &EventData {
-        &self.d
-    }
+/// An event reporting the error with the specified message and status.
+pub fn error(message: &str, code: StatusCode) -> Event {
+    Event::from(ApiError::from(StringError(message.to_string(), code)))
 }
@@ -0,0 +1,174 @@
+use crate::server::{handlers::StringError, strings};
+use axum::http::StatusCode;
+pub use othello_api_types::Page;
+use sea_orm::{ConnectionTrait, EntityTrait, PaginatorTrait, QuerySelect, Select};
+use serde::Deserialize;
+
+/// How many items a page holds if the client doesn't say.
+pub const DEFAULT_PAGE_SIZE: u64 = 50;
+/// The most items a single page can hold.
+pub const MAX_PAGE_SIZE: u64 = 100;
+
+/// Which way a list is sorted. Lists are sorted newest first unless they say otherwise.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl From<Order> for sea_orm::Order {
+    fn from(order: Order) -> Self {
+        match order {
+            Order::Asc => Self::Asc,
+            Order::Desc => Self::Desc,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PaginationParams {
+    limit: Option<u64>,
+    offset: Option<u64>,
+    /// The `next` cursor of a previous page, which takes the place of `offset`.
+    cursor: Option<String>,
+    #[serde(default)]
+    order: Order,
+}
+
+/// The slice of a list that a client asked for, as the `limit`, `offset` (or `cursor`) and
+/// `order` query parameters of a request.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Pagination {
+    pub offset: u64,
+    limit: Option<u64>,
+    pub order: Order,
+}
+
+impl Pagination {
+    /// Check the pagination a client asked for.
+    /// # Errors
+    /// Returns an error if the limit is too large or the cursor isn't one the server issued.
+    pub fn new(params: PaginationParams) -> Result<Self, StringError> {
+        let invalid = |message: &str| StringError(message.into(), StatusCode::BAD_REQUEST);
+        if params
+            .limit
+            .is_some_and(|limit| limit == 0 || limit > MAX_PAGE_SIZE)
+        {
+            return Err(invalid(strings::INVALID_PAGE_SIZE));
+        }
+        let offset = match params.cursor {
+            // Cursors are currently just offsets, but clients shouldn't rely on that.
+            Some(cursor) => cursor
+                .parse()
+                .map_err(|_| invalid(strings::INVALID_CURSOR))?,
+            None => params.offset.unwrap_or_default(),
+        };
+        Ok(Self {
+            offset,
+            limit: params.limit,
+            order: params.order,
+        })
+    }
+
+    /// The number of items on the page, falling back to `DEFAULT_PAGE_SIZE`.
+    #[must_use]
+    pub fn limit(self) -> u64 {
+        self.limit_or(DEFAULT_PAGE_SIZE)
+    }
+
+    /// The number of items on the page, falling back to the specified default.
+    #[must_use]
+    pub fn limit_or(self, default: u64) -> u64 {
+        self.limit.unwrap_or(default)
+    }
+
+    /// Describe the page of the specified size taken from a list of `total` items.
+    #[must_use]
+    pub fn page(self, total: u64, limit: u64) -> Page {
+        let end = self.offset.saturating_add(limit);
+        Page {
+            total,
+            offset: self.offset,
+            limit,
+            next: (end < total).then(|| end.to_string()),
+        }
+    }
+
+    /// Take the page out of a list that has already been fetched and sorted.
+    #[must_use]
+    pub fn slice<T>(self, items: Vec<T>, limit: u64) -> (Vec<T>, Page) {
+        let page = self.page(items.len() as u64, limit);
+        let items = items
+            .into_iter()
+            .skip(usize::try_from(self.offset).unwrap_or(usize::MAX))
+            .take(usize::try_from(limit).unwrap_or(usize::MAX))
+            .collect();
+        (items, page)
+    }
+
+    /// Fetch the page out of the results of a sorted query.
+    /// # Errors
+    /// Returns an error if the database can't be queried.
+    pub async fn fetch<E, C>(
+        self,
+        db: &C,
+        query: Select<E>,
+    ) -> Result<(Vec<E::Model>, Page), StringError>
+    where
+        E: EntityTrait,
+        E::Model: Sync,
+        C: ConnectionTrait,
+    {
+        let error =
+            |e: sea_orm::DbErr| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR);
+        let total = query.clone().count(db).await.map_err(error)?;
+        let limit = self.limit();
+        let items = query
+            .offset(self.offset)
+            .limit(limit)
+            .all(db)
+            .await
+            .map_err(error)?;
+        Ok((items, self.page(total, limit)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pagination, PaginationParams, MAX_PAGE_SIZE};
+
+    #[test]
+    fn pages() {
+        let pagination = Pagination::new(PaginationParams {
+            limit: Some(2),
+            ..PaginationParams::default()
+        })
+        .unwrap();
+        let (items, page) = pagination.slice(vec![1, 2, 3, 4, 5], pagination.limit());
+        assert_eq!(items, [1, 2]);
+        assert_eq!(page.total, 5);
+        // Following the cursor picks up where the page left off.
+        let pagination = Pagination::new(PaginationParams {
+            limit: Some(2),
+            cursor: page.next,
+            ..PaginationParams::default()
+        })
+        .unwrap();
+        assert_eq!(pagination.offset, 2);
+        let (items, page) = pagination.slice(vec![1, 2, 3, 4], pagination.limit());
+        assert_eq!(items, [3, 4]);
+        assert_eq!(page.next, None);
+        assert!(Pagination::new(PaginationParams {
+            limit: Some(MAX_PAGE_SIZE + 1),
+            ..PaginationParams::default()
+        })
+        .is_err());
+        assert!(Pagination::new(PaginationParams {
+            cursor: Some(String::from("nonsense")),
+            ..PaginationParams::default()
+        })
+        .is_err());
+    }
+}
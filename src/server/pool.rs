@@ -0,0 +1,136 @@
+use redis::{aio::MultiplexedConnection, RedisResult};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// How many connections to Redis are kept, and how long they're given to answer.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSettings {
+    /// How many connections requests are spread across. Each connection can carry many
+    /// requests at once.
+    pub size: usize,
+    /// How long to wait for a new connection to be opened.
+    pub connect_timeout: Duration,
+    /// How long to wait for the answer to a command before giving up on it.
+    pub response_timeout: Duration,
+    /// How long a connection can go without being checked before it's pinged on its way out
+    /// of the pool, and replaced if it doesn't answer.
+    pub check_interval: Duration,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            size: 8,
+            connect_timeout: Duration::from_secs(2),
+            response_timeout: Duration::from_secs(2),
+            check_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+struct Slot {
+    conn: MultiplexedConnection,
+    checked: Instant,
+}
+
+/// Connections to Redis, opened on first use rather than up front, so that the server can
+/// start while Redis is still coming up.
+pub struct RedisPool {
+    client: redis::Client,
+    settings: PoolSettings,
+    slots: Vec<Mutex<Option<Slot>>>,
+    next: AtomicUsize,
+}
+
+impl RedisPool {
+    #[must_use]
+    pub fn new(client: redis::Client, settings: PoolSettings) -> Self {
+        Self {
+            client,
+            settings,
+            slots: (0..settings.size.max(1))
+                .map(|_| Mutex::new(None))
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// The client the pool's connections are opened with, for the few things that need a
+    /// connection to themselves (e.g. subscribing to channels).
+    #[must_use]
+    pub fn client(&self) -> &redis::Client {
+        &self.client
+    }
+
+    /// Get a connection, taking turns between those in the pool. Connections are opened the
+    /// first time they're needed, and replaced if they stop answering.
+    /// # Errors
+    /// Returns an error if a connection has to be opened and can't be.
+    pub async fn get(&self) -> RedisResult<MultiplexedConnection> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.slots.len();
+        let mut slot = self.slots[index].lock().await;
+        if let Some(Slot { conn, checked }) = slot.as_mut() {
+            if checked.elapsed() < self.settings.check_interval
+                || redis::cmd("PING")
+                    .query_async::<_, String>(conn)
+                    .await
+                    .is_ok()
+            {
+                *checked = Instant::now();
+                return Ok(conn.clone());
+            }
+            tracing::warn!("Replacing a Redis connection that stopped answering");
+        }
+        *slot = None;
+        let conn = self
+            .client
+            .get_multiplexed_async_connection_with_timeouts(
+                self.settings.response_timeout,
+                self.settings.connect_timeout,
+            )
+            .await?;
+        *slot = Some(Slot {
+            conn: conn.clone(),
+            checked: Instant::now(),
+        });
+        Ok(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PoolSettings, RedisPool};
+    use crate::server;
+    use redis::AsyncCommands;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn lazy() {
+        let settings = PoolSettings {
+            size: 2,
+            check_interval: Duration::ZERO,
+            ..PoolSettings::default()
+        };
+        // Nothing is opened until a connection is asked for, so an unreachable server is
+        // only noticed then.
+        let pool = RedisPool::new(
+            redis::Client::open("redis://127.0.0.1:1").unwrap(),
+            settings,
+        );
+        assert!(pool.get().await.is_err());
+        let pool = RedisPool::new(
+            redis::Client::open(server::Config::test().redis_url).unwrap(),
+            settings,
+        );
+        let key = format!("pool:{}", uuid::Uuid::now_v7());
+        for value in 0..4 {
+            let mut conn = pool.get().await.unwrap();
+            conn.set::<_, _, ()>(&key, value).await.unwrap();
+            assert_eq!(conn.get::<_, i32>(&key).await.unwrap(), value);
+        }
+        pool.get().await.unwrap().del::<_, ()>(&key).await.unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use crate::server::{
+    entities::{friend::Column as FriendColumn, prelude::Friend},
+    packet::{Event, EventKind, ServerMessage},
+    state::AppState,
+};
+pub use othello_api_types::Status;
+use redis::AsyncCommands;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The Redis hash holding the status of each of a user's open connections.
+fn key(user: Uuid) -> String {
+    format!("presence:{user}")
+}
+
+/// Fetch the specified user's presence, which is the liveliest status across all of their
+/// open connections.
+pub async fn status(state: &AppState, user: Uuid) -> Status {
+    let Ok(mut conn) = state.redis.get().await else {
+        return Status::Offline;
+    };
+    let connections: HashMap<String, String> = conn.hgetall(key(user)).await.unwrap_or_default();
+    connections
+        .values()
+        .filter_map(|status| status.parse().ok())
+        .max()
+        .unwrap_or(Status::Offline)
+}
+
+/// Keep the specified user's presence from expiring. Presence is only kept for as long as
+/// the heartbeat timeout, so that connections lost without a trace (e.g. when the server
+/// crashes) don't leave users online forever.
+pub async fn refresh(state: &AppState, user: Uuid) {
+    if let Ok(mut conn) = state.redis.get().await {
+        #[allow(clippy::cast_possible_wrap)] // Heartbeat timeouts are far below i64::MAX seconds
+        let _ = conn
+            .expire::<_, ()>(key(user), state.heartbeat.timeout.as_secs() as i64 + 1)
+            .await;
+    }
+}
+
+/// Record the status of one of the specified user's connections, or that it closed, letting
+/// their friends know if that changes the user's presence.
+pub async fn update(state: &AppState, user: Uuid, connection: Uuid, status: Option<Status>) {
+    let before = self::status(state, user).await;
+    if let Ok(mut conn) = state.redis.get().await {
+        let _ = match status {
+            Some(status) => {
+                conn.hset::<_, _, _, ()>(key(user), connection.to_string(), status.name())
+                    .await
+            }
+            None => {
+                conn.hdel::<_, _, ()>(key(user), connection.to_string())
+                    .await
+            }
+        };
+    }
+    refresh(state, user).await;
+    let after = self::status(state, user).await;
+    if before == after {
+        return;
+    }
+    let Ok(friends) = Friend::find()
+        .filter(FriendColumn::A.eq(user).or(FriendColumn::B.eq(user)))
+        .all(state.database.as_ref())
+        .await
+    else {
+        return;
+    };
+    for friend in friends {
+        let id = if friend.a == user { friend.b } else { friend.a };
+        state.notify(
+            id,
+            Event::new(
+                EventKind::FriendPresence,
+                ServerMessage::FriendPresence {
+                    user: user.to_string(),
+                    status: after,
+                },
+            ),
+        );
+    }
+}
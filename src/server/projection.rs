@@ -0,0 +1,128 @@
+use crate::{
+    server::{
+        packet::{Event, ServerMessage},
+        state::AppState,
+    },
+    settings::Variant,
+    Game, GameSettings, Piece,
+};
+use uuid::Uuid;
+
+/// Who game data is being shown to. Everything sent about a game passes through here, so
+/// that what each kind of viewer may see is decided in one place.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Viewer {
+    /// One of the game's players, and the piece they play.
+    Player(Piece),
+    /// A signed-in user who isn't playing in the game.
+    Spectator,
+    /// Someone who isn't signed in.
+    Anonymous,
+}
+
+/// What a viewer may see of a game.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Permissions {
+    /// The piece whose view of the board the viewer is limited to, or `None` for the whole
+    /// board.
+    pub fog: Option<Piece>,
+    /// Whether the viewer may see the engine's assessment of the position.
+    pub evaluations: bool,
+}
+
+impl Viewer {
+    /// Work out who the specified user (or an anonymous viewer) is to the specified game.
+    pub fn of(state: &AppState, game: Uuid, user: Option<Uuid>) -> Self {
+        match user {
+            None => Self::Anonymous,
+            Some(user) => state.seat(game, user).map_or(Self::Spectator, Self::Player),
+        }
+    }
+
+    /// What the viewer may see of a game played with the specified settings.
+    #[must_use]
+    pub fn permissions(self, settings: &GameSettings, over: bool) -> Permissions {
+        // Fog games keep their secrets from the players until the end. Spectators can't
+        // play, so they see the whole board, though the engine could still tip them off.
+        let fogged = settings.variant == Variant::Fog && !over;
+        match self {
+            Self::Player(piece) => Permissions {
+                fog: fogged.then_some(piece),
+                evaluations: !fogged,
+            },
+            Self::Spectator => Permissions {
+                fog: None,
+                evaluations: !fogged,
+            },
+            // Running the engine for anyone who asks isn't worth it.
+            Self::Anonymous => Permissions {
+                fog: None,
+                evaluations: false,
+            },
+        }
+    }
+
+    /// The event as the viewer may see it.
+    #[must_use]
+    pub fn project(self, event: Event) -> Event {
+        let d = match event.d {
+            ServerMessage::GameUpdate { mut game } => {
+                let over = game.over();
+                let permissions = self.permissions(game.settings(), over);
+                ServerMessage::GameUpdate {
+                    game: permissions.position(&game),
+                }
+            }
+            d => d,
+        };
+        Event {
+            op: event.op,
+            d,
+            g: event.g,
+            s: event.s,
+        }
+    }
+}
+
+impl Permissions {
+    /// The game as the viewer may see it.
+    #[must_use]
+    pub fn position(self, game: &Game) -> Game {
+        match self.fog {
+            Some(piece) => game.visible_to(piece),
+            None => game.clone(),
+        }
+    }
+
+    /// Whether the viewer may see the specified square.
+    #[must_use]
+    pub fn square(self, game: &Game, x: usize, y: usize) -> bool {
+        self.fog.is_none_or(|piece| game.visible(x, y, piece))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Viewer;
+    use crate::{settings::Variant, GameSettings, Piece};
+
+    #[test]
+    fn permissions() {
+        let fog = GameSettings {
+            variant: Variant::Fog,
+            ..GameSettings::default()
+        };
+        let player = Viewer::Player(Piece::White);
+        assert_eq!(player.permissions(&fog, false).fog, Some(Piece::White));
+        assert!(!player.permissions(&fog, false).evaluations);
+        // Everything is revealed once the game is over.
+        assert_eq!(player.permissions(&fog, true).fog, None);
+        assert!(
+            player
+                .permissions(&GameSettings::default(), false)
+                .evaluations
+        );
+        assert_eq!(Viewer::Spectator.permissions(&fog, false).fog, None);
+        assert!(!Viewer::Anonymous.permissions(&fog, true).evaluations);
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{
+    server::{
+        entities::{
+            daily_puzzle, prelude::DailyPuzzle, prelude::Puzzle, prelude::PuzzleAttempt,
+            prelude::PuzzleStreak, puzzle, puzzle_attempt, puzzle_streak,
+        },
+        helpers,
+        state::AppState,
+    },
+    Game, PlaceError,
+};
+use chrono::{Duration, NaiveDate};
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ActiveModelTrait, ActiveValue, DbErr, EntityTrait, IntoActiveModel, IsolationLevel, QueryOrder,
+    TransactionTrait,
+};
+use uuid::Uuid;
+
+/// A member's run of days solving the daily puzzle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Streak {
+    pub current: i32,
+    pub best: i32,
+    pub last_solved: Option<NaiveDate>,
+}
+
+impl From<puzzle_streak::Model> for Streak {
+    fn from(model: puzzle_streak::Model) -> Self {
+        Self {
+            current: model.current,
+            best: model.best,
+            last_solved: model.last_solved,
+        }
+    }
+}
+
+impl Streak {
+    /// The streak after answering the puzzle for `day`. Solving the day after the last solve
+    /// extends it, solving any other day starts a new one, and a wrong answer ends it.
+    #[must_use]
+    pub fn advance(self, day: NaiveDate, solved: bool) -> Self {
+        if !solved {
+            return Self { current: 0, ..self };
+        }
+        let current = if self.last_solved == Some(day - Duration::days(1)) {
+            self.current + 1
+        } else {
+            1
+        };
+        Self {
+            current,
+            best: self.best.max(current),
+            last_solved: Some(day),
+        }
+    }
+
+    /// The streak as of `today`. A streak lapses once a whole day passes without a solve.
+    #[must_use]
+    pub fn as_of(self, today: NaiveDate) -> i32 {
+        match self.last_solved {
+            Some(last) if last >= today - Duration::days(1) => self.current,
+            _ => 0,
+        }
+    }
+}
+
+/// Play out a puzzle's moves from the starting position.
+/// # Errors
+/// Returns the first move that isn't legal.
+pub fn position(moves: &[(usize, usize)]) -> Result<Game, PlaceError> {
+    let mut game = Game::new();
+    for &(x, y) in moves {
+        game.place(x, y, game.turn())?;
+    }
+    Ok(game)
+}
+
+/// Parse a puzzle's stored list of squares.
+#[must_use]
+pub fn squares(value: &serde_json::Value) -> Vec<(usize, usize)> {
+    serde_json::from_value(value.clone()).unwrap_or_default()
+}
+
+/// The puzzle for `day`, picking one the first time it's asked for. Puzzles that have never
+/// been shown come first, then the one shown longest ago. Returns `None` if there are no
+/// puzzles at all.
+/// # Errors
+/// Returns an error if the puzzles can't be read or written.
+pub async fn daily(state: &AppState, day: NaiveDate) -> Result<Option<puzzle::Model>, DbErr> {
+    let db = state.database.as_ref();
+    if let Some(daily) = DailyPuzzle::find_by_id(day).one(db).await? {
+        return Puzzle::find_by_id(daily.puzzle).one(db).await;
+    }
+    let Some(next) = Puzzle::find()
+        .order_by_desc(Expr::col(puzzle::Column::LastShown).is_null())
+        .order_by_asc(puzzle::Column::LastShown)
+        .order_by_asc(puzzle::Column::CreatedAt)
+        .one(db)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let result = DailyPuzzle::insert(daily_puzzle::ActiveModel {
+        day: ActiveValue::set(day),
+        puzzle: ActiveValue::set(next.id),
+    })
+    .on_conflict(
+        OnConflict::column(daily_puzzle::Column::Day)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec(db)
+    .await;
+    match result {
+        Ok(_) => {
+            let mut active = next.into_active_model();
+            active.last_shown = ActiveValue::set(Some(day));
+            active.update(db).await.map(Some)
+        }
+        // Another request picked the day's puzzle first.
+        Err(DbErr::RecordNotInserted) => {
+            let Some(daily) = DailyPuzzle::find_by_id(day).one(db).await? else {
+                return Ok(None);
+            };
+            Puzzle::find_by_id(daily.puzzle).one(db).await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Record a member's answer to the puzzle for `day` and update their streak. Returns the new
+/// streak, or `None`, writing nothing, if they'd already answered that day's puzzle.
+/// # Errors
+/// Returns an error if the answer can't be recorded.
+pub async fn answer(
+    state: &AppState,
+    member: Uuid,
+    day: NaiveDate,
+    puzzle: Uuid,
+    (x, y): (usize, usize),
+    solved: bool,
+) -> Result<Option<Streak>, DbErr> {
+    helpers::retry_conflicts(|| record(state, member, day, puzzle, (x, y), solved)).await
+}
+
+async fn record(
+    state: &AppState,
+    member: Uuid,
+    day: NaiveDate,
+    puzzle: Uuid,
+    (x, y): (usize, usize),
+    solved: bool,
+) -> Result<Option<Streak>, DbErr> {
+    let txn = state
+        .database
+        .begin_with_config(Some(IsolationLevel::Serializable), None)
+        .await?;
+    let inserted = PuzzleAttempt::insert(puzzle_attempt::ActiveModel {
+        member: ActiveValue::set(member),
+        day: ActiveValue::set(day),
+        puzzle: ActiveValue::set(puzzle),
+        // Answers are checked against the board before they're recorded.
+        x: ActiveValue::set(i32::try_from(x).unwrap_or(i32::MAX)),
+        y: ActiveValue::set(i32::try_from(y).unwrap_or(i32::MAX)),
+        solved: ActiveValue::set(solved),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([puzzle_attempt::Column::Member, puzzle_attempt::Column::Day])
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(&txn)
+    .await?;
+    if inserted == 0 {
+        return Ok(None);
+    }
+    let existing = PuzzleStreak::find_by_id(member).one(&txn).await?;
+    let streak = existing
+        .clone()
+        .map(Streak::from)
+        .unwrap_or_default()
+        .advance(day, solved);
+    let model = puzzle_streak::ActiveModel {
+        member: ActiveValue::set(member),
+        current: ActiveValue::set(streak.current),
+        best: ActiveValue::set(streak.best),
+        last_solved: ActiveValue::set(streak.last_solved),
+    };
+    if existing.is_some() {
+        model.update(&txn).await?;
+    } else {
+        model.insert(&txn).await?;
+    }
+    txn.commit().await?;
+    Ok(Some(streak))
+}
+
+/// A member's streak, or an empty one if they've never answered a puzzle.
+/// # Errors
+/// Returns an error if the streak can't be read.
+pub async fn streak(state: &AppState, member: Uuid) -> Result<Streak, DbErr> {
+    Ok(PuzzleStreak::find_by_id(member)
+        .one(state.database.as_ref())
+        .await?
+        .map(Streak::from)
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{position, Streak};
+    use crate::{Piece, PlaceError};
+    use chrono::NaiveDate;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, d).unwrap()
+    }
+
+    #[test]
+    fn streaks() {
+        let streak = Streak::default().advance(day(1), true);
+        assert_eq!((streak.current, streak.best), (1, 1));
+        let streak = streak.advance(day(2), true).advance(day(3), true);
+        assert_eq!((streak.current, streak.best), (3, 3));
+        // Skipping a day starts over.
+        let streak = streak.advance(day(5), true);
+        assert_eq!((streak.current, streak.best), (1, 3));
+        // So does a wrong answer, without forgetting the last solve.
+        let streak = streak.advance(day(6), false);
+        assert_eq!((streak.current, streak.best), (0, 3));
+        assert_eq!(streak.last_solved, Some(day(5)));
+    }
+
+    #[test]
+    fn lapsed() {
+        let streak = Streak::default().advance(day(1), true);
+        assert_eq!(streak.as_of(day(1)), 1);
+        assert_eq!(streak.as_of(day(2)), 1);
+        assert_eq!(streak.as_of(day(3)), 0);
+    }
+
+    #[test]
+    fn positions() {
+        let mut game = position(&[(2, 3), (2, 2)]).unwrap();
+        assert_eq!(game.turn(), Piece::Black);
+        assert!(!game.moves(Piece::Black).is_empty());
+        assert!(matches!(
+            position(&[(0, 0)]),
+            Err(PlaceError::NotAdjacent(0, 0))
+        ));
+    }
+}
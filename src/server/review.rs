@@ -0,0 +1,191 @@
+use crate::{
+    analysis::{self, Annotation, Classification},
+    server::{
+        entities::{analysis as record, prelude::Analysis},
+        state::AppState,
+    },
+    GameSettings, Piece,
+};
+use sea_orm::{sea_query::OnConflict, ActiveValue, DbErr, EntityTrait};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// How many of each side's moves fell short of the best one, and by how much.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tally {
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+/// A move of an analysed game, with what the engine made of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reviewed {
+    #[serde(rename = "move")]
+    pub played: (usize, usize),
+    #[serde(flatten)]
+    pub annotation: Annotation,
+}
+
+/// Count the inaccuracies, mistakes and blunders each side made in a game played from the
+/// starting position, as black and then white.
+#[must_use]
+pub fn tally(moves: &[Reviewed]) -> (Tally, Tally) {
+    let mut sides = (Tally::default(), Tally::default());
+    for (ply, reviewed) in moves.iter().enumerate() {
+        let side = match mover(ply) {
+            Piece::Black => &mut sides.0,
+            Piece::White => &mut sides.1,
+        };
+        match reviewed.annotation.classification {
+            Classification::Inaccuracy => side.inaccuracies += 1,
+            Classification::Mistake => side.mistakes += 1,
+            Classification::Blunder => side.blunders += 1,
+            Classification::Best | Classification::Good => (),
+        }
+    }
+    sides
+}
+
+/// The side that played the specified ply, counting from zero. Black always moves first.
+#[must_use]
+pub fn mover(ply: usize) -> Piece {
+    if ply.is_multiple_of(2) {
+        Piece::Black
+    } else {
+        Piece::White
+    }
+}
+
+/// Analyse a finished game in the background, storing the annotation of each of its moves.
+/// Games are only ever analysed once.
+pub fn queue(state: &AppState, game: Uuid, settings: GameSettings, history: Vec<(usize, usize)>) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        let result = match analyse(&state, settings, history).await {
+            Ok(reviewed) => store(&state, game, &reviewed).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::error!(%game, "Failed to analyse game: {e}");
+        }
+    });
+}
+
+/// The analysis of a finished game, running it through the engine (and storing the result)
+/// first if it hasn't been analysed yet, e.g. because it ended before games were analysed.
+/// # Errors
+/// Returns an error if the analysis can't be read or stored.
+pub async fn get_or_analyse(
+    state: &AppState,
+    game: Uuid,
+    settings: GameSettings,
+    history: Vec<(usize, usize)>,
+) -> Result<Vec<Reviewed>, DbErr> {
+    if let Some(reviewed) = get(state, game).await? {
+        return Ok(reviewed);
+    }
+    let reviewed = analyse(state, settings, history).await?;
+    store(state, game, &reviewed).await?;
+    Ok(reviewed)
+}
+
+/// Annotate each move of a game, away from the async runtime since it takes a while.
+async fn analyse(
+    state: &AppState,
+    settings: GameSettings,
+    history: Vec<(usize, usize)>,
+) -> Result<Vec<Reviewed>, DbErr> {
+    let weights = state.assets.weights();
+    tokio::task::spawn_blocking(move || {
+        let annotations = analysis::annotate(settings, &history, &weights);
+        history
+            .into_iter()
+            .zip(annotations)
+            .map(|(played, annotation)| Reviewed { played, annotation })
+            .collect()
+    })
+    .await
+    .map_err(|e| DbErr::Custom(e.to_string()))
+}
+
+async fn store(state: &AppState, game: Uuid, moves: &[Reviewed]) -> Result<(), DbErr> {
+    Analysis::insert(record::ActiveModel {
+        game: ActiveValue::set(game),
+        moves: ActiveValue::set(serde_json::to_value(moves).unwrap()),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(record::Column::Game)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(state.database.as_ref())
+    .await?;
+    Ok(())
+}
+
+/// The stored analysis of a game, if it's been analysed yet.
+/// # Errors
+/// Returns an error if the analysis can't be read.
+pub async fn get(state: &AppState, game: Uuid) -> Result<Option<Vec<Reviewed>>, DbErr> {
+    let Some(model) = Analysis::find_by_id(game)
+        .one(state.database.as_ref())
+        .await?
+    else {
+        return Ok(None);
+    };
+    serde_json::from_value(model.moves)
+        .map(Some)
+        .map_err(|e| DbErr::Json(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mover, tally, Reviewed, Tally};
+    use crate::{
+        analysis::{Annotation, Classification},
+        Piece,
+    };
+
+    fn reviewed(classification: Classification) -> Reviewed {
+        Reviewed {
+            played: (0, 0),
+            annotation: Annotation {
+                eval: 0,
+                best: (0, 0),
+                classification,
+            },
+        }
+    }
+
+    #[test]
+    fn tallies() {
+        let moves = [
+            reviewed(Classification::Blunder),
+            reviewed(Classification::Best),
+            reviewed(Classification::Inaccuracy),
+            reviewed(Classification::Mistake),
+            reviewed(Classification::Blunder),
+        ];
+        let (black, white) = tally(&moves);
+        assert_eq!(
+            black,
+            Tally {
+                inaccuracies: 1,
+                mistakes: 0,
+                blunders: 2
+            }
+        );
+        assert_eq!(
+            white,
+            Tally {
+                inaccuracies: 0,
+                mistakes: 1,
+                blunders: 0
+            }
+        );
+        assert_eq!(mover(4), Piece::Black);
+        assert_eq!(mover(3), Piece::White);
+    }
+}
@@ -0,0 +1,251 @@
+//! Jobs the server runs over and over in the background for as long as it's up, each on a
+//! schedule of its own. Jobs are added to the state before the server starts, started along
+//! with it, and stop when it starts shutting down, finishing any run already under way.
+
+use crate::server::{
+    archive, correspondence, handlers::StringError, helpers, invites, season, state::AppState,
+    webhooks,
+};
+use futures::future::{self, BoxFuture};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+
+/// How often finished games are checked for archiving.
+const ARCHIVE_INTERVAL: Duration = Duration::from_hours(1);
+/// How often expired sessions are cleared out.
+const SESSION_CLEANUP_INTERVAL: Duration = Duration::from_hours(1);
+/// How often unanswered invites are checked for having expired.
+const INVITE_EXPIRY_INTERVAL: Duration = Duration::from_mins(1);
+/// How often correspondence games are checked for deadlines coming up or gone by.
+const CORRESPONDENCE_INTERVAL: Duration = Duration::from_mins(5);
+/// How often the current season is checked for having ended.
+const SEASON_INTERVAL: Duration = Duration::from_mins(10);
+/// How often webhook deliveries that failed are checked for being due another try.
+const WEBHOOK_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+type Run = Arc<dyn Fn(Arc<AppState>) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// Something to do every so often. Runs of a job never overlap: if one takes longer than the
+/// period, the next starts a period after it finishes.
+#[derive(Clone)]
+pub struct Job {
+    name: &'static str,
+    period: Duration,
+    run: Run,
+}
+
+impl Job {
+    /// A job that runs straight away and then once every period.
+    pub fn new<F, Fut>(name: &'static str, period: Duration, run: F) -> Self
+    where
+        F: Fn(Arc<AppState>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        Self {
+            name,
+            period,
+            run: Arc::new(move |state| Box::pin(run(state))),
+        }
+    }
+
+    /// Move games that finished longer ago than the specified age into the archive.
+    #[must_use]
+    pub fn archival(age: Duration) -> Self {
+        Self::new("archival", ARCHIVE_INTERVAL, move |state| async move {
+            let archived = archive::archive_older_than(&state, age)
+                .await
+                .map_err(|e| e.to_string())?;
+            if archived > 0 {
+                tracing::info!("Archived {archived} finished games");
+            }
+            Ok(())
+        })
+    }
+
+    /// Delete sessions older than the specified lifetime, which can no longer be used.
+    #[must_use]
+    pub fn session_cleanup(ttl: Duration) -> Self {
+        Self::new(
+            "session_cleanup",
+            SESSION_CLEANUP_INTERVAL,
+            move |state| async move {
+                let deleted = helpers::delete_expired_sessions(&state, ttl)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if deleted > 0 {
+                    tracing::info!("Deleted {deleted} expired sessions");
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Delete game invites that have gone unanswered for longer than the specified lifetime,
+    /// letting their hosts know.
+    #[must_use]
+    pub fn invite_expiry(ttl: Duration) -> Self {
+        Self::new(
+            "invite_expiry",
+            INVITE_EXPIRY_INTERVAL,
+            move |state| async move {
+                let expired = invites::expire(&state, ttl)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if expired > 0 {
+                    tracing::info!("Expired {expired} unanswered invites");
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Remind players of the deadlines on their moves in correspondence games, and forfeit
+    /// the games of those who miss them.
+    #[must_use]
+    pub fn correspondence() -> Self {
+        Self::new(
+            "correspondence",
+            CORRESPONDENCE_INTERVAL,
+            |state| async move {
+                let (reminded, forfeited) = correspondence::enforce(&state)
+                    .await
+                    .map_err(|StringError(message, _)| message)?;
+                if reminded > 0 || forfeited > 0 {
+                    tracing::info!(
+                        "Reminded {reminded} players of deadlines and forfeited {forfeited} games"
+                    );
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Archive the current season once it's over, handing out its rewards and starting the
+    /// next, rather than waiting for somebody to ask about it.
+    #[must_use]
+    pub fn season_rollover() -> Self {
+        Self::new("season_rollover", SEASON_INTERVAL, |state| async move {
+            season::current(&state)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+    }
+
+    /// Retry the webhook deliveries that failed and are due another try.
+    #[must_use]
+    pub fn webhook_retries() -> Self {
+        Self::new(
+            "webhook_retries",
+            WEBHOOK_RETRY_INTERVAL,
+            |state| async move {
+                let retried = webhooks::retry(&state)
+                    .await
+                    .map_err(|StringError(message, _)| message)?;
+                if retried > 0 {
+                    tracing::info!("Retried {retried} webhook deliveries");
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+/// The jobs the server runs, and the tasks running them once they've started.
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Mutex<Vec<Job>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Scheduler {
+    pub(super) fn add(&self, job: Job) {
+        self.jobs.lock().expect("mutex was poisoned").push(job);
+    }
+
+    /// Start running every job that's been added, each on a task of its own.
+    pub(super) fn start(&self, state: &Arc<AppState>) {
+        let jobs = std::mem::take(&mut *self.jobs.lock().expect("mutex was poisoned"));
+        let mut tasks = self.tasks.lock().expect("mutex was poisoned");
+        for job in jobs {
+            tasks.push(tokio::spawn(run(Arc::clone(state), job)));
+        }
+    }
+
+    /// Wait for every job to stop, once the server has started shutting down.
+    pub(super) async fn stopped(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.lock().expect("mutex was poisoned"));
+        let _ = future::join_all(tasks).await;
+    }
+}
+
+/// Run the specified job on its schedule until the server starts shutting down.
+async fn run(state: Arc<AppState>, job: Job) {
+    let mut interval = tokio::time::interval(job.period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            () = state.shutting_down() => return,
+        }
+        let started = Instant::now();
+        match (job.run)(Arc::clone(&state)).await {
+            Ok(()) => tracing::debug!(job = job.name, elapsed = ?started.elapsed(), "Ran job"),
+            Err(e) => tracing::error!(job = job.name, "Job failed: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Job;
+    use crate::server;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn jobs() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&runs);
+        let job = Job::new("count", Duration::from_millis(20), move |_| {
+            let counted = Arc::clone(&counted);
+            async move {
+                counted.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        });
+        let failing = Job::new("fail", Duration::from_millis(20), |_| async {
+            Err(String::from("nothing to do"))
+        });
+        let state = Arc::new(
+            server::AppState::new(database, redis)
+                .with_job(job)
+                .with_job(failing),
+        );
+        // Nothing runs until the jobs are started.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        state.start_jobs();
+        tokio::time::sleep(Duration::from_millis(110)).await;
+        // Jobs run straight away, and failing ones carry on with their schedule.
+        assert!(runs.load(Ordering::SeqCst) >= 3);
+        state.shut_down();
+        state.jobs_stopped().await;
+        let stopped = runs.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), stopped);
+    }
+}
@@ -0,0 +1,512 @@
+use crate::server::{
+    entities::{
+        prelude::{Season, SeasonRating},
+        season, season_rating,
+    },
+    notifications::{self, Kind},
+    state::AppState,
+    summary::Outcome,
+};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait,
+    DatabaseTransaction, DbErr, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, QuerySelect,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{cmp::Reverse, collections::HashMap, str::FromStr};
+use uuid::Uuid;
+
+/// How long a season lasts, in days.
+pub const SEASON_DAYS: i64 = 90;
+/// The rating a player starts their first season with.
+pub const INITIAL_RATING: i32 = 1500;
+/// How many rated games a player plays each season before they're placed in the standings.
+pub const PLACEMENT_GAMES: i32 = 10;
+/// How far a single game can move a placed player's rating.
+const K: f64 = 24.0;
+/// How far a single game can move a rating during placement, so that players find their level
+/// quickly.
+const PLACEMENT_K: f64 = 64.0;
+/// How long a player can go without playing a rated game before their rating starts to decay,
+/// in days.
+const DECAY_GRACE_DAYS: i64 = 14;
+/// How many points an inactive player loses for each further week without playing.
+const DECAY_POINTS: i32 = 25;
+
+/// What a player earns for where they finished a season. Players who didn't finish their
+/// placement games don't earn one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    Bronze,
+    Silver,
+    Gold,
+    Platinum,
+    Diamond,
+}
+
+impl Tier {
+    /// The tier a season finished with the specified rating earns.
+    #[must_use]
+    pub fn for_rating(rating: i32) -> Self {
+        match rating {
+            ..=1399 => Self::Bronze,
+            1400..=1599 => Self::Silver,
+            1600..=1799 => Self::Gold,
+            1800..=1999 => Self::Platinum,
+            _ => Self::Diamond,
+        }
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bronze => "bronze",
+            Self::Silver => "silver",
+            Self::Gold => "gold",
+            Self::Platinum => "platinum",
+            Self::Diamond => "diamond",
+        }
+    }
+}
+
+impl FromStr for Tier {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bronze" => Ok(Self::Bronze),
+            "silver" => Ok(Self::Silver),
+            "gold" => Ok(Self::Gold),
+            "platinum" => Ok(Self::Platinum),
+            "diamond" => Ok(Self::Diamond),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The share of the points a player is expected to take from a game against an opponent.
+fn expected(rating: i32, opponent: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf(f64::from(opponent - rating) / 400.0))
+}
+
+/// How much a player's rating moves after a game against an opponent, where `score` is 1 for a
+/// win, 0.5 for a draw and 0 for a loss, and `games` is how many rated games they had already
+/// played this season.
+#[must_use]
+pub fn delta(rating: i32, opponent: i32, score: f64, games: i32) -> i32 {
+    let k = if games < PLACEMENT_GAMES {
+        PLACEMENT_K
+    } else {
+        K
+    };
+    #[allow(clippy::cast_possible_truncation)] // |k * (score - expected)| <= k
+    let delta = (k * (score - expected(rating, opponent))).round() as i32;
+    delta
+}
+
+/// A rating after the decay for going without rated games since `last_played` until `now`.
+/// Ratings decay towards `INITIAL_RATING` but never past it, so only players above it lose
+/// points for sitting on their rating.
+#[must_use]
+pub fn decayed(rating: i32, last_played: DateTime<FixedOffset>, now: DateTime<FixedOffset>) -> i32 {
+    let idle = now - last_played - Duration::days(DECAY_GRACE_DAYS);
+    if idle < Duration::zero() || rating <= INITIAL_RATING {
+        return rating;
+    }
+    let weeks = i32::try_from(idle.num_weeks() + 1).unwrap_or(i32::MAX);
+    rating
+        .saturating_sub(weeks.saturating_mul(DECAY_POINTS))
+        .max(INITIAL_RATING)
+}
+
+/// The rating a player starts a season with, given the one they finished their last season
+/// with: halfway back to `INITIAL_RATING`, so that everyone has something to play for.
+#[must_use]
+pub fn reset(rating: i32) -> i32 {
+    INITIAL_RATING + (rating - INITIAL_RATING) / 2
+}
+
+/// Whether a player has finished their placement games for the season.
+#[must_use]
+pub fn placed(rating: &season_rating::Model) -> bool {
+    rating.games >= PLACEMENT_GAMES
+}
+
+/// Fetch the season being played, archiving the last one and starting the next if it's over.
+/// # Errors
+/// Returns an error if the seasons can't be read or written.
+pub async fn current(state: &AppState) -> Result<season::Model, DbErr> {
+    let now = Utc::now().fixed_offset();
+    loop {
+        let latest = Season::find()
+            .order_by_desc(season::Column::Number)
+            .one(state.database.as_ref())
+            .await?;
+        match latest {
+            Some(season) if season.ends_at > now => return Ok(season),
+            Some(season) => {
+                if let Some(rewards) = archive(state, season.id).await? {
+                    reward(state, &season, &rewards).await;
+                }
+                begin(state, season.number + 1, now).await?;
+            }
+            None => begin(state, 1, now).await?,
+        }
+    }
+}
+
+/// Fetch a season by its number.
+/// # Errors
+/// Returns an error if the season can't be read.
+pub async fn by_number(state: &AppState, number: i32) -> Result<Option<season::Model>, DbErr> {
+    Season::find()
+        .filter(season::Column::Number.eq(number))
+        .one(state.database.as_ref())
+        .await
+}
+
+/// Start the season with the specified number, unless another request already has.
+async fn begin(state: &AppState, number: i32, now: DateTime<FixedOffset>) -> Result<(), DbErr> {
+    let result = Season::insert(season::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        number: ActiveValue::set(number),
+        starts_at: ActiveValue::set(now),
+        ends_at: ActiveValue::set(now + Duration::days(SEASON_DAYS)),
+        archived: ActiveValue::set(false),
+    })
+    .on_conflict(
+        OnConflict::column(season::Column::Number)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec(state.database.as_ref())
+    .await;
+    match result {
+        Ok(_) | Err(DbErr::RecordNotInserted) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Settle a finished season's ratings, decaying them up to the end of the season and handing
+/// out a tier to everyone who was placed. Returns the ratings that earned a tier, or `None` if
+/// the season had already been archived.
+async fn archive(state: &AppState, id: Uuid) -> Result<Option<Vec<season_rating::Model>>, DbErr> {
+    let txn = state.database.begin().await?;
+    let Some(season) = Season::find_by_id(id).lock_exclusive().one(&txn).await? else {
+        return Ok(None);
+    };
+    if season.archived {
+        return Ok(None);
+    }
+    let ratings = SeasonRating::find()
+        .filter(season_rating::Column::Season.eq(season.id))
+        .all(&txn)
+        .await?;
+    let mut rewards = vec![];
+    for rating in ratings {
+        let settled = decayed(rating.rating, rating.last_played_at, season.ends_at);
+        let tier = placed(&rating).then(|| Tier::for_rating(settled));
+        let mut active = rating.into_active_model();
+        active.rating = ActiveValue::set(settled);
+        active.tier = ActiveValue::set(tier.map(|tier| tier.name().into()));
+        let rating = active.update(&txn).await?;
+        if tier.is_some() {
+            rewards.push(rating);
+        }
+    }
+    let mut active = season.into_active_model();
+    active.archived = ActiveValue::set(true);
+    active.update(&txn).await?;
+    txn.commit().await?;
+    Ok(Some(rewards))
+}
+
+/// Let each player know the tier they earned for the season.
+async fn reward(state: &AppState, season: &season::Model, rewards: &[season_rating::Model]) {
+    for rating in rewards {
+        notifications::send(
+            state,
+            rating.member,
+            Kind::SeasonReward,
+            json!({
+                "season": season.number,
+                "rating": rating.rating,
+                "tier": rating.tier,
+            }),
+        )
+        .await;
+    }
+}
+
+/// Update both players' ratings for the season after a rated game, returning the change in
+/// each player's rating as `(black, white)`. Meant to run in the same transaction that records
+/// the game's result.
+/// # Errors
+/// Returns an error if the ratings can't be read or written.
+pub async fn rate(
+    txn: &DatabaseTransaction,
+    season: Uuid,
+    black: Uuid,
+    white: Uuid,
+    result: Outcome,
+) -> Result<(i32, i32), DbErr> {
+    let now = Utc::now().fixed_offset();
+    let black = entry(txn, season, black).await?;
+    let white = entry(txn, season, white).await?;
+    let score = match result {
+        Outcome::Black => 1.0,
+        Outcome::White => 0.0,
+        Outcome::Draw => 0.5,
+    };
+    let (black_rating, white_rating) = (
+        decayed(black.rating, black.last_played_at, now),
+        decayed(white.rating, white.last_played_at, now),
+    );
+    let black_delta = delta(black_rating, white_rating, score, black.games);
+    let white_delta = delta(white_rating, black_rating, 1.0 - score, white.games);
+    // Deltas are reported against the rating the player had before the game, decay included.
+    let black_change = black_rating + black_delta - black.rating;
+    let white_change = white_rating + white_delta - white.rating;
+    update(txn, black, black_rating + black_delta, score, now).await?;
+    update(txn, white, white_rating + white_delta, 1.0 - score, now).await?;
+    Ok((black_change, white_change))
+}
+
+/// Fetch a player's rating for the season, locking it for the rest of the transaction, and
+/// starting them off with one if they haven't played this season yet.
+async fn entry(
+    txn: &DatabaseTransaction,
+    season: Uuid,
+    member: Uuid,
+) -> Result<season_rating::Model, DbErr> {
+    if let Some(rating) = SeasonRating::find_by_id((season, member))
+        .lock_exclusive()
+        .one(txn)
+        .await?
+    {
+        return Ok(rating);
+    }
+    // Players pick up from whatever they finished their most recent season with.
+    let previous = SeasonRating::find()
+        .filter(season_rating::Column::Member.eq(member))
+        .filter(season_rating::Column::Season.ne(season))
+        .order_by_desc(season_rating::Column::LastPlayedAt)
+        .one(txn)
+        .await?;
+    let rating = previous.map_or(INITIAL_RATING, |previous| reset(previous.rating));
+    SeasonRating::insert(season_rating::ActiveModel {
+        season: ActiveValue::set(season),
+        member: ActiveValue::set(member),
+        rating: ActiveValue::set(rating),
+        peak: ActiveValue::set(rating),
+        games: ActiveValue::set(0),
+        wins: ActiveValue::set(0),
+        losses: ActiveValue::set(0),
+        draws: ActiveValue::set(0),
+        last_played_at: ActiveValue::set(Utc::now().fixed_offset()),
+        tier: ActiveValue::set(None),
+    })
+    .exec_with_returning(txn)
+    .await
+}
+
+async fn update(
+    txn: &DatabaseTransaction,
+    model: season_rating::Model,
+    rating: i32,
+    score: f64,
+    now: DateTime<FixedOffset>,
+) -> Result<(), DbErr> {
+    let mut active = model.clone().into_active_model();
+    active.rating = ActiveValue::set(rating);
+    // Placement games don't count towards a player's peak.
+    if model.games + 1 >= PLACEMENT_GAMES {
+        active.peak = ActiveValue::set(model.peak.max(rating));
+    }
+    active.games = ActiveValue::set(model.games + 1);
+    if score > 0.5 {
+        active.wins = ActiveValue::set(model.wins + 1);
+    } else if score < 0.5 {
+        active.losses = ActiveValue::set(model.losses + 1);
+    } else {
+        active.draws = ActiveValue::set(model.draws + 1);
+    }
+    active.last_played_at = ActiveValue::set(now);
+    active.update(txn).await?;
+    Ok(())
+}
+
+/// The ratings in the season being played of every placed player among the specified ones,
+/// keyed by their ID, fetched in a single query. Players who haven't been placed yet are left
+/// out.
+/// # Errors
+/// Returns an error if the ratings can't be read.
+pub async fn ratings<C: ConnectionTrait>(
+    db: &C,
+    members: Vec<Uuid>,
+) -> Result<HashMap<Uuid, i32>, DbErr> {
+    if members.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let now = Utc::now().fixed_offset();
+    let ratings = SeasonRating::find()
+        .inner_join(Season)
+        .filter(season::Column::Archived.eq(false))
+        .filter(season_rating::Column::Member.is_in(members))
+        .filter(season_rating::Column::Games.gte(PLACEMENT_GAMES))
+        // Should a finished season not have been archived yet, the newest one wins.
+        .order_by_asc(season::Column::Number)
+        .all(db)
+        .await?;
+    Ok(ratings
+        .into_iter()
+        .map(|rating| {
+            (
+                rating.member,
+                decayed(rating.rating, rating.last_played_at, now),
+            )
+        })
+        .collect())
+}
+
+/// A placed player's position in a season.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standing {
+    pub member: Uuid,
+    /// The player's rating, with any decay applied.
+    pub rating: i32,
+    /// The tier the player has earned, or would earn if the season ended now.
+    pub tier: Tier,
+    pub model: season_rating::Model,
+}
+
+/// Rank everyone who has been placed in a season, highest rating first. Ratings are decayed up
+/// to `now`, or to the end of the season if it's over, and ties go to whoever reached the
+/// higher peak.
+#[must_use]
+pub fn standings(
+    season: &season::Model,
+    ratings: Vec<season_rating::Model>,
+    now: DateTime<FixedOffset>,
+) -> Vec<Standing> {
+    let until = now.min(season.ends_at);
+    let mut standings: Vec<_> = ratings
+        .into_iter()
+        .filter(placed)
+        .map(|model| {
+            let rating = if season.archived {
+                model.rating
+            } else {
+                decayed(model.rating, model.last_played_at, until)
+            };
+            Standing {
+                member: model.member,
+                rating,
+                tier: Tier::for_rating(rating),
+                model,
+            }
+        })
+        .collect();
+    standings.sort_by_key(|standing| {
+        (
+            Reverse(standing.rating),
+            Reverse(standing.model.peak),
+            standing.member,
+        )
+    });
+    standings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tier, INITIAL_RATING, PLACEMENT_GAMES};
+    use crate::server::entities::{season, season_rating};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    #[test]
+    fn delta() {
+        // Evenly matched players trade half of K.
+        assert_eq!(super::delta(1500, 1500, 1.0, PLACEMENT_GAMES), 12);
+        assert_eq!(super::delta(1500, 1500, 0.0, PLACEMENT_GAMES), -12);
+        assert_eq!(super::delta(1500, 1500, 0.5, PLACEMENT_GAMES), 0);
+        // Placement games move ratings much further.
+        assert_eq!(super::delta(1500, 1500, 1.0, 0), 32);
+        // Beating a much weaker player is worth very little.
+        assert_eq!(super::delta(1900, 1500, 1.0, PLACEMENT_GAMES), 2);
+        assert_eq!(super::delta(1500, 1900, 1.0, PLACEMENT_GAMES), 22);
+    }
+
+    #[test]
+    fn decayed() {
+        let now = Utc::now().fixed_offset();
+        assert_eq!(super::decayed(1800, now - Duration::days(13), now), 1800);
+        assert_eq!(super::decayed(1800, now - Duration::days(15), now), 1775);
+        assert_eq!(super::decayed(1800, now - Duration::days(22), now), 1750);
+        // Decay stops at the initial rating, and never touches anyone below it.
+        assert_eq!(
+            super::decayed(1550, now - Duration::days(365), now),
+            INITIAL_RATING
+        );
+        assert_eq!(super::decayed(1300, now - Duration::days(365), now), 1300);
+    }
+
+    #[test]
+    fn reset() {
+        assert_eq!(super::reset(1900), 1700);
+        assert_eq!(super::reset(1300), 1400);
+        assert_eq!(super::reset(INITIAL_RATING), INITIAL_RATING);
+    }
+
+    #[test]
+    fn tiers() {
+        assert_eq!(Tier::for_rating(900), Tier::Bronze);
+        assert_eq!(Tier::for_rating(1400), Tier::Silver);
+        assert_eq!(Tier::for_rating(1799), Tier::Gold);
+        assert_eq!(Tier::for_rating(1800), Tier::Platinum);
+        assert_eq!(Tier::for_rating(2400), Tier::Diamond);
+        assert_eq!("gold".parse(), Ok(Tier::Gold));
+    }
+
+    #[test]
+    fn standings() {
+        let now = Utc::now().fixed_offset();
+        let season = season::Model {
+            id: Uuid::now_v7(),
+            number: 1,
+            starts_at: now - Duration::days(60),
+            ends_at: now + Duration::days(30),
+            archived: false,
+        };
+        let rating = |rating, peak, games, idle| season_rating::Model {
+            season: season.id,
+            member: Uuid::now_v7(),
+            rating,
+            peak,
+            games,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            last_played_at: now - Duration::days(idle),
+            tier: None,
+        };
+        let ann = rating(1700, 1700, PLACEMENT_GAMES, 0);
+        let bob = rating(1700, 1750, PLACEMENT_GAMES, 0);
+        // Cat's rating has decayed below theirs.
+        let cat = rating(1720, 1720, PLACEMENT_GAMES, 20);
+        // Dan is still being placed.
+        let dan = rating(2000, 2000, PLACEMENT_GAMES - 1, 0);
+        let standings = super::standings(
+            &season,
+            vec![ann.clone(), bob.clone(), cat.clone(), dan],
+            now,
+        );
+        let order: Vec<_> = standings.iter().map(|standing| standing.member).collect();
+        assert_eq!(order, [bob.member, ann.member, cat.member]);
+        assert_eq!(standings[2].rating, 1695);
+        assert_eq!(standings[2].tier, Tier::Gold);
+    }
+}
@@ -1,19 +1,131 @@
-use crate::{server::packet::Event, Game};
+use crate::{
+    server::{
+        assets::Assets,
+        bots::{BotLimits, Usage},
+        cors::CorsPolicy,
+        delay::Delayed,
+        event_log, fanout,
+        moderation::WordFilter,
+        network::NetworkPolicy,
+        opponent::{SearchPool, DEFAULT_SEARCH_WORKERS},
+        packet::{Channel, Event, EventKind, ServerMessage},
+        pool::{PoolSettings, RedisPool},
+        scheduler::{Job, Scheduler},
+        storage::{MemoryStorage, Storage},
+        webhooks::Dispatcher,
+    },
+    Game, Piece,
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use redis::AsyncCommands;
 use sea_orm::DatabaseConnection;
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{broadcast, mpsc, watch},
+    task::AbortHandle,
 };
-use tokio::sync::broadcast;
 use uuid::Uuid;
 
+/// How often the server pings websocket clients, and how long it waits to hear anything
+/// back before considering the connection dead.
+#[derive(Debug, Clone, Copy)]
+pub struct Heartbeat {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(45),
+        }
+    }
+}
+
+/// How many failed logins in a row lock an account, and for how long.
+#[derive(Debug, Clone, Copy)]
+pub struct LoginLimits {
+    pub max_failures: u64,
+    /// How long an account stays locked, and how long failures are remembered for.
+    pub lockout: Duration,
+}
+
+impl Default for LoginLimits {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            lockout: Duration::from_mins(15),
+        }
+    }
+}
+
+/// How long users can go without sending anything before they're considered idle, unless
+/// configured otherwise.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_mins(5);
+/// How long disconnected players have to come back before forfeiting their games, unless
+/// configured otherwise.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_mins(1);
+
 #[derive(Clone)]
 #[allow(clippy::module_name_repetitions)] // This seems fine
 pub struct AppState {
     pub(super) games: Arc<Mutex<HashMap<Uuid, Game>>>,
     pub(super) rooms: Arc<Mutex<HashMap<Uuid, broadcast::Sender<Event>>>>,
+    pub(super) users: Arc<Mutex<HashMap<Uuid, broadcast::Sender<Event>>>>,
+    /// Challenges being opened in and leaving the lobby.
+    pub(super) lobby: broadcast::Sender<Event>,
+    pub(super) connections: Arc<Mutex<HashMap<Uuid, usize>>>,
+    pub(super) absent: Arc<Mutex<HashMap<Uuid, Instant>>>,
+    /// The tasks buffering the events of suspended sessions, by resume token, along with an ID
+    /// for each suspension.
+    pub(super) suspended: Arc<Mutex<HashMap<String, (Uuid, AbortHandle)>>>,
+    pub(super) turns: Arc<Mutex<HashMap<Uuid, Instant>>>,
+    /// The sequence number of the latest event sent to each game's room.
+    pub(super) sequences: Arc<Mutex<HashMap<Uuid, u64>>>,
+    /// The players of each game in memory, as black and then white.
+    pub(super) seats: Arc<Mutex<HashMap<Uuid, (Uuid, Uuid)>>>,
+    pub(super) heartbeat: Heartbeat,
+    pub(super) idle: Duration,
+    pub(super) grace: Duration,
+    pub(super) stall: Option<Duration>,
+    pub(super) session_ttl: Option<Duration>,
+    pub(super) invite_ttl: Option<Duration>,
+    /// How far behind their players spectators watch tournament games.
+    pub(super) spectator_delay: Option<Duration>,
+    /// The events of each tournament game being watched, as its spectators see them.
+    pub(super) delayed: Arc<Mutex<HashMap<Uuid, Delayed>>>,
+    pub(super) login: LoginLimits,
+    pub(super) bot_limits: BotLimits,
+    /// How many requests each bot has made in its current window.
+    pub(super) bot_usage: Arc<Mutex<HashMap<Uuid, Usage>>>,
+    pub(super) network: NetworkPolicy,
+    pub(super) cors: CorsPolicy,
+    pub(super) word_filter: WordFilter,
+    /// Where hosted opponents search for their moves.
+    pub(super) searches: Arc<SearchPool>,
+    /// Set once the server starts shutting down, telling open connections to close.
+    pub(super) shutdown: Arc<watch::Sender<bool>>,
+    /// The recurring jobs run in the background, which stop when the server shuts down.
+    pub(super) scheduler: Arc<Scheduler>,
+    /// Identifies this instance among any others sharing the cache.
+    pub(super) instance: Uuid,
+    /// Whether events are published for other instances to pass on.
+    pub(super) fanout: bool,
+    /// Events waiting to be published, as their channel and payload. Set up the first time
+    /// an event is published, so that they go out in the order they were sent.
+    pub(super) outbox: Arc<OnceLock<mpsc::UnboundedSender<(String, String)>>>,
+    pub(super) storage: Arc<dyn Storage>,
+    /// How webhook deliveries are sent.
+    pub(super) webhooks: Dispatcher,
+    pub(super) assets: Arc<Assets>,
     pub(super) database: Arc<DatabaseConnection>,
-    pub(super) redis: Arc<redis::Client>,
+    pub(super) redis: Arc<RedisPool>,
 }
 
 impl AppState {
@@ -22,8 +134,448 @@ impl AppState {
         Self {
             games: Arc::new(Mutex::new(HashMap::new())),
             rooms: Arc::new(Mutex::new(HashMap::new())),
+            users: Arc::new(Mutex::new(HashMap::new())),
+            lobby: broadcast::channel(64).0,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            absent: Arc::new(Mutex::new(HashMap::new())),
+            suspended: Arc::new(Mutex::new(HashMap::new())),
+            turns: Arc::new(Mutex::new(HashMap::new())),
+            sequences: Arc::new(Mutex::new(HashMap::new())),
+            seats: Arc::new(Mutex::new(HashMap::new())),
+            heartbeat: Heartbeat::default(),
+            idle: DEFAULT_IDLE_TIMEOUT,
+            grace: DEFAULT_GRACE_PERIOD,
+            stall: None,
+            session_ttl: None,
+            invite_ttl: None,
+            spectator_delay: None,
+            delayed: Arc::new(Mutex::new(HashMap::new())),
+            login: LoginLimits::default(),
+            bot_limits: BotLimits::default(),
+            bot_usage: Arc::new(Mutex::new(HashMap::new())),
+            network: NetworkPolicy::default(),
+            cors: CorsPolicy::default(),
+            word_filter: WordFilter::default(),
+            searches: Arc::new(SearchPool::new(DEFAULT_SEARCH_WORKERS)),
+            shutdown: Arc::new(watch::channel(false).0),
+            scheduler: Arc::new(Scheduler::default()),
+            instance: Uuid::now_v7(),
+            fanout: false,
+            outbox: Arc::new(OnceLock::new()),
+            storage: Arc::new(MemoryStorage::default()),
+            webhooks: Dispatcher::new(false),
+            assets: Arc::new(Assets::default()),
             database: Arc::new(database),
-            redis: Arc::new(redis),
+            redis: Arc::new(RedisPool::new(redis, PoolSettings::default())),
+        }
+    }
+
+    /// Keep as many connections to Redis as the specified settings say, with their timeouts.
+    #[must_use]
+    pub fn with_redis_pool(mut self, settings: PoolSettings) -> Self {
+        self.redis = Arc::new(RedisPool::new(self.redis.client().clone(), settings));
+        self
+    }
+
+    /// Use the specified heartbeat configuration for websocket connections.
+    #[must_use]
+    pub fn with_heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// Consider users idle once they go the specified amount of time without sending
+    /// anything over any of their connections.
+    #[must_use]
+    pub fn with_idle_timeout(mut self, idle: Duration) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// Forfeit active games on behalf of players who stay disconnected for longer than the
+    /// specified grace period.
+    #[must_use]
+    pub fn with_grace_period(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Keep uploaded files (e.g. avatars) in the specified storage. Without one, uploads are
+    /// only kept in memory.
+    #[must_use]
+    pub fn with_storage(mut self, storage: impl Storage + 'static) -> Self {
+        self.storage = Arc::new(storage);
+        self
+    }
+
+    /// Let webhooks be delivered to private addresses (e.g. this host), which they're kept from
+    /// by default so that they can't be used to reach the server's own network.
+    #[must_use]
+    pub fn with_private_webhooks(mut self, allowed: bool) -> Self {
+        self.webhooks = Dispatcher::new(allowed);
+        self
+    }
+
+    /// Read the engine's evaluation weights from the specified JSON file, both when the
+    /// assets are first loaded and whenever they're reloaded.
+    #[must_use]
+    pub fn with_eval_weights(mut self, path: impl Into<PathBuf>) -> Self {
+        let book_path = self.assets.book_path().cloned();
+        self.assets = Arc::new(Assets::new(Some(path.into()), book_path));
+        self
+    }
+
+    /// Read the opening book from the specified JSON file, both when the assets are first
+    /// loaded and whenever they're reloaded.
+    #[must_use]
+    pub fn with_opening_book(mut self, path: impl Into<PathBuf>) -> Self {
+        let weights_path = self.assets.weights_path().cloned();
+        self.assets = Arc::new(Assets::new(weights_path, Some(path.into())));
+        self
+    }
+
+    /// Reload the engine's assets from disk, returning the names of those that were reloaded.
+    /// # Errors
+    /// Returns an error if any of the assets can't be loaded, in which case the ones already
+    /// in use are kept.
+    pub async fn reload_assets(&self) -> Result<Vec<&'static str>, String> {
+        self.assets.reload().await
+    }
+
+    /// Let players claim the win or declare a draw once their opponent has spent longer than
+    /// the specified time on a single turn. Nobody can while this is unset.
+    #[must_use]
+    pub fn with_stall_timeout(mut self, stall: Duration) -> Self {
+        self.stall = Some(stall);
+        self
+    }
+
+    /// End sessions once they're older than the specified duration, making their users log in
+    /// again. Sessions last until their users log out while this is unset.
+    #[must_use]
+    pub fn with_session_ttl(mut self, ttl: Duration) -> Self {
+        self.session_ttl = Some(ttl);
+        self
+    }
+
+    /// Expire game invites that go unanswered for longer than the specified duration. Invites
+    /// wait for an answer forever while this is unset.
+    #[must_use]
+    pub fn with_invite_ttl(mut self, ttl: Duration) -> Self {
+        self.invite_ttl = Some(ttl);
+        self
+    }
+
+    /// Hold the events of tournament games back from their spectators for the specified
+    /// duration, so that nobody can coach a player as they play. Spectators watch as the
+    /// games are played while this is unset.
+    #[must_use]
+    pub fn with_spectator_delay(mut self, delay: Duration) -> Self {
+        self.spectator_delay = Some(delay);
+        self
+    }
+
+    /// Lock accounts according to the specified limits on failed logins.
+    #[must_use]
+    pub fn with_login_limits(mut self, login: LoginLimits) -> Self {
+        self.login = login;
+        self
+    }
+
+    /// Limit how many requests each bot account can make, as the specified limits say.
+    #[must_use]
+    pub fn with_bot_limits(mut self, bots: BotLimits) -> Self {
+        self.bot_limits = bots;
+        self
+    }
+
+    /// Use the specified policy to decide which addresses to believe and serve.
+    #[must_use]
+    pub fn with_network_policy(mut self, network: NetworkPolicy) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Let browser clients on other sites call the API as the specified policy allows.
+    #[must_use]
+    pub fn with_cors(mut self, cors: CorsPolicy) -> Self {
+        self.cors = cors;
+        self
+    }
+
+    /// Screen user-written text (e.g. reported messages) against the specified word filter.
+    #[must_use]
+    pub fn with_word_filter(mut self, word_filter: WordFilter) -> Self {
+        self.word_filter = word_filter;
+        self
+    }
+
+    /// Have hosted opponents search for their moves on the specified number of threads.
+    #[must_use]
+    pub fn with_search_workers(mut self, workers: usize) -> Self {
+        self.searches = Arc::new(SearchPool::new(workers));
+        self
+    }
+
+    /// Publish the events sent to rooms and users through the cache, so that several instances
+    /// can serve the same games and users. The instances need to run `relay` to pass on what
+    /// the others publish.
+    #[must_use]
+    pub fn with_fanout(mut self) -> Self {
+        self.fanout = true;
+        self
+    }
+
+    /// Run the specified job in the background on its schedule, once the jobs are started.
+    #[must_use]
+    pub fn with_job(self, job: Job) -> Self {
+        self.scheduler.add(job);
+        self
+    }
+
+    /// Start running the jobs added with `with_job`, until the server starts shutting down.
+    pub fn start_jobs(self: &Arc<Self>) {
+        self.scheduler.start(self);
+    }
+
+    /// Wait for the jobs to stop after the server starts shutting down, letting any that are
+    /// running finish first.
+    pub async fn jobs_stopped(&self) {
+        self.scheduler.stopped().await;
+    }
+
+    /// Start shutting down: every open websocket connection is told the server is restarting
+    /// and then closed. Safe to call more than once.
+    pub fn shut_down(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Whether the server has started shutting down.
+    pub(super) fn is_shutting_down(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    /// Wait until the server starts shutting down.
+    pub async fn shutting_down(&self) {
+        let mut shutdown = self.shutdown.subscribe();
+        let _ = shutdown.wait_for(|down| *down).await;
+    }
+
+    /// Write every game in memory to the cache, so that `restore_active_games` picks them up
+    /// where they were left when the server comes back.
+    /// # Errors
+    /// Returns an error if the cache can't be reached.
+    /// # Panics
+    /// Panics if the mutex is poisoned.
+    pub async fn persist_games(&self) -> redis::RedisResult<usize> {
+        let mut conn = self.redis.get().await?;
+        let games: Vec<_> = self
+            .games
+            .lock()
+            .expect("mutex was poisoned")
+            .iter()
+            .map(|(id, game)| (format!("game:{id}"), serde_json::to_string(game).unwrap()))
+            .collect();
+        for (key, game) in &games {
+            conn.set::<_, _, ()>(key, game).await?;
+        }
+        Ok(games.len())
+    }
+
+    /// The piece the specified user plays in the specified game, if they're playing in it.
+    pub(super) fn seat(&self, game: Uuid, user: Uuid) -> Option<Piece> {
+        let seats = self.seats.lock().expect("mutex was poisoned");
+        match seats.get(&game) {
+            Some(&(black, _)) if black == user => Some(Piece::Black),
+            Some(&(_, white)) if white == user => Some(Piece::White),
+            _ => None,
+        }
+    }
+
+    /// Subscribe to the events addressed to the specified user, regardless of which game
+    /// (if any) they relate to.
+    pub(super) fn subscribe(&self, user: Uuid) -> broadcast::Receiver<Event> {
+        let mut users = self.users.lock().expect("mutex was poisoned");
+        users
+            .entry(user)
+            .or_insert_with(|| broadcast::channel(16).0)
+            .subscribe()
+    }
+
+    /// Send an event to every connection the specified user currently has open. Users
+    /// without an open connection simply miss the event.
+    pub(super) fn notify(&self, user: Uuid, event: Event) {
+        if self.fanout {
+            fanout::publish(self, &fanout::user_channel(user), &event);
+        }
+        let users = self.users.lock().expect("mutex was poisoned");
+        if let Some(tx) = users.get(&user) {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Subscribe to the events of the specified channel, unless it's a game that isn't being
+    /// played here.
+    pub(super) fn listen(&self, channel: Channel) -> Option<broadcast::Receiver<Event>> {
+        match channel {
+            Channel::Lobby => Some(self.lobby.subscribe()),
+            Channel::Game(game) => self
+                .rooms
+                .lock()
+                .expect("mutex was poisoned")
+                .get(&game)
+                .map(broadcast::Sender::subscribe),
+        }
+    }
+
+    /// Send an event to every connection subscribed to the lobby.
+    pub(super) fn update_lobby(&self, event: Event) {
+        if self.fanout {
+            fanout::publish(self, fanout::LOBBY_CHANNEL, &event);
+        }
+        let _ = self.lobby.send(event);
+    }
+
+    /// Send an event to every connection in the specified game's room, marked as coming from
+    /// the game and numbered after the game's previous event. The event is kept for
+    /// connections that rejoin having missed it.
+    pub(super) fn broadcast(&self, game: Uuid, event: Event) {
+        let event = {
+            let mut sequences = self.sequences.lock().expect("mutex was poisoned");
+            let sequence = sequences.entry(game).or_default();
+            *sequence += 1;
+            Event {
+                s: Some(*sequence),
+                ..event.in_game(&game)
+            }
+        };
+        event_log::append(self, game, &event);
+        if self.fanout {
+            fanout::publish(self, &fanout::room_channel(game), &event);
+        }
+        let rooms = self.rooms.lock().expect("mutex was poisoned");
+        if let Some(tx) = rooms.get(&game) {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Carry on numbering the events of the specified game's room after the specified sequence
+    /// number, unless they're already past it, e.g. after restoring the game or hearing of an
+    /// event another instance sent.
+    pub(super) fn resume_sequence(&self, game: Uuid, sequence: u64) {
+        let mut sequences = self.sequences.lock().expect("mutex was poisoned");
+        let latest = sequences.entry(game).or_default();
+        *latest = (*latest).max(sequence);
+    }
+
+    /// Record that the specified user opened a websocket connection. Returns whether this is
+    /// their only open connection, i.e. whether they just came online.
+    pub(super) fn connect(&self, user: Uuid) -> bool {
+        let mut connections = self.connections.lock().expect("mutex was poisoned");
+        let count = connections.entry(user).or_insert(0);
+        *count += 1;
+        let mut absent = self.absent.lock().expect("mutex was poisoned");
+        absent.remove(&user);
+        *count == 1
+    }
+
+    /// Record that one of the specified user's websocket connections closed, releasing
+    /// everything held on their behalf once the last one is gone. Returns whether the user
+    /// just went offline.
+    pub(super) fn disconnect(&self, user: Uuid) -> bool {
+        let mut connections = self.connections.lock().expect("mutex was poisoned");
+        let Some(count) = connections.get_mut(&user) else {
+            return false;
+        };
+        *count -= 1;
+        if *count > 0 {
+            return false;
+        }
+        connections.remove(&user);
+        self.unsubscribe(user);
+        true
+    }
+
+    /// Forget the channel of the events addressed to the specified user once no connection is
+    /// listening to it any more.
+    pub(super) fn unsubscribe(&self, user: Uuid) {
+        let mut users = self.users.lock().expect("mutex was poisoned");
+        if users.get(&user).is_some_and(|tx| tx.receiver_count() == 0) {
+            users.remove(&user);
         }
     }
+
+    /// Start keeping track of how long the specified user has been away while they have
+    /// games in progress. The absence ends when they next connect.
+    pub(super) fn depart(&self, user: Uuid) -> Instant {
+        let now = Instant::now();
+        let mut absent = self.absent.lock().expect("mutex was poisoned");
+        absent.insert(user, now);
+        now
+    }
+
+    /// Whether the specified user has been away without interruption since the specified
+    /// time, forgetting about the absence if so.
+    pub(super) fn returned_since(&self, user: Uuid, since: Instant) -> bool {
+        let mut absent = self.absent.lock().expect("mutex was poisoned");
+        if absent.get(&user) == Some(&since) {
+            absent.remove(&user);
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Start the clock on the current turn of the specified game.
+    pub(super) fn start_turn(&self, game: Uuid) {
+        let mut turns = self.turns.lock().expect("mutex was poisoned");
+        turns.insert(game, Instant::now());
+    }
+
+    /// Pick up the clock on the current turn of the specified game from when it started, e.g.
+    /// when restoring the game after a restart.
+    pub(super) fn resume_turn(&self, game: Uuid, started: DateTime<FixedOffset>) {
+        let elapsed = Utc::now()
+            .signed_duration_since(started)
+            .to_std()
+            .unwrap_or_default();
+        let started = Instant::now()
+            .checked_sub(elapsed)
+            .unwrap_or_else(Instant::now);
+        let mut turns = self.turns.lock().expect("mutex was poisoned");
+        turns.insert(game, started);
+    }
+
+    /// How long the current turn of the specified game has taken so far, if it's being played
+    /// here.
+    pub(super) fn turn_time(&self, game: Uuid) -> Option<Duration> {
+        let turns = self.turns.lock().expect("mutex was poisoned");
+        turns.get(&game).map(Instant::elapsed)
+    }
+
+    /// Whether the player on turn in the specified game has been stalling, i.e. has spent
+    /// longer than the stall timeout on the current turn.
+    pub(super) fn stalled(&self, game: Uuid) -> bool {
+        let Some(stall) = self.stall else {
+            return false;
+        };
+        let turns = self.turns.lock().expect("mutex was poisoned");
+        turns
+            .get(&game)
+            .is_some_and(|started| started.elapsed() >= stall)
+    }
+
+    /// Let everyone in the specified game's room know whether a player is connected.
+    pub(super) fn announce(&self, game: Uuid, user: Uuid, online: bool) {
+        self.broadcast(
+            game,
+            Event::new(
+                EventKind::Presence,
+                ServerMessage::Presence {
+                    user: user.to_string(),
+                    online,
+                },
+            ),
+        );
+    }
 }
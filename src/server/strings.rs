@@ -6,12 +6,41 @@ pub const PASSWORD_MISMATCH: &str = "New password and confirmation do not match.
 pub const PASSWORD_TOO_SHORT: &str = "Password must be at least 8 characters.";
 pub const PASSWORD_NO_ALPHA: &str = "Password must contain at least one alphabetic character.";
 pub const PASSWORD_NO_NUMERIC: &str = "Password must contain at least one number.";
+pub const INVALID_TIMEZONE: &str = "Time zone must be UTC or a tz database name like Europe/London.";
 pub const INVALID_USERNAME: &str =
     "That user doesn't exist! Make sure their username is spelled correctly.";
 pub const ALREADY_FRIENDS: &str = "You're already friends with that user!";
 pub const FRIEND_SELF: &str = "You can't friend yourself!";
 pub const GAME_SELF: &str = "You can't create a game with yourself!";
+pub const BLOCK_SELF: &str = "You can't block yourself!";
+pub const ALREADY_BLOCKED: &str = "You've already blocked that user.";
+pub const BLOCKED: &str = "You can't interact with that user.";
+pub const DUPLICATE_GUEST: &str = "You can only invite each user to a game once.";
+pub const INVITE_EXPIRED: &str = "That invite has expired.";
+pub const INVALID_JOIN_CODE: &str = "That join code doesn't exist or has expired.";
+pub const CHALLENGE_NOT_FOUND: &str = "That challenge isn't open anymore.";
+pub const CHALLENGE_ALREADY_OPEN: &str = "You already have a challenge open in the lobby.";
+pub const CLAIM_TOO_EARLY: &str =
+    "You can only claim the game once your opponent has stalled on their turn for a while.";
+pub const GAME_OVER: &str = "That game is already over.";
+pub const NOT_YOUR_PIECE: &str = "You can only play your own pieces.";
 pub const RESERVED_OPCODE: &str = "Reserved opcode: no action";
+pub const BANNED: &str =
+    "You've been temporarily banned from playing. Check your account page for details.";
+pub const CASUAL_ONLY: &str =
+    "You can only play casual games for now. Check your account page for details.";
+pub const AVATAR_TOO_LARGE: &str = "Avatars can be at most 1 MB.";
+pub const AVATAR_UNSUPPORTED: &str = "Avatars must be PNG, JPEG, GIF or WebP images.";
+pub const FOG_REPLAY: &str = "Fog games can only be replayed once they're over.";
+pub const ANALYSIS_NOT_READY: &str = "that game has not been analysed yet";
+pub const REPORT_SELF: &str = "You can't report yourself!";
+pub const REASON_MISSING: &str = "Please give a reason.";
+pub const REASON_TOO_LONG: &str = "Reasons can be at most 1000 characters.";
+pub const QUOTE_TOO_LONG: &str = "Quoted messages can be at most 500 characters.";
+pub const SEARCH_QUERY_MISSING: &str = "Enter a username to search for.";
+pub const SUSPENDED: &str = "Your account has been suspended";
+pub const ACCOUNT_LOCKED: &str =
+    "Too many failed login attempts. Your account is temporarily locked, so try again later.";
 
 // -- internal --
 pub const BAD_REQUEST: &str = "bad request";
@@ -20,7 +49,63 @@ pub const IDENTIFY_TIMEOUT: &str = "connection timed out";
 pub const INVALID_GAME_ID: &str = "no game exists with specified id";
 pub const INVALID_GAME_ID_FORMAT: &str = "invalid game id format (expected uuid)";
 pub const INVALID_PASSWORD_FORMAT: &str = "password failed to hash correctly";
+pub const UNSUPPORTED_PROTOCOL_VERSION: &str = "unsupported protocol version";
 pub const INVALID_TOKEN: &str = "invalid user token";
+pub const RATE_LIMITED: &str = "too many requests, slow down";
 pub const SESSION_COOKIE_NAME: &str = "sid";
+pub const CSRF_COOKIE_NAME: &str = "csrf";
+pub const CSRF_HEADER_NAME: &str = "X-CSRF-Token";
+pub const CSRF_MISMATCH: &str = "missing or invalid csrf token";
+pub const IDEMPOTENCY_HEADER_NAME: &str = "Idempotency-Key";
+pub const INVALID_IDEMPOTENCY_KEY: &str = "idempotency key must be 1 to 255 characters";
+pub const IDEMPOTENCY_IN_PROGRESS: &str =
+    "a request with this idempotency key is still in progress";
+pub const IDEMPOTENCY_KEY_REUSED: &str =
+    "this idempotency key was already used for a different request";
+pub const PAYLOAD_TOO_LARGE: &str = "request body is too large";
 pub const FRIEND_REQUEST_NOT_FOUND: &str = "no friend request exists from that user";
+pub const OAUTH_UNKNOWN_PROVIDER: &str = "unknown identity provider";
+pub const OAUTH_UNAVAILABLE: &str = "identity provider is not configured";
+pub const OAUTH_INVALID_STATE: &str = "invalid or expired oauth state";
+pub const OAUTH_FAILED: &str = "failed to verify identity with provider";
+pub const OAUTH_IDENTITY_TAKEN: &str = "that identity is already linked to another account";
+pub const ADDRESS_FORBIDDEN: &str = "requests from this address are not allowed";
+pub const NOT_ADMIN: &str = "only admins can do that";
+pub const BLOCK_NOT_FOUND: &str = "authenticated user has not blocked that user";
+pub const AVATAR_MISSING: &str = "expected an avatar field in the multipart form";
+pub const AVATAR_NOT_FOUND: &str = "no avatar exists with that name";
+pub const REPORT_NOT_FOUND: &str = "no report exists with specified id";
+pub const BAN_NOT_FOUND: &str = "no ban exists with specified id";
+pub const INVALID_BAN_DURATION: &str = "ban duration must be a positive number of hours";
+pub const TOO_MANY_USERNAMES: &str = "too many usernames (at most 50 per request)";
+pub const DUPLICATE_USERNAME: &str = "username is listed more than once";
+pub const INVALID_PAGE_SIZE: &str = "limit must be between 1 and 100";
+pub const INVALID_CURSOR: &str = "invalid pagination cursor";
 pub const FRIEND_NOT_FOUND: &str = "authenticated user is not friends with that user";
+pub const NOTIFICATION_NOT_FOUND: &str = "no notification exists with specified id";
+pub const TOURNAMENT_NOT_FOUND: &str = "no tournament exists with specified id";
+pub const INVALID_TOURNAMENT_NAME: &str = "tournament names must be 1 to 100 characters";
+pub const INVALID_TOURNAMENT_SIZE: &str = "tournaments must be for 2 to 64 players";
+pub const INVALID_TOURNAMENT_ROUNDS: &str =
+    "only Swiss tournaments can set their rounds, from 1 to 20";
+pub const TOURNAMENT_STARTED: &str = "tournament has already started";
+pub const TOURNAMENT_FULL: &str = "tournament is full";
+pub const TOURNAMENT_TOO_SMALL: &str = "tournaments need at least 2 players to start";
+pub const ALREADY_ENTERED: &str = "authenticated user has already entered that tournament";
+pub const NOT_TOURNAMENT_HOST: &str = "only the host can start the tournament";
+pub const SEASON_NOT_FOUND: &str = "no season exists with that number";
+pub const PUZZLE_NOT_FOUND: &str = "there is no puzzle today";
+pub const PUZZLE_ALREADY_ANSWERED: &str = "authenticated user has already answered today's puzzle";
+pub const INVALID_PUZZLE_SOLUTIONS: &str =
+    "puzzle solutions must be legal moves in the puzzle's position";
+pub const BOT_NOT_FOUND: &str = "authenticated user does not own a bot with specified id";
+pub const TOO_MANY_BOTS: &str = "users can own at most 5 bots";
+pub const BOT_OWNER: &str = "bots can't own other bots";
+pub const ARENA_NOT_FOUND: &str = "no arena exists with specified id";
+pub const INVALID_ARENA_GAMES: &str = "arenas must be 1 to 20 games";
+pub const ARENA_SAME_CONTENDER: &str = "arenas need two different contenders";
+pub const NOT_A_BOT: &str = "only registered bots can play in arenas";
+pub const WEBHOOK_NOT_FOUND: &str = "authenticated user does not have a webhook with specified id";
+pub const TOO_MANY_WEBHOOKS: &str = "users can have at most 5 webhooks";
+pub const INVALID_WEBHOOK_URL: &str = "webhook URLs must be absolute http or https URLs";
+pub const WEBHOOK_EVENTS_MISSING: &str = "webhooks must subscribe to at least one event";
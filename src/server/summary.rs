@@ -0,0 +1,231 @@
+use crate::{
+    server::{
+        conduct,
+        entities::{game, prelude::Game as GameModel},
+        handlers::StringError,
+        helpers,
+        notifications::{self, Kind},
+        packet::{Event, EventKind, ServerMessage},
+        review, season,
+        state::AppState,
+        strings, tournament, webhooks,
+    },
+    Game, Piece,
+};
+use axum::http::StatusCode;
+use chrono::Utc;
+pub use othello_api_types::{Links, Outcome, Score, Summary, Termination};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, DbErr, EntityTrait, IntoActiveModel, IsolationLevel,
+    TransactionTrait,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+/// A result decided off the board.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The specified side forfeited the game.
+    Forfeit(Piece, Termination),
+    /// The game was declared drawn.
+    Draw(Termination),
+}
+
+/// Finish the specified game: decide the result, persist the summary, and broadcast it to
+/// anyone watching. `verdict` is how the game was decided off the board, if it was, and
+/// `stalled` is the player (and the side they played) who stalled it out, if anyone did.
+/// Results on the board are only ever decided here, from the server's own position: clients
+/// have no say in them.
+/// # Errors
+/// Fails with `GAME_OVER` if the game had already ended by the time its result was written.
+pub async fn conclude(
+    state: &AppState,
+    metadata: &game::Model,
+    game: &Game,
+    verdict: Option<Verdict>,
+    stalled: Option<(Uuid, Piece)>,
+) -> Result<Summary, StringError> {
+    let (black, white) = game.score();
+    let (result, termination) = match verdict {
+        Some(Verdict::Forfeit(Piece::Black, termination)) => (Outcome::White, termination),
+        Some(Verdict::Forfeit(Piece::White, termination)) => (Outcome::Black, termination),
+        Some(Verdict::Draw(termination)) => (Outcome::Draw, termination),
+        None if black > white => (Outcome::Black, Termination::Normal),
+        None if white > black => (Outcome::White, Termination::Normal),
+        None => (Outcome::Draw, Termination::Normal),
+    };
+    let (black_player, white_player) = helpers::black_white(metadata);
+    let winner = match result {
+        Outcome::Black => black_player,
+        Outcome::White => white_player,
+        Outcome::Draw => None,
+    };
+    let winner = match winner {
+        Some(winner) => Some(
+            helpers::get_user(state, &winner.to_string(), false)
+                .await?
+                .username,
+        ),
+        None => None,
+    };
+    let mut summary = Summary {
+        result,
+        winner,
+        termination,
+        score: Score { black, white },
+        points: match result {
+            Outcome::Black | Outcome::Draw => black,
+            Outcome::White => white,
+        },
+        total: black + white,
+        rating_deltas: None,
+        links: Links {
+            game: format!("/game/{}", metadata.id),
+            export: format!("/game/{}/export", metadata.id),
+        },
+    };
+    // Rated games count towards the season being played, which may have to be started first.
+    let season = if helpers::game_settings(metadata).rated {
+        Some(
+            season::current(state)
+                .await
+                .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+                .id,
+        )
+    } else {
+        None
+    };
+    let ended =
+        helpers::retry_conflicts(|| record(state, metadata, game, &summary, stalled, season))
+            .await
+            .map_err(|e| StringError(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let Some(rating_deltas) = ended else {
+        return Err(StringError(
+            strings::GAME_OVER.into(),
+            StatusCode::BAD_REQUEST,
+        ));
+    };
+    summary.rating_deltas = rating_deltas;
+    review::queue(state, metadata.id, *game.settings(), game.history());
+    state.broadcast(
+        metadata.id,
+        Event::new(
+            EventKind::GameEnd,
+            ServerMessage::GameEnd(Box::new(summary.clone())),
+        ),
+    );
+    for player in [&metadata.host, &metadata.guest] {
+        let Ok(player) = Uuid::parse_str(player) else {
+            continue;
+        };
+        let ended = json!({
+            "game": metadata.id,
+            "result": summary.result,
+            "winner": summary.winner,
+            "termination": summary.termination,
+        });
+        notifications::send(state, player, Kind::GameEnd, ended.clone()).await;
+        webhooks::send(state, player, webhooks::Event::GameEnd, ended).await;
+    }
+    tournament::advance(state, metadata, &summary).await;
+    Ok(summary)
+}
+
+/// Write the end of a game in one transaction: its result and final position, the players'
+/// new ratings if it was played for `season`, and whatever stalling it out costs the player
+/// who did. Returns the players' rating changes, or `None`, writing nothing, if the game had
+/// already ended (e.g. because one player resigned just as the other's grace period ran out).
+async fn record(
+    state: &AppState,
+    metadata: &game::Model,
+    game: &Game,
+    summary: &Summary,
+    stalled: Option<(Uuid, Piece)>,
+    season: Option<Uuid>,
+) -> Result<Option<Option<(i32, i32)>>, DbErr> {
+    let txn = state
+        .database
+        .begin_with_config(Some(IsolationLevel::Serializable), None)
+        .await?;
+    let Some(current) = GameModel::find_by_id(metadata.id).one(&txn).await? else {
+        return Ok(None);
+    };
+    if current.ended {
+        return Ok(None);
+    }
+    let rating_deltas = match (season, helpers::black_white(metadata)) {
+        (Some(season), (Some(black), Some(white))) => {
+            Some(season::rate(&txn, season, black, white, summary.result).await?)
+        }
+        _ => None,
+    };
+    let summary = Summary {
+        rating_deltas,
+        ..summary.clone()
+    };
+    let mut model = current.into_active_model();
+    model.ended = ActiveValue::set(true);
+    model.ended_at = ActiveValue::set(Some(Utc::now().fixed_offset()));
+    model.result = ActiveValue::set(Some(serde_json::to_value(&summary).unwrap()));
+    // The final position is kept with the result, so the two always agree.
+    model.state = ActiveValue::set(Some(serde_json::to_value(game).unwrap()));
+    model.update(&txn).await?;
+    if let Some((member, piece)) = stalled {
+        conduct::record_stall(state, &txn, member, metadata.id, game, piece).await?;
+    }
+    txn.commit().await?;
+    Ok(Some(rating_deltas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Termination, Verdict};
+    use crate::{
+        server::{self, helpers, strings},
+        Game, Piece,
+    };
+    use serde_json::json;
+    use std::sync::Arc;
+    use test_utils::{function, Client, Map};
+
+    #[tokio::test]
+    async fn conclude_once() {
+        let database = sea_orm::Database::connect(server::Config::test().database_url)
+            .await
+            .unwrap();
+        let redis = redis::Client::open(server::Config::test().redis_url).unwrap();
+        let state = Arc::new(server::AppState::new(database, redis));
+        let url = test_utils::init(crate::server::app(Arc::clone(&state))).await;
+        let host = function!();
+        let guest = format!("{host}::guest");
+        let client = Client::authenticated(&[&host, &guest], &url, true).await;
+        let resp: Map = client.post(&url, "/game", json!({ "guest": guest })).await;
+        let id = resp["message"]["id"].as_str().unwrap().to_string();
+        let other = Client::authenticated(&[&guest], &url, false).await;
+        other
+            .post::<_, Map>(&url, &format!("/@me/games/{id}/accept"), json!({}))
+            .await;
+        let metadata = helpers::get_game(&state, &id).await.unwrap();
+        let game = Game::new();
+        // Two ways of ending the game race each other, and only one of them gets to.
+        let resign = Some(Verdict::Forfeit(Piece::Black, Termination::Resignation));
+        let abandon = Some(Verdict::Forfeit(Piece::White, Termination::Abandonment));
+        let white = metadata
+            .guest
+            .parse()
+            .ok()
+            .map(|guest| (guest, Piece::White));
+        let (first, second) = tokio::join!(
+            super::conclude(&state, &metadata, &game, resign, None),
+            super::conclude(&state, &metadata, &game, abandon, white),
+        );
+        let (won, lost) = match (first, second) {
+            (Ok(won), Err(lost)) | (Err(lost), Ok(won)) => (won, lost),
+            results => panic!("expected exactly one ending to win, got {results:?}"),
+        };
+        assert_eq!(lost.0, strings::GAME_OVER);
+        let stored = helpers::get_game(&state, &id).await.unwrap();
+        assert!(stored.ended);
+        assert_eq!(stored.result, Some(serde_json::to_value(&won).unwrap()));
+    }
+}
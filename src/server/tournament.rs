@@ -0,0 +1,671 @@
+use crate::{
+    server::{
+        correspondence, create_in_memory_game,
+        entities::{
+            game,
+            prelude::{Tournament, TournamentEntrant, TournamentRound},
+            tournament, tournament_entrant, tournament_round,
+        },
+        helpers,
+        notifications::{self, Kind},
+        state::AppState,
+        summary::{Outcome, Summary},
+        webhooks,
+    },
+    GameSettings, Piece,
+};
+use rand::seq::SliceRandom;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseTransaction, DbErr, EntityTrait,
+    IntoActiveModel, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{cmp::Reverse, collections::HashMap, str::FromStr};
+use uuid::Uuid;
+
+/// The fewest players a tournament can be held for.
+pub const MIN_SIZE: i32 = 2;
+/// The most players a tournament can be held for.
+pub const MAX_SIZE: i32 = 64;
+/// The most rounds a Swiss tournament can be played over.
+pub const MAX_ROUNDS: i32 = 20;
+
+/// How far along a tournament is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Players can still join.
+    Open,
+    /// The first round has been paired and games are being played.
+    Running,
+    /// Someone won.
+    Finished,
+}
+
+impl Status {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Running => "running",
+            Self::Finished => "finished",
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Self::Open),
+            "running" => Ok(Self::Running),
+            "finished" => Ok(Self::Finished),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How a tournament's players are paired and its winner decided.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// Players are knocked out by a single loss until one is left.
+    #[default]
+    SingleElimination,
+    /// Everyone plays every round against someone with a similar score, and the winner is
+    /// whoever has the most points after the last one.
+    Swiss,
+}
+
+impl Format {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SingleElimination => "single_elimination",
+            Self::Swiss => "swiss",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "single_elimination" => Ok(Self::SingleElimination),
+            "swiss" => Ok(Self::Swiss),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How a pairing was decided, from the point of view of its colours.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Black,
+    White,
+    Draw,
+    /// The pairing had no second player, so the first won without playing.
+    Bye,
+}
+
+impl Decision {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Black => "black",
+            Self::White => "white",
+            Self::Draw => "draw",
+            Self::Bye => "bye",
+        }
+    }
+}
+
+impl FromStr for Decision {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "black" => Ok(Self::Black),
+            "white" => Ok(Self::White),
+            "draw" => Ok(Self::Draw),
+            "bye" => Ok(Self::Bye),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A pairing of two players, the first playing black. A pairing without a second player is a
+/// bye, which the first player wins without playing.
+pub type Pairing = (Uuid, Option<Uuid>);
+
+/// Pair the entrants (in seed order) for the first round of a bracket. Brackets are padded out
+/// to a power of two with byes, which go to the top seeds so that no two byes meet.
+#[must_use]
+pub fn first_round(entrants: &[Uuid]) -> Vec<Pairing> {
+    let bracket = entrants.len().next_power_of_two();
+    (0..bracket / 2)
+        .map(|i| (entrants[i], entrants.get(bracket - 1 - i).copied()))
+        .collect()
+}
+
+/// Pair the winners of a round (in slot order) for the next one, each meeting the winner of
+/// the neighbouring slot.
+#[must_use]
+pub fn next_round(winners: &[Uuid]) -> Vec<Pairing> {
+    winners
+        .chunks(2)
+        .map(|pair| (pair[0], pair.get(1).copied()))
+        .collect()
+}
+
+/// How many rounds a Swiss tournament is played over if its host doesn't say: enough for one
+/// player to be the only one left who has won every game.
+#[must_use]
+pub fn default_rounds(entrants: usize) -> i32 {
+    i32::try_from(entrants.next_power_of_two().trailing_zeros())
+        .unwrap_or(MAX_ROUNDS)
+        .max(1)
+}
+
+/// Where a player stands in a Swiss tournament.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standing {
+    pub player: Uuid,
+    /// Points (a win or a bye is worth one, and a draw half), counted in halves.
+    pub half_points: u32,
+    /// The points of everyone the player has faced, in halves. This is the first tie-break.
+    pub buchholz: u32,
+    /// The discs the player finished their games with. This is the second tie-break.
+    pub discs: u32,
+}
+
+/// Rank the entrants of a Swiss tournament by their results so far: by points, then Buchholz,
+/// then disc count. Players who are still tied keep the order they were given in.
+#[must_use]
+pub fn standings(entrants: &[Uuid], pairings: &[tournament_round::Model]) -> Vec<Standing> {
+    let mut points: HashMap<Uuid, u32> = HashMap::new();
+    let mut discs: HashMap<Uuid, u32> = HashMap::new();
+    let mut opponents: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for pairing in pairings {
+        let Some(decision) = pairing
+            .result
+            .as_deref()
+            .and_then(|result| Decision::from_str(result).ok())
+        else {
+            continue;
+        };
+        let (black, white) = match decision {
+            Decision::Black | Decision::Bye => (2, 0),
+            Decision::White => (0, 2),
+            Decision::Draw => (1, 1),
+        };
+        *points.entry(pairing.black).or_default() += black;
+        *discs.entry(pairing.black).or_default() +=
+            u32::try_from(pairing.black_discs.unwrap_or_default()).unwrap_or_default();
+        if let Some(other) = pairing.white {
+            *points.entry(other).or_default() += white;
+            *discs.entry(other).or_default() +=
+                u32::try_from(pairing.white_discs.unwrap_or_default()).unwrap_or_default();
+            opponents.entry(pairing.black).or_default().push(other);
+            opponents.entry(other).or_default().push(pairing.black);
+        }
+    }
+    let points_of = |player: &Uuid| points.get(player).copied().unwrap_or_default();
+    let mut standings: Vec<_> = entrants
+        .iter()
+        .map(|&player| Standing {
+            player,
+            half_points: points_of(&player),
+            buchholz: opponents
+                .get(&player)
+                .map(|opponents| opponents.iter().map(points_of).sum())
+                .unwrap_or_default(),
+            discs: discs.get(&player).copied().unwrap_or_default(),
+        })
+        .collect();
+    standings.sort_by_key(|s| Reverse((s.half_points, s.buchholz, s.discs)));
+    standings
+}
+
+/// Pair the next round of a Swiss tournament. Going down the standings, each player meets the
+/// next one they haven't played yet (or just the next one, if they've played everyone left).
+/// With an odd number of players, the lowest-ranked player who hasn't had a bye yet sits the
+/// round out. Whoever has played black less often plays black.
+#[must_use]
+pub fn swiss_round(standings: &[Standing], pairings: &[tournament_round::Model]) -> Vec<Pairing> {
+    let met = |a: Uuid, b: Uuid| {
+        pairings
+            .iter()
+            .any(|p| (p.black == a && p.white == Some(b)) || (p.black == b && p.white == Some(a)))
+    };
+    let blacks = |player: Uuid| {
+        pairings
+            .iter()
+            .filter(|p| p.black == player && p.white.is_some())
+            .count()
+    };
+    let mut pool: Vec<_> = standings.iter().map(|s| s.player).collect();
+    let bye = (pool.len() % 2 == 1).then(|| {
+        let had_bye = |player: Uuid| {
+            pairings
+                .iter()
+                .any(|p| p.black == player && p.white.is_none())
+        };
+        let index = pool
+            .iter()
+            .rposition(|&player| !had_bye(player))
+            .unwrap_or(pool.len() - 1);
+        pool.remove(index)
+    });
+    let mut round = vec![];
+    while !pool.is_empty() {
+        let player = pool.remove(0);
+        let index = pool
+            .iter()
+            .position(|&other| !met(player, other))
+            .unwrap_or(0);
+        let opponent = pool.remove(index);
+        if blacks(player) <= blacks(opponent) {
+            round.push((player, Some(opponent)));
+        } else {
+            round.push((opponent, Some(player)));
+        }
+    }
+    round.extend(bye.map(|player| (player, None)));
+    round
+}
+
+/// Whether the specified game was paired in a tournament.
+/// # Errors
+/// Returns an error if the pairings can't be searched.
+pub async fn includes<C: sea_orm::ConnectionTrait>(db: &C, game: Uuid) -> Result<bool, DbErr> {
+    Ok(TournamentRound::find()
+        .filter(tournament_round::Column::Game.eq(game))
+        .one(db)
+        .await?
+        .is_some())
+}
+
+/// Fetch a tournament's entrants, in the order they joined.
+/// # Errors
+/// Returns an error if the entrants can't be fetched.
+pub async fn entrants<C: sea_orm::ConnectionTrait>(
+    db: &C,
+    tournament: Uuid,
+) -> Result<Vec<Uuid>, DbErr> {
+    Ok(TournamentEntrant::find()
+        .filter(tournament_entrant::Column::Tournament.eq(tournament))
+        .order_by_asc(tournament_entrant::Column::CreatedAt)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|entrant| entrant.member)
+        .collect())
+}
+
+/// Fetch every pairing made in a tournament so far, round by round.
+/// # Errors
+/// Returns an error if the pairings can't be fetched.
+pub async fn pairings<C: sea_orm::ConnectionTrait>(
+    db: &C,
+    tournament: Uuid,
+) -> Result<Vec<tournament_round::Model>, DbErr> {
+    TournamentRound::find()
+        .filter(tournament_round::Column::Tournament.eq(tournament))
+        .order_by_asc(tournament_round::Column::Round)
+        .order_by_asc(tournament_round::Column::Slot)
+        .all(db)
+        .await
+}
+
+/// Pair the first round of a tournament from its entrants, seeded at random, and schedule its
+/// games. The tournament must be locked by `txn`.
+/// # Errors
+/// Returns an error if the pairings can't be saved.
+pub async fn start(
+    txn: &DatabaseTransaction,
+    tournament: tournament::Model,
+) -> Result<Vec<game::Model>, DbErr> {
+    let mut entrants = entrants(txn, tournament.id).await?;
+    entrants.shuffle(&mut rand::thread_rng());
+    let format = Format::from_str(&tournament.format).unwrap_or_default();
+    let pairings = match format {
+        Format::SingleElimination => first_round(&entrants),
+        Format::Swiss => swiss_round(&standings(&entrants, &[]), &[]),
+    };
+    let games = pair(txn, &tournament, 1, pairings).await?;
+    let rounds = match format {
+        Format::SingleElimination => tournament.rounds,
+        Format::Swiss => tournament
+            .rounds
+            .or_else(|| Some(default_rounds(entrants.len()))),
+    };
+    let mut active = tournament.into_active_model();
+    active.status = ActiveValue::set(Status::Running.name().into());
+    active.rounds = ActiveValue::set(rounds);
+    active.update(txn).await?;
+    Ok(games)
+}
+
+/// Save the pairings for a round, scheduling a game for each one that isn't a bye.
+async fn pair(
+    txn: &DatabaseTransaction,
+    tournament: &tournament::Model,
+    round: i32,
+    pairings: Vec<Pairing>,
+) -> Result<Vec<game::Model>, DbErr> {
+    let mut games = vec![];
+    for (slot, (black, white)) in (0..).zip(pairings) {
+        let game = match white {
+            Some(white) => Some(schedule(txn, tournament, black, white).await?),
+            None => None,
+        };
+        let bye = game.is_none();
+        tournament_round::ActiveModel {
+            id: ActiveValue::set(Uuid::now_v7()),
+            tournament: ActiveValue::set(tournament.id),
+            round: ActiveValue::set(round),
+            slot: ActiveValue::set(slot),
+            black: ActiveValue::set(black),
+            white: ActiveValue::set(white),
+            game: ActiveValue::set(game.as_ref().map(|game| game.id)),
+            winner: ActiveValue::set(bye.then_some(black)),
+            result: ActiveValue::set(bye.then(|| Decision::Bye.name().into())),
+            black_discs: ActiveValue::set(None),
+            white_discs: ActiveValue::set(None),
+        }
+        .insert(txn)
+        .await?;
+        games.extend(game);
+    }
+    Ok(games)
+}
+
+/// Create a game between two players with the tournament's settings. Tournament games don't
+/// need to be accepted, so they start out ready to play.
+async fn schedule(
+    txn: &DatabaseTransaction,
+    tournament: &tournament::Model,
+    black: Uuid,
+    white: Uuid,
+) -> Result<game::Model, DbErr> {
+    let settings: GameSettings =
+        serde_json::from_value(tournament.settings.clone()).unwrap_or_default();
+    // Black hosts the game.
+    game::ActiveModel {
+        id: ActiveValue::set(Uuid::now_v7()),
+        host: ActiveValue::set(black.to_string()),
+        guest: ActiveValue::set(white.to_string()),
+        pending: ActiveValue::set(false),
+        ended: ActiveValue::set(false),
+        challenge: ActiveValue::set(None),
+        result: ActiveValue::set(None),
+        settings: ActiveValue::set(tournament.settings.clone()),
+        state: ActiveValue::set(None),
+        turn_started_at: ActiveValue::set(None),
+        opponent: ActiveValue::set(None),
+        arena: ActiveValue::set(None),
+        host_opponent: ActiveValue::set(None),
+        ended_at: ActiveValue::set(None),
+        mode: ActiveValue::set(correspondence::mode(&settings).into()),
+        host_color: ActiveValue::set(helpers::color_name(Piece::Black).into()),
+    }
+    .insert(txn)
+    .await
+}
+
+/// Get newly scheduled tournament games ready to play, and let their players know about them.
+pub async fn announce(state: &AppState, tournament: Uuid, games: &[game::Model]) {
+    for game in games {
+        // The failure is logged, and the players still hear about the game.
+        let _ = create_in_memory_game(state, game).await;
+        webhooks::game_started(state, game).await;
+        for player in [&game.host, &game.guest] {
+            let Ok(player) = Uuid::parse_str(player) else {
+                continue;
+            };
+            notifications::send(
+                state,
+                player,
+                Kind::TournamentGame,
+                json!({ "tournament": tournament, "game": game.id }),
+            )
+            .await;
+        }
+    }
+}
+
+/// Move a tournament along now that one of its games has ended, pairing the next round once
+/// every pairing in this one is decided. In single elimination the winner goes through and
+/// drawn games are replayed with the colours swapped; in Swiss every result stands. Games that
+/// aren't part of a tournament are ignored, and failures are logged, since the game itself has
+/// already ended.
+pub async fn advance(state: &AppState, game: &game::Model, summary: &Summary) {
+    match record(state, game, summary).await {
+        Ok(Some((tournament, games))) => announce(state, tournament, &games).await,
+        Ok(None) => {}
+        Err(e) => tracing::error!(game = %game.id, "Failed to advance tournament: {e}"),
+    }
+}
+
+async fn record(
+    state: &AppState,
+    game: &game::Model,
+    summary: &Summary,
+) -> Result<Option<(Uuid, Vec<game::Model>)>, DbErr> {
+    let Some(pairing) = TournamentRound::find()
+        .filter(tournament_round::Column::Game.eq(game.id))
+        .one(state.database.as_ref())
+        .await?
+    else {
+        return Ok(None);
+    };
+    let txn = state.database.begin().await?;
+    // Lock the tournament so that the last two games of a round can't both pair the next one.
+    let Some(tournament) = Tournament::find_by_id(pairing.tournament)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let Some(pairing) = TournamentRound::find_by_id(pairing.id).one(&txn).await? else {
+        return Ok(None);
+    };
+    if pairing.result.is_some() || pairing.game != Some(game.id) {
+        return Ok(None);
+    }
+    let (Some(black), Some(white)) = helpers::black_white(game) else {
+        return Ok(None);
+    };
+    let format = Format::from_str(&tournament.format).unwrap_or_default();
+    let winner = match summary.result {
+        Outcome::Black => Some(black),
+        Outcome::White => Some(white),
+        Outcome::Draw if format == Format::SingleElimination => {
+            let rematch = schedule(&txn, &tournament, white, black).await?;
+            let mut active = pairing.into_active_model();
+            active.game = ActiveValue::set(Some(rematch.id));
+            active.update(&txn).await?;
+            txn.commit().await?;
+            return Ok(Some((tournament.id, vec![rematch])));
+        }
+        Outcome::Draw => None,
+    };
+    // A replayed game may have been played with the pairing's colours swapped.
+    let (black_discs, white_discs) = if black == pairing.black {
+        (summary.score.black, summary.score.white)
+    } else {
+        (summary.score.white, summary.score.black)
+    };
+    let decision = match winner {
+        Some(winner) if winner == pairing.black => Decision::Black,
+        Some(_) => Decision::White,
+        None => Decision::Draw,
+    };
+    let round = pairing.round;
+    let mut active = pairing.into_active_model();
+    active.winner = ActiveValue::set(winner);
+    active.result = ActiveValue::set(Some(decision.name().into()));
+    active.black_discs = ActiveValue::set(i32::try_from(black_discs).ok());
+    active.white_discs = ActiveValue::set(i32::try_from(white_discs).ok());
+    active.update(&txn).await?;
+    let history = pairings(&txn, tournament.id).await?;
+    let current: Vec<_> = history.iter().filter(|p| p.round == round).collect();
+    if current.iter().any(|pairing| pairing.result.is_none()) {
+        // Other games in the round are still being played.
+        txn.commit().await?;
+        return Ok(Some((tournament.id, vec![])));
+    }
+    let id = tournament.id;
+    let champion = match format {
+        Format::SingleElimination => match current[..] {
+            [last] => last.winner,
+            _ => None,
+        },
+        Format::Swiss if round >= tournament.rounds.unwrap_or(round) => {
+            let entrants = entrants(&txn, id).await?;
+            standings(&entrants, &history)
+                .first()
+                .map(|standing| standing.player)
+        }
+        Format::Swiss => None,
+    };
+    let games = if let Some(champion) = champion {
+        let mut active = tournament.into_active_model();
+        active.status = ActiveValue::set(Status::Finished.name().into());
+        active.winner = ActiveValue::set(Some(champion));
+        active.update(&txn).await?;
+        vec![]
+    } else if format == Format::Swiss {
+        let entrants = entrants(&txn, id).await?;
+        let next = swiss_round(&standings(&entrants, &history), &history);
+        pair(&txn, &tournament, round + 1, next).await?
+    } else {
+        let winners: Vec<_> = current
+            .iter()
+            .filter_map(|pairing| pairing.winner)
+            .collect();
+        pair(&txn, &tournament, round + 1, next_round(&winners)).await?
+    };
+    txn.commit().await?;
+    Ok(Some((id, games)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decision;
+    use crate::server::entities::tournament_round;
+    use uuid::Uuid;
+
+    fn played(
+        black: Uuid,
+        white: Option<Uuid>,
+        decision: Decision,
+        discs: (i32, i32),
+    ) -> tournament_round::Model {
+        tournament_round::Model {
+            id: Uuid::now_v7(),
+            tournament: Uuid::nil(),
+            round: 1,
+            slot: 0,
+            black,
+            white,
+            game: None,
+            winner: None,
+            result: Some(decision.name().into()),
+            black_discs: Some(discs.0),
+            white_discs: Some(discs.1),
+        }
+    }
+
+    #[test]
+    fn first_round() {
+        let players: Vec<_> = (0..5).map(|_| Uuid::now_v7()).collect();
+        let pairings = super::first_round(&players);
+        // Five players fill a bracket of eight, so the top three seeds get byes.
+        assert_eq!(
+            pairings,
+            [
+                (players[0], None),
+                (players[1], None),
+                (players[2], None),
+                (players[3], Some(players[4])),
+            ]
+        );
+        let pairings = super::first_round(&players[..4]);
+        assert_eq!(
+            pairings,
+            [
+                (players[0], Some(players[3])),
+                (players[1], Some(players[2]))
+            ]
+        );
+    }
+
+    #[test]
+    fn next_round() {
+        let players: Vec<_> = (0..4).map(|_| Uuid::now_v7()).collect();
+        assert_eq!(
+            super::next_round(&players),
+            [
+                (players[0], Some(players[1])),
+                (players[2], Some(players[3]))
+            ]
+        );
+    }
+
+    #[test]
+    fn standings() {
+        let [ann, bob, cat, dan, eve] = [(); 5].map(|()| Uuid::now_v7());
+        let history = [
+            played(ann, Some(bob), Decision::Black, (40, 24)),
+            played(cat, Some(dan), Decision::Draw, (32, 32)),
+        ];
+        let standings = super::standings(&[ann, bob, cat, dan], &history);
+        let order: Vec<_> = standings.iter().map(|s| s.player).collect();
+        assert_eq!(order, [ann, cat, dan, bob]);
+        assert_eq!(standings[0].half_points, 2);
+        // cat and dan are tied on points, Buchholz and discs, so they keep their order.
+        assert_eq!(standings[1].buchholz, 1);
+        assert_eq!(standings[3].discs, 24);
+        // Buchholz breaks a tie on points (and discs): cat has played the leader, while eve had
+        // a bye.
+        let history = [
+            played(ann, Some(bob), Decision::Black, (40, 24)),
+            played(cat, Some(dan), Decision::Black, (40, 24)),
+            played(eve, None, Decision::Bye, (64, 0)),
+            played(ann, Some(cat), Decision::Black, (40, 24)),
+        ];
+        let standings = super::standings(&[eve, ann, bob, cat, dan], &history);
+        assert_eq!(standings[1].player, cat);
+        assert_eq!(standings[2].player, eve);
+        assert_eq!(standings[1].half_points, standings[2].half_points);
+        assert_eq!(standings[1].discs, standings[2].discs);
+    }
+
+    #[test]
+    fn swiss_round() {
+        let [ann, bob, cat, dan, eve] = [(); 5].map(|()| Uuid::now_v7());
+        let history = [
+            played(ann, Some(bob), Decision::Black, (40, 24)),
+            played(cat, Some(dan), Decision::Black, (40, 24)),
+            played(eve, None, Decision::Bye, (0, 0)),
+        ];
+        let standings = super::standings(&[ann, bob, cat, dan, eve], &history);
+        let round = super::swiss_round(&standings, &history);
+        // eve already had a bye, so the lowest-ranked player sits out instead. The leaders
+        // meet, each having played black once already.
+        assert_eq!(round, [(ann, Some(cat)), (eve, Some(bob)), (dan, None)]);
+    }
+
+    #[test]
+    fn default_rounds() {
+        assert_eq!(super::default_rounds(2), 1);
+        assert_eq!(super::default_rounds(5), 3);
+        assert_eq!(super::default_rounds(8), 3);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::server::handlers::{ApiError, ErrorCode, StringError};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Something wrong with a single field of a request body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    /// The path to the field (e.g. `settings.board_size`).
+    pub field: String,
+    pub code: ErrorCode,
+    pub message: String,
+    /// Anything else about the error, as in `ApiError`.
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub details: Value,
+}
+
+/// Collects everything wrong with a request body, so that clients can be told about all of
+/// it at once instead of one field per attempt.
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+/// A request body that can check its own fields. Bodies implementing this are extracted with
+/// `Valid`, which rejects the request if any of the checks fail.
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+/// A JSON request body whose fields have all been checked.
+pub struct Valid<T>(pub T);
+
+impl Validator {
+    /// Record the error the specified field failed a check with, if it did.
+    pub fn check<E: Into<ApiError>>(&mut self, field: &str, result: Result<(), E>) -> &mut Self {
+        match result {
+            Ok(()) => self,
+            Err(e) => self.reject(field, e),
+        }
+    }
+
+    /// Record an error against the specified field.
+    pub fn reject(&mut self, field: &str, e: impl Into<ApiError>) -> &mut Self {
+        let e = e.into();
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            code: e.code,
+            message: e.message,
+            details: e.details,
+        });
+        self
+    }
+
+    /// Record the specified message against the field unless the condition holds.
+    pub fn ensure(&mut self, field: &str, condition: bool, message: &str) -> &mut Self {
+        self.check(
+            field,
+            if condition {
+                Ok(())
+            } else {
+                Err(StringError(message.into(), StatusCode::BAD_REQUEST))
+            },
+        )
+    }
+
+    /// Finish checking the body.
+    /// # Errors
+    /// Returns an error listing every field that failed under `details.fields`. The error
+    /// itself takes the code and message of the first one, so clients that only look at those
+    /// still see a sensible reason.
+    pub fn finish(self) -> Result<(), ApiError> {
+        let Some(first) = self.errors.first() else {
+            return Ok(());
+        };
+        Err(
+            ApiError::new(first.code, first.message.clone(), StatusCode::BAD_REQUEST)
+                .with_details(json!({ "fields": self.errors })),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldError, Validator};
+    use crate::server::{
+        handlers::{ErrorCode, StringError},
+        strings, validate_password, validate_username,
+    };
+    use axum::http::StatusCode;
+
+    #[test]
+    fn fields() {
+        let mut v = Validator::default();
+        v.check("username", validate_username("ab"))
+            .check("password", validate_password("password1"))
+            .ensure("reason", false, strings::REASON_MISSING);
+        let e = v.finish().unwrap_err();
+        assert_eq!(e.code, ErrorCode::UsernameTooShort);
+        assert_eq!(e.status(), StatusCode::BAD_REQUEST);
+        let fields: Vec<FieldError> = serde_json::from_value(e.details["fields"].clone()).unwrap();
+        assert_eq!(
+            fields
+                .iter()
+                .map(|f| (f.field.as_str(), f.code))
+                .collect::<Vec<_>>(),
+            [
+                ("username", ErrorCode::UsernameTooShort),
+                ("reason", ErrorCode::ReasonMissing)
+            ]
+        );
+        let mut v = Validator::default();
+        v.check("username", Ok::<_, StringError>(()));
+        assert!(v.finish().is_ok());
+    }
+}
@@ -0,0 +1,408 @@
+//! Webhooks that users point at their own services (e.g. Discord bots), which the server calls
+//! when something happens to them: a game of theirs starting or ending, or somebody
+//! challenging them. Each call is signed with the webhook's secret, so that receivers can tell
+//! it came from here. Deliveries are kept in the database and tried straight away; those that
+//! fail are retried by the `webhook_retries` job, waiting longer after each failure, until
+//! they've been tried `MAX_ATTEMPTS` times.
+
+use crate::server::{
+    entities::{
+        game,
+        prelude::{Webhook, WebhookDelivery},
+        webhook, webhook_delivery,
+    },
+    handlers::StringError,
+    helpers,
+    links::GameLinks,
+    network,
+    state::AppState,
+    timestamp,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    redirect, Url,
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::{
+    fmt::Write,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// The header holding the signature of a delivery's body: `sha256=`, then the HMAC-SHA256 of
+/// the body keyed with the webhook's secret, in hex.
+pub const SIGNATURE_HEADER: &str = "X-Olly-Signature";
+/// The header naming the event a delivery is for.
+pub const EVENT_HEADER: &str = "X-Olly-Event";
+/// The header holding a delivery's ID, which stays the same when it's retried.
+pub const DELIVERY_HEADER: &str = "X-Olly-Delivery";
+/// How many times a delivery is tried before it's given up on.
+pub const MAX_ATTEMPTS: i32 = 6;
+/// How long a webhook has to answer before the attempt counts as failed.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// How long after its first failed attempt a delivery is retried. Each retry after that waits
+/// four times as long as the one before.
+const FIRST_RETRY: Duration = Duration::from_secs(30);
+/// How many due deliveries each run of the retry job takes on.
+const RETRY_BATCH: u64 = 100;
+
+/// A delivery still to be made.
+const PENDING: &str = "pending";
+/// A delivery the webhook answered successfully.
+const DELIVERED: &str = "delivered";
+/// A delivery given up on after failing every attempt.
+const FAILED: &str = "failed";
+
+/// Something that can happen to a user, which their webhooks can subscribe to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// A game the user plays in started.
+    GameStart,
+    /// A game the user played in ended.
+    GameEnd,
+    /// Somebody invited the user to a game.
+    Challenge,
+}
+
+impl Event {
+    /// The name the event is sent and stored as.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::GameStart => "game_start",
+            Self::GameEnd => "game_end",
+            Self::Challenge => "challenge",
+        }
+    }
+}
+
+/// Sends deliveries to webhooks. Unless private addresses are allowed (e.g. for testing),
+/// deliveries only go to public ones, so that webhooks can't be used to reach the server's own
+/// network, and redirects aren't followed, since they could lead anywhere.
+#[derive(Clone)]
+pub struct Dispatcher {
+    client: reqwest::Client,
+    private: bool,
+}
+
+impl Dispatcher {
+    #[must_use]
+    pub fn new(private: bool) -> Self {
+        let builder = reqwest::Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(TIMEOUT);
+        // Names are checked as they're resolved for each delivery, so that one can't resolve
+        // to a public address when it's registered and a private one later.
+        let builder = if private {
+            builder
+        } else {
+            builder.dns_resolver(Arc::new(PublicResolver))
+        };
+        Self {
+            client: builder.build().expect("webhook client settings are valid"),
+            private,
+        }
+    }
+
+    /// Make sure that deliveries to the specified URL would go to a public address, unless
+    /// private ones are allowed.
+    pub(super) async fn ensure_reachable(&self, url: &Url) -> Result<(), String> {
+        if self.private {
+            return Ok(());
+        }
+        let host = url.host_str().unwrap_or_default();
+        // Addresses given outright never go through the resolver, so they're checked here.
+        match host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+        {
+            Ok(ip) if network::is_global(ip) => Ok(()),
+            Ok(_) => Err(format!("{host} isn't a public address")),
+            Err(_) => public_addrs(host, url.port_or_known_default().unwrap_or_default())
+                .await
+                .map(|_| ()),
+        }
+    }
+}
+
+/// Resolves the names of webhooks, refusing those that resolve to anything but public addresses.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = public_addrs(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The addresses the specified host resolves to, failing unless they're all public.
+async fn public_addrs(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<_> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| e.to_string())?
+        .collect();
+    if addrs.is_empty() || addrs.iter().any(|addr| !network::is_global(addr.ip())) {
+        return Err(format!("{host} doesn't resolve to a public address"));
+    }
+    Ok(addrs)
+}
+
+/// The events the specified webhook subscribes to.
+pub(super) fn events(webhook: &webhook::Model) -> Vec<Event> {
+    serde_json::from_value(webhook.events.clone()).unwrap_or_default()
+}
+
+/// Sign the specified body with the specified secret, as the value of `SIGNATURE_HEADER`.
+pub(super) fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .fold(String::from("sha256="), |mut signature, byte| {
+            let _ = write!(signature, "{byte:02x}");
+            signature
+        })
+}
+
+/// How long to wait before trying a delivery again, after the specified number of attempts.
+fn backoff(attempts: i32) -> Duration {
+    let retries = u32::try_from(attempts - 1).unwrap_or_default();
+    FIRST_RETRY.saturating_mul(4u32.saturating_pow(retries))
+}
+
+/// Call the webhooks the specified user has subscribed to the specified event with, with the
+/// specified payload. Webhooks are a courtesy, so failing to queue a delivery is logged rather
+/// than failing whatever prompted it.
+pub async fn send(state: &AppState, member: Uuid, event: Event, payload: Value) {
+    if let Err(StringError(message, _)) = queue(state, member, event, payload).await {
+        tracing::error!(%member, event = event.name(), "Failed to queue webhook: {message}");
+    }
+}
+
+/// Let both players of the specified game know it's started.
+pub async fn game_started(state: &AppState, game: &game::Model) {
+    let (black, white) = helpers::black_white(game);
+    let players = [black, white];
+    let members = helpers::get_users_by_ids(state, players.into_iter().flatten())
+        .await
+        .unwrap_or_default();
+    let username = |player: Option<Uuid>| Some(members.get(&player?)?.username.clone());
+    let payload = json!({
+        "game": game.id,
+        "black": username(black),
+        "white": username(white),
+        "settings": helpers::game_settings(game),
+        "url": GameLinks::new(game.id).web,
+    });
+    for player in players.into_iter().flatten() {
+        send(state, player, Event::GameStart, payload.clone()).await;
+    }
+}
+
+async fn queue(
+    state: &AppState,
+    member: Uuid,
+    event: Event,
+    payload: Value,
+) -> Result<(), StringError> {
+    let webhooks = Webhook::find()
+        .filter(webhook::Column::Member.eq(member))
+        .all(state.database.as_ref())
+        .await?;
+    for webhook in webhooks {
+        if !events(&webhook).contains(&event) {
+            continue;
+        }
+        let id = Uuid::now_v7();
+        let delivery = WebhookDelivery::insert(webhook_delivery::ActiveModel {
+            id: ActiveValue::set(id),
+            webhook: ActiveValue::set(webhook.id),
+            event: ActiveValue::set(event.name().into()),
+            payload: ActiveValue::set(json!({
+                "id": id,
+                "event": event.name(),
+                "created_at": timestamp::rfc3339(&Utc::now().fixed_offset()),
+                "data": payload,
+            })),
+            status: ActiveValue::set(PENDING.into()),
+            attempts: ActiveValue::set(0),
+            // Keep the retry job off it while it's tried straight away.
+            next_attempt_at: ActiveValue::set(Some((Utc::now() + TIMEOUT * 2).fixed_offset())),
+            last_error: ActiveValue::set(None),
+            created_at: ActiveValue::NotSet,
+        })
+        .exec_with_returning(state.database.as_ref())
+        .await?;
+        tokio::spawn(attempt(
+            Arc::clone(&state.database),
+            state.webhooks.clone(),
+            webhook,
+            delivery,
+        ));
+    }
+    Ok(())
+}
+
+/// Try the specified delivery, noting how it went: delivered, due to be retried, or given up
+/// on.
+async fn attempt(
+    database: Arc<DatabaseConnection>,
+    dispatcher: Dispatcher,
+    webhook: webhook::Model,
+    delivery: webhook_delivery::Model,
+) {
+    let attempts = delivery.attempts + 1;
+    let (status, next_attempt_at, last_error) = match dispatcher.post(&webhook, &delivery).await {
+        Ok(()) => (DELIVERED, None, None),
+        Err(e) if attempts >= MAX_ATTEMPTS => (FAILED, None, Some(e)),
+        Err(e) => (
+            PENDING,
+            Some((Utc::now() + backoff(attempts)).fixed_offset()),
+            Some(e),
+        ),
+    };
+    if let Some(e) = &last_error {
+        tracing::warn!(delivery = %delivery.id, attempts, "Webhook delivery failed: {e}");
+    }
+    let update = webhook_delivery::ActiveModel {
+        id: ActiveValue::unchanged(delivery.id),
+        status: ActiveValue::set(status.into()),
+        attempts: ActiveValue::set(attempts),
+        next_attempt_at: ActiveValue::set(next_attempt_at),
+        last_error: ActiveValue::set(last_error),
+        ..Default::default()
+    }
+    .update(database.as_ref())
+    .await;
+    if let Err(e) = update {
+        tracing::error!(delivery = %delivery.id, "Failed to record webhook delivery: {e}");
+    }
+}
+
+impl Dispatcher {
+    /// Send the specified delivery to its webhook, succeeding only if it answers with a success
+    /// status.
+    async fn post(
+        &self,
+        webhook: &webhook::Model,
+        delivery: &webhook_delivery::Model,
+    ) -> Result<(), String> {
+        let url = Url::parse(&webhook.url).map_err(|e| e.to_string())?;
+        self.ensure_reachable(&url).await?;
+        let body = serde_json::to_vec(&delivery.payload).map_err(|e| e.to_string())?;
+        let resp = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign(&webhook.secret, &body))
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("webhook answered with {}", resp.status()))
+        }
+    }
+}
+
+/// Retry the deliveries that are due, returning how many were tried. Each is claimed first by
+/// pushing its next attempt back, so that no two instances try it at once.
+pub(super) async fn retry(state: &AppState) -> Result<u64, StringError> {
+    let now = Utc::now().fixed_offset();
+    let due = WebhookDelivery::find()
+        .filter(webhook_delivery::Column::Status.eq(PENDING))
+        .filter(webhook_delivery::Column::NextAttemptAt.lte(now))
+        .order_by_asc(webhook_delivery::Column::NextAttemptAt)
+        .limit(RETRY_BATCH)
+        .find_also_related(Webhook)
+        .all(state.database.as_ref())
+        .await?;
+    let mut attempts = Vec::new();
+    for (delivery, webhook) in due {
+        let Some(webhook) = webhook else {
+            continue;
+        };
+        let claimed = WebhookDelivery::update_many()
+            .col_expr(
+                webhook_delivery::Column::NextAttemptAt,
+                sea_orm::sea_query::Expr::value(now + TIMEOUT * 2),
+            )
+            .filter(webhook_delivery::Column::Id.eq(delivery.id))
+            .filter(webhook_delivery::Column::NextAttemptAt.eq(delivery.next_attempt_at))
+            .exec(state.database.as_ref())
+            .await?;
+        if claimed.rows_affected == 1 {
+            attempts.push(attempt(
+                Arc::clone(&state.database),
+                state.webhooks.clone(),
+                webhook,
+                delivery,
+            ));
+        }
+    }
+    let tried = attempts.len() as u64;
+    futures::future::join_all(attempts).await;
+    Ok(tried)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff, sign, Dispatcher, FIRST_RETRY};
+    use reqwest::Url;
+
+    #[test]
+    fn signatures() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_ne!(sign("other", b"{}"), sign("secret", b"{}"));
+    }
+
+    #[test]
+    fn backoff_grows() {
+        assert_eq!(backoff(1), FIRST_RETRY);
+        assert_eq!(backoff(2), FIRST_RETRY * 4);
+        assert_eq!(backoff(5), FIRST_RETRY * 256);
+    }
+
+    #[tokio::test]
+    async fn private_addresses() {
+        let dispatcher = Dispatcher::new(false);
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://10.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost/hook",
+        ] {
+            let url = Url::parse(url).unwrap();
+            assert!(dispatcher.ensure_reachable(&url).await.is_err(), "{url}");
+        }
+        // Unless they're allowed.
+        let url = Url::parse("http://127.0.0.1:8080/hook").unwrap();
+        assert!(Dispatcher::new(true).ensure_reachable(&url).await.is_ok());
+    }
+}
@@ -0,0 +1,7 @@
+//! The settings a game can be played with. Clients send them to create games, so they're
+//! defined in `othello-rules-types`, which the API's types share.
+
+pub use othello_rules_types::{
+    ColorPolicy, Correspondence, GameSettings, SettingsError, TimeControl, Variant,
+    MAX_DAYS_PER_MOVE, MAX_HANDICAP,
+};
@@ -0,0 +1,85 @@
+use crate::board::Board;
+
+/// One of the eight ways a square board can be rotated or reflected onto itself. None of them
+/// change who's winning or what can be played, so positions that are symmetries of each other
+/// are really the same position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    Identity,
+    /// A quarter turn clockwise.
+    Rotate90,
+    Rotate180,
+    /// A quarter turn anticlockwise.
+    Rotate270,
+    /// Left to right.
+    FlipHorizontal,
+    /// Top to bottom.
+    FlipVertical,
+    /// Across the diagonal from the top left to the bottom right.
+    Transpose,
+    /// Across the diagonal from the top right to the bottom left.
+    AntiTranspose,
+}
+
+impl Symmetry {
+    pub const ALL: [Self; 8] = [
+        Self::Identity,
+        Self::Rotate90,
+        Self::Rotate180,
+        Self::Rotate270,
+        Self::FlipHorizontal,
+        Self::FlipVertical,
+        Self::Transpose,
+        Self::AntiTranspose,
+    ];
+
+    /// The square the specified square of a standard board is taken to.
+    #[must_use]
+    pub fn apply(self, x: usize, y: usize) -> (usize, usize) {
+        self.apply_within(x, y, Board::width())
+    }
+
+    /// The square the specified square of a board of the specified width is taken to.
+    pub(crate) fn apply_within(self, x: usize, y: usize, width: usize) -> (usize, usize) {
+        let last = width - 1;
+        match self {
+            Self::Identity => (x, y),
+            Self::Rotate90 => (last - y, x),
+            Self::Rotate180 => (last - x, last - y),
+            Self::Rotate270 => (y, last - x),
+            Self::FlipHorizontal => (last - x, y),
+            Self::FlipVertical => (x, last - y),
+            Self::Transpose => (y, x),
+            Self::AntiTranspose => (last - y, last - x),
+        }
+    }
+
+    /// The symmetry that undoes this one.
+    #[must_use]
+    pub fn inverse(self) -> Self {
+        match self {
+            Self::Rotate90 => Self::Rotate270,
+            Self::Rotate270 => Self::Rotate90,
+            symmetry => symmetry,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symmetry;
+
+    #[test]
+    fn inverses() {
+        for symmetry in Symmetry::ALL {
+            for (x, y) in [(0, 0), (5, 4), (2, 7)] {
+                let (tx, ty) = symmetry.apply(x, y);
+                assert_eq!(symmetry.inverse().apply(tx, ty), (x, y));
+            }
+        }
+        // Four quarter turns go all the way round.
+        let turned = (0..4).fold((5, 4), |(x, y), _| Symmetry::Rotate90.apply(x, y));
+        assert_eq!(turned, (5, 4));
+        assert_eq!(Symmetry::Rotate90.apply(0, 0), (7, 0));
+    }
+}
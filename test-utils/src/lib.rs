@@ -1,10 +1,14 @@
 use axum::Router;
+use futures::{SinkExt, StreamExt};
+use reqwest::cookie::{CookieStore, Jar};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
     future::IntoFuture,
     net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
 };
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
 
 #[macro_export]
 /// This macro is used to get the name of the function that calls it.
@@ -30,17 +34,42 @@ macro_rules! function {
 
 pub type Map = serde_json::Map<String, serde_json::Value>;
 
+/// The cookie the server hands out a CSRF token in, and the header it expects the token
+/// back in on requests that change anything.
+const CSRF_COOKIE_NAME: &str = "csrf";
+const CSRF_HEADER_NAME: &str = "X-CSRF-Token";
+
 pub struct Client {
     inner: reqwest::Client,
+    jar: Arc<Jar>,
 }
 
 impl Client {
     pub fn new() -> Self {
+        let jar = Arc::new(Jar::default());
         Self {
             inner: reqwest::Client::builder()
-                .cookie_store(true)
+                .cookie_provider(Arc::clone(&jar))
                 .build()
                 .unwrap(),
+            jar,
+        }
+    }
+
+    /// Fetch the value of the named cookie stored for the specified URL.
+    pub fn cookie(&self, url: &str, name: &str) -> Option<String> {
+        let header = self.jar.cookies(&url.parse().unwrap())?;
+        header.to_str().unwrap().split("; ").find_map(|cookie| {
+            let (key, value) = cookie.split_once('=')?;
+            (key == name).then(|| percent_decode(value))
+        })
+    }
+
+    /// Repeat the CSRF token from the cookie store in a header, as browser clients do.
+    fn csrf(&self, url: &str, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.cookie(url, CSRF_COOKIE_NAME) {
+            Some(token) => request.header(CSRF_HEADER_NAME, token),
+            None => request,
         }
     }
 
@@ -67,7 +96,7 @@ impl Client {
     pub async fn get<D: DeserializeOwned>(&self, url: &str, endpoint: &str) -> D {
         let res = self
             .inner
-            .get(&format!("{url}{endpoint}"))
+            .get(format!("{url}{endpoint}"))
             .send()
             .await
             .unwrap();
@@ -75,6 +104,33 @@ impl Client {
         serde_json::from_str(&text).unwrap()
     }
 
+    /// Send a GET request with an extra header (e.g. a bot's `Authorization`).
+    pub async fn get_with_header<D: DeserializeOwned>(
+        &self,
+        url: &str,
+        endpoint: &str,
+        header: (&str, &str),
+    ) -> D {
+        let res = self
+            .inner
+            .get(format!("{url}{endpoint}"))
+            .header(header.0, header.1)
+            .send()
+            .await
+            .unwrap();
+        let text = res.text().await.unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    /// Send a GET request without decoding the response, for endpoints that don't serve JSON.
+    pub async fn get_raw(&self, url: &str, endpoint: &str) -> reqwest::Response {
+        self.inner
+            .get(format!("{url}{endpoint}"))
+            .send()
+            .await
+            .unwrap()
+    }
+
     pub async fn post<S: Serialize, D: DeserializeOwned>(
         &self,
         url: &str,
@@ -82,8 +138,44 @@ impl Client {
         body: S,
     ) -> D {
         let res = self
-            .inner
-            .post(&format!("{url}{endpoint}"))
+            .csrf(url, self.inner.post(format!("{url}{endpoint}")))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&body).unwrap())
+            .send()
+            .await
+            .unwrap();
+        let text = res.text().await.unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    /// Send a POST request with an extra header (e.g. an idempotency key).
+    pub async fn post_with_header<S: Serialize, D: DeserializeOwned>(
+        &self,
+        url: &str,
+        endpoint: &str,
+        body: S,
+        header: (&str, &str),
+    ) -> D {
+        let res = self
+            .csrf(url, self.inner.post(format!("{url}{endpoint}")))
+            .header("Content-Type", "application/json")
+            .header(header.0, header.1)
+            .body(serde_json::to_string(&body).unwrap())
+            .send()
+            .await
+            .unwrap();
+        let text = res.text().await.unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    pub async fn patch<S: Serialize, D: DeserializeOwned>(
+        &self,
+        url: &str,
+        endpoint: &str,
+        body: S,
+    ) -> D {
+        let res = self
+            .csrf(url, self.inner.patch(format!("{url}{endpoint}")))
             .header("Content-Type", "application/json")
             .body(serde_json::to_string(&body).unwrap())
             .send()
@@ -92,6 +184,36 @@ impl Client {
         let text = res.text().await.unwrap();
         serde_json::from_str(&text).unwrap()
     }
+
+    /// Upload a file as the named field of a multipart form.
+    pub async fn put_file<D: DeserializeOwned>(
+        &self,
+        url: &str,
+        endpoint: &str,
+        field: &str,
+        data: Vec<u8>,
+    ) -> D {
+        let part = reqwest::multipart::Part::bytes(data).file_name(field.to_string());
+        let form = reqwest::multipart::Form::new().part(field.to_string(), part);
+        let res = self
+            .csrf(url, self.inner.put(format!("{url}{endpoint}")))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        let text = res.text().await.unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    pub async fn delete<D: DeserializeOwned>(&self, url: &str, endpoint: &str) -> D {
+        let res = self
+            .csrf(url, self.inner.delete(format!("{url}{endpoint}")))
+            .send()
+            .await
+            .unwrap();
+        let text = res.text().await.unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
 }
 
 impl Default for Client {
@@ -105,6 +227,78 @@ pub async fn init(app: Router) -> String {
         .await
         .unwrap();
     let addr = listener.local_addr().unwrap();
-    tokio::spawn(axum::serve(listener, app).into_future());
+    tokio::spawn(
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .into_future(),
+    );
     format!("http://{addr}")
 }
+
+/// The cookie store percent-encodes cookie values, so undo that to recover the raw value.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(byte) = u8::from_str_radix(&s[i + 1..i + 3], 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(decoded).unwrap()
+}
+
+/// A websocket connection to the server's live endpoint.
+pub struct Socket {
+    inner: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Socket {
+    pub async fn connect(url: &str) -> Self {
+        let url = format!("{}/live", url.replacen("http", "ws", 1));
+        let (inner, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        Self { inner }
+    }
+
+    pub async fn send<S: Serialize>(&mut self, packet: S) {
+        let text = serde_json::to_string(&packet).unwrap();
+        self.inner.send(Message::Text(text)).await.unwrap();
+    }
+
+    pub async fn recv(&mut self) -> serde_json::Value {
+        loop {
+            match self.inner.next().await.unwrap().unwrap() {
+                Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+                Message::Close(frame) => panic!("websocket closed: {frame:?}"),
+                _ => {}
+            }
+        }
+    }
+
+    /// Receive events until one with the specified opcode arrives.
+    pub async fn recv_op(&mut self, op: u64) -> serde_json::Value {
+        loop {
+            let event = self.recv().await;
+            if event["op"] == op {
+                return event;
+            }
+        }
+    }
+
+    /// Discard everything until the server closes the connection.
+    pub async fn closed(&mut self) {
+        while let Some(Ok(msg)) = self.inner.next().await {
+            if let Message::Close(_) = msg {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! The core's rules for the browser, so web clients can check moves and suggest them without
+//! asking the server. Build it with `wasm-pack build wasm --target web`.
+//!
+//! Squares are counted from the top left, from 0, as everywhere else. Lists of squares come
+//! flattened into `[x0, y0, x1, y1, ...]`, which crosses into JavaScript as one typed array.
+
+use olly::{analysis, companion::Companion};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Piece {
+    Black,
+    White,
+}
+
+impl From<olly::Piece> for Piece {
+    fn from(piece: olly::Piece) -> Self {
+        match piece {
+            olly::Piece::Black => Self::Black,
+            olly::Piece::White => Self::White,
+        }
+    }
+}
+
+/// A game played out in the browser, usually following along with one the gateway is sending.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Game(olly::Game);
+
+#[wasm_bindgen]
+impl Game {
+    /// A game from the standard starting position.
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a game from the JSON the gateway sends it as (e.g. the `game` of a `GameUpdate`).
+    /// # Errors
+    /// Returns an error if the JSON isn't a game.
+    #[wasm_bindgen(js_name = fromJson)]
+    pub fn from_json(json: &str) -> Result<Game, JsError> {
+        Ok(Self(serde_json::from_str(json)?))
+    }
+
+    /// The piece on the specified square, if there is one.
+    #[must_use]
+    pub fn piece(&self, x: usize, y: usize) -> Option<Piece> {
+        self.0.piece(x, y).map(Piece::from)
+    }
+
+    #[must_use]
+    pub fn turn(&self) -> Piece {
+        self.0.turn().into()
+    }
+
+    /// The number of black and white pieces on the board, in that order.
+    #[must_use]
+    pub fn score(&self) -> Vec<usize> {
+        let (black, white) = self.0.score();
+        vec![black, white]
+    }
+
+    /// The squares the side to move can play, flattened.
+    pub fn moves(&mut self) -> Vec<usize> {
+        let turn = self.0.turn();
+        flatten(self.0.moves(turn))
+    }
+
+    /// The pieces playing the specified square would flip, flattened, to preview a move
+    /// before it's sent.
+    /// # Errors
+    /// Returns an error if the side to move can't play the square.
+    pub fn flips(&mut self, x: usize, y: usize) -> Result<Vec<usize>, JsError> {
+        let turn = self.0.turn();
+        Ok(flatten(self.0.preview(x, y, turn)?))
+    }
+
+    /// Play the specified square for the side to move.
+    /// # Errors
+    /// Returns an error if the side to move can't play the square, saying why.
+    pub fn place(&mut self, x: usize, y: usize) -> Result<(), JsError> {
+        let turn = self.0.turn();
+        Ok(self.0.place(x, y, turn)?)
+    }
+
+    /// Whether the side to move has run out of moves.
+    pub fn over(&mut self) -> bool {
+        self.0.over()
+    }
+
+    /// The move the companion would play, searching the specified number of moves ahead, or
+    /// `undefined` if there are no legal moves. Searches block the page while they run, so
+    /// hints shouldn't look more than a few moves ahead.
+    #[must_use]
+    pub fn hint(&self, depth: usize) -> Option<Vec<usize>> {
+        if self.0.clone().over() {
+            return None;
+        }
+        let (x, y) = Companion::from(&self.0).choice(depth);
+        Some(vec![x, y])
+    }
+
+    /// The evaluation of the position, without searching ahead. Positive scores favour black
+    /// and negative scores favour white.
+    #[must_use]
+    pub fn evaluate(&self) -> i32 {
+        analysis::evaluate(&self.0)
+    }
+}
+
+fn flatten(squares: Vec<(usize, usize)>) -> Vec<usize> {
+    squares.into_iter().flat_map(|(x, y)| [x, y]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Game, Piece};
+
+    #[test]
+    fn play() {
+        let mut game = Game::new();
+        assert_eq!(game.moves(), vec![2, 3, 3, 2, 4, 5, 5, 4]);
+        assert_eq!(game.flips(2, 3).ok(), Some(vec![3, 3]));
+        assert!(game.place(2, 3).is_ok());
+        assert_eq!(game.piece(3, 3), Some(Piece::Black));
+        assert_eq!(game.turn(), Piece::White);
+        assert_eq!(game.score(), vec![4, 1]);
+        let hint = game.hint(2).unwrap();
+        assert!(game
+            .moves()
+            .chunks(2)
+            .any(|square| square == hint.as_slice()));
+    }
+
+    #[test]
+    fn json() {
+        let mut game = olly::Game::new();
+        game.place(5, 4, olly::Piece::Black).unwrap();
+        let json = serde_json::to_string(&game).unwrap();
+        let Ok(mut read) = Game::from_json(&json) else {
+            panic!("expected the game to be read back");
+        };
+        assert_eq!(read.0, game);
+        assert!(!read.over());
+    }
+}